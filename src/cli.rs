@@ -0,0 +1,320 @@
+use crate::config::app::{AppConfig, DatabaseSettings};
+use crate::config::database::get_schema_database;
+use crate::migration::Migrator;
+use crate::services::bootstrap_admin::BootstrapAdminConfig;
+use crate::services::seed::SeedService;
+use crate::services::tenant::TenantService;
+use crate::utils::tenant::schema_name;
+use clap::{Args, Parser, Subcommand};
+use sea_orm::DatabaseConnection;
+use sea_orm_migration::{MigrationStatus, MigratorTrait};
+
+#[derive(Debug, Parser)]
+#[command(name = "xjy", version, about = "Forum API server")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (the default without a subcommand)
+    Serve {
+        /// Start without applying pending migrations; `/readyz` reports
+        /// unready until they are applied with `migrate up`
+        #[arg(long)]
+        no_migrate: bool,
+    },
+    /// Apply, roll back or inspect database migrations
+    Migrate {
+        /// Run against a tenant's schema instead of the default community
+        #[arg(long, global = true)]
+        tenant: Option<String>,
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Apply pending migrations, then create the admin account and the
+    /// default forums and tags. Safe to run repeatedly.
+    Seed {
+        /// Also create sample posts
+        #[arg(long)]
+        demo: bool,
+        /// Seed a tenant instead of the default community
+        #[arg(long)]
+        tenant: Option<String>,
+        #[command(flatten)]
+        admin: SeedAdmin,
+    },
+    /// Manage the communities served alongside the default one
+    Tenant {
+        #[command(subcommand)]
+        action: TenantAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TenantAction {
+    /// Register a tenant, create its schema and apply migrations to it
+    Add {
+        /// Lowercase letters, digits and `_`; the schema is `tenant_<slug>`
+        slug: String,
+        /// Host the tenant is served on, e.g. `rust.example.com`
+        #[arg(long)]
+        host: String,
+        /// Display name (default: the slug)
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// List tenants
+    List,
+}
+
+/// The admin to create when there is none yet; each falls back to its
+/// `ADMIN_*`, then `BOOTSTRAP_ADMIN_*` setting.
+#[derive(Debug, Args)]
+pub struct SeedAdmin {
+    /// Default: `admin`
+    #[arg(long)]
+    admin_username: Option<String>,
+    /// Default: `admin@example.com`
+    #[arg(long)]
+    admin_email: Option<String>,
+    /// Default: generated and printed once
+    #[arg(long)]
+    admin_password: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateAction {
+    /// Apply pending migrations
+    Up {
+        /// Apply at most this many (default: all)
+        #[arg(short = 'n', long)]
+        steps: Option<u32>,
+    },
+    /// Roll back the most recently applied migrations
+    Down {
+        #[arg(short = 'n', long, default_value_t = 1)]
+        steps: u32,
+    },
+    /// List migrations and whether each is applied
+    Status,
+    /// Drop every table and re-apply all migrations
+    Fresh {
+        /// Required, since this deletes all data
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+impl Cli {
+    pub fn into_command(self) -> Command {
+        self.command.unwrap_or(Command::Serve { no_migrate: false })
+    }
+}
+
+pub async fn migrate(
+    app_config: &AppConfig,
+    tenant: Option<String>,
+    action: MigrateAction,
+) -> anyhow::Result<()> {
+    if let MigrateAction::Fresh { yes: false } = action {
+        anyhow::bail!("`migrate fresh` drops every table; pass --yes to confirm");
+    }
+
+    // Migrations run one statement at a time, and SQLite's
+    // `PRAGMA foreign_keys = OFF` during `fresh` only holds for the
+    // connection it was issued on
+    let settings = DatabaseSettings {
+        max_connections: 1,
+        min_connections: 1,
+        ..app_config.database.clone()
+    };
+    let db = match tenant {
+        Some(slug) => tenant_database(&settings, &slug, 1).await?,
+        None => crate::config::database::get_database(&settings).await?,
+    };
+    match action {
+        MigrateAction::Up { steps } => {
+            let pending = Migrator::get_pending_migrations(&db).await?.len();
+            Migrator::up(&db, steps).await?;
+            let applied = steps.map_or(pending, |n| pending.min(n as usize));
+            println!("Applied {applied} migration(s)");
+        }
+        MigrateAction::Down { steps } => {
+            let applied = Migrator::get_applied_migrations(&db).await?.len();
+            Migrator::down(&db, Some(steps)).await?;
+            println!("Rolled back {} migration(s)", applied.min(steps as usize));
+        }
+        MigrateAction::Status => {
+            let migrations = Migrator::get_migration_with_status(&db).await?;
+            let mut pending = 0;
+            for migration in &migrations {
+                let status = match migration.status() {
+                    MigrationStatus::Applied => "applied",
+                    MigrationStatus::Pending => {
+                        pending += 1;
+                        "pending"
+                    }
+                };
+                println!("{status:<8} {}", migration.name());
+            }
+            println!("{} migration(s), {pending} pending", migrations.len());
+        }
+        MigrateAction::Fresh { .. } => {
+            Migrator::fresh(&db).await?;
+            println!("Dropped all tables and applied every migration");
+        }
+    }
+    Ok(())
+}
+
+pub async fn seed(
+    app_config: &AppConfig,
+    demo: bool,
+    tenant: Option<String>,
+    admin: SeedAdmin,
+) -> anyhow::Result<()> {
+    let password = admin
+        .admin_password
+        .or_else(|| setting(&["ADMIN_PASSWORD", "BOOTSTRAP_ADMIN_PASSWORD"]));
+    let generated_password = password.is_none();
+    let admin = BootstrapAdminConfig {
+        username: admin
+            .admin_username
+            .or_else(|| setting(&["ADMIN_USERNAME", "BOOTSTRAP_ADMIN_USERNAME"]))
+            .unwrap_or_else(|| "admin".to_string()),
+        email: admin
+            .admin_email
+            .or_else(|| setting(&["ADMIN_EMAIL", "BOOTSTRAP_ADMIN_EMAIL"]))
+            .unwrap_or_else(|| "admin@example.com".to_string()),
+        password: password.unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string()),
+    };
+
+    let settings = &app_config.database;
+    let db = match tenant {
+        Some(slug) => tenant_database(settings, &slug, settings.max_connections).await?,
+        None => crate::config::database::get_database(settings).await?,
+    };
+    Migrator::up(&db, None).await?;
+
+    let report = SeedService::new(db).seed(&admin, demo).await?;
+    if report.admin_created {
+        println!(
+            "Created admin '{}' <{}>",
+            report.admin.username, report.admin.email
+        );
+        if generated_password {
+            println!("Generated admin password: {}", admin.password);
+        }
+    } else {
+        println!("Admin '{}' already exists", report.admin.username);
+    }
+    println!("Created {} forum(s)", report.forums_created);
+    if demo {
+        println!("Created {} demo post(s)", report.posts_created);
+    }
+    Ok(())
+}
+
+pub async fn tenant(app_config: &AppConfig, action: TenantAction) -> anyhow::Result<()> {
+    let db = crate::config::database::get_database(&app_config.database).await?;
+    Migrator::up(&db, None).await?;
+    let service = TenantService::new(db);
+    match action {
+        TenantAction::Add { slug, host, name } => {
+            let name = name.unwrap_or_else(|| slug.clone());
+            let tenant = service.create(&slug, &name, &host).await?;
+            service
+                .connect(&tenant, &app_config.database, 1, true)
+                .await?;
+            println!(
+                "Created tenant '{}' on {} (schema {})",
+                tenant.slug,
+                tenant.host,
+                schema_name(&tenant.slug)
+            );
+        }
+        TenantAction::List => {
+            let tenants = service.list().await?;
+            for tenant in &tenants {
+                println!("{:<20} {:<30} {}", tenant.slug, tenant.host, tenant.name);
+            }
+            println!("{} tenant(s)", tenants.len());
+        }
+    }
+    Ok(())
+}
+
+/// A pool on the schema of tenant `slug`; migrations are left to the caller.
+async fn tenant_database(
+    settings: &DatabaseSettings,
+    slug: &str,
+    max_connections: u32,
+) -> anyhow::Result<DatabaseConnection> {
+    let db = crate::config::database::get_database(settings).await?;
+    let service = TenantService::new(db);
+    let tenant = service
+        .find_by_slug(slug)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No tenant '{slug}'; see `xjy tenant list`"))?;
+    service.create_schema(&tenant).await?;
+    Ok(get_schema_database(settings, &schema_name(slug), max_connections).await?)
+}
+
+/// The first of `names` that is set and not blank.
+fn setting(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|v| !v.trim().is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn parses_subcommands() {
+        Cli::command().debug_assert();
+
+        let serve = Cli::parse_from(["xjy"]).into_command();
+        assert!(matches!(serve, Command::Serve { no_migrate: false }));
+        let serve = Cli::parse_from(["xjy", "serve", "--no-migrate"]).into_command();
+        assert!(matches!(serve, Command::Serve { no_migrate: true }));
+
+        let down = Cli::parse_from(["xjy", "migrate", "down"]).into_command();
+        assert!(matches!(
+            down,
+            Command::Migrate {
+                tenant: None,
+                action: MigrateAction::Down { steps: 1 }
+            }
+        ));
+        let up = Cli::parse_from(["xjy", "migrate", "up", "-n", "2"]).into_command();
+        assert!(matches!(
+            up,
+            Command::Migrate {
+                tenant: None,
+                action: MigrateAction::Up { steps: Some(2) }
+            }
+        ));
+
+        let up = Cli::parse_from(["xjy", "migrate", "up", "--tenant", "acme"]).into_command();
+        assert!(matches!(
+            up,
+            Command::Migrate {
+                tenant: Some(ref t),
+                action: MigrateAction::Up { steps: None }
+            } if t == "acme"
+        ));
+        let add =
+            Cli::parse_from(["xjy", "tenant", "add", "acme", "--host", "acme.test"]).into_command();
+        assert!(matches!(
+            add,
+            Command::Tenant {
+                action: TenantAction::Add { ref slug, ref host, name: None }
+            } if slug == "acme" && host == "acme.test"
+        ));
+    }
+}
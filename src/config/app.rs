@@ -0,0 +1,504 @@
+//! Application configuration file
+//!
+//! Settings come from `config.toml` (or the file named by `CONFIG_FILE`),
+//! with environment variables taking precedence. The structural settings
+//! the server needs to boot are typed sections of `AppConfig`; any other
+//! `[table] key = value` is the environment variable `TABLE_KEY` (a
+//! top-level `key` is `KEY`), so `[pow] difficulty = 22` is the same as
+//! `POW_DIFFICULTY=22`. After loading, the effective values are exported to
+//! the process environment, which is where the rest of the app reads them.
+//!
+//! `SharedConfig::reload` re-reads the file at runtime (on SIGHUP or
+//! `POST /admin/config/reload`). Only settings read per request are applied
+//! then; the rest are reported as needing a restart.
+
+use arc_swap::ArcSwap;
+use figment::{
+    providers::{Format, Serialized, Toml},
+    value::{Dict, Value},
+    Figment,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Environment variables behind the typed sections, with their keys.
+const TYPED_KEYS: &[(&str, &str)] = &[
+    ("HOST", "server.host"),
+    ("PORT", "server.port"),
+    ("CORS_ORIGINS", "server.cors_origins"),
+    ("DATABASE_URL", "database.url"),
+    ("DB_MAX_CONNECTIONS", "database.max_connections"),
+    ("DB_MIN_CONNECTIONS", "database.min_connections"),
+    (
+        "DB_ACQUIRE_TIMEOUT_SECONDS",
+        "database.acquire_timeout_seconds",
+    ),
+    ("DB_IDLE_TIMEOUT_SECONDS", "database.idle_timeout_seconds"),
+    ("REDIS_URL", "redis.url"),
+    ("UPLOAD_DIR", "uploads.dir"),
+    ("UPLOAD_MAX_FILE_SIZE", "uploads.max_file_size"),
+];
+
+/// Settings a reload applies; everything else needs a restart.
+const RELOADABLE: &[&str] = &[
+    "CORS_ORIGINS",
+    "UPLOAD_MAX_FILE_SIZE",
+    "RATE_LIMIT_CONFIG",
+    "RATE_LIMIT_NEW_ACCOUNT_CONFIG",
+    "RATE_LIMIT_NEW_ACCOUNT_HOURS",
+    "POW_*",
+    "CAPTCHA_*",
+];
+
+fn is_reloadable(name: &str) -> bool {
+    RELOADABLE
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == *pattern,
+        })
+}
+
+/// Tables deserialized into `AppConfig` itself rather than passed through.
+const TYPED_SECTIONS: &[&str] = &["server", "database", "redis", "uploads"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    pub host: String,
+    pub port: u16,
+    /// Comma-separated origins, or `*`
+    pub cors_origins: String,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            cors_origins: "*".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatabaseSettings {
+    pub url: Option<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long a request waits for a free connection before failing
+    pub acquire_timeout_seconds: u64,
+    /// Idle connections above `min_connections` are closed after this long
+    pub idle_timeout_seconds: u64,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        Self {
+            url: None,
+            max_connections: 10,
+            min_connections: 2,
+            acquire_timeout_seconds: 5,
+            idle_timeout_seconds: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedisSettings {
+    pub url: String,
+}
+
+impl Default for RedisSettings {
+    fn default() -> Self {
+        Self {
+            url: "redis://localhost:6379".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadSettings {
+    pub dir: String,
+    /// Largest accepted upload, in bytes
+    pub max_file_size: usize,
+}
+
+impl Default for UploadSettings {
+    fn default() -> Self {
+        Self {
+            dir: "./uploads".to_string(),
+            max_file_size: 5 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub redis: RedisSettings,
+    pub uploads: UploadSettings,
+    /// Every other setting from the file, by environment variable name,
+    /// with environment overrides applied
+    #[serde(skip)]
+    pub settings: BTreeMap<String, String>,
+    /// The file the settings were read from, if any
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl AppConfig {
+    /// Load `CONFIG_FILE`, or `config.toml` when present, and apply
+    /// environment overrides. A `CONFIG_FILE` that does not exist is an
+    /// error; a missing `config.toml` just means env-only configuration.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = match std::env::var("CONFIG_FILE") {
+            Ok(path) if !path.trim().is_empty() => {
+                let path = PathBuf::from(path.trim());
+                if !path.is_file() {
+                    anyhow::bail!("CONFIG_FILE '{}' does not exist", path.display());
+                }
+                Some(path)
+            }
+            _ => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|p| p.is_file()),
+        };
+        let file = match &path {
+            Some(path) => Figment::from(Toml::file_exact(path)),
+            None => Figment::new(),
+        };
+
+        let env = original_env();
+        let mut config = Self::from_sources(file, |name| env.get(name).cloned())?;
+        config.source = path;
+        Ok(config)
+    }
+
+    /// Settings from `file`, overridden by what `env` returns for each
+    /// variable name.
+    fn from_sources(file: Figment, env: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let mut typed = file.clone();
+        for (name, key) in TYPED_KEYS {
+            if let Some(value) = env(name) {
+                // Parsed the way figment parses environment values
+                let value: Value = value.parse().expect("infallible");
+                typed = typed.merge(Serialized::default(key, value));
+            }
+        }
+        let mut config: AppConfig = typed
+            .extract()
+            .map_err(|e| anyhow::anyhow!("Invalid configuration: {}", e))?;
+
+        let dict: Dict = file
+            .extract()
+            .map_err(|e| anyhow::anyhow!("Invalid configuration: {}", e))?;
+        collect_settings(&dict, &mut config.settings)?;
+        for (name, value) in config.settings.iter_mut() {
+            if let Some(env_value) = env(name) {
+                *value = env_value;
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Every effective setting as `(environment variable, value)`.
+    pub fn env_pairs(&self) -> Vec<(String, String)> {
+        let typed = serde_json::to_value(self).unwrap_or_default();
+        let mut pairs: Vec<(String, String)> = TYPED_KEYS
+            .iter()
+            .filter_map(|(name, key)| {
+                let pointer = format!("/{}", key.replace('.', "/"));
+                scalar_to_string(typed.pointer(&pointer)?).map(|v| (name.to_string(), v))
+            })
+            .collect();
+        pairs.extend(self.settings.clone());
+        pairs
+    }
+
+    /// Export the effective settings to the process environment, for the
+    /// modules that read their configuration from there. Call this before
+    /// spawning anything that reads the environment.
+    pub fn apply_to_env(&self) {
+        for (name, value) in self.env_pairs() {
+            std::env::set_var(name, value);
+        }
+    }
+
+    /// Log every effective setting, with secrets masked.
+    pub fn log_effective(&self) {
+        match &self.source {
+            Some(path) => tracing::info!("Configuration loaded from {}", path.display()),
+            None => tracing::info!("No config file, using environment only"),
+        }
+        for (name, value) in self.env_pairs() {
+            tracing::info!("  {} = {}", name, redact(&name, &value));
+        }
+    }
+}
+
+/// The environment as the process started, before any settings were
+/// exported to it, so a reload can tell overrides from file values.
+fn original_env() -> &'static HashMap<String, String> {
+    static ORIGINAL: OnceLock<HashMap<String, String>> = OnceLock::new();
+    ORIGINAL.get_or_init(|| std::env::vars().collect())
+}
+
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Bumped on every reload, for state derived from the configuration.
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+/// What a reload changed, by environment variable name.
+#[derive(Debug, Default)]
+pub struct ConfigReload {
+    pub applied: Vec<String>,
+    /// Changed in the file but only read at startup
+    pub restart_required: Vec<String>,
+}
+
+/// The live configuration, shared via `Extension`.
+#[derive(Clone)]
+pub struct SharedConfig(Arc<ArcSwap<AppConfig>>);
+
+impl SharedConfig {
+    pub fn new(config: AppConfig) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(config)))
+    }
+
+    pub fn current(&self) -> Arc<AppConfig> {
+        self.0.load_full()
+    }
+
+    /// Re-read the config file and apply the settings that can change at
+    /// runtime. An invalid file leaves the running configuration alone.
+    pub fn reload(&self) -> anyhow::Result<ConfigReload> {
+        static RELOADING: Mutex<()> = Mutex::new(());
+        let _guard = RELOADING.lock().unwrap_or_else(|e| e.into_inner());
+
+        let old = self.current();
+        let fresh = AppConfig::load()?;
+        let old_pairs: BTreeMap<String, String> = old.env_pairs().into_iter().collect();
+        let new_pairs: BTreeMap<String, String> = fresh.env_pairs().into_iter().collect();
+
+        let mut next = (*old).clone();
+        next.source = fresh.source.clone();
+        let mut outcome = ConfigReload::default();
+        let mut names: Vec<&String> = old_pairs.keys().chain(new_pairs.keys()).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let value = new_pairs.get(name);
+            if old_pairs.get(name) == value {
+                continue;
+            }
+            if !is_reloadable(name) {
+                outcome.restart_required.push(name.clone());
+                continue;
+            }
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+            outcome.applied.push(name.clone());
+        }
+
+        next.server.cors_origins = fresh.server.cors_origins.clone();
+        next.uploads.max_file_size = fresh.uploads.max_file_size;
+        next.settings.retain(|name, _| !is_reloadable(name));
+        next.settings.extend(
+            fresh
+                .settings
+                .iter()
+                .filter(|(name, _)| is_reloadable(name))
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+
+        self.0.store(Arc::new(next));
+        GENERATION.fetch_add(1, Ordering::AcqRel);
+
+        tracing::info!(
+            applied = ?outcome.applied,
+            restart_required = ?outcome.restart_required,
+            "Configuration reloaded"
+        );
+        Ok(outcome)
+    }
+}
+
+/// Flatten the pass-through tables of a parsed file into `TABLE_KEY` names.
+fn collect_settings(dict: &Dict, out: &mut BTreeMap<String, String>) -> anyhow::Result<()> {
+    for (key, value) in dict {
+        match value {
+            Value::Dict(_, table) => {
+                if TYPED_SECTIONS.contains(&key.as_str()) {
+                    continue;
+                }
+                for (inner, value) in table {
+                    let name = format!("{}_{}", key, inner).to_uppercase();
+                    out.insert(name.clone(), setting_value(&name, value)?);
+                }
+            }
+            _ => {
+                let name = key.to_uppercase();
+                out.insert(name.clone(), setting_value(&name, value)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn setting_value(name: &str, value: &Value) -> anyhow::Result<String> {
+    serde_json::to_value(value)
+        .ok()
+        .as_ref()
+        .and_then(scalar_to_string)
+        .ok_or_else(|| anyhow::anyhow!("Invalid configuration: {} must be a plain value", name))
+}
+
+fn scalar_to_string(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Mask secrets and the credentials in connection URLs.
+fn redact(name: &str, value: &str) -> String {
+    const SENSITIVE: [&str; 5] = ["SECRET", "PASSWORD", "TOKEN", "KEY", "DSN"];
+    if SENSITIVE.iter().any(|s| name.contains(s)) {
+        return if value.is_empty() {
+            String::new()
+        } else {
+            "********".to_string()
+        };
+    }
+    match url::Url::parse(value) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some("********"));
+            url.to_string()
+        }
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(toml: &str, env: &[(&str, &str)]) -> anyhow::Result<AppConfig> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        AppConfig::from_sources(Figment::from(Toml::string(toml)), |name| {
+            env.get(name).cloned()
+        })
+    }
+
+    #[test]
+    fn test_file_settings_with_env_overrides() {
+        let config = load(
+            r#"
+            log_format = "json"
+
+            [server]
+            host = "0.0.0.0"
+            port = 8080
+
+            [database]
+            url = "postgres://xjy:hunter2@db/xjy"
+
+            [pow]
+            difficulty = 22
+            required_actions = "vote,register"
+
+            [rate_limit]
+            enabled = false
+            "#,
+            &[("PORT", "9000"), ("POW_DIFFICULTY", "18")],
+        )
+        .unwrap();
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.database.max_connections, 10);
+        assert_eq!(config.settings["POW_DIFFICULTY"], "18");
+        assert_eq!(config.settings["POW_REQUIRED_ACTIONS"], "vote,register");
+        assert_eq!(config.settings["RATE_LIMIT_ENABLED"], "false");
+        assert_eq!(config.settings["LOG_FORMAT"], "json");
+        assert!(!config.settings.contains_key("SERVER_HOST"));
+
+        let pairs = config.env_pairs();
+        assert!(pairs.contains(&("PORT".to_string(), "9000".to_string())));
+        assert!(pairs.contains(&(
+            "DATABASE_URL".to_string(),
+            "postgres://xjy:hunter2@db/xjy".to_string()
+        )));
+        assert!(!pairs.iter().any(|(name, _)| name == "DB_URL"));
+    }
+
+    #[test]
+    fn test_env_only() {
+        let config = load(
+            "",
+            &[
+                ("DATABASE_URL", "postgres://localhost/xjy"),
+                ("CORS_ORIGINS", "https://a.example,https://b.example"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            config.database.url.as_deref(),
+            Some("postgres://localhost/xjy")
+        );
+        assert_eq!(
+            config.server.cors_origins,
+            "https://a.example,https://b.example"
+        );
+        assert_eq!(config.uploads.dir, "./uploads");
+        assert!(config.settings.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        assert!(load("[server]\nport = \"eighty\"\n", &[]).is_err());
+        assert!(load("", &[("PORT", "eighty")]).is_err());
+        assert!(load("[pow]\nlimits = { vote = 1 }\n", &[]).is_err());
+    }
+
+    #[test]
+    fn test_reloadable_settings() {
+        assert!(is_reloadable("CORS_ORIGINS"));
+        assert!(is_reloadable("POW_DIFFICULTY"));
+        assert!(is_reloadable("CAPTCHA_REQUIRED_ACTIONS"));
+        assert!(is_reloadable("RATE_LIMIT_CONFIG"));
+        assert!(!is_reloadable("RATE_LIMIT_ENABLED"));
+        assert!(!is_reloadable("PORT"));
+        assert!(!is_reloadable("JWT_SECRET"));
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(redact("JWT_SECRET", "abc"), "********");
+        assert_eq!(redact("SMTP_PASSWORD", ""), "");
+        assert_eq!(
+            redact("DATABASE_URL", "postgres://xjy:hunter2@db/xjy"),
+            "postgres://xjy:********@db/xjy"
+        );
+        assert_eq!(redact("HOST", "0.0.0.0"), "0.0.0.0");
+    }
+}
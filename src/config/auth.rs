@@ -1,8 +1,20 @@
 use std::env;
 
+/// What happens to a deleted user's posts and comments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeletedUserContent {
+    /// Hand them to the shared `[deleted]` account so threads stay intact
+    Anonymize,
+    /// Delete them with the user
+    Delete,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct AuthConfig {
     pub require_email_verification: bool,
+    /// Days a user must wait between username changes; 0 for no limit
+    pub username_change_interval_days: i64,
+    pub deleted_user_content: DeletedUserContent,
 }
 
 impl AuthConfig {
@@ -19,8 +31,26 @@ impl AuthConfig {
             })
             .unwrap_or(false);
 
+        let username_change_interval_days = env::var("USERNAME_CHANGE_INTERVAL_DAYS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &i64| *v >= 0)
+            .unwrap_or(30);
+
+        let deleted_user_content = match env::var("DELETED_USER_CONTENT")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "delete" => DeletedUserContent::Delete,
+            _ => DeletedUserContent::Anonymize,
+        };
+
         Self {
             require_email_verification,
+            username_change_interval_days,
+            deleted_user_content,
         }
     }
 }
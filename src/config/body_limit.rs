@@ -0,0 +1,34 @@
+use std::env;
+
+/// Largest request body each group of routes accepts, in bytes. Requests
+/// over the limit get 413.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimitConfig {
+    /// Registration, login and the other `/auth` routes
+    pub auth: usize,
+    /// Creating and editing posts and comments, and reports
+    pub content: usize,
+    /// Image and avatar uploads
+    pub uploads: usize,
+    /// Everything else
+    pub default: usize,
+}
+
+impl BodyLimitConfig {
+    pub fn from_env() -> Self {
+        Self {
+            auth: bytes("BODY_LIMIT_AUTH", 64 * 1024),
+            content: bytes("BODY_LIMIT_CONTENT", 2 * 1024 * 1024),
+            uploads: bytes("BODY_LIMIT_UPLOADS", 6 * 1024 * 1024),
+            default: bytes("BODY_LIMIT_DEFAULT", 1024 * 1024),
+        }
+    }
+}
+
+fn bytes(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|v: &usize| *v >= 1)
+        .unwrap_or(default)
+}
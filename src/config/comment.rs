@@ -0,0 +1,42 @@
+use std::env;
+
+/// What to do with a reply that would nest deeper than `max_depth`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthOverflow {
+    /// Refuse the reply with a validation error
+    Reject,
+    /// Attach the reply to the deepest allowed ancestor instead
+    Reparent,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CommentConfig {
+    /// Maximum number of nesting levels, counting the top-level comment as one
+    pub max_depth: u32,
+    pub overflow: DepthOverflow,
+}
+
+impl CommentConfig {
+    pub fn from_env() -> Self {
+        let max_depth = env::var("MAX_COMMENT_DEPTH")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u32| *v >= 1)
+            .unwrap_or(10);
+
+        let overflow = match env::var("COMMENT_DEPTH_OVERFLOW")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "reparent" => DepthOverflow::Reparent,
+            _ => DepthOverflow::Reject,
+        };
+
+        Self {
+            max_depth,
+            overflow,
+        }
+    }
+}
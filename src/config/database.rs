@@ -1,26 +1,165 @@
+use crate::config::app::DatabaseSettings;
 use sea_orm::{ConnectOptions, Database, DatabaseConnection, DbErr};
-use std::env;
+use serde::Serialize;
 use std::time::Duration;
+use utoipa::ToSchema;
 
-pub async fn get_database() -> Result<DatabaseConnection, DbErr> {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+pub async fn get_database(settings: &DatabaseSettings) -> Result<DatabaseConnection, DbErr> {
+    let opt = connect_options(settings)?;
 
-    let max_connections: u32 = env::var("DB_MAX_CONNECTIONS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(10);
+    tracing::info!(
+        "Database pool: max_connections={} min_connections={} acquire_timeout={:?} idle_timeout={:?}",
+        opt.get_max_connections().unwrap_or_default(),
+        opt.get_min_connections().unwrap_or_default(),
+        opt.get_acquire_timeout().unwrap_or_default(),
+        opt.get_idle_timeout().unwrap_or_default(),
+    );
 
-    let min_connections: u32 = env::var("DB_MIN_CONNECTIONS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(2);
+    Database::connect(opt).await
+}
 
-    let mut opt = ConnectOptions::new(database_url);
+/// Connect to the same Postgres database with `schema` first on the search
+/// path, so unqualified table names resolve there. `public` stays on the
+/// path for extensions such as `pg_trgm`.
+pub async fn get_schema_database(
+    settings: &DatabaseSettings,
+    schema: &str,
+    max_connections: u32,
+) -> Result<DatabaseConnection, DbErr> {
+    let mut opt = connect_options(settings)?;
     opt.max_connections(max_connections)
-        .min_connections(min_connections)
+        .min_connections(settings.min_connections.min(max_connections))
+        .set_schema_search_path(format!("{schema},public"));
+    Database::connect(opt).await
+}
+
+fn connect_options(settings: &DatabaseSettings) -> Result<ConnectOptions, DbErr> {
+    let database_url = settings
+        .url
+        .clone()
+        .ok_or_else(|| DbErr::Custom("DATABASE_URL must be set".to_string()))?;
+    validate_pool(settings)?;
+    let in_memory = is_sqlite_memory(&database_url);
+
+    let mut opt = ConnectOptions::new(database_url);
+    opt.max_connections(settings.max_connections)
+        .min_connections(settings.min_connections)
         .connect_timeout(Duration::from_secs(5))
-        .idle_timeout(Duration::from_secs(300))
+        .acquire_timeout(Duration::from_secs(settings.acquire_timeout_seconds))
+        .idle_timeout(Duration::from_secs(settings.idle_timeout_seconds))
         .sqlx_logging(true);
 
-    Database::connect(opt).await
+    // Each connection to an in-memory SQLite database gets its own empty
+    // database, so keep exactly one open for the life of the pool
+    if in_memory {
+        let forever = Duration::from_secs(u32::MAX as u64);
+        opt.max_connections(1)
+            .min_connections(1)
+            .idle_timeout(forever)
+            .max_lifetime(forever);
+    }
+
+    Ok(opt)
+}
+
+fn validate_pool(settings: &DatabaseSettings) -> Result<(), DbErr> {
+    if settings.max_connections == 0 {
+        return Err(DbErr::Custom(
+            "DB_MAX_CONNECTIONS must be at least 1".to_string(),
+        ));
+    }
+    if settings.min_connections > settings.max_connections {
+        return Err(DbErr::Custom(format!(
+            "DB_MIN_CONNECTIONS ({}) must not exceed DB_MAX_CONNECTIONS ({})",
+            settings.min_connections, settings.max_connections
+        )));
+    }
+    if settings.acquire_timeout_seconds == 0 {
+        return Err(DbErr::Custom(
+            "DB_ACQUIRE_TIMEOUT_SECONDS must be at least 1".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn is_sqlite_memory(url: &str) -> bool {
+    url.starts_with("sqlite:") && (url.contains(":memory:") || url.contains("mode=memory"))
+}
+
+/// Point-in-time utilization of the connection pool.
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct PoolStats {
+    /// Open connections, idle or checked out
+    pub size: u32,
+    pub idle: u32,
+    /// Connections currently checked out by queries or transactions
+    pub in_use: u32,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// `in_use / max_connections`; near 1.0 means requests are queueing
+    /// for a connection
+    pub utilization: f64,
+}
+
+impl PoolStats {
+    fn new<DB: sea_orm::sqlx::Database>(pool: &sea_orm::sqlx::Pool<DB>) -> Self {
+        let size = pool.size();
+        let idle = (pool.num_idle() as u32).min(size);
+        let in_use = size - idle;
+        let max_connections = pool.options().get_max_connections();
+        Self {
+            size,
+            idle,
+            in_use,
+            max_connections,
+            min_connections: pool.options().get_min_connections(),
+            utilization: if max_connections == 0 {
+                0.0
+            } else {
+                in_use as f64 / max_connections as f64
+            },
+        }
+    }
+}
+
+/// Current pool utilization, or `None` for a connection without a pool.
+pub fn pool_stats(db: &DatabaseConnection) -> Option<PoolStats> {
+    match db {
+        DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+            Some(PoolStats::new(db.get_postgres_connection_pool()))
+        }
+        DatabaseConnection::SqlxMySqlPoolConnection(_) => {
+            Some(PoolStats::new(db.get_mysql_connection_pool()))
+        }
+        DatabaseConnection::SqlxSqlitePoolConnection(_) => {
+            Some(PoolStats::new(db.get_sqlite_connection_pool()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(max: u32, min: u32) -> DatabaseSettings {
+        DatabaseSettings {
+            max_connections: max,
+            min_connections: min,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pool_limits_are_validated() {
+        assert!(validate_pool(&settings(10, 2)).is_ok());
+        assert!(validate_pool(&settings(4, 4)).is_ok());
+        assert!(validate_pool(&settings(0, 0)).is_err());
+        assert!(validate_pool(&settings(2, 5)).is_err());
+        let no_wait = DatabaseSettings {
+            acquire_timeout_seconds: 0,
+            ..Default::default()
+        };
+        assert!(validate_pool(&no_wait).is_err());
+    }
 }
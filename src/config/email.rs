@@ -1,38 +1,236 @@
 use std::env;
+use std::time::Duration;
+
+/// Where outgoing email is handed off.
+#[derive(Clone)]
+pub enum EmailProviderConfig {
+    Smtp {
+        host: String,
+        port: u16,
+        username: String,
+        password: String,
+    },
+    SendGrid {
+        api_key: String,
+        api_url: String,
+    },
+    Ses {
+        region: String,
+        access_key_id: String,
+        secret_access_key: String,
+        endpoint: String,
+    },
+}
+
+impl EmailProviderConfig {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmailProviderConfig::Smtp { .. } => "smtp",
+            EmailProviderConfig::SendGrid { .. } => "sendgrid",
+            EmailProviderConfig::Ses { .. } => "ses",
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct EmailConfig {
-    pub smtp_host: String,
-    pub smtp_port: u16,
-    pub smtp_username: String,
-    pub smtp_password: String,
+    pub provider: EmailProviderConfig,
     pub from_address: String,
-    pub frontend_url: String,
+    /// Most emails handed to the provider per second; unlimited if `None`
+    pub rate_limit_per_second: Option<f64>,
 }
 
 impl EmailConfig {
-    /// Read email config from environment variables.
-    /// Returns None if SMTP is not configured (graceful degradation).
+    /// Read email config from environment variables. `EMAIL_PROVIDER`
+    /// selects `smtp`, `sendgrid` or `ses`, defaulting to `smtp` when
+    /// `SMTP_HOST` is set. Returns None if no provider is fully configured
+    /// (graceful degradation).
     pub fn from_env() -> Option<Self> {
-        let smtp_host = env::var("SMTP_HOST").ok()?;
-        let smtp_port = env::var("SMTP_PORT")
+        let provider = match env::var("EMAIL_PROVIDER")
             .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(587);
-        let smtp_username = env::var("SMTP_USERNAME").ok()?;
-        let smtp_password = env::var("SMTP_PASSWORD").ok()?;
-        let from_address =
-            env::var("SMTP_FROM").unwrap_or_else(|_| format!("Forum <{}>", smtp_username.clone()));
-        let frontend_url =
-            env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+            .map(|v| v.trim().to_ascii_lowercase())
+            .filter(|v| !v.is_empty())
+        {
+            Some(name) => match name.as_str() {
+                "smtp" => smtp_from_env(),
+                "sendgrid" => sendgrid_from_env(),
+                "ses" => ses_from_env(),
+                other => {
+                    tracing::warn!("Unknown EMAIL_PROVIDER '{}', email disabled", other);
+                    None
+                }
+            },
+            None if env::var("SMTP_HOST").is_ok() => smtp_from_env(),
+            None => None,
+        }?;
+
+        let from_address = match env::var("EMAIL_FROM").or_else(|_| env::var("SMTP_FROM")) {
+            Ok(from) => from,
+            Err(_) => match &provider {
+                EmailProviderConfig::Smtp { username, .. } => format!("Forum <{}>", username),
+                _ => {
+                    tracing::warn!(
+                        "EMAIL_FROM must be set for the {} provider, email disabled",
+                        provider.name()
+                    );
+                    return None;
+                }
+            },
+        };
+
+        // SES starts accounts in the sandbox, limited to one email per second
+        let default_rate = match provider {
+            EmailProviderConfig::Ses { .. } => Some(1.0),
+            _ => None,
+        };
+        let rate_limit_per_second = env::var("EMAIL_RATE_LIMIT_PER_SECOND")
+            .ok()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .map(|v| (v > 0.0).then_some(v))
+            .unwrap_or(default_rate);
 
         Some(Self {
-            smtp_host,
-            smtp_port,
-            smtp_username,
-            smtp_password,
+            provider,
             from_address,
-            frontend_url,
+            rate_limit_per_second,
         })
     }
 }
+
+fn required(name: &str, provider: &str) -> Option<String> {
+    let value = env::var(name).ok().filter(|v| !v.trim().is_empty());
+    if value.is_none() {
+        tracing::warn!("{} is required for the {} email provider", name, provider);
+    }
+    value
+}
+
+fn smtp_from_env() -> Option<EmailProviderConfig> {
+    let host = required("SMTP_HOST", "smtp")?;
+    let port = env::var("SMTP_PORT")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(587);
+    let username = required("SMTP_USERNAME", "smtp")?;
+    let password = required("SMTP_PASSWORD", "smtp")?;
+    Some(EmailProviderConfig::Smtp {
+        host,
+        port,
+        username,
+        password,
+    })
+}
+
+fn sendgrid_from_env() -> Option<EmailProviderConfig> {
+    let api_key = required("SENDGRID_API_KEY", "sendgrid")?;
+    let api_url = env::var("SENDGRID_API_URL")
+        .unwrap_or_else(|_| "https://api.sendgrid.com".to_string())
+        .trim_end_matches('/')
+        .to_string();
+    Some(EmailProviderConfig::SendGrid { api_key, api_url })
+}
+
+fn ses_from_env() -> Option<EmailProviderConfig> {
+    let region = env::var("SES_REGION")
+        .or_else(|_| env::var("AWS_REGION"))
+        .unwrap_or_else(|_| "us-east-1".to_string());
+    let access_key_id = env::var("SES_ACCESS_KEY_ID")
+        .ok()
+        .or_else(|| required("AWS_ACCESS_KEY_ID", "ses"))?;
+    let secret_access_key = env::var("SES_SECRET_ACCESS_KEY")
+        .ok()
+        .or_else(|| required("AWS_SECRET_ACCESS_KEY", "ses"))?;
+    let endpoint = env::var("SES_ENDPOINT")
+        .unwrap_or_else(|_| format!("https://email.{}.amazonaws.com", region))
+        .trim_end_matches('/')
+        .to_string();
+    Some(EmailProviderConfig::Ses {
+        region,
+        access_key_id,
+        secret_access_key,
+        endpoint,
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EmailQueueConfig {
+    /// Delivery attempts before an email is marked `failed`
+    pub max_attempts: i32,
+    /// Delay before the first retry; doubles with each further attempt
+    pub retry_base: Duration,
+    /// How often the worker looks for due emails when not woken by a new one
+    pub poll_interval: Duration,
+}
+
+impl EmailQueueConfig {
+    pub fn from_env() -> Self {
+        let max_attempts = env::var("EMAIL_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &i32| *v >= 1)
+            .unwrap_or(5);
+
+        let retry_base_seconds = env::var("EMAIL_RETRY_BASE_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(30);
+
+        let poll_interval_seconds = env::var("EMAIL_POLL_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(5);
+
+        Self {
+            max_attempts,
+            retry_base: Duration::from_secs(retry_base_seconds),
+            poll_interval: Duration::from_secs(poll_interval_seconds),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DigestConfig {
+    /// How often the scheduler looks for users whose digest is due
+    pub check_interval: Duration,
+    /// Most posts listed in one digest
+    pub max_posts: u64,
+}
+
+impl DigestConfig {
+    pub fn from_env() -> Self {
+        let check_interval_seconds = env::var("DIGEST_CHECK_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(3600);
+
+        let max_posts = env::var("DIGEST_MAX_POSTS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(10);
+
+        Self {
+            check_interval: Duration::from_secs(check_interval_seconds),
+            max_posts,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EmailWebhookConfig {
+    /// Shared secret the bounce and complaint webhooks must be called with
+    /// as `?token=`; the webhooks are disabled without it
+    pub secret: Option<String>,
+}
+
+impl EmailWebhookConfig {
+    pub fn from_env() -> Self {
+        Self {
+            secret: env::var("EMAIL_WEBHOOK_SECRET")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::env;
+use std::time::Duration;
+
+/// Which ActivityPub object type posts are published as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostObjectType {
+    /// Titled long-form object, shown by Lemmy, kbin and similar as a thread
+    Article,
+    /// Microblog status; the title becomes the first line of the content
+    Note,
+}
+
+impl PostObjectType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostObjectType::Article => "Article",
+            PostObjectType::Note => "Note",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FederationConfig {
+    /// Serve actors and WebFinger, accept follows and deliver new posts
+    pub enabled: bool,
+    pub post_type: PostObjectType,
+    /// How often the delivery worker looks for due activities
+    pub poll_interval: Duration,
+    /// Delivery attempts before an activity is dropped
+    pub max_attempts: i32,
+    /// Timeout of requests to remote servers
+    pub timeout: Duration,
+    /// How long a fetched remote actor is trusted before it is re-fetched
+    pub actor_ttl: Duration,
+}
+
+impl FederationConfig {
+    pub fn from_env() -> Self {
+        let enabled = env::var("FEDERATION_ENABLED")
+            .ok()
+            .map(|v| {
+                matches!(
+                    v.trim().to_ascii_lowercase().as_str(),
+                    "1" | "true" | "yes" | "y" | "on"
+                )
+            })
+            .unwrap_or(false);
+
+        let post_type = match env::var("FEDERATION_POST_TYPE")
+            .map(|v| v.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            Ok("note") => PostObjectType::Note,
+            _ => PostObjectType::Article,
+        };
+
+        let poll_interval_seconds = env::var("FEDERATION_POLL_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(10);
+
+        let max_attempts = env::var("FEDERATION_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &i32| *v >= 1)
+            .unwrap_or(8);
+
+        let timeout_seconds = env::var("FEDERATION_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(10);
+
+        Self {
+            enabled,
+            post_type,
+            poll_interval: Duration::from_secs(poll_interval_seconds),
+            max_attempts,
+            timeout: Duration::from_secs(timeout_seconds),
+            actor_ttl: Duration::from_secs(24 * 3600),
+        }
+    }
+}
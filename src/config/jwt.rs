@@ -4,8 +4,9 @@ use std::env;
 #[derive(Debug, Clone)]
 pub struct JwtConfig {
     pub secret: String,
-    pub access_token_expiry: u64,  // 15 minutes
-    pub refresh_token_expiry: u64, // 7 days
+    pub access_token_expiry: u64,        // 15 minutes
+    pub refresh_token_expiry: u64,       // 7 days
+    pub impersonation_token_expiry: u64, // 10 minutes
 }
 
 impl JwtConfig {
@@ -27,10 +28,16 @@ impl JwtConfig {
             .and_then(|s| s.parse().ok())
             .unwrap_or(604800); // 7 days
 
+        let impersonation_token_expiry = env::var("JWT_IMPERSONATION_EXPIRATION")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(600); // 10 minutes
+
         Ok(Self {
             secret,
             access_token_expiry,
             refresh_token_expiry,
+            impersonation_token_expiry,
         })
     }
 }
@@ -1,6 +1,19 @@
+pub mod app;
 pub mod auth;
+pub mod body_limit;
+pub mod comment;
 pub mod database;
 pub mod email;
+pub mod federation;
 pub mod jwt;
 pub mod rate_limit;
 pub mod redis;
+pub mod search;
+pub mod sentry;
+pub mod seo;
+pub mod tenancy;
+pub mod tls;
+pub mod upload_cleanup;
+pub mod views;
+pub mod virus_scan;
+pub mod websocket;
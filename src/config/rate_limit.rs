@@ -1,5 +1,10 @@
 use std::env;
 
+/// Hours an account counts as new when `RATE_LIMIT_NEW_ACCOUNT_HOURS` is unset.
+const DEFAULT_NEW_ACCOUNT_HOURS: i64 = 72;
+
+/// One request is replenished every `per_second` seconds, up to
+/// `burst_size` banked.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RateLimitRule {
     pub per_second: u64,
@@ -13,23 +18,109 @@ impl RateLimitRule {
             burst_size,
         }
     }
+
+    /// The default budget for new accounts: half the burst, replenished at
+    /// half the rate.
+    fn for_new_accounts(self) -> Self {
+        Self::new(self.per_second * 2, (self.burst_size / 2).max(1))
+    }
+}
+
+/// Routes sharing a budget. Groups other than `Auth`, `PublicRead` and
+/// `Protected` inherit their parent's rule unless configured themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitGroup {
+    Auth,
+    PublicRead,
+    /// Search endpoints; inherits `PublicRead`
+    Search,
+    Protected,
+    /// Creating and editing posts, comments and reports; inherits `Protected`
+    Content,
+    /// Voting; inherits `Protected`
+    Votes,
+    /// Uploads; inherits `Protected`
+    Uploads,
+    /// Admin and moderation endpoints; inherits `Protected`
+    Admin,
+}
+
+impl RateLimitGroup {
+    pub const ALL: [RateLimitGroup; 8] = [
+        RateLimitGroup::Auth,
+        RateLimitGroup::PublicRead,
+        RateLimitGroup::Search,
+        RateLimitGroup::Protected,
+        RateLimitGroup::Content,
+        RateLimitGroup::Votes,
+        RateLimitGroup::Uploads,
+        RateLimitGroup::Admin,
+    ];
+
+    fn parent(&self) -> Option<RateLimitGroup> {
+        match self {
+            RateLimitGroup::Search => Some(RateLimitGroup::PublicRead),
+            RateLimitGroup::Content
+            | RateLimitGroup::Votes
+            | RateLimitGroup::Uploads
+            | RateLimitGroup::Admin => Some(RateLimitGroup::Protected),
+            _ => None,
+        }
+    }
+
+    fn index(&self) -> usize {
+        Self::ALL.iter().position(|g| g == self).unwrap_or(0)
+    }
+}
+
+/// A rule for every group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupRules([RateLimitRule; 8]);
+
+impl GroupRules {
+    pub fn get(&self, group: RateLimitGroup) -> RateLimitRule {
+        self.0[group.index()]
+    }
+
+    fn set(&mut self, group: RateLimitGroup, rule: RateLimitRule) {
+        self.0[group.index()] = rule;
+    }
+
+    fn map(&self, f: impl Fn(RateLimitRule) -> RateLimitRule) -> Self {
+        Self(self.0.map(f))
+    }
+}
+
+impl Default for GroupRules {
+    fn default() -> Self {
+        let mut rules = Self([RateLimitRule::new(10, 20); 8]);
+        rules.set(RateLimitGroup::Auth, RateLimitRule::new(5, 10));
+        rules.set(RateLimitGroup::PublicRead, RateLimitRule::new(30, 60));
+        rules.set(RateLimitGroup::Search, RateLimitRule::new(30, 60));
+        rules
+    }
 }
 
+/// Authenticated requests are limited per user, anonymous ones per IP.
+/// Accounts younger than `new_account_hours` get the `new_account` budgets.
 #[derive(Debug, Clone, Copy)]
 pub struct RateLimitConfig {
     pub enabled: bool,
-    pub auth: RateLimitRule,
-    pub public_read: RateLimitRule,
-    pub protected: RateLimitRule,
+    /// Budgets for established accounts and anonymous clients
+    pub established: GroupRules,
+    pub new_account: GroupRules,
+    /// 0 treats every account as established
+    pub new_account_hours: i64,
 }
 
 impl Default for RateLimitConfig {
     fn default() -> Self {
+        let established = GroupRules::default();
         Self {
             enabled: true,
-            auth: RateLimitRule::new(5, 10),
-            public_read: RateLimitRule::new(30, 60),
-            protected: RateLimitRule::new(10, 20),
+            established,
+            new_account: established.map(RateLimitRule::for_new_accounts),
+            new_account_hours: DEFAULT_NEW_ACCOUNT_HOURS,
         }
     }
 }
@@ -41,32 +132,40 @@ impl RateLimitConfig {
 
         if let Ok(raw) = env::var("RATE_LIMIT_CONFIG") {
             match parse_rate_limit_config(&raw) {
-                Ok(parsed) => cfg = cfg.apply_partial(parsed),
+                Ok(parsed) => {
+                    cfg.established = parsed.apply_to(cfg.established);
+                    cfg.new_account = cfg.established.map(RateLimitRule::for_new_accounts);
+                }
                 Err(err) => {
                     tracing::warn!("Invalid RATE_LIMIT_CONFIG '{}': {}", raw, err);
                 }
             }
         }
+        if let Ok(raw) = env::var("RATE_LIMIT_NEW_ACCOUNT_CONFIG") {
+            match parse_rate_limit_config(&raw) {
+                Ok(parsed) => cfg.new_account = parsed.apply_to(cfg.new_account),
+                Err(err) => {
+                    tracing::warn!("Invalid RATE_LIMIT_NEW_ACCOUNT_CONFIG '{}': {}", raw, err);
+                }
+            }
+        }
+        if let Ok(raw) = env::var("RATE_LIMIT_NEW_ACCOUNT_HOURS") {
+            match raw.trim().parse::<i64>() {
+                Ok(hours) if hours >= 0 => cfg.new_account_hours = hours,
+                _ => tracing::warn!("Invalid RATE_LIMIT_NEW_ACCOUNT_HOURS '{}'", raw),
+            }
+        }
 
         cfg
     }
 
-    fn apply_partial(mut self, parsed: PartialRateLimitConfig) -> Self {
-        if let Some(rule) = parsed.global {
-            self.auth = rule;
-            self.public_read = rule;
-            self.protected = rule;
-        }
-        if let Some(rule) = parsed.auth {
-            self.auth = rule;
-        }
-        if let Some(rule) = parsed.public_read {
-            self.public_read = rule;
+    /// The rule for `group`, for a new account or not.
+    pub fn rule(&self, group: RateLimitGroup, new_account: bool) -> RateLimitRule {
+        if new_account {
+            self.new_account.get(group)
+        } else {
+            self.established.get(group)
         }
-        if let Some(rule) = parsed.protected {
-            self.protected = rule;
-        }
-        self
     }
 }
 
@@ -76,6 +175,41 @@ struct PartialRateLimitConfig {
     auth: Option<RateLimitRule>,
     public_read: Option<RateLimitRule>,
     protected: Option<RateLimitRule>,
+    search: Option<RateLimitRule>,
+    content: Option<RateLimitRule>,
+    votes: Option<RateLimitRule>,
+    uploads: Option<RateLimitRule>,
+    admin: Option<RateLimitRule>,
+}
+
+impl PartialRateLimitConfig {
+    fn explicit(&self, group: RateLimitGroup) -> Option<RateLimitRule> {
+        match group {
+            RateLimitGroup::Auth => self.auth,
+            RateLimitGroup::PublicRead => self.public_read,
+            RateLimitGroup::Search => self.search,
+            RateLimitGroup::Protected => self.protected,
+            RateLimitGroup::Content => self.content,
+            RateLimitGroup::Votes => self.votes,
+            RateLimitGroup::Uploads => self.uploads,
+            RateLimitGroup::Admin => self.admin,
+        }
+    }
+
+    /// Override `rules` with what was configured. A group left out follows
+    /// its parent if that was configured, then the global rule.
+    fn apply_to(&self, mut rules: GroupRules) -> GroupRules {
+        for group in RateLimitGroup::ALL {
+            let rule = self
+                .explicit(group)
+                .or_else(|| group.parent().and_then(|p| self.explicit(p)))
+                .or(self.global);
+            if let Some(rule) = rule {
+                rules.set(group, rule);
+            }
+        }
+        rules
+    }
 }
 
 fn parse_bool_env(var_name: &str, default: bool) -> bool {
@@ -119,9 +253,14 @@ fn parse_rate_limit_config(raw: &str) -> Result<PartialRateLimitConfig, String>
             Some("auth") => parsed.auth = Some(rule),
             Some("public_read") => parsed.public_read = Some(rule),
             Some("protected") => parsed.protected = Some(rule),
+            Some("search") => parsed.search = Some(rule),
+            Some("content") => parsed.content = Some(rule),
+            Some("votes") => parsed.votes = Some(rule),
+            Some("uploads") => parsed.uploads = Some(rule),
+            Some("admin") => parsed.admin = Some(rule),
             _ => {
                 return Err(format!(
-                    "unknown group '{}', expected auth/public/protected",
+                    "unknown group '{}', expected auth/public/search/protected/content/votes/uploads/admin",
                     name.trim()
                 ));
             }
@@ -136,6 +275,11 @@ fn normalize_group_name(name: &str) -> Option<&'static str> {
         "auth" => Some("auth"),
         "public" | "public_read" | "public-read" => Some("public_read"),
         "protected" => Some("protected"),
+        "search" => Some("search"),
+        "content" => Some("content"),
+        "votes" | "vote" => Some("votes"),
+        "uploads" | "upload" => Some("uploads"),
+        "admin" => Some("admin"),
         _ => None,
     }
 }
@@ -189,6 +333,38 @@ mod tests {
         assert_eq!(parsed.public_read, Some(RateLimitRule::new(8, 16)));
     }
 
+    #[test]
+    fn child_groups_follow_parent() {
+        let parsed = parse_rate_limit_config("protected=5:6,votes=7:8").unwrap();
+        let rules = parsed.apply_to(GroupRules::default());
+        assert_eq!(rules.get(RateLimitGroup::Content), RateLimitRule::new(5, 6));
+        assert_eq!(rules.get(RateLimitGroup::Votes), RateLimitRule::new(7, 8));
+        assert_eq!(
+            rules.get(RateLimitGroup::Search),
+            RateLimitRule::new(30, 60)
+        );
+
+        let global = parse_rate_limit_config("3:4").unwrap();
+        let rules = global.apply_to(GroupRules::default());
+        assert!(RateLimitGroup::ALL
+            .iter()
+            .all(|g| rules.get(*g) == RateLimitRule::new(3, 4)));
+    }
+
+    #[test]
+    fn new_accounts_get_smaller_budgets() {
+        let cfg = RateLimitConfig::default();
+        assert_eq!(
+            cfg.rule(RateLimitGroup::Protected, false),
+            RateLimitRule::new(10, 20)
+        );
+        assert_eq!(
+            cfg.rule(RateLimitGroup::Protected, true),
+            RateLimitRule::new(20, 10)
+        );
+        assert_eq!(RateLimitRule::new(1, 1).for_new_accounts().burst_size, 1);
+    }
+
     #[test]
     fn parse_invalid_rule() {
         let err = parse_rate_limit_config("auth=abc").unwrap_err();
@@ -1,9 +1,7 @@
 use redis::aio::ConnectionManager;
 use tokio::time::{timeout, Duration};
 
-pub async fn get_redis() -> anyhow::Result<ConnectionManager> {
-    let redis_url =
-        std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string());
+pub async fn get_redis(redis_url: &str) -> anyhow::Result<ConnectionManager> {
     let client = redis::Client::open(redis_url)?;
 
     // Add 5 second timeout for Redis connection
@@ -0,0 +1,36 @@
+use std::env;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchConfig {
+    /// Retry zero-hit Postgres searches with trigram similarity on titles
+    pub fuzzy_fallback: bool,
+    /// Minimum `word_similarity` for a fuzzy match (0.0 - 1.0)
+    pub fuzzy_threshold: f64,
+}
+
+impl SearchConfig {
+    pub fn from_env() -> Self {
+        let fuzzy_fallback = env::var("SEARCH_FUZZY_FALLBACK")
+            .ok()
+            .and_then(|v| {
+                let v = v.trim().to_ascii_lowercase();
+                match v.as_str() {
+                    "1" | "true" | "yes" | "y" | "on" => Some(true),
+                    "0" | "false" | "no" | "n" | "off" => Some(false),
+                    _ => None,
+                }
+            })
+            .unwrap_or(true);
+
+        let fuzzy_threshold = env::var("SEARCH_FUZZY_THRESHOLD")
+            .ok()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|v| (0.0..=1.0).contains(v))
+            .unwrap_or(0.5);
+
+        Self {
+            fuzzy_fallback,
+            fuzzy_threshold,
+        }
+    }
+}
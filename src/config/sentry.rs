@@ -0,0 +1,32 @@
+use std::env;
+
+#[derive(Debug, Clone)]
+pub struct SentryConfig {
+    /// Error reporting is disabled when unset
+    pub dsn: Option<String>,
+    pub environment: Option<String>,
+    /// Fraction of error events sent, between 0 and 1
+    pub sample_rate: f32,
+}
+
+impl SentryConfig {
+    pub fn from_env() -> Self {
+        let dsn = env::var("SENTRY_DSN").ok().filter(|v| !v.trim().is_empty());
+
+        let environment = env::var("SENTRY_ENVIRONMENT")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+
+        let sample_rate = env::var("SENTRY_SAMPLE_RATE")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &f32| (0.0..=1.0).contains(v))
+            .unwrap_or(1.0);
+
+        Self {
+            dsn,
+            environment,
+            sample_rate,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::env;
+use std::time::Duration;
+
+/// Sitemaps may list at most 50,000 URLs each.
+const MAX_SITEMAP_URLS: u64 = 50_000;
+
+#[derive(Debug, Clone)]
+pub struct SeoConfig {
+    /// Origin of the public site the sitemap links to, without trailing `/`
+    pub site_url: String,
+    /// Origin this API is served on, for the sitemap links themselves
+    pub public_api_url: String,
+    /// URLs per child sitemap
+    pub page_size: u64,
+    /// How long crawlers and proxies may cache sitemaps
+    pub max_age: Duration,
+    /// Path prefixes crawlers are asked to skip
+    pub robots_disallow: Vec<String>,
+    pub robots_crawl_delay: Option<u32>,
+    /// Served verbatim as `robots.txt` instead of the generated one
+    pub robots_txt_path: Option<String>,
+    /// Provider name shown on link previews
+    pub site_name: String,
+    /// Preview image for posts without one of their own
+    pub og_default_image: Option<String>,
+}
+
+impl SeoConfig {
+    pub fn from_env() -> Self {
+        let site_url = non_empty("SITE_URL")
+            .or_else(|| non_empty("FRONTEND_URL"))
+            .unwrap_or_else(|| "http://localhost:3000".to_string())
+            .trim_end_matches('/')
+            .to_string();
+        // Same origin as the site unless the API is served elsewhere
+        let public_api_url = non_empty("PUBLIC_API_URL")
+            .map(|v| v.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| site_url.clone());
+
+        let page_size = env::var("SITEMAP_PAGE_SIZE")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(10_000)
+            .min(MAX_SITEMAP_URLS);
+
+        let max_age_seconds = env::var("SITEMAP_MAX_AGE_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(3600);
+
+        let robots_disallow = match env::var("ROBOTS_DISALLOW") {
+            Ok(v) => v
+                .split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+            Err(_) => vec!["/api/".to_string(), "/out".to_string()],
+        };
+
+        let robots_crawl_delay = env::var("ROBOTS_CRAWL_DELAY")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u32| *v >= 1);
+
+        Self {
+            site_url,
+            public_api_url,
+            page_size,
+            max_age: Duration::from_secs(max_age_seconds),
+            robots_disallow,
+            robots_crawl_delay,
+            robots_txt_path: non_empty("ROBOTS_TXT_PATH"),
+            site_name: non_empty("SITE_NAME").unwrap_or_else(|| "XJY".to_string()),
+            og_default_image: non_empty("OG_DEFAULT_IMAGE"),
+        }
+    }
+
+    /// Same settings for a tenant served on `host`, keeping the scheme of
+    /// the site URL.
+    pub fn for_host(self, host: &str) -> Self {
+        let scheme = self
+            .site_url
+            .split_once("://")
+            .map_or("https", |(scheme, _)| scheme);
+        let origin = format!("{scheme}://{}", host.to_ascii_lowercase());
+        Self {
+            site_url: origin.clone(),
+            public_api_url: origin,
+            ..self
+        }
+    }
+}
+
+fn non_empty(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
@@ -0,0 +1,36 @@
+use std::env;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TenancyConfig {
+    /// Serve the communities in the `tenants` table on their own hosts;
+    /// requests for any other host get the default community
+    pub enabled: bool,
+    /// Connection pool size of each tenant; the default community keeps
+    /// `DB_MAX_CONNECTIONS`
+    pub max_connections: u32,
+}
+
+impl TenancyConfig {
+    pub fn from_env() -> Self {
+        let enabled = env::var("TENANCY_ENABLED")
+            .ok()
+            .map(|v| {
+                matches!(
+                    v.trim().to_ascii_lowercase().as_str(),
+                    "1" | "true" | "yes" | "y" | "on"
+                )
+            })
+            .unwrap_or(false);
+
+        let max_connections = env::var("TENANT_DB_MAX_CONNECTIONS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u32| *v >= 1)
+            .unwrap_or(5);
+
+        Self {
+            enabled,
+            max_connections,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::Context;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// TLS termination in the server itself, for deployments without a
+/// reverse proxy. Enabled by setting both `TLS_CERT_PATH` and
+/// `TLS_KEY_PATH`.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first
+    pub cert_path: PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_path: PathBuf,
+    /// How often to check the files for a rotated certificate; zero only
+    /// loads them at startup
+    pub reload_interval: Duration,
+}
+
+impl TlsConfig {
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let path = |name: &str| {
+            env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+
+        let (cert_path, key_path) = match (path("TLS_CERT_PATH"), path("TLS_KEY_PATH")) {
+            (Some(cert), Some(key)) => (cert, key),
+            (None, None) => return Ok(None),
+            _ => anyhow::bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+        };
+
+        let reload_seconds = env::var("TLS_RELOAD_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(60);
+
+        Ok(Some(Self {
+            cert_path,
+            key_path,
+            reload_interval: Duration::from_secs(reload_seconds),
+        }))
+    }
+
+    /// Read the certificate and key into a rustls server config.
+    pub fn load(&self) -> anyhow::Result<Arc<ServerConfig>> {
+        let certs = CertificateDer::pem_file_iter(&self.cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .with_context(|| format!("Failed to read {}", self.cert_path.display()))?;
+        if certs.is_empty() {
+            anyhow::bail!("No certificates found in {}", self.cert_path.display());
+        }
+        let key = PrivateKeyDer::from_pem_file(&self.key_path)
+            .with_context(|| format!("Failed to read {}", self.key_path.display()))?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .context("Certificate and key do not match")?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+}
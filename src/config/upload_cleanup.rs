@@ -0,0 +1,31 @@
+use std::env;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy)]
+pub struct UploadCleanupConfig {
+    /// Uploads no post, comment or avatar refers to are deleted once they
+    /// are this old, leaving time to use a file after uploading it
+    pub grace_period: Duration,
+    /// How often the background task looks for such uploads
+    pub interval: Duration,
+}
+
+impl UploadCleanupConfig {
+    pub fn from_env() -> Self {
+        let grace_seconds = env::var("UPLOAD_ORPHAN_GRACE_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(24 * 3600);
+
+        let interval_seconds = env::var("UPLOAD_CLEANUP_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(3600);
+
+        Self {
+            grace_period: Duration::from_secs(grace_seconds),
+            interval: Duration::from_secs(interval_seconds),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::env;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ViewConfig {
+    /// Repeat views of a post by the same user or IP within this window are
+    /// not counted; zero counts every view
+    pub dedup_window: Duration,
+    /// Pending in-memory views are written to `posts.view_count` once this
+    /// many have accumulated
+    pub flush_threshold: u64,
+    /// How often the background task writes pending views
+    pub flush_interval: Duration,
+}
+
+impl ViewConfig {
+    pub fn from_env() -> Self {
+        let dedup_seconds = env::var("VIEW_DEDUP_WINDOW_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(1800);
+
+        let flush_threshold = env::var("VIEW_FLUSH_THRESHOLD")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(50);
+
+        let flush_interval_seconds = env::var("VIEW_FLUSH_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(10);
+
+        Self {
+            dedup_window: Duration::from_secs(dedup_seconds),
+            flush_threshold,
+            flush_interval: Duration::from_secs(flush_interval_seconds),
+        }
+    }
+}
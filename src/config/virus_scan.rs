@@ -0,0 +1,60 @@
+use std::env;
+use std::time::Duration;
+
+/// How uploads are scanned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanBackend {
+    /// clamd's `INSTREAM` command over TCP, at `host:port`
+    Clamd(String),
+    /// A program run with the file path as its last argument; exit code 0
+    /// means clean and 1 infected, like `clamdscan` and `clamscan`
+    Command(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct VirusScanConfig {
+    /// `None` leaves uploads unscanned
+    pub backend: Option<ScanBackend>,
+    pub timeout: Duration,
+    /// Accept uploads when the scanner can't be reached or fails, instead
+    /// of rejecting them
+    pub fail_open: bool,
+}
+
+impl VirusScanConfig {
+    pub fn from_env() -> Self {
+        let clamd = env::var("UPLOAD_SCAN_CLAMD")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        let command = env::var("UPLOAD_SCAN_COMMAND")
+            .ok()
+            .map(|v| v.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+            .filter(|v| !v.is_empty());
+        let backend = clamd
+            .map(ScanBackend::Clamd)
+            .or(command.map(ScanBackend::Command));
+
+        let timeout_seconds = env::var("UPLOAD_SCAN_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(30);
+
+        let fail_open = env::var("UPLOAD_SCAN_FAIL_OPEN")
+            .ok()
+            .map(|v| {
+                matches!(
+                    v.trim().to_ascii_lowercase().as_str(),
+                    "1" | "true" | "yes" | "y" | "on"
+                )
+            })
+            .unwrap_or(false);
+
+        Self {
+            backend,
+            timeout: Duration::from_secs(timeout_seconds),
+            fail_open,
+        }
+    }
+}
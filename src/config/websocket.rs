@@ -0,0 +1,59 @@
+use std::env;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WebSocketConfig {
+    /// How long a socket opened without a token has to send its auth
+    /// message before it is closed
+    pub auth_timeout: Duration,
+    /// How often changed post stats are sent to the posts' channels; a
+    /// post's changes within one interval go out as one update
+    pub post_stats_interval: Duration,
+    /// Messages queued for a socket before it counts as too slow
+    pub send_queue_size: usize,
+    /// Messages a client may send per second once its burst is spent
+    pub client_messages_per_second: NonZeroU32,
+    /// Messages a client may send at once
+    pub client_message_burst: NonZeroU32,
+}
+
+impl WebSocketConfig {
+    pub fn from_env() -> Self {
+        let auth_timeout_seconds = env::var("WS_AUTH_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(10);
+
+        let post_stats_interval_ms = env::var("WS_POST_STATS_INTERVAL_MS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 100)
+            .unwrap_or(1000);
+
+        let send_queue_size = env::var("WS_SEND_QUEUE_SIZE")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &usize| *v >= 1)
+            .unwrap_or(64);
+
+        let client_messages_per_second = env::var("WS_CLIENT_MESSAGES_PER_SECOND")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(NonZeroU32::new(5).unwrap());
+
+        let client_message_burst = env::var("WS_CLIENT_MESSAGE_BURST")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(NonZeroU32::new(20).unwrap());
+
+        Self {
+            auth_timeout: Duration::from_secs(auth_timeout_seconds),
+            post_stats_interval: Duration::from_millis(post_stats_interval_ms),
+            send_queue_size,
+            client_messages_per_second,
+            client_message_burst,
+        }
+    }
+}
@@ -1,14 +1,18 @@
+use crate::utils::i18n::{self, t};
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Json, Response},
 };
+use sea_orm::{DbErr, SqlErr};
 use serde_json::json;
+use std::collections::BTreeMap;
 use thiserror::Error;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
 
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Database error: {0}")]
-    Database(#[from] sea_orm::DbErr),
+    Database(sea_orm::DbErr),
 
     #[error("Authentication failed")]
     Unauthorized,
@@ -25,6 +29,10 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     Validation(String),
 
+    /// Request fields that failed validation, with their messages
+    #[error("Validation error: {}", summary(.0))]
+    InvalidFields(BTreeMap<String, Vec<String>>),
+
     #[error("Conflict: {0}")]
     Conflict(String),
 
@@ -33,12 +41,25 @@ pub enum AppError {
 
     #[error("Payload too large")]
     PayloadTooLarge,
+
+    #[error("Too many requests, retry after {retry_after_seconds}s")]
+    TooManyRequests { retry_after_seconds: u64 },
 }
 
 #[derive(serde::Serialize, utoipa::ToSchema)]
 pub struct ErrorResponse {
-    /// Error message
+    /// Error message, in the request's locale when translated
     pub error: String,
+    /// Stable machine-readable error code, e.g. `not_found` or
+    /// `username_taken`
+    pub code: String,
+    /// Seconds to wait before retrying; only on 429
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_seconds: Option<u64>,
+    /// Messages by request field, e.g. `{"title": ["must be at most 200
+    /// characters"]}`; only on 400 for invalid fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<BTreeMap<String, Vec<String>>>,
 }
 
 impl utoipa::ToSchema for AppError {
@@ -55,41 +76,326 @@ impl utoipa::PartialSchema for AppError {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+        let (status, code, error_message) = match self {
+            AppError::TooManyRequests {
+                retry_after_seconds,
+            } => {
+                let body = json!({
+                    "error": t("too_many_requests"),
+                    "code": "too_many_requests",
+                    "retry_after_seconds": retry_after_seconds,
+                });
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after_seconds.to_string())],
+                    Json(body),
+                )
+                    .into_response();
+            }
             AppError::Database(e) => {
                 tracing::error!("Database error: {:?}", e);
+                crate::services::error_reporting::capture_database(&e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    "Database error".to_string(),
+                    "database_error",
+                    t("database_error").to_string(),
                 )
             }
-            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()),
+            AppError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                t("unauthorized").to_string(),
+            ),
             AppError::Jwt(e) => {
                 tracing::error!("JWT error: {:?}", e);
-                (StatusCode::UNAUTHORIZED, "Invalid token".to_string())
+                (
+                    StatusCode::UNAUTHORIZED,
+                    "invalid_token",
+                    t("invalid_token").to_string(),
+                )
+            }
+            AppError::NotFound => (
+                StatusCode::NOT_FOUND,
+                "not_found",
+                t("not_found").to_string(),
+            ),
+            AppError::Forbidden => (
+                StatusCode::FORBIDDEN,
+                "forbidden",
+                t("forbidden").to_string(),
+            ),
+            AppError::Validation(msg) => {
+                let (code, msg) = localize(msg, "validation_failed");
+                (StatusCode::BAD_REQUEST, code, msg)
+            }
+            AppError::InvalidFields(fields) => {
+                let body = json!({
+                    "error": summary(&fields),
+                    "code": "invalid_fields",
+                    "fields": fields,
+                });
+                return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+            }
+            AppError::Conflict(msg) => {
+                let (code, msg) = localize(msg, "conflict");
+                (StatusCode::CONFLICT, code, msg)
             }
-            AppError::NotFound => (StatusCode::NOT_FOUND, "Resource not found".to_string()),
-            AppError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden".to_string()),
-            AppError::Validation(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
             AppError::Internal(e) => {
                 tracing::error!("Internal error: {:?}", e);
+                crate::services::error_reporting::capture_internal(&e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    "Internal server error".to_string(),
+                    "internal_error",
+                    t("internal_error").to_string(),
                 )
             }
-            AppError::PayloadTooLarge => {
-                (StatusCode::PAYLOAD_TOO_LARGE, "File too large".to_string())
-            }
+            AppError::PayloadTooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "file_too_large",
+                t("file_too_large").to_string(),
+            ),
         };
 
         let body = json!({
             "error": error_message,
+            "code": code,
         });
 
         (status, Json(body)).into_response()
     }
 }
 
+/// The catalogued code and translation of a message, or the message as it
+/// is under the variant's generic code.
+fn localize(message: String, fallback_code: &'static str) -> (&'static str, String) {
+    match i18n::translate(&message) {
+        Some((code, translated)) => (code, translated.to_string()),
+        None => (fallback_code, message),
+    }
+}
+
+/// Unique constraints clients can run into, by the names the databases
+/// report them under: the constraint or index (Postgres, MySQL) or
+/// `table.column` (SQLite, MySQL).
+const UNIQUE_VIOLATIONS: &[(&[&str], &str)] = &[
+    (
+        &[
+            "users_username",
+            "users.username",
+            "username_history_username",
+            "username_history.username",
+        ],
+        "Username is already taken",
+    ),
+    (
+        &["users_email", "users.email"],
+        "Email is already registered",
+    ),
+    (
+        &["forums_name", "forums.name"],
+        "A forum with this name already exists",
+    ),
+    (
+        &["forums_slug", "forums.slug"],
+        "A forum with this slug already exists",
+    ),
+    (
+        &["tags_name", "tags.name"],
+        "A tag with this name already exists",
+    ),
+    (
+        &["tags_slug", "tags.slug"],
+        "A tag with this slug already exists",
+    ),
+    (
+        &["post_flairs_forum_id_name", "post_flairs.forum_id"],
+        "A flair with this name already exists",
+    ),
+    (
+        &["post_attachments_upload_id", "post_attachments.upload_id"],
+        "Upload is already attached to a post",
+    ),
+    (
+        &["idx_posts_crosspost_forum", "posts.crosspost_of_id"],
+        "Post is already crossposted to this forum",
+    ),
+    (
+        &["idx_posts_forum_slug", "posts.slug"],
+        "A post with this slug already exists in the forum",
+    ),
+    (
+        &["custom_emoji_shortcode", "custom_emoji.shortcode"],
+        "An emoji with this shortcode already exists",
+    ),
+    (
+        &["idx_reports_unique", "reports.reporter_id"],
+        "You have already reported this",
+    ),
+    (
+        &["idx_votes_unique", "votes.user_id"],
+        "You have already voted on this",
+    ),
+];
+
+/// A unique violation is the client's doing, like a taken slug or a second
+/// report of the same post, so it is a 409 rather than a 500.
+impl From<DbErr> for AppError {
+    fn from(e: DbErr) -> Self {
+        match e.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(detail)) => {
+                AppError::Conflict(unique_violation_message(&detail).to_string())
+            }
+            _ => AppError::Database(e),
+        }
+    }
+}
+
+fn unique_violation_message(detail: &str) -> &'static str {
+    UNIQUE_VIOLATIONS
+        .iter()
+        .find(|(names, _)| names.iter().any(|name| detail.contains(name)))
+        .map_or("Already exists", |(_, message)| message)
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields = BTreeMap::new();
+        collect_field_errors(&errors, "", &mut fields);
+        AppError::InvalidFields(fields)
+    }
+}
+
+/// Flatten nested structs and lists into `parent.child` and `list[0].child`
+/// field names.
+fn collect_field_errors(
+    errors: &ValidationErrors,
+    prefix: &str,
+    fields: &mut BTreeMap<String, Vec<String>>,
+) {
+    for (field, kind) in errors.errors() {
+        let name = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{prefix}.{field}")
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                fields
+                    .entry(name)
+                    .or_default()
+                    .extend(errors.iter().map(field_message));
+            }
+            ValidationErrorsKind::Struct(errors) => {
+                collect_field_errors(errors, &name, fields);
+            }
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect_field_errors(errors, &format!("{name}[{index}]"), fields);
+                }
+            }
+        }
+    }
+}
+
+/// The validator's own message, or one made from the rule it broke.
+fn field_message(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.to_string();
+    }
+    let param = |name: &str| error.params.get(name).map(|v| v.to_string());
+    let bounds = |unit: &str| match (param("min"), param("max"), param("equal")) {
+        (_, _, Some(equal)) => format!("must be exactly {equal}{unit}"),
+        (Some(min), Some(max), _) => format!("must be between {min} and {max}{unit}"),
+        (Some(min), None, _) => format!("must be at least {min}{unit}"),
+        (None, Some(max), _) => format!("must be at most {max}{unit}"),
+        (None, None, _) => "is out of range".to_string(),
+    };
+    match error.code.as_ref() {
+        "length" => bounds(" characters"),
+        "range" => bounds(""),
+        "email" => "must be a valid email address".to_string(),
+        "url" => "must be a valid URL".to_string(),
+        "required" => "is required".to_string(),
+        code => format!("is invalid ({code})"),
+    }
+}
+
+/// One line for the `error` field: `title: must be at most 200 characters`.
+fn summary(fields: &BTreeMap<String, Vec<String>>) -> String {
+    fields
+        .iter()
+        .flat_map(|(field, messages)| messages.iter().map(move |m| format!("{field}: {m}")))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 pub type AppResult<T> = Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use validator::Validate;
+
+    #[test]
+    fn test_unique_violations_name_the_duplicate() {
+        let postgres = "duplicate key value violates unique constraint \"forums_slug_key\"";
+        assert_eq!(
+            unique_violation_message(postgres),
+            "A forum with this slug already exists"
+        );
+        let sqlite = "UNIQUE constraint failed: reports.reporter_id, reports.target_type";
+        assert_eq!(
+            unique_violation_message(sqlite),
+            "You have already reported this"
+        );
+        assert_eq!(
+            unique_violation_message("Duplicate entry 'x' for key 'bookmarks.idx_x'"),
+            "Already exists"
+        );
+
+        let err = AppError::from(DbErr::Custom("connection reset".to_string()));
+        assert!(matches!(err, AppError::Database(_)));
+    }
+
+    #[derive(Validate)]
+    struct Form {
+        #[validate(length(min = 3, max = 50))]
+        username: String,
+        #[validate(email)]
+        email: String,
+        #[validate(range(min = 1))]
+        count: i32,
+        #[validate(nested)]
+        inner: Inner,
+    }
+
+    #[derive(Validate)]
+    struct Inner {
+        #[validate(length(max = 2, message = "too long"))]
+        note: String,
+    }
+
+    #[test]
+    fn test_validation_errors_are_reported_per_field() {
+        let form = Form {
+            username: "ab".to_string(),
+            email: "nope".to_string(),
+            count: 0,
+            inner: Inner {
+                note: "abc".to_string(),
+            },
+        };
+        let AppError::InvalidFields(fields) = AppError::from(form.validate().unwrap_err()) else {
+            panic!("expected field errors");
+        };
+        assert_eq!(fields["username"], ["must be between 3 and 50 characters"]);
+        assert_eq!(fields["email"], ["must be a valid email address"]);
+        assert_eq!(fields["count"], ["must be at least 1"]);
+        assert_eq!(fields["inner.note"], ["too long"]);
+        assert_eq!(
+            summary(&fields),
+            "count: must be at least 1; email: must be a valid email address; \
+             inner.note: too long; username: must be between 3 and 50 characters"
+        );
+    }
+}
@@ -1,12 +1,32 @@
 use crate::error::{AppError, AppResult};
-use crate::middleware::auth::{require_admin, AuthUser};
-use crate::models::UserModel;
-use crate::response::{ApiResponse, PaginatedResponse, PaginationQuery};
-use crate::services::admin::AdminService;
-use axum::{extract::Path, extract::Query, response::IntoResponse, Extension, Json};
+use crate::handlers::comment::CommentResponse;
+use crate::handlers::post::PostResponse;
+use crate::handlers::search::SearchGroupResponse;
+use crate::middleware::auth::{require_permission, AuthUser};
+use crate::middleware::permission::Permission;
+use crate::models::{AuditLogModel, CommentModel, EmailOutboxModel, PostModel, UserModel};
+use crate::response::{ApiResponse, NoContent, PaginatedResponse};
+use crate::services::account_deletion::AccountDeletionService;
+use crate::services::admin::{AdminService, StatsInterval, StatsMetric};
+use crate::services::audit::{AuditEntry, AuditLogService};
+use crate::services::cache::CacheService;
+use crate::services::export::{ExportFormat, ExportResource, ExportService};
+use crate::services::post::invalidate_post_cache;
+use crate::services::search::{SearchIndex, SearchService, SearchType};
+use crate::utils::i18n::t;
+use crate::utils::jwt::impersonation_token_expiry_seconds;
+use axum::{
+    body::Body,
+    extract::Path,
+    extract::Query,
+    http::header,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use futures_util::StreamExt;
 use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -30,6 +50,54 @@ pub struct StatsResponse {
     pub users_today: u64,
     /// Posts created today
     pub posts_today: u64,
+    /// Cache hit/miss counts since startup (empty without Redis)
+    pub cache: Vec<CacheMetricResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheMetricResponse {
+    /// Key namespace, e.g. `posts` or `forums`
+    pub namespace: String,
+    /// Lookups served from the cache
+    pub hits: u64,
+    /// Lookups that fell through to the database
+    pub misses: u64,
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct TimeseriesQuery {
+    /// `signups`, `posts`, `comments` or `active_users`
+    pub metric: String,
+    /// `day` (default), `week` or `month`
+    pub interval: Option<String>,
+    /// First day, `YYYY-MM-DD` (default: 30 days, 12 weeks or 12 months
+    /// before `to`)
+    pub from: Option<String>,
+    /// Last day, `YYYY-MM-DD` (default: today, UTC)
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TimeseriesResponse {
+    pub metric: String,
+    pub interval: String,
+    pub from: String,
+    pub to: String,
+    /// One point per bucket, oldest first, including empty buckets
+    pub points: Vec<TimeseriesPointResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TimeseriesPointResponse {
+    /// First day of the bucket, `YYYY-MM-DD`
+    pub bucket: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct ExportQuery {
+    /// `csv` (default) or `json`
+    pub format: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -38,6 +106,8 @@ pub struct AdminUserResponse {
     pub id: i32,
     /// Username
     pub username: String,
+    /// Name shown instead of the username
+    pub display_name: Option<String>,
     /// Email address
     pub email: String,
     /// Avatar URL
@@ -48,8 +118,15 @@ pub struct AdminUserResponse {
     pub karma: i32,
     /// User role
     pub role: String,
+    /// `bounce` or `complaint` if the email provider reported the address;
+    /// no email is sent to it
+    pub email_undeliverable: Option<String>,
+    /// When the address was reported
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub email_undeliverable_at: Option<chrono::NaiveDateTime>,
     /// Account creation timestamp
-    pub created_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
 }
 
 impl From<UserModel> for AdminUserResponse {
@@ -57,31 +134,43 @@ impl From<UserModel> for AdminUserResponse {
         Self {
             id: u.id,
             username: u.username,
+            display_name: u.display_name,
             email: u.email,
             avatar_url: u.avatar_url,
             bio: u.bio,
             karma: u.karma,
             role: u.role,
-            created_at: u.created_at.to_string(),
+            email_undeliverable: u.email_undeliverable,
+            email_undeliverable_at: u.email_undeliverable_at,
+            created_at: u.created_at,
         }
     }
 }
 
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct ListUsersQuery {
+    /// Only users whose address is (`true`) or isn't (`false`) undeliverable
+    pub undeliverable: Option<bool>,
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+}
+
 #[utoipa::path(
     get,
     path = "/api/v1/admin/stats",
     security(("jwt_token" = [])),
     responses(
         (status = 200, description = "Platform statistics", body = StatsResponse),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
     ),
     tag = "admin"
 )]
 pub async fn get_stats(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
     auth_user: AuthUser,
 ) -> AppResult<impl IntoResponse> {
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::ViewStats).await?;
 
     let service = AdminService::new(db);
     let stats = service.get_stats().await?;
@@ -93,35 +182,110 @@ pub async fn get_stats(
         total_forums: stats.total_forums,
         users_today: stats.users_today,
         posts_today: stats.posts_today,
+        cache: cache
+            .map(|Extension(c)| c.metrics())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|m| CacheMetricResponse {
+                namespace: m.namespace,
+                hits: m.hits,
+                misses: m.misses,
+            })
+            .collect(),
     }))
 }
 
+fn parse_date(name: &str, value: &str) -> AppResult<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| AppError::Validation(format!("{} must be a date like 2024-01-31", name)))
+}
+
 #[utoipa::path(
     get,
-    path = "/api/v1/admin/users",
+    path = "/api/v1/admin/stats/timeseries",
     security(("jwt_token" = [])),
-    params(
-        ("page" = Option<u64>, Query, description = "Page number"),
-        ("per_page" = Option<u64>, Query, description = "Items per page"),
+    params(TimeseriesQuery),
+    responses(
+        (status = 200, description = "Bucketed counts over a date range", body = TimeseriesResponse),
+        (status = 400, description = "Unknown metric or interval, or bad range", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
     ),
+    tag = "admin"
+)]
+pub async fn get_stats_timeseries(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+    Query(params): Query<TimeseriesQuery>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ViewStats).await?;
+
+    let metric = StatsMetric::parse(&params.metric).ok_or_else(|| {
+        AppError::Validation(format!(
+            "Invalid metric. Must be one of: {}",
+            StatsMetric::ALL.map(|m| m.as_str()).join(", ")
+        ))
+    })?;
+    let interval = match params.interval.as_deref() {
+        Some(name) => StatsInterval::parse(name).ok_or_else(|| {
+            AppError::Validation("Invalid interval. Must be one of: day, week, month".to_string())
+        })?,
+        None => StatsInterval::Day,
+    };
+    let to = match params.to.as_deref() {
+        Some(to) => parse_date("to", to)?,
+        None => chrono::Utc::now().date_naive(),
+    };
+    let from = match params.from.as_deref() {
+        Some(from) => parse_date("from", from)?,
+        None => interval.default_from(to),
+    };
+
+    let points = AdminService::new(db)
+        .with_cache(cache.map(|Extension(c)| c))
+        .timeseries(metric, interval, from, to)
+        .await?;
+
+    Ok(ApiResponse::ok(TimeseriesResponse {
+        metric: metric.as_str().to_string(),
+        interval: interval.as_str().to_string(),
+        from: from.to_string(),
+        to: to.to_string(),
+        points: points
+            .into_iter()
+            .map(|p| TimeseriesPointResponse {
+                bucket: p.bucket.date().to_string(),
+                count: p.count,
+            })
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/users",
+    security(("jwt_token" = [])),
+    params(ListUsersQuery),
     responses(
         (status = 200, description = "List of users", body = PaginatedResponse<AdminUserResponse>),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
     ),
     tag = "admin"
 )]
 pub async fn list_users(
     Extension(db): Extension<DatabaseConnection>,
     auth_user: AuthUser,
-    Query(params): Query<PaginationQuery>,
+    Query(params): Query<ListUsersQuery>,
 ) -> AppResult<impl IntoResponse> {
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::ManageUsers).await?;
 
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20).min(100);
 
     let service = AdminService::new(db);
-    let (users, total) = service.list_users(page, per_page).await?;
+    let (users, total) = service
+        .list_users(params.undeliverable, page, per_page)
+        .await?;
     let items = users.into_iter().map(AdminUserResponse::from).collect();
 
     Ok(ApiResponse::ok(PaginatedResponse::new(
@@ -138,28 +302,228 @@ pub async fn list_users(
     responses(
         (status = 200, description = "User role updated", body = AdminUserResponse),
         (status = 400, description = "Validation error", body = AppError),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
     ),
     tag = "admin"
 )]
 pub async fn update_user_role(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
     auth_user: AuthUser,
     Path(id): Path<i32>,
     Json(payload): Json<UpdateRoleRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::ManageUsers).await?;
 
-    let service = AdminService::new(db);
+    let service = AdminService::new(db).with_cache(cache.map(|c| c.0));
     let user = service.update_user_role(id, &payload.role).await?;
 
     Ok(ApiResponse::ok(AdminUserResponse::from(user)))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/{id}/logout",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "All sessions of the user invalidated", body = String),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "User not found", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn force_logout_user(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageUsers).await?;
+
+    let service = AdminService::new(db).with_cache(cache.map(|c| c.0));
+    service.force_logout(id).await?;
+
+    Ok(ApiResponse::ok(t("sessions_invalidated")))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/users/{id}",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "User deleted; their posts and comments are kept under `[deleted]` or deleted, per `DELETED_USER_CONTENT`", body = String),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "User not found", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn delete_user(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageUsers).await?;
+
+    AccountDeletionService::new(db)
+        .with_cache(cache.map(|c| c.0))
+        .delete(
+            id,
+            crate::config::auth::AuthConfig::from_env().deleted_user_content,
+        )
+        .await?;
+
+    Ok(ApiResponse::ok(t("user_deleted")))
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct ImpersonateRequest {
+    /// `read_only` (default) allows only GET requests; `full` allows writes
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImpersonationResponse {
+    /// Access token acting as the user; no refresh token is issued
+    pub token: String,
+    /// Seconds until the token expires
+    pub expires_in: u64,
+    /// `read_only` or `full`
+    pub mode: String,
+    /// The impersonated user
+    pub user: AdminUserResponse,
+}
+
+/// Requests made with the token carry an `X-Impersonated-By` response header
+/// and are each written to the audit log.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/impersonate/{user_id}",
+    security(("jwt_token" = [])),
+    params(("user_id" = i32, Path, description = "User to act as")),
+    request_body(content = Option<ImpersonateRequest>),
+    responses(
+        (status = 200, description = "Impersonation token issued", body = ImpersonationResponse),
+        (status = 400, description = "Invalid mode or target is an admin", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "User not found", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn impersonate_user(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(user_id): Path<i32>,
+    payload: Option<Json<ImpersonateRequest>>,
+) -> AppResult<impl IntoResponse> {
+    let admin_id = require_permission(&auth_user, Permission::ImpersonateUsers).await?;
+    let payload = payload.map(|Json(p)| p).unwrap_or_default();
+    let read_only = match payload.mode.as_deref() {
+        None | Some("read_only") => true,
+        Some("full") => false,
+        Some(other) => {
+            return Err(AppError::Validation(format!(
+                "mode must be read_only or full, got {}",
+                other
+            )))
+        }
+    };
+
+    let service = AdminService::new(db);
+    let (token, user) = service.impersonate(admin_id, user_id, read_only).await?;
+    Ok(ApiResponse::ok(ImpersonationResponse {
+        token,
+        expires_in: impersonation_token_expiry_seconds(),
+        mode: if read_only { "read_only" } else { "full" }.to_string(),
+        user: AdminUserResponse::from(user),
+    }))
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct AuditLogQuery {
+    /// Only entries by this staff member
+    pub actor_id: Option<i32>,
+    /// Only entries concerning this user
+    pub user_id: Option<i32>,
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditLogResponse {
+    /// Entry ID
+    pub id: i64,
+    /// Staff member who acted
+    pub actor_id: Option<i32>,
+    /// `impersonation_started`, `impersonated_request`, ...
+    pub action: String,
+    /// User acted upon or impersonated
+    pub target_user_id: Option<i32>,
+    /// HTTP method, for requests
+    pub method: Option<String>,
+    /// Request path, for requests
+    pub path: Option<String>,
+    /// Response status, for requests
+    pub status: Option<i32>,
+    /// Extra context, e.g. the impersonation mode
+    pub detail: Option<String>,
+    /// When it happened
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<AuditLogModel> for AuditLogResponse {
+    fn from(e: AuditLogModel) -> Self {
+        Self {
+            id: e.id,
+            actor_id: e.actor_id,
+            action: e.action,
+            target_user_id: e.target_user_id,
+            method: e.method,
+            path: e.path,
+            status: e.status,
+            detail: e.detail,
+            created_at: e.created_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit-log",
+    security(("jwt_token" = [])),
+    params(AuditLogQuery),
+    responses(
+        (status = 200, description = "Audit log, newest first", body = PaginatedResponse<AuditLogResponse>),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn list_audit_log(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Query(params): Query<AuditLogQuery>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ViewAuditLog).await?;
+
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(50).min(200);
+
+    let service = AuditLogService::new(db);
+    let (entries, total) = service
+        .list(params.actor_id, params.user_id, page, per_page)
+        .await?;
+    let items = entries.into_iter().map(AuditLogResponse::from).collect();
+
+    Ok(ApiResponse::ok(PaginatedResponse::new(
+        items, total, page, per_page,
+    )))
+}
+
 #[utoipa::path(
     delete,
     path = "/api/v1/admin/posts/{id}",
@@ -167,22 +531,28 @@ pub async fn update_user_role(
     params(("id" = i32, Path, description = "Post ID")),
     responses(
         (status = 200, description = "Post deleted by admin", body = String),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
         (status = 404, description = "Post not found", body = AppError),
     ),
     tag = "admin"
 )]
 pub async fn admin_delete_post(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(search): Extension<SearchIndex>,
+    cache: Option<Extension<CacheService>>,
     auth_user: AuthUser,
     Path(id): Path<i32>,
 ) -> AppResult<impl IntoResponse> {
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::DeleteAnyPost).await?;
 
-    let service = AdminService::new(db);
-    service.admin_delete_post(id).await?;
+    let service = AdminService::new(db.clone());
+    let post = service.admin_delete_post(id).await?;
+    search.refresh_post(&db, id).await;
+    if let Some(Extension(cache)) = cache {
+        invalidate_post_cache(&cache, post.id, post.forum_id).await;
+    }
 
-    Ok(ApiResponse::ok("Post deleted by admin"))
+    Ok(NoContent(ApiResponse::ok(t("post_deleted_by_admin"))))
 }
 
 #[utoipa::path(
@@ -192,7 +562,7 @@ pub async fn admin_delete_post(
     params(("id" = i32, Path, description = "Comment ID")),
     responses(
         (status = 200, description = "Comment deleted by admin", body = String),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
         (status = 404, description = "Comment not found", body = AppError),
     ),
     tag = "admin"
@@ -202,10 +572,352 @@ pub async fn admin_delete_comment(
     auth_user: AuthUser,
     Path(id): Path<i32>,
 ) -> AppResult<impl IntoResponse> {
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::DeleteAnyComment).await?;
 
     let service = AdminService::new(db);
     service.admin_delete_comment(id).await?;
 
-    Ok(ApiResponse::ok("Comment deleted by admin"))
+    Ok(NoContent(ApiResponse::ok(t("comment_deleted_by_admin"))))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReindexResponse {
+    /// Active search backend
+    pub backend: String,
+    /// Number of posts sent to the index (0 for Postgres, which needs no index)
+    pub indexed: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/search/reindex",
+    security(("jwt_token" = [])),
+    responses(
+        (status = 200, description = "Search index rebuilt", body = ReindexResponse),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 500, description = "Search backend unavailable", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn reindex_search(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(search): Extension<SearchIndex>,
+    auth_user: AuthUser,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageSearch).await?;
+
+    let indexed = search.reindex(&db).await?;
+    tracing::info!(backend = search.name(), indexed, "Search index rebuilt");
+
+    Ok(ApiResponse::ok(ReindexResponse {
+        backend: search.name().to_string(),
+        indexed,
+    }))
+}
+
+/// Result types staff search covers.
+const ADMIN_SEARCH_TYPES: [SearchType; 3] =
+    [SearchType::Posts, SearchType::Comments, SearchType::Users];
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct AdminSearchQuery {
+    /// Search query
+    pub q: String,
+    /// Only return one group: posts, comments, users
+    #[serde(rename = "type")]
+    #[param(rename = "type")]
+    pub search_type: Option<String>,
+    /// Also match hidden posts and comments and banned users
+    pub include_hidden: Option<bool>,
+    /// Maximum items per group (default 20, max 100)
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminPostHit {
+    #[serde(flatten)]
+    pub post: PostResponse,
+    /// Hidden from everyone but staff
+    pub is_hidden: bool,
+}
+
+impl From<PostModel> for AdminPostHit {
+    fn from(p: PostModel) -> Self {
+        Self {
+            is_hidden: p.is_hidden,
+            post: p.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminCommentHit {
+    #[serde(flatten)]
+    pub comment: CommentResponse,
+    /// Hidden from everyone but staff
+    pub is_hidden: bool,
+}
+
+impl From<CommentModel> for AdminCommentHit {
+    fn from(c: CommentModel) -> Self {
+        Self {
+            is_hidden: c.is_hidden,
+            comment: c.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminSearchResponse {
+    /// Matching posts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub posts: Option<SearchGroupResponse<AdminPostHit>>,
+    /// Matching comments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<SearchGroupResponse<AdminCommentHit>>,
+    /// Matching users
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub users: Option<SearchGroupResponse<AdminUserResponse>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/search",
+    security(("jwt_token" = [])),
+    params(AdminSearchQuery),
+    responses(
+        (status = 200, description = "Grouped search results, including hidden content when asked", body = AdminSearchResponse),
+        (status = 400, description = "Invalid query", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn search(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(search): Extension<SearchIndex>,
+    auth_user: AuthUser,
+    Query(params): Query<AdminSearchQuery>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::SearchHiddenContent).await?;
+
+    let q = params.q.trim();
+    if q.is_empty() || q.len() > 200 {
+        return Err(AppError::Validation(
+            "Search query must be 1-200 characters".to_string(),
+        ));
+    }
+    let only = match params.search_type.as_deref() {
+        Some(t) if !t.trim().is_empty() => Some(
+            SearchType::parse(t)
+                .filter(|t| ADMIN_SEARCH_TYPES.contains(t))
+                .ok_or_else(|| {
+                    AppError::Validation("type must be one of: posts, comments, users".to_string())
+                })?,
+        ),
+        _ => None,
+    };
+    let types = only
+        .as_ref()
+        .map_or(&ADMIN_SEARCH_TYPES[..], std::slice::from_ref);
+    let limit = params.limit.unwrap_or(20).clamp(1, 100);
+
+    let results = SearchService::new(db, search)
+        .search_all(q, types, limit, params.include_hidden.unwrap_or(false))
+        .await?;
+
+    Ok(ApiResponse::ok(AdminSearchResponse {
+        posts: results.posts.map(Into::into),
+        comments: results.comments.map(Into::into),
+        users: results.users.map(Into::into),
+    }))
+}
+
+const EMAIL_STATUSES: &[&str] = &["pending", "sending", "sent", "failed"];
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ListEmailsQuery {
+    /// `pending`, `sending`, `sent` or `failed` (default)
+    pub status: Option<String>,
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminEmailResponse {
+    /// Email ID
+    pub id: i32,
+    /// What the email is for, e.g. `verification` or `password_reset`
+    pub kind: String,
+    /// Recipient address
+    pub to_address: String,
+    /// Subject line
+    pub subject: String,
+    /// Delivery status
+    pub status: String,
+    /// Delivery attempts so far
+    pub attempts: i32,
+    /// Error from the latest failed attempt
+    pub last_error: Option<String>,
+    /// When the next attempt is due, while pending
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub next_attempt_at: chrono::NaiveDateTime,
+    /// When the email was delivered
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub sent_at: Option<chrono::NaiveDateTime>,
+    /// When the email was queued
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<EmailOutboxModel> for AdminEmailResponse {
+    fn from(e: EmailOutboxModel) -> Self {
+        Self {
+            id: e.id,
+            kind: e.kind,
+            to_address: e.to_address,
+            subject: e.subject,
+            status: e.status,
+            attempts: e.attempts,
+            last_error: e.last_error,
+            next_attempt_at: e.next_attempt_at,
+            sent_at: e.sent_at,
+            created_at: e.created_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/emails",
+    security(("jwt_token" = [])),
+    params(
+        ("status" = Option<String>, Query, description = "pending, sending, sent or failed (default)"),
+        ("page" = Option<u64>, Query, description = "Page number"),
+        ("per_page" = Option<u64>, Query, description = "Items per page"),
+    ),
+    responses(
+        (status = 200, description = "Outgoing emails", body = PaginatedResponse<AdminEmailResponse>),
+        (status = 400, description = "Invalid status", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn list_emails(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Query(params): Query<ListEmailsQuery>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageEmails).await?;
+
+    let status = params.status.as_deref().unwrap_or("failed");
+    if !EMAIL_STATUSES.contains(&status) {
+        return Err(AppError::Validation(format!(
+            "Invalid status. Must be one of: {}",
+            EMAIL_STATUSES.join(", ")
+        )));
+    }
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(20).min(100);
+
+    let service = AdminService::new(db);
+    let (emails, total) = service.list_emails(status, page, per_page).await?;
+    let items = emails.into_iter().map(AdminEmailResponse::from).collect();
+
+    Ok(ApiResponse::ok(PaginatedResponse::new(
+        items, total, page, per_page,
+    )))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/emails/{id}/retry",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Email ID")),
+    responses(
+        (status = 200, description = "Email queued again", body = AdminEmailResponse),
+        (status = 400, description = "Email has not failed", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Email not found", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn retry_email(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageEmails).await?;
+
+    let service = AdminService::new(db);
+    let email = service.retry_email(id).await?;
+    Ok(ApiResponse::ok(AdminEmailResponse::from(email)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/export/{resource}",
+    security(("jwt_token" = [])),
+    params(
+        ("resource" = String, Path, description = "`users`, `posts` or `reports`"),
+        ExportQuery,
+    ),
+    responses(
+        (status = 200, description = "Every row of the resource, oldest first, as a CSV or JSON download", content_type = "text/csv"),
+        (status = 400, description = "Unknown format", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Unknown resource", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn export(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(resource): Path<String>,
+    Query(params): Query<ExportQuery>,
+) -> AppResult<Response> {
+    let admin_id = require_permission(&auth_user, Permission::ExportData).await?;
+
+    let resource = ExportResource::parse(&resource).ok_or(AppError::NotFound)?;
+    let format = match params.format.as_deref() {
+        Some(name) => ExportFormat::parse(name).ok_or_else(|| {
+            AppError::Validation("Invalid format. Must be one of: csv, json".to_string())
+        })?,
+        None => ExportFormat::Csv,
+    };
+
+    AuditLogService::new(db.clone())
+        .record(AuditEntry {
+            actor_id: Some(admin_id),
+            action: "data_exported",
+            detail: Some(format!("{}.{}", resource.as_str(), format.as_str())),
+            ..Default::default()
+        })
+        .await;
+
+    let filename = format!(
+        "{}-{}.{}",
+        resource.as_str(),
+        chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+        format.as_str()
+    );
+    let body = ExportService::new(db)
+        .stream(resource, format)
+        .inspect(|chunk| {
+            if let Err(e) = chunk {
+                tracing::error!("Export failed part-way: {}", e);
+            }
+        });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response())
 }
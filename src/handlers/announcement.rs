@@ -0,0 +1,254 @@
+use crate::error::{AppError, AppResult};
+use crate::middleware::auth::{parse_user_id, require_permission};
+use crate::middleware::permission::Permission;
+use crate::middleware::AuthUser;
+use crate::models::AnnouncementModel;
+use crate::response::{ApiResponse, Created, NoContent, PaginatedResponse, PaginationQuery};
+use crate::services::announcement::{AnnouncementService, NewAnnouncement};
+use crate::services::email::EmailService;
+use crate::websocket::hub::NotificationHub;
+use axum::{extract::Path, extract::Query, response::IntoResponse, Extension, Json};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnnouncementResponse {
+    /// Announcement ID
+    pub id: i32,
+    /// Title, also the notification message
+    pub title: String,
+    /// Plain text body
+    pub body: String,
+    /// Forum whose members were targeted; null for all users
+    pub forum_id: Option<i32>,
+    /// Whether it was also emailed
+    pub send_email: bool,
+    /// Users notified when it was published
+    pub recipient_count: i32,
+    /// When it stops being listed as active; null for never
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    /// Creation timestamp
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+    /// Last update timestamp
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl From<AnnouncementModel> for AnnouncementResponse {
+    fn from(a: AnnouncementModel) -> Self {
+        Self {
+            id: a.id,
+            title: a.title,
+            body: a.body,
+            forum_id: a.forum_id,
+            send_email: a.send_email,
+            recipient_count: a.recipient_count,
+            expires_at: a.expires_at,
+            created_at: a.created_at,
+            updated_at: a.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateAnnouncementRequest {
+    /// Title (1-200 characters)
+    #[validate(length(min = 1, max = 200))]
+    pub title: String,
+    /// Plain text body (1-10000 characters)
+    #[validate(length(min = 1, max = 10000))]
+    pub body: String,
+    /// Only notify members of this forum (users who posted or commented
+    /// there); omit for all users
+    pub forum_id: Option<i32>,
+    /// Also email recipients with a verified address (default false)
+    pub send_email: Option<bool>,
+    /// RFC 3339 timestamp after which it is no longer active
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateAnnouncementRequest {
+    /// Title (1-200 characters)
+    #[validate(length(min = 1, max = 200))]
+    pub title: String,
+    /// Plain text body (1-10000 characters)
+    #[validate(length(min = 1, max = 10000))]
+    pub body: String,
+    /// RFC 3339 expiry; omit to never expire
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct ActiveAnnouncementsQuery {
+    /// Also include announcements for this forum's members
+    pub forum_id: Option<i32>,
+}
+
+fn parse_expires_at(value: Option<&str>) -> AppResult<Option<chrono::NaiveDateTime>> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|dt| Some(dt.naive_utc()))
+        .map_err(|_| AppError::Validation("expires_at must be an RFC 3339 timestamp".to_string()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/announcements/active",
+    params(ActiveAnnouncementsQuery),
+    responses(
+        (status = 200, description = "Unexpired announcements, newest first", body = Vec<AnnouncementResponse>),
+    ),
+    tag = "announcements"
+)]
+pub async fn active_announcements(
+    Extension(db): Extension<DatabaseConnection>,
+    Query(params): Query<ActiveAnnouncementsQuery>,
+) -> AppResult<impl IntoResponse> {
+    let service = AnnouncementService::new(db);
+    let items: Vec<AnnouncementResponse> = service
+        .active(params.forum_id)
+        .await?
+        .into_iter()
+        .map(AnnouncementResponse::from)
+        .collect();
+    Ok(ApiResponse::ok(items))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/announcements",
+    security(("jwt_token" = [])),
+    params(
+        ("page" = Option<u64>, Query, description = "Page number"),
+        ("per_page" = Option<u64>, Query, description = "Items per page"),
+    ),
+    responses(
+        (status = 200, description = "All announcements, newest first", body = PaginatedResponse<AnnouncementResponse>),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "announcements"
+)]
+pub async fn list_announcements(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Query(params): Query<PaginationQuery>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageAnnouncements).await?;
+
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(20).min(100);
+
+    let service = AnnouncementService::new(db);
+    let (announcements, total) = service.list(page, per_page).await?;
+    let items = announcements
+        .into_iter()
+        .map(AnnouncementResponse::from)
+        .collect();
+
+    Ok(ApiResponse::ok(PaginatedResponse::new(
+        items, total, page, per_page,
+    )))
+}
+
+/// Publishing notifies every recipient right away, and queues emails if
+/// `send_email` is set.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/announcements",
+    security(("jwt_token" = [])),
+    request_body = CreateAnnouncementRequest,
+    responses(
+        (status = 200, description = "Announcement published", body = AnnouncementResponse),
+        (status = 400, description = "Validation error", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "announcements"
+)]
+pub async fn create_announcement(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
+    Extension(email_service): Extension<EmailService>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateAnnouncementRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    require_permission(&auth_user, Permission::ManageAnnouncements).await?;
+    let user_id = parse_user_id(&auth_user)?;
+
+    let input = NewAnnouncement {
+        expires_at: parse_expires_at(payload.expires_at.as_deref())?,
+        title: payload.title,
+        body: payload.body,
+        forum_id: payload.forum_id,
+        send_email: payload.send_email.unwrap_or(false),
+    };
+    let service = AnnouncementService::new(db);
+    let announcement = service.create(user_id, input, hub, &email_service).await?;
+    Ok(Created(ApiResponse::ok(AnnouncementResponse::from(
+        announcement,
+    ))))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/announcements/{id}",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Announcement ID")),
+    request_body = UpdateAnnouncementRequest,
+    responses(
+        (status = 200, description = "Announcement updated", body = AnnouncementResponse),
+        (status = 400, description = "Validation error", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Announcement not found", body = AppError),
+    ),
+    tag = "announcements"
+)]
+pub async fn update_announcement(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<UpdateAnnouncementRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    require_permission(&auth_user, Permission::ManageAnnouncements).await?;
+
+    let expires_at = parse_expires_at(payload.expires_at.as_deref())?;
+    let service = AnnouncementService::new(db);
+    let announcement = service
+        .update(id, payload.title, payload.body, expires_at)
+        .await?;
+    Ok(ApiResponse::ok(AnnouncementResponse::from(announcement)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/announcements/{id}",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Announcement ID")),
+    responses(
+        (status = 200, description = "Announcement deleted", body = String),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Announcement not found", body = AppError),
+    ),
+    tag = "announcements"
+)]
+pub async fn delete_announcement(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageAnnouncements).await?;
+
+    let service = AnnouncementService::new(db);
+    service.delete(id).await?;
+    Ok(NoContent(ApiResponse::ok(
+        "Announcement deleted successfully",
+    )))
+}
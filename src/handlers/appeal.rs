@@ -0,0 +1,367 @@
+use crate::error::{AppError, AppResult};
+use crate::middleware::auth::{invalidate_cached_auth, parse_user_id, require_permission};
+use crate::middleware::permission::Permission;
+use crate::middleware::AuthUser;
+use crate::models::{AppealCommentModel, AppealModel, ModerationActionModel};
+use crate::response::{ApiResponse, Created, PaginatedResponse};
+use crate::services::appeal::{AppealDecision, AppealService};
+use crate::services::cache::CacheService;
+use crate::services::notification::NotificationService;
+use crate::websocket::hub::NotificationHub;
+use axum::{extract::Path, extract::Query, response::IntoResponse, Extension, Json};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AppealResponse {
+    /// Appeal ID
+    pub id: i32,
+    /// Appellant user ID
+    pub user_id: i32,
+    /// Warning or ban being appealed; null for a ban issued outside a report
+    pub moderation_action_id: Option<i32>,
+    /// Why the user thinks the action was wrong
+    pub reason: String,
+    /// `pending`, `accepted` or `declined`
+    pub status: String,
+    /// Reviewer who decided the appeal
+    pub decided_by: Option<i32>,
+    /// Decision timestamp
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub decided_at: Option<chrono::NaiveDateTime>,
+    /// Reviewer's explanation, shown to the appellant
+    pub decision_note: Option<String>,
+    /// Creation timestamp
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<AppealModel> for AppealResponse {
+    fn from(a: AppealModel) -> Self {
+        Self {
+            id: a.id,
+            user_id: a.user_id,
+            moderation_action_id: a.moderation_action_id,
+            reason: a.reason,
+            status: a.status,
+            decided_by: a.decided_by,
+            decided_at: a.decided_at,
+            decision_note: a.decision_note,
+            created_at: a.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModerationActionResponse {
+    /// Action ID
+    pub id: i32,
+    /// `warn_user`, `ban_user`, ...
+    pub action: String,
+    /// Moderator who took the action
+    pub moderator_id: Option<i32>,
+    /// Report the action resolved
+    pub report_id: Option<i32>,
+    /// Content the action concerned
+    pub target_type: Option<String>,
+    pub target_id: Option<i32>,
+    /// Moderator's note
+    pub note: Option<String>,
+    /// Creation timestamp
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<ModerationActionModel> for ModerationActionResponse {
+    fn from(a: ModerationActionModel) -> Self {
+        Self {
+            id: a.id,
+            action: a.action,
+            moderator_id: a.moderator_id,
+            report_id: a.report_id,
+            target_type: a.target_type,
+            target_id: a.target_id,
+            note: a.note,
+            created_at: a.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AppealCommentResponse {
+    /// Comment ID
+    pub id: i32,
+    /// Reviewer who wrote it
+    pub user_id: Option<i32>,
+    /// Comment text
+    pub body: String,
+    /// Creation timestamp
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<AppealCommentModel> for AppealCommentResponse {
+    fn from(c: AppealCommentModel) -> Self {
+        Self {
+            id: c.id,
+            user_id: c.user_id,
+            body: c.body,
+            created_at: c.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AppealDetailResponse {
+    pub appeal: AppealResponse,
+    /// The warning or ban being appealed
+    pub action: Option<ModerationActionResponse>,
+    /// Reviewers' comments, oldest first
+    pub comments: Vec<AppealCommentResponse>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateAppealRequest {
+    /// Warning or ban to appeal; omit to appeal a ban issued outside a report
+    pub moderation_action_id: Option<i32>,
+    /// Why the action was wrong (1-2000 characters)
+    #[validate(length(min = 1, max = 2000))]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ListAppealsQuery {
+    /// Filter by status
+    pub status: Option<String>,
+    /// Page number
+    pub page: Option<u64>,
+    /// Items per page
+    pub per_page: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct AppealCommentRequest {
+    /// Comment text (1-2000 characters)
+    #[validate(length(min = 1, max = 2000))]
+    pub body: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResolveAppealRequest {
+    /// `accept` or `decline`
+    pub decision: String,
+    /// Explanation sent to the appellant
+    #[validate(length(max = 1000))]
+    pub note: Option<String>,
+}
+
+/// Open to banned users, so they can contest their ban.
+#[utoipa::path(
+    post,
+    path = "/api/v1/appeals",
+    security(("jwt_token" = [])),
+    request_body = CreateAppealRequest,
+    responses(
+        (status = 200, description = "Appeal filed", body = AppealResponse),
+        (status = 400, description = "Validation error", body = AppError),
+        (status = 404, description = "No such action against you", body = AppError),
+        (status = 409, description = "Already appealed", body = AppError),
+    ),
+    tag = "appeals"
+)]
+pub async fn create_appeal(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateAppealRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    let user_id = parse_user_id(&auth_user)?;
+
+    let service = AppealService::new(db);
+    let appeal = service
+        .create(user_id, payload.moderation_action_id, payload.reason.trim())
+        .await?;
+    Ok(Created(ApiResponse::ok(AppealResponse::from(appeal))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/appeals",
+    security(("jwt_token" = [])),
+    responses(
+        (status = 200, description = "Your appeals, newest first", body = Vec<AppealResponse>),
+        (status = 401, description = "Unauthorized", body = AppError),
+    ),
+    tag = "appeals"
+)]
+pub async fn list_my_appeals(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+
+    let service = AppealService::new(db);
+    let items: Vec<AppealResponse> = service
+        .list_for_user(user_id)
+        .await?
+        .into_iter()
+        .map(AppealResponse::from)
+        .collect();
+    Ok(ApiResponse::ok(items))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/appeals",
+    security(("jwt_token" = [])),
+    params(
+        ("status" = Option<String>, Query, description = "Filter by status"),
+        ("page" = Option<u64>, Query, description = "Page number"),
+        ("per_page" = Option<u64>, Query, description = "Items per page"),
+    ),
+    responses(
+        (status = 200, description = "Appeals, newest first", body = PaginatedResponse<AppealResponse>),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "appeals"
+)]
+pub async fn list_appeals(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Query(params): Query<ListAppealsQuery>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ReviewAppeals).await?;
+
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(20).min(100);
+
+    let service = AppealService::new(db);
+    let (appeals, total) = service
+        .list(params.status.as_deref(), page, per_page)
+        .await?;
+    let items = appeals.into_iter().map(AppealResponse::from).collect();
+
+    Ok(ApiResponse::ok(PaginatedResponse::new(
+        items, total, page, per_page,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/appeals/{id}",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Appeal ID")),
+    responses(
+        (status = 200, description = "Appeal with the appealed action and comments", body = AppealDetailResponse),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Appeal not found", body = AppError),
+    ),
+    tag = "appeals"
+)]
+pub async fn get_appeal(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ReviewAppeals).await?;
+
+    let service = AppealService::new(db);
+    let (appeal, action, comments) = service.get(id).await?;
+    Ok(ApiResponse::ok(AppealDetailResponse {
+        appeal: AppealResponse::from(appeal),
+        action: action.map(ModerationActionResponse::from),
+        comments: comments
+            .into_iter()
+            .map(AppealCommentResponse::from)
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/appeals/{id}/comments",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Appeal ID")),
+    request_body = AppealCommentRequest,
+    responses(
+        (status = 200, description = "Comment added", body = AppealCommentResponse),
+        (status = 400, description = "Validation error", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Appeal not found", body = AppError),
+    ),
+    tag = "appeals"
+)]
+pub async fn add_appeal_comment(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<AppealCommentRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    let user_id = require_permission(&auth_user, Permission::ReviewAppeals).await?;
+
+    let service = AppealService::new(db);
+    let comment = service
+        .add_comment(id, user_id, payload.body.trim())
+        .await?;
+    Ok(ApiResponse::ok(AppealCommentResponse::from(comment)))
+}
+
+/// Accepting lifts the appellant's ban. Either way the appellant is notified.
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/appeals/{id}/resolve",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Appeal ID")),
+    request_body = ResolveAppealRequest,
+    responses(
+        (status = 200, description = "Appeal decided", body = AppealResponse),
+        (status = 400, description = "Validation error or already decided", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Appeal not found", body = AppError),
+        (status = 409, description = "Claimed by another moderator", body = AppError),
+    ),
+    tag = "appeals"
+)]
+pub async fn resolve_appeal(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<ResolveAppealRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    let reviewer_id = require_permission(&auth_user, Permission::ReviewAppeals).await?;
+    let decision = AppealDecision::parse(&payload.decision)
+        .ok_or_else(|| AppError::Validation("decision must be accept or decline".to_string()))?;
+
+    let service = AppealService::new(db.clone());
+    let (appeal, unbanned) = service
+        .decide(id, reviewer_id, decision, payload.note.as_deref())
+        .await?;
+    if unbanned {
+        invalidate_cached_auth(cache.as_ref().map(|Extension(c)| c), appeal.user_id).await;
+    }
+
+    let message = match (decision, appeal.decision_note.as_deref()) {
+        (AppealDecision::Accept, None) => "Your appeal was accepted".to_string(),
+        (AppealDecision::Decline, None) => "Your appeal was declined".to_string(),
+        (AppealDecision::Accept, Some(note)) => format!("Your appeal was accepted: {}", note),
+        (AppealDecision::Decline, Some(note)) => format!("Your appeal was declined: {}", note),
+    };
+    let _ = NotificationService::new(db, hub)
+        .notify(
+            appeal.user_id,
+            reviewer_id,
+            "appeal_decided",
+            "appeal",
+            appeal.id,
+            &message,
+        )
+        .await;
+
+    Ok(ApiResponse::ok(AppealResponse::from(appeal)))
+}
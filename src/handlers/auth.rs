@@ -2,9 +2,17 @@ use crate::error::{AppError, AppResult};
 use crate::middleware::auth::parse_user_id;
 use crate::middleware::AuthUser;
 use crate::models::UserModel;
-use crate::response::ApiResponse;
+use crate::response::{ApiResponse, Created};
+use crate::services::account_deletion::AccountDeletionService;
 use crate::services::auth::AuthService;
+use crate::services::cache::CacheService;
+use crate::services::captcha::{
+    clear_login_failures, record_login_failure, require_captcha, CaptchaAction, CaptchaConfig,
+};
 use crate::services::email::EmailService;
+use crate::services::email_template::{Locale, SUPPORTED_LOCALES};
+use crate::utils::i18n::t;
+use crate::utils::pow::{require_pow, PowAction, PowConfig};
 use anyhow::anyhow;
 use axum::{
     http::{header, HeaderMap, HeaderValue},
@@ -27,6 +35,17 @@ pub struct RegisterRequest {
     /// Password (min 8 characters)
     #[validate(length(min = 8))]
     pub password: String,
+    /// Language for emails (`en` or `zh`); defaults to the `Accept-Language`
+    /// header, then `en`
+    pub locale: Option<String>,
+    /// Required while registration is invite-only
+    pub invite_code: Option<String>,
+    /// PoW token for `register`; required while registration needs PoW
+    pub pow_token: Option<String>,
+    pub pow_nonce: Option<String>,
+    /// hCaptcha/Turnstile response; required while registration needs a
+    /// CAPTCHA
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -35,6 +54,9 @@ pub struct LoginRequest {
     pub username: String,
     /// User password
     pub password: String,
+    /// hCaptcha/Turnstile response; required after repeated failed logins
+    /// when login CAPTCHA is on
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -47,6 +69,8 @@ pub struct AuthResponse {
     pub user_id: i32,
     /// Username
     pub username: String,
+    /// Name shown instead of the username; `null` if not set
+    pub display_name: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -69,6 +93,8 @@ pub struct UserResponse {
     pub id: i32,
     /// Username
     pub username: String,
+    /// Name shown instead of the username; `null` if not set
+    pub display_name: Option<String>,
     /// Email address
     pub email: String,
     /// Avatar URL
@@ -79,6 +105,16 @@ pub struct UserResponse {
     pub karma: i32,
     /// User role (user, admin, moderator)
     pub role: String,
+    /// Whether commenting on a thread starts watching it
+    pub auto_watch: bool,
+    /// Language for emails
+    pub locale: String,
+    /// Email digest frequency: `off`, `daily` or `weekly`
+    pub digest_frequency: String,
+    /// Whether follows need the user's approval
+    pub is_private: bool,
+    /// Whether others see when the user is online
+    pub show_presence: bool,
 }
 
 impl From<UserModel> for UserResponse {
@@ -86,11 +122,17 @@ impl From<UserModel> for UserResponse {
         Self {
             id: user.id,
             username: user.username,
+            display_name: user.display_name,
             email: user.email,
             avatar_url: user.avatar_url,
             bio: user.bio,
             karma: user.karma,
             role: user.role,
+            auto_watch: user.auto_watch,
+            locale: user.locale,
+            digest_frequency: user.digest_frequency,
+            is_private: user.is_private,
+            show_presence: user.show_presence,
         }
     }
 }
@@ -101,7 +143,8 @@ impl From<UserModel> for UserResponse {
     request_body = RegisterRequest,
     responses(
         (status = 200, description = "User registered successfully", body = RegisterResponse),
-        (status = 400, description = "Validation error", body = AppError),
+        (status = 400, description = "Validation error, missing or invalid invite code, or email domain not allowed", body = AppError),
+        (status = 403, description = "Registration is closed", body = AppError),
         (status = 409, description = "Username or email already exists", body = AppError),
     ),
     tag = "auth"
@@ -109,12 +152,35 @@ impl From<UserModel> for UserResponse {
 pub async fn register(
     Extension(db): Extension<DatabaseConnection>,
     Extension(email_service): Extension<EmailService>,
+    headers: HeaderMap,
     Json(payload): Json<RegisterRequest>,
 ) -> AppResult<impl IntoResponse> {
     // Validate input
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(format!("Validation error: {e}")))?;
+    payload.validate()?;
+    require_pow(
+        &PowConfig::from_env()?,
+        PowAction::Register,
+        "",
+        0,
+        0,
+        payload.pow_token.as_deref(),
+        payload.pow_nonce.as_deref(),
+    )?;
+    require_captcha(
+        &CaptchaConfig::from_env(),
+        CaptchaAction::Register,
+        payload.captcha_token.as_deref(),
+    )
+    .await?;
+
+    let locale = match payload.locale.as_deref() {
+        Some(tag) => parse_locale(tag)?,
+        None => headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Locale::from_accept_language)
+            .unwrap_or(Locale::En),
+    };
 
     let service = AuthService::new(db);
     let (user, access_token, refresh_token) = service
@@ -122,6 +188,8 @@ pub async fn register(
             &payload.username,
             &payload.email,
             &payload.password,
+            locale.as_str(),
+            payload.invite_code.as_deref(),
             &email_service,
         )
         .await?;
@@ -143,7 +211,7 @@ pub async fn register(
 
     let mut http_response = ApiResponse::ok(response).into_response();
     set_auth_cookies(&mut http_response, &access_token, &refresh_token)?;
-    Ok(http_response)
+    Ok(Created(http_response))
 }
 
 #[utoipa::path(
@@ -161,15 +229,34 @@ pub async fn login(
     Extension(db): Extension<DatabaseConnection>,
     Json(payload): Json<LoginRequest>,
 ) -> AppResult<impl IntoResponse> {
+    let captcha = CaptchaConfig::from_env();
+    if captcha.login_needs_captcha(&payload.username) {
+        require_captcha(
+            &captcha,
+            CaptchaAction::Login,
+            payload.captcha_token.as_deref(),
+        )
+        .await?;
+    }
+
     let service = AuthService::new(db);
     let (user, access_token, refresh_token) =
-        service.login(&payload.username, &payload.password).await?;
+        match service.login(&payload.username, &payload.password).await {
+            Ok(session) => session,
+            Err(AppError::Unauthorized) => {
+                record_login_failure(&captcha, &payload.username);
+                return Err(AppError::Unauthorized);
+            }
+            Err(e) => return Err(e),
+        };
+    clear_login_failures(&payload.username);
 
     let response = AuthResponse {
         token: access_token.clone(),
         refresh_token: refresh_token.clone(),
         user_id: user.id,
         username: user.username,
+        display_name: user.display_name,
     };
 
     let mut http_response = ApiResponse::ok(response).into_response();
@@ -222,21 +309,20 @@ pub struct ChangePasswordRequest {
 )]
 pub async fn change_password(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
     auth_user: AuthUser,
     Json(payload): Json<ChangePasswordRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
     let user_id = parse_user_id(&auth_user)?;
 
-    let service = AuthService::new(db);
+    let service = AuthService::new(db).with_cache(cache.map(|c| c.0));
     service
         .change_password(user_id, &payload.current_password, &payload.new_password)
         .await?;
 
-    Ok(ApiResponse::ok("Password changed successfully"))
+    Ok(ApiResponse::ok(t("password_changed")))
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -261,7 +347,7 @@ pub async fn verify_email(
 ) -> AppResult<impl IntoResponse> {
     let service = AuthService::new(db);
     service.verify_email(&payload.token).await?;
-    Ok(ApiResponse::ok("Email verified successfully"))
+    Ok(ApiResponse::ok(t("email_verified")))
 }
 
 #[utoipa::path(
@@ -310,9 +396,7 @@ pub async fn forgot_password(
     Extension(email_service): Extension<EmailService>,
     Json(payload): Json<ForgotPasswordRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
     let service = AuthService::new(db);
     service
@@ -347,13 +431,12 @@ pub struct ResetPasswordRequest {
 )]
 pub async fn reset_password(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
     Json(payload): Json<ResetPasswordRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
-    let service = AuthService::new(db);
+    let service = AuthService::new(db).with_cache(cache.map(|c| c.0));
     service
         .reset_password(&payload.token, &payload.new_password)
         .await?;
@@ -458,7 +541,84 @@ pub async fn logout(
         let _ = service.revoke_refresh_token(&token).await;
     }
 
-    let mut response = ApiResponse::ok("Logout successful").into_response();
+    let mut response = ApiResponse::ok(t("logged_out")).into_response();
+    clear_auth_cookies(&mut response)?;
+    Ok(response)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeactivateRequest {
+    /// Current password
+    pub password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/deactivate",
+    security(("jwt_token" = [])),
+    request_body = DeactivateRequest,
+    responses(
+        (status = 200, description = "Account deactivated and logged out everywhere; logging in reactivates it", body = String),
+        (status = 400, description = "Incorrect password", body = AppError),
+        (status = 401, description = "Unauthorized", body = AppError),
+    ),
+    tag = "auth"
+)]
+pub async fn deactivate(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+    Json(payload): Json<DeactivateRequest>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    AuthService::new(db)
+        .with_cache(cache.map(|c| c.0))
+        .deactivate(user_id, &payload.password)
+        .await?;
+
+    let mut response = ApiResponse::ok(t("account_deactivated")).into_response();
+    clear_auth_cookies(&mut response)?;
+    Ok(response)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeleteAccountRequest {
+    /// Current password
+    pub password: String,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/auth/account",
+    security(("jwt_token" = [])),
+    request_body = DeleteAccountRequest,
+    responses(
+        (status = 200, description = "Account deleted; posts and comments are kept under `[deleted]` or deleted, per `DELETED_USER_CONTENT`", body = String),
+        (status = 400, description = "Incorrect password", body = AppError),
+        (status = 401, description = "Unauthorized", body = AppError),
+    ),
+    tag = "auth"
+)]
+pub async fn delete_account(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+    Json(payload): Json<DeleteAccountRequest>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let cache = cache.map(|c| c.0);
+    AuthService::new(db.clone())
+        .check_password(user_id, &payload.password)
+        .await?;
+    AccountDeletionService::new(db)
+        .with_cache(cache)
+        .delete(
+            user_id,
+            crate::config::auth::AuthConfig::from_env().deleted_user_content,
+        )
+        .await?;
+
+    let mut response = ApiResponse::ok(t("account_deleted")).into_response();
     clear_auth_cookies(&mut response)?;
     Ok(response)
 }
@@ -503,3 +663,13 @@ fn append_set_cookie(response: &mut Response, cookie_value: &str) -> AppResult<(
     response.headers_mut().append(header::SET_COOKIE, value);
     Ok(())
 }
+
+/// Parse a locale chosen by the user, rejecting unsupported ones.
+pub fn parse_locale(tag: &str) -> AppResult<Locale> {
+    Locale::parse(tag).ok_or_else(|| {
+        AppError::Validation(format!(
+            "Unsupported locale. Must be one of: {}",
+            SUPPORTED_LOCALES.join(", ")
+        ))
+    })
+}
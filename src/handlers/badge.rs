@@ -0,0 +1,214 @@
+use crate::error::{AppError, AppResult};
+use crate::middleware::auth::require_permission;
+use crate::middleware::permission::Permission;
+use crate::middleware::AuthUser;
+use crate::models::BadgeModel;
+use crate::response::{ApiResponse, Created, NoContent};
+use crate::services::badge::{BadgeService, HeldBadge};
+use crate::utils::i18n::t;
+use crate::websocket::hub::NotificationHub;
+use axum::{extract::Path, response::IntoResponse, Extension, Json};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BadgeResponse {
+    /// Badge ID
+    pub id: i32,
+    /// Unique slug
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    pub icon_url: Option<String>,
+    /// Awarded automatically rather than by admins
+    pub is_builtin: bool,
+}
+
+impl From<BadgeModel> for BadgeResponse {
+    fn from(b: BadgeModel) -> Self {
+        Self {
+            id: b.id,
+            slug: b.slug,
+            name: b.name,
+            description: b.description,
+            icon_url: b.icon_url,
+            is_builtin: b.rule.is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserBadgeResponse {
+    #[serde(flatten)]
+    pub badge: BadgeResponse,
+    /// When the user earned or was given the badge
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub awarded_at: chrono::NaiveDateTime,
+}
+
+impl From<HeldBadge> for UserBadgeResponse {
+    fn from(h: HeldBadge) -> Self {
+        Self {
+            badge: BadgeResponse::from(h.badge),
+            awarded_at: h.awarded_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateBadgeRequest {
+    /// Unique slug (1-50 characters)
+    #[validate(length(min = 1, max = 50))]
+    pub slug: String,
+    /// Name (1-100 characters)
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    /// What the badge is for (max 500 characters)
+    #[validate(length(max = 500))]
+    #[serde(default)]
+    pub description: String,
+    /// Icon URL (max 500 characters)
+    #[validate(length(max = 500))]
+    pub icon_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AwardBadgeRequest {
+    /// Custom badge to award
+    pub badge_id: i32,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/badges",
+    responses(
+        (status = 200, description = "All badges, built-in first", body = Vec<BadgeResponse>),
+    ),
+    tag = "badges"
+)]
+pub async fn list_badges(
+    Extension(db): Extension<DatabaseConnection>,
+) -> AppResult<impl IntoResponse> {
+    let items: Vec<BadgeResponse> = BadgeService::new(db)
+        .list()
+        .await?
+        .into_iter()
+        .map(BadgeResponse::from)
+        .collect();
+    Ok(ApiResponse::ok(items))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/badges",
+    security(("jwt_token" = [])),
+    request_body = CreateBadgeRequest,
+    responses(
+        (status = 200, description = "Custom badge created", body = BadgeResponse),
+        (status = 400, description = "Validation error", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 409, description = "Slug already taken", body = AppError),
+    ),
+    tag = "badges"
+)]
+pub async fn create_badge(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateBadgeRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    require_permission(&auth_user, Permission::ManageBadges).await?;
+
+    let badge = BadgeService::new(db)
+        .create_custom(
+            payload.slug.trim(),
+            payload.name.trim(),
+            payload.description.trim(),
+            payload.icon_url,
+        )
+        .await?;
+    Ok(Created(ApiResponse::ok(BadgeResponse::from(badge))))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/badges/{id}",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Badge ID")),
+    responses(
+        (status = 200, description = "Custom badge deleted and taken from its holders", body = String),
+        (status = 400, description = "Built-in badges can't be deleted", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Badge not found", body = AppError),
+    ),
+    tag = "badges"
+)]
+pub async fn delete_badge(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageBadges).await?;
+    BadgeService::new(db).delete_custom(id).await?;
+    Ok(NoContent(ApiResponse::ok(t("badge_deleted"))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/{id}/badges",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "User ID")),
+    request_body = AwardBadgeRequest,
+    responses(
+        (status = 200, description = "Badge awarded; the user is notified unless they already held it", body = String),
+        (status = 400, description = "Built-in badges can't be awarded by hand", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "User or badge not found", body = AppError),
+    ),
+    tag = "badges"
+)]
+pub async fn award_badge(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<AwardBadgeRequest>,
+) -> AppResult<impl IntoResponse> {
+    let admin_id = require_permission(&auth_user, Permission::ManageBadges).await?;
+    let awarded = BadgeService::new(db)
+        .award(hub, id, payload.badge_id, admin_id)
+        .await?;
+    let message = match awarded {
+        Some(_) => t("badge_awarded"),
+        None => t("badge_already_held"),
+    };
+    Ok(ApiResponse::ok(message))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/users/{id}/badges/{badge_id}",
+    security(("jwt_token" = [])),
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        ("badge_id" = i32, Path, description = "Badge ID"),
+    ),
+    responses(
+        (status = 200, description = "Badge revoked", body = String),
+        (status = 400, description = "Built-in badges can't be revoked", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "The user doesn't hold the badge", body = AppError),
+    ),
+    tag = "badges"
+)]
+pub async fn revoke_badge(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path((id, badge_id)): Path<(i32, i32)>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageBadges).await?;
+    BadgeService::new(db).revoke(id, badge_id).await?;
+    Ok(NoContent(ApiResponse::ok(t("badge_revoked"))))
+}
@@ -1,5 +1,7 @@
 use crate::error::AppResult;
-use crate::handlers::post::PostResponse;
+use crate::handlers::post::{
+    attach_attachments, attach_crosspost_sources, attach_flairs, attach_link_previews, PostResponse,
+};
 use crate::middleware::auth::parse_user_id;
 use crate::middleware::AuthUser;
 use crate::response::{ApiResponse, PaginatedResponse, PaginationQuery};
@@ -104,9 +106,13 @@ pub async fn list_bookmarks(
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20).min(100);
 
-    let service = BookmarkService::new(db);
+    let service = BookmarkService::new(db.clone());
     let (posts, total) = service.list_user_bookmarks(user_id, page, per_page).await?;
-    let items = posts.into_iter().map(PostResponse::from).collect();
+    let mut items: Vec<PostResponse> = posts.into_iter().map(PostResponse::from).collect();
+    attach_link_previews(&db, &mut items).await?;
+    attach_flairs(&db, &mut items).await?;
+    attach_attachments(&db, &mut items).await?;
+    attach_crosspost_sources(&db, &mut items).await?;
     Ok(ApiResponse::ok(PaginatedResponse::new(
         items, total, page, per_page,
     )))
@@ -1,11 +1,16 @@
 use crate::error::{AppError, AppResult};
 use crate::middleware::auth::parse_user_id;
+use crate::middleware::auth::require_permission;
+use crate::middleware::permission::Permission;
 use crate::middleware::AuthUser;
-use crate::models::CommentModel;
-use crate::response::ApiResponse;
+use crate::models::{CommentModel, CommentRevisionModel};
+use crate::response::{ApiResponse, Created, NoContent};
 use crate::services::comment::CommentService;
 use crate::services::notification::NotificationService;
 use crate::services::post::PostService;
+use crate::services::watch::WatchService;
+use crate::utils::i18n::t;
+use crate::utils::markdown::render_quote;
 use crate::utils::render_markdown;
 use crate::websocket::hub::NotificationHub;
 use axum::{extract::Path, response::IntoResponse, Extension, Json};
@@ -24,6 +29,8 @@ pub struct CreateCommentRequest {
     /// Comment content (Markdown supported)
     #[validate(length(min = 1))]
     pub content: String,
+    /// Comment on the same post to quote above this one
+    pub quoted_comment_id: Option<i32>,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -52,14 +59,52 @@ pub struct CommentResponse {
     /// Downvote count
     pub downvotes: i32,
     /// Creation timestamp
-    pub created_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
     /// Last update timestamp
-    pub updated_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub updated_at: chrono::NaiveDateTime,
+    /// Set when the content was edited after posting
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub edited_at: Option<chrono::NaiveDateTime>,
+    /// The comment this one quotes, also rendered at the top of `content_html`
+    pub quote: Option<QuoteResponse>,
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct QuoteResponse {
+    /// Quoted comment ID, null once that comment is deleted
+    pub comment_id: Option<i32>,
+    /// Plain-text excerpt of the quoted comment, as it was when quoted
+    pub excerpt: String,
+}
+
+impl QuoteResponse {
+    fn from_comment(c: &CommentModel) -> Option<Self> {
+        c.quote_excerpt.as_ref().map(|excerpt| Self {
+            comment_id: c.quoted_comment_id,
+            excerpt: excerpt.clone(),
+        })
+    }
+}
+
+/// The comment's Markdown rendered, after the comment it quotes.
+fn comment_html(c: &CommentModel) -> String {
+    let html = render_markdown(&c.content);
+    match &c.quote_excerpt {
+        Some(excerpt) => format!(
+            "{}\n{}",
+            render_quote(excerpt, c.post_id, c.quoted_comment_id),
+            html
+        ),
+        None => html,
+    }
 }
 
 impl From<CommentModel> for CommentResponse {
     fn from(c: CommentModel) -> Self {
-        let content_html = render_markdown(&c.content);
+        let content_html = comment_html(&c);
+        let quote = QuoteResponse::from_comment(&c);
         Self {
             id: c.id,
             post_id: c.post_id,
@@ -69,8 +114,10 @@ impl From<CommentModel> for CommentResponse {
             content_html,
             upvotes: c.upvotes,
             downvotes: c.downvotes,
-            created_at: c.created_at.to_string(),
-            updated_at: c.updated_at.to_string(),
+            created_at: c.created_at,
+            updated_at: c.updated_at,
+            edited_at: c.edited_at,
+            quote,
         }
     }
 }
@@ -85,8 +132,15 @@ pub struct CommentTreeNode {
     pub content_html: String,
     pub upvotes: i32,
     pub downvotes: i32,
-    pub created_at: String,
-    pub updated_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub updated_at: chrono::NaiveDateTime,
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub edited_at: Option<chrono::NaiveDateTime>,
+    pub quote: Option<QuoteResponse>,
+    /// Nesting level, 0 for top-level comments
+    pub depth: u32,
     pub children: Vec<CommentTreeNode>,
 }
 
@@ -112,6 +166,12 @@ impl utoipa::PartialSchema for CommentTreeNode {
                 .property("downvotes", i32::schema())
                 .property("created_at", String::schema())
                 .property("updated_at", String::schema())
+                .property("edited_at", Option::<String>::schema())
+                .property(
+                    "quote",
+                    utoipa::openapi::Ref::from_schema_name("QuoteResponse"),
+                )
+                .property("depth", u32::schema())
                 .property(
                     "children",
                     utoipa::openapi::schema::ArrayBuilder::new()
@@ -127,6 +187,7 @@ impl utoipa::PartialSchema for CommentTreeNode {
                 .required("downvotes")
                 .required("created_at")
                 .required("updated_at")
+                .required("depth")
                 .required("children")
                 .description(Some("Comment node in tree structure with nested children"))
                 .build(),
@@ -136,7 +197,8 @@ impl utoipa::PartialSchema for CommentTreeNode {
 
 impl From<CommentModel> for CommentTreeNode {
     fn from(c: CommentModel) -> Self {
-        let content_html = render_markdown(&c.content);
+        let content_html = comment_html(&c);
+        let quote = QuoteResponse::from_comment(&c);
         Self {
             id: c.id,
             post_id: c.post_id,
@@ -146,8 +208,11 @@ impl From<CommentModel> for CommentTreeNode {
             content_html,
             upvotes: c.upvotes,
             downvotes: c.downvotes,
-            created_at: c.created_at.to_string(),
-            updated_at: c.updated_at.to_string(),
+            created_at: c.created_at,
+            updated_at: c.updated_at,
+            edited_at: c.edited_at,
+            quote,
+            depth: 0,
             children: Vec::new(),
         }
     }
@@ -170,14 +235,16 @@ fn build_comment_tree(comments: Vec<CommentModel>) -> Vec<CommentTreeNode> {
 
     fn attach_children(
         node_id: i32,
+        depth: u32,
         nodes: &mut HashMap<i32, CommentTreeNode>,
         children_map: &HashMap<Option<i32>, Vec<i32>>,
     ) -> Option<CommentTreeNode> {
         let mut node = nodes.remove(&node_id)?;
+        node.depth = depth;
         if let Some(child_ids) = children_map.get(&Some(node_id)) {
             for &child_id in child_ids {
                 if nodes.contains_key(&child_id) {
-                    if let Some(child) = attach_children(child_id, nodes, children_map) {
+                    if let Some(child) = attach_children(child_id, depth + 1, nodes, children_map) {
                         node.children.push(child);
                     }
                 }
@@ -189,16 +256,27 @@ fn build_comment_tree(comments: Vec<CommentModel>) -> Vec<CommentTreeNode> {
     let root_ids = children_map.get(&None).cloned().unwrap_or_default();
     root_ids
         .into_iter()
-        .filter_map(|id| attach_children(id, &mut nodes, &children_map))
+        .filter_map(|id| attach_children(id, 0, &mut nodes, &children_map))
         .collect()
 }
 
+/// Put the post's pinned comment ahead of the other top-level comments.
+fn move_pinned_first(roots: &mut Vec<CommentTreeNode>, pinned: Option<i32>) {
+    let Some(pinned) = pinned else {
+        return;
+    };
+    if let Some(pos) = roots.iter().position(|n| n.id == pinned) {
+        let node = roots.remove(pos);
+        roots.insert(0, node);
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/api/v1/posts/{post_id}/comments",
     params(("post_id" = i32, Path, description = "Post ID")),
     responses(
-        (status = 200, description = "Comment tree", body = Vec<CommentTreeNode>),
+        (status = 200, description = "Comment tree, pinned comment first", body = Vec<CommentTreeNode>),
     ),
     tag = "comments"
 )]
@@ -206,12 +284,67 @@ pub async fn list_comments(
     Extension(db): Extension<DatabaseConnection>,
     Path(post_id): Path<i32>,
 ) -> AppResult<impl IntoResponse> {
+    let pinned = PostService::new(db.clone())
+        .get_by_id(post_id)
+        .await
+        .ok()
+        .and_then(|p| p.pinned_comment_id);
+
     let service = CommentService::new(db);
     let comments = service.list_by_post(post_id).await?;
-    let tree = build_comment_tree(comments);
+    let mut tree = build_comment_tree(comments);
+    move_pinned_first(&mut tree, pinned);
     Ok(ApiResponse::ok(tree))
 }
 
+/// Number of direct replies included with a single comment.
+const CONTEXT_CHILDREN_LIMIT: u64 = 5;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommentContextResponse {
+    /// The requested comment
+    pub comment: CommentResponse,
+    /// Ancestors from the root comment down to the direct parent
+    pub ancestors: Vec<CommentResponse>,
+    /// First few direct replies, oldest first
+    pub children: Vec<CommentResponse>,
+    /// Total number of direct replies
+    pub children_total: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/comments/{id}",
+    params(("id" = i32, Path, description = "Comment ID")),
+    responses(
+        (status = 200, description = "Comment with surrounding thread", body = CommentContextResponse),
+        (status = 404, description = "Comment not found", body = AppError),
+    ),
+    tag = "comments"
+)]
+pub async fn get_comment(
+    Extension(db): Extension<DatabaseConnection>,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let service = CommentService::new(db);
+    let ctx = service.get_with_context(id, CONTEXT_CHILDREN_LIMIT).await?;
+
+    Ok(ApiResponse::ok(CommentContextResponse {
+        comment: CommentResponse::from(ctx.comment),
+        ancestors: ctx
+            .ancestors
+            .into_iter()
+            .map(CommentResponse::from)
+            .collect(),
+        children: ctx
+            .children
+            .into_iter()
+            .map(CommentResponse::from)
+            .collect(),
+        children_total: ctx.children_total,
+    }))
+}
+
 #[utoipa::path(
     post,
     path = "/api/v1/comments",
@@ -219,8 +352,9 @@ pub async fn list_comments(
     request_body = CreateCommentRequest,
     responses(
         (status = 200, description = "Comment created", body = CommentResponse),
-        (status = 400, description = "Validation error", body = AppError),
+        (status = 400, description = "Validation error, or the post is locked or closed by its author", body = AppError),
         (status = 401, description = "Unauthorized", body = AppError),
+        (status = 404, description = "Post not found", body = AppError),
     ),
     tag = "comments"
 )]
@@ -230,9 +364,7 @@ pub async fn create_comment(
     auth_user: AuthUser,
     Json(payload): Json<CreateCommentRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
     let user_id = parse_user_id(&auth_user)?;
 
@@ -243,15 +375,21 @@ pub async fn create_comment(
             user_id,
             payload.parent_id,
             &payload.content,
+            payload.quoted_comment_id,
         )
         .await?;
 
+    // Live comment counts for the thread's watchers
+    hub.post_changed(payload.post_id, 0);
+
     // Fire notifications (best-effort, don't fail the request)
     let notif_service = NotificationService::new(db.clone(), hub);
-    let post_service = PostService::new(db);
+    let post_service = PostService::new(db.clone());
 
     // Notify post author
+    let mut post_author = None;
     if let Ok(post) = post_service.get_by_id(payload.post_id).await {
+        post_author = Some(post.user_id);
         let _ = notif_service
             .notify(
                 post.user_id,
@@ -265,8 +403,10 @@ pub async fn create_comment(
     }
 
     // Notify parent comment author (if replying)
+    let mut parent_author = None;
     if let Some(parent_id) = payload.parent_id {
         if let Ok(parent) = comment_service.get_by_id(parent_id).await {
+            parent_author = Some(parent.user_id);
             let _ = notif_service
                 .notify(
                     parent.user_id,
@@ -280,7 +420,35 @@ pub async fn create_comment(
         }
     }
 
-    Ok(ApiResponse::ok(CommentResponse::from(comment)))
+    // Notify watchers not already told above, then watch the thread ourselves
+    let watch_service = WatchService::new(db);
+    if let Ok(watchers) = watch_service.watchers(payload.post_id).await {
+        for watcher in watchers {
+            if Some(watcher) == post_author || Some(watcher) == parent_author {
+                continue;
+            }
+            let _ = notif_service
+                .notify(
+                    watcher,
+                    user_id,
+                    "comment_on_watched_post",
+                    "post",
+                    payload.post_id,
+                    "New comment on a thread you watch",
+                )
+                .await;
+        }
+    }
+    if let Err(e) = watch_service.auto_watch(user_id, payload.post_id).await {
+        tracing::warn!(
+            user_id,
+            post_id = payload.post_id,
+            "Failed to auto-watch post: {}",
+            e
+        );
+    }
+
+    Ok(Created(ApiResponse::ok(CommentResponse::from(comment))))
 }
 
 #[utoipa::path(
@@ -303,9 +471,7 @@ pub async fn update_comment(
     Path(id): Path<i32>,
     Json(payload): Json<UpdateCommentRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
     let user_id = parse_user_id(&auth_user)?;
 
@@ -315,6 +481,63 @@ pub async fn update_comment(
     Ok(ApiResponse::ok(CommentResponse::from(comment)))
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommentRevisionResponse {
+    /// Revision ID
+    pub id: i32,
+    /// Comment ID
+    pub comment_id: i32,
+    /// User who made the edit
+    pub editor_id: i32,
+    /// Comment content before the edit (Markdown)
+    pub content: String,
+    /// When the edit was made
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<CommentRevisionModel> for CommentRevisionResponse {
+    fn from(r: CommentRevisionModel) -> Self {
+        Self {
+            id: r.id,
+            comment_id: r.comment_id,
+            editor_id: r.editor_id,
+            content: r.content,
+            created_at: r.created_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/comments/{id}/revisions",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Comment ID")),
+    responses(
+        (status = 200, description = "Previous versions, newest first", body = Vec<CommentRevisionResponse>),
+        (status = 401, description = "Unauthorized", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Comment not found", body = AppError),
+    ),
+    tag = "comments"
+)]
+pub async fn list_comment_revisions(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ViewCommentRevisions).await?;
+
+    let service = CommentService::new(db);
+    let revisions = service.list_revisions(id).await?;
+    let items: Vec<CommentRevisionResponse> = revisions
+        .into_iter()
+        .map(CommentRevisionResponse::from)
+        .collect();
+
+    Ok(ApiResponse::ok(items))
+}
+
 #[utoipa::path(
     delete,
     path = "/api/v1/comments/{id}",
@@ -329,19 +552,21 @@ pub async fn update_comment(
 )]
 pub async fn delete_comment(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
     auth_user: AuthUser,
     Path(id): Path<i32>,
 ) -> AppResult<impl IntoResponse> {
     let user_id = parse_user_id(&auth_user)?;
 
     let service = CommentService::new(db.clone());
-    service.delete(id, user_id).await?;
+    let deleted = service.delete(id, user_id).await?;
+    hub.post_changed(deleted.post_id, 0);
 
     // 回滚该评论产生的积分（如果有）
     let points = crate::services::points::PointsService::new(db);
     let _ = points.rollback_by_ref("comment", id).await;
 
-    Ok(ApiResponse::ok("Comment deleted"))
+    Ok(NoContent(ApiResponse::ok(t("comment_deleted"))))
 }
 
 #[cfg(test)]
@@ -362,6 +587,9 @@ mod tests {
             is_hidden: false,
             created_at: now,
             updated_at: now,
+            edited_at: None,
+            quoted_comment_id: None,
+            quote_excerpt: None,
         }
     }
 
@@ -391,6 +619,8 @@ mod tests {
         assert_eq!(tree[0].children[0].id, 2);
         assert_eq!(tree[0].children[0].children.len(), 1);
         assert_eq!(tree[0].children[0].children[0].id, 3);
+        assert_eq!(tree[0].depth, 0);
+        assert_eq!(tree[0].children[0].children[0].depth, 2);
     }
 
     #[test]
@@ -425,6 +655,23 @@ mod tests {
         assert_eq!(tree[1].children.len(), 1);
     }
 
+    #[test]
+    fn pinned_comment_moves_to_front() {
+        let comments = vec![
+            make_comment(1, 1, None),
+            make_comment(2, 1, None),
+            make_comment(3, 1, None),
+        ];
+        let mut tree = build_comment_tree(comments);
+        move_pinned_first(&mut tree, Some(3));
+        let ids: Vec<i32> = tree.iter().map(|n| n.id).collect();
+        assert_eq!(ids[0], 3);
+        assert_eq!(ids.len(), 3);
+
+        move_pinned_first(&mut tree, Some(999));
+        assert_eq!(tree[0].id, 3);
+    }
+
     #[test]
     fn content_html_is_rendered() {
         let mut c = make_comment(1, 1, None);
@@ -0,0 +1,245 @@
+use crate::config::email::EmailWebhookConfig;
+use crate::error::{AppError, AppResult};
+use crate::middleware::auth::parse_user_id;
+use crate::middleware::AuthUser;
+use crate::response::ApiResponse;
+use crate::services::email_feedback::{
+    is_sns_url, parse_sendgrid_events, parse_sns, EmailFeedbackService, SnsMessage,
+};
+use crate::services::email_preferences::{EmailCategory, EmailPreferenceService};
+use axum::{extract::Query, response::IntoResponse, Extension, Json};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::Duration;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmailPreferenceResponse {
+    /// Category name: `account`, `digest` or `announcement`
+    pub category: String,
+    /// Whether the user receives this category
+    pub enabled: bool,
+    /// Required categories (account emails) can't be turned off
+    pub required: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmailPreferencesResponse {
+    pub categories: Vec<EmailPreferenceResponse>,
+}
+
+impl From<Vec<(EmailCategory, bool)>> for EmailPreferencesResponse {
+    fn from(preferences: Vec<(EmailCategory, bool)>) -> Self {
+        Self {
+            categories: preferences
+                .into_iter()
+                .map(|(category, enabled)| EmailPreferenceResponse {
+                    category: category.as_str().to_string(),
+                    enabled,
+                    required: category.is_required(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateEmailPreferencesRequest {
+    /// Category name to whether it should be sent; omitted categories are
+    /// unchanged
+    pub categories: HashMap<String, bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct UnsubscribeQuery {
+    /// Signed token from the email's unsubscribe link
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UnsubscribeResponse {
+    /// Categories that were turned off
+    pub unsubscribed: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/email-preferences",
+    security(("jwt_token" = [])),
+    responses(
+        (status = 200, description = "Email preferences", body = EmailPreferencesResponse),
+        (status = 401, description = "Unauthorized", body = AppError),
+    ),
+    tag = "email"
+)]
+pub async fn get_email_preferences(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let service = EmailPreferenceService::new(db);
+    let preferences = service.preferences(user_id).await?;
+    Ok(ApiResponse::ok(EmailPreferencesResponse::from(preferences)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/auth/email-preferences",
+    security(("jwt_token" = [])),
+    request_body = UpdateEmailPreferencesRequest,
+    responses(
+        (status = 200, description = "Email preferences updated", body = EmailPreferencesResponse),
+        (status = 400, description = "Unknown or required category", body = AppError),
+        (status = 401, description = "Unauthorized", body = AppError),
+    ),
+    tag = "email"
+)]
+pub async fn update_email_preferences(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Json(payload): Json<UpdateEmailPreferencesRequest>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let changes = payload
+        .categories
+        .iter()
+        .map(|(name, enabled)| {
+            EmailCategory::parse(name)
+                .map(|category| (category, *enabled))
+                .ok_or_else(|| AppError::Validation(format!("Unknown email category: {}", name)))
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    let service = EmailPreferenceService::new(db);
+    service.set(user_id, &changes).await?;
+    let preferences = service.preferences(user_id).await?;
+    Ok(ApiResponse::ok(EmailPreferencesResponse::from(preferences)))
+}
+
+/// Served for both GET (the link in the email body) and POST (one-click
+/// unsubscribe from the mail client, RFC 8058).
+#[utoipa::path(
+    get,
+    path = "/api/v1/email/unsubscribe",
+    params(UnsubscribeQuery),
+    responses(
+        (status = 200, description = "Unsubscribed", body = UnsubscribeResponse),
+        (status = 400, description = "Invalid token", body = AppError),
+        (status = 404, description = "Account no longer exists", body = AppError),
+    ),
+    tag = "email"
+)]
+pub async fn unsubscribe(
+    Extension(db): Extension<DatabaseConnection>,
+    Query(params): Query<UnsubscribeQuery>,
+) -> AppResult<impl IntoResponse> {
+    let service = EmailPreferenceService::new(db);
+    let categories = service.unsubscribe(&params.token).await?;
+    Ok(ApiResponse::ok(UnsubscribeResponse {
+        unsubscribed: categories
+            .into_iter()
+            .map(|c| c.as_str().to_string())
+            .collect(),
+    }))
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct EmailWebhookQuery {
+    /// Must equal `EMAIL_WEBHOOK_SECRET`
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmailWebhookResponse {
+    /// Users whose address was newly marked undeliverable
+    pub marked: u64,
+}
+
+/// Webhooks are off (404) without `EMAIL_WEBHOOK_SECRET`, and need it as the
+/// `token` query parameter otherwise.
+fn check_webhook_token(token: Option<&str>) -> AppResult<()> {
+    let Some(secret) = EmailWebhookConfig::from_env().secret else {
+        return Err(AppError::NotFound);
+    };
+    // Compare digests so the check takes the same time for any token
+    match token {
+        Some(token) if Sha256::digest(token) == Sha256::digest(&secret) => Ok(()),
+        _ => Err(AppError::Unauthorized),
+    }
+}
+
+fn parse_json(body: &str) -> AppResult<serde_json::Value> {
+    serde_json::from_str(body).map_err(|e| AppError::Validation(format!("Invalid JSON: {}", e)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks/email/sendgrid",
+    params(EmailWebhookQuery),
+    request_body(content = String, description = "SendGrid Event Webhook batch", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Events processed", body = EmailWebhookResponse),
+        (status = 400, description = "Invalid JSON", body = AppError),
+        (status = 401, description = "Wrong token", body = AppError),
+        (status = 404, description = "Webhooks disabled", body = AppError),
+    ),
+    tag = "email"
+)]
+pub async fn sendgrid_webhook(
+    Extension(db): Extension<DatabaseConnection>,
+    Query(params): Query<EmailWebhookQuery>,
+    body: String,
+) -> AppResult<impl IntoResponse> {
+    check_webhook_token(params.token.as_deref())?;
+    let feedback = parse_sendgrid_events(&parse_json(&body)?);
+    let marked = EmailFeedbackService::new(db).record(&feedback).await?;
+    Ok(ApiResponse::ok(EmailWebhookResponse { marked }))
+}
+
+/// SNS posts JSON as `text/plain`, so the body is read as a string.
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks/email/ses",
+    params(EmailWebhookQuery),
+    request_body(content = String, description = "SNS message carrying SES notifications", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "Message processed", body = EmailWebhookResponse),
+        (status = 400, description = "Invalid message", body = AppError),
+        (status = 401, description = "Wrong token", body = AppError),
+        (status = 404, description = "Webhooks disabled", body = AppError),
+    ),
+    tag = "email"
+)]
+pub async fn ses_webhook(
+    Extension(db): Extension<DatabaseConnection>,
+    Query(params): Query<EmailWebhookQuery>,
+    body: String,
+) -> AppResult<impl IntoResponse> {
+    check_webhook_token(params.token.as_deref())?;
+    let marked = match parse_sns(&parse_json(&body)?) {
+        SnsMessage::SubscriptionConfirmation { subscribe_url } => {
+            if !is_sns_url(&subscribe_url) {
+                return Err(AppError::Validation("Invalid SubscribeURL".to_string()));
+            }
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .map_err(anyhow::Error::from)?;
+            client
+                .get(&subscribe_url)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| anyhow::anyhow!("Failed to confirm SNS subscription: {}", e))?;
+            tracing::info!("Confirmed SNS subscription for SES notifications");
+            0
+        }
+        SnsMessage::Notification(feedback) => {
+            EmailFeedbackService::new(db).record(&feedback).await?
+        }
+        SnsMessage::Ignored => 0,
+    };
+    Ok(ApiResponse::ok(EmailWebhookResponse { marked }))
+}
@@ -0,0 +1,122 @@
+use crate::config::app::SharedConfig;
+use crate::error::{AppError, AppResult};
+use crate::handlers::upload::receive_file;
+use crate::middleware::auth::{parse_user_id, require_permission};
+use crate::middleware::permission::Permission;
+use crate::middleware::AuthUser;
+use crate::models::CustomEmojiModel;
+use crate::response::{ApiResponse, Created, NoContent};
+use crate::services::emoji::EmojiService;
+use crate::services::upload::{UploadConfig, UploadService};
+use crate::utils::i18n::t;
+use axum::{
+    extract::{Multipart, Path},
+    response::IntoResponse,
+    Extension,
+};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmojiResponse {
+    /// Emoji ID
+    pub id: i32,
+    /// Name between the colons, e.g. `party_parrot`
+    pub shortcode: String,
+    /// URL of the image
+    pub url: String,
+}
+
+impl From<CustomEmojiModel> for EmojiResponse {
+    fn from(e: CustomEmojiModel) -> Self {
+        Self {
+            id: e.id,
+            shortcode: e.shortcode,
+            url: e.image_url,
+        }
+    }
+}
+
+/// The custom emoji `:shortcode:` expands to in posts and comments, for
+/// emoji pickers.
+#[utoipa::path(
+    get,
+    path = "/api/v1/emoji",
+    responses(
+        (status = 200, description = "Custom emoji by shortcode", body = Vec<EmojiResponse>),
+    ),
+    tag = "emoji"
+)]
+pub async fn list_emoji(
+    Extension(db): Extension<DatabaseConnection>,
+) -> AppResult<impl IntoResponse> {
+    let items: Vec<EmojiResponse> = EmojiService::new(db)
+        .list()
+        .await?
+        .into_iter()
+        .map(EmojiResponse::from)
+        .collect();
+    Ok(ApiResponse::ok(items))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/emoji",
+    security(("jwt_token" = [])),
+    request_body(content_type = "multipart/form-data", description = "JPEG, PNG, GIF or WebP image in a `file` field, and a `shortcode` field"),
+    responses(
+        (status = 200, description = "Emoji added", body = EmojiResponse),
+        (status = 400, description = "Invalid file or shortcode, or rejected by the virus scan", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 409, description = "An emoji with this shortcode already exists", body = AppError),
+        (status = 413, description = "File too large", body = AppError),
+    ),
+    tag = "emoji"
+)]
+pub async fn create_emoji(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(shared_config): Extension<SharedConfig>,
+    auth_user: AuthUser,
+    mut multipart: Multipart,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageEmoji).await?;
+    let user_id = parse_user_id(&auth_user)?;
+
+    let config = UploadConfig::from(&shared_config.current().uploads);
+    let (mut upload, content_type, fields) = receive_file(&mut multipart, &config).await?;
+    let shortcode = fields
+        .get("shortcode")
+        .map(|s| s.trim().trim_matches(':').to_string())
+        .unwrap_or_default();
+    UploadService::scan(&db, &mut upload, &content_type, user_id).await?;
+    let saved = UploadService::save_upload(&config, upload, &content_type, "emoji").await?;
+    UploadService::record(&db, &config, &saved, &content_type, user_id).await?;
+
+    let emoji = EmojiService::new(db)
+        .create(&shortcode, &saved.url, user_id)
+        .await?;
+    Ok(Created(ApiResponse::ok(EmojiResponse::from(emoji))))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/emoji/{id}",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Emoji ID")),
+    responses(
+        (status = 200, description = "Emoji deleted; posts show its plain shortcode again", body = String),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Emoji not found", body = AppError),
+    ),
+    tag = "emoji"
+)]
+pub async fn delete_emoji(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageEmoji).await?;
+    EmojiService::new(db).delete(id).await?;
+    Ok(NoContent(ApiResponse::ok(t("emoji_deleted"))))
+}
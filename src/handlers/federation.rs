@@ -0,0 +1,232 @@
+use crate::error::{AppError, AppResult};
+use crate::handlers::seo::seo_config;
+use crate::services::federation::{ActorKind, FederationService, ACTIVITY_JSON, JRD_JSON};
+use axum::{
+    body::Bytes,
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use serde_json::Value;
+use utoipa::IntoParams;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct WebFingerQuery {
+    /// `acct:name@host`, naming a user or a forum
+    pub resource: String,
+}
+
+/// Find the ActivityPub actor of a user or forum.
+#[utoipa::path(
+    get,
+    path = "/.well-known/webfinger",
+    params(WebFingerQuery),
+    responses(
+        (status = 200, description = "JRD document", content_type = "application/jrd+json", body = Object),
+        (status = 404, description = "No such account, or federation is disabled", body = AppError),
+    ),
+    tag = "federation"
+)]
+pub async fn webfinger(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Query(query): Query<WebFingerQuery>,
+) -> AppResult<Response> {
+    let jrd = federation(db, &headers)?.webfinger(&query.resource).await?;
+    Ok(([(header::CONTENT_TYPE, JRD_JSON)], Json(jrd)).into_response())
+}
+
+/// A user as an ActivityPub `Person`.
+#[utoipa::path(
+    get,
+    path = "/ap/users/{username}",
+    params(("username" = String, Path, description = "Username")),
+    responses(
+        (status = 200, description = "Actor document", content_type = "application/activity+json", body = Object),
+        (status = 404, description = "User not found, or federation is disabled", body = AppError),
+    ),
+    tag = "federation"
+)]
+pub async fn user_actor(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> AppResult<Response> {
+    let service = federation(db, &headers)?;
+    Ok(activity_json(
+        service.actor(ActorKind::User, &username).await?,
+    ))
+}
+
+/// Posts written by a user, as `Create` activities.
+#[utoipa::path(
+    get,
+    path = "/ap/users/{username}/outbox",
+    params(("username" = String, Path, description = "Username")),
+    responses(
+        (status = 200, description = "Latest activities", content_type = "application/activity+json", body = Object),
+        (status = 404, description = "User not found, or federation is disabled", body = AppError),
+    ),
+    tag = "federation"
+)]
+pub async fn user_outbox(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> AppResult<Response> {
+    let service = federation(db, &headers)?;
+    Ok(activity_json(
+        service.outbox(ActorKind::User, &username).await?,
+    ))
+}
+
+/// How many remote actors follow a user.
+#[utoipa::path(
+    get,
+    path = "/ap/users/{username}/followers",
+    params(("username" = String, Path, description = "Username")),
+    responses(
+        (status = 200, description = "Follower collection", content_type = "application/activity+json", body = Object),
+        (status = 404, description = "User not found, or federation is disabled", body = AppError),
+    ),
+    tag = "federation"
+)]
+pub async fn user_followers(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> AppResult<Response> {
+    let service = federation(db, &headers)?;
+    Ok(activity_json(
+        service.followers(ActorKind::User, &username).await?,
+    ))
+}
+
+/// A forum as an ActivityPub `Group`.
+#[utoipa::path(
+    get,
+    path = "/ap/forums/{slug}",
+    params(("slug" = String, Path, description = "Forum slug")),
+    responses(
+        (status = 200, description = "Actor document", content_type = "application/activity+json", body = Object),
+        (status = 404, description = "Forum not found, or federation is disabled", body = AppError),
+    ),
+    tag = "federation"
+)]
+pub async fn forum_actor(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+) -> AppResult<Response> {
+    let service = federation(db, &headers)?;
+    Ok(activity_json(service.actor(ActorKind::Forum, &slug).await?))
+}
+
+/// Posts in a forum, as `Announce` activities.
+#[utoipa::path(
+    get,
+    path = "/ap/forums/{slug}/outbox",
+    params(("slug" = String, Path, description = "Forum slug")),
+    responses(
+        (status = 200, description = "Latest activities", content_type = "application/activity+json", body = Object),
+        (status = 404, description = "Forum not found, or federation is disabled", body = AppError),
+    ),
+    tag = "federation"
+)]
+pub async fn forum_outbox(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+) -> AppResult<Response> {
+    let service = federation(db, &headers)?;
+    Ok(activity_json(
+        service.outbox(ActorKind::Forum, &slug).await?,
+    ))
+}
+
+/// How many remote actors follow a forum.
+#[utoipa::path(
+    get,
+    path = "/ap/forums/{slug}/followers",
+    params(("slug" = String, Path, description = "Forum slug")),
+    responses(
+        (status = 200, description = "Follower collection", content_type = "application/activity+json", body = Object),
+        (status = 404, description = "Forum not found, or federation is disabled", body = AppError),
+    ),
+    tag = "federation"
+)]
+pub async fn forum_followers(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+) -> AppResult<Response> {
+    let service = federation(db, &headers)?;
+    Ok(activity_json(
+        service.followers(ActorKind::Forum, &slug).await?,
+    ))
+}
+
+/// A post as an `Article`, or a `Note` with `FEDERATION_POST_TYPE=note`.
+#[utoipa::path(
+    get,
+    path = "/ap/posts/{id}",
+    params(("id" = i32, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "Post object", content_type = "application/activity+json", body = Object),
+        (status = 404, description = "Post not found, or federation is disabled", body = AppError),
+    ),
+    tag = "federation"
+)]
+pub async fn post_object(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AppResult<Response> {
+    let service = federation(db, &headers)?;
+    Ok(activity_json(service.post_object(id).await?))
+}
+
+/// Signed activities from remote servers. The per-actor inboxes and the
+/// shared one behave the same: `Follow` and `Undo` of a follow are handled,
+/// anything else is accepted and ignored.
+#[utoipa::path(
+    post,
+    path = "/ap/inbox",
+    request_body(content = Object, content_type = "application/activity+json"),
+    responses(
+        (status = 202, description = "Activity accepted"),
+        (status = 401, description = "Missing or invalid HTTP signature", body = AppError),
+        (status = 403, description = "Activity not sent by its signer", body = AppError),
+        (status = 404, description = "Followed actor not found, or federation is disabled", body = AppError),
+    ),
+    tag = "federation"
+)]
+pub async fn inbox(
+    Extension(db): Extension<DatabaseConnection>,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<StatusCode> {
+    let path = uri.path_and_query().map_or(uri.path(), |p| p.as_str());
+    federation(db, &headers)?
+        .receive(path, &headers, &body)
+        .await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// The service for this request's host; everything is 404 while
+/// federation is disabled.
+fn federation(db: DatabaseConnection, headers: &HeaderMap) -> AppResult<FederationService> {
+    let service = FederationService::new(db, &seo_config(headers));
+    if !service.enabled() {
+        return Err(AppError::NotFound);
+    }
+    Ok(service)
+}
+
+fn activity_json(document: Value) -> Response {
+    ([(header::CONTENT_TYPE, ACTIVITY_JSON)], Json(document)).into_response()
+}
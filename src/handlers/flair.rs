@@ -0,0 +1,145 @@
+use crate::error::{AppError, AppResult};
+use crate::middleware::auth::require_permission;
+use crate::middleware::permission::Permission;
+use crate::middleware::AuthUser;
+use crate::models::PostFlairModel;
+use crate::response::{ApiResponse, Created, NoContent};
+use crate::services::flair::FlairService;
+use crate::utils::i18n::t;
+use axum::{extract::Path, response::IntoResponse, Extension, Json};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FlairResponse {
+    /// Flair ID
+    pub id: i32,
+    pub name: String,
+    /// Hex color such as `#1f6feb`
+    pub color: Option<String>,
+}
+
+impl From<PostFlairModel> for FlairResponse {
+    fn from(f: PostFlairModel) -> Self {
+        Self {
+            id: f.id,
+            name: f.name,
+            color: f.color,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct FlairRequest {
+    /// Name, unique within the forum (1-50 characters)
+    #[validate(length(min = 1, max = 50))]
+    pub name: String,
+    /// Hex color such as `#1f6feb`
+    pub color: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/forums/{forum_id}/flairs",
+    params(("forum_id" = i32, Path, description = "Forum ID")),
+    responses(
+        (status = 200, description = "The forum's flairs by name", body = Vec<FlairResponse>),
+    ),
+    tag = "forums"
+)]
+pub async fn list_flairs(
+    Extension(db): Extension<DatabaseConnection>,
+    Path(forum_id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let items: Vec<FlairResponse> = FlairService::new(db)
+        .list(forum_id)
+        .await?
+        .into_iter()
+        .map(FlairResponse::from)
+        .collect();
+    Ok(ApiResponse::ok(items))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/forums/{forum_id}/flairs",
+    security(("jwt_token" = [])),
+    params(("forum_id" = i32, Path, description = "Forum ID")),
+    request_body = FlairRequest,
+    responses(
+        (status = 200, description = "Flair created", body = FlairResponse),
+        (status = 400, description = "Validation error", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Forum not found", body = AppError),
+        (status = 409, description = "The forum already has a flair with this name", body = AppError),
+    ),
+    tag = "forums"
+)]
+pub async fn create_flair(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(forum_id): Path<i32>,
+    Json(payload): Json<FlairRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    require_permission(&auth_user, Permission::ManageForums).await?;
+
+    let flair = FlairService::new(db)
+        .create(forum_id, payload.name.trim(), payload.color)
+        .await?;
+    Ok(Created(ApiResponse::ok(FlairResponse::from(flair))))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/flairs/{id}",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Flair ID")),
+    request_body = FlairRequest,
+    responses(
+        (status = 200, description = "Flair updated", body = FlairResponse),
+        (status = 400, description = "Validation error", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Flair not found", body = AppError),
+        (status = 409, description = "The forum already has a flair with this name", body = AppError),
+    ),
+    tag = "forums"
+)]
+pub async fn update_flair(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<FlairRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    require_permission(&auth_user, Permission::ManageForums).await?;
+
+    let flair = FlairService::new(db)
+        .update(id, payload.name.trim(), payload.color)
+        .await?;
+    Ok(ApiResponse::ok(FlairResponse::from(flair)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/flairs/{id}",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Flair ID")),
+    responses(
+        (status = 200, description = "Flair deleted; its posts are left without flair", body = String),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Flair not found", body = AppError),
+    ),
+    tag = "forums"
+)]
+pub async fn delete_flair(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageForums).await?;
+    FlairService::new(db).delete(id).await?;
+    Ok(NoContent(ApiResponse::ok(t("flair_deleted"))))
+}
@@ -2,17 +2,106 @@ use crate::error::AppResult;
 use crate::handlers::user::UserProfileResponse;
 use crate::middleware::auth::parse_user_id;
 use crate::middleware::AuthUser;
+use crate::models::UserModel;
 use crate::response::{ApiResponse, PaginatedResponse, PaginationQuery};
-use crate::services::follow::FollowService;
+use crate::services::follow::{FollowService, FollowStatus};
+use crate::services::notification::NotificationService;
+use crate::utils::i18n::t;
+use crate::websocket::hub::NotificationHub;
 use axum::{extract::Path, extract::Query, response::IntoResponse, Extension};
 use sea_orm::DatabaseConnection;
 use serde::Serialize;
 use utoipa::ToSchema;
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FollowUserResponse {
+    #[serde(flatten)]
+    pub user: UserProfileResponse,
+    /// Whether this user follows the requester (only when authenticated)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follows_you: Option<bool>,
+    /// Whether the requester follows this user (only when authenticated)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub followed_by_you: Option<bool>,
+    /// Whether the requester's request to follow this private user is
+    /// pending (only when authenticated)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_by_you: Option<bool>,
+}
+
+/// Attach the requester's follow state to each user of a follower or
+/// following page.
+async fn follow_users(
+    service: &FollowService,
+    auth_user: Option<&AuthUser>,
+    users: Vec<UserModel>,
+) -> AppResult<Vec<FollowUserResponse>> {
+    let relations = match auth_user {
+        Some(auth_user) => {
+            let viewer_id = parse_user_id(auth_user)?;
+            let ids: Vec<i32> = users.iter().map(|u| u.id).collect();
+            Some(service.relations(viewer_id, &ids).await?)
+        }
+        None => None,
+    };
+
+    Ok(users
+        .into_iter()
+        .map(|u| {
+            let relation = relations
+                .as_ref()
+                .map(|r| r.get(&u.id).copied().unwrap_or_default());
+            FollowUserResponse {
+                follows_you: relation.map(|r| r.follows_you),
+                followed_by_you: relation.map(|r| r.followed_by_you),
+                requested_by_you: relation.map(|r| r.requested_by_you),
+                user: u.into(),
+            }
+        })
+        .collect())
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct FollowToggleResponse {
     /// Whether user is now being followed
     pub following: bool,
+    /// Whether a request to follow the private user awaits their approval
+    pub requested: bool,
+}
+
+/// Tell a private user about a new request to follow them.
+async fn notify_requested(
+    db: DatabaseConnection,
+    hub: NotificationHub,
+    follower_id: i32,
+    following_id: i32,
+    status: FollowStatus,
+) {
+    if let FollowStatus::Requested {
+        request_id,
+        new: true,
+    } = status
+    {
+        let _ = NotificationService::new(db, hub)
+            .notify(
+                following_id,
+                follower_id,
+                "follow_request",
+                "follow_request",
+                request_id,
+                "Someone asked to follow you",
+            )
+            .await;
+    }
+}
+
+impl From<FollowStatus> for FollowToggleResponse {
+    fn from(status: FollowStatus) -> Self {
+        Self {
+            following: status == FollowStatus::Following,
+            requested: matches!(status, FollowStatus::Requested { .. }),
+        }
+    }
 }
 
 #[utoipa::path(
@@ -21,20 +110,22 @@ pub struct FollowToggleResponse {
     security(("jwt_token" = [])),
     params(("id" = i32, Path, description = "User ID to follow")),
     responses(
-        (status = 200, description = "Followed", body = FollowToggleResponse),
+        (status = 200, description = "Followed, or requested if the profile is private", body = FollowToggleResponse),
         (status = 401, description = "Unauthorized", body = crate::error::AppError),
     ),
     tag = "follows"
 )]
 pub async fn follow_user(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
     auth_user: AuthUser,
     Path(user_id): Path<i32>,
 ) -> AppResult<impl IntoResponse> {
     let follower_id = parse_user_id(&auth_user)?;
-    let service = FollowService::new(db);
-    let following = service.follow(follower_id, user_id).await?;
-    Ok(ApiResponse::ok(FollowToggleResponse { following }))
+    let service = FollowService::new(db.clone());
+    let status = service.follow(follower_id, user_id).await?;
+    notify_requested(db, hub, follower_id, user_id, status).await;
+    Ok(ApiResponse::ok(FollowToggleResponse::from(status)))
 }
 
 #[utoipa::path(
@@ -43,7 +134,7 @@ pub async fn follow_user(
     security(("jwt_token" = [])),
     params(("id" = i32, Path, description = "User ID to unfollow")),
     responses(
-        (status = 200, description = "Unfollowed", body = FollowToggleResponse),
+        (status = 200, description = "Unfollowed, or request withdrawn", body = FollowToggleResponse),
         (status = 401, description = "Unauthorized", body = crate::error::AppError),
     ),
     tag = "follows"
@@ -55,8 +146,8 @@ pub async fn unfollow_user(
 ) -> AppResult<impl IntoResponse> {
     let follower_id = parse_user_id(&auth_user)?;
     let service = FollowService::new(db);
-    let following = service.unfollow(follower_id, user_id).await?;
-    Ok(ApiResponse::ok(FollowToggleResponse { following }))
+    let status = service.unfollow(follower_id, user_id).await?;
+    Ok(ApiResponse::ok(FollowToggleResponse::from(status)))
 }
 
 #[utoipa::path(
@@ -72,13 +163,136 @@ pub async fn unfollow_user(
 )]
 pub async fn toggle_follow(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
     auth_user: AuthUser,
     Path(user_id): Path<i32>,
 ) -> AppResult<impl IntoResponse> {
     let follower_id = parse_user_id(&auth_user)?;
+    let service = FollowService::new(db.clone());
+    let status = service.toggle(follower_id, user_id).await?;
+    notify_requested(db, hub, follower_id, user_id, status).await;
+    Ok(ApiResponse::ok(FollowToggleResponse::from(status)))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FollowRequestResponse {
+    /// Follow request ID
+    pub id: i32,
+    /// User asking to follow
+    pub requester: UserProfileResponse,
+    /// When the request was made
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/follow-requests",
+    security(("jwt_token" = [])),
+    params(
+        ("page" = Option<u64>, Query, description = "Page number"),
+        ("per_page" = Option<u64>, Query, description = "Items per page"),
+    ),
+    responses(
+        (status = 200, description = "Pending requests to follow the current user, newest first", body = PaginatedResponse<FollowRequestResponse>),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+    ),
+    tag = "follows"
+)]
+pub async fn list_follow_requests(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Query(params): Query<PaginationQuery>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(20).min(100);
+
     let service = FollowService::new(db);
-    let following = service.toggle(follower_id, user_id).await?;
-    Ok(ApiResponse::ok(FollowToggleResponse { following }))
+    let (requests, total) = service.list_requests(user_id, page, per_page).await?;
+    let items = requests
+        .into_iter()
+        .map(|(request, requester)| FollowRequestResponse {
+            id: request.id,
+            requester: requester.into(),
+            created_at: request.created_at,
+        })
+        .collect();
+    Ok(ApiResponse::ok(PaginatedResponse::new(
+        items, total, page, per_page,
+    )))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/follow-requests/{id}/approve",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Follow request ID")),
+    responses(
+        (status = 200, description = "Approved; the requester now follows the current user", body = String),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+        (status = 404, description = "No such pending request", body = crate::error::AppError),
+    ),
+    tag = "follows"
+)]
+pub async fn approve_follow_request(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let service = FollowService::new(db.clone());
+    let request = service.approve_request(user_id, id).await?;
+
+    let _ = NotificationService::new(db, hub)
+        .notify(
+            request.requester_id,
+            user_id,
+            "follow_request_approved",
+            "user",
+            user_id,
+            "Your follow request was approved",
+        )
+        .await;
+
+    Ok(ApiResponse::ok(t("follow_request_approved")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/follow-requests/{id}/deny",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Follow request ID")),
+    responses(
+        (status = 200, description = "Denied", body = String),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+        (status = 404, description = "No such pending request", body = crate::error::AppError),
+    ),
+    tag = "follows"
+)]
+pub async fn deny_follow_request(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let service = FollowService::new(db.clone());
+    let request = service.deny_request(user_id, id).await?;
+
+    let _ = NotificationService::new(db, hub)
+        .notify(
+            request.requester_id,
+            user_id,
+            "follow_request_denied",
+            "user",
+            user_id,
+            "Your follow request was declined",
+        )
+        .await;
+
+    Ok(ApiResponse::ok(t("follow_request_denied")))
 }
 
 #[utoipa::path(
@@ -90,12 +304,13 @@ pub async fn toggle_follow(
         ("per_page" = Option<u64>, Query, description = "Items per page"),
     ),
     responses(
-        (status = 200, description = "List of followers", body = PaginatedResponse<UserProfileResponse>),
+        (status = 200, description = "List of followers, newest first; includes follow state when authenticated", body = PaginatedResponse<FollowUserResponse>),
     ),
     tag = "follows"
 )]
 pub async fn list_followers(
     Extension(db): Extension<DatabaseConnection>,
+    auth_user: Option<AuthUser>,
     Path(user_id): Path<i32>,
     Query(params): Query<PaginationQuery>,
 ) -> AppResult<impl IntoResponse> {
@@ -104,7 +319,7 @@ pub async fn list_followers(
 
     let service = FollowService::new(db);
     let (users, total) = service.list_followers(user_id, page, per_page).await?;
-    let items = users.into_iter().map(UserProfileResponse::from).collect();
+    let items = follow_users(&service, auth_user.as_ref(), users).await?;
     Ok(ApiResponse::ok(PaginatedResponse::new(
         items, total, page, per_page,
     )))
@@ -119,12 +334,13 @@ pub async fn list_followers(
         ("per_page" = Option<u64>, Query, description = "Items per page"),
     ),
     responses(
-        (status = 200, description = "List of following", body = PaginatedResponse<UserProfileResponse>),
+        (status = 200, description = "List of following, newest first; includes follow state when authenticated", body = PaginatedResponse<FollowUserResponse>),
     ),
     tag = "follows"
 )]
 pub async fn list_following(
     Extension(db): Extension<DatabaseConnection>,
+    auth_user: Option<AuthUser>,
     Path(user_id): Path<i32>,
     Query(params): Query<PaginationQuery>,
 ) -> AppResult<impl IntoResponse> {
@@ -133,7 +349,7 @@ pub async fn list_following(
 
     let service = FollowService::new(db);
     let (users, total) = service.list_following(user_id, page, per_page).await?;
-    let items = users.into_iter().map(UserProfileResponse::from).collect();
+    let items = follow_users(&service, auth_user.as_ref(), users).await?;
     Ok(ApiResponse::ok(PaginatedResponse::new(
         items, total, page, per_page,
     )))
@@ -1,9 +1,11 @@
 use crate::error::{AppError, AppResult};
-use crate::middleware::auth::{require_admin, AuthUser};
+use crate::middleware::auth::{require_permission, AuthUser};
+use crate::middleware::permission::Permission;
 use crate::models::ForumModel;
-use crate::response::ApiResponse;
+use crate::response::{ApiResponse, Created, NoContent};
 use crate::services::cache::CacheService;
 use crate::services::forum::ForumService;
+use crate::utils::i18n::t;
 use axum::{extract::Path, response::IntoResponse, Extension, Json};
 use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
@@ -56,9 +58,11 @@ pub struct ForumResponse {
     /// Icon URL
     pub icon_url: Option<String>,
     /// Creation timestamp
-    pub created_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
     /// Last update timestamp
-    pub updated_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub updated_at: chrono::NaiveDateTime,
 }
 
 impl From<ForumModel> for ForumResponse {
@@ -70,8 +74,8 @@ impl From<ForumModel> for ForumResponse {
             slug: f.slug,
             sort_order: f.sort_order,
             icon_url: f.icon_url,
-            created_at: f.created_at.to_string(),
-            updated_at: f.updated_at.to_string(),
+            created_at: f.created_at,
+            updated_at: f.updated_at,
         }
     }
 }
@@ -129,7 +133,8 @@ pub async fn get_forum(
     responses(
         (status = 200, description = "Forum created", body = ForumResponse),
         (status = 400, description = "Validation error", body = AppError),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 409, description = "Name or slug already taken", body = AppError),
     ),
     tag = "forums"
 )]
@@ -139,11 +144,9 @@ pub async fn create_forum(
     auth_user: AuthUser,
     Json(payload): Json<CreateForumRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::ManageForums).await?;
 
     let service = make_forum_service(db, cache.map(|c| c.0));
     let forum = service
@@ -156,7 +159,7 @@ pub async fn create_forum(
         )
         .await?;
 
-    Ok(ApiResponse::ok(ForumResponse::from(forum)))
+    Ok(Created(ApiResponse::ok(ForumResponse::from(forum))))
 }
 
 #[utoipa::path(
@@ -168,7 +171,8 @@ pub async fn create_forum(
     responses(
         (status = 200, description = "Forum updated", body = ForumResponse),
         (status = 400, description = "Validation error", body = AppError),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 409, description = "Name or slug already taken", body = AppError),
     ),
     tag = "forums"
 )]
@@ -179,11 +183,9 @@ pub async fn update_forum(
     Path(slug): Path<String>,
     Json(payload): Json<UpdateForumRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::ManageForums).await?;
 
     let service = make_forum_service(db, cache.map(|c| c.0));
     let forum = service
@@ -206,7 +208,7 @@ pub async fn update_forum(
     params(("slug" = String, Path, description = "Forum slug")),
     responses(
         (status = 200, description = "Forum deleted", body = String),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
     ),
     tag = "forums"
 )]
@@ -216,10 +218,10 @@ pub async fn delete_forum(
     auth_user: AuthUser,
     Path(slug): Path<String>,
 ) -> AppResult<impl IntoResponse> {
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::ManageForums).await?;
 
     let service = make_forum_service(db, cache.map(|c| c.0));
     service.delete(&slug).await?;
 
-    Ok(ApiResponse::ok("Forum deleted"))
+    Ok(NoContent(ApiResponse::ok(t("forum_deleted"))))
 }
@@ -0,0 +1,150 @@
+use crate::config::database::{pool_stats, PoolStats};
+use crate::migration::Migrator;
+use crate::services::cache::CacheService;
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use sea_orm::{ConnectionTrait, DatabaseConnection, Statement};
+use sea_orm_migration::MigratorTrait;
+use serde::Serialize;
+use std::time::Instant;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LivenessResponse {
+    /// Always `ok` while the process can serve requests
+    pub status: &'static str,
+    pub service: &'static str,
+    pub version: &'static str,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DependencyCheck {
+    pub ok: bool,
+    /// Round-trip time of the check
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MigrationCheck {
+    pub ok: bool,
+    /// Migrations known to this build but not applied to the database
+    pub pending: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// `ok`, or `unavailable` when a required dependency fails
+    pub status: &'static str,
+    pub database: DependencyCheck,
+    /// Connection pool utilization, for diagnosing saturation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolStats>,
+    pub migrations: MigrationCheck,
+    /// Redis is optional: absent when not configured, and a failure only
+    /// degrades caching, so it never makes the instance unready
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redis: Option<DependencyCheck>,
+}
+
+fn liveness() -> LivenessResponse {
+    LivenessResponse {
+        status: "ok",
+        service: "Forum API",
+        version: env!("CARGO_PKG_VERSION"),
+    }
+}
+
+/// Liveness probe: answers without touching any dependency, so a slow
+/// database never gets the process restarted.
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    responses(
+        (status = 200, description = "Process is alive", body = LivenessResponse)
+    ),
+    tag = "health"
+)]
+pub async fn healthz() -> impl IntoResponse {
+    Json(liveness())
+}
+
+/// Kept for existing monitors of `/`; same as `/healthz`.
+pub async fn root() -> impl IntoResponse {
+    Json(liveness())
+}
+
+/// Readiness probe: the database is reachable and fully migrated. Returns
+/// 503 otherwise so orchestrators stop routing traffic here.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = ReadinessResponse),
+        (status = 503, description = "A required dependency is unavailable", body = ReadinessResponse)
+    ),
+    tag = "health"
+)]
+pub async fn readyz(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+) -> impl IntoResponse {
+    let started = Instant::now();
+    let db_result = db
+        .query_one(Statement::from_string(
+            db.get_database_backend(),
+            "SELECT 1".to_string(),
+        ))
+        .await;
+    let database = DependencyCheck {
+        ok: db_result.is_ok(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        error: db_result.err().map(|e| e.to_string()),
+    };
+
+    let migrations = if database.ok {
+        match Migrator::get_pending_migrations(&db).await {
+            Ok(pending) => MigrationCheck {
+                ok: pending.is_empty(),
+                pending: pending.len(),
+            },
+            Err(_) => MigrationCheck {
+                ok: false,
+                pending: 0,
+            },
+        }
+    } else {
+        MigrationCheck {
+            ok: false,
+            pending: 0,
+        }
+    };
+
+    let redis = match cache {
+        Some(Extension(cache)) => {
+            let started = Instant::now();
+            let result = cache.ping().await;
+            Some(DependencyCheck {
+                ok: result.is_ok(),
+                latency_ms: started.elapsed().as_millis() as u64,
+                error: result.err(),
+            })
+        }
+        None => None,
+    };
+
+    let ready = database.ok && migrations.ok;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = ReadinessResponse {
+        status: if ready { "ok" } else { "unavailable" },
+        database,
+        pool: pool_stats(&db),
+        migrations,
+        redis,
+    };
+    (status, Json(body))
+}
@@ -0,0 +1,46 @@
+use crate::error::{AppError, AppResult};
+use crate::services::image_proxy::ImageProxy;
+use axum::{
+    extract::Path,
+    http::header,
+    response::{IntoResponse, Response},
+    Extension,
+};
+
+#[utoipa::path(
+    get,
+    path = "/img/{signature}/{encoded_url}",
+    params(
+        ("signature" = String, Path, description = "URL signature"),
+        ("encoded_url" = String, Path, description = "Base64url-encoded image URL"),
+    ),
+    responses(
+        (status = 200, description = "Proxied image bytes"),
+        (status = 400, description = "Invalid signature or unsupported image", body = AppError),
+        (status = 404, description = "Image proxy disabled or upstream missing", body = AppError),
+        (status = 413, description = "Image too large", body = AppError),
+    ),
+    tag = "outbound"
+)]
+pub async fn proxy_image(
+    Extension(proxy): Extension<ImageProxy>,
+    Path((signature, encoded_url)): Path<(String, String)>,
+) -> AppResult<Response> {
+    let image = proxy.fetch(&signature, &encoded_url).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, image.content_type),
+            (
+                header::CACHE_CONTROL,
+                format!("public, max-age={}", proxy.cache_ttl().as_secs()),
+            ),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                "default-src 'none'; sandbox".to_string(),
+            ),
+        ],
+        image.data.as_ref().clone(),
+    )
+        .into_response())
+}
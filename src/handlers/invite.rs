@@ -0,0 +1,176 @@
+use crate::error::{AppError, AppResult};
+use crate::middleware::auth::require_permission;
+use crate::middleware::permission::Permission;
+use crate::middleware::AuthUser;
+use crate::models::InviteCodeModel;
+use crate::response::{ApiResponse, Created, PaginatedResponse};
+use crate::services::invite::{is_active, InviteService, MAX_INVITES_PER_REQUEST};
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    Extension, Json,
+};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InviteCodeResponse {
+    /// Invite ID
+    pub id: i32,
+    /// Code to give to the invitee
+    pub code: String,
+    /// Admin who generated it
+    pub created_by: Option<i32>,
+    /// Registrations the code allows
+    pub max_uses: i32,
+    /// Registrations so far
+    pub uses: i32,
+    /// Staff note
+    pub note: Option<String>,
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub revoked_at: Option<chrono::NaiveDateTime>,
+    /// Whether the code can still be used
+    pub active: bool,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<InviteCodeModel> for InviteCodeResponse {
+    fn from(i: InviteCodeModel) -> Self {
+        Self {
+            active: is_active(&i, chrono::Utc::now().naive_utc()),
+            id: i.id,
+            code: i.code,
+            created_by: i.created_by,
+            max_uses: i.max_uses,
+            uses: i.uses,
+            note: i.note,
+            expires_at: i.expires_at,
+            revoked_at: i.revoked_at,
+            created_at: i.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateInvitesRequest {
+    /// Number of codes to generate (1-50, default 1)
+    #[validate(range(min = 1, max = 50))]
+    pub count: Option<u32>,
+    /// Registrations each code allows (1-1000, default 1)
+    #[validate(range(min = 1, max = 1000))]
+    pub max_uses: Option<i32>,
+    /// Days until the codes expire (1-365); never when omitted
+    #[validate(range(min = 1, max = 365))]
+    pub expires_in_days: Option<i64>,
+    /// Staff note, e.g. who the codes are for (up to 500 characters)
+    #[validate(length(max = 500))]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct ListInvitesQuery {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/invites",
+    security(("jwt_token" = [])),
+    request_body = CreateInvitesRequest,
+    responses(
+        (status = 200, description = "Generated invite codes", body = Vec<InviteCodeResponse>),
+        (status = 400, description = "Validation error", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn create_invites(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateInvitesRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    let admin_id = require_permission(&auth_user, Permission::ManageInvites).await?;
+
+    let expires_at = payload
+        .expires_in_days
+        .map(|days| chrono::Utc::now().naive_utc() + chrono::Duration::days(days));
+    let note = payload
+        .note
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty());
+
+    let service = InviteService::new(db);
+    let invites: Vec<InviteCodeResponse> = service
+        .create(
+            admin_id,
+            payload.count.unwrap_or(1).min(MAX_INVITES_PER_REQUEST),
+            payload.max_uses.unwrap_or(1),
+            expires_at,
+            note,
+        )
+        .await?
+        .into_iter()
+        .map(InviteCodeResponse::from)
+        .collect();
+    Ok(Created(ApiResponse::ok(invites)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/invites",
+    security(("jwt_token" = [])),
+    params(ListInvitesQuery),
+    responses(
+        (status = 200, description = "Invite codes, newest first", body = PaginatedResponse<InviteCodeResponse>),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn list_invites(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Query(params): Query<ListInvitesQuery>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageInvites).await?;
+
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(20).min(100);
+    let service = InviteService::new(db);
+    let (items, total) = service.list(page, per_page).await?;
+    let items = items.into_iter().map(InviteCodeResponse::from).collect();
+    Ok(ApiResponse::ok(PaginatedResponse::new(
+        items, total, page, per_page,
+    )))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/invites/{id}",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Invite ID")),
+    responses(
+        (status = 200, description = "Invite revoked", body = InviteCodeResponse),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Invite not found", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn revoke_invite(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageInvites).await?;
+
+    let service = InviteService::new(db);
+    let invite = service.revoke(id).await?;
+    Ok(ApiResponse::ok(InviteCodeResponse::from(invite)))
+}
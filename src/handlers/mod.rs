@@ -1,16 +1,34 @@
 pub mod admin;
+pub mod announcement;
+pub mod appeal;
 pub mod auth;
+pub mod badge;
 pub mod bookmark;
 pub mod comment;
+pub mod email;
+pub mod emoji;
+pub mod federation;
+pub mod flair;
 pub mod follow;
 pub mod forum;
+pub mod health;
+pub mod image_proxy;
+pub mod invite;
+pub mod mod_queue;
 pub mod notification;
+pub mod outbound;
 pub mod post;
+pub mod post_read;
 pub mod pow;
 pub mod report;
+pub mod search;
+pub mod seo;
+pub mod settings;
 pub mod tag;
 pub mod upload;
 pub mod user;
+pub mod user_note;
 pub mod vote;
+pub mod watch;
 
 pub use auth::*;
@@ -0,0 +1,251 @@
+use crate::error::{AppError, AppResult};
+use crate::middleware::auth::{require_permission, AuthUser};
+use crate::middleware::permission::{role_has_permission, Permission};
+use crate::models::ModQueueClaimModel;
+use crate::response::{ApiResponse, PaginatedResponse};
+use crate::services::mod_queue::{
+    claim_expires_at, ClaimFilter, ModQueueService, QueueItem, QueueItemKind,
+};
+use crate::utils::i18n::t;
+use axum::{extract::Path, extract::Query, response::IntoResponse, Extension, Json};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct ModQueueQuery {
+    /// Only items of this type: `report` or `appeal`
+    #[serde(rename = "type")]
+    #[param(rename = "type")]
+    pub item_type: Option<String>,
+    /// `mine` for items you claimed, `unclaimed` for items nobody is working
+    /// on; omit for all
+    pub claim: Option<String>,
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModQueueItemResponse {
+    /// Item type: `report` or `appeal`
+    pub item_type: String,
+    /// ID of the report or appeal
+    pub item_id: i32,
+    /// What the item is about: `post` or `comment` for reports, `user` for
+    /// appeals
+    pub target_type: String,
+    /// ID of the post, comment or appellant
+    pub target_id: i32,
+    /// Short description, e.g. the report reason
+    pub summary: String,
+    /// When the item entered the queue
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+    /// Moderator working on the item, if any
+    pub claimed_by: Option<i32>,
+    /// Their username
+    pub claimed_by_username: Option<String>,
+    /// Their display name, if set
+    pub claimed_by_display_name: Option<String>,
+    /// When the claim lapses unless renewed
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub claim_expires_at: Option<chrono::NaiveDateTime>,
+}
+
+impl From<QueueItem> for ModQueueItemResponse {
+    fn from(item: QueueItem) -> Self {
+        Self {
+            item_type: item.kind,
+            item_id: item.id,
+            target_type: item.target_type,
+            target_id: item.target_id,
+            summary: item.summary,
+            created_at: item.created_at,
+            claimed_by: item.claimed_by,
+            claimed_by_username: item.claimed_by_username,
+            claimed_by_display_name: item.claimed_by_display_name,
+            claim_expires_at: item.claimed_at.map(claim_expires_at),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModQueueClaimResponse {
+    /// Item type
+    pub item_type: String,
+    /// Item ID
+    pub item_id: i32,
+    /// Moderator holding the claim
+    pub claimed_by: i32,
+    /// When the claim was made or last renewed
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub claimed_at: chrono::NaiveDateTime,
+    /// When the claim lapses unless renewed
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+impl From<ModQueueClaimModel> for ModQueueClaimResponse {
+    fn from(c: ModQueueClaimModel) -> Self {
+        Self {
+            item_type: c.item_type,
+            item_id: c.item_id,
+            claimed_by: c.claimed_by,
+            claimed_at: c.claimed_at,
+            expires_at: claim_expires_at(c.claimed_at),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AssignQueueItemRequest {
+    /// Moderator to hand the item to
+    pub user_id: i32,
+}
+
+fn parse_kind(name: &str) -> AppResult<QueueItemKind> {
+    QueueItemKind::parse(name)
+        .ok_or_else(|| AppError::Validation(format!("Unknown queue item type: {}", name)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/mod/queue",
+    security(("jwt_token" = [])),
+    params(ModQueueQuery),
+    responses(
+        (status = 200, description = "Items the caller can act on, oldest first", body = PaginatedResponse<ModQueueItemResponse>),
+        (status = 400, description = "Invalid filter", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "moderation"
+)]
+pub async fn list_queue(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Query(params): Query<ModQueueQuery>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = require_permission(&auth_user, Permission::ViewReports).await?;
+
+    let kind = params.item_type.as_deref().map(parse_kind).transpose()?;
+    // Only the kinds this moderator can act on
+    let kinds: Vec<QueueItemKind> = QueueItemKind::ALL
+        .into_iter()
+        .filter(|k| kind.is_none_or(|kind| kind == *k))
+        .filter(|k| role_has_permission(&auth_user.role, k.permission()))
+        .collect();
+    if kind.is_some() && kinds.is_empty() {
+        return Err(AppError::Forbidden);
+    }
+    let claim = match params.claim.as_deref() {
+        None | Some("") | Some("all") => ClaimFilter::All,
+        Some("mine") => ClaimFilter::Mine,
+        Some("unclaimed") => ClaimFilter::Unclaimed,
+        Some(other) => {
+            return Err(AppError::Validation(format!(
+                "claim must be mine, unclaimed or all, got {}",
+                other
+            )))
+        }
+    };
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(20).min(100);
+
+    let service = ModQueueService::new(db);
+    let (items, total) = service.list(user_id, &kinds, claim, page, per_page).await?;
+    let items = items.into_iter().map(ModQueueItemResponse::from).collect();
+
+    Ok(ApiResponse::ok(PaginatedResponse::new(
+        items, total, page, per_page,
+    )))
+}
+
+/// Claiming an item you already hold renews the claim.
+#[utoipa::path(
+    post,
+    path = "/api/v1/mod/queue/{item_type}/{item_id}/claim",
+    security(("jwt_token" = [])),
+    params(
+        ("item_type" = String, Path, description = "Item type"),
+        ("item_id" = i32, Path, description = "Item ID"),
+    ),
+    responses(
+        (status = 200, description = "Item claimed", body = ModQueueClaimResponse),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Item not found or already handled", body = AppError),
+        (status = 409, description = "Claimed by another moderator", body = AppError),
+    ),
+    tag = "moderation"
+)]
+pub async fn claim_item(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path((item_type, item_id)): Path<(String, i32)>,
+) -> AppResult<impl IntoResponse> {
+    let kind = parse_kind(&item_type)?;
+    let user_id = require_permission(&auth_user, kind.permission()).await?;
+
+    let service = ModQueueService::new(db);
+    let claim = service.claim(kind, item_id, user_id).await?;
+    Ok(ApiResponse::ok(ModQueueClaimResponse::from(claim)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/mod/queue/{item_type}/{item_id}/claim",
+    security(("jwt_token" = [])),
+    params(
+        ("item_type" = String, Path, description = "Item type"),
+        ("item_id" = i32, Path, description = "Item ID"),
+    ),
+    responses(
+        (status = 200, description = "Claim released", body = String),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 409, description = "Claimed by another moderator", body = AppError),
+    ),
+    tag = "moderation"
+)]
+pub async fn release_item(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path((item_type, item_id)): Path<(String, i32)>,
+) -> AppResult<impl IntoResponse> {
+    let kind = parse_kind(&item_type)?;
+    let user_id = require_permission(&auth_user, kind.permission()).await?;
+
+    let service = ModQueueService::new(db);
+    service.release(kind, item_id, user_id).await?;
+    Ok(ApiResponse::ok(t("claim_released")))
+}
+
+/// Assigning replaces any existing claim on the item.
+#[utoipa::path(
+    put,
+    path = "/api/v1/mod/queue/{item_type}/{item_id}/assign",
+    security(("jwt_token" = [])),
+    params(
+        ("item_type" = String, Path, description = "Item type"),
+        ("item_id" = i32, Path, description = "Item ID"),
+    ),
+    request_body = AssignQueueItemRequest,
+    responses(
+        (status = 200, description = "Item assigned", body = ModQueueClaimResponse),
+        (status = 400, description = "Assignee can't handle the item", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Item not found or already handled", body = AppError),
+    ),
+    tag = "moderation"
+)]
+pub async fn assign_item(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path((item_type, item_id)): Path<(String, i32)>,
+    Json(payload): Json<AssignQueueItemRequest>,
+) -> AppResult<impl IntoResponse> {
+    let kind = parse_kind(&item_type)?;
+    require_permission(&auth_user, Permission::AssignQueueItems).await?;
+
+    let service = ModQueueService::new(db);
+    let claim = service.assign(kind, item_id, payload.user_id).await?;
+    Ok(ApiResponse::ok(ModQueueClaimResponse::from(claim)))
+}
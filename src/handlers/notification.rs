@@ -1,13 +1,19 @@
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
 use crate::middleware::AuthUser;
-use crate::models::NotificationModel;
-use crate::response::{ApiResponse, PaginatedResponse, PaginationQuery};
-use crate::services::notification::NotificationService;
+use crate::models::{NotificationModel, UserModel};
+use crate::response::{ApiResponse, PaginatedResponse};
+use crate::services::notification::{NotificationFilter, NotificationService};
+use crate::services::quiet_hours::{
+    format_time, parse_time, parse_timezone, QuietHours, QuietHoursService,
+};
+use crate::services::user::UserService;
+use crate::utils::i18n::t;
 use crate::websocket::hub::NotificationHub;
-use axum::{extract::Path, extract::Query, response::IntoResponse, Extension};
+use axum::{extract::Path, extract::Query, response::IntoResponse, Extension, Json};
 use sea_orm::DatabaseConnection;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
+use validator::Validate;
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct NotificationResponse {
@@ -26,7 +32,8 @@ pub struct NotificationResponse {
     /// Whether notification has been read
     pub is_read: bool,
     /// Creation timestamp
-    pub created_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
 }
 
 impl From<NotificationModel> for NotificationResponse {
@@ -39,7 +46,7 @@ impl From<NotificationModel> for NotificationResponse {
             target_id: n.target_id,
             message: n.message,
             is_read: n.is_read,
-            created_at: n.created_at.to_string(),
+            created_at: n.created_at,
         }
     }
 }
@@ -50,6 +57,44 @@ pub struct UnreadCountResponse {
     pub count: u64,
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NotificationListQuery {
+    /// Page number
+    pub page: Option<u64>,
+    /// Items per page
+    pub per_page: Option<u64>,
+    /// Only unread notifications when true
+    pub unread: Option<bool>,
+    /// Comma-separated kinds to include, e.g. `comment_on_post,reply_to_comment`
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+}
+
+impl NotificationListQuery {
+    fn filter(&self) -> NotificationFilter {
+        NotificationFilter {
+            unread_only: self.unread.unwrap_or(false),
+            kinds: self
+                .kind
+                .iter()
+                .flat_map(|kinds| kinds.split(','))
+                .map(str::trim)
+                .filter(|kind| !kind.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct MarkReadRequest {
+    /// Notifications to mark as read (at most 500)
+    #[validate(length(max = 500))]
+    pub ids: Option<Vec<i32>>,
+    /// Also mark everything created at or before this RFC 3339 timestamp
+    pub before: Option<String>,
+}
+
 fn get_user_id(auth_user: &AuthUser) -> AppResult<i32> {
     crate::middleware::auth::parse_user_id(auth_user)
 }
@@ -61,6 +106,8 @@ fn get_user_id(auth_user: &AuthUser) -> AppResult<i32> {
     params(
         ("page" = Option<u64>, Query, description = "Page number"),
         ("per_page" = Option<u64>, Query, description = "Items per page"),
+        ("unread" = Option<bool>, Query, description = "Only unread notifications when true"),
+        ("type" = Option<String>, Query, description = "Comma-separated kinds to include, e.g. comment_on_post,reply_to_comment"),
     ),
     responses(
         (status = 200, description = "List of notifications", body = PaginatedResponse<NotificationResponse>),
@@ -72,14 +119,16 @@ pub async fn list_notifications(
     Extension(db): Extension<DatabaseConnection>,
     Extension(hub): Extension<NotificationHub>,
     auth_user: AuthUser,
-    Query(params): Query<PaginationQuery>,
+    Query(params): Query<NotificationListQuery>,
 ) -> AppResult<impl IntoResponse> {
     let user_id = get_user_id(&auth_user)?;
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20).min(100);
 
     let service = NotificationService::new(db, hub);
-    let (notifications, total) = service.list_for_user(user_id, page, per_page).await?;
+    let (notifications, total) = service
+        .list_for_user(user_id, &params.filter(), page, per_page)
+        .await?;
     let items = notifications
         .into_iter()
         .map(NotificationResponse::from)
@@ -131,7 +180,50 @@ pub async fn mark_read(
     let user_id = get_user_id(&auth_user)?;
     let service = NotificationService::new(db, hub);
     service.mark_read(id, user_id).await?;
-    Ok(ApiResponse::ok("Notification marked as read"))
+    Ok(ApiResponse::ok(t("notification_read")))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/notifications/read",
+    security(("jwt_token" = [])),
+    request_body = MarkReadRequest,
+    responses(
+        (status = 200, description = "Selected notifications marked as read", body = serde_json::Value),
+        (status = 400, description = "Neither ids nor before given, or before is not a timestamp", body = crate::error::AppError),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+    ),
+    tag = "notifications"
+)]
+pub async fn mark_read_many(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
+    auth_user: AuthUser,
+    Json(payload): Json<MarkReadRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    let user_id = get_user_id(&auth_user)?;
+    let ids = payload.ids.unwrap_or_default();
+    let before = payload
+        .before
+        .as_deref()
+        .map(|value| {
+            chrono::DateTime::parse_from_rfc3339(value.trim())
+                .map(|dt| dt.naive_utc())
+                .map_err(|_| {
+                    AppError::Validation("before must be an RFC 3339 timestamp".to_string())
+                })
+        })
+        .transpose()?;
+    if ids.is_empty() && before.is_none() {
+        return Err(AppError::Validation(
+            "Give ids or before to choose notifications".to_string(),
+        ));
+    }
+
+    let service = NotificationService::new(db, hub);
+    let count = service.mark_read_many(user_id, &ids, before).await?;
+    Ok(ApiResponse::ok(serde_json::json!({ "marked_read": count })))
 }
 
 #[utoipa::path(
@@ -154,3 +246,146 @@ pub async fn mark_all_read(
     let count = service.mark_all_read(user_id).await?;
     Ok(ApiResponse::ok(serde_json::json!({ "marked_read": count })))
 }
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/notifications/{id}",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Notification ID")),
+    responses(
+        (status = 200, description = "Notification deleted", body = String),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+        (status = 403, description = "Not your notification", body = crate::error::AppError),
+        (status = 404, description = "Notification not found", body = crate::error::AppError),
+    ),
+    tag = "notifications"
+)]
+pub async fn delete_notification(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = get_user_id(&auth_user)?;
+    let service = NotificationService::new(db, hub);
+    service.delete(id, user_id).await?;
+    Ok(ApiResponse::ok(t("notification_deleted")))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/notifications",
+    security(("jwt_token" = [])),
+    responses(
+        (status = 200, description = "All notifications deleted", body = serde_json::Value),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+    ),
+    tag = "notifications"
+)]
+pub async fn clear_notifications(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
+    auth_user: AuthUser,
+) -> AppResult<impl IntoResponse> {
+    let user_id = get_user_id(&auth_user)?;
+    let service = NotificationService::new(db, hub);
+    let count = service.delete_all(user_id).await?;
+    Ok(ApiResponse::ok(serde_json::json!({ "deleted": count })))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuietHoursResponse {
+    /// Local start of the quiet hours, `HH:MM`; null when off
+    pub start: Option<String>,
+    /// Local end of the quiet hours, `HH:MM`; before the start when they
+    /// span midnight
+    pub end: Option<String>,
+    /// IANA timezone the hours are in
+    pub timezone: String,
+    /// Whether the quiet hours are in effect now
+    pub active: bool,
+}
+
+impl From<UserModel> for QuietHoursResponse {
+    fn from(user: UserModel) -> Self {
+        let hours = QuietHours::of(&user);
+        Self {
+            start: hours.map(|h| format_time(h.start)),
+            end: hours.map(|h| format_time(h.end)),
+            active: hours.is_some_and(|h| h.contains(chrono::Utc::now().naive_utc())),
+            timezone: user.timezone,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateQuietHoursRequest {
+    /// Local start, `HH:MM`; omit with `end` to turn quiet hours off
+    pub start: Option<String>,
+    /// Local end, `HH:MM`
+    pub end: Option<String>,
+    /// IANA timezone such as `Asia/Shanghai` (unchanged if omitted)
+    pub timezone: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/notifications/quiet-hours",
+    security(("jwt_token" = [])),
+    responses(
+        (status = 200, description = "Quiet hours", body = QuietHoursResponse),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+    ),
+    tag = "notifications"
+)]
+pub async fn get_quiet_hours(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+) -> AppResult<impl IntoResponse> {
+    let user_id = get_user_id(&auth_user)?;
+    let user = UserService::new(db).get_by_id(user_id).await?;
+    Ok(ApiResponse::ok(QuietHoursResponse::from(user)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/notifications/quiet-hours",
+    security(("jwt_token" = [])),
+    request_body = UpdateQuietHoursRequest,
+    responses(
+        (status = 200, description = "Quiet hours updated", body = QuietHoursResponse),
+        (status = 400, description = "Invalid time or timezone", body = crate::error::AppError),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+    ),
+    tag = "notifications"
+)]
+pub async fn update_quiet_hours(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Json(payload): Json<UpdateQuietHoursRequest>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = get_user_id(&auth_user)?;
+    let time = |value: &str| {
+        parse_time(value)
+            .ok_or_else(|| AppError::Validation(format!("Invalid time {}, use HH:MM", value)))
+    };
+    let window = match (payload.start.as_deref(), payload.end.as_deref()) {
+        (Some(start), Some(end)) => Some((time(start)?, time(end)?)),
+        (None, None) => None,
+        _ => {
+            return Err(AppError::Validation(
+                "start and end go together".to_string(),
+            ))
+        }
+    };
+    let timezone = payload
+        .timezone
+        .as_deref()
+        .map(parse_timezone)
+        .transpose()?;
+
+    let user = QuietHoursService::new(db)
+        .update(user_id, window, timezone)
+        .await?;
+    Ok(ApiResponse::ok(QuietHoursResponse::from(user)))
+}
@@ -0,0 +1,60 @@
+use crate::error::{AppError, AppResult};
+use crate::utils::url_sign::{url_signing_secret, verify_url_signature};
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Redirect},
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct OutboundQuery {
+    /// Destination URL (http/https)
+    pub url: String,
+    /// Signature generated when the markdown link was rendered
+    pub sig: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/out",
+    params(
+        ("url" = String, Query, description = "Destination URL"),
+        ("sig" = String, Query, description = "URL signature"),
+    ),
+    responses(
+        (status = 303, description = "Redirect to the external URL"),
+        (status = 400, description = "Invalid or unsigned URL", body = AppError),
+    ),
+    tag = "outbound"
+)]
+pub async fn outbound_redirect(
+    headers: HeaderMap,
+    Query(params): Query<OutboundQuery>,
+) -> AppResult<impl IntoResponse> {
+    let url = url::Url::parse(&params.url)
+        .map_err(|_| AppError::Validation("Invalid url".to_string()))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AppError::Validation("Unsupported url scheme".to_string()));
+    }
+
+    // Only follow links we rendered ourselves, so /out is not an open redirect.
+    if !verify_url_signature(&url_signing_secret(), &params.url, &params.sig) {
+        return Err(AppError::Validation("Invalid url signature".to_string()));
+    }
+
+    let referer = headers
+        .get(header::REFERER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    tracing::info!(
+        target: "outbound",
+        url = %params.url,
+        host = url.host_str().unwrap_or(""),
+        referer,
+        "Outbound link click"
+    );
+
+    Ok(Redirect::to(&params.url))
+}
@@ -1,13 +1,39 @@
 use crate::error::{AppError, AppResult};
-use crate::middleware::auth::{parse_user_id, require_admin, AuthUser};
-use crate::models::PostModel;
-use crate::response::{ApiResponse, PaginatedResponse};
+use crate::handlers::flair::FlairResponse;
+use crate::handlers::seo::seo_config;
+use crate::middleware::auth::{parse_user_id, require_permission, AuthUser};
+use crate::middleware::permission::Permission;
+use crate::models::{PostModel, UploadModel};
+use crate::response::{ApiResponse, Created, NoContent, PaginatedResponse};
+use crate::services::attachment::{upload_url, AttachmentService};
+use crate::services::cache::CacheService;
+use crate::services::captcha::{require_captcha, CaptchaAction, CaptchaConfig};
+use crate::services::federation::FederationService;
+use crate::services::flair::FlairService;
+use crate::services::forum::ForumService;
+use crate::services::link_preview::{normalize_link_url, LinkPreviewFetcher, STATUS_PENDING};
 use crate::services::post::PostService;
+use crate::services::post_fanout;
+use crate::services::post_read::PostReadService;
+use crate::services::search::{PostSearchFilters, PostSearchQuery, SearchIndex, SearchService};
 use crate::services::tag::TagService;
+use crate::services::user::UserService;
+use crate::services::view_counter::{ViewCounter, Viewer};
+use crate::utils::i18n::t;
+use crate::utils::markdown::proxied_image_url;
+use crate::utils::pow::{require_pow, PowAction, PowConfig};
 use crate::utils::render_markdown;
-use axum::{extract::Path, extract::Query, response::IntoResponse, Extension, Json};
+use crate::utils::slug::parse_post_ref;
+use axum::{
+    extract::{ConnectInfo, Path, Query},
+    http::HeaderMap,
+    response::IntoResponse,
+    Extension, Json,
+};
 use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use utoipa::ToSchema;
 use validator::Validate;
 
@@ -18,11 +44,39 @@ pub struct CreatePostRequest {
     /// Post title (1-200 characters)
     #[validate(length(min = 1, max = 200))]
     pub title: String,
-    /// Post content (Markdown supported)
-    #[validate(length(min = 1))]
+    /// Post content (Markdown supported); may be empty for link posts
+    #[serde(default)]
     pub content: String,
+    /// Makes this a link post: an http(s) URL, up to 2048 characters, whose
+    /// preview is fetched in the background
+    pub url: Option<String>,
     /// Tags (up to 5 tags, each max 30 characters)
     pub tags: Option<Vec<String>>,
+    /// One of the forum's flairs
+    pub flair_id: Option<i32>,
+    /// IDs of the author's own uploads to attach (up to 10)
+    pub attachment_ids: Option<Vec<i32>>,
+    /// PoW token for `create_post` with target `forum`/`forum_id`; required
+    /// while posting needs PoW
+    pub pow_token: Option<String>,
+    pub pow_nonce: Option<String>,
+    /// hCaptcha/Turnstile response; required while posting needs a CAPTCHA
+    pub captcha_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CrosspostRequest {
+    /// Forum to crosspost to
+    pub forum_id: i32,
+    /// Title of the crosspost (1-200 characters), the original's if omitted
+    #[validate(length(min = 1, max = 200))]
+    pub title: Option<String>,
+    /// PoW token for `create_post` with target `forum`/`forum_id`; required
+    /// while posting needs PoW
+    pub pow_token: Option<String>,
+    pub pow_nonce: Option<String>,
+    /// hCaptcha/Turnstile response; required while posting needs a CAPTCHA
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -30,9 +84,11 @@ pub struct UpdatePostRequest {
     /// Post title (1-200 characters)
     #[validate(length(min = 1, max = 200))]
     pub title: String,
-    /// Post content (Markdown supported)
-    #[validate(length(min = 1))]
+    /// Post content (Markdown supported); may be empty for link posts
+    #[serde(default)]
     pub content: String,
+    /// One of the forum's flairs; omit to remove the post's flair
+    pub flair_id: Option<i32>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -49,6 +105,10 @@ pub struct PostResponse {
     pub content: String,
     /// Rendered HTML content
     pub content_html: String,
+    /// Page a link post points at
+    pub url: Option<String>,
+    /// Preview of `url`, for link posts
+    pub link_preview: Option<LinkPreviewResponse>,
     /// Upvote count
     pub upvotes: i32,
     /// Downvote count
@@ -59,12 +119,39 @@ pub struct PostResponse {
     pub is_pinned: bool,
     /// Whether post is locked (no new comments)
     pub is_locked: bool,
+    /// Whether the author closed the thread to new comments
+    pub closed_by_author: bool,
+    /// Comment pinned to the top of the thread
+    pub pinned_comment_id: Option<i32>,
+    /// ID of the post's flair
+    pub flair_id: Option<i32>,
+    /// The post's flair
+    pub flair: Option<FlairResponse>,
+    /// Attached files, in order
+    pub attachments: Vec<AttachmentResponse>,
+    /// URL slug from the title, unique within the forum; the post can be
+    /// fetched as `/posts/{id}-{slug}` or `/forums/{forum}/posts/{slug}`
+    pub slug: Option<String>,
+    /// For a crosspost, ID of the post it was crossposted from
+    pub crosspost_of_id: Option<i32>,
+    /// For a crosspost, the post it was crossposted from, unless hidden
+    pub crosspost_of: Option<CrosspostSourceResponse>,
     /// Creation timestamp
-    pub created_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
     /// Last update timestamp
-    pub updated_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub updated_at: chrono::NaiveDateTime,
     /// Post tags
     pub tags: Vec<String>,
+    /// Comments by others since the caller last read the post (forum
+    /// listings, authenticated only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unread_comment_count: Option<i64>,
+    /// Whether the caller has never read the post or has unread comments
+    /// (forum listings, authenticated only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_unread: Option<bool>,
 }
 
 impl From<PostModel> for PostResponse {
@@ -77,14 +164,26 @@ impl From<PostModel> for PostResponse {
             title: p.title,
             content: p.content,
             content_html,
+            url: p.url,
+            link_preview: None,
             upvotes: p.upvotes,
             downvotes: p.downvotes,
             view_count: p.view_count,
             is_pinned: p.is_pinned,
             is_locked: p.is_locked,
-            created_at: p.created_at.to_string(),
-            updated_at: p.updated_at.to_string(),
+            closed_by_author: p.closed_by_author,
+            pinned_comment_id: p.pinned_comment_id,
+            flair_id: p.flair_id,
+            flair: None,
+            attachments: Vec::new(),
+            slug: p.slug,
+            crosspost_of_id: p.crosspost_of_id,
+            crosspost_of: None,
+            created_at: p.created_at,
+            updated_at: p.updated_at,
             tags: Vec::new(),
+            unread_comment_count: None,
+            is_unread: None,
         }
     }
 }
@@ -99,18 +198,193 @@ impl PostResponse {
             title: p.title,
             content: p.content,
             content_html,
+            url: p.url,
+            link_preview: None,
             upvotes: p.upvotes,
             downvotes: p.downvotes,
             view_count: p.view_count,
             is_pinned: p.is_pinned,
             is_locked: p.is_locked,
-            created_at: p.created_at.to_string(),
-            updated_at: p.updated_at.to_string(),
+            closed_by_author: p.closed_by_author,
+            pinned_comment_id: p.pinned_comment_id,
+            flair_id: p.flair_id,
+            flair: None,
+            attachments: Vec::new(),
+            slug: p.slug,
+            crosspost_of_id: p.crosspost_of_id,
+            crosspost_of: None,
+            created_at: p.created_at,
+            updated_at: p.updated_at,
             tags,
+            unread_comment_count: None,
+            is_unread: None,
         }
     }
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CrosspostSourceResponse {
+    /// Original post ID
+    pub id: i32,
+    /// Forum the original was posted in
+    pub forum_id: i32,
+    /// Author of the original
+    pub user_id: i32,
+    pub title: String,
+}
+
+impl From<PostModel> for CrosspostSourceResponse {
+    fn from(p: PostModel) -> Self {
+        Self {
+            id: p.id,
+            forum_id: p.forum_id,
+            user_id: p.user_id,
+            title: p.title,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AttachmentResponse {
+    /// Upload ID
+    pub id: i32,
+    /// URL of the file
+    pub url: String,
+    pub mime: String,
+    /// In bytes
+    pub size: i64,
+}
+
+impl From<UploadModel> for AttachmentResponse {
+    fn from(u: UploadModel) -> Self {
+        Self {
+            id: u.id,
+            url: upload_url(&u),
+            mime: u.mime,
+            size: u.size,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LinkPreviewResponse {
+    /// `pending` until the page has been fetched, then `ready` or `failed`
+    pub status: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Loaded through the image proxy when that is enabled
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+}
+
+/// Fill in `link_preview` for the link posts among `posts`.
+pub(crate) async fn attach_link_previews(
+    db: &DatabaseConnection,
+    posts: &mut [PostResponse],
+) -> AppResult<()> {
+    let fetcher = LinkPreviewFetcher::new(db.clone());
+    let urls: Vec<&str> = posts.iter().filter_map(|p| p.url.as_deref()).collect();
+    let mut previews = fetcher.previews_for(&urls).await?;
+
+    for post in posts.iter_mut() {
+        let Some(url) = &post.url else {
+            continue;
+        };
+        post.link_preview = match previews.remove(url) {
+            Some(p) => Some(LinkPreviewResponse {
+                status: p.status,
+                title: p.title,
+                description: p.description,
+                image_url: p.image_url.as_deref().map(proxied_image_url),
+                site_name: p.site_name,
+            }),
+            None if fetcher.enabled() => Some(LinkPreviewResponse {
+                status: STATUS_PENDING.to_string(),
+                title: None,
+                description: None,
+                image_url: None,
+                site_name: None,
+            }),
+            None => None,
+        };
+    }
+    Ok(())
+}
+
+/// Fill in `flair` for the flaired posts among `posts`.
+pub(crate) async fn attach_flairs(
+    db: &DatabaseConnection,
+    posts: &mut [PostResponse],
+) -> AppResult<()> {
+    let ids: Vec<i32> = posts.iter().filter_map(|p| p.flair_id).collect();
+    let flairs = FlairService::new(db.clone()).get_many(&ids).await?;
+    for post in posts.iter_mut() {
+        post.flair = post
+            .flair_id
+            .and_then(|id| flairs.get(&id).cloned())
+            .map(FlairResponse::from);
+    }
+    Ok(())
+}
+
+/// Fill in `attachments` for `posts`.
+pub(crate) async fn attach_attachments(
+    db: &DatabaseConnection,
+    posts: &mut [PostResponse],
+) -> AppResult<()> {
+    let ids: Vec<i32> = posts.iter().map(|p| p.id).collect();
+    let mut attachments = AttachmentService::new(db.clone()).for_posts(&ids).await?;
+    for post in posts.iter_mut() {
+        post.attachments = attachments
+            .remove(&post.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(AttachmentResponse::from)
+            .collect();
+    }
+    Ok(())
+}
+
+/// Fill in `crosspost_of` for the crossposts among `posts`.
+pub(crate) async fn attach_crosspost_sources(
+    db: &DatabaseConnection,
+    posts: &mut [PostResponse],
+) -> AppResult<()> {
+    let ids: Vec<i32> = posts.iter().filter_map(|p| p.crosspost_of_id).collect();
+    let originals = PostService::new(db.clone()).get_many(&ids).await?;
+    for post in posts.iter_mut() {
+        post.crosspost_of = post
+            .crosspost_of_id
+            .and_then(|id| originals.get(&id).cloned())
+            .filter(|p| !p.is_hidden)
+            .map(CrosspostSourceResponse::from);
+    }
+    Ok(())
+}
+
+/// Response for a single post, with its link preview, flair, attachments
+/// and crosspost source.
+async fn post_response(
+    db: &DatabaseConnection,
+    post: PostModel,
+    tags: Vec<String>,
+) -> AppResult<PostResponse> {
+    let mut resp = PostResponse::with_tags(post, tags);
+    attach_link_previews(db, std::slice::from_mut(&mut resp)).await?;
+    attach_flairs(db, std::slice::from_mut(&mut resp)).await?;
+    attach_attachments(db, std::slice::from_mut(&mut resp)).await?;
+    attach_crosspost_sources(db, std::slice::from_mut(&mut resp)).await?;
+    Ok(resp)
+}
+
+fn make_post_service(db: DatabaseConnection, cache: Option<CacheService>) -> PostService {
+    let service = PostService::new(db);
+    match cache {
+        Some(c) => service.with_cache(c),
+        None => service,
+    }
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct PostListQuery {
     /// Page number
@@ -119,6 +393,8 @@ pub struct PostListQuery {
     pub per_page: Option<u64>,
     /// Sort order: new, top, hot
     pub sort: Option<String>,
+    /// Only posts with this flair
+    pub flair: Option<i32>,
 }
 
 #[utoipa::path(
@@ -128,39 +404,69 @@ pub struct PostListQuery {
         ("forum_id" = i32, Path, description = "Forum ID"),
         ("page" = Option<u64>, Query, description = "Page number"),
         ("per_page" = Option<u64>, Query, description = "Items per page"),
-        ("sort" = Option<String>, Query, description = "Sort order: new, top, hot"),
+        ("sort" = Option<String>, Query, description = "Sort order: new, top, hot; defaults to the user's preference, else new"),
+        ("flair" = Option<i32>, Query, description = "Only posts with this flair ID"),
     ),
     responses(
-        (status = 200, description = "List of posts", body = PaginatedResponse<PostResponse>),
+        (status = 200, description = "List of posts; includes unread state when authenticated", body = PaginatedResponse<PostResponse>),
     ),
     tag = "posts"
 )]
 pub async fn list_posts(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: Option<AuthUser>,
     Path(forum_id): Path<i32>,
     Query(params): Query<PostListQuery>,
 ) -> AppResult<impl IntoResponse> {
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20).min(100);
-    let sort = params.sort.as_deref().unwrap_or("new");
+    let sort = match (params.sort, &auth_user) {
+        (Some(sort), _) => sort,
+        (None, Some(auth_user)) => {
+            UserService::new(db.clone())
+                .get_by_id(parse_user_id(auth_user)?)
+                .await?
+                .default_post_sort
+        }
+        (None, None) => "new".to_string(),
+    };
 
-    let service = PostService::new(db.clone());
+    let service = make_post_service(db.clone(), cache.map(|c| c.0));
     let (posts, total) = service
-        .list_by_forum(forum_id, page, per_page, sort)
+        .list_by_forum(forum_id, params.flair, page, per_page, &sort)
         .await?;
 
     // Batch-fetch tags for all posts in the page
     let post_ids: Vec<i32> = posts.iter().map(|p| p.id).collect();
-    let tag_service = TagService::new(db);
+    let tag_service = TagService::new(db.clone());
     let tags_map = tag_service.get_tags_for_posts(&post_ids).await?;
 
-    let items: Vec<PostResponse> = posts
+    let unread = match &auth_user {
+        Some(auth_user) => {
+            let user_id = parse_user_id(auth_user)?;
+            PostReadService::new(db.clone())
+                .unread_states(user_id, &post_ids)
+                .await?
+        }
+        None => HashMap::new(),
+    };
+
+    let mut items: Vec<PostResponse> = posts
         .into_iter()
         .map(|p| {
             let tags = tags_map.get(&p.id).cloned().unwrap_or_default();
-            PostResponse::with_tags(p, tags)
+            let state = unread.get(&p.id).copied();
+            let mut resp = PostResponse::with_tags(p, tags);
+            resp.unread_comment_count = state.map(|s| s.unread_comment_count);
+            resp.is_unread = state.map(|s| s.is_unread);
+            resp
         })
         .collect();
+    attach_link_previews(&db, &mut items).await?;
+    attach_flairs(&db, &mut items).await?;
+    attach_attachments(&db, &mut items).await?;
+    attach_crosspost_sources(&db, &mut items).await?;
 
     Ok(ApiResponse::ok(PaginatedResponse::new(
         items, total, page, per_page,
@@ -170,7 +476,7 @@ pub async fn list_posts(
 #[utoipa::path(
     get,
     path = "/api/v1/posts/{id}",
-    params(("id" = i32, Path, description = "Post ID")),
+    params(("id" = String, Path, description = "Post ID, optionally followed by `-` and the post's slug")),
     responses(
         (status = 200, description = "Post details", body = PostResponse),
         (status = 404, description = "Post not found", body = AppError),
@@ -179,17 +485,72 @@ pub async fn list_posts(
 )]
 pub async fn get_post(
     Extension(db): Extension<DatabaseConnection>,
-    Path(id): Path<i32>,
+    cache: Option<Extension<CacheService>>,
+    Extension(views): Extension<ViewCounter>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: Option<AuthUser>,
+    Path(id): Path<String>,
 ) -> AppResult<impl IntoResponse> {
-    let service = PostService::new(db.clone());
-    service.increment_view_count(id).await?;
-    let post = service.get_by_id(id).await?;
+    let id = parse_post_ref(&id).ok_or(AppError::NotFound)?;
+    let post = view_post(&db, cache, &views, addr, auth_user.as_ref(), id).await?;
+    Ok(ApiResponse::ok(post))
+}
 
-    let tag_service = TagService::new(db);
+#[utoipa::path(
+    get,
+    path = "/api/v1/forums/{slug}/posts/{post_slug}",
+    params(
+        ("slug" = String, Path, description = "Forum slug"),
+        ("post_slug" = String, Path, description = "Post slug"),
+    ),
+    responses(
+        (status = 200, description = "Post details", body = PostResponse),
+        (status = 404, description = "Forum or post not found", body = AppError),
+    ),
+    tag = "posts"
+)]
+pub async fn get_post_by_slug(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    Extension(views): Extension<ViewCounter>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    auth_user: Option<AuthUser>,
+    Path((slug, post_slug)): Path<(String, String)>,
+) -> AppResult<impl IntoResponse> {
+    let forum = ForumService::new(db.clone()).get_by_slug(&slug).await?;
+    let id = PostService::new(db.clone())
+        .get_by_slug(forum.id, &post_slug)
+        .await?
+        .id;
+    let post = view_post(&db, cache, &views, addr, auth_user.as_ref(), id).await?;
+    Ok(ApiResponse::ok(post))
+}
+
+/// Record a view of the post and build its full response.
+async fn view_post(
+    db: &DatabaseConnection,
+    cache: Option<Extension<CacheService>>,
+    views: &ViewCounter,
+    addr: SocketAddr,
+    auth_user: Option<&AuthUser>,
+    id: i32,
+) -> AppResult<PostResponse> {
+    let viewer = match auth_user {
+        Some(auth_user) => Viewer::User(parse_user_id(auth_user)?),
+        None => Viewer::Ip(addr.ip()),
+    };
+    views.record_view(db, id, viewer).await?;
+
+    let service = make_post_service(db.clone(), cache.map(|c| c.0));
+    let mut post = service.get_by_id_cached(id).await?;
+    // Include views that have not been flushed yet
+    post.view_count += views.pending_for(id).await as i32;
+
+    let tag_service = TagService::new(db.clone());
     let tags = tag_service.get_post_tags(id).await?;
     let tag_names: Vec<String> = tags.into_iter().map(|t| t.name).collect();
 
-    Ok(ApiResponse::ok(PostResponse::with_tags(post, tag_names)))
+    post_response(db, post, tag_names).await
 }
 
 #[utoipa::path(
@@ -206,12 +567,13 @@ pub async fn get_post(
 )]
 pub async fn create_post(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    Extension(search): Extension<SearchIndex>,
     auth_user: AuthUser,
+    headers: HeaderMap,
     Json(payload): Json<CreatePostRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
     // Validate tags
     let tag_names = payload.tags.unwrap_or_default();
@@ -226,7 +588,28 @@ pub async fn create_post(
         }
     }
 
+    let url = payload.url.as_deref().map(normalize_link_url).transpose()?;
+
     let user_id = parse_user_id(&auth_user)?;
+    let attachment_service = AttachmentService::new(db.clone());
+    let attachments = attachment_service
+        .attachable(user_id, &payload.attachment_ids.unwrap_or_default())
+        .await?;
+    require_pow(
+        &PowConfig::from_env()?,
+        PowAction::CreatePost,
+        "forum",
+        payload.forum_id,
+        user_id,
+        payload.pow_token.as_deref(),
+        payload.pow_nonce.as_deref(),
+    )?;
+    require_captcha(
+        &CaptchaConfig::from_env(),
+        CaptchaAction::CreatePost,
+        payload.captcha_token.as_deref(),
+    )
+    .await?;
 
     // Verify forum exists
     let forum_service = crate::services::forum::ForumService::new(db.clone());
@@ -235,24 +618,136 @@ pub async fn create_post(
         .await
         .map_err(|_| AppError::Validation("Forum not found".to_string()))?;
 
-    let service = PostService::new(db.clone());
+    let service = make_post_service(db.clone(), cache.map(|c| c.0));
     let post = service
-        .create(user_id, payload.forum_id, &payload.title, &payload.content)
+        .create(
+            user_id,
+            payload.forum_id,
+            &payload.title,
+            &payload.content,
+            url.as_deref(),
+            payload.flair_id,
+        )
         .await?;
+    if let Some(url) = url {
+        LinkPreviewFetcher::new(db.clone()).spawn_refresh(url);
+    }
+    attachment_service.attach(post.id, &attachments).await?;
 
     // Assign tags
     let mut response_tags = Vec::new();
     if !tag_names.is_empty() {
-        let tag_service = TagService::new(db);
+        let tag_service = TagService::new(db.clone());
         let tags = tag_service.get_or_create_tags(tag_names).await?;
         response_tags = tags.iter().map(|t| t.name.clone()).collect();
         let tag_ids: Vec<i32> = tags.into_iter().map(|t| t.id).collect();
         tag_service.set_post_tags(post.id, tag_ids).await?;
     }
 
-    Ok(ApiResponse::ok(PostResponse::with_tags(
-        post,
-        response_tags,
+    // After tagging so the index sees the post's tags
+    search.refresh_post(&db, post.id).await;
+
+    // Followers on other servers get the post through the delivery queue
+    let federation = FederationService::new(db.clone(), &seo_config(&headers));
+    if federation.enabled() {
+        if let Err(e) = federation.publish_post(&post).await {
+            tracing::warn!("Failed to queue federation of post {}: {}", post.id, e);
+        }
+    }
+
+    // Local followers are notified from the queue, not while we wait
+    if let Err(e) = post_fanout::enqueue(&db, &post).await {
+        tracing::warn!(
+            "Failed to queue follower notifications for post {}: {}",
+            post.id,
+            e
+        );
+    }
+
+    Ok(Created(ApiResponse::ok(
+        post_response(&db, post, response_tags).await?,
+    )))
+}
+
+/// Crosspost a post to another forum. The crosspost is a new post by the
+/// caller with the original's content, link and tags, pointing back at the
+/// original; it has its own comments and votes.
+#[utoipa::path(
+    post,
+    path = "/api/v1/posts/{id}/crosspost",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Post ID")),
+    request_body = CrosspostRequest,
+    responses(
+        (status = 200, description = "Crosspost created", body = PostResponse),
+        (status = 400, description = "Validation error, or the post is already in that forum", body = AppError),
+        (status = 401, description = "Unauthorized", body = AppError),
+        (status = 404, description = "Post not found", body = AppError),
+        (status = 409, description = "The post is already crossposted to that forum", body = AppError),
+    ),
+    tag = "posts"
+)]
+pub async fn crosspost_post(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    Extension(search): Extension<SearchIndex>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<CrosspostRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+
+    let user_id = parse_user_id(&auth_user)?;
+    require_pow(
+        &PowConfig::from_env()?,
+        PowAction::CreatePost,
+        "forum",
+        payload.forum_id,
+        user_id,
+        payload.pow_token.as_deref(),
+        payload.pow_nonce.as_deref(),
+    )?;
+    require_captcha(
+        &CaptchaConfig::from_env(),
+        CaptchaAction::CreatePost,
+        payload.captcha_token.as_deref(),
+    )
+    .await?;
+
+    let service = make_post_service(db.clone(), cache.map(|c| c.0));
+    let post = service
+        .crosspost(id, user_id, payload.forum_id, payload.title.as_deref())
+        .await?;
+
+    let tag_service = TagService::new(db.clone());
+    let tags = tag_service
+        .get_post_tags(post.crosspost_of_id.unwrap_or(id))
+        .await?;
+    let tag_names: Vec<String> = tags.iter().map(|t| t.name.clone()).collect();
+    if !tags.is_empty() {
+        let tag_ids: Vec<i32> = tags.into_iter().map(|t| t.id).collect();
+        tag_service.set_post_tags(post.id, tag_ids).await?;
+    }
+
+    search.refresh_post(&db, post.id).await;
+
+    let federation = FederationService::new(db.clone(), &seo_config(&headers));
+    if federation.enabled() {
+        if let Err(e) = federation.publish_post(&post).await {
+            tracing::warn!("Failed to queue federation of post {}: {}", post.id, e);
+        }
+    }
+    if let Err(e) = post_fanout::enqueue(&db, &post).await {
+        tracing::warn!(
+            "Failed to queue follower notifications for post {}: {}",
+            post.id,
+            e
+        );
+    }
+
+    Ok(Created(ApiResponse::ok(
+        post_response(&db, post, tag_names).await?,
     )))
 }
 
@@ -271,22 +766,29 @@ pub async fn create_post(
 )]
 pub async fn update_post(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    Extension(search): Extension<SearchIndex>,
     auth_user: AuthUser,
     Path(id): Path<i32>,
     Json(payload): Json<UpdatePostRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
     let user_id = parse_user_id(&auth_user)?;
 
-    let service = PostService::new(db);
+    let service = make_post_service(db.clone(), cache.map(|c| c.0));
     let post = service
-        .update(id, user_id, &payload.title, &payload.content)
+        .update(
+            id,
+            user_id,
+            &payload.title,
+            &payload.content,
+            payload.flair_id,
+        )
         .await?;
+    search.refresh_post(&db, post.id).await;
 
-    Ok(ApiResponse::ok(PostResponse::from(post)))
+    Ok(ApiResponse::ok(post_response(&db, post, Vec::new()).await?))
 }
 
 #[utoipa::path(
@@ -302,19 +804,24 @@ pub async fn update_post(
 )]
 pub async fn delete_post(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    Extension(search): Extension<SearchIndex>,
     auth_user: AuthUser,
     Path(id): Path<i32>,
 ) -> AppResult<impl IntoResponse> {
     let user_id = parse_user_id(&auth_user)?;
 
-    let service = PostService::new(db.clone());
-    service.delete(id, user_id).await?;
+    let service = make_post_service(db.clone(), cache.map(|c| c.0));
+    let crossposts = service.delete(id, user_id).await?;
+    for post_id in std::iter::once(id).chain(crossposts) {
+        search.refresh_post(&db, post_id).await;
+    }
 
     // 回滚该帖产生的积分（如果有）
     let points = crate::services::points::PointsService::new(db);
     let _ = points.rollback_by_ref("post", id).await;
 
-    Ok(ApiResponse::ok("Post deleted"))
+    Ok(NoContent(ApiResponse::ok(t("post_deleted"))))
 }
 
 #[utoipa::path(
@@ -324,20 +831,55 @@ pub async fn delete_post(
     params(("id" = i32, Path, description = "Post ID")),
     responses(
         (status = 200, description = "Post pin toggled", body = PostResponse),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
     ),
     tag = "posts"
 )]
 pub async fn pin_post(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
     auth_user: AuthUser,
     Path(id): Path<i32>,
 ) -> AppResult<impl IntoResponse> {
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::PinPosts).await?;
 
-    let service = PostService::new(db);
+    let service = make_post_service(db.clone(), cache.map(|c| c.0));
     let post = service.toggle_pin(id).await?;
-    Ok(ApiResponse::ok(PostResponse::from(post)))
+    Ok(ApiResponse::ok(post_response(&db, post, Vec::new()).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/posts/{id}/pin-comment/{comment_id}",
+    security(("jwt_token" = [])),
+    params(
+        ("id" = i32, Path, description = "Post ID"),
+        ("comment_id" = i32, Path, description = "Top-level comment ID"),
+    ),
+    responses(
+        (status = 200, description = "Pinned comment toggled", body = PostResponse),
+        (status = 400, description = "Comment is a reply", body = AppError),
+        (status = 403, description = "Not the post author and insufficient permissions", body = AppError),
+        (status = 404, description = "Post or comment not found", body = AppError),
+    ),
+    tag = "posts"
+)]
+pub async fn pin_comment(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+    Path((id, comment_id)): Path<(i32, i32)>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+
+    let service = make_post_service(db.clone(), cache.map(|c| c.0));
+    let post = service.get_by_id(id).await?;
+    if post.user_id != user_id {
+        require_permission(&auth_user, Permission::PinComments).await?;
+    }
+
+    let post = service.toggle_pinned_comment(id, comment_id).await?;
+    Ok(ApiResponse::ok(post_response(&db, post, Vec::new()).await?))
 }
 
 #[utoipa::path(
@@ -347,20 +889,51 @@ pub async fn pin_post(
     params(("id" = i32, Path, description = "Post ID")),
     responses(
         (status = 200, description = "Post lock toggled", body = PostResponse),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
     ),
     tag = "posts"
 )]
 pub async fn lock_post(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
     auth_user: AuthUser,
     Path(id): Path<i32>,
 ) -> AppResult<impl IntoResponse> {
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::LockPosts).await?;
 
-    let service = PostService::new(db);
+    let service = make_post_service(db.clone(), cache.map(|c| c.0));
     let post = service.toggle_lock(id).await?;
-    Ok(ApiResponse::ok(PostResponse::from(post)))
+    Ok(ApiResponse::ok(post_response(&db, post, Vec::new()).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/posts/{id}/close",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "Thread closed or reopened", body = PostResponse),
+        (status = 403, description = "Not the post author and insufficient permissions", body = AppError),
+        (status = 404, description = "Post not found", body = AppError),
+    ),
+    tag = "posts"
+)]
+pub async fn close_post(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+
+    let service = make_post_service(db.clone(), cache.map(|c| c.0));
+    let post = service.get_by_id(id).await?;
+    if post.user_id != user_id {
+        require_permission(&auth_user, Permission::LockPosts).await?;
+    }
+
+    let post = service.toggle_closed(id).await?;
+    Ok(ApiResponse::ok(post_response(&db, post, Vec::new()).await?))
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -375,6 +948,78 @@ pub struct SearchPostsQuery {
     pub per_page: Option<u64>,
     /// Sort order: relevance, new, top
     pub sort: Option<String>,
+    /// Only posts by this username
+    pub author: Option<String>,
+    /// Only posts carrying this tag (name or slug)
+    pub tag: Option<String>,
+    /// Only posts created at or after this time (RFC 3339 or YYYY-MM-DD)
+    pub created_after: Option<String>,
+    /// Only posts created before this time (RFC 3339 or YYYY-MM-DD)
+    pub created_before: Option<String>,
+    /// Only posts whose score (upvotes - downvotes) is at least this
+    pub min_score: Option<i32>,
+    /// Comma-separated content requirements; supported: image
+    pub has: Option<String>,
+}
+
+/// Parse an RFC 3339 timestamp, or a bare date meaning midnight UTC.
+fn parse_search_date(value: &str, field: &str) -> AppResult<chrono::NaiveDateTime> {
+    let value = value.trim();
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.naive_utc());
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|d| d.and_time(chrono::NaiveTime::MIN))
+        .map_err(|_| {
+            AppError::Validation(format!(
+                "{} must be an RFC 3339 timestamp or YYYY-MM-DD",
+                field
+            ))
+        })
+}
+
+fn parse_search_filters(params: &SearchPostsQuery) -> AppResult<PostSearchFilters> {
+    let non_empty = |v: &Option<String>| {
+        v.as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+
+    let mut filters = PostSearchFilters {
+        author: non_empty(&params.author),
+        tag: non_empty(&params.tag),
+        min_score: params.min_score,
+        ..Default::default()
+    };
+    if let Some(v) = non_empty(&params.created_after) {
+        filters.created_after = Some(parse_search_date(&v, "created_after")?);
+    }
+    if let Some(v) = non_empty(&params.created_before) {
+        filters.created_before = Some(parse_search_date(&v, "created_before")?);
+    }
+    for item in params.has.as_deref().unwrap_or("").split(',') {
+        match item.trim().to_ascii_lowercase().as_str() {
+            "" => {}
+            "image" => filters.has_image = true,
+            other => {
+                return Err(AppError::Validation(format!(
+                    "Unsupported has filter: {}",
+                    other
+                )))
+            }
+        }
+    }
+    Ok(filters)
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchPostsResponse {
+    #[serde(flatten)]
+    pub page: PaginatedResponse<PostResponse>,
+    /// Suggested query when no exact matches were found and the results come
+    /// from the fuzzy title match
+    pub did_you_mean: Option<String>,
 }
 
 #[utoipa::path(
@@ -386,15 +1031,22 @@ pub struct SearchPostsQuery {
         ("page" = Option<u64>, Query, description = "Page number"),
         ("per_page" = Option<u64>, Query, description = "Items per page"),
         ("sort" = Option<String>, Query, description = "Sort: relevance, new, top"),
+        ("author" = Option<String>, Query, description = "Only posts by this username"),
+        ("tag" = Option<String>, Query, description = "Only posts with this tag name or slug"),
+        ("created_after" = Option<String>, Query, description = "Created at or after: RFC 3339 timestamp or YYYY-MM-DD (midnight UTC)"),
+        ("created_before" = Option<String>, Query, description = "Created before: RFC 3339 timestamp or YYYY-MM-DD (midnight UTC)"),
+        ("min_score" = Option<i32>, Query, description = "Minimum score (upvotes - downvotes)"),
+        ("has" = Option<String>, Query, description = "Comma-separated content requirements; supported: image"),
     ),
     responses(
-        (status = 200, description = "Search results", body = PaginatedResponse<PostResponse>),
+        (status = 200, description = "Search results", body = SearchPostsResponse),
         (status = 400, description = "Invalid query", body = AppError),
     ),
     tag = "posts"
 )]
 pub async fn search_posts(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(search): Extension<SearchIndex>,
     Query(params): Query<SearchPostsQuery>,
 ) -> AppResult<impl IntoResponse> {
     let q = params.q.trim();
@@ -406,15 +1058,28 @@ pub async fn search_posts(
 
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20).min(100);
-    let sort = params.sort.as_deref().unwrap_or("relevance");
+    let filters = parse_search_filters(&params)?;
+    let sort = params.sort.unwrap_or_else(|| "relevance".to_string());
 
-    let service = PostService::new(db);
-    let (posts, total) = service
-        .search(q, params.forum_id, page, per_page, sort)
+    let service = SearchService::new(db.clone(), search);
+    let found = service
+        .search_posts(&PostSearchQuery {
+            q: q.to_string(),
+            forum_id: params.forum_id,
+            page,
+            per_page,
+            sort,
+            filters,
+        })
         .await?;
-    let items = posts.into_iter().map(PostResponse::from).collect();
+    let mut items: Vec<PostResponse> = found.posts.into_iter().map(PostResponse::from).collect();
+    attach_link_previews(&db, &mut items).await?;
+    attach_flairs(&db, &mut items).await?;
+    attach_attachments(&db, &mut items).await?;
+    attach_crosspost_sources(&db, &mut items).await?;
 
-    Ok(ApiResponse::ok(PaginatedResponse::new(
-        items, total, page, per_page,
-    )))
+    Ok(ApiResponse::ok(SearchPostsResponse {
+        page: PaginatedResponse::new(items, found.total, page, per_page),
+        did_you_mean: found.did_you_mean,
+    }))
 }
@@ -0,0 +1,44 @@
+use crate::error::AppResult;
+use crate::middleware::auth::parse_user_id;
+use crate::middleware::AuthUser;
+use crate::response::ApiResponse;
+use crate::services::post_read::PostReadService;
+use axum::{extract::Path, response::IntoResponse, Extension};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PostReadResponse {
+    /// Post ID
+    pub post_id: i32,
+    /// When the post was marked read
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub last_read_at: chrono::NaiveDateTime,
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/posts/{id}/read",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "Post marked as read", body = PostReadResponse),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+        (status = 404, description = "Post not found", body = crate::error::AppError),
+    ),
+    tag = "posts"
+)]
+pub async fn mark_post_read(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(post_id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let service = PostReadService::new(db);
+    let last_read_at = service.mark_read(user_id, post_id).await?;
+    Ok(ApiResponse::ok(PostReadResponse {
+        post_id,
+        last_read_at,
+    }))
+}
@@ -1,9 +1,10 @@
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
 use crate::middleware::auth::parse_user_id;
 use crate::middleware::AuthUser;
 use crate::response::ApiResponse;
+use crate::services::captcha::{CaptchaAction, CaptchaConfig};
 use crate::utils::pow::{
-    generate_salt, now_epoch_seconds, sign_challenge, PowChallenge, PowConfig,
+    generate_salt, now_epoch_seconds, sign_challenge, PowAction, PowChallenge, PowConfig,
 };
 use axum::{response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
@@ -11,8 +12,14 @@ use utoipa::ToSchema;
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct PowChallengeRequest {
+    /// `vote`, `register`, `create_post` or `report`
     pub action: String,
+    /// What the action applies to: `post`/`comment` for votes, `forum` for
+    /// new posts, the report's target type for reports; unused for
+    /// registration
+    #[serde(default)]
     pub target_type: String,
+    #[serde(default)]
     pub target_id: i32,
 }
 
@@ -21,24 +28,103 @@ pub struct PowChallengeResponse {
     pub pow_token: String,
     pub difficulty: u8,
     pub expires_at: i64,
+    /// Whether the action currently requires a solved challenge
+    pub required: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PowActionPolicy {
+    pub action: String,
+    /// Whether the action needs a solved PoW challenge
+    pub required: bool,
+    /// Difficulty a challenge issued now would have
+    pub difficulty: u8,
+    /// Whether the action needs a CAPTCHA as well
+    pub captcha_required: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CaptchaPolicy {
+    /// `hcaptcha` or `turnstile`
+    pub provider: String,
+    pub site_key: Option<String>,
+    /// Whether login asks for a CAPTCHA after repeated failures
+    pub login_required: bool,
+    /// Failed logins for a username before login asks for one
+    pub login_after_failures: u32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PowPolicyResponse {
+    pub actions: Vec<PowActionPolicy>,
+    /// How long an issued challenge stays valid
+    pub ttl_seconds: i64,
+    /// Absent when no CAPTCHA provider is configured
+    pub captcha: Option<CaptchaPolicy>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/pow/challenge",
+    responses(
+        (status = 200, description = "Which actions require PoW (at what difficulty) or a CAPTCHA", body = PowPolicyResponse),
+    ),
+    tag = "pow"
+)]
+pub async fn get_pow_policy() -> AppResult<impl IntoResponse> {
+    let cfg = PowConfig::from_env()?;
+    let captcha = CaptchaConfig::from_env();
+    let actions = PowAction::ALL
+        .into_iter()
+        .map(|action| PowActionPolicy {
+            action: action.as_str().to_string(),
+            required: cfg.requires(action),
+            difficulty: cfg.difficulty_for(action),
+            captcha_required: CaptchaAction::parse(action.as_str())
+                .is_some_and(|a| captcha.requires(a)),
+        })
+        .collect();
+
+    Ok(ApiResponse::ok(PowPolicyResponse {
+        actions,
+        ttl_seconds: cfg.ttl_seconds,
+        captcha: captcha.provider.map(|provider| CaptchaPolicy {
+            provider: provider.as_str().to_string(),
+            site_key: captcha.site_key.clone(),
+            login_required: captcha.requires(CaptchaAction::Login),
+            login_after_failures: captcha.login_after_failures,
+        }),
+    }))
+}
+
+/// Registration challenges are anonymous; every other action needs a
+/// signed-in user, whom the challenge is bound to.
 #[utoipa::path(
     post,
     path = "/api/v1/pow/challenge",
-    security(("jwt_token" = [])),
+    security((), ("jwt_token" = [])),
     request_body = PowChallengeRequest,
     responses(
         (status = 200, description = "PoW challenge", body = PowChallengeResponse),
+        (status = 400, description = "Unknown action", body = crate::error::AppError),
         (status = 401, description = "Unauthorized", body = crate::error::AppError),
     ),
     tag = "pow"
 )]
 pub async fn create_pow_challenge(
-    auth_user: AuthUser,
+    auth_user: Option<AuthUser>,
     Json(payload): Json<PowChallengeRequest>,
 ) -> AppResult<impl IntoResponse> {
-    let user_id = parse_user_id(&auth_user)?;
+    let action = PowAction::parse(&payload.action).ok_or_else(|| {
+        AppError::Validation(
+            "Invalid action. Must be one of: vote, register, create_post, report".to_string(),
+        )
+    })?;
+    let user_id = match (action, &auth_user) {
+        (PowAction::Register, _) => 0,
+        (_, Some(auth_user)) => parse_user_id(auth_user)?,
+        (_, None) => return Err(AppError::Unauthorized),
+    };
     let cfg = PowConfig::from_env()?;
 
     let now = now_epoch_seconds();
@@ -52,7 +138,7 @@ pub async fn create_pow_challenge(
         user_id,
         issued_at: now,
         expires_at,
-        difficulty: cfg.difficulty,
+        difficulty: cfg.difficulty_for(action),
         salt: generate_salt(),
     };
 
@@ -62,5 +148,6 @@ pub async fn create_pow_challenge(
         pow_token,
         difficulty: challenge.difficulty,
         expires_at: challenge.expires_at,
+        required: cfg.requires(action),
     }))
 }
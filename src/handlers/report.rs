@@ -1,8 +1,18 @@
 use crate::error::{AppError, AppResult};
-use crate::middleware::auth::{parse_user_id, require_admin, AuthUser};
+use crate::middleware::auth::{
+    invalidate_cached_auth, parse_user_id, require_permission, AuthUser,
+};
+use crate::middleware::permission::Permission;
 use crate::models::ReportModel;
-use crate::response::{ApiResponse, PaginatedResponse};
-use crate::services::report::ReportService;
+use crate::response::{ApiResponse, Created, PaginatedResponse};
+use crate::services::cache::CacheService;
+use crate::services::captcha::{require_captcha, CaptchaAction, CaptchaConfig};
+use crate::services::notification::NotificationService;
+use crate::services::post::{invalidate_post_cache, PostService};
+use crate::services::report::{ReportAction, ReportService};
+use crate::services::search::SearchIndex;
+use crate::utils::pow::{require_pow, PowAction, PowConfig};
+use crate::websocket::hub::NotificationHub;
 use axum::{extract::Path, extract::Query, response::IntoResponse, Extension, Json};
 use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
@@ -21,6 +31,12 @@ pub struct CreateReportRequest {
     pub reason: String,
     /// Detailed description
     pub description: Option<String>,
+    /// PoW token for `report` with this report's target; required while
+    /// reporting needs PoW
+    pub pow_token: Option<String>,
+    pub pow_nonce: Option<String>,
+    /// hCaptcha/Turnstile response; required while reporting needs a CAPTCHA
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -35,9 +51,13 @@ pub struct ListReportsQuery {
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct ResolveReportRequest {
-    /// Action taken (1-20 characters)
+    /// Action to take: dismiss, hide_content, delete_content, warn_user or
+    /// ban_user (`hide` and `delete` are accepted as aliases)
     #[validate(length(min = 1, max = 20))]
     pub action: String,
+    /// Explanation recorded with the action; sent to a warned user
+    #[validate(length(max = 1000))]
+    pub note: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -58,10 +78,16 @@ pub struct ReportResponse {
     pub status: String,
     /// Admin user ID who resolved
     pub resolved_by: Option<i32>,
+    /// Action taken when resolving
+    pub action: Option<String>,
+    /// Moderator's note on the resolution
+    pub resolution_note: Option<String>,
     /// Resolution timestamp
-    pub resolved_at: Option<String>,
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub resolved_at: Option<chrono::NaiveDateTime>,
     /// Creation timestamp
-    pub created_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
 }
 
 impl From<ReportModel> for ReportResponse {
@@ -75,8 +101,10 @@ impl From<ReportModel> for ReportResponse {
             description: r.description,
             status: r.status,
             resolved_by: r.resolved_by,
-            resolved_at: r.resolved_at.map(|t| t.to_string()),
-            created_at: r.created_at.to_string(),
+            action: r.action,
+            resolution_note: r.resolution_note,
+            resolved_at: r.resolved_at,
+            created_at: r.created_at,
         }
     }
 }
@@ -90,6 +118,7 @@ impl From<ReportModel> for ReportResponse {
         (status = 200, description = "Report created", body = ReportResponse),
         (status = 400, description = "Validation error", body = AppError),
         (status = 401, description = "Unauthorized", body = AppError),
+        (status = 409, description = "Already reported", body = AppError),
     ),
     tag = "reports"
 )]
@@ -98,11 +127,24 @@ pub async fn create_report(
     auth_user: AuthUser,
     Json(payload): Json<CreateReportRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
     let user_id = parse_user_id(&auth_user)?;
+    require_pow(
+        &PowConfig::from_env()?,
+        PowAction::Report,
+        &payload.target_type,
+        payload.target_id,
+        user_id,
+        payload.pow_token.as_deref(),
+        payload.pow_nonce.as_deref(),
+    )?;
+    require_captcha(
+        &CaptchaConfig::from_env(),
+        CaptchaAction::Report,
+        payload.captcha_token.as_deref(),
+    )
+    .await?;
 
     let service = ReportService::new(db);
     let report = service
@@ -115,7 +157,7 @@ pub async fn create_report(
         )
         .await?;
 
-    Ok(ApiResponse::ok(ReportResponse::from(report)))
+    Ok(Created(ApiResponse::ok(ReportResponse::from(report))))
 }
 
 #[utoipa::path(
@@ -129,7 +171,7 @@ pub async fn create_report(
     ),
     responses(
         (status = 200, description = "List of reports", body = PaginatedResponse<ReportResponse>),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
     ),
     tag = "reports"
 )]
@@ -138,7 +180,7 @@ pub async fn list_reports(
     auth_user: AuthUser,
     Query(params): Query<ListReportsQuery>,
 ) -> AppResult<impl IntoResponse> {
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::ViewReports).await?;
 
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20).min(100);
@@ -154,6 +196,8 @@ pub async fn list_reports(
     )))
 }
 
+/// Carries out the action, records it against the content's author and
+/// notifies the reporter of the outcome.
 #[utoipa::path(
     put,
     path = "/api/v1/admin/reports/{id}/resolve",
@@ -163,24 +207,89 @@ pub async fn list_reports(
     responses(
         (status = 200, description = "Report resolved", body = ReportResponse),
         (status = 400, description = "Validation error", body = AppError),
-        (status = 403, description = "Admin only", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 409, description = "Claimed by another moderator", body = AppError),
     ),
     tag = "reports"
 )]
 pub async fn resolve_report(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(search): Extension<SearchIndex>,
+    Extension(hub): Extension<NotificationHub>,
+    cache: Option<Extension<CacheService>>,
     auth_user: AuthUser,
     Path(id): Path<i32>,
     Json(payload): Json<ResolveReportRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
-    let admin_id = require_admin(&db, &auth_user).await?;
+    let admin_id = require_permission(&auth_user, Permission::ResolveReports).await?;
+    let action = ReportAction::parse(&payload.action).ok_or_else(|| {
+        AppError::Validation(format!(
+            "action must be one of: {}",
+            ReportAction::ALL.map(|a| a.as_str()).join(", ")
+        ))
+    })?;
+    if action == ReportAction::BanUser {
+        require_permission(&auth_user, Permission::BanUsers).await?;
+    }
 
-    let service = ReportService::new(db);
-    let report = service.resolve(id, admin_id, &payload.action).await?;
+    let service = ReportService::new(db.clone());
+    let resolution = service
+        .resolve(id, admin_id, action, payload.note.as_deref())
+        .await?;
+    let report = resolution.report;
+
+    if report.target_type == "post" {
+        search.refresh_post(&db, report.target_id).await;
+        if let Some(Extension(cache)) = &cache {
+            // Hidden or deleted; once deleted its forum is unknown, so drop
+            // every cached post and listing
+            match PostService::new(db.clone())
+                .get_by_id(report.target_id)
+                .await
+            {
+                Ok(post) => invalidate_post_cache(cache, post.id, post.forum_id).await,
+                Err(_) => cache.invalidate_pattern("posts:*").await,
+            }
+        }
+    }
+
+    let notifications = NotificationService::new(db, hub);
+    if let Some(author_id) = resolution.author_id {
+        match action {
+            ReportAction::WarnUser => {
+                let message = report
+                    .resolution_note
+                    .as_deref()
+                    .unwrap_or("You received a warning from the moderators");
+                let _ = notifications
+                    .notify(
+                        author_id,
+                        admin_id,
+                        "moderation_warning",
+                        &report.target_type,
+                        report.target_id,
+                        message,
+                    )
+                    .await;
+            }
+            ReportAction::BanUser => {
+                invalidate_cached_auth(cache.as_ref().map(|Extension(c)| c), author_id).await;
+            }
+            _ => {}
+        }
+    }
+    let _ = notifications
+        .notify(
+            report.reporter_id,
+            admin_id,
+            "report_resolved",
+            "report",
+            report.id,
+            action.outcome_message(),
+        )
+        .await;
 
     Ok(ApiResponse::ok(ReportResponse::from(report)))
 }
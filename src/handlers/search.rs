@@ -0,0 +1,110 @@
+use crate::error::{AppError, AppResult};
+use crate::handlers::comment::CommentResponse;
+use crate::handlers::forum::ForumResponse;
+use crate::handlers::post::PostResponse;
+use crate::handlers::tag::TagResponse;
+use crate::handlers::user::UserProfileResponse;
+use crate::response::ApiResponse;
+use crate::services::search::{SearchGroup, SearchIndex, SearchService, SearchType};
+use axum::{extract::Query, response::IntoResponse, Extension};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SearchAllQuery {
+    /// Search query
+    pub q: String,
+    /// Only return one group: posts, comments, users, tags, forums
+    #[serde(rename = "type")]
+    pub search_type: Option<String>,
+    /// Maximum items per group (default 5, max 50)
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchGroupResponse<T> {
+    /// Top matches in this group
+    pub items: Vec<T>,
+    /// Total number of matches in this group
+    pub total: u64,
+}
+
+impl<M, T: From<M>> From<SearchGroup<M>> for SearchGroupResponse<T> {
+    fn from(g: SearchGroup<M>) -> Self {
+        Self {
+            items: g.items.into_iter().map(T::from).collect(),
+            total: g.total,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchAllResponse {
+    /// Matching posts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub posts: Option<SearchGroupResponse<PostResponse>>,
+    /// Matching comments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<SearchGroupResponse<CommentResponse>>,
+    /// Matching users
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub users: Option<SearchGroupResponse<UserProfileResponse>>,
+    /// Matching tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<SearchGroupResponse<TagResponse>>,
+    /// Matching forums
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forums: Option<SearchGroupResponse<ForumResponse>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/search/all",
+    params(
+        ("q" = String, Query, description = "Search query"),
+        ("type" = Option<String>, Query, description = "Only search one type: posts, comments, users, tags, forums"),
+        ("limit" = Option<u64>, Query, description = "Maximum items per group (default 5, max 50)"),
+    ),
+    responses(
+        (status = 200, description = "Grouped search results", body = SearchAllResponse),
+        (status = 400, description = "Invalid query", body = AppError),
+    ),
+    tag = "search"
+)]
+pub async fn search_all(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(search): Extension<SearchIndex>,
+    Query(params): Query<SearchAllQuery>,
+) -> AppResult<impl IntoResponse> {
+    let q = params.q.trim();
+    if q.is_empty() || q.len() > 200 {
+        return Err(AppError::Validation(
+            "Search query must be 1-200 characters".to_string(),
+        ));
+    }
+
+    let only = match params.search_type.as_deref() {
+        Some(t) if !t.trim().is_empty() => Some(SearchType::parse(t).ok_or_else(|| {
+            AppError::Validation(
+                "type must be one of: posts, comments, users, tags, forums".to_string(),
+            )
+        })?),
+        _ => None,
+    };
+    let limit = params.limit.unwrap_or(5).clamp(1, 50);
+
+    let service = SearchService::new(db, search);
+    let types = only
+        .as_ref()
+        .map_or(&SearchType::ALL[..], std::slice::from_ref);
+    let results = service.search_all(q, types, limit, false).await?;
+
+    Ok(ApiResponse::ok(SearchAllResponse {
+        posts: results.posts.map(Into::into),
+        comments: results.comments.map(Into::into),
+        users: results.users.map(Into::into),
+        tags: results.tags.map(Into::into),
+        forums: results.forums.map(Into::into),
+    }))
+}
@@ -0,0 +1,438 @@
+use crate::config::seo::SeoConfig;
+use crate::error::{AppError, AppResult};
+use crate::services::seo::{
+    page_url, LinkPreviewService, PostPreview, SitemapKind, SitemapService,
+};
+use crate::utils::slug::{parse_post_ref, post_ref};
+use crate::utils::tenant;
+use askama::Template;
+use axum::{
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{NaiveDateTime, SecondsFormat};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct OEmbedQuery {
+    /// Link to a post, on the site (`/posts/{id}`) or its preview page
+    pub url: String,
+    /// Only `json` is supported. `maxwidth`/`maxheight` are ignored, as
+    /// link embeds have no size.
+    pub format: Option<String>,
+}
+
+/// oEmbed 1.0 `link` response. It carries no thumbnail, since oEmbed
+/// requires its dimensions; consumers take the image from the preview
+/// page's Open Graph tags instead.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OEmbedResponse {
+    pub version: String,
+    /// Always `link`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub author_name: String,
+    pub author_url: String,
+    pub provider_name: String,
+    pub provider_url: String,
+    /// Seconds the response may be cached for
+    pub cache_age: u64,
+}
+
+#[derive(Template)]
+#[template(path = "preview/post.html")]
+struct PostPreviewPage<'a> {
+    site_name: &'a str,
+    title: &'a str,
+    excerpt: &'a str,
+    author: &'a str,
+    author_url: &'a str,
+    forum: &'a str,
+    canonical: &'a str,
+    oembed_url: &'a str,
+    image: Option<&'a str>,
+    published: &'a str,
+    modified: &'a str,
+}
+
+/// Sitemap index linking every page of the child sitemaps.
+#[utoipa::path(
+    get,
+    path = "/sitemap.xml",
+    responses(
+        (status = 200, description = "Sitemap index", content_type = "application/xml", body = String),
+    ),
+    tag = "seo"
+)]
+pub async fn sitemap_index(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    let config = seo_config(&headers);
+    let service = SitemapService::new(db);
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for kind in SitemapKind::ALL {
+        let pages = service.count(kind).await?.div_ceil(config.page_size);
+        for page in 1..=pages {
+            let _ = writeln!(
+                xml,
+                "  <sitemap><loc>{}/sitemaps/{}-{}.xml</loc></sitemap>",
+                xml_escape(&config.public_api_url),
+                kind.as_str(),
+                page
+            );
+        }
+    }
+    xml.push_str("</sitemapindex>\n");
+
+    Ok(xml_response(&config, xml))
+}
+
+/// One page of forums, tags, posts or user profiles, e.g. `posts-2.xml`.
+#[utoipa::path(
+    get,
+    path = "/sitemaps/{file}",
+    params(("file" = String, Path, description = "`<forums|tags|posts|users>-<page>.xml`")),
+    responses(
+        (status = 200, description = "Sitemap", content_type = "application/xml", body = String),
+        (status = 404, description = "No such sitemap page", body = AppError),
+    ),
+    tag = "seo"
+)]
+pub async fn sitemap_page(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Path(file): Path<String>,
+) -> AppResult<Response> {
+    let (kind, page) = parse_sitemap_file(&file).ok_or(AppError::NotFound)?;
+    let config = seo_config(&headers);
+    let entries = SitemapService::new(db)
+        .entries(kind, page, config.page_size)
+        .await?;
+    if entries.is_empty() && page > 1 {
+        return Err(AppError::NotFound);
+    }
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for entry in &entries {
+        let _ = writeln!(
+            xml,
+            "  <url><loc>{}</loc><lastmod>{}</lastmod></url>",
+            xml_escape(&page_url(&config.site_url, &entry.segments)),
+            entry.lastmod.and_utc().format("%Y-%m-%dT%H:%M:%SZ")
+        );
+    }
+    xml.push_str("</urlset>\n");
+
+    Ok(xml_response(&config, xml))
+}
+
+/// `ROBOTS_TXT_PATH` if set, otherwise generated from `ROBOTS_DISALLOW` and
+/// `ROBOTS_CRAWL_DELAY`, pointing crawlers at the sitemap.
+#[utoipa::path(
+    get,
+    path = "/robots.txt",
+    responses(
+        (status = 200, description = "robots.txt", content_type = "text/plain", body = String),
+    ),
+    tag = "seo"
+)]
+pub async fn robots_txt(headers: HeaderMap) -> Response {
+    let config = seo_config(&headers);
+
+    if let Some(path) = &config.robots_txt_path {
+        match tokio::fs::read_to_string(path).await {
+            Ok(body) => return text_response(body),
+            Err(e) => tracing::warn!("Failed to read ROBOTS_TXT_PATH {}: {}", path, e),
+        }
+    }
+
+    let mut body = String::from("User-agent: *\n");
+    if config.robots_disallow.is_empty() {
+        body.push_str("Disallow:\n");
+    }
+    for path in &config.robots_disallow {
+        let _ = writeln!(body, "Disallow: {}", path);
+    }
+    if let Some(delay) = config.robots_crawl_delay {
+        let _ = writeln!(body, "Crawl-delay: {}", delay);
+    }
+    let _ = writeln!(body, "\nSitemap: {}/sitemap.xml", config.public_api_url);
+    text_response(body)
+}
+
+/// oEmbed for links to posts, so chat apps and embedding sites can unfurl
+/// them.
+#[utoipa::path(
+    get,
+    path = "/oembed",
+    params(OEmbedQuery),
+    responses(
+        (status = 200, description = "oEmbed response", body = OEmbedResponse),
+        (status = 404, description = "Not a link to a visible post", body = AppError),
+        (status = 501, description = "Unsupported format"),
+    ),
+    tag = "seo"
+)]
+pub async fn oembed(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Query(query): Query<OEmbedQuery>,
+) -> AppResult<Response> {
+    if query.format.as_deref().is_some_and(|f| f != "json") {
+        return Ok(StatusCode::NOT_IMPLEMENTED.into_response());
+    }
+    let config = seo_config(&headers);
+    let id = post_id_from_url(&config, &query.url).ok_or(AppError::NotFound)?;
+    let preview = LinkPreviewService::new(db).post(id).await?;
+
+    let response = OEmbedResponse {
+        version: "1.0".to_string(),
+        kind: "link".to_string(),
+        title: preview.title,
+        author_url: page_url(
+            &config.site_url,
+            &["users".to_string(), preview.author.clone()],
+        ),
+        author_name: preview.author_name,
+        provider_name: config.site_name.clone(),
+        provider_url: config.site_url.clone(),
+        cache_age: config.max_age.as_secs(),
+    };
+    Ok(([cache_control(&config)], Json(response)).into_response())
+}
+
+/// Minimal HTML page for a post carrying Open Graph and Twitter card tags,
+/// for crawlers that unfurl links. Browsers are sent on to the post itself.
+#[utoipa::path(
+    get,
+    path = "/p/{id}",
+    params(("id" = String, Path, description = "Post ID, optionally followed by `-` and the post's slug")),
+    responses(
+        (status = 200, description = "Preview page", content_type = "text/html", body = String),
+        (status = 404, description = "Post not found", body = AppError),
+    ),
+    tag = "seo"
+)]
+pub async fn post_preview(
+    Extension(db): Extension<DatabaseConnection>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> AppResult<Response> {
+    let id = parse_post_ref(&id).ok_or(AppError::NotFound)?;
+    let config = seo_config(&headers);
+    let preview = LinkPreviewService::new(db).post(id).await?;
+    let html = render_post_preview(&config, &preview).map_err(anyhow::Error::from)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8".to_string()),
+            cache_control(&config),
+        ],
+        html,
+    )
+        .into_response())
+}
+
+fn render_post_preview(config: &SeoConfig, preview: &PostPreview) -> askama::Result<String> {
+    let canonical = page_url(
+        &config.site_url,
+        &[
+            "posts".to_string(),
+            post_ref(preview.id, preview.slug.as_deref()),
+        ],
+    );
+    let author_url = page_url(
+        &config.site_url,
+        &["users".to_string(), preview.author.clone()],
+    );
+    let oembed_url = format!(
+        "{}/oembed?url={}",
+        config.public_api_url,
+        url::form_urlencoded::byte_serialize(canonical.as_bytes()).collect::<String>()
+    );
+    let image = preview
+        .image
+        .as_deref()
+        .or(config.og_default_image.as_deref())
+        .and_then(|image| absolute_url(&config.public_api_url, image));
+
+    PostPreviewPage {
+        site_name: &config.site_name,
+        title: &preview.title,
+        excerpt: &preview.excerpt,
+        author: &preview.author_name,
+        author_url: &author_url,
+        forum: &preview.forum,
+        canonical: &canonical,
+        oembed_url: &oembed_url,
+        image: image.as_deref(),
+        published: &rfc3339(preview.created_at),
+        modified: &rfc3339(preview.updated_at),
+    }
+    .render()
+}
+
+/// A tenant's sitemaps link to its own host.
+pub(crate) fn seo_config(headers: &HeaderMap) -> SeoConfig {
+    let config = SeoConfig::from_env();
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    match host {
+        Some(host) if tenant::current().is_some() => config.for_host(host),
+        _ => config,
+    }
+}
+
+/// `posts-2.xml` -> `(Posts, 2)`
+fn parse_sitemap_file(file: &str) -> Option<(SitemapKind, u64)> {
+    let (kind, page) = file.strip_suffix(".xml")?.rsplit_once('-')?;
+    let page = page.parse().ok().filter(|p| *p >= 1)?;
+    Some((SitemapKind::parse(kind)?, page))
+}
+
+/// The post a link points at: `/posts/{id}` on the site or `/p/{id}` on
+/// the API, under either origin's base path.
+fn post_id_from_url(config: &SeoConfig, link: &str) -> Option<i32> {
+    let link = url::Url::parse(link).ok()?;
+    [&config.site_url, &config.public_api_url]
+        .into_iter()
+        .find_map(|origin| {
+            let origin = url::Url::parse(origin).ok()?;
+            if origin.host_str()? != link.host_str()?
+                || origin.port_or_known_default() != link.port_or_known_default()
+            {
+                return None;
+            }
+            let rest = link
+                .path()
+                .strip_prefix(origin.path().trim_end_matches('/'))?;
+            let id = rest
+                .strip_prefix("/posts/")
+                .or_else(|| rest.strip_prefix("/p/"))?;
+            parse_post_ref(id.trim_end_matches('/'))
+        })
+}
+
+/// Resolve an upload path against the API origin. Only http(s) URLs are
+/// usable as preview images.
+fn absolute_url(base: &str, url: &str) -> Option<String> {
+    let url = url::Url::parse(base).ok()?.join(url).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+fn rfc3339(at: NaiveDateTime) -> String {
+    at.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn cache_control(config: &SeoConfig) -> (header::HeaderName, String) {
+    (
+        header::CACHE_CONTROL,
+        format!("public, max-age={}", config.max_age.as_secs()),
+    )
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_response(config: &SeoConfig, xml: String) -> Response {
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                "application/xml; charset=utf-8".to_string(),
+            ),
+            cache_control(config),
+        ],
+        xml,
+    )
+        .into_response()
+}
+
+fn text_response(body: String) -> Response {
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sitemap_file_names() {
+        assert_eq!(
+            parse_sitemap_file("posts-2.xml"),
+            Some((SitemapKind::Posts, 2))
+        );
+        assert_eq!(
+            parse_sitemap_file("users-1.xml"),
+            Some((SitemapKind::Users, 1))
+        );
+        assert_eq!(parse_sitemap_file("posts-0.xml"), None);
+        assert_eq!(parse_sitemap_file("posts.xml"), None);
+        assert_eq!(parse_sitemap_file("comments-1.xml"), None);
+        assert_eq!(parse_sitemap_file("posts-1.txt"), None);
+    }
+
+    #[test]
+    fn page_urls_are_percent_encoded() {
+        let path = ["users".to_string(), "a b/c".to_string()];
+        assert_eq!(
+            page_url("https://forum.test", &path),
+            "https://forum.test/users/a%20b%2Fc"
+        );
+        assert_eq!(
+            page_url("https://forum.test/community", &path),
+            "https://forum.test/community/users/a%20b%2Fc"
+        );
+    }
+
+    #[test]
+    fn post_ids_are_read_from_site_and_preview_links() {
+        let mut config = SeoConfig::from_env();
+        config.site_url = "https://forum.test/community".to_string();
+        config.public_api_url = "https://api.forum.test".to_string();
+
+        let id = |link: &str| post_id_from_url(&config, link);
+        assert_eq!(id("https://forum.test/community/posts/42"), Some(42));
+        assert_eq!(id("https://FORUM.test/community/posts/42/?ref=x"), Some(42));
+        assert_eq!(id("https://api.forum.test/p/7"), Some(7));
+        assert_eq!(id("https://forum.test/community/posts/42-hello"), Some(42));
+        assert_eq!(id("https://forum.test/posts/42"), None);
+        assert_eq!(id("https://forum.test:8443/community/posts/42"), None);
+        assert_eq!(id("https://evil.test/community/posts/42"), None);
+        assert_eq!(id("https://forum.test/community/forums/42"), None);
+        assert_eq!(id("not a url"), None);
+    }
+
+    #[test]
+    fn preview_images_must_be_http() {
+        let base = "https://api.forum.test";
+        assert_eq!(
+            absolute_url(base, "/uploads/avatars/a.png").as_deref(),
+            Some("https://api.forum.test/uploads/avatars/a.png")
+        );
+        assert_eq!(
+            absolute_url(base, "https://cdn.test/b.png").as_deref(),
+            Some("https://cdn.test/b.png")
+        );
+        assert_eq!(absolute_url(base, "javascript:alert(1)"), None);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::config::app::SharedConfig;
+use crate::error::{AppError, AppResult};
+use crate::middleware::auth::require_permission;
+use crate::middleware::permission::Permission;
+use crate::middleware::AuthUser;
+use crate::response::ApiResponse;
+use crate::services::audit::{AuditEntry, AuditLogService};
+use crate::services::cache::CacheService;
+use crate::services::settings::{
+    normalize_domains, MaintenanceSettings, RegistrationMode, RegistrationSettings, SettingsService,
+};
+use axum::{response::IntoResponse, Extension, Json};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RegistrationSettingsResponse {
+    /// `open`, `invite_only` or `closed`
+    pub mode: String,
+    /// When non-empty, only these email domains (and their subdomains) may
+    /// register
+    pub allowed_email_domains: Vec<String>,
+    /// Email domains (and their subdomains) that may never register
+    pub denied_email_domains: Vec<String>,
+}
+
+impl From<RegistrationSettings> for RegistrationSettingsResponse {
+    fn from(s: RegistrationSettings) -> Self {
+        Self {
+            mode: s.mode.as_str().to_string(),
+            allowed_email_domains: s.allowed_email_domains,
+            denied_email_domains: s.denied_email_domains,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MaintenanceSettingsResponse {
+    /// While on, writes from non-admins get 503
+    pub enabled: bool,
+    /// Shown to users instead of the default notice
+    pub message: Option<String>,
+    /// Sent as `Retry-After` with the 503
+    pub retry_after_seconds: u32,
+}
+
+impl From<MaintenanceSettings> for MaintenanceSettingsResponse {
+    fn from(s: MaintenanceSettings) -> Self {
+        Self {
+            enabled: s.enabled,
+            message: s.message,
+            retry_after_seconds: s.retry_after_seconds,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SettingsResponse {
+    pub registration: RegistrationSettingsResponse,
+    pub maintenance: MaintenanceSettingsResponse,
+}
+
+/// Fields left out keep their current value.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateRegistrationSettings {
+    /// `open`, `invite_only` or `closed`
+    pub mode: Option<String>,
+    /// Replaces the allow list; `[]` allows every domain
+    pub allowed_email_domains: Option<Vec<String>>,
+    /// Replaces the deny list
+    pub denied_email_domains: Option<Vec<String>>,
+}
+
+/// Fields left out keep their current value.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateMaintenanceSettings {
+    pub enabled: Option<bool>,
+    /// An empty message restores the default notice
+    #[validate(length(max = 500))]
+    pub message: Option<String>,
+    #[validate(range(min = 1, max = 86400))]
+    pub retry_after_seconds: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateSettingsRequest {
+    pub registration: Option<UpdateRegistrationSettings>,
+    pub maintenance: Option<UpdateMaintenanceSettings>,
+}
+
+async fn current_settings(service: &SettingsService) -> AppResult<SettingsResponse> {
+    Ok(SettingsResponse {
+        registration: service.registration().await?.into(),
+        maintenance: service.maintenance().await?.into(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/settings",
+    security(("jwt_token" = [])),
+    responses(
+        (status = 200, description = "Current site settings", body = SettingsResponse),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn get_settings(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageSettings).await?;
+
+    let service = SettingsService::new(db).with_cache(cache.map(|Extension(c)| c));
+    Ok(ApiResponse::ok(current_settings(&service).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/settings",
+    security(("jwt_token" = [])),
+    request_body = UpdateSettingsRequest,
+    responses(
+        (status = 200, description = "Settings updated", body = SettingsResponse),
+        (status = 400, description = "Invalid setting", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn update_settings(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+    Json(payload): Json<UpdateSettingsRequest>,
+) -> AppResult<impl IntoResponse> {
+    let admin_id = require_permission(&auth_user, Permission::ManageSettings).await?;
+    if let Some(update) = &payload.maintenance {
+        update.validate()?;
+    }
+
+    let service = SettingsService::new(db.clone()).with_cache(cache.map(|Extension(c)| c));
+    if let Some(update) = payload.registration {
+        let mut settings = service.registration().await?;
+        if let Some(mode) = update.mode.as_deref() {
+            settings.mode = RegistrationMode::parse(mode).ok_or_else(|| {
+                AppError::Validation(
+                    "Invalid mode. Must be one of: open, invite_only, closed".to_string(),
+                )
+            })?;
+        }
+        if let Some(domains) = &update.allowed_email_domains {
+            settings.allowed_email_domains = normalize_domains(domains)?;
+        }
+        if let Some(domains) = &update.denied_email_domains {
+            settings.denied_email_domains = normalize_domains(domains)?;
+        }
+        service.set_registration(&settings, admin_id).await?;
+
+        AuditLogService::new(db.clone())
+            .record(AuditEntry {
+                actor_id: Some(admin_id),
+                action: "settings_updated",
+                detail: Some(format!(
+                    "registration: {}",
+                    serde_json::to_string(&settings).unwrap_or_default()
+                )),
+                ..Default::default()
+            })
+            .await;
+    }
+    if let Some(update) = payload.maintenance {
+        let mut settings = service.maintenance().await?;
+        if let Some(enabled) = update.enabled {
+            settings.enabled = enabled;
+        }
+        if let Some(message) = update.message {
+            let message = message.trim();
+            settings.message = (!message.is_empty()).then(|| message.to_string());
+        }
+        if let Some(retry_after) = update.retry_after_seconds {
+            settings.retry_after_seconds = retry_after;
+        }
+        service.set_maintenance(&settings, admin_id).await?;
+
+        AuditLogService::new(db)
+            .record(AuditEntry {
+                actor_id: Some(admin_id),
+                action: "settings_updated",
+                detail: Some(format!(
+                    "maintenance: {}",
+                    serde_json::to_string(&settings).unwrap_or_default()
+                )),
+                ..Default::default()
+            })
+            .await;
+    }
+
+    Ok(ApiResponse::ok(current_settings(&service).await?))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ConfigReloadResponse {
+    /// Settings that changed and now apply, by environment variable name
+    pub applied: Vec<String>,
+    /// Settings that changed in the file but only take effect on restart
+    pub restart_required: Vec<String>,
+}
+
+/// Re-read the config file, as on SIGHUP. Rate limits, PoW and CAPTCHA
+/// settings, upload limits and CORS origins apply right away.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/config/reload",
+    security(("jwt_token" = [])),
+    responses(
+        (status = 200, description = "Configuration reloaded", body = ConfigReloadResponse),
+        (status = 400, description = "Invalid config file; nothing was changed", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn reload_config(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(shared_config): Extension<SharedConfig>,
+    auth_user: AuthUser,
+) -> AppResult<impl IntoResponse> {
+    let admin_id = require_permission(&auth_user, Permission::ManageSettings).await?;
+
+    let reload = shared_config
+        .reload()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    AuditLogService::new(db)
+        .record(AuditEntry {
+            actor_id: Some(admin_id),
+            action: "config_reloaded",
+            detail: Some(format!("applied: {}", reload.applied.join(", "))),
+            ..Default::default()
+        })
+        .await;
+
+    Ok(ApiResponse::ok(ConfigReloadResponse {
+        applied: reload.applied,
+        restart_required: reload.restart_required,
+    }))
+}
@@ -1,10 +1,14 @@
 use crate::error::AppResult;
-use crate::handlers::post::PostResponse;
-use crate::middleware::auth::require_admin;
+use crate::handlers::post::{
+    attach_attachments, attach_crosspost_sources, attach_flairs, attach_link_previews, PostResponse,
+};
+use crate::middleware::auth::require_permission;
+use crate::middleware::permission::Permission;
 use crate::middleware::AuthUser;
 use crate::models::TagModel;
-use crate::response::{ApiResponse, PaginatedResponse};
+use crate::response::{ApiResponse, Created, NoContent, PaginatedResponse};
 use crate::services::tag::TagService;
+use crate::utils::i18n::t;
 use axum::{extract::Path, extract::Query, response::IntoResponse, Extension, Json};
 use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
@@ -78,9 +82,13 @@ pub async fn get_posts_by_tag(
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20).min(100);
 
-    let service = TagService::new(db);
+    let service = TagService::new(db.clone());
     let (posts, total) = service.get_posts_by_tag(&slug, page, per_page).await?;
-    let items: Vec<PostResponse> = posts.into_iter().map(PostResponse::from).collect();
+    let mut items: Vec<PostResponse> = posts.into_iter().map(PostResponse::from).collect();
+    attach_link_previews(&db, &mut items).await?;
+    attach_flairs(&db, &mut items).await?;
+    attach_attachments(&db, &mut items).await?;
+    attach_crosspost_sources(&db, &mut items).await?;
 
     Ok(ApiResponse::ok(PaginatedResponse::new(
         items, total, page, per_page,
@@ -102,7 +110,7 @@ pub struct CreateTagRequest {
     responses(
         (status = 200, description = "Tag created", body = TagResponse),
         (status = 400, description = "Validation error", body = crate::error::AppError),
-        (status = 403, description = "Admin only", body = crate::error::AppError),
+        (status = 403, description = "Insufficient permissions", body = crate::error::AppError),
     ),
     tag = "tags"
 )]
@@ -111,14 +119,12 @@ pub async fn create_tag(
     auth_user: AuthUser,
     Json(payload): Json<CreateTagRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| crate::error::AppError::Validation(e.to_string()))?;
-    require_admin(&db, &auth_user).await?;
+    payload.validate()?;
+    require_permission(&auth_user, Permission::ManageTags).await?;
 
     let service = TagService::new(db);
     let tag = service.create_tag(&payload.name).await?;
-    Ok(ApiResponse::ok(TagResponse::from(tag)))
+    Ok(Created(ApiResponse::ok(TagResponse::from(tag))))
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -137,7 +143,7 @@ pub struct UpdateTagRequest {
     responses(
         (status = 200, description = "Tag updated", body = TagResponse),
         (status = 400, description = "Validation error", body = crate::error::AppError),
-        (status = 403, description = "Admin only", body = crate::error::AppError),
+        (status = 403, description = "Insufficient permissions", body = crate::error::AppError),
     ),
     tag = "tags"
 )]
@@ -147,10 +153,8 @@ pub async fn update_tag(
     Path(id): Path<i32>,
     Json(payload): Json<UpdateTagRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| crate::error::AppError::Validation(e.to_string()))?;
-    require_admin(&db, &auth_user).await?;
+    payload.validate()?;
+    require_permission(&auth_user, Permission::ManageTags).await?;
 
     let service = TagService::new(db);
     let tag = service.update_tag(id, &payload.name).await?;
@@ -164,7 +168,7 @@ pub async fn update_tag(
     params(("id" = i32, Path, description = "Tag ID")),
     responses(
         (status = 200, description = "Tag deleted", body = String),
-        (status = 403, description = "Admin only", body = crate::error::AppError),
+        (status = 403, description = "Insufficient permissions", body = crate::error::AppError),
     ),
     tag = "tags"
 )]
@@ -173,9 +177,9 @@ pub async fn delete_tag(
     auth_user: AuthUser,
     Path(id): Path<i32>,
 ) -> AppResult<impl IntoResponse> {
-    require_admin(&db, &auth_user).await?;
+    require_permission(&auth_user, Permission::ManageTags).await?;
 
     let service = TagService::new(db);
     service.delete_tag(id).await?;
-    Ok(ApiResponse::ok("Tag deleted successfully"))
+    Ok(NoContent(ApiResponse::ok(t("tag_deleted"))))
 }
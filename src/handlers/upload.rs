@@ -1,27 +1,45 @@
+use crate::config::app::SharedConfig;
 use crate::error::{AppError, AppResult};
 use crate::middleware::auth::parse_user_id;
 use crate::middleware::AuthUser;
 use crate::response::ApiResponse;
-use crate::services::upload::{UploadConfig, UploadService};
-use crate::services::user::UserService;
-use axum::{extract::Multipart, response::IntoResponse, Extension};
+use crate::services::avatar::{AvatarCrop, AvatarService};
+use crate::services::upload::{TempUpload, UploadConfig, UploadService};
+use axum::{
+    extract::{multipart::MultipartError, Multipart},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
 use sea_orm::DatabaseConnection;
 use serde::Serialize;
+use std::collections::HashMap;
 use utoipa::ToSchema;
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct UploadResponse {
+    /// Upload ID, for attaching the file to a post (images only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
     /// URL of the uploaded file
     pub url: String,
+    /// For avatars, the uploaded image the avatar was cropped from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_url: Option<String>,
 }
 
+/// Upload an avatar. The image is kept as the avatar's original; the avatar
+/// is a 256×256 thumbnail cut from it, placed by optional text fields
+/// `crop_x`, `crop_y`, `crop_width` and `crop_height`, or `focus_x`,
+/// `focus_y` and `zoom` (see [`AvatarCrop`]).
 #[utoipa::path(
     post,
     path = "/api/v1/upload/avatar",
     security(("jwt_token" = [])),
+    request_body(content_type = "multipart/form-data", description = "JPEG, PNG, GIF or WebP image in a `file` field, with optional crop fields"),
     responses(
         (status = 200, description = "Avatar uploaded", body = UploadResponse),
-        (status = 400, description = "Invalid file", body = AppError),
+        (status = 400, description = "Invalid file or crop, or rejected by the virus scan", body = AppError),
         (status = 401, description = "Unauthorized", body = AppError),
         (status = 413, description = "File too large", body = AppError),
     ),
@@ -29,85 +47,146 @@ pub struct UploadResponse {
 )]
 pub async fn upload_avatar(
     Extension(db): Extension<DatabaseConnection>,
-    Extension(config): Extension<UploadConfig>,
+    Extension(shared_config): Extension<SharedConfig>,
     auth_user: AuthUser,
     mut multipart: Multipart,
 ) -> AppResult<impl IntoResponse> {
     let user_id = parse_user_id(&auth_user)?;
 
-    let mut field = multipart
-        .next_field()
-        .await
-        .map_err(|e| AppError::Validation(format!("Failed to read upload: {}", e)))?
-        .ok_or_else(|| AppError::Validation("No file provided".to_string()))?;
-
-    let content_type = field
-        .content_type()
-        .unwrap_or("application/octet-stream")
-        .to_string();
+    let config = UploadConfig::from(&shared_config.current().uploads);
+    let (mut upload, content_type, fields) = receive_file(&mut multipart, &config).await?;
+    let crop = AvatarCrop::from_fields(&fields)?;
+    UploadService::scan(&db, &mut upload, &content_type, user_id).await?;
+    let (avatar, original) = AvatarService::new(db)
+        .upload(&config, user_id, upload, &content_type, crop)
+        .await?;
 
-    let mut data = Vec::new();
-    while let Some(chunk) = field
-        .chunk()
-        .await
-        .map_err(|e| AppError::Validation(format!("Failed to read file data: {}", e)))?
-    {
-        if data.len() + chunk.len() > crate::services::upload::MAX_FILE_SIZE {
-            return Err(AppError::PayloadTooLarge);
-        }
-        data.extend_from_slice(&chunk);
-    }
+    Ok(ApiResponse::ok(UploadResponse {
+        id: None,
+        url: avatar.url,
+        original_url: Some(original.url),
+    }))
+}
 
-    let url = UploadService::save_file(&config, &data, &content_type, "avatars").await?;
+/// Cut the avatar again from the last uploaded original.
+#[utoipa::path(
+    post,
+    path = "/api/v1/upload/avatar/crop",
+    security(("jwt_token" = [])),
+    request_body = AvatarCrop,
+    responses(
+        (status = 200, description = "Avatar cropped", body = UploadResponse),
+        (status = 400, description = "Invalid crop, or no uploaded avatar", body = AppError),
+        (status = 401, description = "Unauthorized", body = AppError),
+    ),
+    tag = "uploads"
+)]
+pub async fn crop_avatar(
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(shared_config): Extension<SharedConfig>,
+    auth_user: AuthUser,
+    Json(crop): Json<AvatarCrop>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
 
-    // Update user avatar_url
-    let service = UserService::new(db);
-    service.update_avatar_url(user_id, &url).await?;
+    let config = UploadConfig::from(&shared_config.current().uploads);
+    let (avatar, original_url) = AvatarService::new(db)
+        .recrop(&config, user_id, crop)
+        .await?;
 
-    Ok(ApiResponse::ok(UploadResponse { url }))
+    Ok(ApiResponse::ok(UploadResponse {
+        id: None,
+        url: avatar.url,
+        original_url: Some(original_url),
+    }))
 }
 
 #[utoipa::path(
     post,
     path = "/api/v1/upload/image",
     security(("jwt_token" = [])),
+    request_body(content_type = "multipart/form-data", description = "JPEG, PNG, GIF or WebP image in a `file` field"),
     responses(
         (status = 200, description = "Image uploaded", body = UploadResponse),
-        (status = 400, description = "Invalid file", body = AppError),
+        (status = 400, description = "Invalid file, or rejected by the virus scan", body = AppError),
         (status = 401, description = "Unauthorized", body = AppError),
         (status = 413, description = "File too large", body = AppError),
     ),
     tag = "uploads"
 )]
 pub async fn upload_image(
-    Extension(config): Extension<UploadConfig>,
-    _auth_user: AuthUser,
+    Extension(db): Extension<DatabaseConnection>,
+    Extension(shared_config): Extension<SharedConfig>,
+    auth_user: AuthUser,
     mut multipart: Multipart,
 ) -> AppResult<impl IntoResponse> {
-    let mut field = multipart
-        .next_field()
-        .await
-        .map_err(|e| AppError::Validation(format!("Failed to read upload: {}", e)))?
-        .ok_or_else(|| AppError::Validation("No file provided".to_string()))?;
+    let user_id = parse_user_id(&auth_user)?;
 
-    let content_type = field
-        .content_type()
-        .unwrap_or("application/octet-stream")
-        .to_string();
+    let config = UploadConfig::from(&shared_config.current().uploads);
+    let (mut upload, content_type, _) = receive_file(&mut multipart, &config).await?;
+    UploadService::scan(&db, &mut upload, &content_type, user_id).await?;
+    let saved = UploadService::save_upload(&config, upload, &content_type, "images").await?;
+    let record = UploadService::record(&db, &config, &saved, &content_type, user_id).await?;
 
-    let mut data = Vec::new();
-    while let Some(chunk) = field
-        .chunk()
+    Ok(ApiResponse::ok(UploadResponse {
+        id: Some(record.id),
+        url: saved.url,
+        original_url: None,
+    }))
+}
+
+/// Stream the file part of a `multipart/form-data` body to a temp file,
+/// checking the size as it arrives, and collect the text fields. The file
+/// is the part named `file`, or else the first with a filename; later
+/// files are skipped.
+pub(crate) async fn receive_file(
+    multipart: &mut Multipart,
+    config: &UploadConfig,
+) -> AppResult<(TempUpload, String, HashMap<String, String>)> {
+    let mut file = None;
+    let mut fields = HashMap::new();
+    while let Some(mut field) = multipart
+        .next_field()
         .await
-        .map_err(|e| AppError::Validation(format!("Failed to read file data: {}", e)))?
+        .map_err(|e| multipart_error(e, "Failed to read upload"))?
     {
-        if data.len() + chunk.len() > crate::services::upload::MAX_FILE_SIZE {
-            return Err(AppError::PayloadTooLarge);
+        let name = field.name().unwrap_or_default().to_string();
+        if name != "file" && field.file_name().is_none() {
+            let value = field
+                .text()
+                .await
+                .map_err(|e| multipart_error(e, "Failed to read upload"))?;
+            fields.insert(name, value);
+            continue;
         }
-        data.extend_from_slice(&chunk);
-    }
+        if file.is_some() {
+            continue;
+        }
+        let content_type = field
+            .content_type()
+            .unwrap_or("application/octet-stream")
+            .to_string();
 
-    let url = UploadService::save_file(&config, &data, &content_type, "images").await?;
+        let mut upload = TempUpload::create(config).await?;
+        while let Some(chunk) = field
+            .chunk()
+            .await
+            .map_err(|e| multipart_error(e, "Failed to read file data"))?
+        {
+            upload.write(&chunk).await?;
+        }
+        file = Some((upload, content_type));
+    }
+    let (upload, content_type) =
+        file.ok_or_else(|| AppError::Validation("No file provided".to_string()))?;
+    Ok((upload, content_type, fields))
+}
 
-    Ok(ApiResponse::ok(UploadResponse { url }))
+/// A body over the route's limit surfaces as a multipart error.
+fn multipart_error(e: MultipartError, context: &str) -> AppError {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        AppError::PayloadTooLarge
+    } else {
+        AppError::Validation(format!("{}: {}", context, e))
+    }
 }
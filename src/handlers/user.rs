@@ -1,10 +1,32 @@
 use crate::error::{AppError, AppResult};
-use crate::middleware::auth::parse_user_id;
+use crate::handlers::badge::UserBadgeResponse;
+use crate::handlers::comment::CommentResponse;
+use crate::handlers::post::{
+    attach_attachments, attach_crosspost_sources, attach_flairs, attach_link_previews, PostResponse,
+};
+use crate::middleware::auth::{invalidate_cached_auth, parse_user_id};
 use crate::middleware::AuthUser;
 use crate::models::UserModel;
-use crate::response::ApiResponse;
-use crate::services::user::UserService;
-use axum::{extract::Path, response::IntoResponse, Extension, Json};
+use crate::response::{ApiResponse, PaginatedResponse, PaginationQuery};
+use crate::services::activity::{ActivityItem, ActivityService, HistorySort};
+use crate::services::badge::BadgeService;
+use crate::services::cache::CacheService;
+use crate::services::digest::DIGEST_FREQUENCIES;
+use crate::services::follow::FollowService;
+use crate::services::notification::NotificationService;
+use crate::services::post::POST_SORTS;
+use crate::services::presence::Presence;
+use crate::services::profile_stats::{ProfileStats, ProfileStatsService};
+use crate::services::quiet_hours::parse_timezone;
+use crate::services::tag::TagService;
+use crate::services::user::{PreferencesUpdate, ProfileUpdate, ResolvedUser, UserService};
+use crate::utils::markdown::summarize_markdown;
+use crate::websocket::hub::NotificationHub;
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    Extension, Json,
+};
 use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -16,37 +38,185 @@ pub struct UserProfileResponse {
     pub id: i32,
     /// Username
     pub username: String,
+    /// Name shown instead of the username; `null` if not set
+    pub display_name: Option<String>,
     /// Avatar URL
     pub avatar_url: Option<String>,
     /// User bio/description
     pub bio: Option<String>,
     /// User karma score
     pub karma: i32,
+    /// Whether follows need the user's approval
+    pub is_private: bool,
+    /// Whether the user was active in the last few minutes; `null` if they
+    /// hide their presence
+    pub is_online: Option<bool>,
+    /// When the user was last active; `null` if never or hidden
+    #[serde(serialize_with = "crate::utils::time::serialize_option")]
+    pub last_seen_at: Option<chrono::NaiveDateTime>,
     /// Account creation timestamp
-    pub created_at: String,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+    /// Contribution statistics (profile page only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ProfileStatsResponse>,
+    /// Badges held, oldest award first (profile page only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub badges: Option<Vec<UserBadgeResponse>>,
+    /// The former username the profile was requested by; links should use
+    /// `username` instead (profile page only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirected_from: Option<String>,
 }
 
 impl From<UserModel> for UserProfileResponse {
     fn from(u: UserModel) -> Self {
+        let presence = Presence::of(&u, chrono::Utc::now().naive_utc());
         Self {
             id: u.id,
             username: u.username,
+            display_name: u.display_name,
             avatar_url: u.avatar_url,
             bio: u.bio,
             karma: u.karma,
-            created_at: u.created_at.to_string(),
+            is_private: u.is_private,
+            is_online: presence.map(|p| p.is_online),
+            last_seen_at: presence.and_then(|p| p.last_seen_at),
+            created_at: u.created_at,
+            stats: None,
+            badges: None,
+            redirected_from: None,
         }
     }
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProfileStatsResponse {
+    /// Visible posts
+    pub post_count: i64,
+    /// Visible comments
+    pub comment_count: i64,
+    /// Score of all the user's posts
+    pub post_karma: i64,
+    /// Score of all the user's comments
+    pub comment_karma: i64,
+    pub follower_count: i64,
+    pub following_count: i64,
+    /// Date the user joined, `YYYY-MM-DD`
+    pub joined_at: String,
+    /// Tags on most of the user's posts, most used first
+    pub top_tags: Vec<TopTagResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TopTagResponse {
+    pub name: String,
+    pub slug: String,
+    /// The user's posts with the tag
+    pub post_count: i64,
+}
+
+impl ProfileStatsResponse {
+    fn new(stats: ProfileStats, joined_at: chrono::NaiveDateTime) -> Self {
+        let counts = stats.counts;
+        Self {
+            post_count: counts.post_count,
+            comment_count: counts.comment_count,
+            post_karma: counts.post_karma,
+            comment_karma: counts.comment_karma,
+            follower_count: counts.follower_count,
+            following_count: counts.following_count,
+            joined_at: joined_at.date().to_string(),
+            top_tags: stats
+                .top_tags
+                .into_iter()
+                .map(|t| TopTagResponse {
+                    name: t.name,
+                    slug: t.slug,
+                    post_count: t.post_count,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Characters of a post or comment shown in the activity feed.
+const ACTIVITY_EXCERPT_CHARS: usize = 200;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActivityResponse {
+    /// `post` or `comment`
+    pub kind: String,
+    /// Post or comment ID
+    pub id: i32,
+    /// The post, or the post commented on
+    pub post_id: i32,
+    pub post_title: String,
+    /// Start of the content as plain text
+    pub excerpt: String,
+    /// Upvotes minus downvotes
+    pub score: i32,
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<ActivityItem> for ActivityResponse {
+    fn from(item: ActivityItem) -> Self {
+        Self {
+            excerpt: summarize_markdown(&item.content, ACTIVITY_EXCERPT_CHARS).excerpt,
+            kind: item.kind,
+            id: item.id,
+            post_id: item.post_id,
+            post_title: item.post_title,
+            score: item.score,
+            created_at: item.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct HistoryQuery {
+    /// Page number
+    pub page: Option<u64>,
+    /// Items per page
+    pub per_page: Option<u64>,
+    /// Sort order: new, old, top
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserCommentResponse {
+    #[serde(flatten)]
+    pub comment: CommentResponse,
+    /// Title of the post the comment is on
+    pub post_title: String,
+}
+
 #[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct UpdateProfileRequest {
+    /// Name shown instead of the username (max 50 characters); omitted or
+    /// empty to show the username
+    #[validate(length(max = 50))]
+    pub display_name: Option<String>,
     /// User bio/description (max 500 characters)
     #[validate(length(max = 500))]
     pub bio: Option<String>,
     /// Avatar URL (max 500 characters)
     #[validate(length(max = 500))]
     pub avatar_url: Option<String>,
+    /// Watch threads automatically after commenting on them (unchanged if omitted)
+    pub auto_watch: Option<bool>,
+    /// Language for emails, `en` or `zh` (unchanged if omitted)
+    pub locale: Option<String>,
+    /// Email digest of followed users' posts: `off`, `daily` or `weekly`
+    /// (unchanged if omitted)
+    pub digest_frequency: Option<String>,
+    /// Require approval of new followers (unchanged if omitted). Making the
+    /// profile public approves pending follow requests.
+    pub is_private: Option<bool>,
+    /// Show others whether the user is online and when they were last seen
+    /// (unchanged if omitted)
+    pub show_presence: Option<bool>,
 }
 
 #[utoipa::path(
@@ -54,18 +224,159 @@ pub struct UpdateProfileRequest {
     path = "/api/v1/users/{username}",
     params(("username" = String, Path, description = "Username")),
     responses(
-        (status = 200, description = "User profile", body = UserProfileResponse),
+        (status = 200, description = "User profile with contribution statistics and badges; `redirected_from` is set when requested by a former username", body = UserProfileResponse),
         (status = 404, description = "User not found", body = AppError),
     ),
     tag = "users"
 )]
 pub async fn get_user_profile(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
     Path(username): Path<String>,
 ) -> AppResult<impl IntoResponse> {
-    let service = UserService::new(db);
-    let user = service.get_by_username(&username).await?;
-    Ok(ApiResponse::ok(UserProfileResponse::from(user)))
+    let service = UserService::new(db.clone());
+    let ResolvedUser { user, renamed_from } = service.resolve_username(&username).await?;
+    let stats = ProfileStatsService::new(db.clone(), cache.map(|c| c.0))
+        .get(user.id)
+        .await?;
+    let badges = BadgeService::new(db).held_by(user.id).await?;
+    let joined_at = user.created_at;
+    let mut profile = UserProfileResponse::from(user);
+    profile.stats = Some(ProfileStatsResponse::new(stats, joined_at));
+    profile.badges = Some(badges.into_iter().map(UserBadgeResponse::from).collect());
+    profile.redirected_from = renamed_from;
+    Ok(ApiResponse::ok(profile))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{username}/activity",
+    params(
+        ("username" = String, Path, description = "Username"),
+        ("page" = Option<u64>, Query, description = "Page number"),
+        ("per_page" = Option<u64>, Query, description = "Items per page"),
+    ),
+    responses(
+        (status = 200, description = "The user's posts and comments, newest first", body = PaginatedResponse<ActivityResponse>),
+        (status = 404, description = "User not found", body = AppError),
+    ),
+    tag = "users"
+)]
+pub async fn get_user_activity(
+    Extension(db): Extension<DatabaseConnection>,
+    Path(username): Path<String>,
+    Query(params): Query<PaginationQuery>,
+) -> AppResult<impl IntoResponse> {
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(20).min(100);
+
+    let user = UserService::new(db.clone())
+        .get_by_username(&username)
+        .await?;
+    let (items, total) = ActivityService::new(db)
+        .list(user.id, page, per_page)
+        .await?;
+    let items = items.into_iter().map(ActivityResponse::from).collect();
+    Ok(ApiResponse::ok(PaginatedResponse::new(
+        items, total, page, per_page,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{username}/posts",
+    params(
+        ("username" = String, Path, description = "Username"),
+        ("page" = Option<u64>, Query, description = "Page number"),
+        ("per_page" = Option<u64>, Query, description = "Items per page"),
+        ("sort" = Option<String>, Query, description = "Sort order: new, old, top"),
+    ),
+    responses(
+        (status = 200, description = "The user's posts", body = PaginatedResponse<PostResponse>),
+        (status = 400, description = "Invalid sort", body = AppError),
+        (status = 404, description = "User not found", body = AppError),
+    ),
+    tag = "users"
+)]
+pub async fn get_user_posts(
+    Extension(db): Extension<DatabaseConnection>,
+    Path(username): Path<String>,
+    Query(params): Query<HistoryQuery>,
+) -> AppResult<impl IntoResponse> {
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(20).min(100);
+    let sort = HistorySort::parse(params.sort.as_deref().unwrap_or("new"))?;
+
+    let user = UserService::new(db.clone())
+        .get_by_username(&username)
+        .await?;
+    let (posts, total) = ActivityService::new(db.clone())
+        .list_posts(user.id, sort, page, per_page)
+        .await?;
+
+    let post_ids: Vec<i32> = posts.iter().map(|p| p.id).collect();
+    let tags_map = TagService::new(db.clone())
+        .get_tags_for_posts(&post_ids)
+        .await?;
+    let mut items: Vec<PostResponse> = posts
+        .into_iter()
+        .map(|p| {
+            let tags = tags_map.get(&p.id).cloned().unwrap_or_default();
+            PostResponse::with_tags(p, tags)
+        })
+        .collect();
+    attach_link_previews(&db, &mut items).await?;
+    attach_flairs(&db, &mut items).await?;
+    attach_attachments(&db, &mut items).await?;
+    attach_crosspost_sources(&db, &mut items).await?;
+
+    Ok(ApiResponse::ok(PaginatedResponse::new(
+        items, total, page, per_page,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{username}/comments",
+    params(
+        ("username" = String, Path, description = "Username"),
+        ("page" = Option<u64>, Query, description = "Page number"),
+        ("per_page" = Option<u64>, Query, description = "Items per page"),
+        ("sort" = Option<String>, Query, description = "Sort order: new, old, top"),
+    ),
+    responses(
+        (status = 200, description = "The user's comments with their posts' titles", body = PaginatedResponse<UserCommentResponse>),
+        (status = 400, description = "Invalid sort", body = AppError),
+        (status = 404, description = "User not found", body = AppError),
+    ),
+    tag = "users"
+)]
+pub async fn get_user_comments(
+    Extension(db): Extension<DatabaseConnection>,
+    Path(username): Path<String>,
+    Query(params): Query<HistoryQuery>,
+) -> AppResult<impl IntoResponse> {
+    let page = params.page.unwrap_or(1);
+    let per_page = params.per_page.unwrap_or(20).min(100);
+    let sort = HistorySort::parse(params.sort.as_deref().unwrap_or("new"))?;
+
+    let user = UserService::new(db.clone())
+        .get_by_username(&username)
+        .await?;
+    let (comments, total) = ActivityService::new(db)
+        .list_comments(user.id, sort, page, per_page)
+        .await?;
+    let items: Vec<UserCommentResponse> = comments
+        .into_iter()
+        .map(|(comment, post_title)| UserCommentResponse {
+            comment: CommentResponse::from(comment),
+            post_title,
+        })
+        .collect();
+
+    Ok(ApiResponse::ok(PaginatedResponse::new(
+        items, total, page, per_page,
+    )))
 }
 
 #[utoipa::path(
@@ -82,19 +393,251 @@ pub async fn get_user_profile(
 )]
 pub async fn update_profile(
     Extension(db): Extension<DatabaseConnection>,
+    Extension(hub): Extension<NotificationHub>,
+    cache: Option<Extension<CacheService>>,
     auth_user: AuthUser,
     Json(payload): Json<UpdateProfileRequest>,
 ) -> AppResult<impl IntoResponse> {
-    payload
-        .validate()
-        .map_err(|e| AppError::Validation(e.to_string()))?;
+    payload.validate()?;
 
     let user_id = parse_user_id(&auth_user)?;
+    let locale = payload
+        .locale
+        .as_deref()
+        .map(crate::handlers::auth::parse_locale)
+        .transpose()?;
+    if let Some(frequency) = payload.digest_frequency.as_deref() {
+        if !DIGEST_FREQUENCIES.contains(&frequency) {
+            return Err(AppError::Validation(format!(
+                "Invalid digest frequency. Must be one of: {}",
+                DIGEST_FREQUENCIES.join(", ")
+            )));
+        }
+    }
 
-    let service = UserService::new(db);
+    let service = UserService::new(db.clone());
     let user = service
-        .update_profile(user_id, payload.bio, payload.avatar_url)
+        .update_profile(
+            user_id,
+            ProfileUpdate {
+                display_name: payload
+                    .display_name
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty()),
+                bio: payload.bio,
+                avatar_url: payload.avatar_url,
+                auto_watch: payload.auto_watch,
+                locale: locale.map(|l| l.as_str()),
+                digest_frequency: payload.digest_frequency.as_deref(),
+                is_private: payload.is_private,
+                show_presence: payload.show_presence,
+            },
+        )
         .await?;
+    if locale.is_some() {
+        // The cached auth state carries the locale messages are sent in
+        invalidate_cached_auth(cache.as_ref().map(|c| &c.0), user_id).await;
+    }
+
+    if !user.is_private {
+        let approved = FollowService::new(db.clone())
+            .approve_all_requests(user_id)
+            .await?;
+        if !approved.is_empty() {
+            let _ = NotificationService::new(db, hub)
+                .notify_many(
+                    &approved,
+                    user_id,
+                    "follow_request_approved",
+                    "user",
+                    user_id,
+                    "Your follow request was approved",
+                )
+                .await;
+        }
+    }
 
     Ok(ApiResponse::ok(UserProfileResponse::from(user)))
 }
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ChangeUsernameRequest {
+    /// New username (3-50 characters)
+    #[validate(length(min = 3, max = 50))]
+    pub username: String,
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/auth/username",
+    security(("jwt_token" = [])),
+    request_body = ChangeUsernameRequest,
+    responses(
+        (status = 200, description = "Username changed; the old one now resolves to this profile", body = UserProfileResponse),
+        (status = 400, description = "Validation error", body = AppError),
+        (status = 401, description = "Unauthorized", body = AppError),
+        (status = 409, description = "Username taken", body = AppError),
+        (status = 429, description = "Username changed too recently", body = AppError),
+    ),
+    tag = "users"
+)]
+pub async fn change_username(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Json(payload): Json<ChangeUsernameRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    let user_id = parse_user_id(&auth_user)?;
+    let interval = crate::config::auth::AuthConfig::from_env().username_change_interval_days;
+    let user = UserService::new(db)
+        .change_username(user_id, &payload.username, interval)
+        .await?;
+    Ok(ApiResponse::ok(UserProfileResponse::from(user)))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PreferencesResponse {
+    /// IANA timezone for digests and quiet hours
+    pub timezone: String,
+    /// Language for emails, `en` or `zh`
+    pub locale: String,
+    /// Language tag of the content the user prefers to read; `null` for any
+    pub content_language: Option<String>,
+    /// Show NSFW content without a click-through
+    pub show_nsfw: bool,
+    /// Sort of post lists that don't ask for one: `new`, `top` or `hot`
+    pub default_post_sort: String,
+}
+
+impl From<UserModel> for PreferencesResponse {
+    fn from(u: UserModel) -> Self {
+        Self {
+            timezone: u.timezone,
+            locale: u.locale,
+            content_language: u.content_language,
+            show_nsfw: u.show_nsfw,
+            default_post_sort: u.default_post_sort,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdatePreferencesRequest {
+    /// IANA timezone such as `Asia/Shanghai` (unchanged if omitted)
+    pub timezone: Option<String>,
+    /// Language for emails, `en` or `zh` (unchanged if omitted)
+    pub locale: Option<String>,
+    /// Language tag such as `en` or `zh-CN`; an empty string clears it
+    /// (unchanged if omitted)
+    pub content_language: Option<String>,
+    /// Show NSFW content without a click-through (unchanged if omitted)
+    pub show_nsfw: Option<bool>,
+    /// `new`, `top` or `hot` (unchanged if omitted)
+    pub default_post_sort: Option<String>,
+}
+
+/// Parse a BCP 47 language tag such as `zh-Hant-TW`, or `None` for an empty
+/// one. Only the shape is checked: a 2-3 letter language, then subtags of
+/// 1-8 letters or digits.
+fn parse_language_tag(tag: &str) -> AppResult<Option<String>> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Ok(None);
+    }
+    let mut subtags = tag.split(['-', '_']);
+    let language = subtags.next().unwrap_or_default();
+    let valid = tag.len() <= 35
+        && (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags
+            .all(|s| (1..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()));
+    if !valid {
+        return Err(AppError::Validation(format!(
+            "Invalid language tag: {}",
+            tag
+        )));
+    }
+    let mut normalized = language.to_ascii_lowercase();
+    normalized.push_str(&tag[language.len()..].replace('_', "-"));
+    Ok(Some(normalized))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/preferences",
+    security(("jwt_token" = [])),
+    responses(
+        (status = 200, description = "The user's preferences", body = PreferencesResponse),
+        (status = 401, description = "Unauthorized", body = AppError),
+    ),
+    tag = "users"
+)]
+pub async fn get_preferences(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let user = UserService::new(db).get_by_id(user_id).await?;
+    Ok(ApiResponse::ok(PreferencesResponse::from(user)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/auth/preferences",
+    security(("jwt_token" = [])),
+    request_body = UpdatePreferencesRequest,
+    responses(
+        (status = 200, description = "Preferences updated", body = PreferencesResponse),
+        (status = 400, description = "Invalid timezone, locale, language or sort", body = AppError),
+        (status = 401, description = "Unauthorized", body = AppError),
+    ),
+    tag = "users"
+)]
+pub async fn update_preferences(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    auth_user: AuthUser,
+    Json(payload): Json<UpdatePreferencesRequest>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let timezone = payload
+        .timezone
+        .as_deref()
+        .map(parse_timezone)
+        .transpose()?;
+    let locale = payload
+        .locale
+        .as_deref()
+        .map(crate::handlers::auth::parse_locale)
+        .transpose()?;
+    let content_language = payload
+        .content_language
+        .as_deref()
+        .map(parse_language_tag)
+        .transpose()?;
+    if let Some(sort) = payload.default_post_sort.as_deref() {
+        if !POST_SORTS.contains(&sort) {
+            return Err(AppError::Validation(format!(
+                "Invalid sort. Must be one of: {}",
+                POST_SORTS.join(", ")
+            )));
+        }
+    }
+
+    let user = UserService::new(db)
+        .update_preferences(
+            user_id,
+            PreferencesUpdate {
+                timezone: timezone.as_ref().map(|tz| tz.name()),
+                locale: locale.map(|l| l.as_str()),
+                content_language,
+                show_nsfw: payload.show_nsfw,
+                default_post_sort: payload.default_post_sort.as_deref(),
+            },
+        )
+        .await?;
+    if locale.is_some() {
+        invalidate_cached_auth(cache.as_ref().map(|c| &c.0), user_id).await;
+    }
+    Ok(ApiResponse::ok(PreferencesResponse::from(user)))
+}
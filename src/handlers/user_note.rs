@@ -0,0 +1,151 @@
+use crate::error::{AppError, AppResult};
+use crate::middleware::auth::require_permission;
+use crate::middleware::permission::{role_has_permission, Permission};
+use crate::middleware::AuthUser;
+use crate::models::UserNoteModel;
+use crate::response::{ApiResponse, Created, NoContent};
+use crate::services::user_note::{UserNoteService, UserNoteWithAuthor};
+use crate::utils::i18n::t;
+use axum::{extract::Path, response::IntoResponse, Extension, Json};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserNoteResponse {
+    /// Note ID
+    pub id: i32,
+    /// User the note is about
+    pub user_id: i32,
+    /// Staff member who wrote it
+    pub author_id: Option<i32>,
+    /// Their username
+    pub author_username: Option<String>,
+    /// Their display name, if set
+    pub author_display_name: Option<String>,
+    /// Note text
+    pub body: String,
+    /// Creation timestamp
+    #[serde(serialize_with = "crate::utils::time::serialize")]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<UserNoteWithAuthor> for UserNoteResponse {
+    fn from(n: UserNoteWithAuthor) -> Self {
+        Self {
+            author_username: n.author_username,
+            author_display_name: n.author_display_name,
+            ..Self::from(n.note)
+        }
+    }
+}
+
+impl From<UserNoteModel> for UserNoteResponse {
+    fn from(n: UserNoteModel) -> Self {
+        Self {
+            id: n.id,
+            user_id: n.user_id,
+            author_id: n.author_id,
+            author_username: None,
+            author_display_name: None,
+            body: n.body,
+            created_at: n.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateUserNoteRequest {
+    /// Note text (1-5000 characters)
+    #[validate(length(min = 1, max = 5000))]
+    pub body: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/users/{id}/notes",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "Staff notes on the user, newest first", body = Vec<UserNoteResponse>),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "User not found", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn list_user_notes(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    require_permission(&auth_user, Permission::ManageUserNotes).await?;
+
+    let service = UserNoteService::new(db);
+    let items: Vec<UserNoteResponse> = service
+        .list(id)
+        .await?
+        .into_iter()
+        .map(UserNoteResponse::from)
+        .collect();
+    Ok(ApiResponse::ok(items))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/users/{id}/notes",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "User ID")),
+    request_body = CreateUserNoteRequest,
+    responses(
+        (status = 200, description = "Note added", body = UserNoteResponse),
+        (status = 400, description = "Validation error", body = AppError),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "User not found", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn create_user_note(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<CreateUserNoteRequest>,
+) -> AppResult<impl IntoResponse> {
+    payload.validate()?;
+    let author_id = require_permission(&auth_user, Permission::ManageUserNotes).await?;
+
+    let service = UserNoteService::new(db);
+    let note = service.create(id, author_id, payload.body.trim()).await?;
+    Ok(Created(ApiResponse::ok(UserNoteResponse::from(note))))
+}
+
+/// Staff can delete their own notes; admins can delete any.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/users/{id}/notes/{note_id}",
+    security(("jwt_token" = [])),
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        ("note_id" = i32, Path, description = "Note ID"),
+    ),
+    responses(
+        (status = 200, description = "Note deleted", body = String),
+        (status = 403, description = "Insufficient permissions", body = AppError),
+        (status = 404, description = "Note not found", body = AppError),
+    ),
+    tag = "admin"
+)]
+pub async fn delete_user_note(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path((id, note_id)): Path<(i32, i32)>,
+) -> AppResult<impl IntoResponse> {
+    let requester_id = require_permission(&auth_user, Permission::ManageUserNotes).await?;
+    let any_author = role_has_permission(&auth_user.role, Permission::ManageUsers);
+
+    let service = UserNoteService::new(db);
+    service
+        .delete(id, note_id, requester_id, any_author)
+        .await?;
+    Ok(NoContent(ApiResponse::ok(t("note_deleted"))))
+}
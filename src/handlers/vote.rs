@@ -2,12 +2,14 @@ use crate::error::AppResult;
 use crate::middleware::auth::parse_user_id;
 use crate::middleware::AuthUser;
 use crate::response::ApiResponse;
+use crate::services::cache::CacheService;
+use crate::services::captcha::{require_captcha, CaptchaAction, CaptchaConfig};
 use crate::services::comment::CommentService;
 use crate::services::notification::NotificationService;
 use crate::services::points::PointsService;
-use crate::services::post::PostService;
+use crate::services::post::{invalidate_post_cache, PostService};
 use crate::services::vote::VoteService;
-use crate::utils::pow::{validate_pow_solution, verify_and_decode_challenge, PowConfig};
+use crate::utils::pow::{require_pow, PowAction, PowConfig};
 use crate::websocket::hub::NotificationHub;
 use axum::{extract::Path, response::IntoResponse, Extension, Json};
 use sea_orm::DatabaseConnection;
@@ -18,10 +20,12 @@ use utoipa::ToSchema;
 pub struct VoteRequest {
     /// Vote value: -1 (downvote), 0 (remove vote), 1 (upvote)
     pub value: i16,
-    /// PoW token from /api/v1/pow/challenge
-    pub pow_token: String,
+    /// PoW token from /api/v1/pow/challenge; required while votes need PoW
+    pub pow_token: Option<String>,
     /// PoW nonce computed on client
-    pub pow_nonce: String,
+    pub pow_nonce: Option<String>,
+    /// hCaptcha/Turnstile response; required while votes need a CAPTCHA
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -49,6 +53,7 @@ pub struct VoteResponse {
 pub async fn vote_post(
     Extension(db): Extension<DatabaseConnection>,
     Extension(hub): Extension<NotificationHub>,
+    cache: Option<Extension<CacheService>>,
     auth_user: AuthUser,
     Path(id): Path<i32>,
     Json(payload): Json<VoteRequest>,
@@ -56,18 +61,21 @@ pub async fn vote_post(
     let user_id = parse_user_id(&auth_user)?;
 
     // PoW verify (bind to user/action/target)
-    let pow_cfg = PowConfig::from_env()?;
-    let challenge = verify_and_decode_challenge(&pow_cfg.secret, &payload.pow_token)?;
-    if challenge.user_id != user_id
-        || challenge.action != "vote"
-        || challenge.target_type != "post"
-        || challenge.target_id != id
-    {
-        return Err(crate::error::AppError::Validation(
-            "pow_token mismatch".to_string(),
-        ));
-    }
-    validate_pow_solution(&challenge, &payload.pow_nonce)?;
+    require_pow(
+        &PowConfig::from_env()?,
+        PowAction::Vote,
+        "post",
+        id,
+        user_id,
+        payload.pow_token.as_deref(),
+        payload.pow_nonce.as_deref(),
+    )?;
+    require_captcha(
+        &CaptchaConfig::from_env(),
+        CaptchaAction::Vote,
+        payload.captcha_token.as_deref(),
+    )
+    .await?;
 
     let service = VoteService::new(db.clone());
     let change = service.set_vote(user_id, "post", id, payload.value).await?;
@@ -89,10 +97,19 @@ pub async fn vote_post(
         }
     }
 
+    // Live scores for the thread's watchers
+    hub.post_changed(id, i64::from(change.new_value - change.old_value));
+
+    let post = PostService::new(db.clone()).get_by_id(id).await;
+
+    // Scores changed, so cached copies and top/hot orderings are stale
+    if let (Some(Extension(cache)), Ok(post)) = (&cache, &post) {
+        invalidate_post_cache(cache, post.id, post.forum_id).await;
+    }
+
     // Notify post author on vote (not on toggle-off)
     if change.new_value != 0 {
-        let post_service = PostService::new(db.clone());
-        if let Ok(post) = post_service.get_by_id(id).await {
+        if let Ok(post) = post {
             let notif = NotificationService::new(db, hub);
             let _ = notif
                 .notify(
@@ -136,18 +153,21 @@ pub async fn vote_comment(
     let user_id = parse_user_id(&auth_user)?;
 
     // PoW verify (bind to user/action/target)
-    let pow_cfg = PowConfig::from_env()?;
-    let challenge = verify_and_decode_challenge(&pow_cfg.secret, &payload.pow_token)?;
-    if challenge.user_id != user_id
-        || challenge.action != "vote"
-        || challenge.target_type != "comment"
-        || challenge.target_id != id
-    {
-        return Err(crate::error::AppError::Validation(
-            "pow_token mismatch".to_string(),
-        ));
-    }
-    validate_pow_solution(&challenge, &payload.pow_nonce)?;
+    require_pow(
+        &PowConfig::from_env()?,
+        PowAction::Vote,
+        "comment",
+        id,
+        user_id,
+        payload.pow_token.as_deref(),
+        payload.pow_nonce.as_deref(),
+    )?;
+    require_captcha(
+        &CaptchaConfig::from_env(),
+        CaptchaAction::Vote,
+        payload.captcha_token.as_deref(),
+    )
+    .await?;
 
     let service = VoteService::new(db.clone());
     let change = service
@@ -0,0 +1,60 @@
+use crate::error::AppResult;
+use crate::middleware::auth::parse_user_id;
+use crate::middleware::AuthUser;
+use crate::response::ApiResponse;
+use crate::services::watch::WatchService;
+use axum::{extract::Path, response::IntoResponse, Extension};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WatchResponse {
+    /// Whether the post is now watched
+    pub watching: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/posts/{id}/watch",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "Watching post", body = WatchResponse),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+        (status = 404, description = "Post not found", body = crate::error::AppError),
+    ),
+    tag = "posts"
+)]
+pub async fn watch_post(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(post_id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let service = WatchService::new(db);
+    let watching = service.watch(user_id, post_id).await?;
+    Ok(ApiResponse::ok(WatchResponse { watching }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/posts/{id}/watch",
+    security(("jwt_token" = [])),
+    params(("id" = i32, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "Stopped watching post", body = WatchResponse),
+        (status = 401, description = "Unauthorized", body = crate::error::AppError),
+    ),
+    tag = "posts"
+)]
+pub async fn unwatch_post(
+    Extension(db): Extension<DatabaseConnection>,
+    auth_user: AuthUser,
+    Path(post_id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&auth_user)?;
+    let service = WatchService::new(db);
+    let watching = service.unwatch(user_id, post_id).await?;
+    Ok(ApiResponse::ok(WatchResponse { watching }))
+}
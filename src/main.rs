@@ -1,3 +1,4 @@
+mod cli;
 mod config;
 mod error;
 mod handlers;
@@ -10,19 +11,14 @@ mod services;
 mod utils;
 mod websocket;
 
-use axum::{
-    extract::Extension, http::Request, middleware as axum_middleware, response::IntoResponse,
-    routing::get, Json, Router,
-};
-use sea_orm::{ConnectionTrait, DatabaseConnection, Statement};
+use axum::serve::ListenerExt;
+use axum::{extract::Extension, http::Request, middleware as axum_middleware, Router};
+use clap::Parser;
+use config::app::{AppConfig, SharedConfig};
+use sea_orm::ConnectionTrait;
 use sea_orm_migration::MigratorTrait;
-use serde_json::json;
 use services::cache::CacheService;
-use services::upload::UploadConfig;
-use std::env;
 use std::net::SocketAddr;
-use tower_http::cors::CorsLayer;
-use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use tower_http::services::ServeDir;
 use tower_http::trace::TraceLayer;
@@ -34,7 +30,8 @@ use websocket::hub::NotificationHub;
 #[derive(OpenApi)]
 #[openapi(
     paths(
-        health_check,
+        crate::handlers::health::healthz,
+        crate::handlers::health::readyz,
         // Auth routes
         crate::handlers::register,
         crate::handlers::login,
@@ -46,29 +43,56 @@ use websocket::hub::NotificationHub;
         crate::handlers::auth::forgot_password,
         crate::handlers::auth::reset_password,
         crate::handlers::auth::logout,
+        crate::handlers::auth::deactivate,
+        crate::handlers::auth::delete_account,
+        crate::handlers::email::get_email_preferences,
+        crate::handlers::email::update_email_preferences,
+        crate::handlers::email::unsubscribe,
+        crate::handlers::email::sendgrid_webhook,
+        crate::handlers::email::ses_webhook,
         // User routes
         crate::handlers::user::get_user_profile,
+        crate::handlers::user::get_preferences,
+        crate::handlers::user::update_preferences,
+        crate::handlers::user::get_user_activity,
+        crate::handlers::user::get_user_posts,
+        crate::handlers::user::get_user_comments,
         crate::handlers::user::update_profile,
+        crate::handlers::user::change_username,
         // Forum routes
         crate::handlers::forum::list_forums,
         crate::handlers::forum::get_forum,
         crate::handlers::forum::create_forum,
         crate::handlers::forum::update_forum,
         crate::handlers::forum::delete_forum,
+        crate::handlers::flair::list_flairs,
+        crate::handlers::flair::create_flair,
+        crate::handlers::flair::update_flair,
+        crate::handlers::flair::delete_flair,
+        crate::handlers::emoji::list_emoji,
+        crate::handlers::emoji::create_emoji,
+        crate::handlers::emoji::delete_emoji,
         // Post routes
         crate::handlers::post::list_posts,
         crate::handlers::post::get_post,
+        crate::handlers::post::get_post_by_slug,
         crate::handlers::post::create_post,
+        crate::handlers::post::crosspost_post,
         crate::handlers::post::update_post,
         crate::handlers::post::delete_post,
         crate::handlers::post::pin_post,
         crate::handlers::post::lock_post,
+        crate::handlers::post::close_post,
+        crate::handlers::post::pin_comment,
         crate::handlers::post::search_posts,
+        crate::handlers::search::search_all,
         // Comment routes
         crate::handlers::comment::list_comments,
+        crate::handlers::comment::get_comment,
         crate::handlers::comment::create_comment,
         crate::handlers::comment::update_comment,
         crate::handlers::comment::delete_comment,
+        crate::handlers::comment::list_comment_revisions,
         // Tag routes
         crate::handlers::tag::list_tags,
         crate::handlers::tag::get_posts_by_tag,
@@ -79,39 +103,115 @@ use websocket::hub::NotificationHub;
         crate::handlers::vote::vote_post,
         crate::handlers::vote::vote_comment,
         // PoW routes
+        crate::handlers::pow::get_pow_policy,
         crate::handlers::pow::create_pow_challenge,
         // Follow routes
         crate::handlers::follow::list_followers,
         crate::handlers::follow::list_following,
+        crate::handlers::follow::list_follow_requests,
+        crate::handlers::follow::approve_follow_request,
+        crate::handlers::follow::deny_follow_request,
         crate::handlers::follow::follow_user,
         crate::handlers::follow::unfollow_user,
         crate::handlers::follow::toggle_follow,
         // Notification routes
         crate::handlers::notification::list_notifications,
         crate::handlers::notification::unread_count,
+        crate::handlers::notification::mark_read_many,
         crate::handlers::notification::mark_all_read,
         crate::handlers::notification::mark_read,
+        crate::handlers::notification::delete_notification,
+        crate::handlers::notification::clear_notifications,
+        crate::handlers::notification::get_quiet_hours,
+        crate::handlers::notification::update_quiet_hours,
         // Bookmark routes
         crate::handlers::bookmark::add_bookmark,
         crate::handlers::bookmark::remove_bookmark,
         crate::handlers::bookmark::toggle_bookmark,
         crate::handlers::bookmark::list_bookmarks,
+        crate::handlers::watch::watch_post,
+        crate::handlers::watch::unwatch_post,
+        crate::handlers::post_read::mark_post_read,
         // Upload routes
         crate::handlers::upload::upload_avatar,
+        crate::handlers::upload::crop_avatar,
         crate::handlers::upload::upload_image,
         // Report routes
         crate::handlers::report::create_report,
         crate::handlers::report::list_reports,
         crate::handlers::report::resolve_report,
+        // Moderation queue
+        crate::handlers::appeal::create_appeal,
+        crate::handlers::appeal::list_my_appeals,
+        crate::handlers::appeal::list_appeals,
+        crate::handlers::appeal::get_appeal,
+        crate::handlers::appeal::add_appeal_comment,
+        crate::handlers::appeal::resolve_appeal,
+        crate::handlers::mod_queue::list_queue,
+        crate::handlers::mod_queue::claim_item,
+        crate::handlers::mod_queue::release_item,
+        crate::handlers::mod_queue::assign_item,
         // Admin routes
         crate::handlers::admin::get_stats,
+        crate::handlers::admin::get_stats_timeseries,
+        crate::handlers::admin::export,
+        crate::handlers::admin::search,
         crate::handlers::admin::list_users,
         crate::handlers::admin::update_user_role,
+        crate::handlers::admin::force_logout_user,
+        crate::handlers::admin::delete_user,
+        crate::handlers::admin::impersonate_user,
+        crate::handlers::admin::list_audit_log,
+        crate::handlers::user_note::list_user_notes,
+        crate::handlers::user_note::create_user_note,
+        crate::handlers::user_note::delete_user_note,
+        crate::handlers::settings::get_settings,
+        crate::handlers::settings::update_settings,
+        crate::handlers::settings::reload_config,
+        crate::handlers::invite::create_invites,
+        crate::handlers::invite::list_invites,
+        crate::handlers::invite::revoke_invite,
         crate::handlers::admin::admin_delete_post,
         crate::handlers::admin::admin_delete_comment,
+        crate::handlers::admin::list_emails,
+        crate::handlers::admin::retry_email,
+        crate::handlers::admin::reindex_search,
+        // Announcement routes
+        crate::handlers::announcement::active_announcements,
+        crate::handlers::announcement::list_announcements,
+        crate::handlers::announcement::create_announcement,
+        crate::handlers::announcement::update_announcement,
+        crate::handlers::announcement::delete_announcement,
+        crate::handlers::badge::list_badges,
+        crate::handlers::badge::create_badge,
+        crate::handlers::badge::delete_badge,
+        crate::handlers::badge::award_badge,
+        crate::handlers::badge::revoke_badge,
+        // Outbound links
+        crate::handlers::outbound::outbound_redirect,
+        crate::handlers::seo::robots_txt,
+        crate::handlers::seo::sitemap_index,
+        crate::handlers::seo::sitemap_page,
+        crate::handlers::seo::oembed,
+        crate::handlers::seo::post_preview,
+        crate::handlers::federation::webfinger,
+        crate::handlers::federation::user_actor,
+        crate::handlers::federation::user_outbox,
+        crate::handlers::federation::user_followers,
+        crate::handlers::federation::forum_actor,
+        crate::handlers::federation::forum_outbox,
+        crate::handlers::federation::forum_followers,
+        crate::handlers::federation::post_object,
+        crate::handlers::federation::inbox,
+        crate::handlers::image_proxy::proxy_image,
     ),
     components(
         schemas(
+            crate::handlers::health::LivenessResponse,
+            crate::handlers::health::ReadinessResponse,
+            crate::config::database::PoolStats,
+            crate::handlers::health::DependencyCheck,
+            crate::handlers::health::MigrationCheck,
             crate::response::ApiResponse<serde_json::Value>,
             crate::response::PaginatedResponse<serde_json::Value>,
             crate::response::PaginationQuery,
@@ -125,27 +225,58 @@ use websocket::hub::NotificationHub;
             crate::handlers::auth::TokenResponse,
             crate::handlers::auth::UserResponse,
             crate::handlers::auth::ChangePasswordRequest,
+            crate::handlers::auth::DeactivateRequest,
+            crate::handlers::auth::DeleteAccountRequest,
             crate::handlers::auth::VerifyEmailRequest,
             crate::handlers::auth::ForgotPasswordRequest,
             crate::handlers::auth::ResetPasswordRequest,
+            // Email
+            crate::handlers::email::EmailPreferenceResponse,
+            crate::handlers::email::EmailPreferencesResponse,
+            crate::handlers::email::UpdateEmailPreferencesRequest,
+            crate::handlers::email::UnsubscribeQuery,
+            crate::handlers::email::UnsubscribeResponse,
+            crate::handlers::email::EmailWebhookQuery,
+            crate::handlers::email::EmailWebhookResponse,
             // User
             crate::handlers::user::UserProfileResponse,
+            crate::handlers::user::ProfileStatsResponse,
+            crate::handlers::user::TopTagResponse,
+            crate::handlers::user::ActivityResponse,
+            crate::handlers::user::UserCommentResponse,
+            crate::handlers::user::HistoryQuery,
             crate::handlers::user::UpdateProfileRequest,
+            crate::handlers::user::ChangeUsernameRequest,
+            crate::handlers::user::PreferencesResponse,
+            crate::handlers::user::UpdatePreferencesRequest,
             // Forum
             crate::handlers::forum::ForumResponse,
+            crate::handlers::flair::FlairResponse,
+            crate::handlers::flair::FlairRequest,
+            crate::handlers::emoji::EmojiResponse,
             crate::handlers::forum::CreateForumRequest,
             crate::handlers::forum::UpdateForumRequest,
             // Post
             crate::handlers::post::PostResponse,
+            crate::handlers::post::LinkPreviewResponse,
+            crate::handlers::post::AttachmentResponse,
             crate::handlers::post::CreatePostRequest,
+            crate::handlers::post::CrosspostRequest,
+            crate::handlers::post::CrosspostSourceResponse,
             crate::handlers::post::UpdatePostRequest,
             crate::handlers::post::PostListQuery,
             crate::handlers::post::SearchPostsQuery,
+            crate::handlers::post::SearchPostsResponse,
+            crate::handlers::search::SearchAllQuery,
+            crate::handlers::search::SearchAllResponse,
             // Comment
             crate::handlers::comment::CommentResponse,
+            crate::handlers::comment::QuoteResponse,
             crate::handlers::comment::CommentTreeNode,
+            crate::handlers::comment::CommentContextResponse,
             crate::handlers::comment::CreateCommentRequest,
             crate::handlers::comment::UpdateCommentRequest,
+            crate::handlers::comment::CommentRevisionResponse,
             // Tag
             crate::handlers::tag::TagResponse,
             crate::handlers::tag::CreateTagRequest,
@@ -156,32 +287,102 @@ use websocket::hub::NotificationHub;
             // PoW
             crate::handlers::pow::PowChallengeRequest,
             crate::handlers::pow::PowChallengeResponse,
+            crate::handlers::pow::PowPolicyResponse,
+            crate::handlers::pow::PowActionPolicy,
+            crate::handlers::pow::CaptchaPolicy,
             // Follow
             crate::handlers::follow::FollowToggleResponse,
+            crate::handlers::follow::FollowUserResponse,
+            crate::handlers::follow::FollowRequestResponse,
             // Notification
             crate::handlers::notification::NotificationResponse,
             crate::handlers::notification::UnreadCountResponse,
+            crate::handlers::notification::NotificationListQuery,
+            crate::handlers::notification::MarkReadRequest,
+            crate::handlers::notification::QuietHoursResponse,
+            crate::handlers::notification::UpdateQuietHoursRequest,
             // Bookmark
             crate::handlers::bookmark::BookmarkToggleResponse,
+            crate::handlers::watch::WatchResponse,
+            crate::handlers::admin::CacheMetricResponse,
+            crate::handlers::admin::TimeseriesQuery,
+            crate::handlers::admin::TimeseriesResponse,
+            crate::handlers::admin::TimeseriesPointResponse,
+            crate::handlers::admin::ExportQuery,
+            crate::handlers::admin::AdminSearchQuery,
+            crate::handlers::admin::AdminSearchResponse,
+            crate::handlers::admin::AdminPostHit,
+            crate::handlers::admin::AdminCommentHit,
+            crate::handlers::post_read::PostReadResponse,
             // Upload
             crate::handlers::upload::UploadResponse,
+            crate::services::avatar::AvatarCrop,
             // Report
             crate::handlers::report::ReportResponse,
             crate::handlers::report::CreateReportRequest,
             crate::handlers::report::ResolveReportRequest,
+            // Moderation queue
+            crate::handlers::appeal::AppealResponse,
+            crate::handlers::appeal::ModerationActionResponse,
+            crate::handlers::appeal::AppealCommentResponse,
+            crate::handlers::appeal::AppealDetailResponse,
+            crate::handlers::appeal::CreateAppealRequest,
+            crate::handlers::appeal::ListAppealsQuery,
+            crate::handlers::appeal::AppealCommentRequest,
+            crate::handlers::appeal::ResolveAppealRequest,
+            crate::handlers::mod_queue::ModQueueQuery,
+            crate::handlers::mod_queue::ModQueueItemResponse,
+            crate::handlers::mod_queue::ModQueueClaimResponse,
+            crate::handlers::mod_queue::AssignQueueItemRequest,
             // Admin
             crate::handlers::admin::StatsResponse,
             crate::handlers::admin::AdminUserResponse,
+            crate::handlers::admin::ImpersonateRequest,
+            crate::handlers::admin::ImpersonationResponse,
+            crate::handlers::admin::AuditLogQuery,
+            crate::handlers::admin::AuditLogResponse,
+            crate::handlers::user_note::UserNoteResponse,
+            crate::handlers::user_note::CreateUserNoteRequest,
+            crate::handlers::settings::SettingsResponse,
+            crate::handlers::settings::RegistrationSettingsResponse,
+            crate::handlers::settings::UpdateSettingsRequest,
+            crate::handlers::settings::UpdateRegistrationSettings,
+            crate::handlers::settings::MaintenanceSettingsResponse,
+            crate::handlers::settings::ConfigReloadResponse,
+            crate::handlers::settings::UpdateMaintenanceSettings,
+            crate::handlers::invite::InviteCodeResponse,
+            crate::handlers::invite::CreateInvitesRequest,
+            crate::handlers::invite::ListInvitesQuery,
+            crate::handlers::admin::ListUsersQuery,
+            crate::handlers::admin::AdminEmailResponse,
+            crate::handlers::admin::ReindexResponse,
             crate::handlers::admin::UpdateRoleRequest,
+            // Announcement
+            crate::handlers::announcement::AnnouncementResponse,
+            crate::handlers::announcement::CreateAnnouncementRequest,
+            crate::handlers::announcement::UpdateAnnouncementRequest,
+            crate::handlers::announcement::ActiveAnnouncementsQuery,
+            crate::handlers::badge::BadgeResponse,
+            crate::handlers::badge::UserBadgeResponse,
+            crate::handlers::badge::CreateBadgeRequest,
+            crate::handlers::badge::AwardBadgeRequest,
+            // Outbound links
+            crate::handlers::outbound::OutboundQuery,
+            // Link previews
+            crate::handlers::seo::OEmbedQuery,
+            crate::handlers::seo::OEmbedResponse,
         )
     ),
     tags(
+        (name = "health", description = "Liveness and readiness probes"),
         (name = "auth", description = "Authentication operations"),
         (name = "users", description = "User profile operations"),
+        (name = "email", description = "Email preferences, unsubscribe links and provider webhooks"),
         (name = "forums", description = "Forum management operations"),
         (name = "posts", description = "Post management operations"),
         (name = "comments", description = "Comment management operations"),
         (name = "tags", description = "Tag management operations"),
+        (name = "search", description = "Search operations"),
         (name = "votes", description = "Voting operations"),
         (name = "pow", description = "Proof-of-work operations"),
         (name = "follows", description = "Follow operations"),
@@ -189,15 +390,29 @@ use websocket::hub::NotificationHub;
         (name = "bookmarks", description = "Bookmark operations"),
         (name = "uploads", description = "File upload operations"),
         (name = "reports", description = "Report management operations"),
+        (name = "appeals", description = "Appeals against warnings and bans"),
+        (name = "moderation", description = "Moderation queue and claims"),
         (name = "admin", description = "Administrative operations"),
+        (name = "announcements", description = "Admin broadcast announcements"),
+        (name = "badges", description = "Profile badges, awarded by rules or by admins"),
+        (name = "emoji", description = "Custom emoji managed by admins"),
+        (name = "outbound", description = "Outbound link redirects and image proxy"),
+        (name = "seo", description = "robots.txt, sitemaps and link previews"),
+        (name = "federation", description = "ActivityPub actors, inboxes and WebFinger"),
     )
 )]
 struct ApiDoc;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let command = cli::Cli::parse().into_command();
     dotenv::dotenv().ok();
 
+    // Settings from config.toml, overridden by the environment (including
+    // .env), end up in the environment for everything read from there
+    let app_config = AppConfig::load()?;
+    app_config.apply_to_env();
+
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
@@ -206,31 +421,63 @@ async fn main() -> anyhow::Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    // Keep the guard alive so queued error reports are sent on shutdown
+    let _sentry = services::error_reporting::init(config::sentry::SentryConfig::from_env());
+
+    match command {
+        cli::Command::Serve { no_migrate } => serve(app_config, !no_migrate).await,
+        cli::Command::Migrate { tenant, action } => cli::migrate(&app_config, tenant, action).await,
+        cli::Command::Seed {
+            demo,
+            tenant,
+            admin,
+        } => cli::seed(&app_config, demo, tenant, admin).await,
+        cli::Command::Tenant { action } => cli::tenant(&app_config, action).await,
+    }
+}
+
+async fn serve(app_config: AppConfig, run_migrations: bool) -> anyhow::Result<()> {
     // Validate configuration before doing anything else
-    let jwt_config = validate_config()?;
+    app_config.log_effective();
+    let jwt_config = validate_config(&app_config)?;
+    let tls = config::tls::TlsConfig::from_env()?;
 
     // Initialize JWT config
     utils::jwt::init_jwt_config(jwt_config)?;
 
     tracing::info!("Starting Forum API v{}...", env!("CARGO_PKG_VERSION"));
 
-    let db = config::database::get_database().await?;
+    let db = config::database::get_database(&app_config.database).await?;
     tracing::info!("Database connected successfully");
 
-    migration::Migrator::up(&db, None).await?;
-    tracing::info!("Database migrations applied successfully");
+    if run_migrations {
+        migration::Migrator::up(&db, None).await?;
+        tracing::info!("Database migrations applied successfully");
+    } else {
+        let pending = migration::Migrator::get_pending_migrations(&db)
+            .await?
+            .len();
+        if pending > 0 {
+            tracing::warn!(
+                "{} pending migration(s) not applied; run `xjy migrate up`",
+                pending
+            );
+        }
+    }
 
     services::bootstrap_admin::ensure_bootstrap_admin(&db).await?;
 
+    let tenancy = config::tenancy::TenancyConfig::from_env();
+    if tenancy.enabled && db.get_database_backend() != sea_orm::DbBackend::Postgres {
+        return Err(anyhow::anyhow!("TENANCY_ENABLED requires PostgreSQL"));
+    }
+
     let hub = NotificationHub::new();
 
-    let upload_dir = env::var("UPLOAD_DIR").unwrap_or_else(|_| "./uploads".to_string());
-    let upload_config = UploadConfig {
-        upload_dir: upload_dir.clone(),
-    };
+    let upload_dir = app_config.uploads.dir.clone();
 
     // Redis/Cache is optional - graceful degradation if unavailable
-    let cache = match config::redis::get_redis().await {
+    let cache = match config::redis::get_redis(&app_config.redis.url).await {
         Ok(conn) => {
             tracing::info!("Redis connected successfully");
             Some(CacheService::new(conn))
@@ -242,99 +489,179 @@ async fn main() -> anyhow::Result<()> {
     };
 
     let email_service = services::email::EmailService::from_env();
-    if email_service.is_configured() {
-        tracing::info!("SMTP email service configured");
+    if let Some(provider) = email_service.provider_name() {
+        tracing::info!("Email provider {} configured", provider);
+        email_service.spawn_worker(db.clone());
+        services::digest::DigestService::new(
+            db.clone(),
+            email_service.clone(),
+            config::email::DigestConfig::from_env(),
+        )
+        .spawn_scheduler();
     } else {
-        tracing::warn!("SMTP not configured, emails will be skipped");
+        tracing::warn!("Email not configured, emails will be skipped");
     }
 
-    let mut app = create_app(&upload_dir)
+    let image_proxy = services::image_proxy::ImageProxy::from_env();
+
+    let search_index = services::search::SearchIndex::from_env();
+    tracing::info!("Search backend: {}", search_index.name());
+
+    let view_counter = services::view_counter::ViewCounter::from_env(cache.clone());
+    view_counter.spawn_flusher(db.clone());
+    let shutdown_views = (view_counter.clone(), db.clone());
+
+    services::quiet_hours::HeldNotifications::new(db.clone(), hub.clone()).spawn_scheduler();
+    services::post_fanout::PostFanoutQueue::new(db.clone(), hub.clone()).spawn_worker();
+    services::post_stats::PostStatsBroadcaster::new(
+        db.clone(),
+        hub.clone(),
+        config::websocket::WebSocketConfig::from_env(),
+    )
+    .spawn_scheduler();
+    services::badge::BadgeAwarder::new(db.clone()).spawn_scheduler();
+    services::emoji::EmojiCatalogLoader::new(db.clone(), None).spawn_scheduler();
+
+    services::upload::UploadCleanup::new(
+        db.clone(),
+        upload_dir.clone(),
+        config::upload_cleanup::UploadCleanupConfig::from_env(),
+    )
+    .spawn_scheduler();
+
+    let federation = config::federation::FederationConfig::from_env();
+    if federation.enabled {
+        tracing::info!("ActivityPub federation enabled");
+        services::federation::DeliveryQueue::new(db.clone(), federation).spawn_worker();
+    }
+
+    let tenants = tenancy.enabled.then(|| {
+        tracing::info!("Multi-tenancy enabled, tenants are resolved by Host");
+        services::tenant::TenantRegistry::new(
+            db.clone(),
+            app_config.database.clone(),
+            tenancy,
+            run_migrations,
+            cache.clone(),
+            email_service.clone(),
+            upload_dir.clone(),
+        )
+    });
+
+    let addr = format!("{}:{}", app_config.server.host, app_config.server.port);
+
+    let shared_config = SharedConfig::new(app_config);
+    #[cfg(unix)]
+    spawn_reload_on_sighup(shared_config.clone());
+
+    let mut app = create_app(&upload_dir);
+    if let Some(tenants) = &tenants {
+        // Inside the `Extension` layers, so a tenant's services replace the
+        // default community's
+        app = app.layer(axum_middleware::from_fn_with_state(
+            tenants.clone(),
+            crate::middleware::tenant::tenant_middleware,
+        ));
+    }
+    app = app
+        .layer(Extension(shared_config))
         .layer(Extension(db))
-        .layer(Extension(hub))
-        .layer(Extension(upload_config))
-        .layer(Extension(email_service));
+        .layer(Extension(hub.clone()))
+        .layer(Extension(email_service))
+        .layer(Extension(image_proxy))
+        .layer(Extension(search_index))
+        .layer(Extension(view_counter));
 
     if let Some(cache) = cache {
         app = app.layer(Extension(cache));
     }
 
-    let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-    let port = env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-    let addr = format!("{}:{}", host, port);
-
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    tracing::info!("Listening on http://{}", addr);
-    tracing::info!("Swagger UI available at http://{}/swagger-ui/", addr);
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    tracing::info!("Listening on {}://{}", scheme, addr);
+    tracing::info!("Swagger UI available at {}://{}/swagger-ui/", scheme, addr);
+
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    match tls {
+        Some(tls) => {
+            // `tap_io` only to get axum's `ConnectInfo<SocketAddr>` support
+            // for a custom listener
+            let listener = utils::tls::TlsListener::bind(listener, &tls)?.tap_io(|_| {});
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal(hub, tenants.clone()))
+                .await?
+        }
+        None => {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal(hub, tenants.clone()))
+                .await?
+        }
+    }
 
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .with_graceful_shutdown(shutdown_signal())
-    .await?;
+    let timeout = utils::shutdown::drain_timeout();
+    let unfinished = utils::shutdown::drain(timeout).await;
+    if unfinished > 0 {
+        tracing::warn!(
+            "{} background task(s) still running after {:?}, exiting anyway",
+            unfinished,
+            timeout
+        );
+    }
+
+    // Don't lose views still waiting for the next periodic flush
+    let (view_counter, db) = shutdown_views;
+    if let Err(e) = view_counter.flush(&db).await {
+        tracing::warn!("Failed to flush view counts on shutdown: {}", e);
+    }
+    for tenant in tenants.iter().flat_map(|t| t.contexts()) {
+        if let Err(e) = tenant.view_counter.flush(&tenant.db).await {
+            tracing::warn!(
+                "Failed to flush view counts of tenant '{}' on shutdown: {}",
+                tenant.tenant.slug,
+                e
+            );
+        }
+    }
 
     tracing::info!("Server shut down gracefully");
     Ok(())
 }
 
 /// Validate all required configuration at startup (fail-fast).
-fn validate_config() -> anyhow::Result<crate::config::jwt::JwtConfig> {
+fn validate_config(app_config: &AppConfig) -> anyhow::Result<crate::config::jwt::JwtConfig> {
     // JWT config — validated and cached
     let jwt_config = config::jwt::JwtConfig::from_env()?;
 
     // DATABASE_URL — checked here for early error; actual connection happens later
-    if env::var("DATABASE_URL").is_err() {
+    if app_config.database.url.is_none() {
         return Err(anyhow::anyhow!(
-            "DATABASE_URL environment variable must be set"
+            "DATABASE_URL (or database.url in the config file) must be set"
         ));
     }
 
+    // PoW — the secret falls back to JWT_SECRET, so this only fails on bad values
+    utils::pow::PowConfig::from_env()?;
+
+    // Initial admin — ADMIN_EMAIL without a usable ADMIN_PASSWORD fails here
+    services::bootstrap_admin::BootstrapAdminConfig::from_env()?;
+
     // Upload directory — create if needed
-    let upload_dir = env::var("UPLOAD_DIR").unwrap_or_else(|_| "./uploads".to_string());
-    std::fs::create_dir_all(&upload_dir).map_err(|e| {
+    let upload_dir = &app_config.uploads.dir;
+    std::fs::create_dir_all(upload_dir).map_err(|e| {
         anyhow::anyhow!("Failed to create upload directory '{}': {}", upload_dir, e)
     })?;
 
     Ok(jwt_config)
 }
 
-fn build_cors_layer() -> CorsLayer {
-    use axum::http::{header, HeaderValue, Method};
-
-    let origins_str = env::var("CORS_ORIGINS").unwrap_or_else(|_| "*".to_string());
-
-    let cors = CorsLayer::new()
-        .allow_methods([
-            Method::GET,
-            Method::POST,
-            Method::PUT,
-            Method::DELETE,
-            Method::OPTIONS,
-        ])
-        .allow_headers([
-            header::AUTHORIZATION,
-            header::CONTENT_TYPE,
-            header::HeaderName::from_static("x-request-id"),
-            header::HeaderName::from_static("traceparent"),
-        ]);
-
-    if origins_str == "*" {
-        cors.allow_origin(tower_http::cors::Any)
-    } else {
-        let origins: Vec<HeaderValue> = origins_str
-            .split(',')
-            .filter_map(|s| s.trim().parse().ok())
-            .collect();
-        cors.allow_origin(origins).allow_credentials(true)
-    }
-}
-
 fn create_app(upload_dir: &str) -> Router {
     Router::new()
-        .route("/", get(health_check))
         .merge(routes::create_routes())
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .nest_service("/uploads", ServeDir::new(upload_dir))
+        .layer(axum_middleware::from_fn(
+            crate::middleware::error_reporting::error_context_middleware,
+        ))
         .layer(
             TraceLayer::new_for_http().make_span_with(|request: &Request<_>| {
                 let request_id = request
@@ -352,42 +679,71 @@ fn create_app(upload_dir: &str) -> Router {
         )
         .layer(PropagateRequestIdLayer::x_request_id())
         .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
-        .layer(RequestBodyLimitLayer::new(6 * 1024 * 1024))
-        .layer(build_cors_layer())
+        .layer(axum_middleware::from_fn_with_state(
+            crate::middleware::cors::CorsState::default(),
+            crate::middleware::cors::cors_middleware,
+        ))
         .layer(axum_middleware::from_fn(
             crate::middleware::security::security_headers_middleware,
         ))
+        // One Sentry hub per request, so scope tags and the user id don't
+        // leak between requests
+        .layer(sentry::integrations::tower::SentryHttpLayer::new())
+        .layer(sentry::integrations::tower::NewSentryLayer::<
+            Request<axum::body::Body>,
+        >::new_from_top())
 }
 
-#[utoipa::path(
-    get,
-    path = "/",
-    responses(
-        (status = 200, description = "Health check successful", body = serde_json::Value)
-    )
-)]
-async fn health_check(Extension(db): Extension<DatabaseConnection>) -> impl IntoResponse {
-    let db_ok = db
-        .query_one(Statement::from_string(
-            sea_orm::DatabaseBackend::Postgres,
-            "SELECT 1".to_string(),
-        ))
-        .await
-        .is_ok();
-
-    let status = if db_ok { "ok" } else { "degraded" };
+/// Re-read the config file on SIGHUP.
+#[cfg(unix)]
+fn spawn_reload_on_sighup(shared_config: SharedConfig) {
+    use tokio::signal::unix::{signal, SignalKind};
 
-    Json(json!({
-        "status": status,
-        "service": "Forum API",
-        "version": env!("CARGO_PKG_VERSION"),
-        "database": db_ok,
-    }))
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::warn!("Failed to install SIGHUP handler: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            tracing::info!("SIGHUP received, reloading configuration");
+            if let Err(e) = shared_config.reload() {
+                tracing::error!("Configuration reload failed: {}", e);
+            }
+        }
+    });
 }
 
-async fn shutdown_signal() {
-    tokio::signal::ctrl_c()
-        .await
-        .expect("Failed to install CTRL+C signal handler");
+/// Resolves on ctrl-c or SIGTERM. The server then stops accepting
+/// connections and waits for in-flight requests; background jobs are told
+/// to stop and WebSocket clients get a close frame.
+async fn shutdown_signal(hub: NotificationHub, tenants: Option<services::tenant::TenantRegistry>) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install CTRL+C signal handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
     tracing::info!("Shutdown signal received, gracefully shutting down...");
+    utils::shutdown::trigger();
+    hub.close_all();
+    for tenant in tenants.iter().flat_map(|t| t.contexts()) {
+        tenant.hub.close_all();
+    }
 }
@@ -0,0 +1,33 @@
+//! API v2
+//!
+//! `/api/v2` serves the same routes as `/api/v1` with corrected status
+//! codes: 201 when something was created and 204 without a body after a
+//! delete. Handlers mark such responses with [`V2Status`]; v1 leaves them
+//! as they were so existing clients keep working.
+
+use crate::response::V2Status;
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+
+pub async fn api_v2_middleware(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    match response.extensions_mut().remove::<V2Status>() {
+        Some(V2Status(StatusCode::NO_CONTENT)) => {
+            let (mut parts, _) = response.into_parts();
+            parts.status = StatusCode::NO_CONTENT;
+            parts.headers.remove(header::CONTENT_TYPE);
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::empty())
+        }
+        Some(V2Status(status)) => {
+            *response.status_mut() = status;
+            response
+        }
+        None => response,
+    }
+}
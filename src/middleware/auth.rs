@@ -1,18 +1,89 @@
 use crate::{
     error::AppError,
+    middleware::permission::{role_has_permission, Permission},
     models::User,
+    services::{
+        audit::{AuditEntry, AuditLogService},
+        cache::CacheService,
+        email_template::Locale,
+        presence,
+    },
     utils::{
         cookie::{extract_cookie, ACCESS_TOKEN_COOKIE},
+        i18n,
         jwt::decode_jwt,
     },
 };
-use axum::{extract::Request, http::HeaderMap, middleware::Next, response::Response, Extension};
+use axum::{
+    extract::{OriginalUri, Request},
+    http::{HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
 use sea_orm::{DatabaseConnection, EntityTrait};
+use serde::{Deserialize, Serialize};
+
+/// How long a user's role and token version may be served from Redis.
+const AUTH_CACHE_TTL: u64 = 60;
+
+/// Header set on every response to a request made with an impersonation
+/// token, carrying the impersonating admin's ID.
+pub const IMPERSONATED_BY_HEADER: &str = "x-impersonated-by";
 
 /// Extracted user information from JWT token
 #[derive(Debug, Clone)]
 pub struct AuthUser {
     pub user_id: String,
+    /// Role at authentication time
+    pub role: String,
+    /// When the account was created, for new-account rate limits
+    pub created_at: chrono::NaiveDateTime,
+    /// Language the user reads messages in
+    pub locale: String,
+    /// Set when an admin is acting as this user
+    pub impersonation: Option<Impersonation>,
+}
+
+/// An admin acting as another user via an impersonation token.
+#[derive(Debug, Clone)]
+pub struct Impersonation {
+    pub admin_id: i32,
+    /// Only safe (GET/HEAD/OPTIONS) requests are allowed
+    pub read_only: bool,
+}
+
+impl Impersonation {
+    pub fn mode(&self) -> &'static str {
+        if self.read_only {
+            "read_only"
+        } else {
+            "full"
+        }
+    }
+}
+
+/// The parts of a user row that authentication depends on.
+#[derive(Debug, Serialize, Deserialize)]
+struct AuthState {
+    role: String,
+    token_version: i32,
+    created_at: chrono::NaiveDateTime,
+    /// Missing from states cached before it was added
+    #[serde(default)]
+    locale: String,
+}
+
+fn auth_cache_key(user_id: i32) -> String {
+    format!("auth:user:{}", user_id)
+}
+
+/// Drop a user's cached auth state after a role change, ban, password change
+/// or logout-everywhere.
+pub async fn invalidate_cached_auth(cache: Option<&CacheService>, user_id: i32) {
+    if let Some(cache) = cache {
+        cache.invalidate(&auth_cache_key(user_id)).await;
+    }
 }
 
 /// JWT authentication middleware
@@ -21,17 +92,144 @@ pub struct AuthUser {
 /// checks the user is not banned, and adds user info to request extensions.
 pub async fn auth_middleware(
     Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
     headers: HeaderMap,
-    mut request: Request,
+    request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
     // Prefer Authorization: Bearer, fallback to HttpOnly cookie.
-    let token = extract_bearer_token(&headers)
-        .or_else(|| extract_cookie(&headers, ACCESS_TOKEN_COOKIE))
-        .ok_or(AppError::Unauthorized)?;
+    let token = extract_token(&headers).ok_or(AppError::Unauthorized)?;
+    let auth_user = authenticate(&db, cache.as_ref().map(|c| &c.0), &token, false).await?;
+
+    // Continue to next handler
+    run_as(db, auth_user, request, next).await
+}
 
+/// JWT authentication that lets banned users through
+///
+/// For the few routes a banned user still needs, such as appealing the ban.
+/// Handlers see the `banned` role, which grants no permissions.
+pub async fn allow_banned_auth_middleware(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let token = extract_token(&headers).ok_or(AppError::Unauthorized)?;
+    let auth_user = authenticate(&db, cache.as_ref().map(|c| &c.0), &token, true).await?;
+    run_as(db, auth_user, request, next).await
+}
+
+/// Optional JWT authentication for public routes
+///
+/// Adds `AuthUser` when a valid token is present so handlers can personalize
+/// responses; missing or invalid tokens fall through as anonymous.
+pub async fn optional_auth_middleware(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if let Some(token) = extract_token(&headers) {
+        if let Ok(auth_user) = authenticate(&db, cache.as_ref().map(|c| &c.0), &token, false).await
+        {
+            return run_as(db, auth_user, request, next).await;
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+/// Run the request as `auth_user` in their locale, noting that the user is
+/// active.
+/// Impersonated requests don't count as activity; they are held to their
+/// mode, flagged with `IMPERSONATED_BY_HEADER` and written to the audit log.
+async fn run_as(
+    db: DatabaseConnection,
+    auth_user: AuthUser,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let impersonation = auth_user.impersonation.clone();
+    let target_user_id = auth_user.user_id.parse().ok();
+    if let Some(locale) = Locale::parse(&auth_user.locale) {
+        i18n::prefer(locale);
+    }
+    request.extensions_mut().insert(auth_user);
+    let Some(impersonation) = impersonation else {
+        if let Some(user_id) = target_user_id {
+            if let Err(e) = presence::touch(&db, user_id).await {
+                tracing::warn!("Failed to update last seen of user {}: {}", user_id, e);
+            }
+        }
+        return Ok(next.run(request).await);
+    };
+
+    let method = request.method().clone();
+    // Full path, including the prefix routes are nested under
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    if impersonation.read_only && !method.is_safe() {
+        record_impersonated_request(
+            &db,
+            &impersonation,
+            target_user_id,
+            &method,
+            path,
+            StatusCode::FORBIDDEN,
+        )
+        .await;
+        return Err(AppError::Forbidden);
+    }
+
+    let mut response = next.run(request).await;
+    response.headers_mut().insert(
+        IMPERSONATED_BY_HEADER,
+        HeaderValue::from(impersonation.admin_id),
+    );
+    let status = response.status();
+    record_impersonated_request(&db, &impersonation, target_user_id, &method, path, status).await;
+    Ok(response)
+}
+
+async fn record_impersonated_request(
+    db: &DatabaseConnection,
+    impersonation: &Impersonation,
+    target_user_id: Option<i32>,
+    method: &Method,
+    path: String,
+    status: StatusCode,
+) {
+    AuditLogService::new(db.clone())
+        .record(AuditEntry {
+            actor_id: Some(impersonation.admin_id),
+            action: "impersonated_request",
+            target_user_id,
+            method: Some(method.to_string()),
+            path: Some(path),
+            status: Some(status.as_u16() as i32),
+            detail: Some(impersonation.mode().to_string()),
+        })
+        .await;
+}
+
+fn extract_token(headers: &HeaderMap) -> Option<String> {
+    extract_bearer_token(headers).or_else(|| extract_cookie(headers, ACCESS_TOKEN_COOKIE))
+}
+
+/// Verify an access token and the state of the user it belongs to.
+async fn authenticate(
+    db: &DatabaseConnection,
+    cache: Option<&CacheService>,
+    token: &str,
+    allow_banned: bool,
+) -> Result<AuthUser, AppError> {
     // Verify JWT
-    let claims = decode_jwt(&token).map_err(|_| AppError::Unauthorized)?;
+    let claims = decode_jwt(token).map_err(|_| AppError::Unauthorized)?;
 
     // Access routes must use access token (not refresh token).
     if !crate::utils::jwt::is_access_token(&claims) {
@@ -44,23 +242,83 @@ pub async fn auth_middleware(
         .parse()
         .map_err(|_| AppError::Validation("Invalid user ID in token".to_string()))?;
 
-    let user = User::find_by_id(user_id)
-        .one(&db)
-        .await?
-        .ok_or(AppError::Unauthorized)?;
+    let mut state = match cache {
+        Some(cache) => cache.get::<AuthState>(&auth_cache_key(user_id)).await,
+        None => None,
+    };
+    // A cached version that disagrees with the token may predate a re-login;
+    // only the database can settle it.
+    if state
+        .as_ref()
+        .is_some_and(|s| s.token_version != claims.ver)
+    {
+        state = None;
+    }
+    let state = match state {
+        Some(state) => state,
+        None => {
+            let user = User::find_by_id(user_id)
+                .one(db)
+                .await?
+                .ok_or(AppError::Unauthorized)?;
+            let state = AuthState {
+                role: user.role,
+                token_version: user.token_version,
+                created_at: user.created_at,
+                locale: user.locale,
+            };
+            if let Some(cache) = cache {
+                cache
+                    .set(&auth_cache_key(user_id), &state, AUTH_CACHE_TTL)
+                    .await;
+            }
+            state
+        }
+    };
 
-    if user.role == "banned" {
+    if state.role == "banned" && !allow_banned {
         return Err(AppError::Forbidden);
     }
 
-    // Add user info to request extensions
-    let auth_user = AuthUser {
-        user_id: claims.sub,
+    // Role changes, bans, password changes and force-logout bump token_version.
+    if claims.ver != state.token_version {
+        return Err(AppError::Unauthorized);
+    }
+
+    let impersonation = match claims.act {
+        Some(admin_id) => Some(verify_impersonator(db, admin_id, claims.imp.as_deref()).await?),
+        None => None,
     };
-    request.extensions_mut().insert(auth_user);
 
-    // Continue to next handler
-    Ok(next.run(request).await)
+    crate::services::error_reporting::set_user(&claims.sub);
+
+    Ok(AuthUser {
+        user_id: claims.sub,
+        role: state.role,
+        created_at: state.created_at,
+        locale: state.locale,
+        impersonation,
+    })
+}
+
+/// An impersonation token is only good while its admin may still
+/// impersonate.
+async fn verify_impersonator(
+    db: &DatabaseConnection,
+    admin_id: i32,
+    mode: Option<&str>,
+) -> Result<Impersonation, AppError> {
+    let admin = User::find_by_id(admin_id)
+        .one(db)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+    if !role_has_permission(&admin.role, Permission::ImpersonateUsers) {
+        return Err(AppError::Unauthorized);
+    }
+    Ok(Impersonation {
+        admin_id,
+        read_only: mode != Some("full"),
+    })
 }
 
 fn extract_bearer_token(headers: &HeaderMap) -> Option<String> {
@@ -84,22 +342,26 @@ pub fn parse_user_id(auth_user: &AuthUser) -> crate::error::AppResult<i32> {
         .map_err(|_| AppError::Validation("Invalid user ID".to_string()))
 }
 
-/// Verify the current user has admin role
-pub async fn require_admin(
-    db: &sea_orm::DatabaseConnection,
+/// Verify the current user's role grants `permission`, returning the user ID
+pub async fn require_permission(
     auth_user: &AuthUser,
+    permission: Permission,
 ) -> crate::error::AppResult<i32> {
     let user_id = parse_user_id(auth_user)?;
-    let auth_service = crate::services::auth::AuthService::new(db.clone());
-    let user = auth_service.get_user_by_id(user_id).await?;
-    if user.role != "admin" {
+    if !role_has_permission(&auth_user.role, permission) {
+        tracing::debug!(
+            user_id,
+            role = %auth_user.role,
+            permission = permission.as_str(),
+            "Permission denied"
+        );
         return Err(AppError::Forbidden);
     }
     Ok(user_id)
 }
 
 /// Extractor for AuthUser from request extensions
-use axum::extract::FromRequestParts;
+use axum::extract::{FromRequestParts, OptionalFromRequestParts};
 
 impl<S> FromRequestParts<S> for AuthUser
 where
@@ -118,3 +380,18 @@ where
             .ok_or(AppError::Unauthorized)
     }
 }
+
+/// `Option<AuthUser>` for routes behind `optional_auth_middleware`
+impl<S> OptionalFromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<AuthUser>().cloned())
+    }
+}
@@ -0,0 +1,90 @@
+//! CORS, following `CORS_ORIGINS` across configuration reloads.
+
+use crate::config::app::SharedConfig;
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderValue, Method},
+    middleware::Next,
+    response::Response,
+};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use tower::{Layer, ServiceExt};
+use tower_http::cors::CorsLayer;
+
+/// The layer built for the origins last seen, rebuilt when they change.
+#[derive(Clone, Default)]
+pub struct CorsState(Arc<Mutex<Option<(String, CorsLayer)>>>);
+
+impl CorsState {
+    fn layer_for(&self, origins: &str) -> CorsLayer {
+        let mut cached = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        match cached.as_ref() {
+            Some((built_for, layer)) if built_for == origins => layer.clone(),
+            _ => {
+                let layer = build_cors_layer(origins);
+                *cached = Some((origins.to_string(), layer.clone()));
+                layer
+            }
+        }
+    }
+}
+
+/// `*` allows any origin without credentials; a comma-separated list allows
+/// those origins with credentials.
+fn build_cors_layer(origins: &str) -> CorsLayer {
+    let cors = CorsLayer::new()
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::HeaderName::from_static("x-request-id"),
+            header::HeaderName::from_static("traceparent"),
+        ]);
+
+    if origins.trim() == "*" {
+        cors.allow_origin(tower_http::cors::Any)
+    } else {
+        let origins: Vec<HeaderValue> = origins
+            .split(',')
+            .filter_map(|s| s.trim().parse().ok())
+            .collect();
+        cors.allow_origin(origins).allow_credentials(true)
+    }
+}
+
+/// Apply the CORS policy for the current `CORS_ORIGINS`. Needs the
+/// `SharedConfig` extension layered outside it.
+pub async fn cors_middleware(
+    State(state): State<CorsState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let origins = request
+        .extensions()
+        .get::<SharedConfig>()
+        .map(|config| config.current().server.cors_origins.clone())
+        .unwrap_or_else(|| "*".to_string());
+
+    let mut next = Some(next);
+    let inner = tower::service_fn(move |request: Request<Body>| {
+        let next = next.take().expect("CORS inner service called once");
+        async move { Ok::<_, Infallible>(next.run(request).await) }
+    });
+    match state
+        .layer_for(&origins)
+        .layer(inner)
+        .oneshot(request)
+        .await
+    {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
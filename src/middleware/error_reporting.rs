@@ -0,0 +1,30 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+
+/// Tag the request's Sentry scope with its route and request id, so captured
+/// errors and panics can be traced back to the request. Runs inside the
+/// per-request hub created by `sentry::integrations::tower::NewSentryLayer`.
+pub async fn error_context_middleware(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    sentry::configure_scope(|scope| {
+        scope.set_tag("route", route);
+        if let Some(request_id) = request_id {
+            scope.set_tag("request_id", request_id);
+        }
+    });
+
+    next.run(request).await
+}
@@ -0,0 +1,89 @@
+//! JSON bodies for the errors axum answers itself: unknown paths,
+//! unsupported methods, and extractor rejections such as malformed JSON
+//! (400/422), a wrong content type (415) or a body over the route's limit
+//! (413). Those come back empty or as plain text, while clients expect the
+//! usual `{"error": ..., "code": ...}` body. The request id is included so a report can
+//! be matched with the logs.
+
+use crate::utils::i18n::t;
+use axum::{
+    body::to_bytes,
+    extract::Request,
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// Plain-text rejections are short; anything longer is cut off.
+const MAX_MESSAGE_BYTES: usize = 4096;
+
+/// Fallback for paths no route matches.
+pub async fn not_found(request: Request) -> Response {
+    let body = error_body(
+        "route_not_found",
+        t("route_not_found"),
+        request_id(request.headers()),
+    );
+    (StatusCode::NOT_FOUND, Json(body)).into_response()
+}
+
+/// Turn empty and plain-text 4xx responses into the error body. Headers
+/// such as `Allow` on a 405 are kept.
+pub async fn json_errors_middleware(request: Request, next: Next) -> Response {
+    let request_id = request_id(request.headers()).map(str::to_string);
+    let response = next.run(request).await;
+    let status = response.status();
+    let plain = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|v| v.starts_with("text/plain"));
+    if !status.is_client_error() || !plain {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_TYPE);
+    let text = to_bytes(body, MAX_MESSAGE_BYTES).await.unwrap_or_default();
+    let reason = status.canonical_reason().unwrap_or("Bad request");
+    let (code, message) = match status {
+        StatusCode::METHOD_NOT_ALLOWED => (
+            "method_not_allowed".to_string(),
+            t("method_not_allowed").to_string(),
+        ),
+        StatusCode::PAYLOAD_TOO_LARGE => (
+            "request_too_large".to_string(),
+            t("request_too_large").to_string(),
+        ),
+        _ if !text.is_empty() => (
+            status_code_name(reason),
+            String::from_utf8_lossy(&text).trim().to_string(),
+        ),
+        _ => (status_code_name(reason), reason.to_string()),
+    };
+    (
+        parts,
+        Json(error_body(&code, &message, request_id.as_deref())),
+    )
+        .into_response()
+}
+
+/// `unsupported_media_type` for "Unsupported Media Type".
+fn status_code_name(reason: &str) -> String {
+    reason.to_ascii_lowercase().replace([' ', '-'], "_")
+}
+
+fn error_body(code: &str, message: &str, request_id: Option<&str>) -> serde_json::Value {
+    match request_id {
+        Some(request_id) => json!({ "error": message, "code": code, "request_id": request_id }),
+        None => json!({ "error": message, "code": code }),
+    }
+}
+
+/// Set by `SetRequestIdLayer`, or by the client.
+fn request_id(headers: &HeaderMap) -> Option<&str> {
+    headers.get("x-request-id").and_then(|v| v.to_str().ok())
+}
@@ -0,0 +1,347 @@
+//! Idempotent retries
+//!
+//! A client whose POST timed out can't tell whether it went through. With an
+//! `Idempotency-Key` header a retry is safe: the first response (anything
+//! but a 5xx) is kept for `IDEMPOTENCY_TTL_SECONDS` and replayed, marked
+//! `Idempotent-Replayed: true`, to later requests from the same user with
+//! the same key and path. Reusing a key for a different body is refused with
+//! 400, and a retry arriving while the first request is still running gets
+//! 409.
+//!
+//! Responses are kept in Redis, or in process memory without it.
+
+use crate::config::body_limit::BodyLimitConfig;
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use crate::response::V2Status;
+use crate::services::cache::CacheService;
+use crate::utils::tenant;
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const REPLAYED: &str = "idempotent-replayed";
+const MAX_KEY_LEN: usize = 255;
+/// How long a request may hold its key before a retry is let through
+const LOCK_TTL_SECONDS: u64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredResponse {
+    /// SHA-256 of the request body the response was for
+    fingerprint: String,
+    status: u16,
+    content_type: Option<String>,
+    /// Status under `/api/v2`, when it differs
+    #[serde(default)]
+    v2_status: Option<u16>,
+    /// Response body, base64
+    body: String,
+}
+
+/// Replay the stored response to POSTs carrying an `Idempotency-Key` the
+/// user already used on this path. Layer it inside the auth middleware so
+/// keys are scoped per user.
+pub async fn idempotency_middleware(
+    cache: Option<Extension<CacheService>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY) else {
+        return next.run(request).await;
+    };
+    let Some(key) = key
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|k| !k.is_empty() && k.len() <= MAX_KEY_LEN)
+    else {
+        return AppError::Validation(format!(
+            "Idempotency-Key must be 1 to {} visible ASCII characters",
+            MAX_KEY_LEN
+        ))
+        .into_response();
+    };
+
+    let user = request
+        .extensions()
+        .get::<AuthUser>()
+        .map_or("", |u| u.user_id.as_str());
+    let store_key = format!(
+        "idempotency:{}:{}",
+        user,
+        hex(&Sha256::digest(format!("{} {}", request.uri().path(), key)))
+    );
+
+    let (parts, body) = request.into_parts();
+    // Buffered here, so the route's `DefaultBodyLimit` would come too late
+    let Ok(body) = to_bytes(body, max_body_bytes()).await else {
+        return AppError::PayloadTooLarge.into_response();
+    };
+    let fingerprint = hex(&Sha256::digest(&body));
+    let request = Request::from_parts(parts, Body::from(body));
+
+    let store = match &cache {
+        Some(Extension(cache)) => Store::Redis(cache),
+        None => Store::Memory(memory_store()),
+    };
+    if let Some(stored) = store.get(&store_key).await {
+        return replay(stored, &fingerprint);
+    }
+    match store.lock(&store_key).await {
+        Some(true) => {}
+        Some(false) => {
+            return AppError::Conflict(
+                "A request with this Idempotency-Key is still being processed".to_string(),
+            )
+            .into_response()
+        }
+        None => {
+            // Fail open: without the store the request just isn't deduplicated
+            tracing::warn!("Idempotency store unavailable, processing request as is");
+            return next.run(request).await;
+        }
+    }
+    // The first request may have finished between the lookup and the lock
+    if let Some(stored) = store.get(&store_key).await {
+        store.unlock(&store_key).await;
+        return replay(stored, &fingerprint);
+    }
+
+    let response = next.run(request).await;
+    let response = if response.status().is_server_error() {
+        response
+    } else {
+        save(&store, &store_key, fingerprint, response).await
+    };
+    store.unlock(&store_key).await;
+    response
+}
+
+/// Store `response` and hand it on.
+async fn save(store: &Store<'_>, key: &str, fingerprint: String, response: Response) -> Response {
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => return AppError::Internal(anyhow::anyhow!(e)).into_response(),
+    };
+    let stored = StoredResponse {
+        fingerprint,
+        status: parts.status.as_u16(),
+        content_type: parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        v2_status: parts.extensions.get::<V2Status>().map(|s| s.0.as_u16()),
+        body: STANDARD.encode(&body),
+    };
+    store.save(key, &stored, ttl()).await;
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn replay(stored: StoredResponse, fingerprint: &str) -> Response {
+    if stored.fingerprint != fingerprint {
+        return AppError::Validation(
+            "Idempotency-Key was already used for a different request".to_string(),
+        )
+        .into_response();
+    }
+
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let body = STANDARD.decode(stored.body).unwrap_or_default();
+    let mut response = (status, body).into_response();
+    if let Some(status) = stored.v2_status.and_then(|s| StatusCode::from_u16(s).ok()) {
+        response.extensions_mut().insert(V2Status(status));
+    }
+    let headers = response.headers_mut();
+    if let Some(content_type) = stored
+        .content_type
+        .and_then(|v| HeaderValue::from_str(&v).ok())
+    {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    headers.insert(REPLAYED, HeaderValue::from_static("true"));
+    response
+}
+
+fn ttl() -> Duration {
+    static TTL: OnceLock<Duration> = OnceLock::new();
+    *TTL.get_or_init(|| {
+        let seconds = std::env::var("IDEMPOTENCY_TTL_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|v: &u64| *v >= 1)
+            .unwrap_or(24 * 3600);
+        Duration::from_secs(seconds)
+    })
+}
+
+/// The keyed routes are the content routes.
+fn max_body_bytes() -> usize {
+    static LIMIT: OnceLock<usize> = OnceLock::new();
+    *LIMIT.get_or_init(|| BodyLimitConfig::from_env().content)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+enum Store<'a> {
+    Redis(&'a CacheService),
+    Memory(&'static MemoryStore),
+}
+
+impl Store<'_> {
+    async fn get(&self, key: &str) -> Option<StoredResponse> {
+        match self {
+            Store::Redis(cache) => cache.get(key).await,
+            Store::Memory(memory) => memory.get(&memory_key(key)),
+        }
+    }
+
+    /// Claim `key` for a request in progress. `None` if Redis is down.
+    async fn lock(&self, key: &str) -> Option<bool> {
+        match self {
+            Store::Redis(cache) => cache.set_nx(&format!("{key}:lock"), LOCK_TTL_SECONDS).await,
+            Store::Memory(memory) => Some(memory.lock(memory_key(key), Instant::now())),
+        }
+    }
+
+    async fn unlock(&self, key: &str) {
+        match self {
+            Store::Redis(cache) => cache.invalidate(&format!("{key}:lock")).await,
+            Store::Memory(memory) => memory.unlock(&memory_key(key)),
+        }
+    }
+
+    async fn save(&self, key: &str, response: &StoredResponse, ttl: Duration) {
+        match self {
+            Store::Redis(cache) => cache.set(key, response, ttl.as_secs()).await,
+            Store::Memory(memory) => memory.save(memory_key(key), response.clone(), ttl),
+        }
+    }
+}
+
+/// Redis keys are prefixed per tenant by the cache; in memory the tenant
+/// goes into the key.
+fn memory_key(key: &str) -> String {
+    match tenant::current() {
+        Some(slug) => format!("{slug}:{key}"),
+        None => key.to_string(),
+    }
+}
+
+enum Entry {
+    Locked,
+    Done(StoredResponse),
+}
+
+/// Fallback store without Redis, as `key -> (entry, expires_at)`.
+#[derive(Default)]
+struct MemoryStore {
+    entries: Mutex<HashMap<String, (Entry, Instant)>>,
+}
+
+fn memory_store() -> &'static MemoryStore {
+    static STORE: OnceLock<MemoryStore> = OnceLock::new();
+    STORE.get_or_init(MemoryStore::default)
+}
+
+impl MemoryStore {
+    fn get(&self, key: &str) -> Option<StoredResponse> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.get(key) {
+            Some((Entry::Done(stored), expires_at)) if *expires_at > Instant::now() => {
+                Some(stored.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn lock(&self, key: String, now: Instant) -> bool {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, (_, expires_at)| *expires_at > now);
+        match entries.get(&key) {
+            Some((Entry::Locked, _)) => false,
+            // Finished in the meantime; the caller finds it on its next lookup
+            Some((Entry::Done(_), _)) => true,
+            None => {
+                let expires_at = now + Duration::from_secs(LOCK_TTL_SECONDS);
+                entries.insert(key, (Entry::Locked, expires_at));
+                true
+            }
+        }
+    }
+
+    fn unlock(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if matches!(entries.get(key), Some((Entry::Locked, _))) {
+            entries.remove(key);
+        }
+    }
+
+    fn save(&self, key: String, response: StoredResponse, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(key, (Entry::Done(response), Instant::now() + ttl));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored() -> StoredResponse {
+        StoredResponse {
+            fingerprint: "abc".to_string(),
+            status: 200,
+            content_type: Some("application/json".to_string()),
+            v2_status: None,
+            body: STANDARD.encode("{}"),
+        }
+    }
+
+    #[test]
+    fn test_memory_store_locks_until_saved() {
+        let store = MemoryStore::default();
+        let now = Instant::now();
+        assert!(store.lock("k".to_string(), now));
+        assert!(!store.lock("k".to_string(), now));
+        assert!(store.get("k").is_none());
+
+        store.save("k".to_string(), stored(), Duration::from_secs(60));
+        store.unlock("k");
+        assert_eq!(store.get("k").unwrap().fingerprint, "abc");
+        assert!(store.lock("k".to_string(), now));
+
+        store.unlock("other");
+        assert!(store.lock("other".to_string(), now));
+        let later = now + Duration::from_secs(LOCK_TTL_SECONDS + 1);
+        assert!(store.lock("other".to_string(), later));
+    }
+
+    #[test]
+    fn test_replay_rejects_a_different_body() {
+        let response = replay(stored(), "abc");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[REPLAYED], "true");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        let response = replay(stored(), "def");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,29 @@
+//! Picks the locale API messages are translated into; see
+//! [`crate::utils::i18n`]. The chosen locale is echoed in `Content-Language`.
+
+use crate::services::email_template::Locale;
+use crate::utils::i18n;
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+pub async fn locale_middleware(request: Request, next: Next) -> Response {
+    let locale = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Locale::from_accept_language)
+        .unwrap_or(Locale::En);
+    i18n::scope(locale, async {
+        let mut response = next.run(request).await;
+        response.headers_mut().insert(
+            header::CONTENT_LANGUAGE,
+            HeaderValue::from_static(i18n::current().as_str()),
+        );
+        response
+    })
+    .await
+}
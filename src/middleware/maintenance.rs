@@ -0,0 +1,73 @@
+//! Maintenance mode
+//!
+//! While an admin has maintenance mode on, every write request from anyone
+//! but an admin is refused with 503 and `Retry-After`; reads keep working.
+//! Logging in and out stays possible so admins can get in to switch it off,
+//! and unsubscribe links keep working.
+
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permission::{role_has_permission, Permission};
+use crate::services::cache::CacheService;
+use crate::services::settings::{MaintenanceSettings, SettingsService};
+use crate::utils::i18n::t;
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use sea_orm::DatabaseConnection;
+
+/// Writes allowed during maintenance, as paths under `/api/v1`.
+const EXEMPT_PATHS: &[&str] = &[
+    "/auth/login",
+    "/auth/refresh",
+    "/auth/logout",
+    "/email/unsubscribe",
+];
+
+/// Refuse non-admin writes while maintenance mode is on. Layer it inside
+/// the auth middleware so admins are recognized.
+pub async fn maintenance_middleware(
+    Extension(db): Extension<DatabaseConnection>,
+    cache: Option<Extension<CacheService>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_admin = request
+        .extensions()
+        .get::<AuthUser>()
+        .is_some_and(|u| role_has_permission(&u.role, Permission::ManageSettings));
+    if request.method().is_safe() || is_admin || EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let settings = SettingsService::new(db)
+        .with_cache(cache.map(|Extension(c)| c))
+        .maintenance()
+        .await;
+    match settings {
+        Ok(settings) if settings.enabled => maintenance_response(&settings),
+        Ok(_) => next.run(request).await,
+        Err(e) => {
+            // Fail open: a settings lookup error shouldn't take writes down
+            tracing::warn!("Failed to read maintenance settings: {}", e);
+            next.run(request).await
+        }
+    }
+}
+
+fn maintenance_response(settings: &MaintenanceSettings) -> Response {
+    // The default message is translated, the admin's own is shown as given
+    let message = settings.message.as_deref().unwrap_or(t("maintenance"));
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(
+            header::RETRY_AFTER,
+            settings.retry_after_seconds.to_string(),
+        )],
+        Json(serde_json::json!({ "error": message, "code": "maintenance" })),
+    )
+        .into_response()
+}
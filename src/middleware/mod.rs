@@ -1,4 +1,14 @@
+pub mod api_version;
 pub mod auth;
+pub mod cors;
+pub mod error_reporting;
+pub mod fallback;
+pub mod idempotency;
+pub mod locale;
+pub mod maintenance;
+pub mod permission;
+pub mod rate_limit;
 pub mod security;
+pub mod tenant;
 
 pub use auth::*;
@@ -0,0 +1,193 @@
+//! Static role → permission policy.
+//!
+//! Capabilities are granted to roles here instead of checking `role == "admin"`
+//! at every call site, so that e.g. moderators can resolve reports without
+//! being able to change user roles.
+
+/// A single capability that can be checked with `require_permission`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    /// Create, update and delete forums
+    ManageForums,
+    /// Create, update and delete tags
+    ManageTags,
+    /// Pin and unpin posts
+    PinPosts,
+    /// Pin a comment on any post, not just one's own
+    PinComments,
+    /// Lock and unlock posts
+    LockPosts,
+    /// Delete any post regardless of author
+    DeleteAnyPost,
+    /// Delete any comment regardless of author
+    DeleteAnyComment,
+    /// View the edit history of comments
+    ViewCommentRevisions,
+    /// View the report queue
+    ViewReports,
+    /// Resolve reports (dismiss / hide / delete / warn)
+    ResolveReports,
+    /// Ban a reported user while resolving a report
+    BanUsers,
+    /// List users and change their roles
+    ManageUsers,
+    /// Read and write private staff notes on user accounts
+    ManageUserNotes,
+    /// View platform statistics
+    ViewStats,
+    /// View and retry outgoing emails
+    ManageEmails,
+    /// Rebuild the search index
+    ManageSearch,
+    /// Publish, edit and remove announcements
+    ManageAnnouncements,
+    /// Assign moderation queue items to other moderators
+    AssignQueueItems,
+    /// Review, accept and decline appeals against warnings and bans
+    ReviewAppeals,
+    /// Act as another user with a short-lived token
+    ImpersonateUsers,
+    /// Read the audit log
+    ViewAuditLog,
+    /// Download full exports of users, posts and reports
+    ExportData,
+    /// Search hidden posts and comments and banned users
+    SearchHiddenContent,
+    /// Change site-wide settings such as who may register
+    ManageSettings,
+    /// Generate and revoke invite codes
+    ManageInvites,
+    /// Create custom badges and award or revoke them
+    ManageBadges,
+    /// Add and remove custom emoji
+    ManageEmoji,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ManageForums => "manage_forums",
+            Permission::ManageTags => "manage_tags",
+            Permission::PinPosts => "pin_posts",
+            Permission::PinComments => "pin_comments",
+            Permission::LockPosts => "lock_posts",
+            Permission::DeleteAnyPost => "delete_any_post",
+            Permission::DeleteAnyComment => "delete_any_comment",
+            Permission::ViewCommentRevisions => "view_comment_revisions",
+            Permission::ViewReports => "view_reports",
+            Permission::ResolveReports => "resolve_reports",
+            Permission::BanUsers => "ban_users",
+            Permission::ManageUsers => "manage_users",
+            Permission::ManageUserNotes => "manage_user_notes",
+            Permission::ViewStats => "view_stats",
+            Permission::ManageSearch => "manage_search",
+            Permission::ManageEmails => "manage_emails",
+            Permission::ManageAnnouncements => "manage_announcements",
+            Permission::AssignQueueItems => "assign_queue_items",
+            Permission::ReviewAppeals => "review_appeals",
+            Permission::ImpersonateUsers => "impersonate_users",
+            Permission::ViewAuditLog => "view_audit_log",
+            Permission::ExportData => "export_data",
+            Permission::SearchHiddenContent => "search_hidden_content",
+            Permission::ManageSettings => "manage_settings",
+            Permission::ManageInvites => "manage_invites",
+            Permission::ManageBadges => "manage_badges",
+            Permission::ManageEmoji => "manage_emoji",
+        }
+    }
+}
+
+const ADMIN_PERMISSIONS: &[Permission] = &[
+    Permission::ManageForums,
+    Permission::ManageTags,
+    Permission::PinPosts,
+    Permission::PinComments,
+    Permission::LockPosts,
+    Permission::DeleteAnyPost,
+    Permission::DeleteAnyComment,
+    Permission::ViewCommentRevisions,
+    Permission::ViewReports,
+    Permission::ResolveReports,
+    Permission::BanUsers,
+    Permission::ManageUsers,
+    Permission::ManageUserNotes,
+    Permission::ViewStats,
+    Permission::ManageSearch,
+    Permission::ManageEmails,
+    Permission::ManageAnnouncements,
+    Permission::AssignQueueItems,
+    Permission::ReviewAppeals,
+    Permission::ImpersonateUsers,
+    Permission::ViewAuditLog,
+    Permission::ExportData,
+    Permission::SearchHiddenContent,
+    Permission::ManageSettings,
+    Permission::ManageInvites,
+    Permission::ManageBadges,
+    Permission::ManageEmoji,
+];
+
+const MODERATOR_PERMISSIONS: &[Permission] = &[
+    Permission::PinPosts,
+    Permission::PinComments,
+    Permission::LockPosts,
+    Permission::DeleteAnyPost,
+    Permission::DeleteAnyComment,
+    Permission::ViewCommentRevisions,
+    Permission::ViewReports,
+    Permission::ResolveReports,
+    Permission::BanUsers,
+    Permission::ManageUserNotes,
+    Permission::SearchHiddenContent,
+];
+
+/// Permissions granted to a role. Unknown roles get nothing.
+pub fn permissions_for_role(role: &str) -> &'static [Permission] {
+    match role {
+        "admin" => ADMIN_PERMISSIONS,
+        "moderator" => MODERATOR_PERMISSIONS,
+        _ => &[],
+    }
+}
+
+pub fn role_has_permission(role: &str, permission: Permission) -> bool {
+    permissions_for_role(role).contains(&permission)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_has_all_permissions() {
+        for perm in ADMIN_PERMISSIONS {
+            assert!(role_has_permission("admin", *perm));
+        }
+    }
+
+    #[test]
+    fn test_moderator_can_moderate_but_not_manage() {
+        assert!(role_has_permission("moderator", Permission::ResolveReports));
+        assert!(role_has_permission(
+            "moderator",
+            Permission::DeleteAnyComment
+        ));
+        assert!(!role_has_permission("moderator", Permission::ManageUsers));
+        assert!(!role_has_permission("moderator", Permission::ManageForums));
+    }
+
+    #[test]
+    fn test_user_and_banned_have_none() {
+        assert!(permissions_for_role("user").is_empty());
+        assert!(permissions_for_role("banned").is_empty());
+        assert!(permissions_for_role("unknown").is_empty());
+    }
+
+    #[test]
+    fn test_permission_names_are_unique() {
+        let mut names: Vec<_> = ADMIN_PERMISSIONS.iter().map(|p| p.as_str()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), ADMIN_PERMISSIONS.len());
+    }
+}
@@ -0,0 +1,266 @@
+//! Rate limiting
+//!
+//! Each route group has its own budget (see `RateLimitConfig`). Requests
+//! made with a valid token count against the user, anonymous ones against
+//! the peer IP, and accounts younger than `new_account_hours` draw from a
+//! separate, smaller budget. Limited responses carry `X-RateLimit-Limit`,
+//! `X-RateLimit-Remaining` and `X-RateLimit-Reset`; rejections are the usual
+//! JSON error with `retry_after_seconds` and a `Retry-After` header.
+//!
+//! After a configuration reload each group re-reads its rules, starting
+//! fresh counts only for budgets whose rule changed.
+
+use crate::config::app;
+use crate::config::rate_limit::{RateLimitConfig, RateLimitGroup, RateLimitRule};
+use crate::error::AppError;
+use crate::middleware::auth::AuthUser;
+use arc_swap::ArcSwap;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use governor::{
+    clock::{Clock, DefaultClock},
+    middleware::StateInformationMiddleware,
+    state::keyed::DefaultKeyedStateStore,
+    Quota, RateLimiter,
+};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::Duration;
+
+type KeyedLimiter = RateLimiter<
+    RateLimitKey,
+    DefaultKeyedStateStore<RateLimitKey>,
+    DefaultClock,
+    StateInformationMiddleware,
+>;
+
+/// Who a request is counted against.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum RateLimitKey {
+    User(String),
+    Ip(IpAddr),
+}
+
+/// The budgets of one route group.
+#[derive(Clone)]
+pub struct GroupLimiter {
+    group: RateLimitGroup,
+    budgets: Arc<ArcSwap<Budgets>>,
+}
+
+struct Budgets {
+    /// Configuration generation these were built for
+    generation: u64,
+    established: Arc<KeyedLimiter>,
+    established_rule: RateLimitRule,
+    new_account: Arc<KeyedLimiter>,
+    new_account_rule: RateLimitRule,
+    new_account_age: chrono::Duration,
+}
+
+impl Budgets {
+    /// Budgets for `config`, keeping the counts of `previous` where the
+    /// rule is unchanged.
+    fn build(
+        config: &RateLimitConfig,
+        group: RateLimitGroup,
+        generation: u64,
+        previous: Option<&Budgets>,
+    ) -> Self {
+        let limiter =
+            |rule: RateLimitRule, kept: Option<(&Arc<KeyedLimiter>, RateLimitRule)>| match kept {
+                Some((limiter, kept_rule)) if kept_rule == rule => limiter.clone(),
+                _ => Arc::new(keyed_limiter(rule)),
+            };
+        let established_rule = config.rule(group, false);
+        let new_account_rule = config.rule(group, true);
+        Self {
+            generation,
+            established: limiter(
+                established_rule,
+                previous.map(|p| (&p.established, p.established_rule)),
+            ),
+            established_rule,
+            new_account: limiter(
+                new_account_rule,
+                previous.map(|p| (&p.new_account, p.new_account_rule)),
+            ),
+            new_account_rule,
+            new_account_age: chrono::Duration::hours(config.new_account_hours),
+        }
+    }
+
+    fn is_new_account(&self, created_at: chrono::NaiveDateTime) -> bool {
+        chrono::Utc::now().naive_utc() - created_at < self.new_account_age
+    }
+}
+
+impl GroupLimiter {
+    pub fn new(config: &RateLimitConfig, group: RateLimitGroup) -> Self {
+        let budgets = Budgets::build(config, group, app::generation(), None);
+        Self {
+            group,
+            budgets: Arc::new(ArcSwap::from_pointee(budgets)),
+        }
+    }
+
+    /// The current budgets, rebuilt if the configuration was reloaded.
+    fn budgets(&self) -> Arc<Budgets> {
+        let budgets = self.budgets.load_full();
+        let generation = app::generation();
+        if budgets.generation == generation {
+            return budgets;
+        }
+        let rebuilt = Arc::new(Budgets::build(
+            &RateLimitConfig::from_env(),
+            self.group,
+            generation,
+            Some(&budgets),
+        ));
+        self.budgets.store(rebuilt.clone());
+        rebuilt
+    }
+}
+
+fn keyed_limiter(rule: RateLimitRule) -> KeyedLimiter {
+    let quota = Quota::with_period(Duration::from_secs(rule.per_second))
+        .and_then(|q| NonZeroU32::new(rule.burst_size).map(|burst| q.allow_burst(burst)))
+        .expect("Invalid rate limit configuration");
+    RateLimiter::keyed(quota).with_middleware::<StateInformationMiddleware>()
+}
+
+/// Count the request against its user or IP, refusing it with 429 once the
+/// budget is spent. Layer it inside the auth middleware so users are known.
+pub async fn rate_limit_middleware(
+    State(limiter): State<GroupLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let budgets = limiter.budgets();
+    let (key, new_account) = match request.extensions().get::<AuthUser>() {
+        Some(user) => (
+            RateLimitKey::User(user.user_id.clone()),
+            budgets.is_new_account(user.created_at),
+        ),
+        None => (RateLimitKey::Ip(peer_ip(&request)), false),
+    };
+    let limiter = if new_account {
+        &budgets.new_account
+    } else {
+        &budgets.established
+    };
+
+    match limiter.check_key(&key) {
+        Ok(snapshot) => {
+            let quota = snapshot.quota();
+            let remaining = snapshot.remaining_burst_capacity();
+            let mut response = next.run(request).await;
+            insert_headers(
+                response.headers_mut(),
+                quota.burst_size().get(),
+                remaining,
+                time_to_full(quota, remaining, Duration::ZERO),
+            );
+            response
+        }
+        Err(not_until) => {
+            let quota = not_until.quota();
+            let wait = not_until.wait_time_from(limiter.clock().now());
+            let mut response = AppError::TooManyRequests {
+                retry_after_seconds: whole_seconds(wait),
+            }
+            .into_response();
+            insert_headers(
+                response.headers_mut(),
+                quota.burst_size().get(),
+                0,
+                time_to_full(quota, 0, wait),
+            );
+            response
+        }
+    }
+}
+
+fn insert_headers(headers: &mut HeaderMap, limit: u32, remaining: u32, reset: Duration) {
+    headers.insert("x-ratelimit-limit", HeaderValue::from(limit));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(remaining));
+    headers.insert("x-ratelimit-reset", HeaderValue::from(whole_seconds(reset)));
+}
+
+/// Roughly how long until the whole burst is available again, given
+/// `remaining` requests left and `wait` until the next one.
+fn time_to_full(quota: Quota, remaining: u32, wait: Duration) -> Duration {
+    let missing = quota.burst_size().get().saturating_sub(remaining);
+    if missing == 0 {
+        return Duration::ZERO;
+    }
+    if wait.is_zero() {
+        quota.replenish_interval() * missing
+    } else {
+        wait + quota.replenish_interval() * (missing - 1)
+    }
+}
+
+/// Whole seconds, rounded up so clients never retry too early.
+fn whole_seconds(wait: Duration) -> u64 {
+    wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+}
+
+/// The peer address; requests that somehow lack one share a single budget.
+fn peer_ip(request: &Request) -> IpAddr {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whole_seconds_rounds_up() {
+        assert_eq!(whole_seconds(Duration::from_secs(3)), 3);
+        assert_eq!(whole_seconds(Duration::from_millis(2001)), 3);
+        assert_eq!(whole_seconds(Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn test_time_to_full() {
+        let quota = Quota::with_period(Duration::from_secs(10))
+            .unwrap()
+            .allow_burst(NonZeroU32::new(3).unwrap());
+        assert_eq!(time_to_full(quota, 3, Duration::ZERO), Duration::ZERO);
+        assert_eq!(
+            time_to_full(quota, 1, Duration::ZERO),
+            Duration::from_secs(20)
+        );
+        assert_eq!(
+            time_to_full(quota, 0, Duration::from_secs(4)),
+            Duration::from_secs(24)
+        );
+    }
+
+    #[test]
+    fn test_users_and_ips_have_separate_budgets() {
+        let limiter = keyed_limiter(RateLimitRule {
+            per_second: 60,
+            burst_size: 1,
+        });
+        let user = RateLimitKey::User("1".to_string());
+        assert!(limiter.check_key(&user).is_ok());
+        assert!(limiter.check_key(&user).is_err());
+        assert!(limiter
+            .check_key(&RateLimitKey::User("2".to_string()))
+            .is_ok());
+        assert!(limiter
+            .check_key(&RateLimitKey::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST)))
+            .is_ok());
+    }
+}
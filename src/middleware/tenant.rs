@@ -0,0 +1,62 @@
+//! Tenant routing
+//!
+//! Resolves the `Host` of each request to a tenant and serves it with that
+//! tenant's database, cache, hub and other services instead of the default
+//! community's. Layer it inside the `Extension` layers so its inserts win.
+
+use crate::services::tenant::TenantRegistry;
+use crate::utils::tenant::{self, normalize_host};
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Uploads of tenant `<slug>` are stored and served under this path.
+const TENANT_UPLOADS: &str = "/uploads/tenants/";
+
+pub async fn tenant_middleware(
+    State(registry): State<TenantRegistry>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let host = request
+        .uri()
+        .host()
+        .or_else(|| {
+            request
+                .headers()
+                .get(header::HOST)
+                .and_then(|v| v.to_str().ok())
+        })
+        .map(normalize_host);
+
+    let context = match host {
+        Some(host) => match registry.resolve(&host).await {
+            Ok(context) => context,
+            Err(e) => {
+                tracing::error!("Failed to set up tenant for {}: {}", host, e);
+                return StatusCode::SERVICE_UNAVAILABLE.into_response();
+            }
+        },
+        None => None,
+    };
+
+    // Every tenant's files sit under one upload directory; only serve a
+    // tenant's own
+    if let Some(rest) = request.uri().path().strip_prefix(TENANT_UPLOADS) {
+        let owner = rest.split('/').next().unwrap_or_default();
+        if context.as_ref().map(|c| c.tenant.slug.as_str()) != Some(owner) {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    }
+
+    match context {
+        Some(context) => {
+            context.apply(request.extensions_mut());
+            tenant::scope(context.tenant.slug.clone(), next.run(request)).await
+        }
+        None => next.run(request).await,
+    }
+}
@@ -1,4 +1,5 @@
 use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::DbBackend;
 
 #[derive(DeriveMigrationName)]
 pub struct Migration;
@@ -6,6 +7,10 @@ pub struct Migration;
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // tsvector is Postgres-only; SQLite search matches substrings instead
+        if manager.get_database_backend() != DbBackend::Postgres {
+            return Ok(());
+        }
         let db = manager.get_connection();
 
         // Add generated tsvector column for full-text search
@@ -27,6 +32,9 @@ impl MigrationTrait for Migration {
     }
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Postgres {
+            return Ok(());
+        }
         let db = manager.get_connection();
 
         db.execute_unprepared("DROP INDEX IF EXISTS idx_posts_search")
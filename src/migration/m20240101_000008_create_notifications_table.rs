@@ -1,3 +1,4 @@
+use super::sql;
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -95,7 +96,8 @@ impl MigrationTrait for Migration {
 
         // Partial index for unread notifications
         let db = manager.get_connection();
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE INDEX IF NOT EXISTS idx_notifications_unread ON notifications (user_id, is_read) WHERE is_read = FALSE",
         )
         .await?;
@@ -1,3 +1,4 @@
+use super::sql;
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -8,12 +9,14 @@ impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "ALTER TABLE posts ADD COLUMN IF NOT EXISTS is_hidden BOOLEAN NOT NULL DEFAULT FALSE",
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "ALTER TABLE comments ADD COLUMN IF NOT EXISTS is_hidden BOOLEAN NOT NULL DEFAULT FALSE",
         )
         .await?;
@@ -24,11 +27,9 @@ impl MigrationTrait for Migration {
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared("ALTER TABLE posts DROP COLUMN IF EXISTS is_hidden")
-            .await?;
+        sql::execute(db, "ALTER TABLE posts DROP COLUMN IF EXISTS is_hidden").await?;
 
-        db.execute_unprepared("ALTER TABLE comments DROP COLUMN IF EXISTS is_hidden")
-            .await?;
+        sql::execute(db, "ALTER TABLE comments DROP COLUMN IF EXISTS is_hidden").await?;
 
         Ok(())
     }
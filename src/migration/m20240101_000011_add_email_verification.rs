@@ -1,3 +1,4 @@
+use super::sql;
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -8,17 +9,20 @@ impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT FALSE",
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verification_token VARCHAR(255) NULL",
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verification_expires TIMESTAMP NULL",
         )
         .await?;
@@ -29,12 +33,17 @@ impl MigrationTrait for Migration {
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared("ALTER TABLE users DROP COLUMN IF EXISTS email_verified")
-            .await?;
-        db.execute_unprepared("ALTER TABLE users DROP COLUMN IF EXISTS email_verification_token")
-            .await?;
-        db.execute_unprepared("ALTER TABLE users DROP COLUMN IF EXISTS email_verification_expires")
-            .await?;
+        sql::execute(db, "ALTER TABLE users DROP COLUMN IF EXISTS email_verified").await?;
+        sql::execute(
+            db,
+            "ALTER TABLE users DROP COLUMN IF EXISTS email_verification_token",
+        )
+        .await?;
+        sql::execute(
+            db,
+            "ALTER TABLE users DROP COLUMN IF EXISTS email_verification_expires",
+        )
+        .await?;
 
         Ok(())
     }
@@ -1,3 +1,4 @@
+use super::sql;
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -8,7 +9,8 @@ impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE TABLE IF NOT EXISTS bookmarks (
                 id SERIAL PRIMARY KEY,
                 user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
@@ -18,12 +20,14 @@ impl MigrationTrait for Migration {
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE UNIQUE INDEX IF NOT EXISTS idx_bookmarks_user_post ON bookmarks(user_id, post_id)",
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE INDEX IF NOT EXISTS idx_bookmarks_user_id ON bookmarks(user_id)",
         )
         .await?;
@@ -33,8 +37,7 @@ impl MigrationTrait for Migration {
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
-        db.execute_unprepared("DROP TABLE IF EXISTS bookmarks")
-            .await?;
+        sql::execute(db, "DROP TABLE IF EXISTS bookmarks").await?;
         Ok(())
     }
 }
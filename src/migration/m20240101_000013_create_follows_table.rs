@@ -1,3 +1,4 @@
+use super::sql;
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -8,7 +9,8 @@ impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE TABLE IF NOT EXISTS follows (
                 id SERIAL PRIMARY KEY,
                 follower_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
@@ -19,17 +21,20 @@ impl MigrationTrait for Migration {
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE UNIQUE INDEX IF NOT EXISTS idx_follows_pair ON follows(follower_id, following_id)",
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE INDEX IF NOT EXISTS idx_follows_follower ON follows(follower_id)",
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE INDEX IF NOT EXISTS idx_follows_following ON follows(following_id)",
         )
         .await?;
@@ -39,8 +44,7 @@ impl MigrationTrait for Migration {
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
-        db.execute_unprepared("DROP TABLE IF EXISTS follows")
-            .await?;
+        sql::execute(db, "DROP TABLE IF EXISTS follows").await?;
         Ok(())
     }
 }
@@ -1,3 +1,4 @@
+use super::sql;
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -8,7 +9,8 @@ impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE TABLE IF NOT EXISTS tags (
                 id SERIAL PRIMARY KEY,
                 name VARCHAR(50) NOT NULL UNIQUE,
@@ -18,10 +20,10 @@ impl MigrationTrait for Migration {
         )
         .await?;
 
-        db.execute_unprepared("CREATE INDEX IF NOT EXISTS idx_tags_slug ON tags(slug)")
-            .await?;
+        sql::execute(db, "CREATE INDEX IF NOT EXISTS idx_tags_slug ON tags(slug)").await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE TABLE IF NOT EXISTS post_tags (
                 id SERIAL PRIMARY KEY,
                 post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
@@ -30,22 +32,25 @@ impl MigrationTrait for Migration {
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE UNIQUE INDEX IF NOT EXISTS idx_post_tags_pair ON post_tags(post_id, tag_id)",
         )
         .await?;
 
-        db.execute_unprepared("CREATE INDEX IF NOT EXISTS idx_post_tags_tag ON post_tags(tag_id)")
-            .await?;
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_post_tags_tag ON post_tags(tag_id)",
+        )
+        .await?;
 
         Ok(())
     }
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
-        db.execute_unprepared("DROP TABLE IF EXISTS post_tags")
-            .await?;
-        db.execute_unprepared("DROP TABLE IF EXISTS tags").await?;
+        sql::execute(db, "DROP TABLE IF EXISTS post_tags").await?;
+        sql::execute(db, "DROP TABLE IF EXISTS tags").await?;
         Ok(())
     }
 }
@@ -1,3 +1,4 @@
+use super::sql;
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -8,17 +9,20 @@ impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "ALTER TABLE users ADD COLUMN IF NOT EXISTS password_reset_token VARCHAR(255) NULL",
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "ALTER TABLE users ADD COLUMN IF NOT EXISTS password_reset_expires TIMESTAMP NULL",
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE INDEX IF NOT EXISTS idx_users_password_reset_token ON users (password_reset_token) WHERE password_reset_token IS NOT NULL",
         )
         .await?;
@@ -29,12 +33,17 @@ impl MigrationTrait for Migration {
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared("DROP INDEX IF EXISTS idx_users_password_reset_token")
-            .await?;
-        db.execute_unprepared("ALTER TABLE users DROP COLUMN IF EXISTS password_reset_token")
-            .await?;
-        db.execute_unprepared("ALTER TABLE users DROP COLUMN IF EXISTS password_reset_expires")
-            .await?;
+        sql::execute(db, "DROP INDEX IF EXISTS idx_users_password_reset_token").await?;
+        sql::execute(
+            db,
+            "ALTER TABLE users DROP COLUMN IF EXISTS password_reset_token",
+        )
+        .await?;
+        sql::execute(
+            db,
+            "ALTER TABLE users DROP COLUMN IF EXISTS password_reset_expires",
+        )
+        .await?;
 
         Ok(())
     }
@@ -1,3 +1,4 @@
+use super::sql;
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -8,7 +9,8 @@ impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE TABLE IF NOT EXISTS refresh_tokens (
                 id SERIAL PRIMARY KEY,
                 user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
@@ -19,12 +21,14 @@ impl MigrationTrait for Migration {
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE INDEX IF NOT EXISTS idx_refresh_tokens_token ON refresh_tokens (token)",
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE INDEX IF NOT EXISTS idx_refresh_tokens_user_id ON refresh_tokens (user_id)",
         )
         .await?;
@@ -34,8 +38,7 @@ impl MigrationTrait for Migration {
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
-        db.execute_unprepared("DROP TABLE IF EXISTS refresh_tokens")
-            .await?;
+        sql::execute(db, "DROP TABLE IF EXISTS refresh_tokens").await?;
         Ok(())
     }
 }
@@ -1,3 +1,4 @@
+use super::sql;
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -8,13 +9,15 @@ impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE INDEX IF NOT EXISTS idx_notifications_user_created
              ON notifications (user_id, created_at DESC)",
         )
         .await?;
 
-        db.execute_unprepared(
+        sql::execute(
+            db,
             "CREATE INDEX IF NOT EXISTS idx_posts_forum_visible_created
              ON posts (forum_id, is_hidden, created_at DESC)",
         )
@@ -26,10 +29,8 @@ impl MigrationTrait for Migration {
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
 
-        db.execute_unprepared("DROP INDEX IF EXISTS idx_notifications_user_created")
-            .await?;
-        db.execute_unprepared("DROP INDEX IF EXISTS idx_posts_forum_visible_created")
-            .await?;
+        sql::execute(db, "DROP INDEX IF EXISTS idx_notifications_user_created").await?;
+        sql::execute(db, "DROP INDEX IF EXISTS idx_posts_forum_visible_created").await?;
 
         Ok(())
     }
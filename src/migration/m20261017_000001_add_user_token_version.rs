@@ -0,0 +1,30 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Bumped to invalidate every access token issued before a role change,
+        // ban, password change or admin force-logout.
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS token_version INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        sql::execute(db, "ALTER TABLE users DROP COLUMN IF EXISTS token_version").await?;
+
+        Ok(())
+    }
+}
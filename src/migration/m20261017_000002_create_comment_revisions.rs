@@ -0,0 +1,119 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum CommentRevisions {
+    Table,
+    Id,
+    CommentId,
+    EditorId,
+    Content,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Comments {
+    Table,
+    Id,
+    EditedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Checked here rather than with `add_column_if_not_exists`, which
+        // MySQL doesn't support
+        if !manager.has_column("comments", "edited_at").await? {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Comments::Table)
+                        .add_column(ColumnDef::new(Comments::EditedAt).timestamp().null())
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(CommentRevisions::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CommentRevisions::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(CommentRevisions::CommentId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(CommentRevisions::EditorId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(CommentRevisions::Content).text().not_null())
+                    .col(
+                        ColumnDef::new(CommentRevisions::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_comment_revisions_comment_id")
+                            .from(CommentRevisions::Table, CommentRevisions::CommentId)
+                            .to(Comments::Table, Comments::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_comment_revisions_editor_id")
+                            .from(CommentRevisions::Table, CommentRevisions::EditorId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_comment_revisions_comment_created_at")
+                    .table(CommentRevisions::Table)
+                    .col(CommentRevisions::CommentId)
+                    .col(CommentRevisions::CreatedAt)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CommentRevisions::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Comments::Table)
+                    .drop_column(Comments::EditedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
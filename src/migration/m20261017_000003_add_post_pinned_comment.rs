@@ -0,0 +1,33 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        sql::execute(
+            db,
+            "ALTER TABLE posts ADD COLUMN IF NOT EXISTS pinned_comment_id INTEGER \
+             REFERENCES comments(id) ON DELETE SET NULL",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        sql::execute(
+            db,
+            "ALTER TABLE posts DROP COLUMN IF EXISTS pinned_comment_id",
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS watched_posts (
+                id SERIAL PRIMARY KEY,
+                user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_watched_posts_user_post ON watched_posts(user_id, post_id)",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_watched_posts_post_id ON watched_posts(post_id)",
+        )
+        .await?;
+
+        // Whether commenting on a thread starts watching it.
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS auto_watch BOOLEAN NOT NULL DEFAULT TRUE",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        sql::execute(db, "ALTER TABLE users DROP COLUMN IF EXISTS auto_watch").await?;
+        sql::execute(db, "DROP TABLE IF EXISTS watched_posts").await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS post_reads (
+                id SERIAL PRIMARY KEY,
+                user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+                last_read_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_post_reads_user_post ON post_reads(user_id, post_id)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS post_reads").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Language for emails sent to the user.
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS locale VARCHAR(10) NOT NULL DEFAULT 'en'",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "ALTER TABLE email_outbox ADD COLUMN IF NOT EXISTS html_body TEXT",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(
+            db,
+            "ALTER TABLE email_outbox DROP COLUMN IF EXISTS html_body",
+        )
+        .await?;
+        sql::execute(db, "ALTER TABLE users DROP COLUMN IF EXISTS locale").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // 'off', 'daily' or 'weekly'
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS digest_frequency VARCHAR(10) NOT NULL DEFAULT 'off'",
+        )
+        .await?;
+
+        // One row per user and period; the unique key stops a digest being
+        // sent twice when several instances run the scheduler.
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS email_digests (
+                id SERIAL PRIMARY KEY,
+                user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                frequency VARCHAR(10) NOT NULL,
+                period_start TIMESTAMP NOT NULL,
+                period_end TIMESTAMP NOT NULL,
+                post_count INTEGER NOT NULL DEFAULT 0,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (user_id, period_end)
+            )",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS email_digests").await?;
+        sql::execute(
+            db,
+            "ALTER TABLE users DROP COLUMN IF EXISTS digest_frequency",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Categories of optional email a user has turned off
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS email_opt_outs (
+                user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                category VARCHAR(20) NOT NULL,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (user_id, category)
+            )",
+        )
+        .await?;
+
+        // Sent as the List-Unsubscribe header
+        sql::execute(
+            db,
+            "ALTER TABLE email_outbox ADD COLUMN IF NOT EXISTS unsubscribe_url TEXT",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(
+            db,
+            "ALTER TABLE email_outbox DROP COLUMN IF EXISTS unsubscribe_url",
+        )
+        .await?;
+        sql::execute(db, "DROP TABLE IF EXISTS email_opt_outs").await?;
+        Ok(())
+    }
+}
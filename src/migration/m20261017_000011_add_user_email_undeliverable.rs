@@ -0,0 +1,43 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // 'bounce' or 'complaint' once the provider reports the address;
+        // NULL while mail to it is delivered
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS email_undeliverable VARCHAR(20)",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS email_undeliverable_at TIMESTAMP",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(
+            db,
+            "ALTER TABLE users DROP COLUMN IF EXISTS email_undeliverable_at",
+        )
+        .await?;
+        sql::execute(
+            db,
+            "ALTER TABLE users DROP COLUMN IF EXISTS email_undeliverable",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Admin broadcasts; forum_id NULL targets every user
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS announcements (
+                id SERIAL PRIMARY KEY,
+                title VARCHAR(200) NOT NULL,
+                body TEXT NOT NULL,
+                forum_id INTEGER REFERENCES forums(id) ON DELETE CASCADE,
+                send_email BOOLEAN NOT NULL DEFAULT FALSE,
+                recipient_count INTEGER NOT NULL DEFAULT 0,
+                expires_at TIMESTAMP,
+                created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_announcements_forum_id ON announcements(forum_id)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS announcements").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Which moderator is working on a moderation queue item
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS mod_queue_claims (
+                item_type VARCHAR(20) NOT NULL,
+                item_id INTEGER NOT NULL,
+                claimed_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                claimed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (item_type, item_id)
+            )",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS mod_queue_claims").await?;
+        Ok(())
+    }
+}
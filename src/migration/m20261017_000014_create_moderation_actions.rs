@@ -0,0 +1,58 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // What resolving a report did
+        sql::execute(
+            db,
+            "ALTER TABLE reports
+                ADD COLUMN IF NOT EXISTS action VARCHAR(20),
+                ADD COLUMN IF NOT EXISTS resolution_note TEXT",
+        )
+        .await?;
+
+        // Moderation actions taken against a user or their content
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS moderation_actions (
+                id SERIAL PRIMARY KEY,
+                user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                moderator_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                action VARCHAR(20) NOT NULL,
+                report_id INTEGER REFERENCES reports(id) ON DELETE SET NULL,
+                target_type VARCHAR(20),
+                target_id INTEGER,
+                note TEXT,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_moderation_actions_user_id
+                ON moderation_actions(user_id, created_at)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS moderation_actions").await?;
+        sql::execute(
+            db,
+            "ALTER TABLE reports DROP COLUMN IF EXISTS action, DROP COLUMN IF EXISTS resolution_note",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // A user contesting a warning or ban
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS appeals (
+                id SERIAL PRIMARY KEY,
+                user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                moderation_action_id INTEGER REFERENCES moderation_actions(id) ON DELETE SET NULL,
+                reason TEXT NOT NULL,
+                status VARCHAR(20) NOT NULL DEFAULT 'pending',
+                decided_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                decided_at TIMESTAMP,
+                decision_note TEXT,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_appeals_status ON appeals(status, created_at)",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_appeals_user_id ON appeals(user_id)",
+        )
+        .await?;
+
+        // Reviewers' discussion of an appeal
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS appeal_comments (
+                id SERIAL PRIMARY KEY,
+                appeal_id INTEGER NOT NULL REFERENCES appeals(id) ON DELETE CASCADE,
+                user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                body TEXT NOT NULL,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_appeal_comments_appeal_id
+                ON appeal_comments(appeal_id, created_at)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS appeal_comments").await?;
+        sql::execute(db, "DROP TABLE IF EXISTS appeals").await?;
+        Ok(())
+    }
+}
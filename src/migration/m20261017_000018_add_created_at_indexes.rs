@@ -0,0 +1,37 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Tables bucketed by creation time in the admin time series.
+const TABLES: [&str; 4] = ["users", "posts", "comments", "votes"];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        for table in TABLES {
+            sql::execute(
+                db,
+                &format!(
+                    "CREATE INDEX IF NOT EXISTS idx_{table}_created_at ON {table} (created_at)"
+                ),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        for table in TABLES {
+            sql::execute(db, &format!("DROP INDEX IF EXISTS idx_{table}_created_at")).await?;
+        }
+
+        Ok(())
+    }
+}
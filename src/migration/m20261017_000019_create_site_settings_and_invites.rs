@@ -0,0 +1,58 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Site-wide settings changed at runtime by admins, one JSON document per key
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS site_settings (
+                key VARCHAR(50) PRIMARY KEY,
+                value JSONB NOT NULL,
+                updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS invite_codes (
+                id SERIAL PRIMARY KEY,
+                code VARCHAR(32) NOT NULL UNIQUE,
+                created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                max_uses INTEGER NOT NULL DEFAULT 1,
+                uses INTEGER NOT NULL DEFAULT 0,
+                note TEXT,
+                expires_at TIMESTAMP,
+                revoked_at TIMESTAMP,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        // Which invite a user signed up with
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS invite_code_id INTEGER
+                REFERENCES invite_codes(id) ON DELETE SET NULL",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "ALTER TABLE users DROP COLUMN IF EXISTS invite_code_id").await?;
+        sql::execute(db, "DROP TABLE IF EXISTS invite_codes").await?;
+        sql::execute(db, "DROP TABLE IF EXISTS site_settings").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::DbBackend;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // MySQL's stand-in for the tsvector column: MATCH ... AGAINST needs a
+        // FULLTEXT index over exactly the columns searched
+        if manager.get_database_backend() != DbBackend::MySql {
+            return Ok(());
+        }
+        let db = manager.get_connection();
+
+        sql::execute(
+            db,
+            "CREATE FULLTEXT INDEX IF NOT EXISTS idx_posts_fulltext ON posts(title, content)",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE FULLTEXT INDEX IF NOT EXISTS idx_comments_fulltext ON comments(content)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::MySql {
+            return Ok(());
+        }
+        let db = manager.get_connection();
+        sql::execute(db, "DROP INDEX IF EXISTS idx_comments_fulltext").await?;
+        sql::execute(db, "DROP INDEX IF EXISTS idx_posts_fulltext").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Link posts: the URL the post is about
+        sql::execute(db, "ALTER TABLE posts ADD COLUMN IF NOT EXISTS url TEXT").await?;
+
+        // Open Graph metadata fetched from linked pages, shared by every post
+        // linking the same URL. Keyed by the URL's SHA-256 since URLs are too
+        // long for a unique index on MySQL.
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS link_previews (
+                id SERIAL PRIMARY KEY,
+                url_hash VARCHAR(64) NOT NULL UNIQUE,
+                url TEXT NOT NULL,
+                status VARCHAR(20) NOT NULL DEFAULT 'pending',
+                title VARCHAR(300),
+                description TEXT,
+                image_url TEXT,
+                site_name VARCHAR(200),
+                fetched_at TIMESTAMP,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS link_previews").await?;
+        sql::execute(db, "ALTER TABLE posts DROP COLUMN IF EXISTS url").await?;
+        Ok(())
+    }
+}
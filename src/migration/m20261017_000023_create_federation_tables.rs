@@ -0,0 +1,94 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // The instance's RSA key pair, generated on first use. Every local
+        // actor publishes the same public key under its own key id.
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS federation_keys (
+                id INTEGER PRIMARY KEY,
+                private_key_pem TEXT NOT NULL,
+                public_key_pem TEXT NOT NULL,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        // Remote actors following a local user or forum. Actor ids are
+        // URLs, so they are matched by SHA-256 for the unique index.
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS federation_followers (
+                id SERIAL PRIMARY KEY,
+                actor_type VARCHAR(10) NOT NULL,
+                local_id INTEGER NOT NULL,
+                follower_hash VARCHAR(64) NOT NULL,
+                follower_id TEXT NOT NULL,
+                inbox TEXT NOT NULL,
+                shared_inbox TEXT,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (actor_type, local_id, follower_hash)
+            )",
+        )
+        .await?;
+
+        // Remote actor documents, for their inboxes and signing keys
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS federation_actors (
+                id SERIAL PRIMARY KEY,
+                actor_hash VARCHAR(64) NOT NULL UNIQUE,
+                actor_id TEXT NOT NULL,
+                inbox TEXT NOT NULL,
+                shared_inbox TEXT,
+                public_key_pem TEXT NOT NULL,
+                fetched_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        // Signed activities waiting to be POSTed to remote inboxes
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS federation_deliveries (
+                id SERIAL PRIMARY KEY,
+                inbox TEXT NOT NULL,
+                key_id TEXT NOT NULL,
+                activity TEXT NOT NULL,
+                status VARCHAR(20) NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                next_attempt_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                delivered_at TIMESTAMP,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_federation_deliveries_status_next_attempt ON federation_deliveries(status, next_attempt_at)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS federation_deliveries").await?;
+        sql::execute(db, "DROP TABLE IF EXISTS federation_actors").await?;
+        sql::execute(db, "DROP TABLE IF EXISTS federation_followers").await?;
+        sql::execute(db, "DROP TABLE IF EXISTS federation_keys").await?;
+        Ok(())
+    }
+}
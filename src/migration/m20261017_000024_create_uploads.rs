@@ -0,0 +1,43 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Files saved under UPLOAD_DIR, and what refers to them. Files
+        // nothing refers to are deleted after a grace period.
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS uploads (
+                id SERIAL PRIMARY KEY,
+                user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                path VARCHAR(300) NOT NULL UNIQUE,
+                size BIGINT NOT NULL,
+                mime VARCHAR(100) NOT NULL,
+                entity_type VARCHAR(20),
+                entity_id INTEGER,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_uploads_unreferenced ON uploads(created_at) WHERE entity_type IS NULL",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS uploads").await?;
+        Ok(())
+    }
+}
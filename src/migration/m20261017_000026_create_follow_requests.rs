@@ -0,0 +1,52 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Private users approve each follower
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS is_private BOOLEAN NOT NULL DEFAULT FALSE",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS follow_requests (
+                id SERIAL PRIMARY KEY,
+                requester_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                target_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                CHECK (requester_id != target_id)
+            )",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_follow_requests_pair ON follow_requests(requester_id, target_id)",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_follow_requests_target ON follow_requests(target_id, created_at)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS follow_requests").await?;
+        sql::execute(db, "ALTER TABLE users DROP COLUMN IF EXISTS is_private").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Quiet hours, as minutes after midnight in the user's timezone
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+                ADD COLUMN IF NOT EXISTS quiet_hours_start INTEGER,
+                ADD COLUMN IF NOT EXISTS quiet_hours_end INTEGER",
+        )
+        .await?;
+
+        // Notifications saved during quiet hours but not pushed yet
+        sql::execute(
+            db,
+            "ALTER TABLE notifications ADD COLUMN IF NOT EXISTS push_held BOOLEAN NOT NULL DEFAULT FALSE",
+        )
+        .await?;
+
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_notifications_push_held ON notifications(user_id) WHERE push_held",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP INDEX IF EXISTS idx_notifications_push_held").await?;
+        sql::execute(
+            db,
+            "ALTER TABLE notifications DROP COLUMN IF EXISTS push_held",
+        )
+        .await?;
+        sql::execute(
+            db,
+            "ALTER TABLE users DROP COLUMN IF EXISTS quiet_hours_end,
+                DROP COLUMN IF EXISTS quiet_hours_start,
+                DROP COLUMN IF EXISTS timezone",
+        )
+        .await?;
+        Ok(())
+    }
+}
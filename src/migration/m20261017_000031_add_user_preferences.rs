@@ -0,0 +1,35 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Display preferences, next to the existing timezone and locale
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS content_language VARCHAR(35),
+                ADD COLUMN IF NOT EXISTS show_nsfw BOOLEAN NOT NULL DEFAULT FALSE,
+                ADD COLUMN IF NOT EXISTS default_post_sort VARCHAR(10) NOT NULL DEFAULT 'new'",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(
+            db,
+            "ALTER TABLE users DROP COLUMN IF EXISTS default_post_sort,
+                DROP COLUMN IF EXISTS show_nsfw,
+                DROP COLUMN IF EXISTS content_language",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Set while the user has deactivated their account
+        sql::execute(
+            db,
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS deactivated_at TIMESTAMP",
+        )
+        .await?;
+
+        // Content hidden because its author deactivated, as opposed to by a
+        // moderator; shown again when the author comes back
+        sql::execute(
+            db,
+            "ALTER TABLE posts ADD COLUMN IF NOT EXISTS hidden_with_author BOOLEAN NOT NULL DEFAULT FALSE",
+        )
+        .await?;
+        sql::execute(
+            db,
+            "ALTER TABLE comments ADD COLUMN IF NOT EXISTS hidden_with_author BOOLEAN NOT NULL DEFAULT FALSE",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(
+            db,
+            "ALTER TABLE comments DROP COLUMN IF EXISTS hidden_with_author",
+        )
+        .await?;
+        sql::execute(
+            db,
+            "ALTER TABLE posts DROP COLUMN IF EXISTS hidden_with_author",
+        )
+        .await?;
+        sql::execute(db, "ALTER TABLE users DROP COLUMN IF EXISTS deactivated_at").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Labels a forum's posts can be filed under, set up by admins
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS post_flairs (
+                id SERIAL PRIMARY KEY,
+                forum_id INTEGER NOT NULL REFERENCES forums(id) ON DELETE CASCADE,
+                name VARCHAR(50) NOT NULL,
+                color VARCHAR(7),
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (forum_id, name)
+            )",
+        )
+        .await?;
+
+        // Deleting a flair leaves its posts unflaired
+        sql::execute(
+            db,
+            "ALTER TABLE posts ADD COLUMN IF NOT EXISTS flair_id INTEGER REFERENCES post_flairs(id) ON DELETE SET NULL",
+        )
+        .await?;
+        sql::execute(
+            db,
+            "CREATE INDEX IF NOT EXISTS idx_posts_flair_id ON posts(flair_id)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP INDEX IF EXISTS idx_posts_flair_id").await?;
+        sql::execute(db, "ALTER TABLE posts DROP COLUMN IF EXISTS flair_id").await?;
+        sql::execute(db, "DROP TABLE IF EXISTS post_flairs").await?;
+        Ok(())
+    }
+}
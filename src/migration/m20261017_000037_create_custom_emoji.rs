@@ -0,0 +1,33 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Images admins upload for `:shortcode:` in posts and comments
+        sql::execute(
+            db,
+            "CREATE TABLE IF NOT EXISTS custom_emoji (
+                id SERIAL PRIMARY KEY,
+                shortcode VARCHAR(32) NOT NULL UNIQUE,
+                image_url VARCHAR(300) NOT NULL,
+                created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP TABLE IF EXISTS custom_emoji").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // The comment a reply quotes; the excerpt is kept as it was quoted,
+        // so it survives edits and deletion of the original
+        sql::execute(
+            db,
+            "ALTER TABLE comments ADD COLUMN IF NOT EXISTS quoted_comment_id INTEGER REFERENCES comments(id) ON DELETE SET NULL",
+        )
+        .await?;
+        sql::execute(
+            db,
+            "ALTER TABLE comments ADD COLUMN IF NOT EXISTS quote_excerpt TEXT",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(
+            db,
+            "ALTER TABLE comments DROP COLUMN IF EXISTS quote_excerpt",
+        )
+        .await?;
+        sql::execute(
+            db,
+            "ALTER TABLE comments DROP COLUMN IF EXISTS quoted_comment_id",
+        )
+        .await?;
+        Ok(())
+    }
+}
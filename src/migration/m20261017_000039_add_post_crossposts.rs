@@ -0,0 +1,39 @@
+use super::sql;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // A crosspost is a copy of a post in another forum pointing back at
+        // the original, and goes when the original does
+        sql::execute(
+            db,
+            "ALTER TABLE posts ADD COLUMN IF NOT EXISTS crosspost_of_id INTEGER REFERENCES posts(id) ON DELETE CASCADE",
+        )
+        .await?;
+        // One crosspost of a post per forum
+        sql::execute(
+            db,
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_posts_crosspost_forum ON posts(crosspost_of_id, forum_id)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP INDEX IF EXISTS idx_posts_crosspost_forum").await?;
+        sql::execute(
+            db,
+            "ALTER TABLE posts DROP COLUMN IF EXISTS crosspost_of_id",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use super::sql;
+use crate::utils::slug::{slugify, unique_slug};
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, FromQueryResult, Statement};
+use std::collections::HashMap;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(FromQueryResult)]
+struct PostTitle {
+    id: i32,
+    forum_id: i32,
+    title: String,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // URL slug from the title, unique within the forum
+        sql::execute(
+            db,
+            "ALTER TABLE posts ADD COLUMN IF NOT EXISTS slug VARCHAR(100)",
+        )
+        .await?;
+
+        // Existing posts get slugs too, oldest first keeping the plain one
+        let backend = db.get_database_backend();
+        let posts = PostTitle::find_by_statement(Statement::from_string(
+            backend,
+            "SELECT id, forum_id, title FROM posts WHERE slug IS NULL ORDER BY id",
+        ))
+        .all(db)
+        .await?;
+        let mut taken: HashMap<i32, Vec<String>> = HashMap::new();
+        for post in posts {
+            let forum_slugs = taken.entry(post.forum_id).or_default();
+            let slug = unique_slug(&slugify(&post.title), forum_slugs);
+            db.execute(crate::utils::sql::statement(
+                backend,
+                "UPDATE posts SET slug = $1 WHERE id = $2",
+                vec![slug.clone().into(), post.id.into()],
+            ))
+            .await?;
+            forum_slugs.push(slug);
+        }
+
+        sql::execute(
+            db,
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_posts_forum_slug ON posts(forum_id, slug)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        sql::execute(db, "DROP INDEX IF EXISTS idx_posts_forum_slug").await?;
+        sql::execute(db, "ALTER TABLE posts DROP COLUMN IF EXISTS slug").await?;
+        Ok(())
+    }
+}
@@ -18,6 +18,48 @@ mod m20240101_000015_add_password_reset;
 mod m20240101_000016_create_refresh_tokens;
 mod m20240101_000017_add_performance_indexes;
 mod m20260219_000001_create_user_points_ledger;
+mod m20261017_000001_add_user_token_version;
+mod m20261017_000002_create_comment_revisions;
+mod m20261017_000003_add_post_pinned_comment;
+mod m20261017_000004_add_post_title_trgm_index;
+mod m20261017_000005_create_watched_posts;
+mod m20261017_000006_create_post_reads;
+mod m20261017_000007_create_email_outbox;
+mod m20261017_000008_add_user_locale;
+mod m20261017_000009_create_email_digests;
+mod m20261017_000010_create_email_opt_outs;
+mod m20261017_000011_add_user_email_undeliverable;
+mod m20261017_000012_create_announcements;
+mod m20261017_000013_create_mod_queue_claims;
+mod m20261017_000014_create_moderation_actions;
+mod m20261017_000015_create_appeals;
+mod m20261017_000016_create_user_notes;
+mod m20261017_000017_create_audit_log;
+mod m20261017_000018_add_created_at_indexes;
+mod m20261017_000019_create_site_settings_and_invites;
+mod m20261017_000020_add_mysql_fulltext_indexes;
+mod m20261017_000021_create_tenants;
+mod m20261017_000022_create_link_previews;
+mod m20261017_000023_create_federation_tables;
+mod m20261017_000024_create_uploads;
+mod m20261017_000025_add_user_avatar_original;
+mod m20261017_000026_create_follow_requests;
+mod m20261017_000027_add_quiet_hours;
+mod m20261017_000028_create_post_fanouts;
+mod m20261017_000029_add_presence;
+mod m20261017_000030_create_badges;
+mod m20261017_000031_add_user_preferences;
+mod m20261017_000032_add_user_display_name;
+mod m20261017_000033_create_username_history;
+mod m20261017_000034_add_user_deactivation;
+mod m20261017_000035_create_post_flairs;
+mod m20261017_000036_create_post_attachments;
+mod m20261017_000037_create_custom_emoji;
+mod m20261017_000038_add_comment_quotes;
+mod m20261017_000039_add_post_crossposts;
+mod m20261017_000040_add_post_slugs;
+mod m20261017_000041_add_post_closed_by_author;
+mod sql;
 
 pub struct Migrator;
 
@@ -43,6 +85,47 @@ impl MigratorTrait for Migrator {
             Box::new(m20240101_000016_create_refresh_tokens::Migration),
             Box::new(m20240101_000017_add_performance_indexes::Migration),
             Box::new(m20260219_000001_create_user_points_ledger::Migration),
+            Box::new(m20261017_000001_add_user_token_version::Migration),
+            Box::new(m20261017_000002_create_comment_revisions::Migration),
+            Box::new(m20261017_000003_add_post_pinned_comment::Migration),
+            Box::new(m20261017_000004_add_post_title_trgm_index::Migration),
+            Box::new(m20261017_000005_create_watched_posts::Migration),
+            Box::new(m20261017_000006_create_post_reads::Migration),
+            Box::new(m20261017_000007_create_email_outbox::Migration),
+            Box::new(m20261017_000008_add_user_locale::Migration),
+            Box::new(m20261017_000009_create_email_digests::Migration),
+            Box::new(m20261017_000010_create_email_opt_outs::Migration),
+            Box::new(m20261017_000011_add_user_email_undeliverable::Migration),
+            Box::new(m20261017_000012_create_announcements::Migration),
+            Box::new(m20261017_000013_create_mod_queue_claims::Migration),
+            Box::new(m20261017_000014_create_moderation_actions::Migration),
+            Box::new(m20261017_000015_create_appeals::Migration),
+            Box::new(m20261017_000016_create_user_notes::Migration),
+            Box::new(m20261017_000017_create_audit_log::Migration),
+            Box::new(m20261017_000018_add_created_at_indexes::Migration),
+            Box::new(m20261017_000019_create_site_settings_and_invites::Migration),
+            Box::new(m20261017_000020_add_mysql_fulltext_indexes::Migration),
+            Box::new(m20261017_000021_create_tenants::Migration),
+            Box::new(m20261017_000022_create_link_previews::Migration),
+            Box::new(m20261017_000023_create_federation_tables::Migration),
+            Box::new(m20261017_000024_create_uploads::Migration),
+            Box::new(m20261017_000025_add_user_avatar_original::Migration),
+            Box::new(m20261017_000026_create_follow_requests::Migration),
+            Box::new(m20261017_000027_add_quiet_hours::Migration),
+            Box::new(m20261017_000028_create_post_fanouts::Migration),
+            Box::new(m20261017_000029_add_presence::Migration),
+            Box::new(m20261017_000030_create_badges::Migration),
+            Box::new(m20261017_000031_add_user_preferences::Migration),
+            Box::new(m20261017_000032_add_user_display_name::Migration),
+            Box::new(m20261017_000033_create_username_history::Migration),
+            Box::new(m20261017_000034_add_user_deactivation::Migration),
+            Box::new(m20261017_000035_create_post_flairs::Migration),
+            Box::new(m20261017_000036_create_post_attachments::Migration),
+            Box::new(m20261017_000037_create_custom_emoji::Migration),
+            Box::new(m20261017_000038_add_comment_quotes::Migration),
+            Box::new(m20261017_000039_add_post_crossposts::Migration),
+            Box::new(m20261017_000040_add_post_slugs::Migration),
+            Box::new(m20261017_000041_add_post_closed_by_author::Migration),
         ]
     }
 }
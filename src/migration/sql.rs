@@ -0,0 +1,414 @@
+//! Raw migration SQL, written for Postgres and rewritten for SQLite and MySQL.
+//!
+//! The hand-written migrations use Postgres DDL. The other backends accept
+//! most of it as is; the rest is translated here so the same migrations
+//! build their schema:
+//!
+//! - `SERIAL`/`BIGSERIAL PRIMARY KEY` become auto-increment integer keys
+//! - `JSONB` columns are stored as `TEXT` (SQLite) or `JSON` (MySQL)
+//! - `ALTER TABLE` runs one clause at a time, and `ADD COLUMN IF NOT EXISTS` /
+//!   `DROP COLUMN IF EXISTS` check the table first, since neither has them
+//!
+//! MySQL additionally:
+//!
+//! - ignores inline `REFERENCES`, so they become `FOREIGN KEY` clauses
+//! - rejects `CHECK` on columns with cascading foreign keys, so table
+//!   `CHECK`s are dropped (the services validate the same rules)
+//! - has no `IF [NOT] EXISTS` for indexes or partial indexes, so index
+//!   statements check `information_schema` and lose their `WHERE`
+//! - reserves some column names (`key`), so created columns are quoted
+//!
+//! Postgres-only features (generated `tsvector` columns, GIN indexes,
+//! extensions) are skipped by their migrations instead.
+
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DbBackend, Statement, Value};
+
+/// Run a Postgres DDL statement, translated for the connected backend.
+pub async fn execute<C: ConnectionTrait>(db: &C, sql: &str) -> Result<(), DbErr> {
+    match db.get_database_backend() {
+        DbBackend::Sqlite => execute_sqlite(db, sql).await,
+        DbBackend::MySql => execute_mysql(db, sql).await,
+        _ => {
+            db.execute_unprepared(sql).await?;
+            Ok(())
+        }
+    }
+}
+
+async fn execute_sqlite<C: ConnectionTrait>(db: &C, sql: &str) -> Result<(), DbErr> {
+    let sql = sqlite_types(sql);
+    let Some((table, clauses)) = split_alter_table(&sql) else {
+        db.execute_unprepared(&sql).await?;
+        return Ok(());
+    };
+
+    for clause in clauses {
+        let statement = if let Some(rest) = strip_prefix_ci(clause, "ADD COLUMN IF NOT EXISTS ") {
+            if has_column(db, table, first_word(rest)).await? {
+                continue;
+            }
+            format!("ALTER TABLE {table} ADD COLUMN {rest}")
+        } else if let Some(rest) = strip_prefix_ci(clause, "DROP COLUMN IF EXISTS ") {
+            if !has_column(db, table, first_word(rest)).await? {
+                continue;
+            }
+            format!("ALTER TABLE {table} DROP COLUMN {rest}")
+        } else {
+            format!("ALTER TABLE {table} {clause}")
+        };
+        db.execute_unprepared(&statement).await?;
+    }
+    Ok(())
+}
+
+async fn execute_mysql<C: ConnectionTrait>(db: &C, sql: &str) -> Result<(), DbErr> {
+    let sql = mysql_types(sql);
+    let trimmed = sql.trim();
+
+    if let Some(index) = split_create_index(trimmed) {
+        if has_index(db, index.name).await? {
+            return Ok(());
+        }
+        db.execute_unprepared(&index.to_mysql()).await?;
+        return Ok(());
+    }
+
+    if let Some(name) = strip_prefix_ci(trimmed, "DROP INDEX IF EXISTS ") {
+        let name = first_word(name);
+        if let Some(table) = index_table(db, name).await? {
+            db.execute_unprepared(&format!("DROP INDEX {name} ON {table}"))
+                .await?;
+        }
+        return Ok(());
+    }
+
+    if strip_prefix_ci(trimmed, "CREATE TABLE ").is_some() {
+        db.execute_unprepared(&mysql_create_table(trimmed)).await?;
+        return Ok(());
+    }
+
+    let Some((table, clauses)) = split_alter_table(trimmed) else {
+        db.execute_unprepared(trimmed).await?;
+        return Ok(());
+    };
+
+    for clause in clauses {
+        let statement = if let Some(rest) = strip_prefix_ci(clause, "ADD COLUMN IF NOT EXISTS ") {
+            if has_column(db, table, first_word(rest)).await? {
+                continue;
+            }
+            let (column, foreign_key) = mysql_column(rest);
+            match foreign_key {
+                Some(fk) => format!("ALTER TABLE {table} ADD COLUMN {column}, ADD {fk}"),
+                None => format!("ALTER TABLE {table} ADD COLUMN {column}"),
+            }
+        } else if let Some(rest) = strip_prefix_ci(clause, "DROP COLUMN IF EXISTS ") {
+            let column = first_word(rest);
+            if !has_column(db, table, column).await? {
+                continue;
+            }
+            // A column can't be dropped while a foreign key uses it
+            for fk in column_foreign_keys(db, table, column).await? {
+                db.execute_unprepared(&format!("ALTER TABLE {table} DROP FOREIGN KEY {fk}"))
+                    .await?;
+            }
+            format!("ALTER TABLE {table} DROP COLUMN {rest}")
+        } else {
+            format!("ALTER TABLE {table} {clause}")
+        };
+        db.execute_unprepared(&statement).await?;
+    }
+    Ok(())
+}
+
+/// Swap Postgres-only column types for their SQLite equivalents.
+fn sqlite_types(sql: &str) -> String {
+    sql.replace("BIGSERIAL PRIMARY KEY", "INTEGER PRIMARY KEY AUTOINCREMENT")
+        .replace("SERIAL PRIMARY KEY", "INTEGER PRIMARY KEY AUTOINCREMENT")
+        .replace("JSONB", "TEXT")
+}
+
+/// Swap Postgres-only column types for their MySQL equivalents. `SERIAL`
+/// exists there but means an unsigned `BIGINT`, which `INTEGER` foreign
+/// keys can't reference.
+fn mysql_types(sql: &str) -> String {
+    sql.replace("BIGSERIAL PRIMARY KEY", "BIGINT AUTO_INCREMENT PRIMARY KEY")
+        .replace("SERIAL PRIMARY KEY", "INT AUTO_INCREMENT PRIMARY KEY")
+        .replace("JSONB", "JSON")
+}
+
+/// `CREATE TABLE` with quoted column names, inline `REFERENCES` moved to
+/// `FOREIGN KEY` clauses and `CHECK` constraints dropped.
+fn mysql_create_table(sql: &str) -> String {
+    let (Some(open), Some(close)) = (sql.find('('), sql.rfind(')')) else {
+        return sql.to_string();
+    };
+
+    let mut items = Vec::new();
+    let mut foreign_keys = Vec::new();
+    for item in split_top_level(&sql[open + 1..close]) {
+        let keyword = first_word(item).to_ascii_uppercase();
+        if keyword.starts_with("CHECK") {
+            continue;
+        }
+        if matches!(
+            keyword.as_str(),
+            "PRIMARY" | "UNIQUE" | "FOREIGN" | "CONSTRAINT"
+        ) {
+            items.push(item.to_string());
+            continue;
+        }
+        let (column, foreign_key) = mysql_column(item);
+        items.push(column);
+        foreign_keys.extend(foreign_key);
+    }
+    items.extend(foreign_keys);
+
+    format!(
+        "{} ({}){}",
+        sql[..open].trim_end(),
+        items.join(", "),
+        &sql[close + 1..]
+    )
+}
+
+/// A column definition with its name quoted, and its inline `REFERENCES`
+/// split off into a `FOREIGN KEY` clause.
+fn mysql_column(definition: &str) -> (String, Option<String>) {
+    let definition = definition.split_whitespace().collect::<Vec<_>>().join(" ");
+    let name = first_word(&definition).trim_matches('`');
+    let rest = definition[first_word(&definition).len()..].trim();
+
+    match rest.to_ascii_uppercase().find("REFERENCES ") {
+        Some(at) => (
+            format!("`{name}` {}", rest[..at].trim_end()),
+            Some(format!("FOREIGN KEY (`{name}`) {}", &rest[at..])),
+        ),
+        None => (format!("`{name}` {rest}"), None),
+    }
+}
+
+/// A parsed `CREATE [UNIQUE|FULLTEXT] INDEX IF NOT EXISTS name ON ...`.
+struct CreateIndex<'a> {
+    kind: &'a str,
+    name: &'a str,
+    target: &'a str,
+}
+
+impl CreateIndex<'_> {
+    /// The statement without `IF NOT EXISTS` or a partial-index `WHERE`.
+    fn to_mysql(&self) -> String {
+        let target = self.target.split_whitespace().collect::<Vec<_>>().join(" ");
+        let target = match target.to_ascii_uppercase().find(" WHERE ") {
+            Some(at) => target[..at].to_string(),
+            None => target,
+        };
+        format!("CREATE {}INDEX {} {}", self.kind, self.name, target)
+    }
+}
+
+fn split_create_index(sql: &str) -> Option<CreateIndex<'_>> {
+    let rest = strip_prefix_ci(sql, "CREATE ")?;
+    let (kind, rest) = ["UNIQUE ", "FULLTEXT "]
+        .into_iter()
+        .find_map(|kind| Some((kind, strip_prefix_ci(rest, kind)?)))
+        .unwrap_or(("", rest));
+    let rest = strip_prefix_ci(rest, "INDEX IF NOT EXISTS ")?.trim_start();
+    let name = first_word(rest);
+    Some(CreateIndex {
+        kind,
+        name,
+        target: rest[name.len()..].trim(),
+    })
+}
+
+/// `ALTER TABLE name a, b` as `(name, [a, b])`, splitting on top-level commas.
+fn split_alter_table(sql: &str) -> Option<(&str, Vec<&str>)> {
+    let rest = strip_prefix_ci(sql.trim(), "ALTER TABLE ")?.trim_start();
+    let table = first_word(rest);
+    Some((table, split_top_level(rest[table.len()..].trim())))
+}
+
+/// Split on commas outside parentheses.
+fn split_top_level(body: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (i, ch) in body.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(body[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(body[start..].trim());
+    items
+}
+
+fn strip_prefix_ci<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = s.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &s[prefix.len()..])
+}
+
+fn first_word(s: &str) -> &str {
+    s.split_whitespace().next().unwrap_or_default()
+}
+
+async fn has_column<C: ConnectionTrait>(db: &C, table: &str, column: &str) -> Result<bool, DbErr> {
+    let sql = match db.get_database_backend() {
+        DbBackend::MySql => {
+            "SELECT COUNT(*) FROM information_schema.columns \
+                WHERE table_schema = DATABASE() AND table_name = ? AND column_name = ?"
+        }
+        _ => "SELECT COUNT(*) FROM pragma_table_info($1) WHERE name = $2",
+    };
+    let row = db
+        .query_one(Statement::from_sql_and_values(
+            db.get_database_backend(),
+            sql,
+            [table.into(), column.into()],
+        ))
+        .await?
+        .ok_or_else(|| DbErr::Custom(format!("Could not inspect table {table}")))?;
+    let count: i64 = row.try_get_by_index(0)?;
+    Ok(count > 0)
+}
+
+async fn has_index<C: ConnectionTrait>(db: &C, name: &str) -> Result<bool, DbErr> {
+    Ok(index_table(db, name).await?.is_some())
+}
+
+/// The table a MySQL index belongs to, if it exists.
+async fn index_table<C: ConnectionTrait>(db: &C, name: &str) -> Result<Option<String>, DbErr> {
+    let rows = query_strings(
+        db,
+        "SELECT DISTINCT CAST(table_name AS CHAR) FROM information_schema.statistics \
+            WHERE table_schema = DATABASE() AND index_name = ?",
+        [name.into()],
+    )
+    .await?;
+    Ok(rows.into_iter().next())
+}
+
+/// Names of the MySQL foreign keys on `table.column`.
+async fn column_foreign_keys<C: ConnectionTrait>(
+    db: &C,
+    table: &str,
+    column: &str,
+) -> Result<Vec<String>, DbErr> {
+    query_strings(
+        db,
+        "SELECT CAST(constraint_name AS CHAR) FROM information_schema.key_column_usage \
+            WHERE table_schema = DATABASE() AND table_name = ? AND column_name = ? \
+                AND referenced_table_name IS NOT NULL",
+        [table.into(), column.into()],
+    )
+    .await
+}
+
+/// First column of every row of a MySQL query.
+async fn query_strings<C: ConnectionTrait, const N: usize>(
+    db: &C,
+    sql: &str,
+    values: [Value; N],
+) -> Result<Vec<String>, DbErr> {
+    db.query_all(Statement::from_sql_and_values(
+        DbBackend::MySql,
+        sql,
+        values,
+    ))
+    .await?
+    .iter()
+    .map(|row| row.try_get_by_index(0))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_alter_table() {
+        let (table, clauses) = split_alter_table(
+            "ALTER TABLE reports ADD COLUMN IF NOT EXISTS action VARCHAR(20),
+                ADD COLUMN IF NOT EXISTS amount NUMERIC(10, 2)",
+        )
+        .unwrap();
+        assert_eq!(table, "reports");
+        assert_eq!(
+            clauses,
+            vec![
+                "ADD COLUMN IF NOT EXISTS action VARCHAR(20)",
+                "ADD COLUMN IF NOT EXISTS amount NUMERIC(10, 2)"
+            ]
+        );
+        assert!(split_alter_table("CREATE TABLE t (id INTEGER)").is_none());
+    }
+
+    #[test]
+    fn test_sqlite_types() {
+        assert_eq!(
+            sqlite_types("id BIGSERIAL PRIMARY KEY, value JSONB NOT NULL"),
+            "id INTEGER PRIMARY KEY AUTOINCREMENT, value TEXT NOT NULL"
+        );
+    }
+
+    #[test]
+    fn test_mysql_create_table() {
+        let sql = mysql_types(
+            "CREATE TABLE IF NOT EXISTS follows (
+                id SERIAL PRIMARY KEY,
+                follower_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                key VARCHAR(50),
+                CHECK (follower_id != following_id),
+                UNIQUE (follower_id)
+            )",
+        );
+        assert_eq!(
+            mysql_create_table(&sql),
+            "CREATE TABLE IF NOT EXISTS follows (`id` INT AUTO_INCREMENT PRIMARY KEY, \
+             `follower_id` INTEGER NOT NULL, `key` VARCHAR(50), UNIQUE (follower_id), \
+             FOREIGN KEY (`follower_id`) REFERENCES users(id) ON DELETE CASCADE)"
+        );
+    }
+
+    #[test]
+    fn test_mysql_column_and_index() {
+        assert_eq!(
+            mysql_column(
+                "invite_code_id INTEGER\n    REFERENCES invite_codes(id) ON DELETE SET NULL"
+            ),
+            (
+                "`invite_code_id` INTEGER".to_string(),
+                Some(
+                    "FOREIGN KEY (`invite_code_id`) REFERENCES invite_codes(id) ON DELETE SET NULL"
+                        .to_string()
+                )
+            )
+        );
+
+        let index = split_create_index(
+            "CREATE INDEX IF NOT EXISTS idx_unread ON notifications (user_id, is_read) \
+             WHERE is_read = FALSE",
+        )
+        .unwrap();
+        assert_eq!(index.name, "idx_unread");
+        assert_eq!(
+            index.to_mysql(),
+            "CREATE INDEX idx_unread ON notifications (user_id, is_read)"
+        );
+        let index =
+            split_create_index("CREATE FULLTEXT INDEX IF NOT EXISTS idx_ft ON posts(title)")
+                .unwrap();
+        assert_eq!(
+            index.to_mysql(),
+            "CREATE FULLTEXT INDEX idx_ft ON posts(title)"
+        );
+        assert!(split_create_index("CREATE TABLE t (id INTEGER)").is_none());
+    }
+}
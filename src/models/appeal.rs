@@ -0,0 +1,28 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A user's appeal against a warning or ban. `moderation_action_id` is empty
+/// when appealing a ban that wasn't issued from a report.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "appeals")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub user_id: i32,
+    pub moderation_action_id: Option<i32>,
+    #[sea_orm(column_type = "Text")]
+    pub reason: String,
+    /// `pending`, `accepted` or `declined`
+    pub status: String,
+    pub decided_by: Option<i32>,
+    pub decided_at: Option<DateTime>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub decision_note: Option<String>,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,28 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A sensitive staff action. Requests made while impersonating record the
+/// method, path and response status.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "audit_log")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// Staff member who acted
+    pub actor_id: Option<i32>,
+    pub action: String,
+    /// User acted upon or impersonated
+    pub target_user_id: Option<i32>,
+    pub method: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub path: Option<String>,
+    pub status: Option<i32>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub detail: Option<String>,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
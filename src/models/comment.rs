@@ -17,6 +17,13 @@ pub struct Model {
     pub is_hidden: bool,
     pub created_at: DateTime,
     pub updated_at: DateTime,
+    /// Set when the content was edited after posting
+    pub edited_at: Option<DateTime>,
+    /// Comment this one quotes; cleared if that comment is deleted
+    pub quoted_comment_id: Option<i32>,
+    /// Plain-text excerpt of the quoted comment, as it was when quoted
+    #[sea_orm(column_type = "Text", nullable)]
+    pub quote_excerpt: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// An image shown in place of `:shortcode:` in rendered Markdown.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "custom_emoji")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// Name between the colons, e.g. `party_parrot`
+    #[sea_orm(unique)]
+    pub shortcode: String,
+    /// Public URL path of the uploaded image
+    pub image_url: String,
+    /// Admin who added it
+    pub created_by: Option<i32>,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
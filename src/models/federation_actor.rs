@@ -0,0 +1,27 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A remote actor document, cached for its inboxes and public key.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "federation_actors")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// Hex SHA-256 of `actor_id`
+    #[sea_orm(unique)]
+    pub actor_hash: String,
+    #[sea_orm(column_type = "Text")]
+    pub actor_id: String,
+    #[sea_orm(column_type = "Text")]
+    pub inbox: String,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub shared_inbox: Option<String>,
+    #[sea_orm(column_type = "Text")]
+    pub public_key_pem: String,
+    pub fetched_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,33 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// An activity to POST to a remote inbox. `status` moves from `pending` to
+/// `sending` while a worker holds it, then to `delivered`, back to `pending`
+/// for a retry, or to `failed` once retries are exhausted.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "federation_deliveries")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    #[sea_orm(column_type = "Text")]
+    pub inbox: String,
+    /// Key id of the sending actor, for the `Signature` header
+    #[sea_orm(column_type = "Text")]
+    pub key_id: String,
+    /// The activity's JSON
+    #[sea_orm(column_type = "Text")]
+    pub activity: String,
+    pub status: String,
+    pub attempts: i32,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime,
+    pub delivered_at: Option<DateTime>,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,29 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A remote actor following a local user or forum.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "federation_followers")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// `user` or `forum`
+    pub actor_type: String,
+    /// Id of the followed user or forum
+    pub local_id: i32,
+    /// Hex SHA-256 of `follower_id`
+    pub follower_hash: String,
+    /// The follower's actor id
+    #[sea_orm(column_type = "Text")]
+    pub follower_id: String,
+    #[sea_orm(column_type = "Text")]
+    pub inbox: String,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub shared_inbox: Option<String>,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,24 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The instance's RSA key pair for HTTP signatures. There is only ever the
+/// row with `id` 1.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "federation_keys")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i32,
+    /// PKCS#8 PEM
+    #[sea_orm(column_type = "Text")]
+    #[serde(skip_serializing)]
+    pub private_key_pem: String,
+    /// SPKI PEM, as published on actor documents
+    #[sea_orm(column_type = "Text")]
+    pub public_key_pem: String,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
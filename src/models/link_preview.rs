@@ -0,0 +1,32 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Open Graph metadata of a page linked from posts, shared by every post
+/// linking the same URL.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "link_previews")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// Hex SHA-256 of `url`
+    #[sea_orm(unique)]
+    pub url_hash: String,
+    #[sea_orm(column_type = "Text")]
+    pub url: String,
+    /// `pending`, `ready` or `failed`
+    pub status: String,
+    pub title: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub description: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+    /// Last fetch attempt, successful or not
+    pub fetched_at: Option<DateTime>,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
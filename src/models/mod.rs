@@ -1,30 +1,88 @@
+pub mod announcement;
+pub mod appeal;
+pub mod appeal_comment;
+pub mod audit_log;
+pub mod badge;
 pub mod bookmark;
 pub mod comment;
+pub mod comment_revision;
+pub mod custom_emoji;
+pub mod email_digest;
+pub mod email_opt_out;
+pub mod email_outbox;
+pub mod federation_actor;
+pub mod federation_delivery;
+pub mod federation_follower;
+pub mod federation_key;
 pub mod follow;
+pub mod follow_request;
 pub mod forum;
+pub mod invite_code;
+pub mod link_preview;
+pub mod mod_queue_claim;
+pub mod moderation_action;
 pub mod notification;
 pub mod post;
+pub mod post_attachment;
+pub mod post_fanout;
+pub mod post_flair;
 pub mod post_tag;
 pub mod refresh_token;
 pub mod report;
+pub mod site_setting;
 pub mod tag;
+pub mod tenant;
+pub mod upload;
 pub mod user;
+pub mod user_badge;
+pub mod user_note;
 pub mod user_points_ledger;
+pub mod username_history;
 pub mod vote;
+pub mod watched_post;
 
+pub use announcement::{Entity as Announcement, Model as AnnouncementModel};
+pub use appeal::{Entity as Appeal, Model as AppealModel};
+pub use appeal_comment::{Entity as AppealComment, Model as AppealCommentModel};
+pub use audit_log::{Entity as AuditLog, Model as AuditLogModel};
+pub use badge::{Entity as Badge, Model as BadgeModel};
 pub use bookmark::Entity as Bookmark;
 pub use comment::{Entity as Comment, Model as CommentModel};
+pub use comment_revision::{Entity as CommentRevision, Model as CommentRevisionModel};
+pub use custom_emoji::{Entity as CustomEmoji, Model as CustomEmojiModel};
+pub use email_digest::Entity as EmailDigest;
+pub use email_opt_out::Entity as EmailOptOut;
+pub use email_outbox::{Entity as EmailOutbox, Model as EmailOutboxModel};
+pub use federation_actor::{Entity as FederationActor, Model as FederationActorModel};
+pub use federation_delivery::{Entity as FederationDelivery, Model as FederationDeliveryModel};
+pub use federation_follower::Entity as FederationFollower;
+pub use federation_key::{Entity as FederationKey, Model as FederationKeyModel};
 pub use follow::Entity as Follow;
+pub use follow_request::{Entity as FollowRequest, Model as FollowRequestModel};
 pub use forum::{Entity as Forum, Model as ForumModel};
+pub use invite_code::{Entity as InviteCode, Model as InviteCodeModel};
+pub use link_preview::{Entity as LinkPreview, Model as LinkPreviewModel};
+pub use mod_queue_claim::{Entity as ModQueueClaim, Model as ModQueueClaimModel};
+pub use moderation_action::{Entity as ModerationAction, Model as ModerationActionModel};
 pub use notification::{Entity as Notification, Model as NotificationModel};
 pub use post::{Entity as Post, Model as PostModel};
+pub use post_attachment::Entity as PostAttachment;
+pub use post_fanout::{Entity as PostFanout, Model as PostFanoutModel};
+pub use post_flair::{Entity as PostFlair, Model as PostFlairModel};
 #[allow(unused_imports)]
 pub use post_tag::Entity as PostTag;
 #[allow(unused_imports)]
 pub use refresh_token::Entity as RefreshToken;
 pub use report::{Entity as Report, Model as ReportModel};
+pub use site_setting::Entity as SiteSetting;
 pub use tag::{Entity as Tag, Model as TagModel};
+pub use tenant::{Entity as Tenant, Model as TenantModel};
+pub use upload::{Entity as Upload, Model as UploadModel};
 pub use user::{Entity as User, Model as UserModel};
+pub use user_badge::{Entity as UserBadge, Model as UserBadgeModel};
+pub use user_note::{Entity as UserNote, Model as UserNoteModel};
 pub use user_points_ledger::Entity as UserPointsLedger;
+pub use username_history::Entity as UsernameHistory;
 #[allow(unused_imports)]
 pub use vote::{Entity as Vote, Model as VoteModel};
+pub use watched_post::Entity as WatchedPost;
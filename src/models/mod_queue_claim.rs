@@ -0,0 +1,20 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A moderator working on a moderation queue item. Claims older than the
+/// queue's claim timeout no longer count.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "mod_queue_claims")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub item_type: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub item_id: i32,
+    pub claimed_by: i32,
+    pub claimed_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,26 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A moderation action against a user or their content, e.g. a warning or
+/// ban issued while resolving a report.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "moderation_actions")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// User the action was taken against
+    pub user_id: i32,
+    pub moderator_id: Option<i32>,
+    pub action: String,
+    pub report_id: Option<i32>,
+    pub target_type: Option<String>,
+    pub target_id: Option<i32>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub note: Option<String>,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -17,6 +17,8 @@ pub struct Model {
     #[sea_orm(column_type = "Text")]
     pub message: String,
     pub is_read: bool,
+    /// Saved during the recipient's quiet hours and not pushed yet
+    pub push_held: bool,
     pub created_at: DateTime,
 }
 
@@ -20,6 +20,19 @@ pub struct Model {
     pub is_hidden: bool,
     pub created_at: DateTime,
     pub updated_at: DateTime,
+    /// Comment shown at the top of the thread
+    pub pinned_comment_id: Option<i32>,
+    /// Page a link post points at
+    #[sea_orm(column_type = "Text", nullable)]
+    pub url: Option<String>,
+    /// One of the forum's flairs
+    pub flair_id: Option<i32>,
+    /// For a crosspost, the post it was crossposted from
+    pub crosspost_of_id: Option<i32>,
+    /// URL slug from the title at creation, unique within the forum
+    pub slug: Option<String>,
+    /// Closed to new comments by the author, apart from `is_locked`
+    pub closed_by_author: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
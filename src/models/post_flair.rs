@@ -0,0 +1,33 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A label posts in a forum can be filed under.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "post_flairs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub forum_id: i32,
+    pub name: String,
+    /// Hex color such as `#1f6feb`
+    pub color: Option<String>,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::forum::Entity",
+        from = "Column::ForumId",
+        to = "super::forum::Column::Id"
+    )]
+    Forum,
+}
+
+impl Related<super::forum::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Forum.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -19,6 +19,11 @@ pub struct Model {
     pub status: String,
     pub resolved_by: Option<i32>,
     pub resolved_at: Option<DateTime>,
+    /// Action taken when resolved, e.g. `hide_content` or `ban_user`
+    pub action: Option<String>,
+    /// Moderator's note on the resolution
+    #[sea_orm(column_type = "Text", nullable)]
+    pub resolution_note: Option<String>,
     pub created_at: DateTime,
 }
 
@@ -0,0 +1,28 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A file saved under `UPLOAD_DIR`.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "uploads")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// User who uploaded the file
+    pub user_id: Option<i32>,
+    /// Relative to `UPLOAD_DIR`, e.g. `images/<uuid>.png`
+    #[sea_orm(unique)]
+    pub path: String,
+    /// In bytes
+    pub size: i64,
+    pub mime: String,
+    /// `post`, `comment`, `user` (an avatar) or `emoji`, once something
+    /// refers to the file; unreferenced files are deleted after a grace period
+    pub entity_type: Option<String>,
+    pub entity_id: Option<i32>,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
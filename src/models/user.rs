@@ -8,10 +8,14 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub username: String,
+    /// Name shown instead of the username, if set
+    pub display_name: Option<String>,
     pub email: String,
     #[serde(skip_serializing)]
     pub password_hash: String,
     pub avatar_url: Option<String>,
+    /// Uploaded image the avatar was cropped from
+    pub avatar_original_url: Option<String>,
     pub bio: Option<String>,
     pub karma: i32,
     pub role: String,
@@ -22,6 +26,37 @@ pub struct Model {
     pub password_reset_token: Option<String>,
     #[serde(skip_serializing)]
     pub password_reset_expires: Option<DateTime>,
+    #[serde(skip_serializing)]
+    pub token_version: i32,
+    pub auto_watch: bool,
+    pub locale: String,
+    pub digest_frequency: String,
+    /// `bounce` or `complaint` once the email provider reports the address
+    pub email_undeliverable: Option<String>,
+    pub email_undeliverable_at: Option<DateTime>,
+    /// Invite code used to register, if any
+    pub invite_code_id: Option<i32>,
+    /// Follows need the user's approval
+    pub is_private: bool,
+    /// IANA timezone name, e.g. `Asia/Shanghai`
+    pub timezone: String,
+    /// Start of the quiet hours, in minutes after local midnight
+    pub quiet_hours_start: Option<i32>,
+    /// End of the quiet hours; before the start when they span midnight
+    pub quiet_hours_end: Option<i32>,
+    /// Last authenticated request or socket heartbeat, written at most once
+    /// a minute
+    pub last_seen_at: Option<DateTime>,
+    /// Others may see whether the user is online and when they were last seen
+    pub show_presence: bool,
+    /// Language tag of the content the user prefers to read, e.g. `zh-CN`
+    pub content_language: Option<String>,
+    /// Whether clients show NSFW content without a click-through
+    pub show_nsfw: bool,
+    /// Sort used for post lists that don't ask for one: `new`, `top` or `hot`
+    pub default_post_sort: String,
+    /// When the user deactivated their account; cleared when they log in
+    pub deactivated_at: Option<DateTime>,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
@@ -30,3 +65,10 @@ pub struct Model {
 pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// How the user's name appears: the display name, or the username.
+    pub fn shown_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.username)
+    }
+}
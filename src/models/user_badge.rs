@@ -0,0 +1,32 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "user_badges")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub user_id: i32,
+    pub badge_id: i32,
+    /// Admin who awarded a custom badge; `None` for automatic awards
+    pub awarded_by: Option<i32>,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::badge::Entity",
+        from = "Column::BadgeId",
+        to = "super::badge::Column::Id"
+    )]
+    Badge,
+}
+
+impl Related<super::badge::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Badge.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
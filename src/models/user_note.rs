@@ -0,0 +1,21 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A private staff note about a user, e.g. context on a past incident.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "user_notes")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// User the note is about
+    pub user_id: i32,
+    pub author_id: Option<i32>,
+    #[sea_orm(column_type = "Text")]
+    pub body: String,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
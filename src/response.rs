@@ -1,4 +1,8 @@
-use axum::{response::IntoResponse, Json};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -124,3 +128,34 @@ mod tests {
         assert_eq!(resp.total_pages, 1);
     }
 }
+
+/// Status a response has under `/api/v2`. v1 keeps its historical status
+/// codes and ignores this; the v2 middleware applies it.
+#[derive(Debug, Clone, Copy)]
+pub struct V2Status(pub StatusCode);
+
+/// Something was created: 200 under v1, 201 under v2.
+pub struct Created<T>(pub T);
+
+impl<T: IntoResponse> IntoResponse for Created<T> {
+    fn into_response(self) -> Response {
+        with_v2_status(self.0.into_response(), StatusCode::CREATED)
+    }
+}
+
+/// Something was deleted: 200 with the body under v1, 204 without one
+/// under v2.
+pub struct NoContent<T>(pub T);
+
+impl<T: IntoResponse> IntoResponse for NoContent<T> {
+    fn into_response(self) -> Response {
+        with_v2_status(self.0.into_response(), StatusCode::NO_CONTENT)
+    }
+}
+
+fn with_v2_status(mut response: Response, status: StatusCode) -> Response {
+    if response.status() == StatusCode::OK {
+        response.extensions_mut().insert(V2Status(status));
+    }
+    response
+}
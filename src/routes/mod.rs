@@ -1,29 +1,188 @@
-use crate::config::rate_limit::{RateLimitConfig, RateLimitRule};
+use crate::config::body_limit::BodyLimitConfig;
+use crate::config::rate_limit::{RateLimitConfig, RateLimitGroup};
 use crate::handlers;
-use crate::middleware::auth::auth_middleware;
+use crate::middleware::api_version::api_v2_middleware;
+use crate::middleware::auth::{
+    allow_banned_auth_middleware, auth_middleware, optional_auth_middleware,
+};
+use crate::middleware::fallback::{json_errors_middleware, not_found};
+use crate::middleware::idempotency::idempotency_middleware;
+use crate::middleware::locale::locale_middleware;
+use crate::middleware::maintenance::maintenance_middleware;
+use crate::middleware::rate_limit::{rate_limit_middleware, GroupLimiter};
 use crate::websocket;
-use axum::{middleware, routing, Router};
-use tower_governor::{governor::GovernorConfigBuilder, GovernorLayer};
+use axum::{extract::DefaultBodyLimit, middleware, routing, Router};
 
 pub fn create_routes() -> Router {
+    let rate_limit_config = RateLimitConfig::from_env();
+    let body_limits = BodyLimitConfig::from_env();
+    // Both versions share one set of routes, and so their rate limits
+    let api = api_routes(&rate_limit_config, &body_limits);
+
     Router::new()
-        .nest("/api/v1", api_routes())
-        // WebSocket route (auth handled inside the handler via query token)
+        .merge(health_routes())
+        .nest(
+            "/api/v2",
+            api.clone().layer(middleware::from_fn(api_v2_middleware)),
+        )
+        .nest("/api/v1", api)
+        .merge(outbound_routes(&rate_limit_config))
+        .merge(seo_routes(&rate_limit_config))
+        .merge(federation_routes(&rate_limit_config))
+        // WebSocket route (authenticates by subprotocol, first message or query token)
         .route("/ws", routing::get(websocket::notification::ws_handler))
+        .fallback(not_found)
+        // Groups with their own limit set it closer to the handlers
+        .layer(DefaultBodyLimit::max(body_limits.default))
+        .layer(middleware::from_fn(json_errors_middleware))
+        .layer(middleware::from_fn(locale_middleware))
 }
 
-fn api_routes() -> Router {
-    let rate_limit_config = RateLimitConfig::from_env();
+fn api_routes(rate_limit_config: &RateLimitConfig, body_limits: &BodyLimitConfig) -> Router {
+    // Maintenance mode sits inside auth so it can let admins through
+    let auth = auth_routes(rate_limit_config)
+        .layer(DefaultBodyLimit::max(body_limits.auth))
+        .layer(middleware::from_fn(maintenance_middleware));
+    let public_read =
+        public_read_routes(rate_limit_config).layer(middleware::from_fn(optional_auth_middleware));
+    let protected = protected_routes(rate_limit_config, body_limits)
+        .layer(middleware::from_fn(maintenance_middleware))
+        .layer(middleware::from_fn(auth_middleware));
+    let pow = pow_routes(rate_limit_config).layer(middleware::from_fn(optional_auth_middleware));
+    let appeals = appeal_routes(rate_limit_config)
+        .layer(middleware::from_fn(maintenance_middleware))
+        .layer(middleware::from_fn(allow_banned_auth_middleware));
+
+    auth.merge(public_read)
+        .merge(protected)
+        .merge(pow)
+        .merge(appeals)
+        .merge(webhook_routes())
+}
+
+/// Routes banned users can still reach: filing and following their appeals.
+fn appeal_routes(config: &RateLimitConfig) -> Router {
+    let router = Router::new().route(
+        "/appeals",
+        routing::get(handlers::appeal::list_my_appeals).post(handlers::appeal::create_appeal),
+    );
+
+    with_optional_rate_limit(router, config, RateLimitGroup::Protected)
+}
 
-    let auth = auth_routes(&rate_limit_config);
-    let public_read = public_read_routes(&rate_limit_config);
-    let protected =
-        protected_routes(&rate_limit_config).layer(middleware::from_fn(auth_middleware));
+/// PoW policy and challenges. Anyone may fetch a registration challenge;
+/// the handler requires a user for other actions.
+fn pow_routes(config: &RateLimitConfig) -> Router {
+    let router = Router::new().route(
+        "/pow/challenge",
+        routing::get(handlers::pow::get_pow_policy).post(handlers::pow::create_pow_challenge),
+    );
 
-    auth.merge(public_read).merge(protected)
+    with_optional_rate_limit(router, config, RateLimitGroup::Auth)
 }
 
-/// Auth routes: register, login, verify-email.
+/// Email provider callbacks, authenticated by `EMAIL_WEBHOOK_SECRET` and not
+/// rate limited since providers deliver in bursts.
+fn webhook_routes() -> Router {
+    Router::new()
+        .route(
+            "/webhooks/email/sendgrid",
+            routing::post(handlers::email::sendgrid_webhook),
+        )
+        .route(
+            "/webhooks/email/ses",
+            routing::post(handlers::email::ses_webhook),
+        )
+}
+
+/// Liveness and readiness probes, never rate limited.
+fn health_routes() -> Router {
+    Router::new()
+        .route("/", routing::get(handlers::health::root))
+        .route("/healthz", routing::get(handlers::health::healthz))
+        .route("/readyz", routing::get(handlers::health::readyz))
+}
+
+/// Outbound link redirect (`/out?url=..&sig=..`) and image proxy
+/// (`/img/{signature}/{encoded_url}`), served outside `/api/v1` so rendered
+/// markdown can reference them directly.
+fn outbound_routes(config: &RateLimitConfig) -> Router {
+    let router = Router::new()
+        .route("/out", routing::get(handlers::outbound::outbound_redirect))
+        .route(
+            "/img/{signature}/{encoded_url}",
+            routing::get(handlers::image_proxy::proxy_image),
+        );
+
+    with_optional_rate_limit(router, config, RateLimitGroup::PublicRead)
+}
+
+/// `robots.txt`, sitemaps and link previews, at the paths crawlers look
+/// for them.
+fn seo_routes(config: &RateLimitConfig) -> Router {
+    let router = Router::new()
+        .route("/robots.txt", routing::get(handlers::seo::robots_txt))
+        .route("/sitemap.xml", routing::get(handlers::seo::sitemap_index))
+        .route(
+            "/sitemaps/{file}",
+            routing::get(handlers::seo::sitemap_page),
+        )
+        .route("/oembed", routing::get(handlers::seo::oembed))
+        .route("/p/{id}", routing::get(handlers::seo::post_preview));
+
+    with_optional_rate_limit(router, config, RateLimitGroup::PublicRead)
+}
+
+/// ActivityPub actors, objects and inboxes, and WebFinger to find them.
+/// Inboxes authenticate requests by HTTP signature.
+fn federation_routes(config: &RateLimitConfig) -> Router {
+    let router = Router::new()
+        .route(
+            "/.well-known/webfinger",
+            routing::get(handlers::federation::webfinger),
+        )
+        .route("/ap/inbox", routing::post(handlers::federation::inbox))
+        .route(
+            "/ap/users/{username}",
+            routing::get(handlers::federation::user_actor),
+        )
+        .route(
+            "/ap/users/{username}/inbox",
+            routing::post(handlers::federation::inbox),
+        )
+        .route(
+            "/ap/users/{username}/outbox",
+            routing::get(handlers::federation::user_outbox),
+        )
+        .route(
+            "/ap/users/{username}/followers",
+            routing::get(handlers::federation::user_followers),
+        )
+        .route(
+            "/ap/forums/{slug}",
+            routing::get(handlers::federation::forum_actor),
+        )
+        .route(
+            "/ap/forums/{slug}/inbox",
+            routing::post(handlers::federation::inbox),
+        )
+        .route(
+            "/ap/forums/{slug}/outbox",
+            routing::get(handlers::federation::forum_outbox),
+        )
+        .route(
+            "/ap/forums/{slug}/followers",
+            routing::get(handlers::federation::forum_followers),
+        )
+        .route(
+            "/ap/posts/{id}",
+            routing::get(handlers::federation::post_object),
+        );
+
+    with_optional_rate_limit(router, config, RateLimitGroup::PublicRead)
+}
+
+/// Auth routes: register, login, verify-email, and email unsubscribe links.
 fn auth_routes(config: &RateLimitConfig) -> Router {
     let router = Router::new()
         .route("/auth/register", routing::post(handlers::register))
@@ -40,9 +199,13 @@ fn auth_routes(config: &RateLimitConfig) -> Router {
         .route(
             "/auth/reset-password",
             routing::post(handlers::auth::reset_password),
+        )
+        .route(
+            "/email/unsubscribe",
+            routing::get(handlers::email::unsubscribe).post(handlers::email::unsubscribe),
         );
 
-    with_optional_rate_limit(router, config.enabled, config.auth)
+    with_optional_rate_limit(router, config, RateLimitGroup::Auth)
 }
 
 /// Public read routes: all public GETs + search.
@@ -53,22 +216,52 @@ fn public_read_routes(config: &RateLimitConfig) -> Router {
             "/users/{username}",
             routing::get(handlers::user::get_user_profile),
         )
+        .route(
+            "/users/{username}/activity",
+            routing::get(handlers::user::get_user_activity),
+        )
+        .route(
+            "/users/{username}/posts",
+            routing::get(handlers::user::get_user_posts),
+        )
+        .route(
+            "/users/{username}/comments",
+            routing::get(handlers::user::get_user_comments),
+        )
         // Forums
         .route("/forums", routing::get(handlers::forum::list_forums))
         .route("/forums/{slug}", routing::get(handlers::forum::get_forum))
+        .route(
+            "/forums/{forum_id}/flairs",
+            routing::get(handlers::flair::list_flairs),
+        )
+        .route("/emoji", routing::get(handlers::emoji::list_emoji))
         // Posts
         .route(
             "/forums/{forum_id}/posts",
             routing::get(handlers::post::list_posts),
         )
+        .route(
+            "/forums/{slug}/posts/{post_slug}",
+            routing::get(handlers::post::get_post_by_slug),
+        )
         .route("/posts/{id}", routing::get(handlers::post::get_post))
         // Comments
         .route(
             "/posts/{post_id}/comments",
             routing::get(handlers::comment::list_comments),
         )
-        // Search
-        .route("/search", routing::get(handlers::post::search_posts))
+        .route(
+            "/comments/{id}",
+            routing::get(handlers::comment::get_comment),
+        )
+        // Announcements
+        .route(
+            "/announcements/active",
+            routing::get(handlers::announcement::active_announcements),
+        )
+        // Badges
+        .route("/badges", routing::get(handlers::badge::list_badges))
         // Tags
         .route("/tags", routing::get(handlers::tag::list_tags))
         .route(
@@ -85,48 +278,154 @@ fn public_read_routes(config: &RateLimitConfig) -> Router {
             routing::get(handlers::follow::list_following),
         );
 
-    with_optional_rate_limit(router, config.enabled, config.public_read)
+    with_optional_rate_limit(router, config, RateLimitGroup::PublicRead)
+        .merge(search_routes(config))
 }
 
-/// Protected routes: all authenticated writes.
-fn protected_routes(config: &RateLimitConfig) -> Router {
+/// Search, limited apart from other public reads since it costs more.
+fn search_routes(config: &RateLimitConfig) -> Router {
+    let router = Router::new()
+        .route("/search", routing::get(handlers::post::search_posts))
+        .route("/search/all", routing::get(handlers::search::search_all));
+
+    with_optional_rate_limit(router, config, RateLimitGroup::Search)
+}
+
+/// Protected routes: all authenticated writes, in groups with their own
+/// rate limits.
+fn protected_routes(config: &RateLimitConfig, body_limits: &BodyLimitConfig) -> Router {
+    account_routes(config)
+        .merge(content_routes(config).layer(DefaultBodyLimit::max(body_limits.content)))
+        .merge(vote_routes(config))
+        .merge(upload_routes(config).layer(DefaultBodyLimit::max(body_limits.uploads)))
+        .merge(admin_routes(config))
+}
+
+/// The signed-in user's own account, notifications, bookmarks and follows.
+fn account_routes(config: &RateLimitConfig) -> Router {
     let router = Router::new()
         // Auth
         .route("/auth/me", routing::get(handlers::get_current_user))
         .route("/auth/logout", routing::post(handlers::auth::logout))
+        .route(
+            "/auth/deactivate",
+            routing::post(handlers::auth::deactivate),
+        )
+        .route(
+            "/auth/account",
+            routing::delete(handlers::auth::delete_account),
+        )
         .route(
             "/auth/profile",
             routing::put(handlers::user::update_profile),
         )
         .route("/auth/password", routing::put(handlers::change_password))
+        .route(
+            "/auth/username",
+            routing::put(handlers::user::change_username),
+        )
+        .route(
+            "/auth/preferences",
+            routing::get(handlers::user::get_preferences).put(handlers::user::update_preferences),
+        )
+        .route(
+            "/auth/email-preferences",
+            routing::get(handlers::email::get_email_preferences)
+                .put(handlers::email::update_email_preferences),
+        )
         .route(
             "/auth/resend-verification",
             routing::post(handlers::resend_verification),
         )
-        // PoW
         .route(
-            "/pow/challenge",
-            routing::post(handlers::pow::create_pow_challenge),
+            "/comments/{id}/revisions",
+            routing::get(handlers::comment::list_comment_revisions),
         )
-        // Forums (admin only - checked in handler)
-        .route("/forums", routing::post(handlers::forum::create_forum))
+        // Notifications
         .route(
-            "/forums/{slug}",
-            routing::put(handlers::forum::update_forum).delete(handlers::forum::delete_forum),
+            "/notifications",
+            routing::get(handlers::notification::list_notifications)
+                .delete(handlers::notification::clear_notifications),
+        )
+        .route(
+            "/notifications/unread-count",
+            routing::get(handlers::notification::unread_count),
+        )
+        .route(
+            "/notifications/quiet-hours",
+            routing::get(handlers::notification::get_quiet_hours)
+                .put(handlers::notification::update_quiet_hours),
+        )
+        .route(
+            "/notifications/read",
+            routing::put(handlers::notification::mark_read_many),
+        )
+        .route(
+            "/notifications/read-all",
+            routing::put(handlers::notification::mark_all_read),
+        )
+        .route(
+            "/notifications/{id}/read",
+            routing::put(handlers::notification::mark_read),
+        )
+        .route(
+            "/notifications/{id}",
+            routing::delete(handlers::notification::delete_notification),
         )
+        // Bookmarks
+        .route(
+            "/posts/{id}/bookmark",
+            routing::put(handlers::bookmark::add_bookmark)
+                .delete(handlers::bookmark::remove_bookmark)
+                .post(handlers::bookmark::toggle_bookmark),
+        )
+        .route(
+            "/posts/{id}/read",
+            routing::put(handlers::post_read::mark_post_read),
+        )
+        .route(
+            "/posts/{id}/watch",
+            routing::post(handlers::watch::watch_post).delete(handlers::watch::unwatch_post),
+        )
+        .route(
+            "/bookmarks",
+            routing::get(handlers::bookmark::list_bookmarks),
+        )
+        // Follow
+        .route(
+            "/users/{id}/follow",
+            routing::put(handlers::follow::follow_user)
+                .delete(handlers::follow::unfollow_user)
+                .post(handlers::follow::toggle_follow),
+        )
+        .route(
+            "/follow-requests",
+            routing::get(handlers::follow::list_follow_requests),
+        )
+        .route(
+            "/follow-requests/{id}/approve",
+            routing::post(handlers::follow::approve_follow_request),
+        )
+        .route(
+            "/follow-requests/{id}/deny",
+            routing::post(handlers::follow::deny_follow_request),
+        );
+
+    with_optional_rate_limit(router, config, RateLimitGroup::Protected)
+}
+
+/// Writing and editing posts and comments, and filing reports.
+fn content_routes(config: &RateLimitConfig) -> Router {
+    let router = Router::new()
         // Posts
         .route("/posts", routing::post(handlers::post::create_post))
         .route(
             "/posts/{id}",
             routing::put(handlers::post::update_post).delete(handlers::post::delete_post),
         )
-        .route("/posts/{id}/pin", routing::put(handlers::post::pin_post))
-        .route("/posts/{id}/lock", routing::put(handlers::post::lock_post))
-        // Votes
-        .route("/posts/{id}/vote", routing::post(handlers::vote::vote_post))
         .route(
-            "/comments/{id}/vote",
-            routing::post(handlers::vote::vote_comment),
+            "/posts/{id}/crosspost",
+            routing::post(handlers::post::crosspost_post),
         )
         // Comments
         .route(
@@ -138,30 +437,132 @@ fn protected_routes(config: &RateLimitConfig) -> Router {
             routing::put(handlers::comment::update_comment)
                 .delete(handlers::comment::delete_comment),
         )
-        // Notifications
+        // Reports
+        .route("/reports", routing::post(handlers::report::create_report))
+        .layer(middleware::from_fn(idempotency_middleware));
+
+    with_optional_rate_limit(router, config, RateLimitGroup::Content)
+}
+
+fn vote_routes(config: &RateLimitConfig) -> Router {
+    let router = Router::new()
+        .route("/posts/{id}/vote", routing::post(handlers::vote::vote_post))
         .route(
-            "/notifications",
-            routing::get(handlers::notification::list_notifications),
+            "/comments/{id}/vote",
+            routing::post(handlers::vote::vote_comment),
+        );
+
+    with_optional_rate_limit(router, config, RateLimitGroup::Votes)
+}
+
+fn upload_routes(config: &RateLimitConfig) -> Router {
+    let router = Router::new()
+        .route(
+            "/upload/avatar",
+            routing::post(handlers::upload::upload_avatar),
         )
         .route(
-            "/notifications/unread-count",
-            routing::get(handlers::notification::unread_count),
+            "/upload/avatar/crop",
+            routing::post(handlers::upload::crop_avatar),
         )
         .route(
-            "/notifications/read-all",
-            routing::put(handlers::notification::mark_all_read),
+            "/upload/image",
+            routing::post(handlers::upload::upload_image),
+        )
+        .route("/admin/emoji", routing::post(handlers::emoji::create_emoji));
+
+    with_optional_rate_limit(router, config, RateLimitGroup::Uploads)
+}
+
+/// Staff routes; permissions are checked in the handlers.
+fn admin_routes(config: &RateLimitConfig) -> Router {
+    let router = Router::new()
+        // Forums
+        .route("/forums", routing::post(handlers::forum::create_forum))
+        .route(
+            "/forums/{slug}",
+            routing::put(handlers::forum::update_forum).delete(handlers::forum::delete_forum),
         )
         .route(
-            "/notifications/{id}/read",
-            routing::put(handlers::notification::mark_read),
+            "/admin/forums/{forum_id}/flairs",
+            routing::post(handlers::flair::create_flair),
+        )
+        .route(
+            "/admin/flairs/{id}",
+            routing::put(handlers::flair::update_flair).delete(handlers::flair::delete_flair),
+        )
+        .route(
+            "/admin/emoji/{id}",
+            routing::delete(handlers::emoji::delete_emoji),
+        )
+        // Post moderation
+        .route("/posts/{id}/pin", routing::put(handlers::post::pin_post))
+        .route("/posts/{id}/lock", routing::put(handlers::post::lock_post))
+        .route(
+            "/posts/{id}/close",
+            routing::put(handlers::post::close_post),
+        )
+        .route(
+            "/posts/{id}/pin-comment/{comment_id}",
+            routing::put(handlers::post::pin_comment),
         )
         // Admin
         .route("/admin/stats", routing::get(handlers::admin::get_stats))
+        .route(
+            "/admin/stats/timeseries",
+            routing::get(handlers::admin::get_stats_timeseries),
+        )
+        .route("/admin/search", routing::get(handlers::admin::search))
+        .route(
+            "/admin/export/{resource}",
+            routing::get(handlers::admin::export),
+        )
         .route("/admin/users", routing::get(handlers::admin::list_users))
+        .route(
+            "/admin/users/{id}",
+            routing::delete(handlers::admin::delete_user),
+        )
         .route(
             "/admin/users/{id}/role",
             routing::put(handlers::admin::update_user_role),
         )
+        .route(
+            "/admin/users/{id}/logout",
+            routing::post(handlers::admin::force_logout_user),
+        )
+        .route(
+            "/admin/impersonate/{user_id}",
+            routing::post(handlers::admin::impersonate_user),
+        )
+        .route(
+            "/admin/audit-log",
+            routing::get(handlers::admin::list_audit_log),
+        )
+        .route(
+            "/admin/settings",
+            routing::get(handlers::settings::get_settings).put(handlers::settings::update_settings),
+        )
+        .route(
+            "/admin/config/reload",
+            routing::post(handlers::settings::reload_config),
+        )
+        .route(
+            "/admin/invites",
+            routing::get(handlers::invite::list_invites).post(handlers::invite::create_invites),
+        )
+        .route(
+            "/admin/invites/{id}",
+            routing::delete(handlers::invite::revoke_invite),
+        )
+        .route(
+            "/admin/users/{id}/notes",
+            routing::get(handlers::user_note::list_user_notes)
+                .post(handlers::user_note::create_user_note),
+        )
+        .route(
+            "/admin/users/{id}/notes/{note_id}",
+            routing::delete(handlers::user_note::delete_user_note),
+        )
         .route(
             "/admin/posts/{id}",
             routing::delete(handlers::admin::admin_delete_post),
@@ -170,35 +571,43 @@ fn protected_routes(config: &RateLimitConfig) -> Router {
             "/admin/comments/{id}",
             routing::delete(handlers::admin::admin_delete_comment),
         )
-        // Bookmarks
+        .route("/admin/emails", routing::get(handlers::admin::list_emails))
         .route(
-            "/posts/{id}/bookmark",
-            routing::put(handlers::bookmark::add_bookmark)
-                .delete(handlers::bookmark::remove_bookmark)
-                .post(handlers::bookmark::toggle_bookmark),
+            "/admin/emails/{id}/retry",
+            routing::post(handlers::admin::retry_email),
         )
         .route(
-            "/bookmarks",
-            routing::get(handlers::bookmark::list_bookmarks),
+            "/admin/search/reindex",
+            routing::post(handlers::admin::reindex_search),
         )
-        // Follow
         .route(
-            "/users/{id}/follow",
-            routing::put(handlers::follow::follow_user)
-                .delete(handlers::follow::unfollow_user)
-                .post(handlers::follow::toggle_follow),
+            "/admin/announcements",
+            routing::get(handlers::announcement::list_announcements)
+                .post(handlers::announcement::create_announcement),
         )
-        // Upload
         .route(
-            "/upload/avatar",
-            routing::post(handlers::upload::upload_avatar),
+            "/admin/announcements/{id}",
+            routing::put(handlers::announcement::update_announcement)
+                .delete(handlers::announcement::delete_announcement),
         )
+        // Badges
         .route(
-            "/upload/image",
-            routing::post(handlers::upload::upload_image),
+            "/admin/badges",
+            routing::post(handlers::badge::create_badge),
+        )
+        .route(
+            "/admin/badges/{id}",
+            routing::delete(handlers::badge::delete_badge),
+        )
+        .route(
+            "/admin/users/{id}/badges",
+            routing::post(handlers::badge::award_badge),
+        )
+        .route(
+            "/admin/users/{id}/badges/{badge_id}",
+            routing::delete(handlers::badge::revoke_badge),
         )
         // Reports
-        .route("/reports", routing::post(handlers::report::create_report))
         .route(
             "/admin/reports",
             routing::get(handlers::report::list_reports),
@@ -207,6 +616,34 @@ fn protected_routes(config: &RateLimitConfig) -> Router {
             "/admin/reports/{id}/resolve",
             routing::put(handlers::report::resolve_report),
         )
+        // Appeals (review)
+        .route(
+            "/admin/appeals",
+            routing::get(handlers::appeal::list_appeals),
+        )
+        .route(
+            "/admin/appeals/{id}",
+            routing::get(handlers::appeal::get_appeal),
+        )
+        .route(
+            "/admin/appeals/{id}/comments",
+            routing::post(handlers::appeal::add_appeal_comment),
+        )
+        .route(
+            "/admin/appeals/{id}/resolve",
+            routing::put(handlers::appeal::resolve_appeal),
+        )
+        // Moderation queue
+        .route("/mod/queue", routing::get(handlers::mod_queue::list_queue))
+        .route(
+            "/mod/queue/{item_type}/{item_id}/claim",
+            routing::post(handlers::mod_queue::claim_item)
+                .delete(handlers::mod_queue::release_item),
+        )
+        .route(
+            "/mod/queue/{item_type}/{item_id}/assign",
+            routing::put(handlers::mod_queue::assign_item),
+        )
         // Tags (admin)
         .route("/admin/tags", routing::post(handlers::tag::create_tag))
         .route(
@@ -214,19 +651,21 @@ fn protected_routes(config: &RateLimitConfig) -> Router {
             routing::put(handlers::tag::update_tag).delete(handlers::tag::delete_tag),
         );
 
-    with_optional_rate_limit(router, config.enabled, config.protected)
+    with_optional_rate_limit(router, config, RateLimitGroup::Admin)
 }
 
-fn with_optional_rate_limit(router: Router, enabled: bool, rule: RateLimitRule) -> Router {
-    if !enabled {
+fn with_optional_rate_limit(
+    router: Router,
+    config: &RateLimitConfig,
+    group: RateLimitGroup,
+) -> Router {
+    if !config.enabled {
         return router;
     }
 
-    let governor_conf = GovernorConfigBuilder::default()
-        .per_second(rule.per_second)
-        .burst_size(rule.burst_size)
-        .finish()
-        .expect("Invalid rate limit configuration");
-
-    router.layer(GovernorLayer::new(governor_conf))
+    let limiter = GroupLimiter::new(config, group);
+    router.layer(middleware::from_fn_with_state(
+        limiter,
+        rate_limit_middleware,
+    ))
 }
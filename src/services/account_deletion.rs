@@ -0,0 +1,111 @@
+//! Deleting user accounts.
+//!
+//! The user's row goes, and with it everything personal that hangs off it:
+//! sessions, votes, follows, bookmarks, notifications. What happens to their
+//! posts and comments is up to the deployment ([`DeletedUserContent`]): by
+//! default they are handed to a shared `[deleted]` account so replies keep
+//! their context; otherwise they are deleted too.
+
+use crate::{
+    config::auth::DeletedUserContent,
+    error::{AppError, AppResult},
+    middleware::auth::invalidate_cached_auth,
+    models::{user, User},
+    services::cache::CacheService,
+    utils::{hash_password, sql},
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    Set, TransactionTrait,
+};
+
+/// Username of the account deleted users' content is handed to.
+pub const DELETED_USERNAME: &str = "[deleted]";
+/// Role marking that account; it grants no permissions.
+const DELETED_ROLE: &str = "deleted";
+
+/// Tables whose rows are reassigned, by the column naming the user.
+const AUTHORED: &[(&str, &str)] = &[
+    ("posts", "user_id"),
+    ("comments", "user_id"),
+    ("comment_revisions", "editor_id"),
+];
+
+pub struct AccountDeletionService {
+    db: DatabaseConnection,
+    cache: Option<CacheService>,
+}
+
+impl AccountDeletionService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db, cache: None }
+    }
+
+    pub fn with_cache(mut self, cache: Option<CacheService>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Delete a user, anonymizing or deleting their content per `content`.
+    pub async fn delete(&self, user_id: i32, content: DeletedUserContent) -> AppResult<()> {
+        let txn = self.db.begin().await?;
+        let existing = User::find_by_id(user_id)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if existing.role == DELETED_ROLE {
+            return Err(AppError::Validation(
+                "The [deleted] account can't be deleted".to_string(),
+            ));
+        }
+
+        if content == DeletedUserContent::Anonymize {
+            let placeholder = Self::placeholder(&txn).await?;
+            for (table, column) in AUTHORED {
+                txn.execute(sql::statement(
+                    txn.get_database_backend(),
+                    format!("UPDATE {} SET {} = $1 WHERE {} = $2", table, column, column),
+                    [placeholder.into(), user_id.into()],
+                ))
+                .await?;
+            }
+        }
+        User::delete_by_id(user_id).exec(&txn).await?;
+        txn.commit().await?;
+
+        invalidate_cached_auth(self.cache.as_ref(), user_id).await;
+        tracing::info!("Deleted user {} ({:?})", user_id, content);
+        Ok(())
+    }
+
+    /// The `[deleted]` account, created on first use. It has no usable
+    /// password and stays deactivated, so it can't log in, has no profile
+    /// and gets no notifications.
+    async fn placeholder<C: ConnectionTrait>(db: &C) -> AppResult<i32> {
+        if let Some(existing) = User::find()
+            .filter(user::Column::Role.eq(DELETED_ROLE))
+            .one(db)
+            .await?
+        {
+            return Ok(existing.id);
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        let created = user::ActiveModel {
+            username: Set(DELETED_USERNAME.to_string()),
+            email: Set("deleted@invalid".to_string()),
+            password_hash: Set(hash_password(&uuid::Uuid::new_v4().to_string())?),
+            karma: Set(0),
+            role: Set(DELETED_ROLE.to_string()),
+            locale: Set("en".to_string()),
+            email_verified: Set(false),
+            deactivated_at: Set(Some(now)),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        Ok(created.id)
+    }
+}
@@ -0,0 +1,190 @@
+//! A user's contributions for their profile: posts and comments in one
+//! timeline, newest first, or each on their own. Hidden posts, hidden
+//! comments and comments on hidden posts are left out; deleted ones are gone
+//! from their tables.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{comment, post, Comment, CommentModel, Post, PostModel};
+use crate::utils::sql;
+use chrono::NaiveDateTime;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, FromQueryResult, Order,
+    PaginatorTrait, QueryFilter, QueryOrder, Select, Value,
+};
+
+/// Order of a user's posts or comments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistorySort {
+    New,
+    Old,
+    /// Highest score first
+    Top,
+}
+
+impl HistorySort {
+    pub const ALL: [&'static str; 3] = ["new", "old", "top"];
+
+    pub fn parse(value: &str) -> AppResult<Self> {
+        match value {
+            "new" => Ok(Self::New),
+            "old" => Ok(Self::Old),
+            "top" => Ok(Self::Top),
+            _ => Err(AppError::Validation(format!(
+                "Invalid sort. Must be one of: {}",
+                Self::ALL.join(", ")
+            ))),
+        }
+    }
+}
+
+/// Order `select` by `sort`, given the entity's vote and date columns.
+fn sorted<E: EntityTrait>(
+    select: Select<E>,
+    sort: HistorySort,
+    upvotes: impl ColumnTrait,
+    downvotes: impl ColumnTrait,
+    created_at: impl ColumnTrait,
+) -> Select<E> {
+    match sort {
+        HistorySort::New => select.order_by_desc(created_at),
+        HistorySort::Old => select.order_by_asc(created_at),
+        HistorySort::Top => select
+            .order_by(Expr::col(upvotes).sub(Expr::col(downvotes)), Order::Desc)
+            .order_by_desc(created_at),
+    }
+}
+
+const ACTIVITY_SQL: &str = "SELECT 'post' AS kind, p.id, p.id AS post_id, p.title AS post_title, \
+        p.content, p.upvotes - p.downvotes AS score, p.created_at \
+        FROM posts p WHERE p.user_id = $1 AND NOT p.is_hidden \
+    UNION ALL \
+    SELECT 'comment' AS kind, c.id, c.post_id, p.title AS post_title, \
+        c.content, c.upvotes - c.downvotes AS score, c.created_at \
+        FROM comments c JOIN posts p ON p.id = c.post_id \
+        WHERE c.user_id = $1 AND NOT c.is_hidden AND NOT p.is_hidden";
+
+#[derive(Debug, FromQueryResult)]
+pub struct ActivityItem {
+    /// `post` or `comment`
+    pub kind: String,
+    pub id: i32,
+    /// The post itself, or the post commented on
+    pub post_id: i32,
+    pub post_title: String,
+    pub content: String,
+    pub score: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CountRow {
+    count: i64,
+}
+
+pub struct ActivityService {
+    db: DatabaseConnection,
+}
+
+impl ActivityService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// A page of the user's posts and comments, newest first.
+    pub async fn list(
+        &self,
+        user_id: i32,
+        page: u64,
+        per_page: u64,
+    ) -> AppResult<(Vec<ActivityItem>, u64)> {
+        let backend = self.db.get_database_backend();
+        let total = CountRow::find_by_statement(sql::statement(
+            backend,
+            format!("SELECT COUNT(*) AS count FROM ({}) a", ACTIVITY_SQL),
+            vec![user_id.into()],
+        ))
+        .one(&self.db)
+        .await?
+        .map(|r| r.count as u64)
+        .unwrap_or(0);
+
+        let values: Vec<Value> = vec![
+            user_id.into(),
+            (per_page as i64).into(),
+            (page.saturating_sub(1).saturating_mul(per_page) as i64).into(),
+        ];
+        let items = ActivityItem::find_by_statement(sql::statement(
+            backend,
+            format!(
+                "SELECT a.kind, a.id, a.post_id, a.post_title, a.content, a.score, a.created_at \
+                    FROM ({}) a ORDER BY a.created_at DESC, a.kind DESC, a.id DESC \
+                    LIMIT $2 OFFSET $3",
+                ACTIVITY_SQL
+            ),
+            values,
+        ))
+        .all(&self.db)
+        .await?;
+
+        Ok((items, total))
+    }
+
+    /// A page of the user's visible posts.
+    pub async fn list_posts(
+        &self,
+        user_id: i32,
+        sort: HistorySort,
+        page: u64,
+        per_page: u64,
+    ) -> AppResult<(Vec<PostModel>, u64)> {
+        let select = Post::find()
+            .filter(post::Column::UserId.eq(user_id))
+            .filter(post::Column::IsHidden.eq(false));
+        let paginator = sorted(
+            select,
+            sort,
+            post::Column::Upvotes,
+            post::Column::Downvotes,
+            post::Column::CreatedAt,
+        )
+        .paginate(&self.db, per_page);
+
+        let total = paginator.num_items().await?;
+        let posts = paginator.fetch_page(page.saturating_sub(1)).await?;
+        Ok((posts, total))
+    }
+
+    /// A page of the user's visible comments, each with the title of the
+    /// post it is on.
+    pub async fn list_comments(
+        &self,
+        user_id: i32,
+        sort: HistorySort,
+        page: u64,
+        per_page: u64,
+    ) -> AppResult<(Vec<(CommentModel, String)>, u64)> {
+        let select = Comment::find()
+            .filter(comment::Column::UserId.eq(user_id))
+            .filter(comment::Column::IsHidden.eq(false));
+        let paginator = sorted(
+            select,
+            sort,
+            comment::Column::Upvotes,
+            comment::Column::Downvotes,
+            comment::Column::CreatedAt,
+        )
+        .find_also_related(Post)
+        .filter(post::Column::IsHidden.eq(false))
+        .paginate(&self.db, per_page);
+
+        let total = paginator.num_items().await?;
+        let comments = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await?
+            .into_iter()
+            .filter_map(|(comment, post)| Some((comment, post?.title)))
+            .collect();
+        Ok((comments, total))
+    }
+}
@@ -1,19 +1,193 @@
 use crate::{
     error::{AppError, AppResult},
-    models::{post, user, Comment, Forum, Post, User, UserModel},
+    models::{
+        email_outbox, post, user, Comment, EmailOutbox, EmailOutboxModel, Forum, Post, PostModel,
+        User, UserModel,
+    },
+    services::{
+        audit::{AuditEntry, AuditLogService},
+        auth::AuthService,
+        cache::CacheService,
+    },
+    utils::{jwt::encode_impersonation_token, sql},
 };
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
-    QueryOrder,
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait,
+    FromQueryResult, PaginatorTrait, QueryFilter, QueryOrder,
 };
+use serde::{Deserialize, Serialize};
+
+/// Time series are recomputed at most this often.
+const CACHE_TTL_TIMESERIES: u64 = 60;
+
+/// Most buckets a single time series request may span.
+pub const MAX_TIMESERIES_BUCKETS: u32 = 366;
+
+/// What a stats time series counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsMetric {
+    /// Accounts created
+    Signups,
+    /// Posts created
+    Posts,
+    /// Comments created
+    Comments,
+    /// Distinct users who posted, commented or voted
+    ActiveUsers,
+}
+
+impl StatsMetric {
+    pub const ALL: [StatsMetric; 4] = [
+        StatsMetric::Signups,
+        StatsMetric::Posts,
+        StatsMetric::Comments,
+        StatsMetric::ActiveUsers,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatsMetric::Signups => "signups",
+            StatsMetric::Posts => "posts",
+            StatsMetric::Comments => "comments",
+            StatsMetric::ActiveUsers => "active_users",
+        }
+    }
+
+    /// `(bucket, count)` rows for `created_at` in `[$2, $3)`, bucketed by
+    /// the `truncate` expression over `created_at`.
+    fn bucket_sql(&self, truncate: &str) -> String {
+        let range = "created_at >= $2 AND created_at < $3";
+        match self {
+            StatsMetric::ActiveUsers => format!(
+                "SELECT {truncate} AS bucket, COUNT(DISTINCT user_id) AS count \
+                    FROM (SELECT user_id, created_at FROM posts WHERE {range} \
+                        UNION ALL SELECT user_id, created_at FROM comments WHERE {range} \
+                        UNION ALL SELECT user_id, created_at FROM votes WHERE {range}) a \
+                    GROUP BY 1"
+            ),
+            _ => {
+                let table = match self {
+                    StatsMetric::Signups => "users",
+                    StatsMetric::Posts => "posts",
+                    _ => "comments",
+                };
+                format!(
+                    "SELECT {truncate} AS bucket, COUNT(*) AS count \
+                        FROM {table} WHERE {range} GROUP BY 1"
+                )
+            }
+        }
+    }
+}
+
+/// Bucket width of a stats time series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsInterval {
+    Day,
+    /// Weeks start on Monday
+    Week,
+    Month,
+}
+
+impl StatsInterval {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "day" => Some(StatsInterval::Day),
+            "week" => Some(StatsInterval::Week),
+            "month" => Some(StatsInterval::Month),
+            _ => None,
+        }
+    }
+
+    /// Also the Postgres `date_trunc` unit.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatsInterval::Day => "day",
+            StatsInterval::Week => "week",
+            StatsInterval::Month => "month",
+        }
+    }
+
+    /// SQLite `datetime` modifiers truncating to the bucket start, and
+    /// stepping from one bucket to the next.
+    fn sqlite_modifiers(&self) -> (&'static str, &'static str) {
+        match self {
+            StatsInterval::Day => ("'start of day'", "'+1 day'"),
+            StatsInterval::Week => ("'start of day', 'weekday 0', '-6 days'", "'+7 days'"),
+            StatsInterval::Month => ("'start of month'", "'+1 month'"),
+        }
+    }
+
+    /// MySQL expression truncating `value` to the bucket start, and the
+    /// interval from one bucket to the next.
+    fn mysql_truncate(&self, value: &str) -> (String, &'static str) {
+        match self {
+            StatsInterval::Day => (format!("CAST(DATE({value}) AS DATETIME)"), "INTERVAL 1 DAY"),
+            StatsInterval::Week => (
+                format!("CAST(DATE_SUB(DATE({value}), INTERVAL WEEKDAY({value}) DAY) AS DATETIME)"),
+                "INTERVAL 7 DAY",
+            ),
+            StatsInterval::Month => (
+                format!("CAST(DATE_FORMAT({value}, '%Y-%m-01') AS DATETIME)"),
+                "INTERVAL 1 MONTH",
+            ),
+        }
+    }
+
+    /// Range covered when the caller gives no start date: 30 days, 12 weeks
+    /// or 12 months.
+    pub fn default_from(&self, to: NaiveDate) -> NaiveDate {
+        let days = match self {
+            StatsInterval::Day => 29,
+            StatsInterval::Week => 83,
+            StatsInterval::Month => 364,
+        };
+        to - chrono::Duration::days(days)
+    }
+
+    /// Number of buckets touched by `from..=to`.
+    fn bucket_count(&self, from: NaiveDate, to: NaiveDate) -> i64 {
+        match self {
+            StatsInterval::Day => (to - from).num_days() + 1,
+            StatsInterval::Week => {
+                let monday = |d: NaiveDate| {
+                    d - chrono::Duration::days(d.weekday().num_days_from_monday() as i64)
+                };
+                (monday(to) - monday(from)).num_days() / 7 + 1
+            }
+            StatsInterval::Month => {
+                (to.year() - from.year()) as i64 * 12 + to.month() as i64 - from.month() as i64 + 1
+            }
+        }
+    }
+}
+
+/// One bucket of a stats time series; `bucket` is the start of the day,
+/// week or month.
+#[derive(Debug, Clone, Serialize, Deserialize, FromQueryResult)]
+pub struct TimeseriesPoint {
+    pub bucket: NaiveDateTime,
+    pub count: i64,
+}
 
 pub struct AdminService {
     db: DatabaseConnection,
+    cache: Option<CacheService>,
 }
 
 impl AdminService {
     pub fn new(db: DatabaseConnection) -> Self {
-        Self { db }
+        Self { db, cache: None }
+    }
+
+    pub fn with_cache(mut self, cache: Option<CacheService>) -> Self {
+        self.cache = cache;
+        self
     }
 
     pub async fn get_stats(&self) -> AppResult<AdminStats> {
@@ -45,8 +219,112 @@ impl AdminService {
         })
     }
 
-    pub async fn list_users(&self, page: u64, per_page: u64) -> AppResult<(Vec<UserModel>, u64)> {
-        let paginator = User::find()
+    /// Counts of `metric` per `interval` for the days `from..=to`, oldest
+    /// first, with empty buckets included. The first and last buckets only
+    /// count the part inside the range.
+    pub async fn timeseries(
+        &self,
+        metric: StatsMetric,
+        interval: StatsInterval,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> AppResult<Vec<TimeseriesPoint>> {
+        if from > to {
+            return Err(AppError::Validation(
+                "from must not be after to".to_string(),
+            ));
+        }
+        if interval.bucket_count(from, to) > MAX_TIMESERIES_BUCKETS as i64 {
+            return Err(AppError::Validation(format!(
+                "Range spans more than {} buckets; use a wider interval",
+                MAX_TIMESERIES_BUCKETS
+            )));
+        }
+
+        let key = format!(
+            "stats:timeseries:{}:{}:{}:{}",
+            metric.as_str(),
+            interval.as_str(),
+            from,
+            to
+        );
+        if let Some(cache) = &self.cache {
+            if let Some(cached) = cache.get::<Vec<TimeseriesPoint>>(&key).await {
+                return Ok(cached);
+            }
+        }
+
+        let start = from.and_hms_opt(0, 0, 0).unwrap();
+        let end = (to + chrono::Duration::days(1))
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let backend = self.db.get_database_backend();
+        let sql = match backend {
+            DbBackend::Sqlite => {
+                let (truncate, step) = interval.sqlite_modifiers();
+                format!(
+                    "WITH RECURSIVE s(bucket) AS ( \
+                        SELECT datetime($2, {truncate}) \
+                        UNION ALL SELECT datetime(bucket, {step}) FROM s \
+                        WHERE datetime(bucket, {step}) < $3) \
+                    SELECT s.bucket, COALESCE(c.count, 0) AS count FROM s \
+                    LEFT JOIN ({}) c ON c.bucket = s.bucket \
+                    ORDER BY s.bucket",
+                    metric.bucket_sql(&format!("datetime(created_at, {truncate})"))
+                )
+            }
+            DbBackend::MySql => {
+                let (first, step) = interval.mysql_truncate("$2");
+                format!(
+                    "WITH RECURSIVE s(bucket) AS ( \
+                        SELECT {first} \
+                        UNION ALL SELECT bucket + {step} FROM s \
+                        WHERE bucket + {step} < $3) \
+                    SELECT s.bucket, COALESCE(c.count, 0) AS count FROM s \
+                    LEFT JOIN ({}) c ON c.bucket = s.bucket \
+                    ORDER BY s.bucket",
+                    metric.bucket_sql(&interval.mysql_truncate("created_at").0)
+                )
+            }
+            _ => format!(
+                "SELECT s.bucket, COALESCE(c.count, 0) AS count \
+                    FROM generate_series(date_trunc($1, $2::timestamp), \
+                        date_trunc($1, $3::timestamp - INTERVAL '1 day'), \
+                        ('1 ' || $1)::interval) AS s(bucket) \
+                    LEFT JOIN ({}) c ON c.bucket = s.bucket \
+                    ORDER BY s.bucket",
+                metric.bucket_sql("date_trunc($1, created_at)")
+            ),
+        };
+        let points = TimeseriesPoint::find_by_statement(sql::statement(
+            backend,
+            sql,
+            [interval.as_str().into(), start.into(), end.into()],
+        ))
+        .all(&self.db)
+        .await?;
+
+        if let Some(cache) = &self.cache {
+            cache.set(&key, &points, CACHE_TTL_TIMESERIES).await;
+        }
+        Ok(points)
+    }
+
+    /// List users, optionally only those whose address is (or isn't)
+    /// undeliverable.
+    pub async fn list_users(
+        &self,
+        undeliverable: Option<bool>,
+        page: u64,
+        per_page: u64,
+    ) -> AppResult<(Vec<UserModel>, u64)> {
+        let mut query = User::find();
+        match undeliverable {
+            Some(true) => query = query.filter(user::Column::EmailUndeliverable.is_not_null()),
+            Some(false) => query = query.filter(user::Column::EmailUndeliverable.is_null()),
+            None => {}
+        }
+        let paginator = query
             .order_by_desc(user::Column::CreatedAt)
             .paginate(&self.db, per_page);
 
@@ -69,14 +347,78 @@ impl AdminService {
             .await?
             .ok_or(AppError::NotFound)?;
 
+        let role_changed = existing.role != role;
         let mut active: user::ActiveModel = existing.into();
         active.role = sea_orm::ActiveValue::Set(role.to_string());
-        let updated = active.update(&self.db).await?;
-        Ok(updated)
+        active.update(&self.db).await?;
+
+        // Tokens carry no role, but a demoted or banned user must not keep a
+        // live session that was granted under the old role.
+        if role_changed {
+            self.force_logout(user_id).await?;
+        }
+
+        User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)
     }
 
-    pub async fn admin_delete_post(&self, post_id: i32) -> AppResult<()> {
-        Post::find_by_id(post_id)
+    /// Invalidate all access and refresh tokens of a user.
+    pub async fn force_logout(&self, user_id: i32) -> AppResult<()> {
+        User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        AuthService::new(self.db.clone())
+            .with_cache(self.cache.clone())
+            .invalidate_user_sessions(user_id)
+            .await
+    }
+
+    /// Issue a short-lived token letting `admin_id` act as `user_id`, and
+    /// record it in the audit log. Other admins can't be impersonated.
+    pub async fn impersonate(
+        &self,
+        admin_id: i32,
+        user_id: i32,
+        read_only: bool,
+    ) -> AppResult<(String, UserModel)> {
+        let target = User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if target.id == admin_id || target.role == "admin" {
+            return Err(AppError::Validation(
+                "Admins can't be impersonated".to_string(),
+            ));
+        }
+
+        let mode = if read_only { "read_only" } else { "full" };
+        let token = encode_impersonation_token(
+            &target.id.to_string(),
+            target.token_version,
+            admin_id,
+            mode,
+        )
+        .map_err(AppError::Internal)?;
+
+        AuditLogService::new(self.db.clone())
+            .record(AuditEntry {
+                actor_id: Some(admin_id),
+                action: "impersonation_started",
+                target_user_id: Some(target.id),
+                detail: Some(mode.to_string()),
+                ..Default::default()
+            })
+            .await;
+        Ok((token, target))
+    }
+
+    /// Delete any post, returning it as it was before deletion.
+    pub async fn admin_delete_post(&self, post_id: i32) -> AppResult<PostModel> {
+        let post = Post::find_by_id(post_id)
             .one(&self.db)
             .await?
             .ok_or(AppError::NotFound)?;
@@ -85,7 +427,7 @@ impl AdminService {
 
         let points = crate::services::points::PointsService::new(self.db.clone());
         let _ = points.rollback_by_ref("post", post_id).await;
-        Ok(())
+        Ok(post)
     }
 
     pub async fn admin_delete_comment(&self, comment_id: i32) -> AppResult<()> {
@@ -100,6 +442,44 @@ impl AdminService {
         let _ = points.rollback_by_ref("comment", comment_id).await;
         Ok(())
     }
+
+    /// Outgoing emails with the given status, newest first.
+    pub async fn list_emails(
+        &self,
+        status: &str,
+        page: u64,
+        per_page: u64,
+    ) -> AppResult<(Vec<EmailOutboxModel>, u64)> {
+        let paginator = EmailOutbox::find()
+            .filter(email_outbox::Column::Status.eq(status))
+            .order_by_desc(email_outbox::Column::Id)
+            .paginate(&self.db, per_page);
+
+        let total = paginator.num_items().await?;
+        let emails = paginator.fetch_page(page.saturating_sub(1)).await?;
+        Ok((emails, total))
+    }
+
+    /// Queue a failed email again, with a fresh set of attempts.
+    pub async fn retry_email(&self, email_id: i32) -> AppResult<EmailOutboxModel> {
+        let email = EmailOutbox::find_by_id(email_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if email.status != "failed" {
+            return Err(AppError::Validation(
+                "Only failed emails can be retried".to_string(),
+            ));
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        let mut active: email_outbox::ActiveModel = email.into();
+        active.status = sea_orm::ActiveValue::Set("pending".to_string());
+        active.attempts = sea_orm::ActiveValue::Set(0);
+        active.next_attempt_at = sea_orm::ActiveValue::Set(now);
+        active.updated_at = sea_orm::ActiveValue::Set(now);
+        Ok(active.update(&self.db).await?)
+    }
 }
 
 pub struct AdminStats {
@@ -110,3 +490,40 @@ pub struct AdminStats {
     pub users_today: u64,
     pub posts_today: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_metric_names_round_trip() {
+        for metric in StatsMetric::ALL {
+            assert_eq!(StatsMetric::parse(metric.as_str()), Some(metric));
+        }
+        assert_eq!(StatsMetric::parse("votes"), None);
+    }
+
+    #[test]
+    fn test_bucket_count() {
+        let (from, to) = (date("2026-01-31"), date("2026-03-01"));
+        assert_eq!(StatsInterval::Day.bucket_count(from, to), 30);
+        // 2026-01-31 is a Saturday, 2026-03-01 a Sunday
+        assert_eq!(StatsInterval::Week.bucket_count(from, to), 5);
+        assert_eq!(StatsInterval::Month.bucket_count(from, to), 3);
+        assert_eq!(StatsInterval::Day.bucket_count(to, to), 1);
+    }
+
+    #[test]
+    fn test_default_from() {
+        let to = date("2026-10-18");
+        assert_eq!(StatsInterval::Day.default_from(to), date("2026-09-19"));
+        assert_eq!(
+            StatsInterval::Day.bucket_count(StatsInterval::Day.default_from(to), to),
+            30
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Admin broadcast announcements.
+//!
+//! An announcement targets every user, or with `forum_id` the forum's
+//! members: users who have posted or commented there. Publishing one sends
+//! each recipient an in-app notification and, if `send_email` is set, an
+//! email in the `announcement` category (which users can unsubscribe from).
+//! Announcements stay listed at `/announcements/active` until they expire.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    announcement, comment, post, user, Announcement, AnnouncementModel, Comment, Forum, Post, User,
+};
+use crate::services::email::EmailService;
+use crate::services::notification::NotificationService;
+use crate::websocket::hub::NotificationHub;
+use chrono::NaiveDateTime;
+use sea_orm::sea_query::{Condition, Expr, Query};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, Set,
+};
+
+pub struct NewAnnouncement {
+    pub title: String,
+    pub body: String,
+    pub forum_id: Option<i32>,
+    pub send_email: bool,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+pub struct AnnouncementService {
+    db: DatabaseConnection,
+}
+
+impl AnnouncementService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn list(&self, page: u64, per_page: u64) -> AppResult<(Vec<AnnouncementModel>, u64)> {
+        let paginator = Announcement::find()
+            .order_by_desc(announcement::Column::CreatedAt)
+            .paginate(&self.db, per_page);
+        let total = paginator.num_items().await?;
+        let items = paginator.fetch_page(page.saturating_sub(1)).await?;
+        Ok((items, total))
+    }
+
+    /// Unexpired announcements for everyone, plus those for `forum_id`.
+    pub async fn active(&self, forum_id: Option<i32>) -> AppResult<Vec<AnnouncementModel>> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut target = Condition::any().add(announcement::Column::ForumId.is_null());
+        if let Some(forum_id) = forum_id {
+            target = target.add(announcement::Column::ForumId.eq(forum_id));
+        }
+        let items = Announcement::find()
+            .filter(target)
+            .filter(
+                Condition::any()
+                    .add(announcement::Column::ExpiresAt.is_null())
+                    .add(announcement::Column::ExpiresAt.gt(now)),
+            )
+            .order_by_desc(announcement::Column::CreatedAt)
+            .all(&self.db)
+            .await?;
+        Ok(items)
+    }
+
+    /// Save an announcement and deliver it to its recipients.
+    pub async fn create(
+        &self,
+        created_by: i32,
+        input: NewAnnouncement,
+        hub: NotificationHub,
+        email_service: &EmailService,
+    ) -> AppResult<AnnouncementModel> {
+        if let Some(forum_id) = input.forum_id {
+            Forum::find_by_id(forum_id)
+                .one(&self.db)
+                .await?
+                .ok_or_else(|| AppError::Validation("Forum not found".to_string()))?;
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        let saved = announcement::ActiveModel {
+            title: Set(input.title),
+            body: Set(input.body),
+            forum_id: Set(input.forum_id),
+            send_email: Set(input.send_email),
+            recipient_count: Set(0),
+            expires_at: Set(input.expires_at),
+            created_by: Set(Some(created_by)),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+
+        let recipients = self.recipients(created_by, saved.forum_id).await?;
+        let ids: Vec<i32> = recipients.iter().map(|u| u.id).collect();
+        let notified = NotificationService::new(self.db.clone(), hub)
+            .notify_many(
+                &ids,
+                created_by,
+                "announcement",
+                "announcement",
+                saved.id,
+                &saved.title,
+            )
+            .await?;
+
+        if saved.send_email {
+            for user in recipients.iter().filter(|u| u.email_verified) {
+                if let Err(e) = email_service
+                    .send_announcement(&self.db, user, &saved.title, &saved.body)
+                    .await
+                {
+                    tracing::warn!(
+                        "Failed to queue announcement {} for user {}: {}",
+                        saved.id,
+                        user.id,
+                        e
+                    );
+                }
+            }
+        }
+
+        let mut active: announcement::ActiveModel = saved.into();
+        active.recipient_count = Set(notified as i32);
+        Ok(active.update(&self.db).await?)
+    }
+
+    /// Edit an announcement's text and expiry. Recipients aren't notified
+    /// again.
+    pub async fn update(
+        &self,
+        id: i32,
+        title: String,
+        body: String,
+        expires_at: Option<NaiveDateTime>,
+    ) -> AppResult<AnnouncementModel> {
+        let existing = Announcement::find_by_id(id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let mut active: announcement::ActiveModel = existing.into();
+        active.title = Set(title);
+        active.body = Set(body);
+        active.expires_at = Set(expires_at);
+        active.updated_at = Set(chrono::Utc::now().naive_utc());
+        Ok(active.update(&self.db).await?)
+    }
+
+    pub async fn delete(&self, id: i32) -> AppResult<()> {
+        let result = Announcement::delete_by_id(id).exec(&self.db).await?;
+        if result.rows_affected == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Users an announcement goes to: everyone but its author and banned or
+    /// deactivated users, narrowed to those who posted or commented in `forum_id` if
+    /// given.
+    async fn recipients(
+        &self,
+        author_id: i32,
+        forum_id: Option<i32>,
+    ) -> AppResult<Vec<user::Model>> {
+        let mut query = User::find()
+            .filter(user::Column::Id.ne(author_id))
+            .filter(user::Column::Role.ne("banned"))
+            .filter(user::Column::DeactivatedAt.is_null());
+        if let Some(forum_id) = forum_id {
+            let posters = Query::select()
+                .column(post::Column::UserId)
+                .from(Post)
+                .and_where(Expr::col(post::Column::ForumId).eq(forum_id))
+                .to_owned();
+            let commenters = Query::select()
+                .column((Comment, comment::Column::UserId))
+                .from(Comment)
+                .inner_join(
+                    Post,
+                    Expr::col((Post, post::Column::Id)).equals((Comment, comment::Column::PostId)),
+                )
+                .and_where(Expr::col((Post, post::Column::ForumId)).eq(forum_id))
+                .to_owned();
+            query = query.filter(
+                Condition::any()
+                    .add(user::Column::Id.in_subquery(posters))
+                    .add(user::Column::Id.in_subquery(commenters)),
+            );
+        }
+        Ok(query.all(&self.db).await?)
+    }
+}
@@ -0,0 +1,286 @@
+//! Appeals against moderation actions.
+//!
+//! A warned or banned user can appeal each warning or ban once; a ban that
+//! wasn't issued from a report is appealed without naming an action. Pending
+//! appeals enter the moderation queue, where reviewers discuss them in
+//! comments and accept or decline them. Accepting an appeal lifts the
+//! appellant's ban.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    appeal, appeal_comment, moderation_action, user, Appeal, AppealComment, AppealCommentModel,
+    AppealModel, ModerationAction, ModerationActionModel, User,
+};
+use crate::services::mod_queue::{ModQueueService, QueueItemKind};
+use crate::services::report::ReportAction;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+
+/// A reviewer's verdict on an appeal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppealDecision {
+    /// Overturn the action; lifts a ban
+    Accept,
+    /// Let the action stand
+    Decline,
+}
+
+impl AppealDecision {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "accept" => Some(AppealDecision::Accept),
+            "decline" => Some(AppealDecision::Decline),
+            _ => None,
+        }
+    }
+
+    /// Status of an appeal decided this way.
+    pub fn status(&self) -> &'static str {
+        match self {
+            AppealDecision::Accept => "accepted",
+            AppealDecision::Decline => "declined",
+        }
+    }
+}
+
+pub struct AppealService {
+    db: DatabaseConnection,
+}
+
+impl AppealService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// File an appeal against one of the user's warnings or bans, or with no
+    /// `moderation_action_id` against their current ban.
+    pub async fn create(
+        &self,
+        user_id: i32,
+        moderation_action_id: Option<i32>,
+        reason: &str,
+    ) -> AppResult<AppealModel> {
+        let appellant = User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        let mut existing = Appeal::find().filter(appeal::Column::UserId.eq(user_id));
+        match moderation_action_id {
+            Some(action_id) => {
+                let action = ModerationAction::find_by_id(action_id)
+                    .filter(moderation_action::Column::UserId.eq(user_id))
+                    .one(&self.db)
+                    .await?
+                    .ok_or(AppError::NotFound)?;
+                let appealable = [
+                    ReportAction::WarnUser.as_str(),
+                    ReportAction::BanUser.as_str(),
+                ];
+                if !appealable.contains(&action.action.as_str()) {
+                    return Err(AppError::Validation(
+                        "Only warnings and bans can be appealed".to_string(),
+                    ));
+                }
+                existing = existing.filter(appeal::Column::ModerationActionId.eq(action_id));
+            }
+            None => {
+                if appellant.role != "banned" {
+                    return Err(AppError::Validation(
+                        "You are not banned; name the action you are appealing".to_string(),
+                    ));
+                }
+                existing = existing
+                    .filter(appeal::Column::ModerationActionId.is_null())
+                    .filter(appeal::Column::Status.eq("pending"));
+            }
+        }
+        if existing.one(&self.db).await?.is_some() {
+            return Err(AppError::Conflict(
+                "You have already appealed this action".to_string(),
+            ));
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        let saved = appeal::ActiveModel {
+            user_id: Set(user_id),
+            moderation_action_id: Set(moderation_action_id),
+            reason: Set(reason.to_string()),
+            status: Set("pending".to_string()),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(saved)
+    }
+
+    /// The user's own appeals, newest first.
+    pub async fn list_for_user(&self, user_id: i32) -> AppResult<Vec<AppealModel>> {
+        let appeals = Appeal::find()
+            .filter(appeal::Column::UserId.eq(user_id))
+            .order_by_desc(appeal::Column::CreatedAt)
+            .all(&self.db)
+            .await?;
+        Ok(appeals)
+    }
+
+    pub async fn list(
+        &self,
+        status: Option<&str>,
+        page: u64,
+        per_page: u64,
+    ) -> AppResult<(Vec<AppealModel>, u64)> {
+        let mut query = Appeal::find();
+        if let Some(status) = status {
+            query = query.filter(appeal::Column::Status.eq(status));
+        }
+        let paginator = query
+            .order_by_desc(appeal::Column::CreatedAt)
+            .paginate(&self.db, per_page);
+        let total = paginator.num_items().await?;
+        let items = paginator.fetch_page(page.saturating_sub(1)).await?;
+        Ok((items, total))
+    }
+
+    /// An appeal with the action it contests and reviewers' comments.
+    pub async fn get(
+        &self,
+        id: i32,
+    ) -> AppResult<(
+        AppealModel,
+        Option<ModerationActionModel>,
+        Vec<AppealCommentModel>,
+    )> {
+        let appeal = Appeal::find_by_id(id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let action = match appeal.moderation_action_id {
+            Some(action_id) => {
+                ModerationAction::find_by_id(action_id)
+                    .one(&self.db)
+                    .await?
+            }
+            None => None,
+        };
+        let comments = AppealComment::find()
+            .filter(appeal_comment::Column::AppealId.eq(id))
+            .order_by_asc(appeal_comment::Column::CreatedAt)
+            .order_by_asc(appeal_comment::Column::Id)
+            .all(&self.db)
+            .await?;
+        Ok((appeal, action, comments))
+    }
+
+    pub async fn add_comment(
+        &self,
+        appeal_id: i32,
+        user_id: i32,
+        body: &str,
+    ) -> AppResult<AppealCommentModel> {
+        Appeal::find_by_id(appeal_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let comment = appeal_comment::ActiveModel {
+            appeal_id: Set(appeal_id),
+            user_id: Set(Some(user_id)),
+            body: Set(body.to_string()),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(comment)
+    }
+
+    /// Decide a pending appeal. Accepting lifts the appellant's ban; returns
+    /// the decided appeal and whether a ban was lifted.
+    pub async fn decide(
+        &self,
+        appeal_id: i32,
+        reviewer_id: i32,
+        decision: AppealDecision,
+        note: Option<&str>,
+    ) -> AppResult<(AppealModel, bool)> {
+        let queue = ModQueueService::new(self.db.clone());
+        queue
+            .ensure_not_claimed_by_other(QueueItemKind::Appeal, appeal_id, reviewer_id)
+            .await?;
+
+        let txn = self.db.begin().await?;
+        let existing = Appeal::find_by_id(appeal_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if existing.status != "pending" {
+            return Err(AppError::Validation(
+                "Appeal is already decided".to_string(),
+            ));
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        let note = note.map(str::trim).filter(|n| !n.is_empty());
+        let mut unbanned = false;
+        if decision == AppealDecision::Accept {
+            let appellant = User::find_by_id(existing.user_id)
+                .one(&txn)
+                .await?
+                .ok_or(AppError::NotFound)?;
+            if appellant.role == "banned" {
+                let mut active: user::ActiveModel = appellant.into();
+                active.role = Set("user".to_string());
+                active.updated_at = Set(now);
+                active.update(&txn).await?;
+
+                moderation_action::ActiveModel {
+                    user_id: Set(existing.user_id),
+                    moderator_id: Set(Some(reviewer_id)),
+                    action: Set("unban_user".to_string()),
+                    note: Set(note.map(|n| n.to_string())),
+                    created_at: Set(now),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?;
+                unbanned = true;
+            }
+        }
+
+        let mut active: appeal::ActiveModel = existing.into();
+        active.status = Set(decision.status().to_string());
+        active.decided_by = Set(Some(reviewer_id));
+        active.decided_at = Set(Some(now));
+        active.decision_note = Set(note.map(|n| n.to_string()));
+        active.updated_at = Set(now);
+        let updated = active.update(&txn).await?;
+        txn.commit().await?;
+
+        queue.clear(QueueItemKind::Appeal, appeal_id).await?;
+        Ok((updated, unbanned))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decision_parse() {
+        assert_eq!(
+            AppealDecision::parse("accept"),
+            Some(AppealDecision::Accept)
+        );
+        assert_eq!(
+            AppealDecision::parse("decline"),
+            Some(AppealDecision::Decline)
+        );
+        assert_eq!(AppealDecision::parse("maybe"), None);
+        assert_eq!(AppealDecision::Accept.status(), "accepted");
+    }
+}
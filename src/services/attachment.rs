@@ -0,0 +1,119 @@
+//! Files attached to posts.
+//!
+//! Authors upload files first, then list their upload IDs when creating the
+//! post. An upload can be attached by the user who uploaded it, to one post
+//! only; attaching it also records the post as what refers to it, so the
+//! upload cleanup job keeps the file.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{post_attachment, upload, PostAttachment, Upload, UploadModel};
+use crate::services::upload::UploadService;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+};
+use std::collections::HashMap;
+
+/// Most files a post can have attached.
+pub const MAX_ATTACHMENTS: usize = 10;
+
+pub struct AttachmentService {
+    db: DatabaseConnection,
+}
+
+impl AttachmentService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Look up uploads `user_id` may attach to a new post, in the given
+    /// order without repeats: their own, not an avatar or emoji and not
+    /// attached elsewhere.
+    pub async fn attachable(
+        &self,
+        user_id: i32,
+        upload_ids: &[i32],
+    ) -> AppResult<Vec<UploadModel>> {
+        let mut ids: Vec<i32> = Vec::with_capacity(upload_ids.len());
+        for id in upload_ids {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+        if ids.len() > MAX_ATTACHMENTS {
+            return Err(AppError::Validation(format!(
+                "Maximum {} attachments allowed",
+                MAX_ATTACHMENTS
+            )));
+        }
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut uploads: HashMap<i32, UploadModel> = Upload::find()
+            .filter(upload::Column::Id.is_in(ids.clone()))
+            .filter(upload::Column::UserId.eq(user_id))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|u| (u.id, u))
+            .collect();
+        let attached = PostAttachment::find()
+            .filter(post_attachment::Column::UploadId.is_in(ids.clone()))
+            .all(&self.db)
+            .await?;
+
+        ids.into_iter()
+            .map(|id| {
+                uploads
+                    .remove(&id)
+                    .filter(|u| !matches!(u.entity_type.as_deref(), Some("user" | "emoji")))
+                    .filter(|u| !attached.iter().any(|a| a.upload_id == u.id))
+                    .ok_or_else(|| AppError::Validation(format!("Upload {} can't be attached", id)))
+            })
+            .collect()
+    }
+
+    /// Attach uploads checked by [`attachable`](Self::attachable) to a post.
+    pub async fn attach(&self, post_id: i32, uploads: &[UploadModel]) -> AppResult<()> {
+        let now = chrono::Utc::now().naive_utc();
+        for (position, upload) in uploads.iter().enumerate() {
+            post_attachment::ActiveModel {
+                post_id: Set(post_id),
+                upload_id: Set(upload.id),
+                position: Set(position as i32),
+                created_at: Set(now),
+                ..Default::default()
+            }
+            .insert(&self.db)
+            .await?;
+            UploadService::link(&self.db, &upload_url(upload), "post", post_id).await?;
+        }
+        Ok(())
+    }
+
+    /// The attached uploads of each post, in order.
+    pub async fn for_posts(&self, post_ids: &[i32]) -> AppResult<HashMap<i32, Vec<UploadModel>>> {
+        let mut map: HashMap<i32, Vec<UploadModel>> = HashMap::new();
+        if post_ids.is_empty() {
+            return Ok(map);
+        }
+        let rows = PostAttachment::find()
+            .filter(post_attachment::Column::PostId.is_in(post_ids.iter().copied()))
+            .find_also_related(Upload)
+            .order_by_asc(post_attachment::Column::PostId)
+            .order_by_asc(post_attachment::Column::Position)
+            .all(&self.db)
+            .await?;
+        for (attachment, upload) in rows {
+            if let Some(upload) = upload {
+                map.entry(attachment.post_id).or_default().push(upload);
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// Public URL path of an upload.
+pub fn upload_url(upload: &UploadModel) -> String {
+    format!("/uploads/{}", upload.path)
+}
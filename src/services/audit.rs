@@ -0,0 +1,75 @@
+//! Audit log of sensitive staff actions.
+//!
+//! Writes never fail the request being audited: a failed insert is logged
+//! and dropped.
+
+use crate::error::AppResult;
+use crate::models::{audit_log, AuditLog, AuditLogModel};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, Set,
+};
+
+/// An entry to record. Only `action` is required.
+#[derive(Debug, Default)]
+pub struct AuditEntry {
+    pub actor_id: Option<i32>,
+    pub action: &'static str,
+    pub target_user_id: Option<i32>,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub status: Option<i32>,
+    pub detail: Option<String>,
+}
+
+pub struct AuditLogService {
+    db: DatabaseConnection,
+}
+
+impl AuditLogService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn record(&self, entry: AuditEntry) {
+        let result = audit_log::ActiveModel {
+            actor_id: Set(entry.actor_id),
+            action: Set(entry.action.to_string()),
+            target_user_id: Set(entry.target_user_id),
+            method: Set(entry.method),
+            path: Set(entry.path),
+            status: Set(entry.status),
+            detail: Set(entry.detail),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await;
+        if let Err(e) = result {
+            tracing::error!("Failed to write audit log entry {}: {}", entry.action, e);
+        }
+    }
+
+    /// Entries newest first, optionally narrowed to an actor or target user.
+    pub async fn list(
+        &self,
+        actor_id: Option<i32>,
+        target_user_id: Option<i32>,
+        page: u64,
+        per_page: u64,
+    ) -> AppResult<(Vec<AuditLogModel>, u64)> {
+        let mut query = AuditLog::find();
+        if let Some(actor_id) = actor_id {
+            query = query.filter(audit_log::Column::ActorId.eq(actor_id));
+        }
+        if let Some(target_user_id) = target_user_id {
+            query = query.filter(audit_log::Column::TargetUserId.eq(target_user_id));
+        }
+        let paginator = query
+            .order_by_desc(audit_log::Column::Id)
+            .paginate(&self.db, per_page);
+        let total = paginator.num_items().await?;
+        let items = paginator.fetch_page(page.saturating_sub(1)).await?;
+        Ok((items, total))
+    }
+}
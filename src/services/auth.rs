@@ -1,18 +1,30 @@
 use crate::{
     config::auth::AuthConfig,
     error::{AppError, AppResult},
+    middleware::auth::invalidate_cached_auth,
     models::{refresh_token, RefreshToken, User},
-    services::email::EmailService,
-    utils::{encode_access_token, encode_refresh_token, hash_password, verify_password},
+    services::{
+        account_deletion::DELETED_USERNAME,
+        cache::CacheService,
+        email::EmailService,
+        invite::InviteService,
+        settings::{RegistrationMode, SettingsService},
+        user::UserService,
+    },
+    utils::{
+        encode_access_token, encode_refresh_token, hash_password,
+        password::check_password_not_breached, verify_password,
+    },
 };
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
-    PaginatorTrait, QueryFilter, TransactionTrait,
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    EntityTrait, PaginatorTrait, QueryFilter, TransactionTrait,
 };
 
 pub struct AuthService {
     db: DatabaseConnection,
     config: AuthConfig,
+    cache: Option<CacheService>,
 }
 
 impl AuthService {
@@ -20,18 +32,42 @@ impl AuthService {
         Self {
             db,
             config: AuthConfig::from_env(),
+            cache: None,
         }
     }
 
-    /// Register a new user and send verification email.
+    /// Needed wherever sessions are invalidated, to drop the auth state
+    /// `auth_middleware` caches.
+    pub fn with_cache(mut self, cache: Option<CacheService>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Register a new user and send verification email. Enforces the
+    /// registration settings: closed or invite-only registration and the
+    /// email domain lists.
     /// Returns (user_model, access_token, refresh_token).
     pub async fn register(
         &self,
         username: &str,
         email: &str,
         password: &str,
+        locale: &str,
+        invite_code: Option<&str>,
         email_service: &EmailService,
     ) -> AppResult<(crate::models::UserModel, String, String)> {
+        let settings = SettingsService::new(self.db.clone()).registration().await?;
+        if settings.mode == RegistrationMode::Closed {
+            return Err(AppError::Forbidden);
+        }
+        settings.check_email(email)?;
+        let invite_code = invite_code.map(str::trim).filter(|c| !c.is_empty());
+        if settings.mode == RegistrationMode::InviteOnly && invite_code.is_none() {
+            return Err(AppError::Validation(
+                "An invite code is required to register".to_string(),
+            ));
+        }
+
         // Check if username or email already exists
         if self.user_exists(username, email).await? {
             return Err(AppError::Validation(
@@ -39,6 +75,7 @@ impl AuthService {
             ));
         }
 
+        check_password_not_breached(password).await?;
         let password_hash = hash_password(password)?;
         let now = chrono::Utc::now().naive_utc();
         let (email_verified, verification_token, verification_expires) =
@@ -50,28 +87,40 @@ impl AuthService {
                 (true, None, None)
             };
 
+        // Only invite-only registration spends a code
+        let txn = self.db.begin().await?;
+        let invite = match invite_code {
+            Some(code) if settings.mode == RegistrationMode::InviteOnly => {
+                Some(InviteService::redeem(&txn, code).await?)
+            }
+            _ => None,
+        };
+
         let new_user = crate::models::user::ActiveModel {
             username: sea_orm::ActiveValue::Set(username.to_string()),
             email: sea_orm::ActiveValue::Set(email.to_string()),
             password_hash: sea_orm::ActiveValue::Set(password_hash),
             karma: sea_orm::ActiveValue::Set(0),
             role: sea_orm::ActiveValue::Set("user".to_string()),
+            locale: sea_orm::ActiveValue::Set(locale.to_string()),
             email_verified: sea_orm::ActiveValue::Set(email_verified),
             email_verification_token: sea_orm::ActiveValue::Set(verification_token.clone()),
             email_verification_expires: sea_orm::ActiveValue::Set(verification_expires),
             created_at: sea_orm::ActiveValue::Set(now),
             updated_at: sea_orm::ActiveValue::Set(now),
+            invite_code_id: sea_orm::ActiveValue::Set(invite.map(|i| i.id)),
             ..Default::default()
         };
 
-        let user = new_user.insert(&self.db).await?;
+        let user = new_user.insert(&txn).await?;
+        txn.commit().await?;
         let (access_token, refresh_token) = self.issue_tokens_for_user(user.id).await?;
 
         if self.config.require_email_verification {
             if let Some(token) = verification_token {
                 // Send verification email (non-fatal)
                 if let Err(e) = email_service
-                    .send_verification_email(&user.email, &token)
+                    .send_verification_email(&self.db, &user, &token)
                     .await
                 {
                     tracing::warn!("Failed to send verification email: {e}");
@@ -101,11 +150,37 @@ impl AuthService {
             return Err(AppError::Unauthorized);
         }
 
+        // Logging in is how a deactivated account comes back
+        if user.deactivated_at.is_some() {
+            UserService::new(self.db.clone())
+                .reactivate(user.id)
+                .await?;
+        }
+
         let (access_token, refresh_token) = self.issue_tokens_for_user(user.id).await?;
 
         Ok((user, access_token, refresh_token))
     }
 
+    /// Confirm a signed-in user's password before a sensitive change.
+    pub async fn check_password(&self, user_id: i32, password: &str) -> AppResult<()> {
+        let user = self.get_user_by_id(user_id).await?;
+        if !verify_password(password, &user.password_hash)? {
+            return Err(AppError::Validation("Password is incorrect".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Deactivate the user's own account after checking their password, and
+    /// end their sessions. Logging in again reactivates it.
+    pub async fn deactivate(&self, user_id: i32, password: &str) -> AppResult<()> {
+        self.check_password(user_id, password).await?;
+        UserService::new(self.db.clone())
+            .deactivate(user_id)
+            .await?;
+        self.invalidate_user_sessions(user_id).await
+    }
+
     pub async fn rotate_refresh_token(
         &self,
         user_id: i32,
@@ -150,6 +225,21 @@ impl AuthService {
         Ok(())
     }
 
+    /// Invalidate every session of a user: bump `token_version` so existing
+    /// access tokens fail `auth_middleware`, and revoke all refresh tokens.
+    pub async fn invalidate_user_sessions(&self, user_id: i32) -> AppResult<()> {
+        User::update_many()
+            .col_expr(
+                crate::models::user::Column::TokenVersion,
+                Expr::col(crate::models::user::Column::TokenVersion).add(1),
+            )
+            .filter(crate::models::user::Column::Id.eq(user_id))
+            .exec(&self.db)
+            .await?;
+        invalidate_cached_auth(self.cache.as_ref(), user_id).await;
+        self.revoke_all_user_refresh_tokens(user_id).await
+    }
+
     /// Get user by ID
     pub async fn get_user_by_id(&self, id: i32) -> AppResult<crate::models::UserModel> {
         let user = User::find_by_id(id)
@@ -161,6 +251,9 @@ impl AuthService {
 
     /// Check if user exists by username or email
     async fn user_exists(&self, username: &str, email: &str) -> AppResult<bool> {
+        if username == DELETED_USERNAME {
+            return Ok(true);
+        }
         let count = User::find()
             .filter(
                 sea_orm::Condition::any()
@@ -169,8 +262,13 @@ impl AuthService {
             )
             .count(&self.db)
             .await?;
+        // Former names stay with their owners
+        let former = crate::models::UsernameHistory::find()
+            .filter(crate::models::username_history::Column::Username.eq(username))
+            .count(&self.db)
+            .await?;
 
-        Ok(count > 0)
+        Ok(count + former > 0)
     }
 
     /// Find user by username
@@ -197,13 +295,14 @@ impl AuthService {
                 "Current password is incorrect".to_string(),
             ));
         }
+        check_password_not_breached(new_password).await?;
         let new_hash = hash_password(new_password)?;
         let now = chrono::Utc::now().naive_utc();
         let mut active: crate::models::user::ActiveModel = user.into();
         active.password_hash = sea_orm::ActiveValue::Set(new_hash);
         active.updated_at = sea_orm::ActiveValue::Set(now);
         active.update(&self.db).await?;
-        self.revoke_all_user_refresh_tokens(user_id).await?;
+        self.invalidate_user_sessions(user_id).await?;
         Ok(())
     }
 
@@ -247,14 +346,16 @@ impl AuthService {
         let now = chrono::Utc::now().naive_utc();
         let expires = now + chrono::Duration::hours(24);
 
-        let email = user.email.clone();
         let mut active: crate::models::user::ActiveModel = user.into();
         active.email_verification_token = sea_orm::ActiveValue::Set(Some(token.clone()));
         active.email_verification_expires = sea_orm::ActiveValue::Set(Some(expires));
         active.updated_at = sea_orm::ActiveValue::Set(now);
-        active.update(&self.db).await?;
+        let user = active.update(&self.db).await?;
 
-        if let Err(e) = email_service.send_verification_email(&email, &token).await {
+        if let Err(e) = email_service
+            .send_verification_email(&self.db, &user, &token)
+            .await
+        {
             tracing::warn!("Failed to send verification email: {e}");
         }
 
@@ -281,15 +382,14 @@ impl AuthService {
         let now = chrono::Utc::now().naive_utc();
         let expires = now + chrono::Duration::hours(1);
 
-        let user_email = user.email.clone();
         let mut active: crate::models::user::ActiveModel = user.into();
         active.password_reset_token = sea_orm::ActiveValue::Set(Some(token.clone()));
         active.password_reset_expires = sea_orm::ActiveValue::Set(Some(expires));
         active.updated_at = sea_orm::ActiveValue::Set(now);
-        active.update(&self.db).await?;
+        let user = active.update(&self.db).await?;
 
         if let Err(e) = email_service
-            .send_password_reset_email(&user_email, &token)
+            .send_password_reset_email(&self.db, &user, &token)
             .await
         {
             tracing::warn!("Failed to send password reset email: {e}");
@@ -313,6 +413,7 @@ impl AuthService {
             }
         }
 
+        check_password_not_breached(new_password).await?;
         let new_hash = hash_password(new_password)?;
         let now = chrono::Utc::now().naive_utc();
         let mut active: crate::models::user::ActiveModel = user.into();
@@ -321,7 +422,7 @@ impl AuthService {
         active.password_reset_expires = sea_orm::ActiveValue::Set(None);
         active.updated_at = sea_orm::ActiveValue::Set(now);
         active.update(&self.db).await?;
-        self.revoke_all_user_refresh_tokens(user_id).await?;
+        self.invalidate_user_sessions(user_id).await?;
 
         Ok(())
     }
@@ -335,9 +436,14 @@ impl AuthService {
         conn: &C,
         user_id: i32,
     ) -> AppResult<(String, String)> {
+        let token_version = User::find_by_id(user_id)
+            .one(conn)
+            .await?
+            .ok_or(AppError::Unauthorized)?
+            .token_version;
         let user_id_str = user_id.to_string();
-        let access_token = encode_access_token(&user_id_str)?;
-        let refresh_token = encode_refresh_token(&user_id_str)?;
+        let access_token = encode_access_token(&user_id_str, token_version)?;
+        let refresh_token = encode_refresh_token(&user_id_str, token_version)?;
         self.persist_refresh_token(conn, user_id, &refresh_token)
             .await?;
         Ok((access_token, refresh_token))
@@ -406,6 +512,6 @@ mod tests {
     #[test]
     fn validate_password_too_short() {
         let password = "pass";
-        assert!(!(password.len() >= 8));
+        assert!(password.len() < 8);
     }
 }
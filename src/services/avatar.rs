@@ -0,0 +1,282 @@
+//! Avatars are square thumbnails cut from an uploaded image.
+//!
+//! The uploaded image is kept as the avatar's original, so the user can pick
+//! a different crop later without uploading it again. A crop is either a
+//! rectangle in the original's pixels or a focal point with a zoom; without
+//! one the largest centred square is used.
+
+use crate::error::{AppError, AppResult};
+use crate::services::upload::{
+    decode_image, encode_image, image_error, SavedUpload, TempUpload, UploadConfig, UploadService,
+};
+use crate::services::user::UserService;
+use image::imageops::FilterType;
+use image::ImageFormat;
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::fs;
+use utoipa::ToSchema;
+
+/// Width and height of avatar thumbnails, in pixels.
+pub const AVATAR_SIZE: u32 = 256;
+
+const MAX_ZOOM: f32 = 10.0;
+
+/// Which part of the original becomes the avatar.
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+pub struct AvatarCrop {
+    /// Left edge of the crop rectangle, in pixels of the original
+    pub crop_x: Option<u32>,
+    /// Top edge of the crop rectangle
+    pub crop_y: Option<u32>,
+    pub crop_width: Option<u32>,
+    pub crop_height: Option<u32>,
+    /// Point to centre the avatar on, as a fraction of the width (default 0.5)
+    pub focus_x: Option<f32>,
+    /// Point to centre the avatar on, as a fraction of the height (default 0.5)
+    pub focus_y: Option<f32>,
+    /// 1 (default) takes the largest square around the focal point, 2 one
+    /// half as wide, up to 10
+    pub zoom: Option<f32>,
+}
+
+impl AvatarCrop {
+    /// Read the crop from `multipart/form-data` text fields of the same names.
+    pub fn from_fields(fields: &HashMap<String, String>) -> AppResult<Self> {
+        fn parse<T: std::str::FromStr>(
+            fields: &HashMap<String, String>,
+            name: &str,
+        ) -> AppResult<Option<T>> {
+            fields
+                .get(name)
+                .map(|v| {
+                    v.trim()
+                        .parse()
+                        .map_err(|_| AppError::Validation(format!("Invalid {}", name)))
+                })
+                .transpose()
+        }
+
+        Ok(Self {
+            crop_x: parse(fields, "crop_x")?,
+            crop_y: parse(fields, "crop_y")?,
+            crop_width: parse(fields, "crop_width")?,
+            crop_height: parse(fields, "crop_height")?,
+            focus_x: parse(fields, "focus_x")?,
+            focus_y: parse(fields, "focus_y")?,
+            zoom: parse(fields, "zoom")?,
+        })
+    }
+
+    /// The region `(x, y, width, height)` to cut from a `width`×`height`
+    /// image.
+    fn region(&self, width: u32, height: u32) -> AppResult<(u32, u32, u32, u32)> {
+        let rect = [self.crop_x, self.crop_y, self.crop_width, self.crop_height];
+        if rect.iter().any(Option::is_some) {
+            let [Some(x), Some(y), Some(w), Some(h)] = rect else {
+                return Err(AppError::Validation(
+                    "crop_x, crop_y, crop_width and crop_height go together".to_string(),
+                ));
+            };
+            if w == 0 || h == 0 || x.saturating_add(w) > width || y.saturating_add(h) > height {
+                return Err(AppError::Validation(format!(
+                    "Crop rectangle must lie within the {}x{} image",
+                    width, height
+                )));
+            }
+            return Ok((x, y, w, h));
+        }
+
+        let focus_x = self.focus_x.unwrap_or(0.5);
+        let focus_y = self.focus_y.unwrap_or(0.5);
+        let zoom = self.zoom.unwrap_or(1.0);
+        if !(0.0..=1.0).contains(&focus_x) || !(0.0..=1.0).contains(&focus_y) {
+            return Err(AppError::Validation(
+                "focus_x and focus_y must be between 0 and 1".to_string(),
+            ));
+        }
+        if !(1.0..=MAX_ZOOM).contains(&zoom) {
+            return Err(AppError::Validation(format!(
+                "zoom must be between 1 and {}",
+                MAX_ZOOM
+            )));
+        }
+
+        let side = ((width.min(height) as f32 / zoom).round() as u32).max(1);
+        // Centred on the focal point, moved back inside the image if needed
+        let place = |focus: f32, length: u32| {
+            let start = (focus * length as f32 - side as f32 / 2.0).round();
+            (start.max(0.0) as u32).min(length - side)
+        };
+        Ok((place(focus_x, width), place(focus_y, height), side, side))
+    }
+}
+
+pub struct AvatarService {
+    db: DatabaseConnection,
+}
+
+impl AvatarService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Save an uploaded image as the user's avatar original and cut the
+    /// avatar from it. Returns the avatar and the original.
+    pub async fn upload(
+        &self,
+        config: &UploadConfig,
+        user_id: i32,
+        upload: TempUpload,
+        content_type: &str,
+        crop: AvatarCrop,
+    ) -> AppResult<(SavedUpload, SavedUpload)> {
+        let original =
+            UploadService::save_upload(config, upload, content_type, "avatars/originals").await?;
+        UploadService::record(&self.db, config, &original, content_type, user_id).await?;
+        let avatar = self.crop(config, user_id, &original.url, crop).await?;
+        Ok((avatar, original))
+    }
+
+    /// Cut a new avatar from the user's current original. Returns the
+    /// avatar and the original's URL.
+    pub async fn recrop(
+        &self,
+        config: &UploadConfig,
+        user_id: i32,
+        crop: AvatarCrop,
+    ) -> AppResult<(SavedUpload, String)> {
+        let user = UserService::new(self.db.clone()).get_by_id(user_id).await?;
+        let original = user.avatar_original_url.ok_or_else(|| {
+            AppError::Validation("No uploaded avatar to crop, upload one first".to_string())
+        })?;
+        let avatar = self.crop(config, user_id, &original, crop).await?;
+        Ok((avatar, original))
+    }
+
+    /// Make the avatar from the original at `original_url` and set it.
+    async fn crop(
+        &self,
+        config: &UploadConfig,
+        user_id: i32,
+        original_url: &str,
+        crop: AvatarCrop,
+    ) -> AppResult<SavedUpload> {
+        let path = original_url
+            .strip_prefix("/uploads/")
+            .map(|path| Path::new(&config.upload_dir).join(path))
+            .ok_or(AppError::NotFound)?;
+        let format = ImageFormat::from_path(&path)
+            .map_err(|_| AppError::Validation("Unsupported file type".to_string()))?;
+        let data = fs::read(&path).await.map_err(|_| AppError::NotFound)?;
+
+        let (thumbnail, format) =
+            tokio::task::spawn_blocking(move || thumbnail(&data, format, crop))
+                .await
+                .map_err(|e| AppError::Internal(e.into()))??;
+        let content_type = format.to_mime_type();
+        let avatar =
+            UploadService::save_generated(config, &thumbnail, content_type, "avatars").await?;
+        UploadService::record(&self.db, config, &avatar, content_type, user_id).await?;
+
+        UserService::new(self.db.clone())
+            .update_avatar_url(user_id, &avatar.url, original_url)
+            .await?;
+        Ok(avatar)
+    }
+}
+
+/// Cut and scale the avatar. GIFs become still PNGs of their first frame.
+fn thumbnail(
+    data: &[u8],
+    format: ImageFormat,
+    crop: AvatarCrop,
+) -> AppResult<(Vec<u8>, ImageFormat)> {
+    let image = decode_image(data, format).map_err(image_error)?;
+    let (x, y, width, height) = crop.region(image.width(), image.height())?;
+    let avatar = image.crop_imm(x, y, width, height).resize_to_fill(
+        AVATAR_SIZE,
+        AVATAR_SIZE,
+        FilterType::Lanczos3,
+    );
+
+    let format = match format {
+        ImageFormat::Gif => ImageFormat::Png,
+        format => format,
+    };
+    let mut out = Vec::new();
+    encode_image(&avatar, format, &mut out).map_err(image_error)?;
+    Ok((out, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn focus(focus_x: f32, focus_y: f32, zoom: f32) -> AvatarCrop {
+        AvatarCrop {
+            focus_x: Some(focus_x),
+            focus_y: Some(focus_y),
+            zoom: Some(zoom),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_crop_is_the_centred_square() {
+        let crop = AvatarCrop::default();
+        assert_eq!(crop.region(400, 300).unwrap(), (50, 0, 300, 300));
+        assert_eq!(crop.region(300, 400).unwrap(), (0, 50, 300, 300));
+    }
+
+    #[test]
+    fn focal_point_and_zoom_stay_inside_the_image() {
+        assert_eq!(
+            focus(0.5, 0.5, 2.0).region(400, 300).unwrap(),
+            (125, 75, 150, 150)
+        );
+        assert_eq!(
+            focus(0.0, 1.0, 2.0).region(400, 300).unwrap(),
+            (0, 150, 150, 150)
+        );
+        assert!(focus(1.5, 0.5, 1.0).region(400, 300).is_err());
+        assert!(focus(0.5, 0.5, 0.5).region(400, 300).is_err());
+    }
+
+    #[test]
+    fn crop_rectangle_must_be_complete_and_inside() {
+        let rect = |x, y, w, h| AvatarCrop {
+            crop_x: Some(x),
+            crop_y: Some(y),
+            crop_width: Some(w),
+            crop_height: Some(h),
+            ..Default::default()
+        };
+        assert_eq!(
+            rect(10, 20, 100, 80).region(400, 300).unwrap(),
+            (10, 20, 100, 80)
+        );
+        assert!(rect(350, 0, 100, 100).region(400, 300).is_err());
+        assert!(rect(0, 0, 0, 100).region(400, 300).is_err());
+        let partial = AvatarCrop {
+            crop_x: Some(0),
+            ..Default::default()
+        };
+        assert!(partial.region(400, 300).is_err());
+    }
+
+    #[test]
+    fn fields_are_parsed() {
+        let fields: HashMap<String, String> = [("crop_x", "5"), ("zoom", " 1.5 ")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let crop = AvatarCrop::from_fields(&fields).unwrap();
+        assert_eq!((crop.crop_x, crop.zoom), (Some(5), Some(1.5)));
+
+        let fields = HashMap::from([("focus_x".to_string(), "left".to_string())]);
+        assert!(AvatarCrop::from_fields(&fields).is_err());
+    }
+}
@@ -0,0 +1,366 @@
+//! Badges on user profiles.
+//!
+//! Built-in badges are awarded by [`BadgeAwarder`], which checks their rules
+//! for every user in the background and creates the badges themselves the
+//! first time it runs. Admins can add custom badges and award them by hand;
+//! those awards notify the user.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{badge, user_badge, Badge, BadgeModel, User, UserBadge, UserBadgeModel};
+use crate::services::notification::NotificationService;
+use crate::utils::{shutdown, sql};
+use crate::websocket::hub::NotificationHub;
+use chrono::NaiveDateTime;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    FromQueryResult, QueryFilter, QueryOrder, Set, Value,
+};
+use std::time::Duration;
+
+/// How often the rules of built-in badges are checked.
+const AWARD_INTERVAL: Duration = Duration::from_secs(600);
+
+/// What earns a built-in badge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BadgeRule {
+    /// Published a post that isn't hidden
+    FirstPost,
+    /// Received 100 upvotes across posts and comments
+    Upvotes100,
+    /// Registered at least a year ago
+    OneYearMember,
+}
+
+impl BadgeRule {
+    pub const ALL: [BadgeRule; 3] = [
+        BadgeRule::FirstPost,
+        BadgeRule::Upvotes100,
+        BadgeRule::OneYearMember,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BadgeRule::FirstPost => "first_post",
+            BadgeRule::Upvotes100 => "upvotes_100",
+            BadgeRule::OneYearMember => "one_year_member",
+        }
+    }
+
+    fn slug(&self) -> &'static str {
+        match self {
+            BadgeRule::FirstPost => "first-post",
+            BadgeRule::Upvotes100 => "upvotes-100",
+            BadgeRule::OneYearMember => "one-year-member",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            BadgeRule::FirstPost => "First Post",
+            BadgeRule::Upvotes100 => "Well Liked",
+            BadgeRule::OneYearMember => "One Year Club",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            BadgeRule::FirstPost => "Published a first post",
+            BadgeRule::Upvotes100 => "Received 100 upvotes on posts and comments",
+            BadgeRule::OneYearMember => "Member for a year",
+        }
+    }
+
+    /// SQL condition on user `u` for having earned the badge, with its
+    /// values from `$2` on.
+    fn condition(&self, now: NaiveDateTime) -> (&'static str, Vec<Value>) {
+        match self {
+            BadgeRule::FirstPost => (
+                "EXISTS (SELECT 1 FROM posts p WHERE p.user_id = u.id AND p.is_hidden = FALSE)",
+                vec![],
+            ),
+            BadgeRule::Upvotes100 => (
+                "(SELECT COALESCE(SUM(upvotes), 0) FROM posts WHERE user_id = u.id) \
+                    + (SELECT COALESCE(SUM(upvotes), 0) FROM comments WHERE user_id = u.id) >= 100",
+                vec![],
+            ),
+            BadgeRule::OneYearMember => (
+                "u.created_at <= $2",
+                vec![(now - chrono::Duration::days(365)).into()],
+            ),
+        }
+    }
+}
+
+#[derive(Debug, FromQueryResult)]
+struct UserId {
+    id: i32,
+}
+
+/// A badge held by a user.
+pub struct HeldBadge {
+    pub badge: BadgeModel,
+    pub awarded_at: NaiveDateTime,
+}
+
+pub struct BadgeService {
+    db: DatabaseConnection,
+}
+
+impl BadgeService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// All badges, built-in first.
+    pub async fn list(&self) -> AppResult<Vec<BadgeModel>> {
+        let badges = Badge::find()
+            .order_by_asc(badge::Column::Rule.is_null())
+            .order_by_asc(badge::Column::Id)
+            .all(&self.db)
+            .await?;
+        Ok(badges)
+    }
+
+    /// The user's badges, oldest award first.
+    pub async fn held_by(&self, user_id: i32) -> AppResult<Vec<HeldBadge>> {
+        let held = UserBadge::find()
+            .filter(user_badge::Column::UserId.eq(user_id))
+            .find_also_related(Badge)
+            .order_by_asc(user_badge::Column::CreatedAt)
+            .order_by_asc(user_badge::Column::Id)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .filter_map(|(award, badge)| {
+                Some(HeldBadge {
+                    badge: badge?,
+                    awarded_at: award.created_at,
+                })
+            })
+            .collect();
+        Ok(held)
+    }
+
+    pub async fn create_custom(
+        &self,
+        slug: &str,
+        name: &str,
+        description: &str,
+        icon_url: Option<String>,
+    ) -> AppResult<BadgeModel> {
+        let taken = Badge::find()
+            .filter(badge::Column::Slug.eq(slug))
+            .one(&self.db)
+            .await?;
+        // Built-in badges are only created once they're first awarded
+        if taken.is_some() || BadgeRule::ALL.iter().any(|rule| rule.slug() == slug) {
+            return Err(AppError::Conflict(format!(
+                "A badge with slug {} already exists",
+                slug
+            )));
+        }
+        let badge = badge::ActiveModel {
+            slug: Set(slug.to_string()),
+            name: Set(name.to_string()),
+            description: Set(description.to_string()),
+            icon_url: Set(icon_url),
+            rule: Set(None),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(badge)
+    }
+
+    /// Delete a custom badge, taking it from everyone who holds it.
+    pub async fn delete_custom(&self, badge_id: i32) -> AppResult<()> {
+        let badge = self.custom(badge_id).await?;
+        Badge::delete_by_id(badge.id).exec(&self.db).await?;
+        Ok(())
+    }
+
+    /// Award a custom badge by hand and notify the user. Returns `None` if
+    /// they already hold it.
+    pub async fn award(
+        &self,
+        hub: NotificationHub,
+        user_id: i32,
+        badge_id: i32,
+        admin_id: i32,
+    ) -> AppResult<Option<UserBadgeModel>> {
+        let badge = self.custom(badge_id).await?;
+        User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let held = UserBadge::find()
+            .filter(user_badge::Column::UserId.eq(user_id))
+            .filter(user_badge::Column::BadgeId.eq(badge.id))
+            .one(&self.db)
+            .await?;
+        if held.is_some() {
+            return Ok(None);
+        }
+
+        let award = user_badge::ActiveModel {
+            user_id: Set(user_id),
+            badge_id: Set(badge.id),
+            awarded_by: Set(Some(admin_id)),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        NotificationService::new(self.db.clone(), hub)
+            .notify(
+                user_id,
+                admin_id,
+                "badge_awarded",
+                "badge",
+                badge.id,
+                &format!("You were awarded the {} badge", badge.name),
+            )
+            .await?;
+        Ok(Some(award))
+    }
+
+    /// Take a custom badge back from a user.
+    pub async fn revoke(&self, user_id: i32, badge_id: i32) -> AppResult<()> {
+        let badge = self.custom(badge_id).await?;
+        let result = UserBadge::delete_many()
+            .filter(user_badge::Column::UserId.eq(user_id))
+            .filter(user_badge::Column::BadgeId.eq(badge.id))
+            .exec(&self.db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// A badge admins manage; built-in badges only come and go by their
+    /// rules.
+    async fn custom(&self, badge_id: i32) -> AppResult<BadgeModel> {
+        let badge = Badge::find_by_id(badge_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if badge.rule.is_some() {
+            return Err(AppError::Validation(
+                "Built-in badges are awarded automatically".to_string(),
+            ));
+        }
+        Ok(badge)
+    }
+}
+
+/// Awards built-in badges to users who meet their rules.
+#[derive(Clone)]
+pub struct BadgeAwarder {
+    db: DatabaseConnection,
+}
+
+impl BadgeAwarder {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Check the rules every award interval until shutdown.
+    pub fn spawn_scheduler(&self) -> tokio::task::JoinHandle<()> {
+        let awarder = self.clone();
+        shutdown::spawn(async move {
+            let mut ticker = tokio::time::interval(AWARD_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown::requested() => break,
+                }
+                if let Err(e) = awarder.award_all().await {
+                    tracing::warn!("Failed to award badges: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Award every built-in badge its rule calls for. Returns how many
+    /// were awarded.
+    pub async fn award_all(&self) -> AppResult<u64> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut awarded = 0;
+        for rule in BadgeRule::ALL {
+            let badge = self.builtin(rule, now).await?;
+            awarded += self.award_rule(rule, &badge, now).await?;
+        }
+        Ok(awarded)
+    }
+
+    /// The badge of a rule, created the first time it is needed.
+    async fn builtin(&self, rule: BadgeRule, now: NaiveDateTime) -> AppResult<BadgeModel> {
+        let existing = Badge::find()
+            .filter(badge::Column::Rule.eq(rule.as_str()))
+            .one(&self.db)
+            .await?;
+        if let Some(badge) = existing {
+            return Ok(badge);
+        }
+        let badge = badge::ActiveModel {
+            slug: Set(rule.slug().to_string()),
+            name: Set(rule.name().to_string()),
+            description: Set(rule.description().to_string()),
+            icon_url: Set(None),
+            rule: Set(Some(rule.as_str().to_string())),
+            created_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(badge)
+    }
+
+    async fn award_rule(
+        &self,
+        rule: BadgeRule,
+        badge: &BadgeModel,
+        now: NaiveDateTime,
+    ) -> AppResult<u64> {
+        let (condition, extra) = rule.condition(now);
+        let mut values: Vec<Value> = vec![badge.id.into()];
+        values.extend(extra);
+        let earned = UserId::find_by_statement(sql::statement(
+            self.db.get_database_backend(),
+            format!(
+                "SELECT u.id FROM users u \
+                    WHERE u.role <> 'banned' AND {} \
+                    AND NOT EXISTS (SELECT 1 FROM user_badges ub \
+                        WHERE ub.user_id = u.id AND ub.badge_id = $1)",
+                condition
+            ),
+            values,
+        ))
+        .all(&self.db)
+        .await?;
+        if earned.is_empty() {
+            return Ok(0);
+        }
+
+        let awards = earned.iter().map(|u| user_badge::ActiveModel {
+            user_id: Set(u.id),
+            badge_id: Set(badge.id),
+            awarded_by: Set(None),
+            created_at: Set(now),
+            ..Default::default()
+        });
+        // Another instance may be awarding the same badges
+        let result = UserBadge::insert_many(awards)
+            .on_conflict(
+                OnConflict::columns([user_badge::Column::UserId, user_badge::Column::BadgeId])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(&self.db)
+            .await?;
+        Ok(result)
+    }
+}
@@ -1,10 +1,11 @@
 use crate::{
     error::{AppError, AppResult},
     models::{bookmark, post, Bookmark, Post, PostModel},
+    utils::sql,
 };
 use sea_orm::{
     ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
-    QueryOrder, Statement,
+    QueryOrder,
 };
 use std::collections::HashMap;
 
@@ -23,13 +24,20 @@ impl BookmarkService {
             .await?
             .ok_or(AppError::NotFound)?;
 
+        let backend = self.db.get_database_backend();
         self.db
-            .execute(Statement::from_sql_and_values(
-                sea_orm::DatabaseBackend::Postgres,
-                "INSERT INTO bookmarks (user_id, post_id, created_at)
-                 VALUES ($1, $2, NOW())
-                 ON CONFLICT (user_id, post_id) DO NOTHING",
-                vec![user_id.into(), post_id.into()],
+            .execute(sql::statement(
+                backend,
+                format!(
+                    "INSERT INTO bookmarks (user_id, post_id, created_at)
+                     VALUES ($1, $2, $3) {}",
+                    sql::upsert(backend, &["user_id", "post_id"], &[])
+                ),
+                vec![
+                    user_id.into(),
+                    post_id.into(),
+                    chrono::Utc::now().naive_utc().into(),
+                ],
             ))
             .await?;
         Ok(true)
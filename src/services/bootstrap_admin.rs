@@ -1,7 +1,9 @@
 use crate::error::AppResult;
-use crate::models::User;
+use crate::models::{User, UserModel};
 use crate::utils::hash_password;
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+};
 use std::env;
 
 #[derive(Debug, Clone)]
@@ -12,7 +14,29 @@ pub struct BootstrapAdminConfig {
 }
 
 impl BootstrapAdminConfig {
-    pub fn from_env() -> Option<Self> {
+    /// `ADMIN_EMAIL`/`ADMIN_PASSWORD` (with optional `ADMIN_USERNAME`,
+    /// default `admin`), or the older `BOOTSTRAP_ADMIN_*` group. Errors
+    /// only when `ADMIN_EMAIL` is set without a usable password.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let non_empty = |name: &str| env::var(name).ok().filter(|v| !v.trim().is_empty());
+
+        if let Some(email) = non_empty("ADMIN_EMAIL") {
+            let password = non_empty("ADMIN_PASSWORD")
+                .ok_or_else(|| anyhow::anyhow!("ADMIN_PASSWORD must be set with ADMIN_EMAIL"))?;
+            if password.chars().count() < 8 {
+                anyhow::bail!("ADMIN_PASSWORD must be at least 8 characters");
+            }
+            return Ok(Some(Self {
+                username: non_empty("ADMIN_USERNAME").unwrap_or_else(|| "admin".to_string()),
+                email: email.trim().to_string(),
+                password,
+            }));
+        }
+
+        Ok(Self::legacy_from_env())
+    }
+
+    fn legacy_from_env() -> Option<Self> {
         let enabled = env::var("BOOTSTRAP_ADMIN_ENABLED")
             .ok()
             .map(|v| v.trim().to_ascii_lowercase())
@@ -33,30 +57,43 @@ impl BootstrapAdminConfig {
 
 /// 启动时自动创建/提升管理员：
 /// - 若库中已存在任意 admin：不做任何事
-/// - 否则若配置的 email/username 已存在：提升为 admin
+/// - 否则若配置的 email（其次 username）已存在：提升为 admin，不修改密码
 /// - 否则创建一个新的 admin（email_verified=true）
 pub async fn ensure_bootstrap_admin(db: &DatabaseConnection) -> AppResult<()> {
-    let Some(cfg) = BootstrapAdminConfig::from_env() else {
+    let Some(cfg) = BootstrapAdminConfig::from_env()? else {
         return Ok(());
     };
+    ensure_admin(db, &cfg).await?;
+    Ok(())
+}
 
-    let admin_exists = User::find()
+/// 按上述规则确保存在管理员，返回该管理员，以及是否为新创建的账号
+pub async fn ensure_admin(
+    db: &DatabaseConnection,
+    cfg: &BootstrapAdminConfig,
+) -> AppResult<(UserModel, bool)> {
+    let admin = User::find()
         .filter(crate::models::user::Column::Role.eq("admin"))
+        .order_by_asc(crate::models::user::Column::Id)
         .one(db)
-        .await?
-        .is_some();
-    if admin_exists {
-        return Ok(());
+        .await?;
+    if let Some(admin) = admin {
+        return Ok((admin, false));
     }
 
-    let existing = User::find()
-        .filter(
-            sea_orm::Condition::any()
-                .add(crate::models::user::Column::Email.eq(cfg.email.clone()))
-                .add(crate::models::user::Column::Username.eq(cfg.username.clone())),
-        )
+    let existing = match User::find()
+        .filter(crate::models::user::Column::Email.eq(cfg.email.clone()))
         .one(db)
-        .await?;
+        .await?
+    {
+        Some(user) => Some(user),
+        None => {
+            User::find()
+                .filter(crate::models::user::Column::Username.eq(cfg.username.clone()))
+                .one(db)
+                .await?
+        }
+    };
 
     let now = chrono::Utc::now().naive_utc();
 
@@ -64,15 +101,16 @@ pub async fn ensure_bootstrap_admin(db: &DatabaseConnection) -> AppResult<()> {
         let mut active: crate::models::user::ActiveModel = user.into();
         active.role = sea_orm::ActiveValue::Set("admin".to_string());
         active.updated_at = sea_orm::ActiveValue::Set(now);
-        active.update(db).await?;
-        return Ok(());
+        let user = active.update(db).await?;
+        tracing::info!("Promoted '{}' to admin", user.username);
+        return Ok((user, false));
     }
 
     let password_hash = hash_password(&cfg.password)?;
 
     let new_user = crate::models::user::ActiveModel {
-        username: sea_orm::ActiveValue::Set(cfg.username),
-        email: sea_orm::ActiveValue::Set(cfg.email),
+        username: sea_orm::ActiveValue::Set(cfg.username.clone()),
+        email: sea_orm::ActiveValue::Set(cfg.email.clone()),
         password_hash: sea_orm::ActiveValue::Set(password_hash),
         karma: sea_orm::ActiveValue::Set(0),
         role: sea_orm::ActiveValue::Set("admin".to_string()),
@@ -84,6 +122,7 @@ pub async fn ensure_bootstrap_admin(db: &DatabaseConnection) -> AppResult<()> {
         ..Default::default()
     };
 
-    new_user.insert(db).await?;
-    Ok(())
+    let user = new_user.insert(db).await?;
+    tracing::info!("Created admin '{}' <{}>", user.username, user.email);
+    Ok((user, true))
 }
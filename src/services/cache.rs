@@ -1,40 +1,161 @@
+use dashmap::DashMap;
 use redis::aio::ConnectionManager;
 use redis::AsyncCommands;
 use serde::{de::DeserializeOwned, Serialize};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Hit/miss counts for one key namespace (the key up to its first `:`).
+#[derive(Debug, Clone)]
+pub struct CacheMetric {
+    pub namespace: String,
+    pub hits: u64,
+    pub misses: u64,
+}
 
 #[derive(Clone)]
 pub struct CacheService {
     redis: ConnectionManager,
+    metrics: Arc<DashMap<String, Counters>>,
+    /// Prepended to every key, so tenants sharing one Redis don't see each
+    /// other's entries
+    prefix: Option<Arc<str>>,
 }
 
 impl CacheService {
     pub fn new(redis: ConnectionManager) -> Self {
-        Self { redis }
+        Self {
+            redis,
+            metrics: Arc::new(DashMap::new()),
+            prefix: None,
+        }
+    }
+
+    /// Same connection, with every key under `prefix` and its own metrics.
+    pub fn with_prefix(&self, prefix: impl Into<Arc<str>>) -> Self {
+        Self {
+            redis: self.redis.clone(),
+            metrics: Arc::new(DashMap::new()),
+            prefix: Some(prefix.into()),
+        }
+    }
+
+    fn key<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        match &self.prefix {
+            Some(prefix) => Cow::Owned(format!("{prefix}{key}")),
+            None => Cow::Borrowed(key),
+        }
     }
 
     pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
         let mut conn = self.redis.clone();
-        let result: Option<String> = conn.get(key).await.ok()?;
-        result.and_then(|s| serde_json::from_str(&s).ok())
+        let result: Option<String> = conn.get(self.key(key).as_ref()).await.ok().flatten();
+        let value = result.and_then(|s| serde_json::from_str(&s).ok());
+        self.record(key, value.is_some());
+        value
+    }
+
+    /// Hit/miss counts of `get` since startup, by namespace.
+    pub fn metrics(&self) -> Vec<CacheMetric> {
+        let mut metrics: Vec<CacheMetric> = self
+            .metrics
+            .iter()
+            .map(|e| CacheMetric {
+                namespace: e.key().clone(),
+                hits: e.hits.load(Ordering::Relaxed),
+                misses: e.misses.load(Ordering::Relaxed),
+            })
+            .collect();
+        metrics.sort_by(|a, b| a.namespace.cmp(&b.namespace));
+        metrics
+    }
+
+    fn record(&self, key: &str, hit: bool) {
+        let namespace = key.split(':').next().unwrap_or(key);
+        let counters = self.metrics.entry(namespace.to_string()).or_default();
+        if hit {
+            counters.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            counters.misses.fetch_add(1, Ordering::Relaxed);
+        }
     }
 
     pub async fn set<T: Serialize>(&self, key: &str, value: &T, ttl_secs: u64) {
         let mut conn = self.redis.clone();
         if let Ok(json) = serde_json::to_string(value) {
-            let _: Result<(), _> = conn.set_ex(key, json, ttl_secs).await;
+            let _: Result<(), _> = conn.set_ex(self.key(key).as_ref(), json, ttl_secs).await;
         }
     }
 
+    /// Set `key` only if it does not exist yet. Returns whether it was set,
+    /// or `None` if Redis could not be reached.
+    pub async fn set_nx(&self, key: &str, ttl_secs: u64) -> Option<bool> {
+        let mut conn = self.redis.clone();
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(self.key(key).as_ref())
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs)
+            .query_async(&mut conn)
+            .await
+            .ok()?;
+        Some(reply.is_some())
+    }
+
+    /// Add `delta` to a hash field, returning the new value, or `None` if
+    /// Redis could not be reached.
+    pub async fn hincr(&self, key: &str, field: &str, delta: i64) -> Option<i64> {
+        let mut conn = self.redis.clone();
+        conn.hincr(self.key(key).as_ref(), field, delta).await.ok()
+    }
+
+    pub async fn hget_i64(&self, key: &str, field: &str) -> Option<i64> {
+        let mut conn = self.redis.clone();
+        conn.hget(self.key(key).as_ref(), field).await.ok()?
+    }
+
+    /// Atomically take every field of a hash, leaving it empty. Returns `None`
+    /// if Redis could not be reached.
+    pub async fn take_hash(&self, key: &str) -> Option<Vec<(String, i64)>> {
+        let mut conn = self.redis.clone();
+        let key = self.key(key);
+        let (fields, _): (Vec<(String, i64)>, i64) = redis::pipe()
+            .atomic()
+            .hgetall(key.as_ref())
+            .del(key.as_ref())
+            .query_async(&mut conn)
+            .await
+            .ok()?;
+        Some(fields)
+    }
+
+    /// Round-trip to Redis, for readiness checks.
+    pub async fn ping(&self) -> Result<(), String> {
+        let mut conn = self.redis.clone();
+        redis::cmd("PING")
+            .query_async::<String>(&mut conn)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
     pub async fn invalidate(&self, key: &str) {
         let mut conn = self.redis.clone();
-        let _: Result<(), _> = conn.del(key).await;
+        let _: Result<(), _> = conn.del(self.key(key).as_ref()).await;
     }
 
-    #[allow(dead_code)]
     pub async fn invalidate_pattern(&self, pattern: &str) {
         let mut conn = self.redis.clone();
         if let Ok(keys) = redis::cmd("KEYS")
-            .arg(pattern)
+            .arg(self.key(pattern).as_ref())
             .query_async::<Vec<String>>(&mut conn)
             .await
         {
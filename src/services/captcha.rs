@@ -0,0 +1,350 @@
+//! CAPTCHA verification with hCaptcha or Cloudflare Turnstile.
+//!
+//! `CAPTCHA_REQUIRED_ACTIONS` picks the actions that need a solved CAPTCHA,
+//! alongside `POW_REQUIRED_ACTIONS`: an action in one list needs that check,
+//! in both lists needs both. Login only asks for a CAPTCHA once a username
+//! has failed `login_after_failures` times within the failure window.
+//! Failures are counted in process, so with several instances each counts
+//! its own.
+
+use crate::error::{AppError, AppResult};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptchaProvider {
+    HCaptcha,
+    Turnstile,
+}
+
+impl CaptchaProvider {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "hcaptcha" => Some(CaptchaProvider::HCaptcha),
+            "turnstile" => Some(CaptchaProvider::Turnstile),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaptchaProvider::HCaptcha => "hcaptcha",
+            CaptchaProvider::Turnstile => "turnstile",
+        }
+    }
+
+    fn default_verify_url(&self) -> &'static str {
+        match self {
+            CaptchaProvider::HCaptcha => "https://api.hcaptcha.com/siteverify",
+            CaptchaProvider::Turnstile => {
+                "https://challenges.cloudflare.com/turnstile/v0/siteverify"
+            }
+        }
+    }
+}
+
+/// Actions that can be guarded by a CAPTCHA; the PoW actions plus login.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptchaAction {
+    Vote,
+    Register,
+    CreatePost,
+    Report,
+    Login,
+}
+
+impl CaptchaAction {
+    pub const ALL: [CaptchaAction; 5] = [
+        CaptchaAction::Vote,
+        CaptchaAction::Register,
+        CaptchaAction::CreatePost,
+        CaptchaAction::Report,
+        CaptchaAction::Login,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaptchaAction::Vote => "vote",
+            CaptchaAction::Register => "register",
+            CaptchaAction::CreatePost => "create_post",
+            CaptchaAction::Report => "report",
+            CaptchaAction::Login => "login",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptchaConfig {
+    /// `None` turns CAPTCHA checks off whatever the required actions
+    pub provider: Option<CaptchaProvider>,
+    pub secret: String,
+    /// Handed to clients so they can render the widget
+    pub site_key: Option<String>,
+    pub verify_url: String,
+    pub required_actions: Vec<CaptchaAction>,
+    /// Failed logins for a username before login needs a CAPTCHA; 0 always
+    /// needs one
+    pub login_after_failures: u32,
+    pub login_failure_window_seconds: i64,
+    pub timeout: Duration,
+}
+
+impl CaptchaConfig {
+    pub fn from_env() -> Self {
+        let secret = std::env::var("CAPTCHA_SECRET").unwrap_or_default();
+        let provider = std::env::var("CAPTCHA_PROVIDER")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .and_then(|v| {
+                let provider = CaptchaProvider::parse(&v);
+                if provider.is_none() {
+                    tracing::warn!("Unknown CAPTCHA_PROVIDER '{}', CAPTCHA disabled", v);
+                }
+                provider
+            })
+            .filter(|_| {
+                if secret.trim().is_empty() {
+                    tracing::warn!("CAPTCHA_SECRET is not set, CAPTCHA disabled");
+                }
+                !secret.trim().is_empty()
+            });
+
+        let site_key = std::env::var("CAPTCHA_SITE_KEY")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+
+        let verify_url = std::env::var("CAPTCHA_VERIFY_URL")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .or_else(|| provider.map(|p| p.default_verify_url().to_string()))
+            .unwrap_or_default();
+
+        let required_actions = std::env::var("CAPTCHA_REQUIRED_ACTIONS")
+            .map(|raw| parse_actions(&raw))
+            .unwrap_or_default();
+
+        let login_after_failures: u32 = std::env::var("CAPTCHA_LOGIN_AFTER_FAILURES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3);
+
+        let login_failure_window_seconds: i64 =
+            std::env::var("CAPTCHA_LOGIN_FAILURE_WINDOW_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|w| *w > 0)
+                .unwrap_or(900);
+
+        let timeout_ms: u64 = std::env::var("CAPTCHA_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5000);
+
+        Self {
+            provider,
+            secret,
+            site_key,
+            verify_url,
+            required_actions,
+            login_after_failures,
+            login_failure_window_seconds,
+            timeout: Duration::from_millis(timeout_ms),
+        }
+    }
+
+    pub fn requires(&self, action: CaptchaAction) -> bool {
+        self.provider.is_some() && self.required_actions.contains(&action)
+    }
+
+    /// Whether a login as `username` must come with a CAPTCHA now.
+    pub fn login_needs_captcha(&self, username: &str) -> bool {
+        self.requires(CaptchaAction::Login)
+            && login_failures().count(
+                username,
+                self.login_failure_window_seconds,
+                chrono::Utc::now().timestamp(),
+            ) >= self.login_after_failures
+    }
+}
+
+/// Comma-separated action names; unknown names are skipped with a warning.
+fn parse_actions(raw: &str) -> Vec<CaptchaAction> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| {
+            let action = CaptchaAction::parse(name);
+            if action.is_none() {
+                tracing::warn!("Unknown action '{}' in CAPTCHA_REQUIRED_ACTIONS", name);
+            }
+            action
+        })
+        .collect()
+}
+
+/// Check the CAPTCHA response a request carries when `action` requires
+/// one. An unreachable provider fails the request rather than letting it
+/// through unchecked.
+pub async fn require_captcha(
+    cfg: &CaptchaConfig,
+    action: CaptchaAction,
+    captcha_token: Option<&str>,
+) -> AppResult<()> {
+    if !cfg.requires(action) {
+        return Ok(());
+    }
+    let token = captcha_token
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .ok_or_else(|| {
+            AppError::Validation(format!("captcha_token is required for {}", action.as_str()))
+        })?;
+
+    if verify(cfg, token).await? {
+        Ok(())
+    } else {
+        Err(AppError::Validation(
+            "Captcha verification failed".to_string(),
+        ))
+    }
+}
+
+/// Both providers answer `siteverify` the same way.
+#[derive(Debug, Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+    #[serde(default, rename = "error-codes")]
+    error_codes: Vec<String>,
+}
+
+async fn verify(cfg: &CaptchaConfig, token: &str) -> AppResult<bool> {
+    let mut form = vec![("secret", cfg.secret.as_str()), ("response", token)];
+    if let Some(site_key) = &cfg.site_key {
+        form.push(("sitekey", site_key));
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(cfg.timeout)
+        .build()
+        .map_err(|e| AppError::Internal(e.into()))?;
+    let response: SiteVerifyResponse = client
+        .post(&cfg.verify_url)
+        .form(&form)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| AppError::Internal(anyhow::anyhow!("Captcha verification failed: {e}")))?
+        .json()
+        .await
+        .map_err(|e| AppError::Internal(anyhow::anyhow!("Invalid captcha response: {e}")))?;
+
+    if !response.success {
+        tracing::debug!(errors = ?response.error_codes, "Captcha rejected");
+    }
+    Ok(response.success)
+}
+
+/// Recent failed logins per lowercased username, as `(count, first_at)`.
+#[derive(Default)]
+struct LoginFailures {
+    failures: Mutex<HashMap<String, (u32, i64)>>,
+}
+
+fn login_failures() -> &'static LoginFailures {
+    static FAILURES: OnceLock<LoginFailures> = OnceLock::new();
+    FAILURES.get_or_init(LoginFailures::default)
+}
+
+impl LoginFailures {
+    fn record(&self, username: &str, window_seconds: i64, now: i64) {
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures.retain(|_, (_, first_at)| *first_at > now - window_seconds);
+        let entry = failures
+            .entry(username.trim().to_lowercase())
+            .or_insert((0, now));
+        entry.0 += 1;
+    }
+
+    fn count(&self, username: &str, window_seconds: i64, now: i64) -> u32 {
+        let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures
+            .get(&username.trim().to_lowercase())
+            .filter(|(_, first_at)| *first_at > now - window_seconds)
+            .map_or(0, |(count, _)| *count)
+    }
+
+    fn clear(&self, username: &str) {
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures.remove(&username.trim().to_lowercase());
+    }
+}
+
+pub fn record_login_failure(cfg: &CaptchaConfig, username: &str) {
+    login_failures().record(
+        username,
+        cfg.login_failure_window_seconds,
+        chrono::Utc::now().timestamp(),
+    );
+}
+
+pub fn clear_login_failures(username: &str) {
+    login_failures().clear(username);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_actions() {
+        assert_eq!(
+            parse_actions("register, login,nope"),
+            vec![CaptchaAction::Register, CaptchaAction::Login]
+        );
+        assert_eq!(
+            CaptchaProvider::parse("Turnstile"),
+            Some(CaptchaProvider::Turnstile)
+        );
+    }
+
+    #[test]
+    fn test_login_failures_expire() {
+        let failures = LoginFailures::default();
+        failures.record("Alice", 60, 100);
+        failures.record("alice", 60, 110);
+        assert_eq!(failures.count("ALICE", 60, 120), 2);
+        assert_eq!(failures.count("alice", 60, 161), 0);
+        assert_eq!(failures.count("bob", 60, 120), 0);
+
+        failures.clear("alice");
+        assert_eq!(failures.count("alice", 60, 120), 0);
+    }
+
+    #[test]
+    fn test_requires_needs_a_provider() {
+        let cfg = CaptchaConfig {
+            provider: None,
+            secret: String::new(),
+            site_key: None,
+            verify_url: String::new(),
+            required_actions: vec![CaptchaAction::Register],
+            login_after_failures: 3,
+            login_failure_window_seconds: 900,
+            timeout: Duration::from_secs(1),
+        };
+        assert!(!cfg.requires(CaptchaAction::Register));
+        let cfg = CaptchaConfig {
+            provider: Some(CaptchaProvider::HCaptcha),
+            ..cfg
+        };
+        assert!(cfg.requires(CaptchaAction::Register));
+        assert!(!cfg.requires(CaptchaAction::Report));
+    }
+}
@@ -1,11 +1,30 @@
 use crate::{
+    config::comment::{CommentConfig, DepthOverflow},
     error::{AppError, AppResult},
-    models::{comment, Comment, CommentModel},
+    models::{
+        comment, comment_revision, Comment, CommentModel, CommentRevision, CommentRevisionModel,
+        Post,
+    },
+    utils::markdown::summarize_markdown,
 };
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, TransactionTrait,
 };
 
+/// A single comment together with the thread around it.
+pub struct CommentContext {
+    pub comment: CommentModel,
+    /// Ancestors ordered from the root down to the direct parent
+    pub ancestors: Vec<CommentModel>,
+    /// Oldest direct replies, at most the requested limit
+    pub children: Vec<CommentModel>,
+    pub children_total: u64,
+}
+
+/// Longest excerpt of a quoted comment kept with a reply, in characters.
+const QUOTE_EXCERPT_CHARS: usize = 300;
+
 pub struct CommentService {
     db: DatabaseConnection,
 }
@@ -25,17 +44,38 @@ impl CommentService {
         Ok(comments)
     }
 
+    /// Add a comment, optionally quoting another comment on the post, whose
+    /// excerpt is saved with it.
     pub async fn create(
         &self,
         post_id: i32,
         user_id: i32,
         parent_id: Option<i32>,
         content: &str,
+        quoted_comment_id: Option<i32>,
     ) -> AppResult<CommentModel> {
-        if let Some(pid) = parent_id {
-            self.validate_parent(pid, post_id).await?;
+        let post = Post::find_by_id(post_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if post.is_locked {
+            return Err(AppError::Validation("Post is locked".to_string()));
+        }
+        if post.closed_by_author {
+            return Err(AppError::Validation(
+                "Post is closed to new comments by its author".to_string(),
+            ));
         }
 
+        let parent_id = match parent_id {
+            Some(pid) => self.resolve_parent(pid, post_id).await?,
+            None => None,
+        };
+        let quote_excerpt = match quoted_comment_id {
+            Some(id) => Some(self.quote(id, post_id).await?),
+            None => None,
+        };
+
         let now = chrono::Utc::now().naive_utc();
 
         let new_comment = comment::ActiveModel {
@@ -47,6 +87,8 @@ impl CommentService {
             downvotes: sea_orm::ActiveValue::Set(0),
             created_at: sea_orm::ActiveValue::Set(now),
             updated_at: sea_orm::ActiveValue::Set(now),
+            quoted_comment_id: sea_orm::ActiveValue::Set(quoted_comment_id),
+            quote_excerpt: sea_orm::ActiveValue::Set(quote_excerpt),
             ..Default::default()
         };
 
@@ -60,24 +102,97 @@ impl CommentService {
             return Err(AppError::Forbidden);
         }
 
+        if existing.content == content {
+            return Ok(existing);
+        }
+
         let now = chrono::Utc::now().naive_utc();
+        let txn = self.db.begin().await?;
+
+        // Keep the previous content so moderators can review what was changed.
+        comment_revision::ActiveModel {
+            comment_id: sea_orm::ActiveValue::Set(existing.id),
+            editor_id: sea_orm::ActiveValue::Set(user_id),
+            content: sea_orm::ActiveValue::Set(existing.content.clone()),
+            created_at: sea_orm::ActiveValue::Set(now),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
 
         let mut active: comment::ActiveModel = existing.into();
         active.content = sea_orm::ActiveValue::Set(content.to_string());
         active.updated_at = sea_orm::ActiveValue::Set(now);
+        active.edited_at = sea_orm::ActiveValue::Set(Some(now));
 
-        let updated = active.update(&self.db).await?;
+        let updated = active.update(&txn).await?;
+        txn.commit().await?;
         Ok(updated)
     }
 
-    pub async fn delete(&self, id: i32, user_id: i32) -> AppResult<()> {
+    /// Previous versions of a comment, newest first.
+    pub async fn list_revisions(&self, comment_id: i32) -> AppResult<Vec<CommentRevisionModel>> {
+        self.get_by_id(comment_id).await?;
+
+        let revisions = CommentRevision::find()
+            .filter(comment_revision::Column::CommentId.eq(comment_id))
+            .order_by_desc(comment_revision::Column::CreatedAt)
+            .order_by_desc(comment_revision::Column::Id)
+            .all(&self.db)
+            .await?;
+        Ok(revisions)
+    }
+
+    /// Delete the user's comment, returning it.
+    pub async fn delete(&self, id: i32, user_id: i32) -> AppResult<CommentModel> {
         let existing = self.get_by_id(id).await?;
         if existing.user_id != user_id {
             return Err(AppError::Forbidden);
         }
 
         Comment::delete_by_id(id).exec(&self.db).await?;
-        Ok(())
+        Ok(existing)
+    }
+
+    /// Load a visible comment with its ancestors and first few replies, for
+    /// permalinks from notifications.
+    pub async fn get_with_context(
+        &self,
+        id: i32,
+        children_limit: u64,
+    ) -> AppResult<CommentContext> {
+        let comment = self.get_by_id(id).await?;
+        if comment.is_hidden {
+            return Err(AppError::NotFound);
+        }
+
+        let mut ancestors = Vec::new();
+        let mut current_id = comment.parent_id;
+        while let Some(pid) = current_id {
+            let Some(parent) = Comment::find_by_id(pid).one(&self.db).await? else {
+                break;
+            };
+            current_id = parent.parent_id;
+            ancestors.push(parent);
+        }
+        ancestors.reverse();
+
+        let replies = Comment::find()
+            .filter(comment::Column::ParentId.eq(id))
+            .filter(comment::Column::IsHidden.eq(false));
+        let children_total = replies.clone().count(&self.db).await?;
+        let children = replies
+            .order_by_asc(comment::Column::CreatedAt)
+            .limit(children_limit)
+            .all(&self.db)
+            .await?;
+
+        Ok(CommentContext {
+            comment,
+            ancestors,
+            children,
+            children_total,
+        })
     }
 
     pub async fn get_by_id(&self, id: i32) -> AppResult<CommentModel> {
@@ -87,7 +202,9 @@ impl CommentService {
             .ok_or(AppError::NotFound)
     }
 
-    async fn validate_parent(&self, parent_id: i32, post_id: i32) -> AppResult<()> {
+    /// Check the parent belongs to the post and apply `MAX_COMMENT_DEPTH`,
+    /// returning the parent the new reply should actually be attached to.
+    async fn resolve_parent(&self, parent_id: i32, post_id: i32) -> AppResult<Option<i32>> {
         let parent = Comment::find_by_id(parent_id)
             .one(&self.db)
             .await?
@@ -99,18 +216,29 @@ impl CommentService {
             ));
         }
 
-        let depth = self.get_comment_depth(parent_id).await?;
-        if depth >= 10 {
+        let chain = self.ancestor_chain(parent_id).await?;
+        effective_parent(&chain, &CommentConfig::from_env())
+    }
+
+    /// Excerpt of a visible comment on the post, for quoting it.
+    async fn quote(&self, comment_id: i32, post_id: i32) -> AppResult<String> {
+        let quoted = Comment::find_by_id(comment_id)
+            .one(&self.db)
+            .await?
+            .filter(|c| !c.is_hidden)
+            .ok_or(AppError::Validation("Quoted comment not found".to_string()))?;
+
+        if quoted.post_id != post_id {
             return Err(AppError::Validation(
-                "Maximum comment nesting depth reached".to_string(),
+                "Quoted comment belongs to a different post".to_string(),
             ));
         }
-
-        Ok(())
+        Ok(summarize_markdown(&quoted.content, QUOTE_EXCERPT_CHARS).excerpt)
     }
 
-    async fn get_comment_depth(&self, comment_id: i32) -> AppResult<u32> {
-        let mut depth = 0u32;
+    /// IDs from `comment_id` up to its top-level ancestor.
+    async fn ancestor_chain(&self, comment_id: i32) -> AppResult<Vec<i32>> {
+        let mut chain = Vec::new();
         let mut current_id = Some(comment_id);
 
         while let Some(id) = current_id {
@@ -118,19 +246,74 @@ impl CommentService {
                 .one(&self.db)
                 .await?
                 .ok_or(AppError::NotFound)?;
+            chain.push(comment.id);
             current_id = comment.parent_id;
-            depth += 1;
-            if depth > 10 {
+            // Guard against cycles in corrupted data
+            if chain.len() > 1000 {
                 break;
             }
         }
 
-        Ok(depth)
+        Ok(chain)
+    }
+}
+
+/// Pick the parent for a reply to `chain[0]`, where `chain` runs from the
+/// requested parent up to the top-level comment.
+fn effective_parent(chain: &[i32], config: &CommentConfig) -> AppResult<Option<i32>> {
+    let max_depth = config.max_depth.max(1) as usize;
+    let Some(&parent) = chain.first() else {
+        return Ok(None);
+    };
+    // The reply would sit one level below its parent
+    if chain.len() < max_depth {
+        return Ok(Some(parent));
+    }
+
+    match config.overflow {
+        DepthOverflow::Reject => Err(AppError::Validation(
+            "Maximum comment nesting depth reached".to_string(),
+        )),
+        DepthOverflow::Reparent if max_depth == 1 => Ok(None),
+        DepthOverflow::Reparent => Ok(Some(chain[chain.len() - (max_depth - 1)])),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{effective_parent, CommentConfig, DepthOverflow};
+
+    fn config(max_depth: u32, overflow: DepthOverflow) -> CommentConfig {
+        CommentConfig {
+            max_depth,
+            overflow,
+        }
+    }
+
+    #[test]
+    fn test_reply_within_limit_keeps_parent() {
+        let cfg = config(3, DepthOverflow::Reject);
+        assert_eq!(effective_parent(&[5, 4], &cfg).unwrap(), Some(5));
+    }
+
+    #[test]
+    fn test_reply_beyond_limit_is_rejected() {
+        let cfg = config(3, DepthOverflow::Reject);
+        assert!(effective_parent(&[6, 5, 4], &cfg).is_err());
+    }
+
+    #[test]
+    fn test_reply_beyond_limit_is_reparented() {
+        let cfg = config(3, DepthOverflow::Reparent);
+        // Parent at level 3 -> attach to its parent at level 2
+        assert_eq!(effective_parent(&[6, 5, 4], &cfg).unwrap(), Some(5));
+        // Chains deeper than the limit (e.g. after lowering it) climb further
+        assert_eq!(effective_parent(&[8, 7, 6, 5, 4], &cfg).unwrap(), Some(5));
+        // With a single level every reply becomes top-level
+        let flat = config(1, DepthOverflow::Reparent);
+        assert_eq!(effective_parent(&[4], &flat).unwrap(), None);
+    }
+
     const MAX_DEPTH: u32 = 10;
 
     fn is_depth_exceeded(depth: u32) -> bool {
@@ -0,0 +1,333 @@
+//! Opt-in email digests.
+//!
+//! Users choose `daily` or `weekly` digests in their profile. Periods end at
+//! midnight (Monday midnight for weekly digests) in the user's timezone. The
+//! scheduler checks every `DIGEST_CHECK_INTERVAL_SECONDS` for users whose
+//! last period ended without a digest, picks the top posts by people they follow since
+//! their previous digest, and queues the email. Users who unsubscribed from
+//! the `digest` category are skipped. Each period is recorded in
+//! `email_digests`; its unique `(user_id, period_end)` key stops a digest
+//! from going out twice, even with several instances running the scheduler.
+
+use crate::config::email::DigestConfig;
+use crate::models::{
+    email_digest, email_opt_out, follow, post, user, EmailDigest, EmailOptOut, Follow, Forum, Post,
+    User, UserModel,
+};
+use crate::services::email::EmailService;
+use crate::services::email_preferences::EmailCategory;
+use crate::services::email_template::DigestItem;
+use crate::utils::shutdown;
+use anyhow::Result;
+use chrono::{Datelike, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+use sea_orm::sea_query::{Expr, OnConflict, Query};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, Order, QueryFilter,
+    QueryOrder, QuerySelect, Set, SqlErr,
+};
+use std::collections::HashMap;
+
+/// Values accepted for `users.digest_frequency`.
+pub const DIGEST_FREQUENCIES: &[&str] = &["off", "daily", "weekly"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestFrequency {
+    Daily,
+    Weekly,
+}
+
+impl DigestFrequency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestFrequency::Daily => "daily",
+            DigestFrequency::Weekly => "weekly",
+        }
+    }
+
+    fn length(&self) -> chrono::Duration {
+        match self {
+            DigestFrequency::Daily => chrono::Duration::days(1),
+            DigestFrequency::Weekly => chrono::Duration::weeks(1),
+        }
+    }
+
+    /// End of the most recent complete period, in UTC: local midnight for
+    /// daily digests, local Monday midnight for weekly ones.
+    fn period_end(&self, now: NaiveDateTime, timezone: Tz) -> NaiveDateTime {
+        let local = timezone.from_utc_datetime(&now).naive_local();
+        let midnight = local.date().and_time(chrono::NaiveTime::MIN);
+        let boundary = match self {
+            DigestFrequency::Daily => midnight,
+            DigestFrequency::Weekly => {
+                let days = local.weekday().num_days_from_monday() as i64;
+                midnight - chrono::Duration::days(days)
+            }
+        };
+        match timezone.from_local_datetime(&boundary).earliest() {
+            Some(end) => end.naive_utc(),
+            // Midnight skipped by a DST change: use the current offset
+            None => {
+                let offset = timezone.offset_from_utc_datetime(&now).fix();
+                boundary - chrono::Duration::seconds(offset.local_minus_utc() as i64)
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DigestService {
+    db: DatabaseConnection,
+    email: EmailService,
+    config: DigestConfig,
+}
+
+impl DigestService {
+    pub fn new(db: DatabaseConnection, email: EmailService, config: DigestConfig) -> Self {
+        Self { db, email, config }
+    }
+
+    /// Queue every digest due at `now`, returning how many were queued.
+    pub async fn send_due(&self, now: NaiveDateTime) -> Result<usize> {
+        let mut queued = 0;
+        for frequency in [DigestFrequency::Daily, DigestFrequency::Weekly] {
+            // Every user's current period ends within the last period
+            // length, so a digest since then is for the current period
+            let window_start = now - frequency.length();
+            let users = User::find()
+                .filter(user::Column::DigestFrequency.eq(frequency.as_str()))
+                .filter(user::Column::EmailVerified.eq(true))
+                .filter(user::Column::Role.ne("banned"))
+                .filter(user::Column::DeactivatedAt.is_null())
+                .filter(user::Column::EmailUndeliverable.is_null())
+                .filter(
+                    user::Column::Id.not_in_subquery(
+                        Query::select()
+                            .column(email_digest::Column::UserId)
+                            .from(EmailDigest)
+                            .and_where(Expr::col(email_digest::Column::PeriodEnd).gt(window_start))
+                            .to_owned(),
+                    ),
+                )
+                .filter(
+                    user::Column::Id.not_in_subquery(
+                        Query::select()
+                            .column(email_opt_out::Column::UserId)
+                            .from(EmailOptOut)
+                            .and_where(
+                                Expr::col(email_opt_out::Column::Category)
+                                    .eq(EmailCategory::Digest.as_str()),
+                            )
+                            .to_owned(),
+                    ),
+                )
+                .all(&self.db)
+                .await?;
+
+            for user in users {
+                let timezone = user.timezone.parse().unwrap_or(Tz::UTC);
+                let period_end = frequency.period_end(now, timezone);
+                if self.send_to(&user, frequency, period_end).await? {
+                    queued += 1;
+                }
+            }
+        }
+        Ok(queued)
+    }
+
+    /// Send digests as their periods end, until shutdown.
+    pub fn spawn_scheduler(&self) -> tokio::task::JoinHandle<()> {
+        let service = self.clone();
+        shutdown::spawn(async move {
+            let mut ticker = tokio::time::interval(service.config.check_interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown::requested() => break,
+                }
+                if let Err(e) = service.send_due(chrono::Utc::now().naive_utc()).await {
+                    tracing::warn!("Failed to send email digests: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Record the period and queue the user's digest. Returns false if
+    /// another worker already took the period or there was nothing to send.
+    async fn send_to(
+        &self,
+        user: &UserModel,
+        frequency: DigestFrequency,
+        period_end: NaiveDateTime,
+    ) -> Result<bool> {
+        // Continue from the previous digest, but never reach back further
+        // than one period (e.g. after digests were switched off for a while)
+        let previous = EmailDigest::find()
+            .filter(email_digest::Column::UserId.eq(user.id))
+            .order_by_desc(email_digest::Column::PeriodEnd)
+            .one(&self.db)
+            .await?;
+        let earliest = period_end - frequency.length();
+        let period_start = previous
+            .map(|d| d.period_end.max(earliest))
+            .unwrap_or(earliest);
+
+        let posts = self.top_posts(user.id, period_start, period_end).await?;
+
+        let record = email_digest::ActiveModel {
+            user_id: Set(user.id),
+            frequency: Set(frequency.as_str().to_string()),
+            period_start: Set(period_start),
+            period_end: Set(period_end),
+            post_count: Set(posts.len() as i32),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        let mut insert = EmailDigest::insert(record);
+        // MySQL reports an ignored duplicate as an affected row, so there
+        // the unique key violation itself says the period is taken
+        if self.db.get_database_backend() != DbBackend::MySql {
+            insert = insert.on_conflict(
+                OnConflict::columns([
+                    email_digest::Column::UserId,
+                    email_digest::Column::PeriodEnd,
+                ])
+                .do_nothing()
+                .to_owned(),
+            );
+        }
+        let claimed = match insert.exec_without_returning(&self.db).await {
+            Ok(rows) => rows,
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => 0,
+            Err(e) => return Err(e.into()),
+        };
+        if claimed == 0 || posts.is_empty() {
+            return Ok(false);
+        }
+
+        let daily = frequency == DigestFrequency::Daily;
+        if let Err(e) = self.email.send_digest(&self.db, user, daily, &posts).await {
+            // Release the period so the next check tries again
+            EmailDigest::delete_many()
+                .filter(email_digest::Column::UserId.eq(user.id))
+                .filter(email_digest::Column::PeriodEnd.eq(period_end))
+                .exec(&self.db)
+                .await?;
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// Highest-scoring visible posts by users `user_id` follows, created in
+    /// `(start, end]`.
+    async fn top_posts(
+        &self,
+        user_id: i32,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<DigestItem>> {
+        let following: Vec<i32> = Follow::find()
+            .select_only()
+            .column(follow::Column::FollowingId)
+            .filter(follow::Column::FollowerId.eq(user_id))
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        if following.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let posts = Post::find()
+            .filter(post::Column::UserId.is_in(following))
+            .filter(post::Column::IsHidden.eq(false))
+            .filter(post::Column::CreatedAt.gt(start))
+            .filter(post::Column::CreatedAt.lte(end))
+            .order_by(
+                Expr::col(post::Column::Upvotes).sub(Expr::col(post::Column::Downvotes)),
+                Order::Desc,
+            )
+            .order_by_desc(post::Column::CreatedAt)
+            .limit(self.config.max_posts)
+            .all(&self.db)
+            .await?;
+        if posts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let authors: HashMap<i32, String> = User::find()
+            .filter(user::Column::Id.is_in(posts.iter().map(|p| p.user_id)))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|u| (u.id, u.shown_name().to_string()))
+            .collect();
+        let forums: HashMap<i32, String> = Forum::find()
+            .filter(crate::models::forum::Column::Id.is_in(posts.iter().map(|p| p.forum_id)))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|f| (f.id, f.name))
+            .collect();
+
+        Ok(posts
+            .into_iter()
+            .map(|p| DigestItem {
+                post_id: p.id,
+                author: authors.get(&p.user_id).cloned().unwrap_or_default(),
+                forum: forums.get(&p.forum_id).cloned().unwrap_or_default(),
+                score: p.upvotes - p.downvotes,
+                title: p.title,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_period_end_is_last_boundary() {
+        // 2026-10-17 is a Saturday
+        let now = at("2026-10-17 15:30:00");
+        assert_eq!(
+            DigestFrequency::Daily.period_end(now, Tz::UTC),
+            at("2026-10-17 00:00:00")
+        );
+        assert_eq!(
+            DigestFrequency::Weekly.period_end(now, Tz::UTC),
+            at("2026-10-12 00:00:00")
+        );
+        // A boundary is its own period end
+        let monday = at("2026-10-12 00:00:00");
+        assert_eq!(DigestFrequency::Weekly.period_end(monday, Tz::UTC), monday);
+    }
+
+    #[test]
+    fn test_period_end_is_local_midnight() {
+        // 23:30 on Sunday the 11th in Shanghai (UTC+8) is the 12th locally
+        let now = at("2026-10-11 23:30:00");
+        assert_eq!(
+            DigestFrequency::Daily.period_end(now, Tz::Asia__Shanghai),
+            at("2026-10-11 16:00:00")
+        );
+        assert_eq!(
+            DigestFrequency::Weekly.period_end(now, Tz::Asia__Shanghai),
+            at("2026-10-11 16:00:00")
+        );
+        // Still the 11th in New York (UTC-4 in October)
+        assert_eq!(
+            DigestFrequency::Daily.period_end(now, Tz::America__New_York),
+            at("2026-10-11 04:00:00")
+        );
+        assert_eq!(
+            DigestFrequency::Weekly.period_end(now, Tz::America__New_York),
+            at("2026-10-05 04:00:00")
+        );
+    }
+}
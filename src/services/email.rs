@@ -1,115 +1,499 @@
-use crate::config::email::EmailConfig;
+//! Outgoing email.
+//!
+//! Emails are written to the `email_outbox` table and delivered by a
+//! background worker, so a slow or unavailable provider never blocks a
+//! request and a failed send is retried with exponential backoff instead of
+//! being lost. Emails still failing after `EMAIL_MAX_ATTEMPTS` are marked
+//! `failed` and listed for admins.
+
+use crate::config::email::{EmailConfig, EmailQueueConfig};
+use crate::models::{email_outbox, EmailOutbox, EmailOutboxModel, UserModel};
+use crate::services::email_preferences::{EmailCategory, EmailPreferenceService, ALL_OPTIONAL};
+use crate::services::email_provider::{
+    build_provider, EmailProvider, OutgoingEmail, RateLimiter, SendError,
+};
+use crate::services::email_template::{self, DigestItem, Locale, RenderedEmail};
+use crate::services::quiet_hours::QuietHours;
+use crate::utils::url_sign::{unsubscribe_token, url_signing_secret};
+use crate::utils::{shutdown, sql};
 use anyhow::Result;
-use lettre::{
-    message::{header::ContentType, Mailbox},
-    transport::smtp::authentication::Credentials,
-    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+use lettre::message::Mailbox;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait,
+    QueryFilter, QueryOrder, Set, TransactionTrait,
 };
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Emails claimed per worker pass.
+const BATCH_SIZE: u64 = 20;
+/// Longest wait between retries.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+/// A `sending` email not updated for this long belongs to a worker that
+/// died mid-send, and is picked up again.
+const STALE_SENDING: Duration = Duration::from_secs(300);
+
+/// A configured provider and the address it sends from.
+#[derive(Clone)]
+struct Sender {
+    provider: Arc<dyn EmailProvider>,
+    from: Mailbox,
+    limiter: Arc<RateLimiter>,
+}
 
 #[derive(Clone)]
 pub struct EmailService {
-    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
-    from_address: Option<String>,
+    sender: Option<Sender>,
     frontend_url: String,
+    /// Public origin of this API, for links back to it in emails
+    public_api_url: String,
+    queue: EmailQueueConfig,
+    /// Wakes the worker when an email is queued
+    wake: Arc<Notify>,
 }
 
 impl EmailService {
-    /// Build from environment variables. If SMTP is not configured, email
+    /// Build from environment variables. If no provider is configured, email
     /// sending is silently skipped (graceful degradation).
     pub fn from_env() -> Self {
-        match EmailConfig::from_env() {
-            Some(cfg) => {
-                let creds = Credentials::new(cfg.smtp_username.clone(), cfg.smtp_password.clone());
-                let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&cfg.smtp_host)
-                    .map(|builder| builder.port(cfg.smtp_port).credentials(creds).build());
-
-                match transport {
-                    Ok(t) => Self {
-                        transport: Some(t),
-                        from_address: Some(cfg.from_address),
-                        frontend_url: cfg.frontend_url,
-                    },
-                    Err(e) => {
-                        tracing::warn!("Failed to build SMTP transport: {e}");
-                        Self {
-                            transport: None,
-                            from_address: None,
-                            frontend_url: cfg.frontend_url,
-                        }
-                    }
-                }
-            }
-            None => {
-                let frontend_url = std::env::var("FRONTEND_URL")
-                    .unwrap_or_else(|_| "http://localhost:3000".to_string());
-                Self {
-                    transport: None,
-                    from_address: None,
-                    frontend_url,
+        let frontend_url =
+            std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+        // Same origin as the frontend unless the API is served elsewhere
+        let public_api_url = std::env::var("PUBLIC_API_URL")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| frontend_url.clone());
+        Self {
+            sender: EmailConfig::from_env().and_then(|cfg| match build_sender(&cfg) {
+                Ok(sender) => Some(sender),
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to set up {} email provider: {e}",
+                        cfg.provider.name()
+                    );
+                    None
                 }
-            }
+            }),
+            frontend_url,
+            public_api_url: public_api_url.trim_end_matches('/').to_string(),
+            queue: EmailQueueConfig::from_env(),
+            wake: Arc::new(Notify::new()),
         }
     }
 
-    /// Returns true if SMTP is configured and available.
+    /// Same provider, with links pointing at `host` and a queue worker of its
+    /// own, for a tenant served there. Keeps the scheme of `FRONTEND_URL`.
+    pub fn for_host(&self, host: &str) -> Self {
+        let scheme = self
+            .frontend_url
+            .split_once("://")
+            .map_or("https", |(scheme, _)| scheme);
+        let origin = format!("{scheme}://{host}");
+        Self {
+            sender: self.sender.clone(),
+            frontend_url: origin.clone(),
+            public_api_url: origin,
+            queue: self.queue,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Returns true if an email provider is configured and available.
     pub fn is_configured(&self) -> bool {
-        self.transport.is_some()
+        self.sender.is_some()
     }
 
-    /// Send a verification email. Silently succeeds if SMTP is not configured.
-    pub async fn send_verification_email(&self, to: &str, token: &str) -> Result<()> {
-        let link = format!("{}/verify-email?token={}", self.frontend_url, token);
-        let body = format!(
-            "Welcome! Please verify your email by clicking the link below:\n\n{}\n\nThis link expires in 24 hours.",
-            link
-        );
+    /// Name of the configured provider.
+    pub fn provider_name(&self) -> Option<&'static str> {
+        self.sender.as_ref().map(|s| s.provider.name())
+    }
 
-        self.send_email(to, "Verify your email", &body).await
+    /// Queue a verification email in the user's locale. Silently succeeds if
+    /// no provider is configured.
+    pub async fn send_verification_email(
+        &self,
+        db: &DatabaseConnection,
+        user: &UserModel,
+        token: &str,
+    ) -> Result<()> {
+        let link = format!("{}/verify-email?token={}", self.frontend_url, token);
+        let unsubscribe = self.unsubscribe_url(user.id, ALL_OPTIONAL);
+        let email =
+            email_template::verification(Locale::or_default(&user.locale), &link, &unsubscribe)?;
+        self.enqueue(
+            db,
+            "verification",
+            EmailCategory::Account,
+            user,
+            email,
+            unsubscribe,
+        )
+        .await
     }
 
-    /// Send a password reset email. Silently succeeds if SMTP is not configured.
-    pub async fn send_password_reset_email(&self, to: &str, token: &str) -> Result<()> {
+    /// Queue a password reset email in the user's locale. Silently succeeds
+    /// if no provider is configured.
+    pub async fn send_password_reset_email(
+        &self,
+        db: &DatabaseConnection,
+        user: &UserModel,
+        token: &str,
+    ) -> Result<()> {
         let link = format!("{}/reset-password?token={}", self.frontend_url, token);
-        let body = format!(
-            "A password reset was requested for your account.\n\nClick the link below to reset your password:\n\n{}\n\nThis link expires in 1 hour. If you did not request this, you can safely ignore this email.",
-            link
+        let unsubscribe = self.unsubscribe_url(user.id, ALL_OPTIONAL);
+        let email =
+            email_template::password_reset(Locale::or_default(&user.locale), &link, &unsubscribe)?;
+        self.enqueue(
+            db,
+            "password_reset",
+            EmailCategory::Account,
+            user,
+            email,
+            unsubscribe,
+        )
+        .await
+    }
+
+    /// Queue a digest of followed users' posts in the user's locale.
+    pub async fn send_digest(
+        &self,
+        db: &DatabaseConnection,
+        user: &UserModel,
+        daily: bool,
+        posts: &[DigestItem],
+    ) -> Result<()> {
+        let category = EmailCategory::Digest;
+        let unsubscribe = self.unsubscribe_url(user.id, category.as_str());
+        let email = email_template::digest(
+            Locale::or_default(&user.locale),
+            daily,
+            posts,
+            &self.frontend_url,
+            &unsubscribe,
+        )?;
+        self.enqueue(db, "digest", category, user, email, unsubscribe)
+            .await
+    }
+
+    /// Queue an admin announcement in the user's locale.
+    pub async fn send_announcement(
+        &self,
+        db: &DatabaseConnection,
+        user: &UserModel,
+        title: &str,
+        body: &str,
+    ) -> Result<()> {
+        let category = EmailCategory::Announcement;
+        let unsubscribe = self.unsubscribe_url(user.id, category.as_str());
+        let email = email_template::announcement(
+            Locale::or_default(&user.locale),
+            title,
+            body,
+            &unsubscribe,
+        )?;
+        self.enqueue(db, "announcement", category, user, email, unsubscribe)
+            .await
+    }
+
+    /// One-click link that turns off `category` (or every optional category
+    /// for `all`) for the user.
+    fn unsubscribe_url(&self, user_id: i32, category: &str) -> String {
+        format!(
+            "{}/api/v1/email/unsubscribe?token={}",
+            self.public_api_url,
+            unsubscribe_token(&url_signing_secret(), user_id, category)
+        )
+    }
+
+    /// Store an email for the worker to deliver, unless the user has turned
+    /// off its category or their address bounced or complained. Optional
+    /// emails wait until the end of the user's quiet hours.
+    async fn enqueue(
+        &self,
+        db: &DatabaseConnection,
+        kind: &str,
+        category: EmailCategory,
+        user: &UserModel,
+        email: RenderedEmail,
+        unsubscribe_url: String,
+    ) -> Result<()> {
+        if !self.is_configured() {
+            tracing::debug!("Email not configured, skipping email to {}", user.email);
+            return Ok(());
+        }
+        if let Some(reason) = &user.email_undeliverable {
+            tracing::debug!(
+                "Address of user {} is undeliverable ({reason}), skipping",
+                user.id
+            );
+            return Ok(());
+        }
+        if !EmailPreferenceService::new(db.clone())
+            .is_enabled(user.id, category)
+            .await?
+        {
+            tracing::debug!(
+                "User {} unsubscribed from {} emails, skipping",
+                user.id,
+                category.as_str()
+            );
+            return Ok(());
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        let send_at = if category.is_required() {
+            now
+        } else {
+            QuietHours::of(user)
+                .and_then(|hours| hours.ends_at(now))
+                .unwrap_or(now)
+        };
+        email_outbox::ActiveModel {
+            kind: Set(kind.to_string()),
+            to_address: Set(user.email.clone()),
+            subject: Set(email.subject),
+            body: Set(email.text),
+            html_body: Set(Some(email.html)),
+            unsubscribe_url: Set(Some(unsubscribe_url)),
+            status: Set("pending".to_string()),
+            attempts: Set(0),
+            next_attempt_at: Set(send_at),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Deliver every email that is due, returning how many were attempted.
+    pub async fn process_queue(&self, db: &DatabaseConnection) -> Result<usize> {
+        let mut attempted = 0;
+        loop {
+            let batch = self.claim_due(db).await?;
+            let claimed = batch.len();
+            for email in batch {
+                self.deliver(db, email).await?;
+            }
+            attempted += claimed;
+            if (claimed as u64) < BATCH_SIZE {
+                return Ok(attempted);
+            }
+        }
+    }
+
+    /// Deliver queued emails as they arrive, and retries as they fall due,
+    /// until shutdown.
+    pub fn spawn_worker(&self, db: DatabaseConnection) -> tokio::task::JoinHandle<()> {
+        let service = self.clone();
+        shutdown::spawn(async move {
+            loop {
+                if let Err(e) = service.process_queue(&db).await {
+                    tracing::warn!("Failed to process email queue: {e}");
+                }
+                tokio::select! {
+                    _ = service.wake.notified() => {}
+                    _ = tokio::time::sleep(service.queue.poll_interval) => {}
+                    _ = shutdown::requested() => break,
+                }
+            }
+        })
+    }
+
+    /// Mark a batch of due emails as `sending` and return them. `SKIP LOCKED`
+    /// lets several instances share the queue without sending twice.
+    async fn claim_due(&self, db: &DatabaseConnection) -> Result<Vec<EmailOutboxModel>> {
+        let now = chrono::Utc::now().naive_utc();
+        let stale = now - chrono::Duration::from_std(STALE_SENDING)?;
+        let backend = db.get_database_backend();
+        let due = format!(
+            "SELECT id FROM email_outbox
+                WHERE (status = 'pending' AND next_attempt_at <= $1)
+                   OR (status = 'sending' AND updated_at < $2)
+                ORDER BY id
+                LIMIT $3
+                {}",
+            sql::skip_locked(backend)
         );
+        let values = [now.into(), stale.into(), (BATCH_SIZE as i64).into()];
+        if backend == DbBackend::MySql {
+            return Self::claim_due_mysql(db, &due, values, now).await;
+        }
+
+        let query = format!(
+            "UPDATE email_outbox
+            SET status = 'sending', attempts = attempts + 1, updated_at = $1
+            WHERE id IN ({due})
+            RETURNING *"
+        );
+        let emails = EmailOutbox::find()
+            .from_raw_sql(sql::statement(backend, query, values))
+            .all(db)
+            .await?;
+        Ok(emails)
+    }
 
-        self.send_email(to, "Reset your password", &body).await
+    /// [`Self::claim_due`] for MySQL, which has no `RETURNING` and can't
+    /// update a table it selects from: lock the batch, then mark and read
+    /// it back in one transaction.
+    async fn claim_due_mysql(
+        db: &DatabaseConnection,
+        due: &str,
+        values: [sea_orm::Value; 3],
+        now: chrono::NaiveDateTime,
+    ) -> Result<Vec<EmailOutboxModel>> {
+        let txn = db.begin().await?;
+        let ids = txn
+            .query_all(sql::statement(DbBackend::MySql, due, values))
+            .await?
+            .iter()
+            .map(|row| row.try_get_by_index::<i32>(0))
+            .collect::<Result<Vec<_>, _>>()?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        EmailOutbox::update_many()
+            .col_expr(email_outbox::Column::Status, Expr::value("sending"))
+            .col_expr(
+                email_outbox::Column::Attempts,
+                Expr::col(email_outbox::Column::Attempts).add(1),
+            )
+            .col_expr(email_outbox::Column::UpdatedAt, Expr::value(now))
+            .filter(email_outbox::Column::Id.is_in(ids.clone()))
+            .exec(&txn)
+            .await?;
+        let emails = EmailOutbox::find()
+            .filter(email_outbox::Column::Id.is_in(ids))
+            .order_by_asc(email_outbox::Column::Id)
+            .all(&txn)
+            .await?;
+        txn.commit().await?;
+        Ok(emails)
     }
 
-    async fn send_email(&self, to: &str, subject: &str, body: &str) -> Result<()> {
-        let transport = match &self.transport {
-            Some(t) => t,
-            None => {
-                tracing::debug!("SMTP not configured, skipping email to {to}");
-                return Ok(());
+    /// Send one claimed email and record the outcome.
+    async fn deliver(&self, db: &DatabaseConnection, email: EmailOutboxModel) -> Result<()> {
+        let result = self
+            .send_email(
+                &email.to_address,
+                &email.subject,
+                &email.body,
+                email.html_body.as_deref(),
+                email.unsubscribe_url.as_deref(),
+            )
+            .await;
+        let now = chrono::Utc::now().naive_utc();
+        let attempts = email.attempts;
+        let id = email.id;
+        let mut active: email_outbox::ActiveModel = email.into();
+        active.updated_at = Set(now);
+        match result {
+            Ok(()) => {
+                active.status = Set("sent".to_string());
+                active.sent_at = Set(Some(now));
+                active.last_error = Set(None);
             }
-        };
-        let from_address = match &self.from_address {
-            Some(f) => f,
-            None => return Ok(()),
-        };
+            Err(SendError::RateLimited(retry_after)) => {
+                // Throttling is not the email's fault; don't count the attempt
+                tracing::warn!("Email provider is rate limiting, delaying email {id}");
+                let delay = retry_after.unwrap_or(self.queue.retry_base);
+                active.status = Set("pending".to_string());
+                active.attempts = Set(attempts - 1);
+                active.next_attempt_at = Set(now + chrono::Duration::from_std(delay)?);
+            }
+            Err(SendError::Permanent(e)) => {
+                tracing::error!("Email {id} was rejected: {e}");
+                active.last_error = Set(Some(e));
+                active.status = Set("failed".to_string());
+            }
+            Err(SendError::Transient(e)) => {
+                if attempts >= self.queue.max_attempts {
+                    tracing::error!("Giving up on email {id} after {attempts} attempts: {e}");
+                    active.status = Set("failed".to_string());
+                } else {
+                    tracing::warn!("Failed to send email {id} (attempt {attempts}): {e}");
+                    let delay = retry_delay(self.queue.retry_base, attempts);
+                    active.status = Set("pending".to_string());
+                    active.next_attempt_at = Set(now + chrono::Duration::from_std(delay)?);
+                }
+                active.last_error = Set(Some(e));
+            }
+        }
+        active.update(db).await?;
+        Ok(())
+    }
 
-        let from_mailbox: Mailbox =
-            from_address
-                .parse()
-                .map_err(|e: lettre::address::AddressError| {
-                    anyhow::anyhow!("Invalid from address '{}': {}", from_address, e)
-                })?;
+    async fn send_email(
+        &self,
+        to: &str,
+        subject: &str,
+        text: &str,
+        html: Option<&str>,
+        unsubscribe_url: Option<&str>,
+    ) -> Result<(), SendError> {
+        let sender = self
+            .sender
+            .as_ref()
+            .ok_or_else(|| SendError::Transient("Email is not configured".to_string()))?;
         let to_mailbox: Mailbox = to.parse().map_err(|e: lettre::address::AddressError| {
-            anyhow::anyhow!("Invalid to address '{}': {}", to, e)
+            SendError::Permanent(format!("Invalid to address '{}': {}", to, e))
         })?;
 
-        let email = Message::builder()
-            .from(from_mailbox)
-            .to(to_mailbox)
-            .subject(subject)
-            .header(ContentType::TEXT_PLAIN)
-            .body(body.to_string())?;
-
-        transport.send(email).await?;
-        tracing::info!("Email sent to {to}: {subject}");
+        sender.limiter.acquire().await;
+        sender
+            .provider
+            .send(&OutgoingEmail {
+                from: &sender.from,
+                to: &to_mailbox,
+                subject,
+                text,
+                html,
+                unsubscribe_url,
+            })
+            .await?;
+        tracing::info!(
+            "Email sent to {to} via {}: {subject}",
+            sender.provider.name()
+        );
         Ok(())
     }
 }
+
+fn build_sender(config: &EmailConfig) -> Result<Sender> {
+    let from: Mailbox = config
+        .from_address
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid from address '{}': {}", config.from_address, e))?;
+    Ok(Sender {
+        provider: build_provider(&config.provider)?,
+        from,
+        limiter: Arc::new(RateLimiter::new(config.rate_limit_per_second)),
+    })
+}
+
+/// Delay before the next attempt after `attempts` failures.
+fn retry_delay(base: Duration, attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    base.saturating_mul(2u32.pow(exponent)).min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_backs_off_exponentially() {
+        let base = Duration::from_secs(30);
+        assert_eq!(retry_delay(base, 1), Duration::from_secs(30));
+        assert_eq!(retry_delay(base, 2), Duration::from_secs(60));
+        assert_eq!(retry_delay(base, 4), Duration::from_secs(240));
+        assert_eq!(retry_delay(base, 40), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(Duration::ZERO, 3), Duration::ZERO);
+    }
+}
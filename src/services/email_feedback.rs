@@ -0,0 +1,265 @@
+//! Bounce and complaint reports from the email provider.
+//!
+//! SendGrid posts batches of events; SES publishes through SNS, which wraps
+//! each notification in an envelope and first asks for the subscription to
+//! be confirmed. A hard bounce or a spam complaint marks the user's address
+//! undeliverable: nothing more is sent to it and its queued emails fail.
+
+use crate::error::AppResult;
+use crate::models::{email_outbox, user, EmailOutbox, User};
+use sea_orm::sea_query::{Expr, Func};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Undeliverable {
+    /// The receiving server permanently rejected the address
+    Bounce,
+    /// The recipient marked our email as spam
+    Complaint,
+}
+
+impl Undeliverable {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Undeliverable::Bounce => "bounce",
+            Undeliverable::Complaint => "complaint",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feedback {
+    pub address: String,
+    pub reason: Undeliverable,
+}
+
+/// What an SNS delivery asks of us.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SnsMessage {
+    /// Visit the URL to start receiving notifications
+    SubscriptionConfirmation {
+        subscribe_url: String,
+    },
+    Notification(Vec<Feedback>),
+    /// Unsubscribe confirmations and anything else we don't act on
+    Ignored,
+}
+
+/// Bounces and spam reports in a SendGrid Event Webhook batch. `blocked`
+/// bounces are temporary and ignored.
+pub fn parse_sendgrid_events(events: &Value) -> Vec<Feedback> {
+    let Some(events) = events.as_array() else {
+        return Vec::new();
+    };
+    events
+        .iter()
+        .filter_map(|event| {
+            let address = event["email"].as_str()?.to_string();
+            let reason = match event["event"].as_str()? {
+                "bounce" if event["type"].as_str() != Some("blocked") => Undeliverable::Bounce,
+                "spamreport" => Undeliverable::Complaint,
+                _ => return None,
+            };
+            Some(Feedback { address, reason })
+        })
+        .collect()
+}
+
+/// An SNS delivery carrying SES notifications. Both identity notifications
+/// (`notificationType`) and configuration set events (`eventType`) are
+/// understood; only permanent bounces count.
+pub fn parse_sns(envelope: &Value) -> SnsMessage {
+    match envelope["Type"].as_str() {
+        Some("SubscriptionConfirmation") => match envelope["SubscribeURL"].as_str() {
+            Some(url) => SnsMessage::SubscriptionConfirmation {
+                subscribe_url: url.to_string(),
+            },
+            None => SnsMessage::Ignored,
+        },
+        Some("Notification") => {
+            let Some(message) = envelope["Message"]
+                .as_str()
+                .and_then(|m| serde_json::from_str::<Value>(m).ok())
+            else {
+                return SnsMessage::Ignored;
+            };
+            let kind = message["notificationType"]
+                .as_str()
+                .or_else(|| message["eventType"].as_str());
+            let (reason, recipients) = match kind {
+                Some("Bounce") if message["bounce"]["bounceType"] == "Permanent" => (
+                    Undeliverable::Bounce,
+                    &message["bounce"]["bouncedRecipients"],
+                ),
+                Some("Complaint") => (
+                    Undeliverable::Complaint,
+                    &message["complaint"]["complainedRecipients"],
+                ),
+                _ => return SnsMessage::Notification(Vec::new()),
+            };
+            SnsMessage::Notification(
+                recipients
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|r| r["emailAddress"].as_str())
+                    .map(|address| Feedback {
+                        address: address.to_string(),
+                        reason,
+                    })
+                    .collect(),
+            )
+        }
+        _ => SnsMessage::Ignored,
+    }
+}
+
+/// SNS subscription URLs must point at AWS, so a forged confirmation can't
+/// make us fetch arbitrary URLs.
+pub fn is_sns_url(url: &str) -> bool {
+    let Ok(url) = url::Url::parse(url) else {
+        return false;
+    };
+    url.scheme() == "https"
+        && url
+            .host_str()
+            .is_some_and(|host| host.starts_with("sns.") && host.ends_with(".amazonaws.com"))
+}
+
+pub struct EmailFeedbackService {
+    db: DatabaseConnection,
+}
+
+impl EmailFeedbackService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Mark the reported addresses undeliverable and fail the emails still
+    /// queued for them. Returns how many users were newly marked.
+    pub async fn record(&self, feedback: &[Feedback]) -> AppResult<u64> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut marked = 0;
+        for item in feedback {
+            let address = item.address.trim().to_lowercase();
+            if address.is_empty() {
+                continue;
+            }
+            tracing::warn!(
+                "Email provider reported {} for {}",
+                item.reason.as_str(),
+                address
+            );
+
+            let result = User::update_many()
+                .col_expr(
+                    user::Column::EmailUndeliverable,
+                    Expr::value(item.reason.as_str()),
+                )
+                .col_expr(user::Column::EmailUndeliverableAt, Expr::value(now))
+                .filter(Expr::expr(Func::lower(Expr::col(user::Column::Email))).eq(&address))
+                .filter(user::Column::EmailUndeliverable.is_null())
+                .exec(&self.db)
+                .await?;
+            marked += result.rows_affected;
+
+            EmailOutbox::update_many()
+                .col_expr(email_outbox::Column::Status, Expr::value("failed"))
+                .col_expr(
+                    email_outbox::Column::LastError,
+                    Expr::value(format!(
+                        "Address marked undeliverable ({})",
+                        item.reason.as_str()
+                    )),
+                )
+                .col_expr(email_outbox::Column::UpdatedAt, Expr::value(now))
+                .filter(
+                    Expr::expr(Func::lower(Expr::col(email_outbox::Column::ToAddress)))
+                        .eq(&address),
+                )
+                .filter(email_outbox::Column::Status.eq("pending"))
+                .exec(&self.db)
+                .await?;
+        }
+        Ok(marked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sendgrid_events() {
+        let events = serde_json::json!([
+            { "email": "gone@example.com", "event": "bounce", "type": "bounce" },
+            { "email": "full@example.com", "event": "bounce", "type": "blocked" },
+            { "email": "angry@example.com", "event": "spamreport" },
+            { "email": "ok@example.com", "event": "delivered" },
+        ]);
+        assert_eq!(
+            parse_sendgrid_events(&events),
+            vec![
+                Feedback {
+                    address: "gone@example.com".to_string(),
+                    reason: Undeliverable::Bounce,
+                },
+                Feedback {
+                    address: "angry@example.com".to_string(),
+                    reason: Undeliverable::Complaint,
+                },
+            ]
+        );
+        assert!(parse_sendgrid_events(&serde_json::json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_parse_sns() {
+        let confirm = serde_json::json!({
+            "Type": "SubscriptionConfirmation",
+            "SubscribeURL": "https://sns.us-east-1.amazonaws.com/?Action=ConfirmSubscription",
+        });
+        assert!(matches!(
+            parse_sns(&confirm),
+            SnsMessage::SubscriptionConfirmation { .. }
+        ));
+
+        let bounce = serde_json::json!({
+            "notificationType": "Bounce",
+            "bounce": {
+                "bounceType": "Permanent",
+                "bouncedRecipients": [{ "emailAddress": "gone@example.com" }],
+            },
+        });
+        let envelope = serde_json::json!({ "Type": "Notification", "Message": bounce.to_string() });
+        assert_eq!(
+            parse_sns(&envelope),
+            SnsMessage::Notification(vec![Feedback {
+                address: "gone@example.com".to_string(),
+                reason: Undeliverable::Bounce,
+            }])
+        );
+
+        let transient = serde_json::json!({
+            "eventType": "Bounce",
+            "bounce": {
+                "bounceType": "Transient",
+                "bouncedRecipients": [{ "emailAddress": "full@example.com" }],
+            },
+        });
+        let envelope =
+            serde_json::json!({ "Type": "Notification", "Message": transient.to_string() });
+        assert_eq!(parse_sns(&envelope), SnsMessage::Notification(Vec::new()));
+    }
+
+    #[test]
+    fn test_is_sns_url() {
+        assert!(is_sns_url(
+            "https://sns.eu-west-1.amazonaws.com/?Action=ConfirmSubscription&Token=x"
+        ));
+        assert!(!is_sns_url("http://sns.eu-west-1.amazonaws.com/"));
+        assert!(!is_sns_url("https://sns.evil.com/.amazonaws.com"));
+        assert!(!is_sns_url("https://169.254.169.254/"));
+    }
+}
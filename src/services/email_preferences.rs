@@ -0,0 +1,152 @@
+//! Which categories of email a user receives.
+//!
+//! Account emails (verification, password reset) are always sent. Other
+//! categories can be turned off through `/auth/email-preferences` or the
+//! signed one-click unsubscribe link every email carries; each opt-out is a
+//! row in `email_opt_outs`.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{email_opt_out, EmailOptOut, User};
+use crate::utils::url_sign::{parse_unsubscribe_token, url_signing_secret};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+
+/// Unsubscribe token category covering every optional category, used in
+/// emails that can't be turned off themselves.
+pub const ALL_OPTIONAL: &str = "all";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailCategory {
+    /// Verification and password reset emails
+    Account,
+    /// Daily or weekly digests
+    Digest,
+    /// Announcements from the admins
+    Announcement,
+}
+
+impl EmailCategory {
+    pub const ALL: [EmailCategory; 3] = [
+        EmailCategory::Account,
+        EmailCategory::Digest,
+        EmailCategory::Announcement,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailCategory::Account => "account",
+            EmailCategory::Digest => "digest",
+            EmailCategory::Announcement => "announcement",
+        }
+    }
+
+    /// Whether the category is always sent.
+    pub fn is_required(&self) -> bool {
+        matches!(self, EmailCategory::Account)
+    }
+}
+
+pub struct EmailPreferenceService {
+    db: DatabaseConnection,
+}
+
+impl EmailPreferenceService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Every category and whether the user receives it.
+    pub async fn preferences(&self, user_id: i32) -> AppResult<Vec<(EmailCategory, bool)>> {
+        let opted_out: Vec<String> = EmailOptOut::find()
+            .filter(email_opt_out::Column::UserId.eq(user_id))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|o| o.category)
+            .collect();
+        Ok(EmailCategory::ALL
+            .into_iter()
+            .map(|c| (c, !opted_out.iter().any(|o| o == c.as_str())))
+            .collect())
+    }
+
+    pub async fn is_enabled(&self, user_id: i32, category: EmailCategory) -> AppResult<bool> {
+        if category.is_required() {
+            return Ok(true);
+        }
+        let opt_out = EmailOptOut::find_by_id((user_id, category.as_str().to_string()))
+            .one(&self.db)
+            .await?;
+        Ok(opt_out.is_none())
+    }
+
+    /// Turn categories on or off. Required categories can't be turned off.
+    pub async fn set(&self, user_id: i32, changes: &[(EmailCategory, bool)]) -> AppResult<()> {
+        if let Some((category, _)) = changes.iter().find(|(c, on)| c.is_required() && !on) {
+            return Err(AppError::Validation(format!(
+                "The {} category can't be turned off",
+                category.as_str()
+            )));
+        }
+
+        for (category, enabled) in changes {
+            if *enabled {
+                EmailOptOut::delete_by_id((user_id, category.as_str().to_string()))
+                    .exec(&self.db)
+                    .await?;
+            } else {
+                let opt_out = email_opt_out::ActiveModel {
+                    user_id: Set(user_id),
+                    category: Set(category.as_str().to_string()),
+                    created_at: Set(chrono::Utc::now().naive_utc()),
+                };
+                EmailOptOut::insert(opt_out)
+                    .on_conflict(
+                        OnConflict::columns([
+                            email_opt_out::Column::UserId,
+                            email_opt_out::Column::Category,
+                        ])
+                        .do_nothing_on([email_opt_out::Column::UserId])
+                        .to_owned(),
+                    )
+                    .exec_without_returning(&self.db)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a one-click unsubscribe token, returning the categories turned
+    /// off.
+    pub async fn unsubscribe(&self, token: &str) -> AppResult<Vec<EmailCategory>> {
+        let invalid = || AppError::Validation("Invalid unsubscribe token".to_string());
+        let (user_id, name) =
+            parse_unsubscribe_token(&url_signing_secret(), token).ok_or_else(invalid)?;
+
+        let categories: Vec<EmailCategory> = if name == ALL_OPTIONAL {
+            EmailCategory::ALL
+                .into_iter()
+                .filter(|c| !c.is_required())
+                .collect()
+        } else {
+            match EmailCategory::parse(&name) {
+                Some(category) if !category.is_required() => vec![category],
+                _ => return Err(invalid()),
+            }
+        };
+
+        // The account may have been deleted since the email was sent
+        User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        let changes: Vec<_> = categories.iter().map(|c| (*c, false)).collect();
+        self.set(user_id, &changes).await?;
+        Ok(categories)
+    }
+}
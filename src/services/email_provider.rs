@@ -0,0 +1,495 @@
+//! Email delivery backends: SMTP, the SendGrid v3 API and the Amazon SES v2
+//! API, selected by `EMAIL_PROVIDER`. Each maps its own failures onto
+//! [`SendError`] so the outbox worker knows whether to retry.
+
+use crate::config::email::EmailProviderConfig;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use lettre::{
+    message::{
+        header::{ContentType, HeaderName, HeaderValue},
+        Mailbox, MultiPart,
+    },
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct OutgoingEmail<'a> {
+    pub from: &'a Mailbox,
+    pub to: &'a Mailbox,
+    pub subject: &'a str,
+    pub text: &'a str,
+    pub html: Option<&'a str>,
+    /// Sent as `List-Unsubscribe`, with `List-Unsubscribe-Post` so mail
+    /// clients can unsubscribe in one click (RFC 8058)
+    pub unsubscribe_url: Option<&'a str>,
+}
+
+const LIST_UNSUBSCRIBE_POST: &str = "List-Unsubscribe=One-Click";
+
+#[derive(Debug, Error)]
+pub enum SendError {
+    /// Worth retrying later
+    #[error("{0}")]
+    Transient(String),
+    /// Sending the same email again will not help
+    #[error("{0}")]
+    Permanent(String),
+    /// The provider is throttling us; retry after the delay if it gave one
+    #[error("rate limited by provider")]
+    RateLimited(Option<Duration>),
+}
+
+#[async_trait]
+pub trait EmailProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<(), SendError>;
+}
+
+pub fn build_provider(config: &EmailProviderConfig) -> anyhow::Result<Arc<dyn EmailProvider>> {
+    let provider: Arc<dyn EmailProvider> = match config {
+        EmailProviderConfig::Smtp {
+            host,
+            port,
+            username,
+            password,
+        } => {
+            let creds = Credentials::new(username.clone(), password.clone());
+            let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
+                .port(*port)
+                .credentials(creds)
+                .build();
+            Arc::new(SmtpProvider { transport })
+        }
+        EmailProviderConfig::SendGrid { api_key, api_url } => Arc::new(SendGridProvider {
+            client: http_client()?,
+            api_key: api_key.clone(),
+            api_url: api_url.clone(),
+        }),
+        EmailProviderConfig::Ses {
+            region,
+            access_key_id,
+            secret_access_key,
+            endpoint,
+        } => Arc::new(SesProvider {
+            client: http_client()?,
+            region: region.clone(),
+            access_key_id: access_key_id.clone(),
+            secret_access_key: secret_access_key.clone(),
+            endpoint: endpoint.clone(),
+        }),
+    };
+    Ok(provider)
+}
+
+fn http_client() -> anyhow::Result<reqwest::Client> {
+    Ok(reqwest::Client::builder().timeout(HTTP_TIMEOUT).build()?)
+}
+
+struct SmtpProvider {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+#[async_trait]
+impl EmailProvider for SmtpProvider {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<(), SendError> {
+        let mut builder = Message::builder()
+            .from(email.from.clone())
+            .to(email.to.clone())
+            .subject(email.subject);
+        if let Some(url) = email.unsubscribe_url {
+            builder = builder
+                .raw_header(HeaderValue::new(
+                    HeaderName::new_from_ascii_str("List-Unsubscribe"),
+                    format!("<{}>", url),
+                ))
+                .raw_header(HeaderValue::new(
+                    HeaderName::new_from_ascii_str("List-Unsubscribe-Post"),
+                    LIST_UNSUBSCRIBE_POST.to_string(),
+                ));
+        }
+        let message = match email.html {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(
+                email.text.to_string(),
+                html.to_string(),
+            )),
+            None => builder
+                .header(ContentType::TEXT_PLAIN)
+                .body(email.text.to_string()),
+        }
+        .map_err(|e| SendError::Permanent(format!("Invalid message: {}", e)))?;
+
+        self.transport.send(message).await.map_err(|e| {
+            // 5xx replies (unknown mailbox, rejected content) will not change
+            if e.is_permanent() {
+                SendError::Permanent(e.to_string())
+            } else {
+                SendError::Transient(e.to_string())
+            }
+        })?;
+        Ok(())
+    }
+}
+
+/// Map an API status code: throttling and server errors are retried, any
+/// other client error means the request itself is wrong.
+fn classify_status(
+    provider: &str,
+    status: reqwest::StatusCode,
+    retry_after: Option<Duration>,
+    body: &str,
+) -> SendError {
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        SendError::RateLimited(retry_after)
+    } else if status.is_server_error() || status == reqwest::StatusCode::REQUEST_TIMEOUT {
+        SendError::Transient(format!("{} returned {}: {}", provider, status, body))
+    } else {
+        SendError::Permanent(format!("{} returned {}: {}", provider, status, body))
+    }
+}
+
+fn request_error(provider: &str, e: reqwest::Error) -> SendError {
+    SendError::Transient(format!("{} request failed: {}", provider, e))
+}
+
+fn json_address(mailbox: &Mailbox) -> serde_json::Value {
+    match &mailbox.name {
+        Some(name) => serde_json::json!({ "email": mailbox.email.to_string(), "name": name }),
+        None => serde_json::json!({ "email": mailbox.email.to_string() }),
+    }
+}
+
+struct SendGridProvider {
+    client: reqwest::Client,
+    api_key: String,
+    api_url: String,
+}
+
+impl SendGridProvider {
+    fn body(email: &OutgoingEmail<'_>) -> serde_json::Value {
+        let mut content = vec![serde_json::json!({ "type": "text/plain", "value": email.text })];
+        if let Some(html) = email.html {
+            content.push(serde_json::json!({ "type": "text/html", "value": html }));
+        }
+        let mut body = serde_json::json!({
+            "personalizations": [{ "to": [json_address(email.to)] }],
+            "from": json_address(email.from),
+            "subject": email.subject,
+            "content": content,
+        });
+        if let Some(url) = email.unsubscribe_url {
+            body["headers"] = serde_json::json!({
+                "List-Unsubscribe": format!("<{}>", url),
+                "List-Unsubscribe-Post": LIST_UNSUBSCRIBE_POST,
+            });
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl EmailProvider for SendGridProvider {
+    fn name(&self) -> &'static str {
+        "sendgrid"
+    }
+
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<(), SendError> {
+        let resp = self
+            .client
+            .post(format!("{}/v3/mail/send", self.api_url))
+            .bearer_auth(&self.api_key)
+            .json(&Self::body(email))
+            .send()
+            .await
+            .map_err(|e| request_error("SendGrid", e))?;
+        if resp.status().is_success() {
+            return Ok(());
+        }
+
+        // SendGrid reports when the limit resets as a Unix timestamp
+        let retry_after = resp
+            .headers()
+            .get("x-ratelimit-reset")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(|reset| (reset - chrono::Utc::now().timestamp()).max(1) as u64)
+            .map(Duration::from_secs);
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        Err(classify_status("SendGrid", status, retry_after, &body))
+    }
+}
+
+struct SesProvider {
+    client: reqwest::Client,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+    endpoint: String,
+}
+
+impl SesProvider {
+    fn body(email: &OutgoingEmail<'_>) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "Text": { "Data": email.text, "Charset": "UTF-8" },
+        });
+        if let Some(html) = email.html {
+            body["Html"] = serde_json::json!({ "Data": html, "Charset": "UTF-8" });
+        }
+        let mut simple = serde_json::json!({
+            "Subject": { "Data": email.subject, "Charset": "UTF-8" },
+            "Body": body,
+        });
+        if let Some(url) = email.unsubscribe_url {
+            simple["Headers"] = serde_json::json!([
+                { "Name": "List-Unsubscribe", "Value": format!("<{}>", url) },
+                { "Name": "List-Unsubscribe-Post", "Value": LIST_UNSUBSCRIBE_POST },
+            ]);
+        }
+        serde_json::json!({
+            "FromEmailAddress": email.from.to_string(),
+            "Destination": { "ToAddresses": [email.to.to_string()] },
+            "Content": { "Simple": simple },
+        })
+    }
+}
+
+#[async_trait]
+impl EmailProvider for SesProvider {
+    fn name(&self) -> &'static str {
+        "ses"
+    }
+
+    async fn send(&self, email: &OutgoingEmail<'_>) -> Result<(), SendError> {
+        let url = url::Url::parse(&format!("{}/v2/email/outbound-emails", self.endpoint))
+            .map_err(|e| SendError::Permanent(format!("Invalid SES_ENDPOINT: {}", e)))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(SendError::Permanent("Invalid SES_ENDPOINT".to_string())),
+        };
+        let payload = serde_json::to_vec(&Self::body(email))
+            .map_err(|e| SendError::Permanent(e.to_string()))?;
+
+        let signed = sign_v4(&SigV4Request {
+            access_key_id: &self.access_key_id,
+            secret_access_key: &self.secret_access_key,
+            region: &self.region,
+            service: "ses",
+            host: &host,
+            path: url.path(),
+            body: &payload,
+            now: chrono::Utc::now(),
+        });
+
+        let resp = self
+            .client
+            .post(url)
+            .header("content-type", "application/json")
+            .header("x-amz-date", &signed.amz_date)
+            .header("authorization", &signed.authorization)
+            .body(payload)
+            .send()
+            .await
+            .map_err(|e| request_error("SES", e))?;
+        if resp.status().is_success() {
+            return Ok(());
+        }
+
+        let status = resp.status();
+        let error_type = resp
+            .headers()
+            .get("x-amzn-errortype")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let body = resp.text().await.unwrap_or_default();
+        // SES reports throttling as 400 TooManyRequests / LimitExceeded
+        if error_type.starts_with("TooManyRequests") || error_type.starts_with("LimitExceeded") {
+            return Err(SendError::RateLimited(None));
+        }
+        Err(classify_status("SES", status, None, &body))
+    }
+}
+
+struct SigV4Request<'a> {
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+    region: &'a str,
+    service: &'a str,
+    host: &'a str,
+    path: &'a str,
+    body: &'a [u8],
+    now: chrono::DateTime<chrono::Utc>,
+}
+
+struct SignedHeaders {
+    amz_date: String,
+    authorization: String,
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let k_date = hmac_sha256(
+        format!("AWS4{}", secret_access_key).as_bytes(),
+        date.as_bytes(),
+    );
+    let k_region = hmac_sha256(&k_date, region.as_bytes());
+    let k_service = hmac_sha256(&k_region, service.as_bytes());
+    hmac_sha256(&k_service, b"aws4_request")
+}
+
+/// AWS Signature Version 4 for a JSON POST with no query string.
+fn sign_v4(req: &SigV4Request<'_>) -> SignedHeaders {
+    let amz_date = req.now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = req.now.format("%Y%m%d").to_string();
+    let signed_headers = "content-type;host;x-amz-date";
+    let canonical_request = format!(
+        "POST\n{}\n\ncontent-type:application/json\nhost:{}\nx-amz-date:{}\n\n{}\n{}",
+        req.path,
+        req.host,
+        amz_date,
+        signed_headers,
+        hex(&Sha256::digest(req.body)),
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, req.region, req.service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes())),
+    );
+    let key = signing_key(req.secret_access_key, &date, req.region, req.service);
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+    SignedHeaders {
+        authorization: format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            req.access_key_id, scope, signed_headers, signature
+        ),
+        amz_date,
+    }
+}
+
+/// Spaces sends evenly so a burst of queued emails stays under the
+/// provider's per-second limit.
+pub struct RateLimiter {
+    interval: Option<Duration>,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: Option<f64>) -> Self {
+        Self {
+            interval: per_second.map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for the next free send slot.
+    pub async fn acquire(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let slot = {
+            let mut next = self.next_slot.lock().await;
+            let slot = (*next).max(Instant::now());
+            *next = slot + interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_key_matches_aws_example() {
+        // From the AWS documentation on deriving a SigV4 signing key
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn test_sign_v4_authorization_header() {
+        let signed = sign_v4(&SigV4Request {
+            access_key_id: "AKIDEXAMPLE",
+            secret_access_key: "secret",
+            region: "eu-west-1",
+            service: "ses",
+            host: "email.eu-west-1.amazonaws.com",
+            path: "/v2/email/outbound-emails",
+            body: b"{}",
+            now: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        });
+        assert_eq!(signed.amz_date, "20231114T221320Z");
+        assert!(signed.authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20231114/eu-west-1/ses/aws4_request, SignedHeaders=content-type;host;x-amz-date, Signature="
+        ));
+    }
+
+    #[test]
+    fn test_classify_status() {
+        use reqwest::StatusCode;
+        assert!(matches!(
+            classify_status("x", StatusCode::TOO_MANY_REQUESTS, None, ""),
+            SendError::RateLimited(None)
+        ));
+        assert!(matches!(
+            classify_status("x", StatusCode::BAD_GATEWAY, None, ""),
+            SendError::Transient(_)
+        ));
+        assert!(matches!(
+            classify_status("x", StatusCode::BAD_REQUEST, None, ""),
+            SendError::Permanent(_)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_spaces_sends() {
+        let limiter = RateLimiter::new(Some(2.0));
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        let unlimited = RateLimiter::new(None);
+        let start = Instant::now();
+        unlimited.acquire().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+}
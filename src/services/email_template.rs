@@ -0,0 +1,422 @@
+//! Email bodies, rendered from `templates/email/<locale>/` as both plain text
+//! and HTML. Templates are compiled in, so a missing or broken one fails the
+//! build rather than a send.
+
+use askama::Template;
+
+/// Language tags accepted for a user's locale.
+pub const SUPPORTED_LOCALES: &[&str] = &["en", "zh"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Zh,
+}
+
+impl Locale {
+    /// Match a language tag such as `zh-CN` or `en_US` on its primary subtag.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Locale::En),
+            "zh" => Some(Locale::Zh),
+            _ => None,
+        }
+    }
+
+    /// First supported language in an `Accept-Language` header, in the order
+    /// listed.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        header
+            .split(',')
+            .filter_map(|part| part.split(';').next())
+            .find_map(Self::parse)
+    }
+
+    /// A stored preference, falling back to English.
+    pub fn or_default(tag: &str) -> Self {
+        Self::parse(tag).unwrap_or(Locale::En)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Zh => "zh",
+        }
+    }
+}
+
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+#[derive(Template)]
+#[template(path = "email/en/verification.txt")]
+struct EnVerificationText<'a> {
+    link: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/en/verification.html")]
+struct EnVerificationHtml<'a> {
+    link: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/zh/verification.txt")]
+struct ZhVerificationText<'a> {
+    link: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/zh/verification.html")]
+struct ZhVerificationHtml<'a> {
+    link: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/en/password_reset.txt")]
+struct EnPasswordResetText<'a> {
+    link: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/en/password_reset.html")]
+struct EnPasswordResetHtml<'a> {
+    link: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/zh/password_reset.txt")]
+struct ZhPasswordResetText<'a> {
+    link: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/zh/password_reset.html")]
+struct ZhPasswordResetHtml<'a> {
+    link: &'a str,
+    unsubscribe: &'a str,
+}
+
+pub fn verification(
+    locale: Locale,
+    link: &str,
+    unsubscribe: &str,
+) -> askama::Result<RenderedEmail> {
+    let (subject, text, html) = match locale {
+        Locale::En => (
+            "Verify your email",
+            EnVerificationText { link, unsubscribe }.render()?,
+            EnVerificationHtml { link, unsubscribe }.render()?,
+        ),
+        Locale::Zh => (
+            "验证你的邮箱",
+            ZhVerificationText { link, unsubscribe }.render()?,
+            ZhVerificationHtml { link, unsubscribe }.render()?,
+        ),
+    };
+    Ok(RenderedEmail {
+        subject: subject.to_string(),
+        text,
+        html,
+    })
+}
+
+pub fn password_reset(
+    locale: Locale,
+    link: &str,
+    unsubscribe: &str,
+) -> askama::Result<RenderedEmail> {
+    let (subject, text, html) = match locale {
+        Locale::En => (
+            "Reset your password",
+            EnPasswordResetText { link, unsubscribe }.render()?,
+            EnPasswordResetHtml { link, unsubscribe }.render()?,
+        ),
+        Locale::Zh => (
+            "重置你的密码",
+            ZhPasswordResetText { link, unsubscribe }.render()?,
+            ZhPasswordResetHtml { link, unsubscribe }.render()?,
+        ),
+    };
+    Ok(RenderedEmail {
+        subject: subject.to_string(),
+        text,
+        html,
+    })
+}
+
+/// A post listed in a digest.
+pub struct DigestItem {
+    pub post_id: i32,
+    pub title: String,
+    pub author: String,
+    pub forum: String,
+    pub score: i32,
+}
+
+#[derive(Template)]
+#[template(path = "email/en/digest.txt")]
+struct EnDigestText<'a> {
+    daily: bool,
+    posts: &'a [DigestItem],
+    base_url: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/en/digest.html")]
+struct EnDigestHtml<'a> {
+    daily: bool,
+    posts: &'a [DigestItem],
+    base_url: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/zh/digest.txt")]
+struct ZhDigestText<'a> {
+    daily: bool,
+    posts: &'a [DigestItem],
+    base_url: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/zh/digest.html")]
+struct ZhDigestHtml<'a> {
+    daily: bool,
+    posts: &'a [DigestItem],
+    base_url: &'a str,
+    unsubscribe: &'a str,
+}
+
+/// A daily or weekly digest; links are built on `base_url`, the frontend.
+/// Every email ends with the `unsubscribe` link.
+pub fn digest(
+    locale: Locale,
+    daily: bool,
+    posts: &[DigestItem],
+    base_url: &str,
+    unsubscribe: &str,
+) -> askama::Result<RenderedEmail> {
+    let (subject, text, html) = match locale {
+        Locale::En => (
+            if daily {
+                "Your daily digest"
+            } else {
+                "Your weekly digest"
+            },
+            EnDigestText {
+                daily,
+                posts,
+                base_url,
+                unsubscribe,
+            }
+            .render()?,
+            EnDigestHtml {
+                daily,
+                posts,
+                base_url,
+                unsubscribe,
+            }
+            .render()?,
+        ),
+        Locale::Zh => (
+            if daily {
+                "每日摘要"
+            } else {
+                "每周摘要"
+            },
+            ZhDigestText {
+                daily,
+                posts,
+                base_url,
+                unsubscribe,
+            }
+            .render()?,
+            ZhDigestHtml {
+                daily,
+                posts,
+                base_url,
+                unsubscribe,
+            }
+            .render()?,
+        ),
+    };
+    Ok(RenderedEmail {
+        subject: subject.to_string(),
+        text,
+        html,
+    })
+}
+
+#[derive(Template)]
+#[template(path = "email/en/announcement.txt")]
+struct EnAnnouncementText<'a> {
+    title: &'a str,
+    body: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/en/announcement.html")]
+struct EnAnnouncementHtml<'a> {
+    title: &'a str,
+    body: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/zh/announcement.txt")]
+struct ZhAnnouncementText<'a> {
+    title: &'a str,
+    body: &'a str,
+    unsubscribe: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/zh/announcement.html")]
+struct ZhAnnouncementHtml<'a> {
+    title: &'a str,
+    body: &'a str,
+    unsubscribe: &'a str,
+}
+
+/// An admin announcement; the body is sent as plain text.
+pub fn announcement(
+    locale: Locale,
+    title: &str,
+    body: &str,
+    unsubscribe: &str,
+) -> askama::Result<RenderedEmail> {
+    let (subject, text, html) = match locale {
+        Locale::En => (
+            format!("[Announcement] {}", title),
+            EnAnnouncementText {
+                title,
+                body,
+                unsubscribe,
+            }
+            .render()?,
+            EnAnnouncementHtml {
+                title,
+                body,
+                unsubscribe,
+            }
+            .render()?,
+        ),
+        Locale::Zh => (
+            format!("【公告】{}", title),
+            ZhAnnouncementText {
+                title,
+                body,
+                unsubscribe,
+            }
+            .render()?,
+            ZhAnnouncementHtml {
+                title,
+                body,
+                unsubscribe,
+            }
+            .render()?,
+        ),
+    };
+    Ok(RenderedEmail {
+        subject,
+        text,
+        html,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNSUBSCRIBE: &str = "https://api.forum.test/api/v1/email/unsubscribe?token=1.all.x";
+
+    #[test]
+    fn test_locale_parse_uses_primary_subtag() {
+        assert_eq!(Locale::parse("zh-CN"), Some(Locale::Zh));
+        assert_eq!(Locale::parse("en_US"), Some(Locale::En));
+        assert_eq!(Locale::parse("fr"), None);
+        assert_eq!(Locale::or_default("fr"), Locale::En);
+        assert_eq!(
+            Locale::from_accept_language("fr-FR, zh-CN;q=0.8, en;q=0.5"),
+            Some(Locale::Zh)
+        );
+        assert_eq!(Locale::from_accept_language("de"), None);
+        for tag in SUPPORTED_LOCALES {
+            assert_eq!(Locale::parse(tag).unwrap().as_str(), *tag);
+        }
+    }
+
+    #[test]
+    fn test_templates_render_per_locale() {
+        let link = "https://forum.test/verify-email?token=a&b";
+        let en = verification(Locale::En, link, UNSUBSCRIBE).unwrap();
+        assert!(en.text.contains(link));
+        assert!(en
+            .html
+            .contains("https://forum.test/verify-email?token=a&#38;b"));
+        let zh = verification(Locale::Zh, link, UNSUBSCRIBE).unwrap();
+        assert_eq!(zh.subject, "验证你的邮箱");
+        assert!(zh.text.contains("24 小时"));
+        assert!(zh.text.contains(UNSUBSCRIBE));
+        assert!(en.html.contains("/api/v1/email/unsubscribe?token=1.all.x"));
+
+        let reset = password_reset(Locale::Zh, link, UNSUBSCRIBE).unwrap();
+        assert!(reset.html.contains("重置密码"));
+        assert_ne!(
+            password_reset(Locale::En, link, UNSUBSCRIBE)
+                .unwrap()
+                .subject,
+            reset.subject
+        );
+    }
+
+    #[test]
+    fn test_digest_lists_posts() {
+        let posts = [DigestItem {
+            post_id: 7,
+            title: "Rust <3".to_string(),
+            author: "alice".to_string(),
+            forum: "General".to_string(),
+            score: 12,
+        }];
+        let email = digest(Locale::En, false, &posts, "https://forum.test", UNSUBSCRIBE).unwrap();
+        assert_eq!(email.subject, "Your weekly digest");
+        assert!(email.text.contains("Rust <3"));
+        assert!(email.text.contains("https://forum.test/posts/7"));
+        assert!(email.html.contains("Rust &#60;3"));
+        assert!(email.html.contains("last week"));
+        assert!(email.text.contains(UNSUBSCRIBE));
+
+        let zh = digest(Locale::Zh, true, &posts, "https://forum.test", UNSUBSCRIBE).unwrap();
+        assert_eq!(zh.subject, "每日摘要");
+        assert!(zh.text.contains("过去一天"));
+    }
+
+    #[test]
+    fn test_announcement_escapes_html() {
+        let email =
+            announcement(Locale::En, "Maintenance", "Down at <b>9pm</b>", UNSUBSCRIBE).unwrap();
+        assert_eq!(email.subject, "[Announcement] Maintenance");
+        assert!(email.text.contains("Down at <b>9pm</b>"));
+        assert!(email.html.contains("Down at &#60;b&#62;9pm&#60;/b&#62;"));
+        assert!(email.text.contains(UNSUBSCRIBE));
+
+        let zh = announcement(Locale::Zh, "维护", "今晚维护", UNSUBSCRIBE).unwrap();
+        assert_eq!(zh.subject, "【公告】维护");
+    }
+}
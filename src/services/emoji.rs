@@ -0,0 +1,119 @@
+//! Custom emoji: images admins upload under a shortcode, shown in place of
+//! `:shortcode:` in rendered posts and comments.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{custom_emoji, CustomEmoji, CustomEmojiModel};
+use crate::services::upload::UploadService;
+use crate::utils::emoji::{self, EmojiCatalog};
+use crate::utils::{shutdown, tenant};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, QueryOrder, Set};
+use std::time::Duration;
+
+/// How often the in-memory catalog is reloaded, to pick up emoji changed on
+/// other instances.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct EmojiService {
+    db: DatabaseConnection,
+}
+
+impl EmojiService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// All custom emoji by shortcode.
+    pub async fn list(&self) -> AppResult<Vec<CustomEmojiModel>> {
+        let emoji = CustomEmoji::find()
+            .order_by_asc(custom_emoji::Column::Shortcode)
+            .all(&self.db)
+            .await?;
+        Ok(emoji)
+    }
+
+    /// Add an emoji for an uploaded image, linking the upload to it.
+    pub async fn create(
+        &self,
+        shortcode: &str,
+        image_url: &str,
+        admin_id: i32,
+    ) -> AppResult<CustomEmojiModel> {
+        if !emoji::is_valid_shortcode(shortcode) {
+            return Err(AppError::Validation(
+                "Shortcode must be 2-32 lowercase letters, digits or underscores".to_string(),
+            ));
+        }
+        let created = custom_emoji::ActiveModel {
+            shortcode: Set(shortcode.to_string()),
+            image_url: Set(image_url.to_string()),
+            created_by: Set(Some(admin_id)),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        UploadService::link(&self.db, image_url, "emoji", created.id).await?;
+        self.reload().await?;
+        Ok(created)
+    }
+
+    /// Delete an emoji; its image is left to the upload cleanup job. Posts
+    /// using it show the plain `:shortcode:` again.
+    pub async fn delete(&self, id: i32) -> AppResult<()> {
+        let existing = CustomEmoji::find_by_id(id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        CustomEmoji::delete_by_id(id).exec(&self.db).await?;
+        UploadService::release(&self.db, &existing.image_url).await?;
+        self.reload().await
+    }
+
+    /// Shortcode to image URL for every emoji.
+    pub async fn load(&self) -> AppResult<EmojiCatalog> {
+        Ok(self
+            .list()
+            .await?
+            .into_iter()
+            .map(|e| (e.shortcode, e.image_url))
+            .collect())
+    }
+
+    /// Refresh the catalog of the community being served.
+    async fn reload(&self) -> AppResult<()> {
+        emoji::set_catalog(tenant::current(), self.load().await?);
+        Ok(())
+    }
+}
+
+/// Keeps one community's in-memory emoji catalog in step with its database.
+pub struct EmojiCatalogLoader {
+    db: DatabaseConnection,
+    tenant: Option<String>,
+}
+
+impl EmojiCatalogLoader {
+    /// `tenant` is the slug of the community `db` belongs to, `None` for the
+    /// default one.
+    pub fn new(db: DatabaseConnection, tenant: Option<String>) -> Self {
+        Self { db, tenant }
+    }
+
+    /// Load the catalog now and every reload interval until shutdown.
+    pub fn spawn_scheduler(self) -> tokio::task::JoinHandle<()> {
+        shutdown::spawn(async move {
+            let service = EmojiService::new(self.db);
+            let mut ticker = tokio::time::interval(RELOAD_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown::requested() => break,
+                }
+                match service.load().await {
+                    Ok(catalog) => emoji::set_catalog(self.tenant.clone(), catalog),
+                    Err(e) => tracing::warn!("Failed to load custom emoji: {}", e),
+                }
+            }
+        })
+    }
+}
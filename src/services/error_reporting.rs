@@ -0,0 +1,157 @@
+//! Error reporting to Sentry.
+//!
+//! `AppError::Internal` and `AppError::Database` responses and panics are
+//! sent to `SENTRY_DSN` with the request's route, request id and user id.
+//! Credentials are scrubbed from every event before it leaves the process.
+//! Without a DSN the client is disabled and capturing is a no-op.
+
+use crate::config::sentry::SentryConfig;
+use sentry::protocol::{Event, Value};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+const FILTERED: &str = "[Filtered]";
+
+/// Headers, query parameters and extra keys containing any of these are
+/// redacted.
+const SENSITIVE_KEYS: &[&str] = &[
+    "authorization",
+    "cookie",
+    "token",
+    "password",
+    "secret",
+    "sig",
+    "api_key",
+    "api-key",
+];
+
+/// Start the Sentry client. Keep the guard alive until shutdown so queued
+/// events are flushed.
+pub fn init(config: SentryConfig) -> Option<sentry::ClientInitGuard> {
+    let dsn = config.dsn?;
+    let guard = sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            environment: config.environment.map(Cow::Owned),
+            sample_rate: config.sample_rate,
+            send_default_pii: false,
+            before_send: Some(Arc::new(|event| Some(scrub_event(event)))),
+            ..Default::default()
+        },
+    ));
+    if guard.is_enabled() {
+        tracing::info!("Error reporting to Sentry enabled");
+        Some(guard)
+    } else {
+        tracing::warn!("Invalid SENTRY_DSN, error reporting disabled");
+        None
+    }
+}
+
+pub fn capture_internal(error: &anyhow::Error) {
+    sentry::integrations::anyhow::capture_anyhow(error);
+}
+
+pub fn capture_database(error: &sea_orm::DbErr) {
+    sentry::capture_error(error);
+}
+
+/// Tag the current request's scope with the authenticated user.
+pub fn set_user(user_id: &str) {
+    sentry::configure_scope(|scope| {
+        scope.set_user(Some(sentry::User {
+            id: Some(user_id.to_string()),
+            ..Default::default()
+        }));
+    });
+}
+
+fn is_sensitive(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS.iter().any(|s| key.contains(s))
+}
+
+/// Redact the values of sensitive `key=value` pairs in a query string.
+fn scrub_query(query: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _)) if is_sensitive(key) => format!("{}={}", key, FILTERED),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn scrub_event(mut event: Event<'static>) -> Event<'static> {
+    if let Some(request) = event.request.as_mut() {
+        for (name, value) in request.headers.iter_mut() {
+            if is_sensitive(name) {
+                *value = FILTERED.to_string();
+            }
+        }
+        if let Some(url) = request.url.as_mut() {
+            if let Some(query) = url.query().map(scrub_query) {
+                url.set_query(Some(&query));
+            }
+        }
+        if let Some(query) = request.query_string.as_mut() {
+            *query = scrub_query(query);
+        }
+        if request.cookies.is_some() {
+            request.cookies = Some(FILTERED.to_string());
+        }
+        // Request bodies carry passwords and tokens; never send them
+        if request.data.is_some() {
+            request.data = Some(FILTERED.to_string());
+        }
+    }
+    for (key, value) in event.extra.iter_mut() {
+        if is_sensitive(key) {
+            *value = Value::String(FILTERED.to_string());
+        }
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sentry::protocol::{Map, Request};
+
+    #[test]
+    fn test_scrub_event_redacts_credentials() {
+        let mut headers = Map::new();
+        headers.insert("Authorization".to_string(), "Bearer abc".to_string());
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+        let mut event = Event {
+            request: Some(Request {
+                url: "https://forum.test/ws?token=abc&page=2".parse().ok(),
+                query_string: Some("url=https://x&sig=deadbeef".to_string()),
+                data: Some("{\"password\":\"hunter2\"}".to_string()),
+                headers,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        event
+            .extra
+            .insert("refresh_token".to_string(), Value::from("abc"));
+
+        let event = scrub_event(event);
+        let request = event.request.unwrap();
+        assert_eq!(request.headers["Authorization"], FILTERED);
+        assert_eq!(request.headers["Content-Type"], "application/json");
+        assert_eq!(
+            request.url.unwrap().query(),
+            Some("token=[Filtered]&page=2")
+        );
+        assert_eq!(
+            request.query_string.as_deref(),
+            Some("url=https://x&sig=[Filtered]")
+        );
+        assert_eq!(request.data.as_deref(), Some(FILTERED));
+        assert_eq!(event.extra["refresh_token"], FILTERED);
+    }
+}
@@ -0,0 +1,330 @@
+//! Bulk exports of admin lists as CSV or JSON.
+//!
+//! Rows are read in id order in batches of `EXPORT_BATCH_SIZE` and encoded
+//! as they arrive, so an export of any size is streamed to the client
+//! without being held in memory or keeping a transaction open.
+
+use crate::error::AppError;
+use crate::models::{post, report, user, Post, Report, User};
+use axum::body::Bytes;
+use futures_util::{stream, Stream, StreamExt};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Rows fetched per query.
+pub const EXPORT_BATCH_SIZE: u64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportResource {
+    Users,
+    Posts,
+    Reports,
+}
+
+impl ExportResource {
+    pub const ALL: [ExportResource; 3] = [
+        ExportResource::Users,
+        ExportResource::Posts,
+        ExportResource::Reports,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportResource::Users => "users",
+            ExportResource::Posts => "posts",
+            ExportResource::Reports => "reports",
+        }
+    }
+
+    /// Exported fields, in CSV column order. Secrets such as password
+    /// hashes and verification tokens are never exported.
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            ExportResource::Users => &[
+                "id",
+                "username",
+                "email",
+                "role",
+                "karma",
+                "email_verified",
+                "email_undeliverable",
+                "locale",
+                "created_at",
+                "updated_at",
+            ],
+            ExportResource::Posts => &[
+                "id",
+                "user_id",
+                "forum_id",
+                "title",
+                "content",
+                "upvotes",
+                "downvotes",
+                "view_count",
+                "is_pinned",
+                "is_locked",
+                "is_hidden",
+                "created_at",
+                "updated_at",
+            ],
+            ExportResource::Reports => &[
+                "id",
+                "reporter_id",
+                "target_type",
+                "target_id",
+                "reason",
+                "description",
+                "status",
+                "action",
+                "resolution_note",
+                "resolved_by",
+                "resolved_at",
+                "created_at",
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    /// A single JSON array of objects
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+}
+
+/// Where a running export has got to.
+struct Cursor {
+    after_id: i32,
+    first: bool,
+    done: bool,
+}
+
+pub struct ExportService {
+    db: DatabaseConnection,
+}
+
+impl ExportService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// The whole resource encoded as `format`, oldest first. A database
+    /// error ends the stream early with that error.
+    pub fn stream(
+        self,
+        resource: ExportResource,
+        format: ExportFormat,
+    ) -> impl Stream<Item = Result<Bytes, AppError>> + Send + 'static {
+        let header = match format {
+            ExportFormat::Csv => csv_line(resource.columns().iter().map(|c| c.to_string())),
+            ExportFormat::Json => "[".to_string(),
+        };
+        let footer = match format {
+            ExportFormat::Csv => String::new(),
+            ExportFormat::Json => "]".to_string(),
+        };
+        let cursor = Cursor {
+            after_id: 0,
+            first: true,
+            done: false,
+        };
+
+        let rows = stream::unfold((self, cursor), move |(service, mut cursor)| async move {
+            if cursor.done {
+                return None;
+            }
+            let batch = match service.batch(resource, cursor.after_id).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    cursor.done = true;
+                    return Some((Err(e), (service, cursor)));
+                }
+            };
+            if batch.is_empty() {
+                return None;
+            }
+            cursor.done = (batch.len() as u64) < EXPORT_BATCH_SIZE;
+
+            let mut chunk = String::new();
+            for (id, row) in batch {
+                cursor.after_id = id;
+                match format {
+                    ExportFormat::Csv => chunk.push_str(&csv_row(resource.columns(), &row)),
+                    ExportFormat::Json => {
+                        if !cursor.first {
+                            chunk.push(',');
+                        }
+                        chunk.push_str(&row.to_string());
+                    }
+                }
+                cursor.first = false;
+            }
+            Some((Ok(Bytes::from(chunk)), (service, cursor)))
+        });
+
+        stream::once(async move { Ok(Bytes::from(header)) })
+            .chain(rows)
+            .chain(stream::once(async move { Ok(Bytes::from(footer)) }))
+    }
+
+    /// The next rows after `after_id` as `(id, exported fields)`.
+    async fn batch(
+        &self,
+        resource: ExportResource,
+        after_id: i32,
+    ) -> Result<Vec<(i32, Value)>, AppError> {
+        let rows = match resource {
+            ExportResource::Users => pick(
+                resource,
+                User::find()
+                    .filter(user::Column::Id.gt(after_id))
+                    .order_by_asc(user::Column::Id)
+                    .limit(EXPORT_BATCH_SIZE)
+                    .all(&self.db)
+                    .await?,
+                |u| u.id,
+            ),
+            ExportResource::Posts => pick(
+                resource,
+                Post::find()
+                    .filter(post::Column::Id.gt(after_id))
+                    .order_by_asc(post::Column::Id)
+                    .limit(EXPORT_BATCH_SIZE)
+                    .all(&self.db)
+                    .await?,
+                |p| p.id,
+            ),
+            ExportResource::Reports => pick(
+                resource,
+                Report::find()
+                    .filter(report::Column::Id.gt(after_id))
+                    .order_by_asc(report::Column::Id)
+                    .limit(EXPORT_BATCH_SIZE)
+                    .all(&self.db)
+                    .await?,
+                |r| r.id,
+            ),
+        };
+        Ok(rows)
+    }
+}
+
+/// Keep only the resource's exported fields of each model.
+fn pick<M: Serialize>(
+    resource: ExportResource,
+    models: Vec<M>,
+    id: impl Fn(&M) -> i32,
+) -> Vec<(i32, Value)> {
+    models
+        .into_iter()
+        .map(|m| {
+            let mut full = serde_json::to_value(&m).unwrap_or_default();
+            let row: serde_json::Map<String, Value> = resource
+                .columns()
+                .iter()
+                .map(|c| (c.to_string(), full[*c].take()))
+                .collect();
+            (id(&m), Value::Object(row))
+        })
+        .collect()
+}
+
+fn csv_row(columns: &[&str], row: &Value) -> String {
+    csv_line(columns.iter().map(|c| match &row[*c] {
+        Value::Null => String::new(),
+        Value::String(s) => neutralize_formula(s),
+        other => other.to_string(),
+    }))
+}
+
+/// One RFC 4180 line: fields containing commas, quotes or line breaks are
+/// quoted, with quotes doubled.
+fn csv_line(fields: impl Iterator<Item = String>) -> String {
+    let mut line = fields
+        .map(|f| {
+            if f.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
+
+/// User-written text starting like a spreadsheet formula is prefixed with
+/// `'` so opening the export can't run it.
+fn neutralize_formula(s: &str) -> String {
+    if s.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", s)
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_quoting() {
+        let line = csv_line(
+            ["plain", "a,b", "say \"hi\"", "two\nlines"]
+                .into_iter()
+                .map(String::from),
+        );
+        assert_eq!(line, "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\"\r\n");
+    }
+
+    #[test]
+    fn test_csv_row_neutralizes_formulas() {
+        let row = serde_json::json!({
+            "id": 1,
+            "title": "=HYPERLINK(\"x\")",
+            "karma": -3,
+            "bio": null
+        });
+        assert_eq!(
+            csv_row(&["id", "title", "karma", "bio"], &row),
+            "1,\"'=HYPERLINK(\"\"x\"\")\",-3,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_users_export_has_no_secrets() {
+        let columns = ExportResource::Users.columns();
+        assert!(!columns.contains(&"password_hash"));
+        assert!(!columns.contains(&"email_verification_token"));
+    }
+}
@@ -0,0 +1,1125 @@
+//! ActivityPub federation, publish-only for now.
+//!
+//! Users are `Person` actors and forums are `Group` actors, found through
+//! WebFinger. Remote servers can follow either; new posts are then delivered
+//! to the followers as a `Create` from the author and an `Announce` from the
+//! forum. Deliveries are queued in `federation_deliveries` and POSTed with
+//! HTTP signatures by a background worker, like the email outbox. Of what
+//! remote servers send, only follows and their undoing have an effect yet.
+
+use crate::config::federation::{FederationConfig, PostObjectType};
+use crate::config::seo::SeoConfig;
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    federation_actor, federation_delivery, federation_follower, federation_key, forum, post, user,
+    FederationActor, FederationActorModel, FederationDelivery, FederationDeliveryModel,
+    FederationFollower, FederationKey, FederationKeyModel, Forum, ForumModel, Post, PostModel,
+    User, UserModel,
+};
+use crate::services::image_proxy::public_addrs;
+use crate::services::seo::page_url;
+use crate::utils::http_signature::{self, Signature};
+use crate::utils::{render_markdown, shutdown, sql};
+use anyhow::{anyhow, bail};
+use axum::http::HeaderMap;
+use chrono::{NaiveDateTime, SecondsFormat};
+use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+pub const ACTIVITY_JSON: &str = "application/activity+json";
+pub const JRD_JSON: &str = "application/jrd+json";
+
+const ACTIVITY_STREAMS: &str = "https://www.w3.org/ns/activitystreams";
+const SECURITY: &str = "https://w3id.org/security/v1";
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+const KEY_BITS: usize = 2048;
+/// Most recent activities listed in an outbox.
+const OUTBOX_ITEMS: u64 = 20;
+/// Signed requests whose `Date` is further off than this are refused.
+const MAX_CLOCK_SKEW: chrono::Duration = chrono::Duration::hours(12);
+/// Largest remote actor document read.
+const MAX_DOCUMENT_BYTES: usize = 1024 * 1024;
+
+/// Deliveries claimed per worker pass.
+const BATCH_SIZE: u64 = 20;
+const RETRY_BASE: Duration = Duration::from_secs(60);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(6 * 3600);
+/// A `sending` delivery not updated for this long belongs to a worker that
+/// died mid-request, and is picked up again.
+const STALE_SENDING: Duration = Duration::from_secs(300);
+
+/// Kinds of local actor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActorKind {
+    User,
+    Forum,
+}
+
+impl ActorKind {
+    /// Stored in `federation_followers.actor_type`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActorKind::User => "user",
+            ActorKind::Forum => "forum",
+        }
+    }
+
+    fn path_segment(&self) -> &'static str {
+        match self {
+            ActorKind::User => "users",
+            ActorKind::Forum => "forums",
+        }
+    }
+}
+
+/// A local user or forum as an actor.
+enum LocalActor {
+    User(Box<UserModel>),
+    Forum(ForumModel),
+}
+
+impl LocalActor {
+    fn kind(&self) -> ActorKind {
+        match self {
+            LocalActor::User(_) => ActorKind::User,
+            LocalActor::Forum(_) => ActorKind::Forum,
+        }
+    }
+
+    fn id(&self) -> i32 {
+        match self {
+            LocalActor::User(user) => user.id,
+            LocalActor::Forum(forum) => forum.id,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            LocalActor::User(user) => &user.username,
+            LocalActor::Forum(forum) => &forum.slug,
+        }
+    }
+}
+
+pub struct FederationService {
+    db: DatabaseConnection,
+    config: FederationConfig,
+    /// Origin actors and objects are served on
+    api_url: String,
+    /// Origin of the human-readable pages they link to
+    site_url: String,
+}
+
+impl FederationService {
+    /// URLs come from `seo`, so a tenant's actors live on its own host.
+    pub fn new(db: DatabaseConnection, seo: &SeoConfig) -> Self {
+        Self {
+            db,
+            config: FederationConfig::from_env(),
+            api_url: seo.public_api_url.clone(),
+            site_url: seo.site_url.clone(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// The JRD for `acct:name@host`. Users and forums share the name space;
+    /// when both match, the forum is listed first.
+    pub async fn webfinger(&self, resource: &str) -> AppResult<Value> {
+        let account = resource.strip_prefix("acct:").unwrap_or(resource);
+        let (name, host) = account
+            .trim_start_matches('@')
+            .rsplit_once('@')
+            .ok_or_else(|| AppError::Validation("Expected acct:name@host".to_string()))?;
+        if !self.serves_host(host) {
+            return Err(AppError::NotFound);
+        }
+
+        let forum = self.find_forum(name).await?.map(LocalActor::Forum);
+        let user = self
+            .find_user(name)
+            .await?
+            .map(|user| LocalActor::User(Box::new(user)));
+        let actors: Vec<LocalActor> = forum.into_iter().chain(user).collect();
+        if actors.is_empty() {
+            return Err(AppError::NotFound);
+        }
+
+        let mut links = Vec::new();
+        for actor in &actors {
+            links.push(json!({
+                "rel": "self",
+                "type": ACTIVITY_JSON,
+                "href": self.actor_url(actor.kind(), actor.name()),
+            }));
+            links.push(json!({
+                "rel": "http://webfinger.net/rel/profile-page",
+                "type": "text/html",
+                "href": self.page(actor.kind().path_segment(), actor.name()),
+            }));
+        }
+        Ok(json!({
+            "subject": format!("acct:{}@{}", name, host.to_ascii_lowercase()),
+            "aliases": actors
+                .iter()
+                .map(|a| self.actor_url(a.kind(), a.name()))
+                .collect::<Vec<_>>(),
+            "links": links,
+        }))
+    }
+
+    pub async fn actor(&self, kind: ActorKind, name: &str) -> AppResult<Value> {
+        let actor = self.local_actor(kind, name).await?;
+        let key = self.instance_key().await?;
+        let url = self.actor_url(kind, name);
+        let (actor_type, display_name, summary, icon, published) = match &actor {
+            LocalActor::User(user) => (
+                "Person",
+                user.shown_name().to_string(),
+                user.bio.clone(),
+                user.avatar_url.clone(),
+                user.created_at,
+            ),
+            LocalActor::Forum(forum) => (
+                "Group",
+                forum.name.clone(),
+                Some(forum.description.clone()),
+                forum.icon_url.clone(),
+                forum.created_at,
+            ),
+        };
+
+        let mut document = json!({
+            "@context": [ACTIVITY_STREAMS, SECURITY],
+            "id": url,
+            "type": actor_type,
+            "preferredUsername": name,
+            "name": display_name,
+            "url": self.page(kind.path_segment(), name),
+            "inbox": format!("{}/inbox", url),
+            "outbox": format!("{}/outbox", url),
+            "followers": format!("{}/followers", url),
+            "endpoints": { "sharedInbox": self.shared_inbox_url() },
+            "manuallyApprovesFollowers": false,
+            "discoverable": true,
+            "published": rfc3339(published),
+            "publicKey": {
+                "id": key_id(&url),
+                "owner": url,
+                "publicKeyPem": key.public_key_pem,
+            },
+        });
+        if let Some(summary) = summary.filter(|s| !s.trim().is_empty()) {
+            document["summary"] = json!(render_markdown(&summary));
+        }
+        if let Some(icon) = icon.and_then(|icon| self.absolute(&icon)) {
+            document["icon"] = json!({ "type": "Image", "url": icon });
+        }
+        Ok(document)
+    }
+
+    /// The actor's latest activities: posts written by a user, or posts in a
+    /// forum announced by it.
+    pub async fn outbox(&self, kind: ActorKind, name: &str) -> AppResult<Value> {
+        let actor = self.local_actor(kind, name).await?;
+        let query = Post::find().filter(post::Column::IsHidden.eq(false));
+        let query = match &actor {
+            LocalActor::User(user) => query.filter(post::Column::UserId.eq(user.id)),
+            LocalActor::Forum(forum) => query.filter(post::Column::ForumId.eq(forum.id)),
+        };
+        let total = query.clone().count(&self.db).await?;
+        let posts = query
+            .order_by_desc(post::Column::Id)
+            .limit(OUTBOX_ITEMS)
+            .all(&self.db)
+            .await?;
+
+        let items = match &actor {
+            LocalActor::User(user) => {
+                let forums = self.forums_of(&posts).await?;
+                posts
+                    .iter()
+                    .filter_map(|post| {
+                        let forum = forums.get(&post.forum_id)?;
+                        Some(self.create_activity(post, user, forum))
+                    })
+                    .collect::<Vec<_>>()
+            }
+            LocalActor::Forum(forum) => posts
+                .iter()
+                .map(|post| self.announce_activity(post, forum))
+                .collect(),
+        };
+
+        Ok(json!({
+            "@context": ACTIVITY_STREAMS,
+            "id": format!("{}/outbox", self.actor_url(kind, name)),
+            "type": "OrderedCollection",
+            "totalItems": total,
+            "orderedItems": items,
+        }))
+    }
+
+    /// Follower count only; the followers themselves are not listed.
+    pub async fn followers(&self, kind: ActorKind, name: &str) -> AppResult<Value> {
+        let actor = self.local_actor(kind, name).await?;
+        let total = FederationFollower::find()
+            .filter(federation_follower::Column::ActorType.eq(kind.as_str()))
+            .filter(federation_follower::Column::LocalId.eq(actor.id()))
+            .count(&self.db)
+            .await?;
+        Ok(json!({
+            "@context": ACTIVITY_STREAMS,
+            "id": format!("{}/followers", self.actor_url(kind, name)),
+            "type": "OrderedCollection",
+            "totalItems": total,
+        }))
+    }
+
+    /// A post as an `Article` or `Note`.
+    pub async fn post_object(&self, id: i32) -> AppResult<Value> {
+        let post = Post::find_by_id(id)
+            .filter(post::Column::IsHidden.eq(false))
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let (author, forum) = self.post_context(&post).await?;
+        let mut object = self.object(&post, &author, &forum);
+        object["@context"] = json!(ACTIVITY_STREAMS);
+        Ok(object)
+    }
+
+    /// Queue delivery of a new post to the followers of its author and its
+    /// forum, once per inbox.
+    pub async fn publish_post(&self, post: &PostModel) -> AppResult<usize> {
+        let (author, forum) = self.post_context(post).await?;
+        let mut queued = 0;
+
+        let user_inboxes = self.follower_inboxes(ActorKind::User, author.id).await?;
+        if !user_inboxes.is_empty() {
+            let create = self.create_activity(post, &author, &forum);
+            let key = key_id(&self.actor_url(ActorKind::User, &author.username));
+            for inbox in &user_inboxes {
+                self.enqueue(inbox, &key, &create).await?;
+            }
+            queued += user_inboxes.len();
+        }
+
+        let forum_inboxes = self.follower_inboxes(ActorKind::Forum, forum.id).await?;
+        if !forum_inboxes.is_empty() {
+            let announce = self.announce_activity(post, &forum);
+            let key = key_id(&self.actor_url(ActorKind::Forum, &forum.slug));
+            for inbox in &forum_inboxes {
+                self.enqueue(inbox, &key, &announce).await?;
+            }
+            queued += forum_inboxes.len();
+        }
+        Ok(queued)
+    }
+
+    /// Handle a signed POST to an inbox. `path` is the request path and
+    /// query as signed by the sender.
+    pub async fn receive(&self, path: &str, headers: &HeaderMap, body: &[u8]) -> AppResult<()> {
+        let sender = self.verify_signature(path, headers, body).await?;
+        let activity: Value = serde_json::from_slice(body)
+            .map_err(|e| AppError::Validation(format!("Invalid activity: {}", e)))?;
+        // The signer may only speak for itself
+        if object_id(&activity["actor"]) != Some(sender.actor_id.as_str()) {
+            return Err(AppError::Forbidden);
+        }
+
+        match activity["type"].as_str() {
+            Some("Follow") => self.accept_follow(&sender, &activity).await,
+            Some("Undo") if activity["object"]["type"] == "Follow" => {
+                self.undo_follow(&sender, &activity["object"]).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn accept_follow(&self, sender: &FederationActorModel, follow: &Value) -> AppResult<()> {
+        let target = object_id(&follow["object"])
+            .and_then(|id| self.parse_actor_url(id))
+            .ok_or(AppError::NotFound)?;
+        let actor = self.local_actor(target.0, &target.1).await?;
+
+        let follower = federation_follower::ActiveModel {
+            actor_type: Set(actor.kind().as_str().to_string()),
+            local_id: Set(actor.id()),
+            follower_hash: Set(url_hash(&sender.actor_id)),
+            follower_id: Set(sender.actor_id.clone()),
+            inbox: Set(sender.inbox.clone()),
+            shared_inbox: Set(sender.shared_inbox.clone()),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        // A repeated follow is accepted again, but stored once
+        FederationFollower::insert(follower)
+            .on_conflict(
+                OnConflict::columns([
+                    federation_follower::Column::ActorType,
+                    federation_follower::Column::LocalId,
+                    federation_follower::Column::FollowerHash,
+                ])
+                .do_nothing_on([federation_follower::Column::FollowerHash])
+                .to_owned(),
+            )
+            .exec_without_returning(&self.db)
+            .await?;
+
+        let url = self.actor_url(actor.kind(), actor.name());
+        let accept = json!({
+            "@context": ACTIVITY_STREAMS,
+            "id": format!("{}#accepts/{}", url, uuid::Uuid::new_v4()),
+            "type": "Accept",
+            "actor": url,
+            "object": follow,
+        });
+        self.enqueue(&sender.inbox, &key_id(&url), &accept).await
+    }
+
+    async fn undo_follow(&self, sender: &FederationActorModel, follow: &Value) -> AppResult<()> {
+        let Some((kind, name)) =
+            object_id(&follow["object"]).and_then(|id| self.parse_actor_url(id))
+        else {
+            return Ok(());
+        };
+        let actor = match self.local_actor(kind, &name).await {
+            Ok(actor) => actor,
+            Err(AppError::NotFound) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        FederationFollower::delete_many()
+            .filter(federation_follower::Column::ActorType.eq(kind.as_str()))
+            .filter(federation_follower::Column::LocalId.eq(actor.id()))
+            .filter(federation_follower::Column::FollowerHash.eq(url_hash(&sender.actor_id)))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Check the request's signature, digest and date, returning the actor
+    /// that signed it.
+    async fn verify_signature(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> AppResult<FederationActorModel> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        let signature = header("signature")
+            .and_then(|h| Signature::parse(&h))
+            .ok_or(AppError::Unauthorized)?;
+        for required in ["(request-target)", "host", "date", "digest"] {
+            if !signature.headers.iter().any(|h| h == required) {
+                return Err(AppError::Unauthorized);
+            }
+        }
+
+        let digest = header("digest").ok_or(AppError::Unauthorized)?;
+        let expected = http_signature::digest(body);
+        let matches = digest.split(',').any(|d| {
+            d.trim()
+                .split_once('=')
+                .zip(expected.split_once('='))
+                .is_some_and(|((alg, v), (exp_alg, exp))| {
+                    alg.eq_ignore_ascii_case(exp_alg) && v == exp
+                })
+        });
+        if !matches {
+            return Err(AppError::Unauthorized);
+        }
+
+        let date = header("date")
+            .and_then(|d| chrono::DateTime::parse_from_rfc2822(&d).ok())
+            .ok_or(AppError::Unauthorized)?;
+        if (chrono::Utc::now() - date.to_utc()).abs() > MAX_CLOCK_SKEW {
+            return Err(AppError::Unauthorized);
+        }
+
+        let signed = http_signature::signing_string("POST", path, &signature.headers, header)
+            .ok_or(AppError::Unauthorized)?;
+        let owner = signature.key_id.split('#').next().unwrap_or_default();
+
+        let (mut actor, mut fresh) = self.remote_actor(owner, false).await.map_err(|e| {
+            tracing::info!("Could not fetch signing actor {}: {}", owner, e);
+            AppError::Unauthorized
+        })?;
+        loop {
+            let key = http_signature::public_key_from_pem(&actor.public_key_pem);
+            if key.is_some_and(|key| signature.verify(&key, &signed)) {
+                return Ok(actor);
+            }
+            if fresh {
+                return Err(AppError::Unauthorized);
+            }
+            // The key may have been rotated since it was cached
+            (actor, fresh) = self
+                .remote_actor(owner, true)
+                .await
+                .map_err(|_| AppError::Unauthorized)?;
+        }
+    }
+
+    /// A remote actor from the cache, or fetched when missing, expired or
+    /// `refresh` is set. The flag tells whether it was just fetched.
+    async fn remote_actor(
+        &self,
+        id: &str,
+        refresh: bool,
+    ) -> anyhow::Result<(FederationActorModel, bool)> {
+        let hash = url_hash(id);
+        let cached = FederationActor::find()
+            .filter(federation_actor::Column::ActorHash.eq(hash.as_str()))
+            .one(&self.db)
+            .await?;
+        let now = chrono::Utc::now().naive_utc();
+        if let Some(actor) = &cached {
+            let ttl = chrono::Duration::from_std(self.config.actor_ttl)?;
+            if !refresh && now - actor.fetched_at < ttl {
+                return Ok((actor.clone(), false));
+            }
+        }
+
+        let document = self.fetch_document(id).await?;
+        if document["id"].as_str() != Some(id) {
+            bail!("document id does not match {}", id);
+        }
+        let key = &document["publicKey"];
+        if key["owner"].as_str().is_some_and(|owner| owner != id) {
+            bail!("key of {} is owned by someone else", id);
+        }
+        let inbox = document["inbox"]
+            .as_str()
+            .ok_or_else(|| anyhow!("actor has no inbox"))?;
+        let public_key_pem = key["publicKeyPem"]
+            .as_str()
+            .ok_or_else(|| anyhow!("actor has no public key"))?;
+
+        let is_new = cached.is_none();
+        let mut active = match cached {
+            Some(actor) => actor.into(),
+            None => federation_actor::ActiveModel {
+                actor_hash: Set(hash),
+                actor_id: Set(id.to_string()),
+                ..Default::default()
+            },
+        };
+        active.inbox = Set(inbox.to_string());
+        active.shared_inbox = Set(document["endpoints"]["sharedInbox"]
+            .as_str()
+            .map(|s| s.to_string()));
+        active.public_key_pem = Set(public_key_pem.to_string());
+        active.fetched_at = Set(now);
+        let actor = if is_new {
+            active.insert(&self.db).await?
+        } else {
+            active.update(&self.db).await?
+        };
+        Ok((actor, true))
+    }
+
+    async fn fetch_document(&self, url: &str) -> anyhow::Result<Value> {
+        let url = url::Url::parse(url)?;
+        let addrs = public_addrs(&url).await?;
+        let client = reqwest::Client::builder()
+            .timeout(self.config.timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .user_agent("xjy-federation")
+            .resolve_to_addrs(url.host_str().unwrap_or_default(), &addrs)
+            .build()?;
+        let mut resp = client
+            .get(url)
+            .header(
+                reqwest::header::ACCEPT,
+                "application/activity+json, application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"",
+            )
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            bail!("status {}", resp.status());
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = resp.chunk().await? {
+            if body.len() + chunk.len() > MAX_DOCUMENT_BYTES {
+                bail!("document is too large");
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// The instance key pair, generated and stored on first use.
+    async fn instance_key(&self) -> AppResult<FederationKeyModel> {
+        if let Some(key) = FederationKey::find_by_id(1).one(&self.db).await? {
+            return Ok(key);
+        }
+        let (private_key_pem, public_key_pem) = tokio::task::spawn_blocking(generate_key_pair)
+            .await
+            .map_err(anyhow::Error::from)??;
+        let key = federation_key::ActiveModel {
+            id: Set(1),
+            private_key_pem: Set(private_key_pem),
+            public_key_pem: Set(public_key_pem),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+        };
+        // Another request may have generated one meanwhile; theirs is kept
+        FederationKey::insert(key)
+            .on_conflict(
+                OnConflict::column(federation_key::Column::Id)
+                    .do_nothing_on([federation_key::Column::Id])
+                    .to_owned(),
+            )
+            .exec_without_returning(&self.db)
+            .await?;
+        FederationKey::find_by_id(1)
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| AppError::Internal(anyhow!("federation key was not stored")))
+    }
+
+    async fn enqueue(&self, inbox: &str, key_id: &str, activity: &Value) -> AppResult<()> {
+        let now = chrono::Utc::now().naive_utc();
+        federation_delivery::ActiveModel {
+            inbox: Set(inbox.to_string()),
+            key_id: Set(key_id.to_string()),
+            activity: Set(activity.to_string()),
+            status: Set("pending".to_string()),
+            attempts: Set(0),
+            next_attempt_at: Set(now),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Inboxes of an actor's followers, preferring shared inboxes so each
+    /// server gets an activity once.
+    async fn follower_inboxes(
+        &self,
+        kind: ActorKind,
+        local_id: i32,
+    ) -> AppResult<BTreeSet<String>> {
+        let followers = FederationFollower::find()
+            .filter(federation_follower::Column::ActorType.eq(kind.as_str()))
+            .filter(federation_follower::Column::LocalId.eq(local_id))
+            .all(&self.db)
+            .await?;
+        Ok(followers
+            .into_iter()
+            .map(|f| f.shared_inbox.unwrap_or(f.inbox))
+            .collect())
+    }
+
+    fn create_activity(&self, post: &PostModel, author: &UserModel, forum: &ForumModel) -> Value {
+        let object = self.object(post, author, forum);
+        json!({
+            "@context": ACTIVITY_STREAMS,
+            "id": format!("{}#create", self.post_url(post.id)),
+            "type": "Create",
+            "actor": self.actor_url(ActorKind::User, &author.username),
+            "published": object["published"],
+            "to": object["to"],
+            "cc": object["cc"],
+            "object": object,
+        })
+    }
+
+    fn announce_activity(&self, post: &PostModel, forum: &ForumModel) -> Value {
+        let forum_url = self.actor_url(ActorKind::Forum, &forum.slug);
+        json!({
+            "@context": ACTIVITY_STREAMS,
+            "id": format!("{}#announce", self.post_url(post.id)),
+            "type": "Announce",
+            "actor": forum_url,
+            "published": rfc3339(post.created_at),
+            "to": [PUBLIC],
+            "cc": [format!("{}/followers", forum_url)],
+            "object": self.post_url(post.id),
+        })
+    }
+
+    fn object(&self, post: &PostModel, author: &UserModel, forum: &ForumModel) -> Value {
+        let author_url = self.actor_url(ActorKind::User, &author.username);
+        let forum_url = self.actor_url(ActorKind::Forum, &forum.slug);
+        let html = render_markdown(&post.content);
+        let post_type = self.config.post_type;
+
+        let mut object = json!({
+            "id": self.post_url(post.id),
+            "type": post_type.as_str(),
+            "attributedTo": author_url,
+            "audience": forum_url,
+            "url": self.page("posts", &post.id.to_string()),
+            "mediaType": "text/html",
+            "source": { "content": post.content, "mediaType": "text/markdown" },
+            "published": rfc3339(post.created_at),
+            "to": [PUBLIC],
+            "cc": [format!("{}/followers", author_url), forum_url],
+            "sensitive": false,
+        });
+        match post_type {
+            PostObjectType::Article => {
+                object["name"] = json!(post.title);
+                object["content"] = json!(html);
+            }
+            PostObjectType::Note => {
+                let title = html_escape(&post.title);
+                object["content"] = json!(format!("<p><strong>{}</strong></p>{}", title, html));
+            }
+        }
+        if post.updated_at > post.created_at {
+            object["updated"] = json!(rfc3339(post.updated_at));
+        }
+        if let Some(link) = &post.url {
+            object["attachment"] = json!([{ "type": "Link", "href": link }]);
+        }
+        object
+    }
+
+    async fn post_context(&self, post: &PostModel) -> AppResult<(UserModel, ForumModel)> {
+        let author = User::find_by_id(post.user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let forum = Forum::find_by_id(post.forum_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        Ok((author, forum))
+    }
+
+    async fn forums_of(&self, posts: &[PostModel]) -> AppResult<HashMap<i32, ForumModel>> {
+        let ids: BTreeSet<i32> = posts.iter().map(|p| p.forum_id).collect();
+        Ok(Forum::find()
+            .filter(forum::Column::Id.is_in(ids))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|f| (f.id, f))
+            .collect())
+    }
+
+    async fn local_actor(&self, kind: ActorKind, name: &str) -> AppResult<LocalActor> {
+        let actor = match kind {
+            ActorKind::User => self
+                .find_user(name)
+                .await?
+                .map(|user| LocalActor::User(Box::new(user))),
+            ActorKind::Forum => self.find_forum(name).await?.map(LocalActor::Forum),
+        };
+        actor.ok_or(AppError::NotFound)
+    }
+
+    /// Banned users are not federated.
+    async fn find_user(&self, username: &str) -> AppResult<Option<UserModel>> {
+        Ok(User::find()
+            .filter(user::Column::Username.eq(username))
+            .filter(user::Column::Role.ne("banned"))
+            .one(&self.db)
+            .await?)
+    }
+
+    async fn find_forum(&self, slug: &str) -> AppResult<Option<ForumModel>> {
+        Ok(Forum::find()
+            .filter(forum::Column::Slug.eq(slug))
+            .one(&self.db)
+            .await?)
+    }
+
+    fn actor_url(&self, kind: ActorKind, name: &str) -> String {
+        page_url(
+            &self.api_url,
+            &[
+                "ap".to_string(),
+                kind.path_segment().to_string(),
+                name.to_string(),
+            ],
+        )
+    }
+
+    /// Inverse of [`Self::actor_url`].
+    fn parse_actor_url(&self, url: &str) -> Option<(ActorKind, String)> {
+        let base = page_url(&self.api_url, &["ap".to_string()]);
+        let rest = url.strip_prefix(&base)?.strip_prefix('/')?;
+        let (kind, name) = rest.split_once('/')?;
+        let kind = [ActorKind::User, ActorKind::Forum]
+            .into_iter()
+            .find(|k| k.path_segment() == kind)?;
+        let name = percent_encoding::percent_decode_str(name)
+            .decode_utf8()
+            .ok()?;
+        (!name.is_empty() && !name.contains('/')).then(|| (kind, name.into_owned()))
+    }
+
+    fn post_url(&self, id: i32) -> String {
+        page_url(
+            &self.api_url,
+            &["ap".to_string(), "posts".to_string(), id.to_string()],
+        )
+    }
+
+    fn shared_inbox_url(&self) -> String {
+        page_url(&self.api_url, &["ap".to_string(), "inbox".to_string()])
+    }
+
+    fn page(&self, section: &str, name: &str) -> String {
+        page_url(&self.site_url, &[section.to_string(), name.to_string()])
+    }
+
+    fn absolute(&self, url: &str) -> Option<String> {
+        let url = url::Url::parse(&self.api_url).ok()?.join(url).ok()?;
+        matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+    }
+
+    /// Whether `host` names this server in WebFinger resources: the API's
+    /// host or the site's, with the port when it isn't the default.
+    fn serves_host(&self, host: &str) -> bool {
+        [&self.api_url, &self.site_url].into_iter().any(|origin| {
+            url::Url::parse(origin).is_ok_and(|origin| {
+                let authority = match (origin.host_str(), origin.port()) {
+                    (Some(h), Some(port)) => format!("{}:{}", h, port),
+                    (Some(h), None) => h.to_string(),
+                    _ => return false,
+                };
+                authority.eq_ignore_ascii_case(host)
+            })
+        })
+    }
+}
+
+/// Sends queued deliveries with the instance key.
+#[derive(Clone)]
+pub struct DeliveryQueue {
+    db: DatabaseConnection,
+    config: FederationConfig,
+}
+
+impl DeliveryQueue {
+    pub fn new(db: DatabaseConnection, config: FederationConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// Deliver due activities every poll interval until shutdown.
+    pub fn spawn_worker(self) -> tokio::task::JoinHandle<()> {
+        shutdown::spawn(async move {
+            loop {
+                if let Err(e) = self.process_queue().await {
+                    tracing::warn!("Failed to process federation deliveries: {e}");
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(self.config.poll_interval) => {}
+                    _ = shutdown::requested() => break,
+                }
+            }
+        })
+    }
+
+    /// Deliver everything due now, returning how many were attempted.
+    pub async fn process_queue(&self) -> anyhow::Result<usize> {
+        let Some(key) = FederationKey::find_by_id(1).one(&self.db).await? else {
+            // Nothing was ever published or accepted without a key
+            return Ok(0);
+        };
+        let key = RsaPrivateKey::from_pkcs8_pem(&key.private_key_pem)?;
+        let mut attempted = 0;
+        loop {
+            let batch = self.claim_due().await?;
+            let claimed = batch.len();
+            for delivery in batch {
+                self.deliver(&key, delivery).await?;
+            }
+            attempted += claimed;
+            if (claimed as u64) < BATCH_SIZE {
+                return Ok(attempted);
+            }
+        }
+    }
+
+    /// Mark a batch of due deliveries as `sending` and return them, the same
+    /// way the email queue claims emails.
+    async fn claim_due(&self) -> anyhow::Result<Vec<FederationDeliveryModel>> {
+        let now = chrono::Utc::now().naive_utc();
+        let stale = now - chrono::Duration::from_std(STALE_SENDING)?;
+        let backend = self.db.get_database_backend();
+        let due = format!(
+            "SELECT id FROM federation_deliveries
+                WHERE (status = 'pending' AND next_attempt_at <= $1)
+                   OR (status = 'sending' AND updated_at < $2)
+                ORDER BY id
+                LIMIT $3
+                {}",
+            sql::skip_locked(backend)
+        );
+        let values = [now.into(), stale.into(), (BATCH_SIZE as i64).into()];
+        if backend == DbBackend::MySql {
+            return self.claim_due_mysql(&due, values, now).await;
+        }
+
+        let query = format!(
+            "UPDATE federation_deliveries
+            SET status = 'sending', attempts = attempts + 1, updated_at = $1
+            WHERE id IN ({due})
+            RETURNING *"
+        );
+        Ok(FederationDelivery::find()
+            .from_raw_sql(sql::statement(backend, query, values))
+            .all(&self.db)
+            .await?)
+    }
+
+    async fn claim_due_mysql(
+        &self,
+        due: &str,
+        values: [sea_orm::Value; 3],
+        now: NaiveDateTime,
+    ) -> anyhow::Result<Vec<FederationDeliveryModel>> {
+        let txn = self.db.begin().await?;
+        let ids = txn
+            .query_all(sql::statement(DbBackend::MySql, due, values))
+            .await?
+            .iter()
+            .map(|row| row.try_get_by_index::<i32>(0))
+            .collect::<Result<Vec<_>, _>>()?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        FederationDelivery::update_many()
+            .col_expr(federation_delivery::Column::Status, Expr::value("sending"))
+            .col_expr(
+                federation_delivery::Column::Attempts,
+                Expr::col(federation_delivery::Column::Attempts).add(1),
+            )
+            .col_expr(federation_delivery::Column::UpdatedAt, Expr::value(now))
+            .filter(federation_delivery::Column::Id.is_in(ids.clone()))
+            .exec(&txn)
+            .await?;
+        let deliveries = FederationDelivery::find()
+            .filter(federation_delivery::Column::Id.is_in(ids))
+            .order_by_asc(federation_delivery::Column::Id)
+            .all(&txn)
+            .await?;
+        txn.commit().await?;
+        Ok(deliveries)
+    }
+
+    /// Send one claimed delivery and record the outcome.
+    async fn deliver(
+        &self,
+        key: &RsaPrivateKey,
+        delivery: FederationDeliveryModel,
+    ) -> anyhow::Result<()> {
+        let result = self.post(key, &delivery).await;
+        let now = chrono::Utc::now().naive_utc();
+        let attempts = delivery.attempts;
+        let id = delivery.id;
+        let mut active: federation_delivery::ActiveModel = delivery.into();
+        active.updated_at = Set(now);
+        match result {
+            Ok(()) => {
+                active.status = Set("delivered".to_string());
+                active.delivered_at = Set(Some(now));
+                active.last_error = Set(None);
+            }
+            Err(DeliveryError::Rejected(e)) => {
+                tracing::info!("Federation delivery {id} was rejected: {e}");
+                active.status = Set("failed".to_string());
+                active.last_error = Set(Some(e));
+            }
+            Err(DeliveryError::Transient(e)) => {
+                if attempts >= self.config.max_attempts {
+                    tracing::warn!(
+                        "Giving up on federation delivery {id} after {attempts} attempts: {e}"
+                    );
+                    active.status = Set("failed".to_string());
+                } else {
+                    let delay = retry_delay(attempts);
+                    active.status = Set("pending".to_string());
+                    active.next_attempt_at = Set(now + chrono::Duration::from_std(delay)?);
+                }
+                active.last_error = Set(Some(e));
+            }
+        }
+        active.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn post(
+        &self,
+        key: &RsaPrivateKey,
+        delivery: &FederationDeliveryModel,
+    ) -> Result<(), DeliveryError> {
+        let url = url::Url::parse(&delivery.inbox)
+            .map_err(|e| DeliveryError::Rejected(format!("invalid inbox: {}", e)))?;
+        let addrs = public_addrs(&url)
+            .await
+            .map_err(|e| DeliveryError::Rejected(e.to_string()))?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let body = delivery.activity.clone().into_bytes();
+        let date = http_signature::http_date(chrono::Utc::now());
+        let digest = http_signature::digest(&body);
+        let signature = http_signature::sign(
+            key,
+            &delivery.key_id,
+            "POST",
+            &path,
+            &[("host", &host), ("date", &date), ("digest", &digest)],
+        );
+
+        let client = reqwest::Client::builder()
+            .timeout(self.config.timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .user_agent("xjy-federation")
+            .resolve_to_addrs(url.host_str().unwrap_or_default(), &addrs)
+            .build()
+            .map_err(|e| DeliveryError::Transient(e.to_string()))?;
+        let resp = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, ACTIVITY_JSON)
+            .header("date", date)
+            .header("digest", digest)
+            .header("signature", signature)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| DeliveryError::Transient(e.to_string()))?;
+
+        let status = resp.status();
+        if status.is_success() {
+            Ok(())
+        } else if status.is_client_error()
+            && status != reqwest::StatusCode::REQUEST_TIMEOUT
+            && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+        {
+            Err(DeliveryError::Rejected(format!("status {}", status)))
+        } else {
+            Err(DeliveryError::Transient(format!("status {}", status)))
+        }
+    }
+}
+
+enum DeliveryError {
+    /// Retrying won't help: bad inbox or refused by the server
+    Rejected(String),
+    Transient(String),
+}
+
+fn generate_key_pair() -> anyhow::Result<(String, String)> {
+    let key = RsaPrivateKey::new(&mut rsa::rand_core::OsRng, KEY_BITS)?;
+    let private = key.to_pkcs8_pem(LineEnding::LF)?.to_string();
+    let public = RsaPublicKey::from(&key).to_public_key_pem(LineEnding::LF)?;
+    Ok((private, public))
+}
+
+fn key_id(actor_url: &str) -> String {
+    format!("{}#main-key", actor_url)
+}
+
+/// The id of an object given inline or by reference.
+fn object_id(value: &Value) -> Option<&str> {
+    value.as_str().or_else(|| value["id"].as_str())
+}
+
+fn url_hash(url: &str) -> String {
+    Sha256::digest(url.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn rfc3339(at: NaiveDateTime) -> String {
+    at.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Delay before the next attempt after `attempts` failures.
+fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    RETRY_BASE
+        .saturating_mul(2u32.pow(exponent))
+        .min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(api_url: &str) -> FederationService {
+        let mut seo = SeoConfig::from_env();
+        seo.site_url = "https://forum.test".to_string();
+        seo.public_api_url = api_url.to_string();
+        FederationService::new(DatabaseConnection::Disconnected, &seo)
+    }
+
+    #[test]
+    fn actor_urls_round_trip() {
+        let service = service("https://api.forum.test");
+        let url = service.actor_url(ActorKind::Forum, "rust lang");
+        assert_eq!(url, "https://api.forum.test/ap/forums/rust%20lang");
+        assert_eq!(
+            service.parse_actor_url(&url),
+            Some((ActorKind::Forum, "rust lang".to_string()))
+        );
+        assert_eq!(
+            service.parse_actor_url("https://api.forum.test/ap/users/alice"),
+            Some((ActorKind::User, "alice".to_string()))
+        );
+        for foreign in [
+            "https://elsewhere.test/ap/users/alice",
+            "https://api.forum.test/ap/posts/1",
+            "https://api.forum.test/ap/users/alice/outbox",
+            "https://api.forum.test/ap/users/",
+        ] {
+            assert_eq!(service.parse_actor_url(foreign), None, "{}", foreign);
+        }
+    }
+
+    #[test]
+    fn webfinger_hosts_include_ports() {
+        let service = service("http://localhost:8080");
+        assert!(service.serves_host("localhost:8080"));
+        assert!(service.serves_host("FORUM.test"));
+        assert!(!service.serves_host("localhost"));
+    }
+
+    #[test]
+    fn retries_back_off_up_to_a_limit() {
+        assert_eq!(retry_delay(1), Duration::from_secs(60));
+        assert_eq!(retry_delay(3), Duration::from_secs(240));
+        assert_eq!(retry_delay(30), MAX_RETRY_DELAY);
+    }
+}
@@ -0,0 +1,141 @@
+//! Post flair: labels each forum sets up for its posts to be filed under.
+//!
+//! Admins manage a forum's flairs; authors pick one of them when they create
+//! or edit a post, and forum listings can be filtered by it.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{post_flair, Forum, PostFlair, PostFlairModel};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+};
+use std::collections::HashMap;
+
+/// Flair colors are `#rrggbb` hex codes.
+fn validate_color(color: Option<&str>) -> AppResult<()> {
+    let valid = match color {
+        Some(c) => {
+            c.len() == 7 && c.starts_with('#') && c[1..].chars().all(|ch| ch.is_ascii_hexdigit())
+        }
+        None => true,
+    };
+    if !valid {
+        return Err(AppError::Validation(
+            "Flair color must be a hex code like #1f6feb".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub struct FlairService {
+    db: DatabaseConnection,
+}
+
+impl FlairService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// The forum's flairs by name.
+    pub async fn list(&self, forum_id: i32) -> AppResult<Vec<PostFlairModel>> {
+        let flairs = PostFlair::find()
+            .filter(post_flair::Column::ForumId.eq(forum_id))
+            .order_by_asc(post_flair::Column::Name)
+            .all(&self.db)
+            .await?;
+        Ok(flairs)
+    }
+
+    /// Flairs by ID, for showing on posts.
+    pub async fn get_many(&self, ids: &[i32]) -> AppResult<HashMap<i32, PostFlairModel>> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let flairs = PostFlair::find()
+            .filter(post_flair::Column::Id.is_in(ids.iter().copied()))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|f| (f.id, f))
+            .collect();
+        Ok(flairs)
+    }
+
+    pub async fn create(
+        &self,
+        forum_id: i32,
+        name: &str,
+        color: Option<String>,
+    ) -> AppResult<PostFlairModel> {
+        validate_color(color.as_deref())?;
+        Forum::find_by_id(forum_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        let flair = post_flair::ActiveModel {
+            forum_id: Set(forum_id),
+            name: Set(name.to_string()),
+            color: Set(color),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(flair)
+    }
+
+    pub async fn update(
+        &self,
+        id: i32,
+        name: &str,
+        color: Option<String>,
+    ) -> AppResult<PostFlairModel> {
+        validate_color(color.as_deref())?;
+        let existing = PostFlair::find_by_id(id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        let mut active: post_flair::ActiveModel = existing.into();
+        active.name = Set(name.to_string());
+        active.color = Set(color);
+        Ok(active.update(&self.db).await?)
+    }
+
+    /// Delete a flair; posts that had it are left without one.
+    pub async fn delete(&self, id: i32) -> AppResult<()> {
+        let result = PostFlair::delete_by_id(id).exec(&self.db).await?;
+        if result.rows_affected == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Check that a post in `forum_id` may use `flair_id`.
+    pub async fn check(&self, forum_id: i32, flair_id: Option<i32>) -> AppResult<()> {
+        let Some(flair_id) = flair_id else {
+            return Ok(());
+        };
+        let flair = PostFlair::find_by_id(flair_id).one(&self.db).await?;
+        match flair {
+            Some(f) if f.forum_id == forum_id => Ok(()),
+            _ => Err(AppError::Validation(
+                "Flair is not available in this forum".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_color;
+
+    #[test]
+    fn test_flair_colors_are_hex_codes() {
+        assert!(validate_color(None).is_ok());
+        assert!(validate_color(Some("#1f6FEB")).is_ok());
+        assert!(validate_color(Some("1f6feb")).is_err());
+        assert!(validate_color(Some("#1f6fe")).is_err());
+        assert!(validate_color(Some("#zzzzzz")).is_err());
+    }
+}
@@ -1,12 +1,39 @@
 use crate::{
     error::{AppError, AppResult},
-    models::{follow, user, Follow, User, UserModel},
+    models::{
+        follow, follow_request, user, Follow, FollowRequest, FollowRequestModel, User, UserModel,
+    },
+    utils::sql,
 };
 use sea_orm::{
     ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
-    QueryOrder, Statement,
+    QueryOrder,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// Where a follow stands after following or unfollowing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowStatus {
+    NotFollowing,
+    /// Waiting for a private user's approval. `new` is false when the
+    /// request was already pending.
+    Requested {
+        request_id: i32,
+        new: bool,
+    },
+    Following,
+}
+
+/// How a viewer and another user follow each other.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FollowRelation {
+    /// The user follows the viewer
+    pub follows_you: bool,
+    /// The viewer follows the user
+    pub followed_by_you: bool,
+    /// The viewer asked to follow the private user and awaits approval
+    pub requested_by_you: bool,
+}
 
 pub struct FollowService {
     db: DatabaseConnection,
@@ -17,29 +44,26 @@ impl FollowService {
         Self { db }
     }
 
-    pub async fn follow(&self, follower_id: i32, following_id: i32) -> AppResult<bool> {
+    /// Follow a user, or ask to if their profile is private.
+    pub async fn follow(&self, follower_id: i32, following_id: i32) -> AppResult<FollowStatus> {
         if follower_id == following_id {
             return Err(AppError::Validation("Cannot follow yourself".to_string()));
         }
 
-        User::find_by_id(following_id)
+        let target = User::find_by_id(following_id)
             .one(&self.db)
             .await?
             .ok_or(AppError::NotFound)?;
 
-        self.db
-            .execute(Statement::from_sql_and_values(
-                sea_orm::DatabaseBackend::Postgres,
-                "INSERT INTO follows (follower_id, following_id, created_at)
-                 VALUES ($1, $2, NOW())
-                 ON CONFLICT (follower_id, following_id) DO NOTHING",
-                vec![follower_id.into(), following_id.into()],
-            ))
-            .await?;
-        Ok(true)
+        if target.is_private && !self.is_following(follower_id, following_id).await? {
+            return self.request(follower_id, following_id).await;
+        }
+        self.insert_follow(follower_id, following_id).await?;
+        Ok(FollowStatus::Following)
     }
 
-    pub async fn unfollow(&self, follower_id: i32, following_id: i32) -> AppResult<bool> {
+    /// Stop following a user, or withdraw a pending request to.
+    pub async fn unfollow(&self, follower_id: i32, following_id: i32) -> AppResult<FollowStatus> {
         if follower_id == following_id {
             return Err(AppError::Validation("Cannot unfollow yourself".to_string()));
         }
@@ -49,12 +73,16 @@ impl FollowService {
             .filter(follow::Column::FollowingId.eq(following_id))
             .exec(&self.db)
             .await?;
-        Ok(false)
+        FollowRequest::delete_many()
+            .filter(follow_request::Column::RequesterId.eq(follower_id))
+            .filter(follow_request::Column::TargetId.eq(following_id))
+            .exec(&self.db)
+            .await?;
+        Ok(FollowStatus::NotFollowing)
     }
 
-    /// Toggle follow: if exists -> unfollow, if not -> follow.
-    /// Returns true if now following, false if unfollowed.
-    pub async fn toggle(&self, follower_id: i32, following_id: i32) -> AppResult<bool> {
+    /// Toggle follow: following or requested -> unfollow, otherwise follow.
+    pub async fn toggle(&self, follower_id: i32, following_id: i32) -> AppResult<FollowStatus> {
         if follower_id == following_id {
             return Err(AppError::Validation("Cannot follow yourself".to_string()));
         }
@@ -65,19 +93,172 @@ impl FollowService {
             .await?
             .ok_or(AppError::NotFound)?;
 
-        let existing = Follow::find()
-            .filter(follow::Column::FollowerId.eq(follower_id))
-            .filter(follow::Column::FollowingId.eq(following_id))
+        let requested = FollowRequest::find()
+            .filter(follow_request::Column::RequesterId.eq(follower_id))
+            .filter(follow_request::Column::TargetId.eq(following_id))
             .one(&self.db)
-            .await?;
+            .await?
+            .is_some();
 
-        if existing.is_some() {
+        if requested || self.is_following(follower_id, following_id).await? {
             self.unfollow(follower_id, following_id).await
         } else {
             self.follow(follower_id, following_id).await
         }
     }
 
+    async fn is_following(&self, follower_id: i32, following_id: i32) -> AppResult<bool> {
+        Ok(Follow::find()
+            .filter(follow::Column::FollowerId.eq(follower_id))
+            .filter(follow::Column::FollowingId.eq(following_id))
+            .one(&self.db)
+            .await?
+            .is_some())
+    }
+
+    async fn insert_follow(&self, follower_id: i32, following_id: i32) -> AppResult<()> {
+        let backend = self.db.get_database_backend();
+        self.db
+            .execute(sql::statement(
+                backend,
+                format!(
+                    "INSERT INTO follows (follower_id, following_id, created_at)
+                     VALUES ($1, $2, $3) {}",
+                    sql::upsert(backend, &["follower_id", "following_id"], &[])
+                ),
+                vec![
+                    follower_id.into(),
+                    following_id.into(),
+                    chrono::Utc::now().naive_utc().into(),
+                ],
+            ))
+            .await?;
+        Ok(())
+    }
+
+    async fn request(&self, requester_id: i32, target_id: i32) -> AppResult<FollowStatus> {
+        let backend = self.db.get_database_backend();
+        let inserted = self
+            .db
+            .execute(sql::statement(
+                backend,
+                format!(
+                    "INSERT INTO follow_requests (requester_id, target_id, created_at)
+                     VALUES ($1, $2, $3) {}",
+                    sql::upsert(backend, &["requester_id", "target_id"], &[])
+                ),
+                vec![
+                    requester_id.into(),
+                    target_id.into(),
+                    chrono::Utc::now().naive_utc().into(),
+                ],
+            ))
+            .await?
+            .rows_affected();
+
+        let request = FollowRequest::find()
+            .filter(follow_request::Column::RequesterId.eq(requester_id))
+            .filter(follow_request::Column::TargetId.eq(target_id))
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        Ok(FollowStatus::Requested {
+            request_id: request.id,
+            new: inserted > 0,
+        })
+    }
+
+    /// Pending requests to follow `target_id`, newest first, with the
+    /// requesting users.
+    pub async fn list_requests(
+        &self,
+        target_id: i32,
+        page: u64,
+        per_page: u64,
+    ) -> AppResult<(Vec<(FollowRequestModel, UserModel)>, u64)> {
+        let paginator = FollowRequest::find()
+            .filter(follow_request::Column::TargetId.eq(target_id))
+            .order_by_desc(follow_request::Column::CreatedAt)
+            .paginate(&self.db, per_page);
+
+        let total = paginator.num_items().await?;
+        let requests = paginator.fetch_page(page.saturating_sub(1)).await?;
+        if requests.is_empty() {
+            return Ok((vec![], total));
+        }
+
+        let user_ids: Vec<i32> = requests.iter().map(|r| r.requester_id).collect();
+        let mut users: HashMap<i32, UserModel> = User::find()
+            .filter(user::Column::Id.is_in(user_ids))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|u| (u.id, u))
+            .collect();
+        let items = requests
+            .into_iter()
+            .filter_map(|r| users.remove(&r.requester_id).map(|u| (r, u)))
+            .collect();
+
+        Ok((items, total))
+    }
+
+    /// Approve a pending request to follow `target_id`; the requester
+    /// becomes a follower.
+    pub async fn approve_request(
+        &self,
+        target_id: i32,
+        request_id: i32,
+    ) -> AppResult<FollowRequestModel> {
+        let request = self.take_request(target_id, request_id).await?;
+        self.insert_follow(request.requester_id, target_id).await?;
+        Ok(request)
+    }
+
+    /// Deny a pending request to follow `target_id`.
+    pub async fn deny_request(
+        &self,
+        target_id: i32,
+        request_id: i32,
+    ) -> AppResult<FollowRequestModel> {
+        self.take_request(target_id, request_id).await
+    }
+
+    /// Approve every pending request to follow `target_id`, e.g. when the
+    /// profile is made public. Returns the requesters.
+    pub async fn approve_all_requests(&self, target_id: i32) -> AppResult<Vec<i32>> {
+        let requests = FollowRequest::find()
+            .filter(follow_request::Column::TargetId.eq(target_id))
+            .all(&self.db)
+            .await?;
+        let mut requesters = Vec::with_capacity(requests.len());
+        for request in requests {
+            let deleted = FollowRequest::delete_by_id(request.id)
+                .exec(&self.db)
+                .await?
+                .rows_affected;
+            // Skip requests withdrawn or decided meanwhile
+            if deleted > 0 {
+                self.insert_follow(request.requester_id, target_id).await?;
+                requesters.push(request.requester_id);
+            }
+        }
+        Ok(requesters)
+    }
+
+    /// Delete a pending request to follow `target_id`, returning it.
+    async fn take_request(&self, target_id: i32, request_id: i32) -> AppResult<FollowRequestModel> {
+        let request = FollowRequest::find_by_id(request_id)
+            .filter(follow_request::Column::TargetId.eq(target_id))
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        FollowRequest::delete_by_id(request.id)
+            .exec(&self.db)
+            .await?;
+        Ok(request)
+    }
+
     /// List users who follow the given user (followers of user_id).
     pub async fn list_followers(
         &self,
@@ -146,4 +327,52 @@ impl FollowService {
 
         Ok((ordered, total))
     }
+
+    /// The follow relation between `viewer_id` and each of `user_ids`.
+    pub async fn relations(
+        &self,
+        viewer_id: i32,
+        user_ids: &[i32],
+    ) -> AppResult<HashMap<i32, FollowRelation>> {
+        if user_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let followers: HashSet<i32> = Follow::find()
+            .filter(follow::Column::FollowingId.eq(viewer_id))
+            .filter(follow::Column::FollowerId.is_in(user_ids.to_vec()))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|f| f.follower_id)
+            .collect();
+        let following: HashSet<i32> = Follow::find()
+            .filter(follow::Column::FollowerId.eq(viewer_id))
+            .filter(follow::Column::FollowingId.is_in(user_ids.to_vec()))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|f| f.following_id)
+            .collect();
+        let requested: HashSet<i32> = FollowRequest::find()
+            .filter(follow_request::Column::RequesterId.eq(viewer_id))
+            .filter(follow_request::Column::TargetId.is_in(user_ids.to_vec()))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|r| r.target_id)
+            .collect();
+
+        Ok(user_ids
+            .iter()
+            .map(|&id| {
+                let relation = FollowRelation {
+                    follows_you: followers.contains(&id),
+                    followed_by_you: following.contains(&id),
+                    requested_by_you: requested.contains(&id),
+                };
+                (id, relation)
+            })
+            .collect())
+    }
 }
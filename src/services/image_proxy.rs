@@ -0,0 +1,305 @@
+use crate::error::{AppError, AppResult};
+use crate::utils::url_sign::{decode_image_proxy_url, url_signing_secret, verify_url_signature};
+use dashmap::DashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Only raster formats are proxied; SVG can carry script.
+const ALLOWED_CONTENT_TYPES: &[&str] = &[
+    "image/jpeg",
+    "image/png",
+    "image/gif",
+    "image/webp",
+    "image/avif",
+];
+
+const MAX_CACHE_ENTRIES: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct ImageProxyConfig {
+    pub enabled: bool,
+    pub max_bytes: usize,
+    pub cache_ttl: Duration,
+    pub timeout: Duration,
+}
+
+impl ImageProxyConfig {
+    pub fn from_env() -> Self {
+        let enabled = std::env::var("IMAGE_PROXY_ENABLED")
+            .map(|v| {
+                matches!(
+                    v.trim().to_ascii_lowercase().as_str(),
+                    "1" | "true" | "yes" | "y" | "on"
+                )
+            })
+            .unwrap_or(false);
+
+        let max_bytes = std::env::var("IMAGE_PROXY_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5 * 1024 * 1024);
+
+        let cache_seconds: u64 = std::env::var("IMAGE_PROXY_CACHE_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86400);
+
+        let timeout_seconds: u64 = std::env::var("IMAGE_PROXY_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+
+        Self {
+            enabled,
+            max_bytes,
+            cache_ttl: Duration::from_secs(cache_seconds),
+            timeout: Duration::from_secs(timeout_seconds),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ProxiedImage {
+    pub content_type: String,
+    pub data: Arc<Vec<u8>>,
+    fetched_at: Instant,
+}
+
+/// Fetches remote images on behalf of readers so their IPs are not leaked to
+/// third-party hosts, with a small in-memory cache.
+#[derive(Clone)]
+pub struct ImageProxy {
+    config: ImageProxyConfig,
+    client: reqwest::Client,
+    cache: Arc<DashMap<String, ProxiedImage>>,
+}
+
+impl ImageProxy {
+    pub fn new(config: ImageProxyConfig) -> Self {
+        // No redirects: every hop would need its own private-address check.
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .user_agent("xjy-image-proxy")
+            .build()
+            .expect("Failed to build image proxy HTTP client");
+
+        Self {
+            config,
+            client,
+            cache: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(ImageProxyConfig::from_env())
+    }
+
+    pub fn cache_ttl(&self) -> Duration {
+        self.config.cache_ttl
+    }
+
+    /// Verify the signature and return the image, from cache when possible.
+    pub async fn fetch(&self, signature: &str, encoded_url: &str) -> AppResult<ProxiedImage> {
+        if !self.config.enabled {
+            return Err(AppError::NotFound);
+        }
+
+        let url = decode_image_proxy_url(encoded_url)
+            .ok_or_else(|| AppError::Validation("Invalid image url".to_string()))?;
+        if !verify_url_signature(&url_signing_secret(), &url, signature) {
+            return Err(AppError::Validation("Invalid image signature".to_string()));
+        }
+
+        if let Some(hit) = self.cache.get(&url) {
+            if hit.fetched_at.elapsed() < self.config.cache_ttl {
+                return Ok(hit.clone());
+            }
+        }
+
+        let image = self.fetch_remote(&url).await?;
+        if self.cache.len() >= MAX_CACHE_ENTRIES {
+            self.evict_expired_or_oldest();
+        }
+        self.cache.insert(url, image.clone());
+        Ok(image)
+    }
+
+    async fn fetch_remote(&self, url: &str) -> AppResult<ProxiedImage> {
+        let parsed = url::Url::parse(url)
+            .map_err(|_| AppError::Validation("Invalid image url".to_string()))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(AppError::Validation("Unsupported url scheme".to_string()));
+        }
+        ensure_public_host(&parsed).await?;
+
+        let mut resp = self
+            .client
+            .get(parsed)
+            .send()
+            .await
+            .map_err(|e| AppError::Validation(format!("Failed to fetch image: {}", e)))?;
+
+        if !resp.status().is_success() {
+            return Err(AppError::NotFound);
+        }
+
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        if !ALLOWED_CONTENT_TYPES.contains(&content_type.as_str()) {
+            return Err(AppError::Validation(format!(
+                "Unsupported image type: {}",
+                content_type
+            )));
+        }
+
+        if resp
+            .content_length()
+            .is_some_and(|len| len as usize > self.config.max_bytes)
+        {
+            return Err(AppError::PayloadTooLarge);
+        }
+
+        // Content-Length may be missing or wrong, so enforce the limit while reading.
+        let mut data = Vec::new();
+        while let Some(chunk) = resp
+            .chunk()
+            .await
+            .map_err(|e| AppError::Validation(format!("Failed to fetch image: {}", e)))?
+        {
+            if data.len() + chunk.len() > self.config.max_bytes {
+                return Err(AppError::PayloadTooLarge);
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok(ProxiedImage {
+            content_type,
+            data: Arc::new(data),
+            fetched_at: Instant::now(),
+        })
+    }
+
+    fn evict_expired_or_oldest(&self) {
+        let ttl = self.config.cache_ttl;
+        self.cache.retain(|_, v| v.fetched_at.elapsed() < ttl);
+        if self.cache.len() < MAX_CACHE_ENTRIES {
+            return;
+        }
+        let oldest = self
+            .cache
+            .iter()
+            .min_by_key(|e| e.fetched_at)
+            .map(|e| e.key().clone());
+        if let Some(key) = oldest {
+            self.cache.remove(&key);
+        }
+    }
+}
+
+/// Refuse to fetch from loopback, private or link-local addresses so the
+/// proxy cannot be used to reach internal services.
+async fn ensure_public_host(url: &url::Url) -> AppResult<()> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| AppError::Validation("Invalid image url".to_string()))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let addrs = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .map_err(|_| AppError::Validation("Unable to resolve image host".to_string()))?;
+
+    for addr in addrs {
+        if !is_public_ip(addr.ip()) {
+            return Err(AppError::Validation(
+                "Image host is not publicly routable".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The addresses an `http(s)` URL resolves to, refusing hosts with any
+/// address that is not publicly routable. Clients connect to exactly these
+/// (`resolve_to_addrs`) so a second lookup can't be rebound elsewhere.
+pub(crate) async fn public_addrs(url: &url::Url) -> anyhow::Result<Vec<SocketAddr>> {
+    if !matches!(url.scheme(), "http" | "https") {
+        anyhow::bail!("unsupported scheme {}", url.scheme());
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("missing host"))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+        .await?
+        .collect();
+    if addrs.is_empty() {
+        anyhow::bail!("{} did not resolve", host);
+    }
+    if let Some(addr) = addrs.iter().find(|a| !is_public_ip(a.ip())) {
+        anyhow::bail!("{} resolves to non-public address {}", host, addr.ip());
+    }
+    Ok(addrs)
+}
+
+pub(crate) fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                // 100.64.0.0/10 carrier-grade NAT
+                || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xC0) == 64))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let segments = v6.segments();
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                // fc00::/7 unique local
+                || (segments[0] & 0xfe00) == 0xfc00
+                // fe80::/10 link local
+                || (segments[0] & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "192.168.0.1",
+            "172.16.5.4",
+            "169.254.169.254",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(
+                !is_public_ip(ip.parse().unwrap()),
+                "{} should be private",
+                ip
+            );
+        }
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(is_public_ip("2606:2800:220:1::".parse().unwrap()));
+    }
+}
@@ -0,0 +1,180 @@
+//! Invite codes for invite-only registration.
+//!
+//! A code allows `max_uses` registrations until it expires or is revoked.
+//! Redeeming bumps `uses` in a single conditional update, so concurrent
+//! sign-ups can't overdraw a code.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{invite_code, InviteCode, InviteCodeModel};
+use crate::utils::sql;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, Set,
+};
+
+/// Most codes generated by one request.
+pub const MAX_INVITES_PER_REQUEST: u32 = 50;
+
+/// A fresh code: 16 uppercase hex characters.
+fn generate_code() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..16].to_ascii_uppercase()
+}
+
+/// Codes are shown uppercase but accepted in any case.
+fn normalize_code(code: &str) -> String {
+    code.trim().to_ascii_uppercase()
+}
+
+/// Whether the code can still be redeemed at `now`.
+pub fn is_active(invite: &InviteCodeModel, now: chrono::NaiveDateTime) -> bool {
+    invite.revoked_at.is_none()
+        && invite.uses < invite.max_uses
+        && invite.expires_at.is_none_or(|e| e > now)
+}
+
+pub struct InviteService {
+    db: DatabaseConnection,
+}
+
+impl InviteService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Generate `count` codes with the same limits.
+    pub async fn create(
+        &self,
+        created_by: i32,
+        count: u32,
+        max_uses: i32,
+        expires_at: Option<chrono::NaiveDateTime>,
+        note: Option<&str>,
+    ) -> AppResult<Vec<InviteCodeModel>> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut created = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let invite = invite_code::ActiveModel {
+                code: Set(generate_code()),
+                created_by: Set(Some(created_by)),
+                max_uses: Set(max_uses),
+                uses: Set(0),
+                note: Set(note.map(|n| n.to_string())),
+                expires_at: Set(expires_at),
+                created_at: Set(now),
+                ..Default::default()
+            }
+            .insert(&self.db)
+            .await?;
+            created.push(invite);
+        }
+        Ok(created)
+    }
+
+    /// All codes, newest first.
+    pub async fn list(&self, page: u64, per_page: u64) -> AppResult<(Vec<InviteCodeModel>, u64)> {
+        let paginator = InviteCode::find()
+            .order_by_desc(invite_code::Column::CreatedAt)
+            .order_by_desc(invite_code::Column::Id)
+            .paginate(&self.db, per_page);
+        let total = paginator.num_items().await?;
+        let items = paginator.fetch_page(page.saturating_sub(1)).await?;
+        Ok((items, total))
+    }
+
+    /// Stop a code from being redeemed again. Revoking twice is a no-op.
+    pub async fn revoke(&self, id: i32) -> AppResult<InviteCodeModel> {
+        let invite = InviteCode::find_by_id(id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if invite.revoked_at.is_some() {
+            return Ok(invite);
+        }
+        let mut active: invite_code::ActiveModel = invite.into();
+        active.revoked_at = Set(Some(chrono::Utc::now().naive_utc()));
+        Ok(active.update(&self.db).await?)
+    }
+
+    /// Use up one registration of `code`; run inside the registration's
+    /// transaction so a failed sign-up doesn't spend it.
+    pub async fn redeem<C: ConnectionTrait>(conn: &C, code: &str) -> AppResult<InviteCodeModel> {
+        let code = normalize_code(code);
+        let update = "UPDATE invite_codes SET uses = uses + 1
+            WHERE code = $1 AND revoked_at IS NULL AND uses < max_uses
+                AND (expires_at IS NULL OR expires_at > $2)";
+        let values = [code.as_str().into(), chrono::Utc::now().naive_utc().into()];
+        let invalid = || AppError::Validation("Invalid or expired invite code".to_string());
+
+        let backend = conn.get_database_backend();
+        if backend == DbBackend::MySql {
+            // No RETURNING; the updated row stays locked until the
+            // transaction ends, so reading it back is safe
+            let result = conn
+                .execute(sql::statement(backend, update, values))
+                .await?;
+            if result.rows_affected() == 0 {
+                return Err(invalid());
+            }
+            return InviteCode::find()
+                .filter(invite_code::Column::Code.eq(code))
+                .one(conn)
+                .await?
+                .ok_or_else(invalid);
+        }
+
+        InviteCode::find()
+            .from_raw_sql(sql::statement(
+                backend,
+                format!("{update} RETURNING *"),
+                values,
+            ))
+            .one(conn)
+            .await?
+            .ok_or_else(invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_codes_are_normalized() {
+        let code = generate_code();
+        assert_eq!(code.len(), 16);
+        assert_eq!(normalize_code(&format!(" {} ", code.to_lowercase())), code);
+        assert_ne!(generate_code(), code);
+    }
+
+    #[test]
+    fn test_is_active() {
+        let now = chrono::Utc::now().naive_utc();
+        let invite = InviteCodeModel {
+            id: 1,
+            code: "ABC".to_string(),
+            created_by: None,
+            max_uses: 2,
+            uses: 1,
+            note: None,
+            expires_at: Some(now + chrono::Duration::hours(1)),
+            revoked_at: None,
+            created_at: now,
+        };
+        assert!(is_active(&invite, now));
+        assert!(!is_active(
+            &InviteCodeModel {
+                uses: 2,
+                ..invite.clone()
+            },
+            now
+        ));
+        assert!(!is_active(
+            &InviteCodeModel {
+                revoked_at: Some(now),
+                ..invite.clone()
+            },
+            now
+        ));
+        assert!(!is_active(&invite, now + chrono::Duration::hours(2)));
+    }
+}
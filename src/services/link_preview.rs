@@ -0,0 +1,592 @@
+//! Previews of the pages link posts point at: Open Graph metadata fetched in
+//! the background and cached per URL in `link_previews`.
+//!
+//! Fetching is SSRF-safe: every hop of a redirect chain must resolve only to
+//! public addresses, the connection is pinned to the addresses that were
+//! checked, and hosts can be restricted with allow and deny lists.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{link_preview, LinkPreview, LinkPreviewModel};
+use crate::services::image_proxy::public_addrs;
+use crate::utils::shutdown;
+use anyhow::{anyhow, bail};
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait,
+    QueryFilter, Set,
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Longest URL accepted for a link post.
+pub const MAX_URL_LEN: usize = 2048;
+
+const MAX_REDIRECTS: usize = 3;
+const MAX_TITLE_CHARS: usize = 300;
+const MAX_DESCRIPTION_CHARS: usize = 1000;
+const MAX_SITE_NAME_CHARS: usize = 200;
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_READY: &str = "ready";
+pub const STATUS_FAILED: &str = "failed";
+
+#[derive(Debug, Clone)]
+pub struct LinkPreviewConfig {
+    pub enabled: bool,
+    /// When non-empty, only these hosts (and their subdomains) are fetched
+    pub allowed_hosts: Vec<String>,
+    /// Hosts (and their subdomains) never fetched
+    pub denied_hosts: Vec<String>,
+    /// Bytes of a page read looking for its metadata
+    pub max_bytes: usize,
+    pub timeout: Duration,
+    /// How long a fetched preview, or a failure, is kept before refetching
+    pub refresh_after: Duration,
+}
+
+impl LinkPreviewConfig {
+    pub fn from_env() -> Self {
+        let max_bytes = std::env::var("LINK_PREVIEW_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(512 * 1024);
+
+        let timeout_seconds = std::env::var("LINK_PREVIEW_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(5);
+
+        let refresh_seconds = std::env::var("LINK_PREVIEW_REFRESH_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(7 * 86400);
+
+        Self {
+            enabled: parse_bool_env("LINK_PREVIEW_ENABLED", true),
+            allowed_hosts: host_list("LINK_PREVIEW_ALLOWED_HOSTS"),
+            denied_hosts: host_list("LINK_PREVIEW_DENIED_HOSTS"),
+            max_bytes,
+            timeout: Duration::from_secs(timeout_seconds),
+            refresh_after: Duration::from_secs(refresh_seconds),
+        }
+    }
+
+    /// Whether the lists let `host` be fetched.
+    pub fn permits(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let listed = |hosts: &[String]| {
+            hosts
+                .iter()
+                .any(|h| host == *h || host.ends_with(&format!(".{h}")))
+        };
+        !listed(&self.denied_hosts)
+            && (self.allowed_hosts.is_empty() || listed(&self.allowed_hosts))
+    }
+}
+
+/// Validate the URL of a link post, dropping any fragment.
+pub fn normalize_link_url(raw: &str) -> AppResult<String> {
+    let raw = raw.trim();
+    if raw.len() > MAX_URL_LEN {
+        return Err(AppError::Validation(format!(
+            "url must be at most {} characters",
+            MAX_URL_LEN
+        )));
+    }
+    let mut url = url::Url::parse(raw)
+        .ok()
+        .filter(|u| matches!(u.scheme(), "http" | "https") && u.host_str().is_some())
+        .ok_or_else(|| AppError::Validation("url must be an http(s) URL".to_string()))?;
+    url.set_fragment(None);
+    Ok(url.to_string())
+}
+
+/// Fetches link previews and caches them in the database.
+#[derive(Clone)]
+pub struct LinkPreviewFetcher {
+    db: DatabaseConnection,
+    config: LinkPreviewConfig,
+}
+
+impl LinkPreviewFetcher {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self::with_config(db, LinkPreviewConfig::from_env())
+    }
+
+    pub fn with_config(db: DatabaseConnection, config: LinkPreviewConfig) -> Self {
+        Self { db, config }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Cached previews of `urls`, keyed by URL. URLs never fetched are
+    /// missing.
+    pub async fn previews_for(
+        &self,
+        urls: &[&str],
+    ) -> AppResult<HashMap<String, LinkPreviewModel>> {
+        if urls.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let hashes: Vec<String> = urls.iter().map(|u| url_hash(u)).collect();
+        let previews = LinkPreview::find()
+            .filter(link_preview::Column::UrlHash.is_in(hashes))
+            .all(&self.db)
+            .await?;
+        Ok(previews.into_iter().map(|p| (p.url.clone(), p)).collect())
+    }
+
+    /// Refresh the preview of `url` without waiting for it.
+    pub fn spawn_refresh(&self, url: String) {
+        if !self.config.enabled {
+            return;
+        }
+        let fetcher = self.clone();
+        shutdown::spawn(async move {
+            if let Err(e) = fetcher.refresh(&url).await {
+                tracing::warn!("Failed to store link preview for {}: {}", url, e);
+            }
+        });
+    }
+
+    /// Fetch the preview of `url` unless one was fetched within
+    /// `refresh_after`, and return what is stored.
+    pub async fn refresh(&self, url: &str) -> AppResult<LinkPreviewModel> {
+        let hash = url_hash(url);
+        let now = chrono::Utc::now().naive_utc();
+
+        if let Some(existing) = self.find(&hash).await? {
+            let fresh = existing.fetched_at.is_some_and(|at| {
+                (now - at).to_std().unwrap_or_default() < self.config.refresh_after
+            });
+            if fresh {
+                return Ok(existing);
+            }
+        } else {
+            let pending = link_preview::ActiveModel {
+                url_hash: Set(hash.clone()),
+                url: Set(url.to_string()),
+                status: Set(STATUS_PENDING.to_string()),
+                created_at: Set(now),
+                ..Default::default()
+            };
+            // Another post may have linked the same URL meanwhile
+            LinkPreview::insert(pending)
+                .on_conflict(
+                    OnConflict::column(link_preview::Column::UrlHash)
+                        .do_nothing_on([link_preview::Column::UrlHash])
+                        .to_owned(),
+                )
+                .exec_without_returning(&self.db)
+                .await?;
+        }
+
+        let existing = self.find(&hash).await?.ok_or(AppError::NotFound)?;
+        let was_ready = existing.status == STATUS_READY;
+        let mut active: link_preview::ActiveModel = existing.into();
+        match self.fetch(url).await {
+            Ok(meta) => {
+                active.status = Set(STATUS_READY.to_string());
+                active.title = Set(meta.title);
+                active.description = Set(meta.description);
+                active.image_url = Set(meta.image);
+                active.site_name = Set(meta.site_name);
+            }
+            Err(e) => {
+                tracing::debug!("Link preview fetch for {} failed: {}", url, e);
+                // A stale preview beats none
+                if !was_ready {
+                    active.status = Set(STATUS_FAILED.to_string());
+                }
+            }
+        }
+        active.fetched_at = Set(Some(chrono::Utc::now().naive_utc()));
+        Ok(active.update(&self.db).await?)
+    }
+
+    async fn find(&self, hash: &str) -> AppResult<Option<LinkPreviewModel>> {
+        Ok(LinkPreview::find()
+            .filter(link_preview::Column::UrlHash.eq(hash))
+            .one(&self.db)
+            .await?)
+    }
+
+    /// Follow redirects by hand so every hop is checked before connecting.
+    async fn fetch(&self, url: &str) -> anyhow::Result<PageMetadata> {
+        let mut url = url::Url::parse(url)?;
+        for _ in 0..=MAX_REDIRECTS {
+            let addrs = self.resolve_public(&url).await?;
+            let host = url.host_str().unwrap_or_default().to_string();
+            let client = reqwest::Client::builder()
+                .timeout(self.config.timeout)
+                .redirect(reqwest::redirect::Policy::none())
+                .user_agent("xjy-link-preview")
+                // Connect to the addresses checked above, not a fresh lookup
+                .resolve_to_addrs(&host, &addrs)
+                .build()?;
+
+            let mut resp = client
+                .get(url.clone())
+                .header(reqwest::header::ACCEPT, "text/html,application/xhtml+xml")
+                .send()
+                .await?;
+
+            if resp.status().is_redirection() {
+                let location = resp
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .ok_or_else(|| anyhow!("redirect without a location"))?;
+                url = url.join(location)?;
+                continue;
+            }
+            if !resp.status().is_success() {
+                bail!("status {}", resp.status());
+            }
+
+            let content_type = resp
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if !content_type.starts_with("text/html")
+                && !content_type.starts_with("application/xhtml+xml")
+            {
+                bail!("not an HTML page: {}", content_type);
+            }
+
+            // The metadata is in the head, so a long page is read only in part
+            let mut body = Vec::new();
+            while let Some(chunk) = resp.chunk().await? {
+                let room = self.config.max_bytes - body.len();
+                body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+                if body.len() >= self.config.max_bytes {
+                    break;
+                }
+            }
+
+            return Ok(parse_metadata(&String::from_utf8_lossy(&body), &url));
+        }
+        bail!("too many redirects")
+    }
+
+    /// The addresses `url` resolves to, all of them publicly routable and
+    /// its host permitted by the allow and deny lists.
+    async fn resolve_public(&self, url: &url::Url) -> anyhow::Result<Vec<SocketAddr>> {
+        let host = url.host_str().ok_or_else(|| anyhow!("missing host"))?;
+        if !self.config.permits(host) {
+            bail!("host {} is not allowed", host);
+        }
+        public_addrs(url).await
+    }
+}
+
+fn url_hash(url: &str) -> String {
+    Sha256::digest(url.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct PageMetadata {
+    title: Option<String>,
+    description: Option<String>,
+    image: Option<String>,
+    site_name: Option<String>,
+}
+
+/// Open Graph tags, falling back to Twitter card tags and then the plain
+/// `<title>` and description.
+fn parse_metadata(html: &str, page_url: &url::Url) -> PageMetadata {
+    let (meta, title) = scan_head(html);
+    let first = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| meta.get(*k))
+            .map(|v| collapse_whitespace(v))
+            .filter(|v| !v.is_empty())
+    };
+
+    PageMetadata {
+        title: first(&["og:title", "twitter:title"])
+            .or_else(|| {
+                title
+                    .map(|t| collapse_whitespace(&t))
+                    .filter(|t| !t.is_empty())
+            })
+            .map(|t| truncate_chars(&t, MAX_TITLE_CHARS)),
+        description: first(&["og:description", "twitter:description", "description"])
+            .map(|d| truncate_chars(&d, MAX_DESCRIPTION_CHARS)),
+        image: first(&[
+            "og:image:secure_url",
+            "og:image",
+            "og:image:url",
+            "twitter:image",
+        ])
+        .and_then(|src| page_url.join(&src).ok())
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .map(|u| u.to_string())
+        .filter(|u| u.len() <= MAX_URL_LEN),
+        site_name: first(&["og:site_name"]).map(|s| truncate_chars(&s, MAX_SITE_NAME_CHARS)),
+    }
+}
+
+/// `<meta>` tags keyed by lowercase `property`/`name` (first one wins) and
+/// the `<title>` text, up to the end of the head.
+fn scan_head(html: &str) -> (HashMap<String, String>, Option<String>) {
+    // ASCII lowercasing keeps byte offsets, so indices apply to both
+    let lower = html.to_ascii_lowercase();
+    let mut meta = HashMap::new();
+    let mut title = None;
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find('<') {
+        let start = pos + offset;
+        let rest = &lower[start + 1..];
+        if rest.starts_with("/head") || rest.starts_with("body") {
+            break;
+        }
+        if let Some(attrs) = rest.strip_prefix("meta") {
+            if attrs.starts_with(|c: char| c.is_ascii_whitespace() || c == '/') {
+                let Some(end) = find_tag_end(&html[start..]) else {
+                    break;
+                };
+                let attrs = parse_attributes(&html[start + 5..start + end]);
+                let key = attrs.get("property").or_else(|| attrs.get("name"));
+                if let (Some(key), Some(content)) = (key, attrs.get("content")) {
+                    meta.entry(key.to_ascii_lowercase())
+                        .or_insert_with(|| decode_entities(content));
+                }
+                pos = start + end + 1;
+                continue;
+            }
+        }
+        if rest.starts_with("title") && title.is_none() {
+            if let (Some(open_end), Some(close)) =
+                (find_tag_end(&html[start..]), lower[start..].find("</title"))
+            {
+                if open_end < close {
+                    title = Some(decode_entities(&html[start + open_end + 1..start + close]));
+                    pos = start + close;
+                    continue;
+                }
+            }
+        }
+        pos = start + 1;
+    }
+    (meta, title)
+}
+
+/// Offset of the `>` closing the tag at the start of `tag_src`.
+fn find_tag_end(tag_src: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in tag_src.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Attributes of a start tag, names lowercased.
+fn parse_attributes(src: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut chars = src.trim_end_matches('/').chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == '/').is_some() {}
+        let name: String =
+            std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && *c != '=' && *c != '/'))
+                .collect();
+        if name.is_empty() {
+            break;
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.next_if(|c| *c == '"' || *c == '\'') {
+                Some(q) => value.extend(std::iter::from_fn(|| chars.next_if(|c| *c != q))),
+                None => value.extend(std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace()))),
+            }
+            chars.next();
+        }
+        attrs.entry(name.to_ascii_lowercase()).or_insert(value);
+    }
+    attrs
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => match entity.strip_prefix('#') {
+                    Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16)
+                        .ok()
+                        .and_then(char::from_u32),
+                    Some(dec) => dec.parse().ok().and_then(char::from_u32),
+                    None => None,
+                },
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+fn host_list(var_name: &str) -> Vec<String> {
+    std::env::var(var_name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|h| h.trim().trim_end_matches('.').to_ascii_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect()
+}
+
+fn parse_bool_env(var_name: &str, default: bool) -> bool {
+    std::env::var(var_name)
+        .ok()
+        .and_then(|value| match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "y" | "on" => Some(true),
+            "0" | "false" | "no" | "n" | "off" => Some(false),
+            _ => None,
+        })
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(allowed: &[&str], denied: &[&str]) -> LinkPreviewConfig {
+        LinkPreviewConfig {
+            enabled: true,
+            allowed_hosts: allowed.iter().map(|h| h.to_string()).collect(),
+            denied_hosts: denied.iter().map(|h| h.to_string()).collect(),
+            max_bytes: 1024,
+            timeout: Duration::from_secs(1),
+            refresh_after: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn host_lists_match_subdomains() {
+        let open = config(&[], &["evil.test"]);
+        assert!(open.permits("example.com"));
+        assert!(!open.permits("evil.test"));
+        assert!(!open.permits("cdn.EVIL.test."));
+        assert!(open.permits("notevil.test"));
+
+        let closed = config(&["github.com"], &["gist.github.com"]);
+        assert!(closed.permits("github.com"));
+        assert!(closed.permits("www.github.com"));
+        assert!(!closed.permits("gist.github.com"));
+        assert!(!closed.permits("example.com"));
+    }
+
+    #[test]
+    fn link_urls_must_be_http() {
+        assert_eq!(
+            normalize_link_url(" https://Example.com/a?b=1#frag ").unwrap(),
+            "https://example.com/a?b=1"
+        );
+        assert!(normalize_link_url("javascript:alert(1)").is_err());
+        assert!(normalize_link_url("ftp://example.com/").is_err());
+        assert!(normalize_link_url("not a url").is_err());
+        let long = format!("https://example.com/{}", "a".repeat(MAX_URL_LEN));
+        assert!(normalize_link_url(&long).is_err());
+    }
+
+    #[test]
+    fn open_graph_tags_are_preferred() {
+        let html = r#"<!DOCTYPE html><html><head>
+            <title>Plain &amp; simple</title>
+            <meta name="description" content="Fallback">
+            <META property='og:title' content='Rust &quot;1.0&quot; released'>
+            <meta content="The   announcement" property="og:description" />
+            <meta property="og:image" content="/img/cover.png">
+            <meta property="og:site_name" content="Rust Blog">
+            </head><body><meta property="og:title" content="ignored"></body></html>"#;
+        let page = url::Url::parse("https://blog.rust-lang.org/2015/05/15/").unwrap();
+        assert_eq!(
+            parse_metadata(html, &page),
+            PageMetadata {
+                title: Some("Rust \"1.0\" released".to_string()),
+                description: Some("The announcement".to_string()),
+                image: Some("https://blog.rust-lang.org/img/cover.png".to_string()),
+                site_name: Some("Rust Blog".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn falls_back_to_title_and_description() {
+        let html = "<head><title>\n  Hello &#x4E16;&#30028;\n</title>\
+            <meta name=description content=Short>\
+            <meta name=\"twitter:image\" content=\"javascript:alert(1)\"></head>";
+        let page = url::Url::parse("https://example.com/").unwrap();
+        assert_eq!(
+            parse_metadata(html, &page),
+            PageMetadata {
+                title: Some("Hello 世界".to_string()),
+                description: Some("Short".to_string()),
+                image: None,
+                site_name: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn private_addresses_are_refused() {
+        let fetcher_config = config(&[], &[]);
+        let db = sea_orm::DatabaseConnection::Disconnected;
+        let fetcher = LinkPreviewFetcher::with_config(db, fetcher_config);
+        for url in [
+            "http://127.0.0.1/",
+            "http://[::1]:8080/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://localhost/",
+            "file:///etc/passwd",
+        ] {
+            let url = url::Url::parse(url).unwrap();
+            assert!(fetcher.resolve_public(&url).await.is_err(), "{}", url);
+        }
+    }
+}
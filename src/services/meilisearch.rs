@@ -0,0 +1,375 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{post, Post, PostModel};
+use crate::services::search::{PostSearchQuery, PostSearchResults, SearchBackend};
+use async_trait::async_trait;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct MeilisearchConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub index: String,
+    pub timeout: Duration,
+}
+
+impl MeilisearchConfig {
+    pub fn from_env() -> Self {
+        let url = std::env::var("MEILISEARCH_URL")
+            .unwrap_or_else(|_| "http://127.0.0.1:7700".to_string())
+            .trim_end_matches('/')
+            .to_string();
+
+        let api_key = std::env::var("MEILISEARCH_API_KEY")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+
+        let index = std::env::var("MEILISEARCH_INDEX")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| "posts".to_string());
+
+        let timeout_seconds: u64 = std::env::var("MEILISEARCH_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+
+        Self {
+            url,
+            api_key,
+            index,
+            timeout: Duration::from_secs(timeout_seconds),
+        }
+    }
+}
+
+/// Document stored per visible post. Hidden posts are removed, not flagged.
+#[derive(Debug, Serialize)]
+struct PostDocument<'a> {
+    id: i32,
+    title: &'a str,
+    content: &'a str,
+    forum_id: i32,
+    user_id: i32,
+    score: i32,
+    created_at: i64,
+    /// Lowercased tag names
+    tags: Vec<String>,
+    has_image: bool,
+}
+
+impl<'a> PostDocument<'a> {
+    fn new(p: &'a PostModel, tags: &[String]) -> Self {
+        Self {
+            id: p.id,
+            title: &p.title,
+            content: &p.content,
+            forum_id: p.forum_id,
+            user_id: p.user_id,
+            score: p.upvotes - p.downvotes,
+            created_at: p.created_at.and_utc().timestamp(),
+            tags: tags.iter().map(|t| t.to_lowercase()).collect(),
+            has_image: has_image(&p.content),
+        }
+    }
+}
+
+/// Same test as the Postgres backend's `has=image` filter.
+fn has_image(content: &str) -> bool {
+    let lower = content.to_lowercase();
+    lower.contains("<img") || lower.find("![").is_some_and(|i| lower[i..].contains("]("))
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchHit {
+    id: i32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResult {
+    hits: Vec<SearchHit>,
+    #[serde(default)]
+    estimated_total_hits: Option<u64>,
+    #[serde(default)]
+    total_hits: Option<u64>,
+}
+
+/// Meilisearch backend: typo-tolerant search with forum filtering. The index
+/// only returns post ids; posts are then loaded from the database.
+pub struct MeilisearchBackend {
+    config: MeilisearchConfig,
+    client: reqwest::Client,
+    settings_applied: AtomicBool,
+}
+
+impl MeilisearchBackend {
+    pub fn new(config: MeilisearchConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .expect("Failed to build Meilisearch HTTP client");
+        Self {
+            config,
+            client,
+            settings_applied: AtomicBool::new(false),
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}/indexes/{}{}", self.config.url, self.config.index, path);
+        let builder = self.client.request(method, url);
+        match &self.config.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+
+    async fn send(&self, builder: reqwest::RequestBuilder) -> AppResult<reqwest::Response> {
+        let resp = builder.send().await.map_err(|e| {
+            AppError::Internal(anyhow::anyhow!("Meilisearch request failed: {}", e))
+        })?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(AppError::Internal(anyhow::anyhow!(
+                "Meilisearch returned {}: {}",
+                status,
+                body
+            )));
+        }
+        Ok(resp)
+    }
+
+    /// Declare which attributes can be filtered and sorted on.
+    async fn apply_settings(&self) -> AppResult<()> {
+        self.send(
+            self.request(reqwest::Method::PATCH, "/settings")
+                .json(&serde_json::json!({
+                    "searchableAttributes": ["title", "content"],
+                    "filterableAttributes": [
+                        "forum_id", "user_id", "tags", "has_image", "created_at", "score"
+                    ],
+                    "sortableAttributes": ["created_at", "score"],
+                })),
+        )
+        .await?;
+        self.settings_applied.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Filtering and sorting fail until the settings exist, so apply them
+    /// once per process before the first search or write.
+    async fn ensure_settings(&self) -> AppResult<()> {
+        if self.settings_applied.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        self.apply_settings().await
+    }
+}
+
+fn sort_for(sort: &str) -> Option<Vec<&'static str>> {
+    match sort {
+        "new" => Some(vec!["created_at:desc"]),
+        "top" => Some(vec!["score:desc", "created_at:desc"]),
+        _ => None,
+    }
+}
+
+/// Meilisearch filter expression equivalent to the Postgres filters.
+fn filter_for(query: &PostSearchQuery) -> Option<String> {
+    let f = &query.filters;
+    let mut parts = Vec::new();
+    if let Some(fid) = query.forum_id {
+        parts.push(format!("forum_id = {}", fid));
+    }
+    if let Some(author_id) = f.author_id {
+        parts.push(format!("user_id = {}", author_id));
+    }
+    if let Some(tag) = &f.tag {
+        let tag = tag
+            .to_lowercase()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"");
+        parts.push(format!("tags = \"{}\"", tag));
+    }
+    if let Some(after) = f.created_after {
+        parts.push(format!("created_at >= {}", after.and_utc().timestamp()));
+    }
+    if let Some(before) = f.created_before {
+        parts.push(format!("created_at < {}", before.and_utc().timestamp()));
+    }
+    if let Some(min_score) = f.min_score {
+        parts.push(format!("score >= {}", min_score));
+    }
+    if f.has_image {
+        parts.push("has_image = true".to_string());
+    }
+    (!parts.is_empty()).then(|| parts.join(" AND "))
+}
+
+fn search_body(query: &PostSearchQuery) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "q": query.q,
+        "offset": query.page.saturating_sub(1) * query.per_page,
+        "limit": query.per_page,
+        "attributesToRetrieve": ["id"],
+    });
+    if let Some(filter) = filter_for(query) {
+        body["filter"] = serde_json::json!(filter);
+    }
+    if let Some(sort) = sort_for(&query.sort) {
+        body["sort"] = serde_json::json!(sort);
+    }
+    body
+}
+
+#[async_trait]
+impl SearchBackend for MeilisearchBackend {
+    fn name(&self) -> &'static str {
+        "meilisearch"
+    }
+
+    async fn search_posts(
+        &self,
+        db: &DatabaseConnection,
+        query: &PostSearchQuery,
+    ) -> AppResult<PostSearchResults> {
+        self.ensure_settings().await?;
+        let resp = self
+            .send(
+                self.request(reqwest::Method::POST, "/search")
+                    .json(&search_body(query)),
+            )
+            .await?;
+        let result: SearchResult = resp.json().await.map_err(|e| {
+            AppError::Internal(anyhow::anyhow!("Invalid Meilisearch response: {}", e))
+        })?;
+
+        let total = result
+            .total_hits
+            .or(result.estimated_total_hits)
+            .unwrap_or(result.hits.len() as u64);
+        let ids: Vec<i32> = result.hits.iter().map(|h| h.id).collect();
+        if ids.is_empty() {
+            return Ok(PostSearchResults {
+                total,
+                ..Default::default()
+            });
+        }
+
+        // Keep Meilisearch's ranking; skip anything hidden since it was indexed.
+        let mut by_id: HashMap<i32, PostModel> = Post::find()
+            .filter(post::Column::Id.is_in(ids.clone()))
+            .filter(post::Column::IsHidden.eq(false))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|p| (p.id, p))
+            .collect();
+        let posts = ids.into_iter().filter_map(|id| by_id.remove(&id)).collect();
+
+        // Meilisearch is typo tolerant itself, so there is nothing to suggest.
+        Ok(PostSearchResults {
+            posts,
+            total,
+            did_you_mean: None,
+        })
+    }
+
+    async fn index_posts(
+        &self,
+        posts: &[PostModel],
+        tags: &HashMap<i32, Vec<String>>,
+    ) -> AppResult<()> {
+        if posts.is_empty() {
+            return Ok(());
+        }
+        self.ensure_settings().await?;
+        let docs: Vec<PostDocument> = posts
+            .iter()
+            .map(|p| PostDocument::new(p, tags.get(&p.id).map_or(&[], Vec::as_slice)))
+            .collect();
+        self.send(
+            self.request(reqwest::Method::POST, "/documents?primaryKey=id")
+                .json(&docs),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_post(&self, id: i32) -> AppResult<()> {
+        self.send(self.request(reqwest::Method::DELETE, &format!("/documents/{}", id)))
+            .await?;
+        Ok(())
+    }
+
+    async fn clear(&self) -> AppResult<()> {
+        self.apply_settings().await?;
+        self.send(self.request(reqwest::Method::DELETE, "/documents"))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::search::PostSearchFilters;
+
+    fn query(sort: &str, forum_id: Option<i32>) -> PostSearchQuery {
+        PostSearchQuery {
+            q: "rust".to_string(),
+            forum_id,
+            page: 3,
+            per_page: 10,
+            sort: sort.to_string(),
+            filters: PostSearchFilters::default(),
+        }
+    }
+
+    #[test]
+    fn test_search_body_paginates_and_filters() {
+        let body = search_body(&query("relevance", Some(7)));
+        assert_eq!(body["offset"], 20);
+        assert_eq!(body["limit"], 10);
+        assert_eq!(body["filter"], "forum_id = 7");
+        assert!(body.get("sort").is_none());
+    }
+
+    #[test]
+    fn test_search_body_sorts() {
+        let body = search_body(&query("top", None));
+        assert_eq!(body["sort"][0], "score:desc");
+        assert!(body.get("filter").is_none());
+        let body = search_body(&query("new", None));
+        assert_eq!(body["sort"][0], "created_at:desc");
+    }
+
+    #[test]
+    fn test_search_body_combines_filters() {
+        let mut q = query("relevance", Some(7));
+        q.filters = PostSearchFilters {
+            author_id: Some(3),
+            tag: Some("Say \"Hi\"".to_string()),
+            min_score: Some(5),
+            has_image: true,
+            ..Default::default()
+        };
+        let body = search_body(&q);
+        assert_eq!(
+            body["filter"],
+            "forum_id = 7 AND user_id = 3 AND tags = \"say \\\"hi\\\"\" AND score >= 5 AND has_image = true"
+        );
+    }
+
+    #[test]
+    fn test_has_image_detection() {
+        assert!(has_image("look ![cat](https://x/cat.png)"));
+        assert!(has_image("<IMG src=\"a.png\">"));
+        assert!(!has_image("just [a link](https://x) and text!"));
+    }
+}
@@ -1,17 +1,56 @@
+pub mod account_deletion;
+pub mod activity;
 pub mod admin;
+pub mod announcement;
+pub mod appeal;
+pub mod attachment;
+pub mod audit;
 pub mod auth;
+pub mod avatar;
+pub mod badge;
 pub mod bookmark;
 pub mod bootstrap_admin;
 pub mod cache;
+pub mod captcha;
 pub mod comment;
+pub mod digest;
 pub mod email;
+pub mod email_feedback;
+pub mod email_preferences;
+pub mod email_provider;
+pub mod email_template;
+pub mod emoji;
+pub mod error_reporting;
+pub mod export;
+pub mod federation;
+pub mod flair;
 pub mod follow;
 pub mod forum;
+pub mod image_proxy;
+pub mod invite;
+pub mod link_preview;
+pub mod meilisearch;
+pub mod mod_queue;
 pub mod notification;
 pub mod points;
 pub mod post;
+pub mod post_fanout;
+pub mod post_read;
+pub mod post_stats;
+pub mod presence;
+pub mod profile_stats;
+pub mod quiet_hours;
 pub mod report;
+pub mod search;
+pub mod seed;
+pub mod seo;
+pub mod settings;
 pub mod tag;
+pub mod tenant;
 pub mod upload;
 pub mod user;
+pub mod user_note;
+pub mod view_counter;
+pub mod virus_scan;
 pub mod vote;
+pub mod watch;
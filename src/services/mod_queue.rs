@@ -0,0 +1,360 @@
+//! Moderation queue: everything waiting for a moderator, oldest first.
+//!
+//! Each item kind contributes the rows still needing a decision: pending
+//! reports and pending appeals. A moderator claims an item before working on it so two
+//! people don't act on the same thing; claims lapse after
+//! `CLAIM_TIMEOUT_MINUTES` so an abandoned item returns to the pool, and are
+//! cleared once the item is dealt with.
+
+use crate::error::{AppError, AppResult};
+use crate::middleware::permission::{role_has_permission, Permission};
+use crate::models::{
+    appeal, mod_queue_claim, report, Appeal, ModQueueClaim, ModQueueClaimModel, Report, User,
+};
+use crate::utils::sql;
+use chrono::NaiveDateTime;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, FromQueryResult,
+    QueryFilter, Value,
+};
+
+/// How long a claim keeps other moderators off an item.
+pub const CLAIM_TIMEOUT_MINUTES: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueItemKind {
+    /// A pending report against a post or comment
+    Report,
+    /// A pending appeal against a warning or ban
+    Appeal,
+}
+
+impl QueueItemKind {
+    pub const ALL: [QueueItemKind; 2] = [QueueItemKind::Report, QueueItemKind::Appeal];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueueItemKind::Report => "report",
+            QueueItemKind::Appeal => "appeal",
+        }
+    }
+
+    /// Needed to claim and act on items of this kind.
+    pub fn permission(&self) -> Permission {
+        match self {
+            QueueItemKind::Report => Permission::ResolveReports,
+            QueueItemKind::Appeal => Permission::ReviewAppeals,
+        }
+    }
+
+    /// Open items of this kind as `(kind, id, target_type, target_id,
+    /// summary, created_at)` rows.
+    fn source_sql(&self) -> &'static str {
+        match self {
+            QueueItemKind::Report => {
+                "SELECT 'report' AS kind, id, target_type, target_id, reason AS summary, \
+                    created_at FROM reports WHERE status = 'pending'"
+            }
+            QueueItemKind::Appeal => {
+                "SELECT 'appeal' AS kind, id, 'user' AS target_type, user_id AS target_id, \
+                    SUBSTR(reason, 1, 100) AS summary, created_at FROM appeals \
+                    WHERE status = 'pending'"
+            }
+        }
+    }
+}
+
+/// Which items to list by claim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimFilter {
+    All,
+    /// Claimed by the current moderator
+    Mine,
+    /// Not claimed by anyone, or the claim lapsed
+    Unclaimed,
+}
+
+#[derive(Debug, FromQueryResult)]
+pub struct QueueItem {
+    pub kind: String,
+    pub id: i32,
+    pub target_type: String,
+    pub target_id: i32,
+    pub summary: String,
+    pub created_at: NaiveDateTime,
+    pub claimed_by: Option<i32>,
+    pub claimed_by_username: Option<String>,
+    pub claimed_by_display_name: Option<String>,
+    pub claimed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CountRow {
+    count: i64,
+}
+
+/// When a claim made at `claimed_at` lapses.
+pub fn claim_expires_at(claimed_at: NaiveDateTime) -> NaiveDateTime {
+    claimed_at + chrono::Duration::minutes(CLAIM_TIMEOUT_MINUTES)
+}
+
+fn claim_cutoff() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc() - chrono::Duration::minutes(CLAIM_TIMEOUT_MINUTES)
+}
+
+pub struct ModQueueService {
+    db: DatabaseConnection,
+}
+
+impl ModQueueService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Open items of the given kinds, oldest first, with their current claim.
+    pub async fn list(
+        &self,
+        user_id: i32,
+        kinds: &[QueueItemKind],
+        claim: ClaimFilter,
+        page: u64,
+        per_page: u64,
+    ) -> AppResult<(Vec<QueueItem>, u64)> {
+        if kinds.is_empty() {
+            return Ok((Vec::new(), 0));
+        }
+        let sources: Vec<&str> = kinds.iter().map(|k| k.source_sql()).collect();
+        let mut values: Vec<Value> = vec![claim_cutoff().into()];
+        let claim_sql = match claim {
+            ClaimFilter::All => "TRUE",
+            ClaimFilter::Mine => {
+                values.push(user_id.into());
+                "c.claimed_by = $2"
+            }
+            ClaimFilter::Unclaimed => "c.claimed_by IS NULL",
+        };
+        let from = format!(
+            "FROM ({}) q \
+                LEFT JOIN mod_queue_claims c \
+                    ON c.item_type = q.kind AND c.item_id = q.id AND c.claimed_at > $1 \
+                LEFT JOIN users u ON u.id = c.claimed_by \
+                WHERE {}",
+            sources.join(" UNION ALL "),
+            claim_sql
+        );
+
+        let total = CountRow::find_by_statement(sql::statement(
+            self.db.get_database_backend(),
+            format!("SELECT COUNT(*) AS count {}", from),
+            values.clone(),
+        ))
+        .one(&self.db)
+        .await?
+        .map(|r| r.count as u64)
+        .unwrap_or(0);
+
+        let limit = values.len() + 1;
+        values.push((per_page as i64).into());
+        values.push((page.saturating_sub(1).saturating_mul(per_page) as i64).into());
+        let items = QueueItem::find_by_statement(sql::statement(
+            self.db.get_database_backend(),
+            format!(
+                "SELECT q.kind, q.id, q.target_type, q.target_id, q.summary, q.created_at, \
+                    c.claimed_by, u.username AS claimed_by_username, \
+                    u.display_name AS claimed_by_display_name, c.claimed_at {} \
+                    ORDER BY q.created_at, q.kind, q.id LIMIT ${} OFFSET ${}",
+                from,
+                limit,
+                limit + 1
+            ),
+            values,
+        ))
+        .all(&self.db)
+        .await?;
+
+        Ok((items, total))
+    }
+
+    /// Claim an open item, or renew one's own claim. Fails with a conflict
+    /// while another moderator's claim is active.
+    pub async fn claim(
+        &self,
+        kind: QueueItemKind,
+        item_id: i32,
+        user_id: i32,
+    ) -> AppResult<ModQueueClaimModel> {
+        self.ensure_open(kind, item_id).await?;
+        self.upsert_claim(kind, item_id, user_id, false)
+            .await?
+            .ok_or_else(|| AppError::Conflict("Item is claimed by another moderator".to_string()))
+    }
+
+    /// Hand an open item to `assignee_id`, who must be able to resolve it,
+    /// replacing any claim on it.
+    pub async fn assign(
+        &self,
+        kind: QueueItemKind,
+        item_id: i32,
+        assignee_id: i32,
+    ) -> AppResult<ModQueueClaimModel> {
+        let assignee = User::find_by_id(assignee_id)
+            .one(&self.db)
+            .await?
+            .ok_or_else(|| AppError::Validation("User not found".to_string()))?;
+        if !role_has_permission(&assignee.role, kind.permission()) {
+            return Err(AppError::Validation(format!(
+                "{} can't handle {} items",
+                assignee.shown_name(),
+                kind.as_str()
+            )));
+        }
+        self.ensure_open(kind, item_id).await?;
+        self.upsert_claim(kind, item_id, assignee_id, true)
+            .await?
+            .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Failed to assign queue item")))
+    }
+
+    /// Give up one's claim on an item. Releasing an unclaimed item is a
+    /// no-op; someone else's active claim can't be released.
+    pub async fn release(&self, kind: QueueItemKind, item_id: i32, user_id: i32) -> AppResult<()> {
+        self.ensure_not_claimed_by_other(kind, item_id, user_id)
+            .await?;
+        self.clear(kind, item_id).await
+    }
+
+    /// Fail with a conflict if someone other than `user_id` holds an active
+    /// claim on the item. Call before acting on it.
+    pub async fn ensure_not_claimed_by_other(
+        &self,
+        kind: QueueItemKind,
+        item_id: i32,
+        user_id: i32,
+    ) -> AppResult<()> {
+        let claim = ModQueueClaim::find_by_id((kind.as_str().to_string(), item_id))
+            .one(&self.db)
+            .await?;
+        match claim {
+            Some(c) if c.claimed_by != user_id && c.claimed_at > claim_cutoff() => Err(
+                AppError::Conflict("Item is claimed by another moderator".to_string()),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drop the claim on an item, e.g. once it has been dealt with.
+    pub async fn clear(&self, kind: QueueItemKind, item_id: i32) -> AppResult<()> {
+        ModQueueClaim::delete_many()
+            .filter(mod_queue_claim::Column::ItemType.eq(kind.as_str()))
+            .filter(mod_queue_claim::Column::ItemId.eq(item_id))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Insert or take over the claim. Without `force`, an active claim by
+    /// someone else is left alone and `None` returned.
+    async fn upsert_claim(
+        &self,
+        kind: QueueItemKind,
+        item_id: i32,
+        user_id: i32,
+        force: bool,
+    ) -> AppResult<Option<ModQueueClaimModel>> {
+        let now = chrono::Utc::now().naive_utc();
+        let values: [Value; 6] = [
+            kind.as_str().into(),
+            item_id.into(),
+            user_id.into(),
+            now.into(),
+            force.into(),
+            claim_cutoff().into(),
+        ];
+        let backend = self.db.get_database_backend();
+        if backend == DbBackend::MySql {
+            return self
+                .upsert_claim_mysql(kind, item_id, user_id, values)
+                .await;
+        }
+
+        let sql = "INSERT INTO mod_queue_claims (item_type, item_id, claimed_by, claimed_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (item_type, item_id) DO UPDATE
+                SET claimed_by = EXCLUDED.claimed_by, claimed_at = EXCLUDED.claimed_at
+                WHERE $5
+                   OR mod_queue_claims.claimed_by = EXCLUDED.claimed_by
+                   OR mod_queue_claims.claimed_at <= $6
+            RETURNING *";
+        let claim = ModQueueClaim::find()
+            .from_raw_sql(sql::statement(backend, sql, values))
+            .one(&self.db)
+            .await?;
+        Ok(claim)
+    }
+
+    /// [`Self::upsert_claim`] without `RETURNING` or a conditional upsert:
+    /// each column keeps its value unless the claim may be taken, and the
+    /// row is read back to see who holds it. MySQL applies the assignments
+    /// in order, so `claimed_at` sees the new `claimed_by`.
+    async fn upsert_claim_mysql(
+        &self,
+        kind: QueueItemKind,
+        item_id: i32,
+        user_id: i32,
+        values: [Value; 6],
+    ) -> AppResult<Option<ModQueueClaimModel>> {
+        let take = "($5 OR claimed_by = VALUES(claimed_by) OR claimed_at <= $6)";
+        let sql = format!(
+            "INSERT INTO mod_queue_claims (item_type, item_id, claimed_by, claimed_at)
+            VALUES ($1, $2, $3, $4)
+            ON DUPLICATE KEY UPDATE
+                claimed_by = IF({take}, VALUES(claimed_by), claimed_by),
+                claimed_at = IF({take}, VALUES(claimed_at), claimed_at)"
+        );
+        self.db
+            .execute(sql::statement(DbBackend::MySql, sql, values))
+            .await?;
+
+        let claim = ModQueueClaim::find_by_id((kind.as_str().to_string(), item_id))
+            .one(&self.db)
+            .await?;
+        Ok(claim.filter(|c| c.claimed_by == user_id))
+    }
+
+    /// Not found unless the item still needs a decision.
+    async fn ensure_open(&self, kind: QueueItemKind, item_id: i32) -> AppResult<()> {
+        let open = match kind {
+            QueueItemKind::Report => Report::find_by_id(item_id)
+                .filter(report::Column::Status.eq("pending"))
+                .one(&self.db)
+                .await?
+                .is_some(),
+            QueueItemKind::Appeal => Appeal::find_by_id(item_id)
+                .filter(appeal::Column::Status.eq("pending"))
+                .one(&self.db)
+                .await?
+                .is_some(),
+        };
+        if open {
+            Ok(())
+        } else {
+            Err(AppError::NotFound)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_kind_names_round_trip() {
+        for kind in QueueItemKind::ALL {
+            assert_eq!(QueueItemKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(QueueItemKind::parse("automod"), None);
+    }
+}
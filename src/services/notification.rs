@@ -1,13 +1,26 @@
 use crate::{
     error::AppResult,
-    models::{notification, Notification, NotificationModel},
-    websocket::hub::NotificationHub,
+    models::{notification, user, Notification, NotificationModel, User},
+    services::quiet_hours::QuietHours,
+    websocket::{hub::NotificationHub, protocol::ServerMessage},
 };
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
-    QueryOrder,
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder,
 };
 
+/// Notifications inserted per statement by `notify_many`.
+const NOTIFY_BATCH_SIZE: usize = 500;
+
+/// Which notifications to list.
+#[derive(Debug, Clone, Default)]
+pub struct NotificationFilter {
+    /// Only unread notifications
+    pub unread_only: bool,
+    /// Only these kinds, e.g. `comment_on_post`; all when empty
+    pub kinds: Vec<String>,
+}
+
 pub struct NotificationService {
     db: DatabaseConnection,
     hub: NotificationHub,
@@ -18,6 +31,8 @@ impl NotificationService {
         Self { db, hub }
     }
 
+    /// Save a notification and push it over WebSocket. During the user's
+    /// quiet hours the push is held, to be summarised when they end.
     pub async fn notify(
         &self,
         user_id: i32,
@@ -33,6 +48,18 @@ impl NotificationService {
         }
 
         let now = chrono::Utc::now().naive_utc();
+        let recipient = User::find_by_id(user_id).one(&self.db).await?;
+        // Deactivated accounts aren't notified until they come back
+        if recipient
+            .as_ref()
+            .is_some_and(|u| u.deactivated_at.is_some())
+        {
+            return Ok(());
+        }
+        let held = recipient
+            .as_ref()
+            .and_then(QuietHours::of)
+            .is_some_and(|hours| hours.contains(now));
         let model = notification::ActiveModel {
             user_id: sea_orm::ActiveValue::Set(user_id),
             kind: sea_orm::ActiveValue::Set(kind.to_string()),
@@ -41,37 +68,103 @@ impl NotificationService {
             target_id: sea_orm::ActiveValue::Set(target_id),
             message: sea_orm::ActiveValue::Set(message.to_string()),
             is_read: sea_orm::ActiveValue::Set(false),
+            push_held: sea_orm::ActiveValue::Set(held),
             created_at: sea_orm::ActiveValue::Set(now),
             ..Default::default()
         };
 
         let saved = model.insert(&self.db).await?;
+        if held {
+            return Ok(());
+        }
 
-        // Push via WebSocket
-        let json = serde_json::json!({
-            "type": "notification",
-            "data": {
-                "id": saved.id,
-                "kind": &saved.kind,
-                "message": &saved.message,
-                "target_type": &saved.target_type,
-                "target_id": saved.target_id,
-                "created_at": saved.created_at.to_string(),
-            }
-        });
-        self.hub.send_to_user(user_id, &json.to_string());
+        self.hub
+            .send_to_user(user_id, &ServerMessage::notification(&saved));
 
         Ok(())
     }
 
+    /// Notify many users at once, e.g. for an announcement. Inserts in
+    /// batches and pushes each saved notification over WebSocket, except to
+    /// users in their quiet hours. Returns how many were created.
+    pub async fn notify_many(
+        &self,
+        user_ids: &[i32],
+        actor_id: i32,
+        kind: &str,
+        target_type: &str,
+        target_id: i32,
+        message: &str,
+    ) -> AppResult<u64> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut created = 0;
+        for chunk in user_ids.chunks(NOTIFY_BATCH_SIZE) {
+            let recipients = User::find()
+                .filter(user::Column::Id.is_in(chunk.to_vec()))
+                .filter(
+                    Condition::any()
+                        .add(user::Column::QuietHoursStart.is_not_null())
+                        .add(user::Column::DeactivatedAt.is_not_null()),
+                )
+                .all(&self.db)
+                .await?;
+            let quiet: Vec<i32> = recipients
+                .iter()
+                .filter(|u| QuietHours::of(u).is_some_and(|hours| hours.contains(now)))
+                .map(|u| u.id)
+                .collect();
+            let deactivated: Vec<i32> = recipients
+                .iter()
+                .filter(|u| u.deactivated_at.is_some())
+                .map(|u| u.id)
+                .collect();
+            let models: Vec<_> = chunk
+                .iter()
+                .filter(|&&user_id| user_id != actor_id && !deactivated.contains(&user_id))
+                .map(|&user_id| notification::ActiveModel {
+                    user_id: sea_orm::ActiveValue::Set(user_id),
+                    kind: sea_orm::ActiveValue::Set(kind.to_string()),
+                    actor_id: sea_orm::ActiveValue::Set(actor_id),
+                    target_type: sea_orm::ActiveValue::Set(target_type.to_string()),
+                    target_id: sea_orm::ActiveValue::Set(target_id),
+                    message: sea_orm::ActiveValue::Set(message.to_string()),
+                    is_read: sea_orm::ActiveValue::Set(false),
+                    push_held: sea_orm::ActiveValue::Set(quiet.contains(&user_id)),
+                    created_at: sea_orm::ActiveValue::Set(now),
+                    ..Default::default()
+                })
+                .collect();
+            if models.is_empty() {
+                continue;
+            }
+
+            let saved = Notification::insert_many(models)
+                .exec_with_returning_many(&self.db)
+                .await?;
+            for n in saved.iter().filter(|n| !n.push_held) {
+                self.hub
+                    .send_to_user(n.user_id, &ServerMessage::notification(n));
+            }
+            created += saved.len() as u64;
+        }
+        Ok(created)
+    }
+
     pub async fn list_for_user(
         &self,
         user_id: i32,
+        filter: &NotificationFilter,
         page: u64,
         per_page: u64,
     ) -> AppResult<(Vec<NotificationModel>, u64)> {
-        let paginator = Notification::find()
-            .filter(notification::Column::UserId.eq(user_id))
+        let mut query = Notification::find().filter(notification::Column::UserId.eq(user_id));
+        if filter.unread_only {
+            query = query.filter(notification::Column::IsRead.eq(false));
+        }
+        if !filter.kinds.is_empty() {
+            query = query.filter(notification::Column::Kind.is_in(filter.kinds.clone()));
+        }
+        let paginator = query
             .order_by_desc(notification::Column::CreatedAt)
             .paginate(&self.db, per_page);
 
@@ -115,6 +208,61 @@ impl NotificationService {
             .await?;
         Ok(result.rows_affected)
     }
+
+    /// Mark the user's notifications in `ids`, and those created at or
+    /// before `before`, as read. Returns how many were unread.
+    pub async fn mark_read_many(
+        &self,
+        user_id: i32,
+        ids: &[i32],
+        before: Option<chrono::NaiveDateTime>,
+    ) -> AppResult<u64> {
+        use sea_orm::sea_query::Expr;
+        use sea_orm::Condition;
+
+        let mut selected = Condition::any();
+        if !ids.is_empty() {
+            selected = selected.add(notification::Column::Id.is_in(ids.to_vec()));
+        }
+        if let Some(before) = before {
+            selected = selected.add(notification::Column::CreatedAt.lte(before));
+        }
+        if selected.is_empty() {
+            return Ok(0);
+        }
+
+        let result = Notification::update_many()
+            .col_expr(notification::Column::IsRead, Expr::value(true))
+            .filter(notification::Column::UserId.eq(user_id))
+            .filter(notification::Column::IsRead.eq(false))
+            .filter(selected)
+            .exec(&self.db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    pub async fn delete(&self, id: i32, user_id: i32) -> AppResult<()> {
+        let existing = Notification::find_by_id(id)
+            .one(&self.db)
+            .await?
+            .ok_or(crate::error::AppError::NotFound)?;
+
+        if existing.user_id != user_id {
+            return Err(crate::error::AppError::Forbidden);
+        }
+
+        Notification::delete_by_id(id).exec(&self.db).await?;
+        Ok(())
+    }
+
+    /// Delete all of the user's notifications. Returns how many there were.
+    pub async fn delete_all(&self, user_id: i32) -> AppResult<u64> {
+        let result = Notification::delete_many()
+            .filter(notification::Column::UserId.eq(user_id))
+            .exec(&self.db)
+            .await?;
+        Ok(result.rows_affected)
+    }
 }
 
 #[cfg(test)]
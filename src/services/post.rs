@@ -1,35 +1,126 @@
 use crate::{
     error::{AppError, AppResult},
-    models::{post, Post, PostModel},
+    models::{post, Comment, Forum, Post, PostModel},
+    services::cache::CacheService,
+    services::flair::FlairService,
+    services::search::{author_karma_weight, karma_boost_sql},
+    utils::slug::{slugify, unique_slug},
+    utils::sql,
 };
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
-    FromQueryResult, PaginatorTrait, QueryFilter, QueryOrder, Statement,
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait,
+    FromQueryResult, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
 };
+use std::collections::HashMap;
+
+/// Values accepted for the `sort` of post lists and for
+/// `users.default_post_sort`.
+pub const POST_SORTS: &[&str] = &["new", "top", "hot"];
+
+const CACHE_TTL_POST_LIST: u64 = 30;
+const CACHE_TTL_POST: u64 = 60;
+
+fn post_cache_key(id: i32) -> String {
+    format!("posts:item:{}", id)
+}
+
+fn list_cache_key(
+    forum_id: i32,
+    flair_id: Option<i32>,
+    sort: &str,
+    page: u64,
+    per_page: u64,
+) -> String {
+    let flair = flair_id.map_or("all".to_string(), |id| id.to_string());
+    format!(
+        "posts:list:{}:{}:{}:{}:{}",
+        forum_id, flair, sort, page, per_page
+    )
+}
+
+/// Drop the cached post and every cached listing page of its forum.
+pub async fn invalidate_post_cache(cache: &CacheService, post_id: i32, forum_id: i32) {
+    cache.invalidate(&post_cache_key(post_id)).await;
+    cache
+        .invalidate_pattern(&format!("posts:list:{}:*", forum_id))
+        .await;
+}
+
+/// Posts need a body unless they are links.
+fn require_content(content: &str, url: Option<&str>) -> AppResult<()> {
+    if content.is_empty() && url.is_none() {
+        return Err(AppError::Validation(
+            "content must not be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
 
 pub struct PostService {
     db: DatabaseConnection,
+    cache: Option<CacheService>,
 }
 
 impl PostService {
     pub fn new(db: DatabaseConnection) -> Self {
-        Self { db }
+        Self { db, cache: None }
+    }
+
+    /// Serve listings and `get_by_id_cached` from Redis, and invalidate them
+    /// on writes made through this service.
+    pub fn with_cache(mut self, cache: CacheService) -> Self {
+        self.cache = Some(cache);
+        self
     }
 
+    /// A page of the forum's posts, only those with `flair_id` if given.
     pub async fn list_by_forum(
         &self,
         forum_id: i32,
+        flair_id: Option<i32>,
+        page: u64,
+        per_page: u64,
+        sort: &str,
+    ) -> AppResult<(Vec<PostModel>, u64)> {
+        let key = list_cache_key(forum_id, flair_id, sort, page, per_page);
+        if let Some(cache) = &self.cache {
+            if let Some(cached) = cache.get::<(Vec<PostModel>, u64)>(&key).await {
+                return Ok(cached);
+            }
+        }
+
+        let result = self
+            .list_by_forum_uncached(forum_id, flair_id, page, per_page, sort)
+            .await?;
+
+        if let Some(cache) = &self.cache {
+            cache.set(&key, &result, CACHE_TTL_POST_LIST).await;
+        }
+        Ok(result)
+    }
+
+    async fn list_by_forum_uncached(
+        &self,
+        forum_id: i32,
+        flair_id: Option<i32>,
         page: u64,
         per_page: u64,
         sort: &str,
     ) -> AppResult<(Vec<PostModel>, u64)> {
         match sort {
-            "top" | "hot" => self.list_by_forum_raw(forum_id, page, per_page, sort).await,
+            "top" | "hot" => {
+                self.list_by_forum_raw(forum_id, flair_id, page, per_page, sort)
+                    .await
+            }
             _ => {
                 // "new" (default): use SeaORM paginator
-                let paginator = Post::find()
+                let mut query = Post::find()
                     .filter(post::Column::ForumId.eq(forum_id))
-                    .filter(post::Column::IsHidden.eq(false))
+                    .filter(post::Column::IsHidden.eq(false));
+                if let Some(flair_id) = flair_id {
+                    query = query.filter(post::Column::FlairId.eq(flair_id));
+                }
+                let paginator = query
                     .order_by_desc(post::Column::IsPinned)
                     .order_by_desc(post::Column::CreatedAt)
                     .paginate(&self.db, per_page);
@@ -45,63 +136,65 @@ impl PostService {
     async fn list_by_forum_raw(
         &self,
         forum_id: i32,
+        flair_id: Option<i32>,
         page: u64,
         per_page: u64,
         sort: &str,
     ) -> AppResult<(Vec<PostModel>, u64)> {
         let offset = page.saturating_sub(1) * per_page;
 
-        let author_weight: f64 = std::env::var("POST_AUTHOR_KARMA_WEIGHT")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(0.2);
+        let backend = self.db.get_database_backend();
+        let karma_boost = karma_boost_sql(backend, author_karma_weight());
 
         let order_clause = match sort {
             "top" => format!(
                 "p.is_pinned DESC, \
-                ((p.upvotes - p.downvotes) + (LN(GREATEST(u.karma, 0) + 1) * {author_weight})) DESC, \
+                ((p.upvotes - p.downvotes) + {karma_boost}) DESC, \
                 p.created_at DESC"
             ),
             "hot" => format!(
                 "p.is_pinned DESC, \
-                (((p.upvotes - p.downvotes) + (LN(GREATEST(u.karma, 0) + 1) * {author_weight}))::float / \
-                POWER(EXTRACT(EPOCH FROM (NOW() - p.created_at)) / 3600.0 + 2.0, 1.5)) DESC, \
-                p.created_at DESC"
+                (((p.upvotes - p.downvotes) + {karma_boost}) / \
+                POWER({} + 2.0, 1.5)) DESC, \
+                p.created_at DESC",
+                sql::age_hours(backend, "p.created_at")
             ),
             _ => "p.is_pinned DESC, p.created_at DESC".to_string(),
         };
 
+        // $2 is NULL to list every flair
         let count_sql = "SELECT COUNT(*) as count FROM posts \
-            WHERE forum_id = $1 AND is_hidden = FALSE";
+            WHERE forum_id = $1 AND is_hidden = FALSE AND ($2 IS NULL OR flair_id = $2)";
 
         let search_sql = format!(
             "SELECT p.id, p.user_id, p.forum_id, p.title, p.content, p.upvotes, p.downvotes, \
-                p.view_count, p.is_pinned, p.is_locked, p.is_hidden, p.created_at, p.updated_at \
+                p.view_count, p.is_pinned, p.is_locked, p.is_hidden, p.created_at, p.updated_at, p.pinned_comment_id, p.url, p.flair_id, p.crosspost_of_id, p.slug, p.closed_by_author \
                 FROM posts p \
                 JOIN users u ON u.id = p.user_id \
-                WHERE p.forum_id = $1 AND p.is_hidden = FALSE \
+                WHERE p.forum_id = $1 AND p.is_hidden = FALSE AND ($2 IS NULL OR p.flair_id = $2) \
                 ORDER BY {} \
-                LIMIT $2 OFFSET $3",
+                LIMIT $3 OFFSET $4",
             order_clause
         );
 
         let count_result = self
             .db
-            .query_one(Statement::from_sql_and_values(
-                sea_orm::DatabaseBackend::Postgres,
+            .query_one(sql::statement(
+                backend,
                 count_sql,
-                vec![forum_id.into()],
+                vec![forum_id.into(), flair_id.into()],
             ))
             .await?
             .ok_or(AppError::Internal(anyhow::anyhow!("Count query failed")))?;
 
         let total: i64 = count_result.try_get_by_index(0)?;
 
-        let posts = PostModel::find_by_statement(Statement::from_sql_and_values(
-            sea_orm::DatabaseBackend::Postgres,
+        let posts = PostModel::find_by_statement(sql::statement(
+            backend,
             &search_sql,
             vec![
                 forum_id.into(),
+                flair_id.into(),
                 (per_page as i64).into(),
                 (offset as i64).into(),
             ],
@@ -119,75 +212,254 @@ impl PostService {
             .ok_or(AppError::NotFound)
     }
 
+    /// `get_by_id` for read-only display; may be up to `CACHE_TTL_POST`
+    /// seconds stale for changes made outside this service.
+    pub async fn get_by_id_cached(&self, id: i32) -> AppResult<PostModel> {
+        let key = post_cache_key(id);
+        if let Some(cache) = &self.cache {
+            if let Some(cached) = cache.get::<PostModel>(&key).await {
+                return Ok(cached);
+            }
+        }
+
+        let post = self.get_by_id(id).await?;
+
+        if let Some(cache) = &self.cache {
+            cache.set(&key, &post, CACHE_TTL_POST).await;
+        }
+        Ok(post)
+    }
+
+    async fn invalidate(&self, post: &PostModel) {
+        if let Some(cache) = &self.cache {
+            invalidate_post_cache(cache, post.id, post.forum_id).await;
+        }
+    }
+
+    /// `url` makes it a link post, whose content may be empty.
     pub async fn create(
         &self,
         user_id: i32,
         forum_id: i32,
         title: &str,
         content: &str,
+        url: Option<&str>,
+        flair_id: Option<i32>,
     ) -> AppResult<PostModel> {
+        require_content(content, url)?;
+        FlairService::new(self.db.clone())
+            .check(forum_id, flair_id)
+            .await?;
+        let slug = self.free_slug(forum_id, title).await?;
         let now = chrono::Utc::now().naive_utc();
 
         let new_post = post::ActiveModel {
             user_id: sea_orm::ActiveValue::Set(user_id),
             forum_id: sea_orm::ActiveValue::Set(forum_id),
             title: sea_orm::ActiveValue::Set(title.to_string()),
+            slug: sea_orm::ActiveValue::Set(Some(slug)),
             content: sea_orm::ActiveValue::Set(content.to_string()),
             upvotes: sea_orm::ActiveValue::Set(0),
             downvotes: sea_orm::ActiveValue::Set(0),
             view_count: sea_orm::ActiveValue::Set(0),
             is_pinned: sea_orm::ActiveValue::Set(false),
             is_locked: sea_orm::ActiveValue::Set(false),
+            closed_by_author: sea_orm::ActiveValue::Set(false),
+            created_at: sea_orm::ActiveValue::Set(now),
+            updated_at: sea_orm::ActiveValue::Set(now),
+            url: sea_orm::ActiveValue::Set(url.map(str::to_string)),
+            flair_id: sea_orm::ActiveValue::Set(flair_id),
+            ..Default::default()
+        };
+
+        let post = new_post.insert(&self.db).await?;
+        self.invalidate(&post).await;
+        Ok(post)
+    }
+
+    /// Crosspost a visible post to another forum as `user_id`: a copy of
+    /// its title (unless given), content and link that points back at it.
+    /// Crossposting a crosspost crossposts its original; each forum gets
+    /// at most one crosspost of a post.
+    pub async fn crosspost(
+        &self,
+        id: i32,
+        user_id: i32,
+        forum_id: i32,
+        title: Option<&str>,
+    ) -> AppResult<PostModel> {
+        let mut original = self.get_by_id(id).await?;
+        if let Some(source_id) = original.crosspost_of_id {
+            original = self.get_by_id(source_id).await?;
+        }
+        if original.is_hidden {
+            return Err(AppError::NotFound);
+        }
+        if original.forum_id == forum_id {
+            return Err(AppError::Validation(
+                "Post is already in this forum".to_string(),
+            ));
+        }
+        Forum::find_by_id(forum_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::Validation("Forum not found".to_string()))?;
+        let existing = Post::find()
+            .filter(post::Column::CrosspostOfId.eq(original.id))
+            .filter(post::Column::ForumId.eq(forum_id))
+            .count(&self.db)
+            .await?;
+        if existing > 0 {
+            return Err(AppError::Conflict(
+                "Post is already crossposted to this forum".to_string(),
+            ));
+        }
+
+        let title = title.unwrap_or(&original.title);
+        let slug = self.free_slug(forum_id, title).await?;
+        let now = chrono::Utc::now().naive_utc();
+        let new_post = post::ActiveModel {
+            user_id: sea_orm::ActiveValue::Set(user_id),
+            forum_id: sea_orm::ActiveValue::Set(forum_id),
+            title: sea_orm::ActiveValue::Set(title.to_string()),
+            slug: sea_orm::ActiveValue::Set(Some(slug)),
+            content: sea_orm::ActiveValue::Set(original.content.clone()),
+            upvotes: sea_orm::ActiveValue::Set(0),
+            downvotes: sea_orm::ActiveValue::Set(0),
+            view_count: sea_orm::ActiveValue::Set(0),
+            is_pinned: sea_orm::ActiveValue::Set(false),
+            is_locked: sea_orm::ActiveValue::Set(false),
+            closed_by_author: sea_orm::ActiveValue::Set(false),
             created_at: sea_orm::ActiveValue::Set(now),
             updated_at: sea_orm::ActiveValue::Set(now),
+            url: sea_orm::ActiveValue::Set(original.url.clone()),
+            crosspost_of_id: sea_orm::ActiveValue::Set(Some(original.id)),
             ..Default::default()
         };
 
         let post = new_post.insert(&self.db).await?;
+        self.invalidate(&post).await;
         Ok(post)
     }
 
+    /// Slug for a new post titled `title` in the forum, suffixed with `-2`,
+    /// `-3`, … if another post there has it.
+    async fn free_slug(&self, forum_id: i32, title: &str) -> AppResult<String> {
+        let slug = slugify(title);
+        let taken: Vec<String> = Post::find()
+            .select_only()
+            .column(post::Column::Slug)
+            .filter(post::Column::ForumId.eq(forum_id))
+            .filter(
+                Condition::any()
+                    .add(post::Column::Slug.eq(&slug))
+                    .add(post::Column::Slug.starts_with(format!("{}-", slug))),
+            )
+            .into_tuple::<Option<String>>()
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .flatten()
+            .collect();
+        Ok(unique_slug(&slug, &taken))
+    }
+
+    /// The visible post with `slug` in the forum.
+    pub async fn get_by_slug(&self, forum_id: i32, slug: &str) -> AppResult<PostModel> {
+        Post::find()
+            .filter(post::Column::ForumId.eq(forum_id))
+            .filter(post::Column::Slug.eq(slug))
+            .filter(post::Column::IsHidden.eq(false))
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)
+    }
+
+    /// Posts by ID, for showing where crossposts come from.
+    pub async fn get_many(&self, ids: &[i32]) -> AppResult<HashMap<i32, PostModel>> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let posts = Post::find()
+            .filter(post::Column::Id.is_in(ids.iter().copied()))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|p| (p.id, p))
+            .collect();
+        Ok(posts)
+    }
+
     pub async fn update(
         &self,
         id: i32,
         user_id: i32,
         title: &str,
         content: &str,
+        flair_id: Option<i32>,
     ) -> AppResult<PostModel> {
         let existing = self.get_by_id(id).await?;
         if existing.user_id != user_id {
             return Err(AppError::Forbidden);
         }
+        require_content(content, existing.url.as_deref())?;
+        FlairService::new(self.db.clone())
+            .check(existing.forum_id, flair_id)
+            .await?;
 
         let now = chrono::Utc::now().naive_utc();
 
         let mut active: post::ActiveModel = existing.into();
         active.title = sea_orm::ActiveValue::Set(title.to_string());
         active.content = sea_orm::ActiveValue::Set(content.to_string());
+        active.flair_id = sea_orm::ActiveValue::Set(flair_id);
         active.updated_at = sea_orm::ActiveValue::Set(now);
 
         let updated = active.update(&self.db).await?;
+        self.invalidate(&updated).await;
         Ok(updated)
     }
 
-    pub async fn delete(&self, id: i32, user_id: i32) -> AppResult<()> {
+    /// Delete the user's post and its crossposts, returning the crossposts'
+    /// IDs.
+    pub async fn delete(&self, id: i32, user_id: i32) -> AppResult<Vec<i32>> {
         let existing = self.get_by_id(id).await?;
         if existing.user_id != user_id {
             return Err(AppError::Forbidden);
         }
 
+        let crossposts = Post::find()
+            .filter(post::Column::CrosspostOfId.eq(id))
+            .all(&self.db)
+            .await?;
         Post::delete_by_id(id).exec(&self.db).await?;
-        Ok(())
+        self.invalidate(&existing).await;
+        for crosspost in &crossposts {
+            self.invalidate(crosspost).await;
+        }
+        Ok(crossposts.into_iter().map(|p| p.id).collect())
     }
 
-    pub async fn increment_view_count(&self, id: i32) -> AppResult<()> {
-        self.db
-            .execute(Statement::from_sql_and_values(
-                sea_orm::DatabaseBackend::Postgres,
-                "UPDATE posts SET view_count = view_count + 1 WHERE id = $1",
-                [id.into()],
-            ))
-            .await?;
+    /// Add batched view increments `(post_id, views)` in a single UPDATE.
+    pub async fn add_view_counts(&self, increments: &[(i32, i64)]) -> AppResult<()> {
+        if increments.is_empty() {
+            return Ok(());
+        }
+
+        // Both sides are integers, so inline them: a CASE over bound
+        // parameters can't infer their type on every backend
+        let cases: String = increments
+            .iter()
+            .map(|(id, n)| format!(" WHEN {id} THEN {n}"))
+            .collect();
+        let ids: Vec<String> = increments.iter().map(|(id, _)| id.to_string()).collect();
+        let sql = format!(
+            "UPDATE posts SET view_count = view_count + CASE id{cases} END \
+             WHERE id IN ({})",
+            ids.join(", ")
+        );
+
+        self.db.execute_unprepared(&sql).await?;
         Ok(())
     }
 
@@ -196,116 +468,72 @@ impl PostService {
         let mut active: post::ActiveModel = existing.clone().into();
         active.is_pinned = sea_orm::ActiveValue::Set(!existing.is_pinned);
         let updated = active.update(&self.db).await?;
+        self.invalidate(&updated).await;
         Ok(updated)
     }
 
-    pub async fn toggle_lock(&self, id: i32) -> AppResult<PostModel> {
+    /// Pin a top-level comment to the top of the thread, or unpin it if it is
+    /// already the pinned one.
+    pub async fn toggle_pinned_comment(&self, id: i32, comment_id: i32) -> AppResult<PostModel> {
         let existing = self.get_by_id(id).await?;
-        let mut active: post::ActiveModel = existing.clone().into();
-        active.is_locked = sea_orm::ActiveValue::Set(!existing.is_locked);
-        let updated = active.update(&self.db).await?;
-        Ok(updated)
-    }
 
-    pub async fn search(
-        &self,
-        query: &str,
-        forum_id: Option<i32>,
-        page: u64,
-        per_page: u64,
-        sort: &str,
-    ) -> AppResult<(Vec<PostModel>, u64)> {
-        let offset = page.saturating_sub(1) * per_page;
-
-        let author_weight: f64 = std::env::var("POST_AUTHOR_KARMA_WEIGHT")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(0.2);
-
-        let order_clause = match sort {
-            "new" => "p.created_at DESC".to_string(),
-            "top" => format!(
-                "((p.upvotes - p.downvotes) + (LN(GREATEST(u.karma, 0) + 1) * {author_weight})) DESC, p.created_at DESC"
-            ),
-            _ => format!(
-                "(ts_rank(p.search_vector, plainto_tsquery('english', $1)) + (LN(GREATEST(u.karma, 0) + 1) * {author_weight} * 0.05)) DESC"
-            ),
-        };
+        let comment = Comment::find_by_id(comment_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if comment.post_id != id || comment.is_hidden {
+            return Err(AppError::NotFound);
+        }
+        if comment.parent_id.is_some() {
+            return Err(AppError::Validation(
+                "Only top-level comments can be pinned".to_string(),
+            ));
+        }
 
-        // Build parameterized queries — all values passed via bind params
-        let (count_sql, search_sql, values) = if let Some(fid) = forum_id {
-            let count = "SELECT COUNT(*) as count FROM posts \
-                WHERE search_vector @@ plainto_tsquery('english', $1) \
-                AND is_hidden = FALSE AND forum_id = $2";
-            let search = format!(
-                "SELECT p.id, p.user_id, p.forum_id, p.title, p.content, p.upvotes, p.downvotes, \
-                    p.view_count, p.is_pinned, p.is_locked, p.is_hidden, p.created_at, p.updated_at \
-                    FROM posts p \
-                    JOIN users u ON u.id = p.user_id \
-                    WHERE p.search_vector @@ plainto_tsquery('english', $1) \
-                    AND p.is_hidden = FALSE AND p.forum_id = $2 \
-                    ORDER BY {} \
-                    LIMIT $3 OFFSET $4",
-                order_clause
-            );
-            let vals: Vec<sea_orm::Value> = vec![
-                query.into(),
-                fid.into(),
-                (per_page as i64).into(),
-                (offset as i64).into(),
-            ];
-            (count.to_string(), search, vals)
+        let pinned = if existing.pinned_comment_id == Some(comment_id) {
+            None
         } else {
-            let count = "SELECT COUNT(*) as count FROM posts \
-                WHERE search_vector @@ plainto_tsquery('english', $1) \
-                AND is_hidden = FALSE";
-            let search = format!(
-                "SELECT p.id, p.user_id, p.forum_id, p.title, p.content, p.upvotes, p.downvotes, \
-                    p.view_count, p.is_pinned, p.is_locked, p.is_hidden, p.created_at, p.updated_at \
-                    FROM posts p \
-                    JOIN users u ON u.id = p.user_id \
-                    WHERE p.search_vector @@ plainto_tsquery('english', $1) \
-                    AND p.is_hidden = FALSE \
-                    ORDER BY {} \
-                    LIMIT $2 OFFSET $3",
-                order_clause
-            );
-            let vals: Vec<sea_orm::Value> = vec![
-                query.into(),
-                (per_page as i64).into(),
-                (offset as i64).into(),
-            ];
-            (count.to_string(), search, vals)
+            Some(comment_id)
         };
+        let mut active: post::ActiveModel = existing.into();
+        active.pinned_comment_id = sea_orm::ActiveValue::Set(pinned);
+        let updated = active.update(&self.db).await?;
+        self.invalidate(&updated).await;
+        Ok(updated)
+    }
 
-        // Count total matching rows
-        let count_result = self
-            .db
-            .query_one(Statement::from_sql_and_values(
-                sea_orm::DatabaseBackend::Postgres,
-                &count_sql,
-                values[..if forum_id.is_some() { 2 } else { 1 }].to_vec(),
-            ))
-            .await?
-            .ok_or(AppError::Internal(anyhow::anyhow!("Count query failed")))?;
-
-        let total: i64 = count_result.try_get_by_index(0)?;
-
-        // Fetch paginated results
-        let posts = PostModel::find_by_statement(Statement::from_sql_and_values(
-            sea_orm::DatabaseBackend::Postgres,
-            &search_sql,
-            values,
-        ))
-        .all(&self.db)
-        .await?;
+    /// Close the thread to new comments for its author, or reopen it.
+    pub async fn toggle_closed(&self, id: i32) -> AppResult<PostModel> {
+        let existing = self.get_by_id(id).await?;
+        let mut active: post::ActiveModel = existing.clone().into();
+        active.closed_by_author = sea_orm::ActiveValue::Set(!existing.closed_by_author);
+        let updated = active.update(&self.db).await?;
+        self.invalidate(&updated).await;
+        Ok(updated)
+    }
 
-        Ok((posts, total as u64))
+    pub async fn toggle_lock(&self, id: i32) -> AppResult<PostModel> {
+        let existing = self.get_by_id(id).await?;
+        let mut active: post::ActiveModel = existing.clone().into();
+        active.is_locked = sea_orm::ActiveValue::Set(!existing.is_locked);
+        let updated = active.update(&self.db).await?;
+        self.invalidate(&updated).await;
+        Ok(updated)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn test_list_cache_keys_fall_under_forum_pattern() {
+        let key = super::list_cache_key(12, None, "hot", 2, 20);
+        assert_eq!(key, "posts:list:12:all:hot:2:20");
+        assert!(key.starts_with("posts:list:12:"));
+        let flaired = super::list_cache_key(12, Some(3), "hot", 2, 20);
+        assert_eq!(flaired, "posts:list:12:3:hot:2:20");
+        assert!(!super::list_cache_key(123, None, "new", 1, 20).starts_with("posts:list:12:"));
+    }
+
     fn get_order_clause(sort: &str) -> &str {
         match sort {
             "top" => "is_pinned DESC, (upvotes - downvotes) DESC, created_at DESC",
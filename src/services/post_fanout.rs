@@ -0,0 +1,177 @@
+//! Telling followers about new posts.
+//!
+//! `create_post` only queues a `post_fanouts` row; a worker then sends the
+//! `new_post_from_follow` notifications in batches of followers, so posting
+//! stays fast for accounts with many of them. Progress is saved after each
+//! batch, and a job left `sending` by a stopped instance is picked up again.
+
+use crate::error::AppResult;
+use crate::models::{follow, post_fanout, Follow, PostFanout, PostFanoutModel, PostModel};
+use crate::services::notification::NotificationService;
+use crate::utils::shutdown;
+use crate::websocket::hub::NotificationHub;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use std::time::Duration;
+
+/// Followers notified per batch.
+const BATCH_SIZE: u64 = 500;
+
+/// How often the queue is checked for new posts.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a job may stay `sending` without progress before another worker
+/// takes it over.
+const STALE_SENDING: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Queue the follower notifications for a new post. Does nothing if the
+/// author has no followers.
+pub async fn enqueue(db: &DatabaseConnection, post: &PostModel) -> AppResult<()> {
+    let followers = Follow::find()
+        .filter(follow::Column::FollowingId.eq(post.user_id))
+        .count(db)
+        .await?;
+    if followers == 0 {
+        return Ok(());
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    post_fanout::ActiveModel {
+        post_id: Set(post.id),
+        author_id: Set(post.user_id),
+        last_follower_id: Set(0),
+        status: Set("pending".to_string()),
+        created_at: Set(now),
+        updated_at: Set(now),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
+
+/// Works through queued posts, notifying each author's followers.
+#[derive(Clone)]
+pub struct PostFanoutQueue {
+    db: DatabaseConnection,
+    hub: NotificationHub,
+}
+
+impl PostFanoutQueue {
+    pub fn new(db: DatabaseConnection, hub: NotificationHub) -> Self {
+        Self { db, hub }
+    }
+
+    /// Process queued posts every poll interval until shutdown.
+    pub fn spawn_worker(&self) -> tokio::task::JoinHandle<()> {
+        let queue = self.clone();
+        shutdown::spawn(async move {
+            loop {
+                if let Err(e) = queue.process_queue().await {
+                    tracing::warn!("Failed to notify followers of new posts: {}", e);
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                    _ = shutdown::requested() => break,
+                }
+            }
+        })
+    }
+
+    /// Finish every queued post, returning how many notifications were sent.
+    pub async fn process_queue(&self) -> AppResult<u64> {
+        let mut sent = 0;
+        while let Some(job) = self.claim_next().await? {
+            sent += self.run(job).await?;
+        }
+        Ok(sent)
+    }
+
+    /// Claim the oldest pending or stale job. The status check in the
+    /// update keeps two workers from claiming the same one.
+    async fn claim_next(&self) -> AppResult<Option<PostFanoutModel>> {
+        let now = chrono::Utc::now().naive_utc();
+        let stale = now - STALE_SENDING;
+        let claimable = Condition::any()
+            .add(post_fanout::Column::Status.eq("pending"))
+            .add(post_fanout::Column::UpdatedAt.lt(stale));
+
+        loop {
+            let Some(job) = PostFanout::find()
+                .filter(claimable.clone())
+                .order_by_asc(post_fanout::Column::Id)
+                .one(&self.db)
+                .await?
+            else {
+                return Ok(None);
+            };
+
+            let claimed = PostFanout::update_many()
+                .col_expr(post_fanout::Column::Status, Expr::value("sending"))
+                .col_expr(post_fanout::Column::UpdatedAt, Expr::value(now))
+                .filter(post_fanout::Column::Id.eq(job.id))
+                .filter(claimable.clone())
+                .exec(&self.db)
+                .await?
+                .rows_affected;
+            if claimed == 1 {
+                return Ok(Some(job));
+            }
+        }
+    }
+
+    /// Notify the job's remaining followers batch by batch, then delete it.
+    async fn run(&self, job: PostFanoutModel) -> AppResult<u64> {
+        let notifications = NotificationService::new(self.db.clone(), self.hub.clone());
+        let mut last_follower_id = job.last_follower_id;
+        let mut sent = 0;
+        loop {
+            let followers: Vec<i32> = Follow::find()
+                .select_only()
+                .column(follow::Column::FollowerId)
+                .filter(follow::Column::FollowingId.eq(job.author_id))
+                .filter(follow::Column::FollowerId.gt(last_follower_id))
+                .order_by_asc(follow::Column::FollowerId)
+                .limit(BATCH_SIZE)
+                .into_tuple()
+                .all(&self.db)
+                .await?;
+            let Some(&last) = followers.last() else {
+                break;
+            };
+
+            sent += notifications
+                .notify_many(
+                    &followers,
+                    job.author_id,
+                    "new_post_from_follow",
+                    "post",
+                    job.post_id,
+                    "Someone you follow published a new post",
+                )
+                .await?;
+            last_follower_id = last;
+            PostFanout::update_many()
+                .col_expr(
+                    post_fanout::Column::LastFollowerId,
+                    Expr::value(last_follower_id),
+                )
+                .col_expr(
+                    post_fanout::Column::UpdatedAt,
+                    Expr::value(chrono::Utc::now().naive_utc()),
+                )
+                .filter(post_fanout::Column::Id.eq(job.id))
+                .exec(&self.db)
+                .await?;
+            if (followers.len() as u64) < BATCH_SIZE {
+                break;
+            }
+        }
+
+        PostFanout::delete_by_id(job.id).exec(&self.db).await?;
+        Ok(sent)
+    }
+}
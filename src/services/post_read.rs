@@ -0,0 +1,108 @@
+use crate::{
+    error::{AppError, AppResult},
+    models::Post,
+    utils::sql,
+};
+use sea_orm::{ConnectionTrait, DatabaseConnection, EntityTrait, FromQueryResult};
+use std::collections::HashMap;
+
+/// Unread state of one post for one user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnreadState {
+    /// Never opened, or has comments newer than the last read
+    pub is_unread: bool,
+    /// Visible comments by others since the last read (all of them if never read)
+    pub unread_comment_count: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct UnreadRow {
+    post_id: i32,
+    never_read: bool,
+    unread_comment_count: i64,
+}
+
+pub struct PostReadService {
+    db: DatabaseConnection,
+}
+
+impl PostReadService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Record that the user has read the post up to now.
+    pub async fn mark_read(&self, user_id: i32, post_id: i32) -> AppResult<chrono::NaiveDateTime> {
+        Post::find_by_id(post_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let backend = self.db.get_database_backend();
+        self.db
+            .execute(sql::statement(
+                backend,
+                format!(
+                    "INSERT INTO post_reads (user_id, post_id, last_read_at)
+                     VALUES ($1, $2, $3) {}",
+                    sql::upsert(backend, &["user_id", "post_id"], &["last_read_at"])
+                ),
+                vec![user_id.into(), post_id.into(), now.into()],
+            ))
+            .await?;
+        Ok(now)
+    }
+
+    /// Unread state for a page of posts (batch).
+    pub async fn unread_states(
+        &self,
+        user_id: i32,
+        post_ids: &[i32],
+    ) -> AppResult<HashMap<i32, UnreadState>> {
+        if post_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        // $1 is the user; post ids follow
+        let placeholders: Vec<String> = post_ids
+            .iter()
+            .enumerate()
+            .map(|(i, _)| format!("${}", i + 2))
+            .collect();
+        let sql = format!(
+            "SELECT p.id AS post_id, \
+                    r.last_read_at IS NULL AS never_read, \
+                    (SELECT COUNT(*) FROM comments c \
+                     WHERE c.post_id = p.id AND c.is_hidden = FALSE AND c.user_id <> $1 \
+                       AND (r.last_read_at IS NULL OR c.created_at > r.last_read_at) \
+                    ) AS unread_comment_count \
+                FROM posts p \
+                LEFT JOIN post_reads r ON r.post_id = p.id AND r.user_id = $1 \
+                WHERE p.id IN ({})",
+            placeholders.join(", ")
+        );
+
+        let mut values: Vec<sea_orm::Value> = vec![user_id.into()];
+        values.extend(post_ids.iter().map(|&id| sea_orm::Value::from(id)));
+
+        let rows = UnreadRow::find_by_statement(sql::statement(
+            self.db.get_database_backend(),
+            &sql,
+            values,
+        ))
+        .all(&self.db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                let state = UnreadState {
+                    is_unread: r.never_read || r.unread_comment_count > 0,
+                    unread_comment_count: r.unread_comment_count,
+                };
+                (r.post_id, state)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,94 @@
+//! Live post stats for sockets subscribed to a post's channel.
+//!
+//! Votes, comments and viewers joining or leaving only note the post in the
+//! hub; a background task sends the noted posts' current stats once per
+//! interval, so a busy thread costs one update per interval, not one per
+//! vote.
+
+use crate::config::websocket::WebSocketConfig;
+use crate::error::AppResult;
+use crate::models::{comment, post, Comment, Post};
+use crate::utils::shutdown;
+use crate::websocket::hub::NotificationHub;
+use crate::websocket::protocol::{PostStats, ServerMessage};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect};
+use std::collections::HashMap;
+
+#[derive(Clone)]
+pub struct PostStatsBroadcaster {
+    db: DatabaseConnection,
+    hub: NotificationHub,
+    config: WebSocketConfig,
+}
+
+impl PostStatsBroadcaster {
+    pub fn new(db: DatabaseConnection, hub: NotificationHub, config: WebSocketConfig) -> Self {
+        Self { db, hub, config }
+    }
+
+    pub fn spawn_scheduler(&self) -> tokio::task::JoinHandle<()> {
+        let broadcaster = self.clone();
+        shutdown::spawn(async move {
+            let mut ticker = tokio::time::interval(broadcaster.config.post_stats_interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown::requested() => break,
+                }
+                if let Err(e) = broadcaster.broadcast().await {
+                    tracing::warn!("Failed to broadcast post stats: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Send the stats of every post that changed since the last broadcast,
+    /// returning how many posts were sent.
+    pub async fn broadcast(&self) -> AppResult<usize> {
+        // Stats held back from slow sockets go out before newer ones
+        self.hub.flush_stale_stats();
+        let changed: HashMap<i32, i64> = self.hub.take_changed_posts().into_iter().collect();
+        if changed.is_empty() {
+            return Ok(0);
+        }
+        let post_ids: Vec<i32> = changed.keys().copied().collect();
+
+        let scores: Vec<(i32, i32, i32)> = Post::find()
+            .select_only()
+            .columns([
+                post::Column::Id,
+                post::Column::Upvotes,
+                post::Column::Downvotes,
+            ])
+            .filter(post::Column::Id.is_in(post_ids.clone()))
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        let comment_counts: HashMap<i32, i64> = Comment::find()
+            .select_only()
+            .column(comment::Column::PostId)
+            .column_as(comment::Column::Id.count(), "count")
+            .filter(comment::Column::PostId.is_in(post_ids))
+            .filter(comment::Column::IsHidden.eq(false))
+            .group_by(comment::Column::PostId)
+            .into_tuple::<(i32, i64)>()
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .collect();
+
+        for &(post_id, upvotes, downvotes) in &scores {
+            let stats = PostStats {
+                post_id,
+                score: i64::from(upvotes) - i64::from(downvotes),
+                score_delta: changed[&post_id],
+                comment_count: comment_counts.get(&post_id).copied().unwrap_or(0) as u64,
+                viewer_count: self.hub.viewer_count(post_id),
+            };
+            self.hub
+                .send_to_post(post_id, &ServerMessage::post_stats(&stats));
+        }
+        Ok(scores.len())
+    }
+}
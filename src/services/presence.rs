@@ -0,0 +1,58 @@
+//! Online presence shown on profiles.
+//!
+//! `last_seen_at` is written lazily: by authenticated requests and by a
+//! heartbeat while the user has a socket open, each at most once per
+//! [`TOUCH_INTERVAL`]. A user seen within [`ONLINE_WINDOW`] counts as
+//! online. Users who turn off `show_presence` show neither.
+
+use crate::error::AppResult;
+use crate::models::{user, User, UserModel};
+use chrono::{Duration, NaiveDateTime};
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter};
+
+/// How often `last_seen_at` is written for an active user.
+pub const TOUCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How recently a user must have been seen to count as online.
+const ONLINE_WINDOW: Duration = Duration::minutes(5);
+
+/// What others see of a user's presence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Presence {
+    pub is_online: bool,
+    pub last_seen_at: Option<NaiveDateTime>,
+}
+
+impl Presence {
+    /// The user's presence at `now`, or `None` if they hide it.
+    pub fn of(user: &UserModel, now: NaiveDateTime) -> Option<Self> {
+        if !user.show_presence {
+            return None;
+        }
+        Some(Self {
+            is_online: user
+                .last_seen_at
+                .is_some_and(|seen| now - seen < ONLINE_WINDOW),
+            last_seen_at: user.last_seen_at,
+        })
+    }
+}
+
+/// Note that the user is active, unless that was already noted within the
+/// last [`TOUCH_INTERVAL`].
+pub async fn touch(db: &DatabaseConnection, user_id: i32) -> AppResult<()> {
+    let now = chrono::Utc::now().naive_utc();
+    let stale = now - Duration::seconds(TOUCH_INTERVAL.as_secs() as i64);
+    User::update_many()
+        .col_expr(user::Column::LastSeenAt, Expr::value(now))
+        .filter(user::Column::Id.eq(user_id))
+        .filter(
+            Condition::any()
+                .add(user::Column::LastSeenAt.is_null())
+                .add(user::Column::LastSeenAt.lt(stale)),
+        )
+        .exec(db)
+        .await?;
+    Ok(())
+}
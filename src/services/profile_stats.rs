@@ -0,0 +1,125 @@
+//! Contribution statistics shown on profiles.
+//!
+//! The counts come from one aggregate query and the top tags from a second;
+//! both are cached together for `CACHE_TTL_PROFILE_STATS` seconds, since
+//! every profile view needs them and they needn't be exact.
+
+use crate::error::{AppError, AppResult};
+use crate::services::cache::CacheService;
+use crate::utils::sql;
+use sea_orm::{ConnectionTrait, DatabaseConnection, FromQueryResult};
+use serde::{Deserialize, Serialize};
+
+const CACHE_TTL_PROFILE_STATS: u64 = 60;
+
+/// Tags shown as the user's most used.
+const TOP_TAGS: i64 = 5;
+
+/// Counts of a user's contributions and followers. Karma is the score of all
+/// their posts and comments, hidden ones included, as `users.karma` counts
+/// them; the post and comment counts are of visible ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromQueryResult)]
+pub struct ContributionCounts {
+    pub post_count: i64,
+    pub comment_count: i64,
+    pub post_karma: i64,
+    pub comment_karma: i64,
+    pub follower_count: i64,
+    pub following_count: i64,
+}
+
+/// A tag and how many of the user's posts carry it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromQueryResult)]
+pub struct TagUsage {
+    pub name: String,
+    pub slug: String,
+    pub post_count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileStats {
+    pub counts: ContributionCounts,
+    /// Most used first
+    pub top_tags: Vec<TagUsage>,
+}
+
+fn cache_key(user_id: i32) -> String {
+    format!("profile_stats:{}", user_id)
+}
+
+pub struct ProfileStatsService {
+    db: DatabaseConnection,
+    cache: Option<CacheService>,
+}
+
+impl ProfileStatsService {
+    pub fn new(db: DatabaseConnection, cache: Option<CacheService>) -> Self {
+        Self { db, cache }
+    }
+
+    /// The user's stats, up to `CACHE_TTL_PROFILE_STATS` seconds old when
+    /// Redis is configured.
+    pub async fn get(&self, user_id: i32) -> AppResult<ProfileStats> {
+        let key = cache_key(user_id);
+        if let Some(cache) = &self.cache {
+            if let Some(cached) = cache.get::<ProfileStats>(&key).await {
+                return Ok(cached);
+            }
+        }
+
+        let stats = ProfileStats {
+            counts: self.counts(user_id).await?,
+            top_tags: self.top_tags(user_id).await?,
+        };
+        if let Some(cache) = &self.cache {
+            cache.set(&key, &stats, CACHE_TTL_PROFILE_STATS).await;
+        }
+        Ok(stats)
+    }
+
+    async fn counts(&self, user_id: i32) -> AppResult<ContributionCounts> {
+        let backend = self.db.get_database_backend();
+        let score = |table: &str| {
+            format!(
+                "(SELECT {} FROM {} WHERE user_id = $1)",
+                sql::sum_int(backend, "upvotes - downvotes"),
+                table
+            )
+        };
+        let query = format!(
+            "SELECT \
+                (SELECT COUNT(*) FROM posts WHERE user_id = $1 AND is_hidden = FALSE) AS post_count, \
+                (SELECT COUNT(*) FROM comments WHERE user_id = $1 AND is_hidden = FALSE) AS comment_count, \
+                {} AS post_karma, \
+                {} AS comment_karma, \
+                (SELECT COUNT(*) FROM follows WHERE following_id = $1) AS follower_count, \
+                (SELECT COUNT(*) FROM follows WHERE follower_id = $1) AS following_count",
+            score("posts"),
+            score("comments")
+        );
+        ContributionCounts::find_by_statement(sql::statement(backend, query, vec![user_id.into()]))
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::Internal(anyhow::anyhow!(
+                "Profile stats query failed"
+            )))
+    }
+
+    async fn top_tags(&self, user_id: i32) -> AppResult<Vec<TagUsage>> {
+        let tags = TagUsage::find_by_statement(sql::statement(
+            self.db.get_database_backend(),
+            "SELECT t.name, t.slug, COUNT(*) AS post_count \
+                FROM post_tags pt \
+                JOIN posts p ON p.id = pt.post_id \
+                JOIN tags t ON t.id = pt.tag_id \
+                WHERE p.user_id = $1 AND p.is_hidden = FALSE \
+                GROUP BY t.id, t.name, t.slug \
+                ORDER BY post_count DESC, t.name \
+                LIMIT $2",
+            vec![user_id.into(), TOP_TAGS.into()],
+        ))
+        .all(&self.db)
+        .await?;
+        Ok(tags)
+    }
+}
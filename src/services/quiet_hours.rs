@@ -0,0 +1,302 @@
+//! Do-not-disturb quiet hours.
+//!
+//! During a user's quiet hours, in their own timezone, notifications are
+//! still saved but not pushed over WebSocket, and optional emails wait in
+//! the outbox until the window ends. Once it has, the held notifications
+//! are released with a single summary push instead of one per notification.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{notification, user, Notification, User, UserModel};
+use crate::utils::shutdown;
+use crate::websocket::hub::NotificationHub;
+use crate::websocket::protocol::ServerMessage;
+use chrono::{Duration, NaiveDateTime, NaiveTime, TimeZone, Timelike};
+use chrono_tz::Tz;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QuerySelect, Set,
+};
+
+/// How often held notifications are checked for users whose quiet hours
+/// have ended.
+const RELEASE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// A daily window in a timezone. When `end` is before `start` the window
+/// spans midnight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub timezone: Tz,
+}
+
+impl QuietHours {
+    /// The user's quiet hours, if they set any.
+    pub fn of(user: &UserModel) -> Option<Self> {
+        let start = minute_of_day(user.quiet_hours_start?)?;
+        let end = minute_of_day(user.quiet_hours_end?)?;
+        Some(Self {
+            start,
+            end,
+            timezone: user.timezone.parse().unwrap_or(Tz::UTC),
+        })
+    }
+
+    fn local(&self, now: NaiveDateTime) -> chrono::DateTime<Tz> {
+        self.timezone.from_utc_datetime(&now)
+    }
+
+    /// Whether `now` (UTC) is within the quiet hours.
+    pub fn contains(&self, now: NaiveDateTime) -> bool {
+        let time = self.local(now).time();
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// When the quiet hours around `now` end (UTC), or `None` outside them.
+    pub fn ends_at(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        if !self.contains(now) {
+            return None;
+        }
+        let local = self.local(now);
+        let mut date = local.date_naive();
+        if local.time() >= self.end {
+            date = date.succ_opt()?;
+        }
+        let end = date.and_time(self.end);
+        // A local time skipped by a DST change ends the window an hour later
+        let end = self
+            .timezone
+            .from_local_datetime(&end)
+            .earliest()
+            .or_else(|| {
+                self.timezone
+                    .from_local_datetime(&(end + Duration::hours(1)))
+                    .earliest()
+            })?;
+        Some(end.naive_utc())
+    }
+}
+
+fn minute_of_day(minutes: i32) -> Option<NaiveTime> {
+    let minutes = u32::try_from(minutes).ok()?;
+    NaiveTime::from_hms_opt(minutes / 60, minutes % 60, 0)
+}
+
+/// `HH:MM` for a time of day.
+pub fn format_time(time: NaiveTime) -> String {
+    time.format("%H:%M").to_string()
+}
+
+/// Parse an `HH:MM` time of day.
+pub fn parse_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+/// Parse an IANA timezone name such as `Europe/Berlin`.
+pub fn parse_timezone(value: &str) -> AppResult<Tz> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| AppError::Validation(format!("Unknown timezone: {}", value.trim())))
+}
+
+pub struct QuietHoursService {
+    db: DatabaseConnection,
+}
+
+impl QuietHoursService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Set or clear (`None`) the user's quiet hours, and their timezone if
+    /// given.
+    pub async fn update(
+        &self,
+        user_id: i32,
+        window: Option<(NaiveTime, NaiveTime)>,
+        timezone: Option<Tz>,
+    ) -> AppResult<UserModel> {
+        if let Some((start, end)) = window {
+            if start == end {
+                return Err(AppError::Validation(
+                    "Quiet hours must start and end at different times".to_string(),
+                ));
+            }
+        }
+
+        let existing = User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let minutes = |time: NaiveTime| (time.hour() * 60 + time.minute()) as i32;
+
+        let mut active: user::ActiveModel = existing.into();
+        active.quiet_hours_start = Set(window.map(|(start, _)| minutes(start)));
+        active.quiet_hours_end = Set(window.map(|(_, end)| minutes(end)));
+        if let Some(timezone) = timezone {
+            active.timezone = Set(timezone.name().to_string());
+        }
+        active.updated_at = Set(chrono::Utc::now().naive_utc());
+        Ok(active.update(&self.db).await?)
+    }
+}
+
+/// Releases notifications held during quiet hours once they end.
+#[derive(Clone)]
+pub struct HeldNotifications {
+    db: DatabaseConnection,
+    hub: NotificationHub,
+}
+
+impl HeldNotifications {
+    pub fn new(db: DatabaseConnection, hub: NotificationHub) -> Self {
+        Self { db, hub }
+    }
+
+    pub fn spawn_scheduler(&self) -> tokio::task::JoinHandle<()> {
+        let held = self.clone();
+        shutdown::spawn(async move {
+            let mut ticker = tokio::time::interval(RELEASE_INTERVAL);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown::requested() => break,
+                }
+                if let Err(e) = held.release(chrono::Utc::now().naive_utc()).await {
+                    tracing::warn!("Failed to release held notifications: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Release the held notifications of every user no longer in quiet
+    /// hours at `now`, pushing each of them one summary. Returns how many
+    /// notifications were released.
+    pub async fn release(&self, now: NaiveDateTime) -> AppResult<u64> {
+        let user_ids: Vec<i32> = Notification::find()
+            .select_only()
+            .column(notification::Column::UserId)
+            .distinct()
+            .filter(notification::Column::PushHeld.eq(true))
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        if user_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let users = User::find()
+            .filter(user::Column::Id.is_in(user_ids.clone()))
+            .all(&self.db)
+            .await?;
+        let mut released = 0;
+        for user_id in user_ids {
+            let quiet = users
+                .iter()
+                .find(|u| u.id == user_id)
+                .and_then(QuietHours::of)
+                .is_some_and(|hours| hours.contains(now));
+            if !quiet {
+                released += self.release_user(user_id).await?;
+            }
+        }
+        Ok(released)
+    }
+
+    async fn release_user(&self, user_id: i32) -> AppResult<u64> {
+        let count = Notification::update_many()
+            .col_expr(notification::Column::PushHeld, Expr::value(false))
+            .filter(notification::Column::UserId.eq(user_id))
+            .filter(notification::Column::PushHeld.eq(true))
+            .exec(&self.db)
+            .await?
+            .rows_affected;
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let unread = Notification::find()
+            .filter(notification::Column::UserId.eq(user_id))
+            .filter(notification::Column::IsRead.eq(false))
+            .count(&self.db)
+            .await?;
+        self.hub
+            .send_to_user(user_id, &ServerMessage::notification_summary(count, unread));
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(start: &str, end: &str, timezone: &str) -> QuietHours {
+        QuietHours {
+            start: parse_time(start).unwrap(),
+            end: parse_time(end).unwrap(),
+            timezone: parse_timezone(timezone).unwrap(),
+        }
+    }
+
+    fn utc(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn window_within_a_day() {
+        let lunch = hours("12:00", "13:30", "UTC");
+        assert!(lunch.contains(utc("2026-10-18 12:00")));
+        assert!(!lunch.contains(utc("2026-10-18 13:30")));
+        assert_eq!(
+            lunch.ends_at(utc("2026-10-18 12:15")),
+            Some(utc("2026-10-18 13:30"))
+        );
+        assert_eq!(lunch.ends_at(utc("2026-10-18 14:00")), None);
+    }
+
+    #[test]
+    fn window_across_midnight_in_the_users_timezone() {
+        // 22:00-07:00 in Shanghai is 14:00-23:00 UTC
+        let night = hours("22:00", "07:00", "Asia/Shanghai");
+        assert!(night.contains(utc("2026-10-18 15:00")));
+        assert!(night.contains(utc("2026-10-18 22:59")));
+        assert!(!night.contains(utc("2026-10-18 23:00")));
+        assert!(!night.contains(utc("2026-10-18 13:59")));
+        // Before local midnight the window ends the next local morning
+        assert_eq!(
+            night.ends_at(utc("2026-10-18 15:00")),
+            Some(utc("2026-10-18 23:00"))
+        );
+        // After it, the same local morning
+        assert_eq!(
+            night.ends_at(utc("2026-10-18 20:00")),
+            Some(utc("2026-10-18 23:00"))
+        );
+    }
+
+    #[test]
+    fn end_skipped_by_daylight_saving_moves_an_hour_later() {
+        // Clocks in Berlin go from 02:00 to 03:00 on 2026-03-29
+        let night = hours("23:00", "02:30", "Europe/Berlin");
+        assert_eq!(
+            night.ends_at(utc("2026-03-28 23:00")),
+            Some(utc("2026-03-29 01:30"))
+        );
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!(parse_time(" 07:05 "), NaiveTime::from_hms_opt(7, 5, 0));
+        assert!(parse_time("25:00").is_none());
+        assert!(parse_timezone("Mars/Olympus").is_err());
+        assert_eq!(minute_of_day(7 * 60 + 5), NaiveTime::from_hms_opt(7, 5, 0));
+        assert_eq!(minute_of_day(-1), None);
+    }
+}
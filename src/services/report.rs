@@ -1,12 +1,81 @@
 use crate::{
     error::{AppError, AppResult},
-    models::{comment, post, report, Comment, Post, Report, ReportModel},
+    models::{
+        comment, moderation_action, post, refresh_token, report, user, Comment, Post, RefreshToken,
+        Report, ReportModel, User,
+    },
+    services::mod_queue::{ModQueueService, QueueItemKind},
+    services::points::PointsService,
+    utils::sql,
 };
+use sea_orm::sea_query::Expr;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
-    QueryOrder,
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
 };
 
+/// What resolving a report does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportAction {
+    /// Close the report without acting on it
+    Dismiss,
+    /// Hide the reported post or comment
+    HideContent,
+    /// Delete the reported post or comment
+    DeleteContent,
+    /// Record a warning against the author and notify them
+    WarnUser,
+    /// Ban the author and end their sessions
+    BanUser,
+}
+
+impl ReportAction {
+    pub const ALL: [ReportAction; 5] = [
+        ReportAction::Dismiss,
+        ReportAction::HideContent,
+        ReportAction::DeleteContent,
+        ReportAction::WarnUser,
+        ReportAction::BanUser,
+    ];
+
+    /// Also accepts the older `hide` and `delete` names.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "hide" => Some(ReportAction::HideContent),
+            "delete" => Some(ReportAction::DeleteContent),
+            _ => Self::ALL.into_iter().find(|a| a.as_str() == name),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportAction::Dismiss => "dismiss",
+            ReportAction::HideContent => "hide_content",
+            ReportAction::DeleteContent => "delete_content",
+            ReportAction::WarnUser => "warn_user",
+            ReportAction::BanUser => "ban_user",
+        }
+    }
+
+    /// Told to the reporter once their report is resolved.
+    pub fn outcome_message(&self) -> &'static str {
+        match self {
+            ReportAction::Dismiss => "Your report was reviewed and no action was taken",
+            ReportAction::HideContent => "Your report was reviewed and the content was hidden",
+            ReportAction::DeleteContent => "Your report was reviewed and the content was removed",
+            ReportAction::WarnUser => "Your report was reviewed and the author was warned",
+            ReportAction::BanUser => "Your report was reviewed and the author was banned",
+        }
+    }
+}
+
+/// A resolved report and who the action was taken against.
+pub struct Resolution {
+    pub report: ReportModel,
+    /// Author of the reported content, if it still existed
+    pub author_id: Option<i32>,
+}
+
 pub struct ReportService {
     db: DatabaseConnection,
 }
@@ -94,22 +163,26 @@ impl ReportService {
         Ok((reports, total))
     }
 
+    /// Resolve a pending report. The action, its record in
+    /// `moderation_actions` and the report's new status are written in one
+    /// transaction, so a failed action leaves the report pending.
     pub async fn resolve(
         &self,
         report_id: i32,
-        admin_id: i32,
-        action: &str,
-    ) -> AppResult<ReportModel> {
-        let valid_actions = ["hide", "delete", "dismiss"];
-        if !valid_actions.contains(&action) {
-            return Err(AppError::Validation(format!(
-                "action must be one of: {}",
-                valid_actions.join(", ")
-            )));
-        }
+        moderator_id: i32,
+        action: ReportAction,
+        note: Option<&str>,
+    ) -> AppResult<Resolution> {
+        // Don't act on a report another moderator is handling
+        let queue = ModQueueService::new(self.db.clone());
+        queue
+            .ensure_not_claimed_by_other(QueueItemKind::Report, report_id, moderator_id)
+            .await?;
 
+        let txn = self.db.begin().await?;
         let existing = Report::find_by_id(report_id)
-            .one(&self.db)
+            .lock_exclusive()
+            .one(&txn)
             .await?
             .ok_or(AppError::NotFound)?;
 
@@ -119,69 +192,182 @@ impl ReportService {
             ));
         }
 
-        // Apply action on the target
+        let author_id =
+            Self::target_author(&txn, &existing.target_type, existing.target_id).await?;
+        if author_id.is_none() && matches!(action, ReportAction::WarnUser | ReportAction::BanUser) {
+            return Err(AppError::Validation(
+                "Reported content no longer exists".to_string(),
+            ));
+        }
+
         match action {
-            "hide" => {
-                self.hide_target(&existing.target_type, existing.target_id)
-                    .await?;
+            ReportAction::Dismiss | ReportAction::WarnUser => {}
+            ReportAction::HideContent => {
+                Self::hide_target(&txn, &existing.target_type, existing.target_id).await?;
             }
-            "delete" => {
-                self.delete_target(&existing.target_type, existing.target_id)
-                    .await?;
+            ReportAction::DeleteContent => {
+                Self::delete_target(&txn, &existing.target_type, existing.target_id).await?;
+            }
+            ReportAction::BanUser => {
+                if let Some(author_id) = author_id {
+                    Self::ban(&txn, author_id).await?;
+                }
             }
-            "dismiss" => {}
-            _ => unreachable!(),
         }
 
         let now = chrono::Utc::now().naive_utc();
+        let note = note.map(str::trim).filter(|n| !n.is_empty());
+        // Dismissals aren't actions against anyone
+        if let Some(author_id) = author_id.filter(|_| action != ReportAction::Dismiss) {
+            moderation_action::ActiveModel {
+                user_id: sea_orm::ActiveValue::Set(author_id),
+                moderator_id: sea_orm::ActiveValue::Set(Some(moderator_id)),
+                action: sea_orm::ActiveValue::Set(action.as_str().to_string()),
+                report_id: sea_orm::ActiveValue::Set(Some(report_id)),
+                target_type: sea_orm::ActiveValue::Set(Some(existing.target_type.clone())),
+                target_id: sea_orm::ActiveValue::Set(Some(existing.target_id)),
+                note: sea_orm::ActiveValue::Set(note.map(|n| n.to_string())),
+                created_at: sea_orm::ActiveValue::Set(now),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+        }
+
+        let target_type = existing.target_type.clone();
+        let target_id = existing.target_id;
         let mut active: report::ActiveModel = existing.into();
-        active.status = sea_orm::ActiveValue::Set(if action == "dismiss" {
+        active.status = sea_orm::ActiveValue::Set(if action == ReportAction::Dismiss {
             "dismissed".to_string()
         } else {
             "resolved".to_string()
         });
-        active.resolved_by = sea_orm::ActiveValue::Set(Some(admin_id));
+        active.action = sea_orm::ActiveValue::Set(Some(action.as_str().to_string()));
+        active.resolution_note = sea_orm::ActiveValue::Set(note.map(|n| n.to_string()));
+        active.resolved_by = sea_orm::ActiveValue::Set(Some(moderator_id));
         active.resolved_at = sea_orm::ActiveValue::Set(Some(now));
+        let updated = active.update(&txn).await?;
+        txn.commit().await?;
+
+        queue.clear(QueueItemKind::Report, report_id).await?;
+        if action == ReportAction::DeleteContent && author_id.is_some() {
+            let _ = PointsService::new(self.db.clone())
+                .rollback_by_ref(&target_type, target_id)
+                .await;
+        }
 
-        let updated = active.update(&self.db).await?;
-        Ok(updated)
+        Ok(Resolution {
+            report: updated,
+            author_id,
+        })
     }
 
-    async fn hide_target(&self, target_type: &str, target_id: i32) -> AppResult<()> {
+    async fn target_author<C: ConnectionTrait>(
+        db: &C,
+        target_type: &str,
+        target_id: i32,
+    ) -> AppResult<Option<i32>> {
+        let author = match target_type {
+            "post" => Post::find_by_id(target_id)
+                .one(db)
+                .await?
+                .map(|p| p.user_id),
+            "comment" => Comment::find_by_id(target_id)
+                .one(db)
+                .await?
+                .map(|c| c.user_id),
+            _ => None,
+        };
+        Ok(author)
+    }
+
+    async fn hide_target<C: ConnectionTrait>(
+        db: &C,
+        target_type: &str,
+        target_id: i32,
+    ) -> AppResult<()> {
         match target_type {
             "post" => {
                 let existing = Post::find_by_id(target_id)
-                    .one(&self.db)
+                    .one(db)
                     .await?
                     .ok_or(AppError::NotFound)?;
                 let mut active: post::ActiveModel = existing.into();
                 active.is_hidden = sea_orm::ActiveValue::Set(true);
-                active.update(&self.db).await?;
+                active.update(db).await?;
+                Self::keep_hidden("posts", db, target_id).await?;
             }
             "comment" => {
                 let existing = Comment::find_by_id(target_id)
-                    .one(&self.db)
+                    .one(db)
                     .await?
                     .ok_or(AppError::NotFound)?;
                 let mut active: comment::ActiveModel = existing.into();
                 active.is_hidden = sea_orm::ActiveValue::Set(true);
-                active.update(&self.db).await?;
+                active.update(db).await?;
+                Self::keep_hidden("comments", db, target_id).await?;
             }
             _ => {}
         }
         Ok(())
     }
 
-    async fn delete_target(&self, target_type: &str, target_id: i32) -> AppResult<()> {
+    /// Content a moderator hid stays hidden when its author comes back from
+    /// deactivation.
+    async fn keep_hidden<C: ConnectionTrait>(table: &str, db: &C, id: i32) -> AppResult<()> {
+        db.execute(sql::statement(
+            db.get_database_backend(),
+            format!(
+                "UPDATE {} SET hidden_with_author = FALSE WHERE id = $1",
+                table
+            ),
+            [id.into()],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_target<C: ConnectionTrait>(
+        db: &C,
+        target_type: &str,
+        target_id: i32,
+    ) -> AppResult<()> {
         match target_type {
             "post" => {
-                Post::delete_by_id(target_id).exec(&self.db).await?;
+                Post::delete_by_id(target_id).exec(db).await?;
             }
             "comment" => {
-                Comment::delete_by_id(target_id).exec(&self.db).await?;
+                Comment::delete_by_id(target_id).exec(db).await?;
             }
             _ => {}
         }
         Ok(())
     }
+
+    /// Ban a user and end their sessions. Staff can't be banned this way.
+    async fn ban<C: ConnectionTrait>(db: &C, user_id: i32) -> AppResult<()> {
+        let target = User::find_by_id(user_id)
+            .one(db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if target.role == "admin" || target.role == "moderator" {
+            return Err(AppError::Validation(
+                "Staff accounts can't be banned from a report".to_string(),
+            ));
+        }
+        User::update_many()
+            .col_expr(user::Column::Role, Expr::value("banned"))
+            .col_expr(
+                user::Column::TokenVersion,
+                Expr::col(user::Column::TokenVersion).add(1),
+            )
+            .filter(user::Column::Id.eq(user_id))
+            .exec(db)
+            .await?;
+        RefreshToken::delete_many()
+            .filter(refresh_token::Column::UserId.eq(user_id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
 }
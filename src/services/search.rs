@@ -0,0 +1,868 @@
+//! Search across posts, comments, users, tags and forums.
+//!
+//! All searches go through here so they rank consistently: full-text matches
+//! (posts, comments) use `ts_rank` boosted by the author's karma, and name
+//! matches (users, tags, forums) rank exact > prefix > substring.
+//!
+//! Post search itself is delegated to a [`SearchBackend`], selected with
+//! `SEARCH_BACKEND`: Postgres full-text search by default, or Meilisearch for
+//! typo tolerance. On MySQL the database backend uses `FULLTEXT` indexes
+//! instead of `tsvector`, and on SQLite it falls back to substring matching
+//! (see [`crate::utils::sql`]).
+
+use crate::{
+    config::search::SearchConfig,
+    error::{AppError, AppResult},
+    models::{post, CommentModel, ForumModel, Post, PostModel, TagModel, UserModel},
+    services::{
+        meilisearch::{MeilisearchBackend, MeilisearchConfig},
+        tag::TagService,
+        user::UserService,
+    },
+    utils::sql,
+};
+use async_trait::async_trait;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, FromQueryResult,
+    PaginatorTrait, QueryFilter, QueryOrder, Statement, TransactionTrait, Value,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Parameters of a post search, shared by every backend.
+#[derive(Debug, Clone)]
+pub struct PostSearchQuery {
+    pub q: String,
+    pub forum_id: Option<i32>,
+    pub page: u64,
+    pub per_page: u64,
+    /// `relevance`, `new` or `top`
+    pub sort: String,
+    pub filters: PostSearchFilters,
+}
+
+/// Optional narrowing of a post search; every set field must match.
+#[derive(Debug, Clone, Default)]
+pub struct PostSearchFilters {
+    /// Author username, resolved to `author_id` by `SearchService`
+    pub author: Option<String>,
+    pub author_id: Option<i32>,
+    /// Tag name or slug
+    pub tag: Option<String>,
+    pub created_after: Option<chrono::NaiveDateTime>,
+    pub created_before: Option<chrono::NaiveDateTime>,
+    /// Minimum `upvotes - downvotes`
+    pub min_score: Option<i32>,
+    /// Only posts whose content embeds an image
+    pub has_image: bool,
+    /// Also match hidden posts (staff search). Always answered by Postgres,
+    /// since other backends only index visible posts.
+    pub include_hidden: bool,
+}
+
+/// One page of post search results.
+#[derive(Debug, Default)]
+pub struct PostSearchResults {
+    pub posts: Vec<PostModel>,
+    pub total: u64,
+    /// Corrected query, set when the results came from the fuzzy fallback
+    pub did_you_mean: Option<String>,
+}
+
+/// Engine that answers post searches.
+///
+/// Backends with their own index (`needs_sync`) are told about every post
+/// change through [`SearchIndex::refresh_post`]; Postgres reads the table
+/// directly and ignores them.
+#[async_trait]
+pub trait SearchBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn needs_sync(&self) -> bool {
+        true
+    }
+
+    async fn search_posts(
+        &self,
+        db: &DatabaseConnection,
+        query: &PostSearchQuery,
+    ) -> AppResult<PostSearchResults>;
+
+    /// Add or replace visible posts in the index, with their tag names by post id.
+    async fn index_posts(
+        &self,
+        _posts: &[PostModel],
+        _tags: &HashMap<i32, Vec<String>>,
+    ) -> AppResult<()> {
+        Ok(())
+    }
+
+    async fn remove_post(&self, _id: i32) -> AppResult<()> {
+        Ok(())
+    }
+
+    /// Drop every document before a full reindex.
+    async fn clear(&self) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+/// Default backend: the generated `posts.search_vector` column, or a
+/// substring match when the database is SQLite.
+pub struct PostgresSearch;
+
+#[async_trait]
+impl SearchBackend for PostgresSearch {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn needs_sync(&self) -> bool {
+        false
+    }
+
+    async fn search_posts(
+        &self,
+        db: &DatabaseConnection,
+        query: &PostSearchQuery,
+    ) -> AppResult<PostSearchResults> {
+        postgres_search_posts(db, query).await
+    }
+}
+
+/// Shared handle to the configured backend, passed to handlers as an `Extension`.
+#[derive(Clone)]
+pub struct SearchIndex {
+    backend: Arc<dyn SearchBackend>,
+}
+
+impl SearchIndex {
+    pub fn new(backend: Arc<dyn SearchBackend>) -> Self {
+        Self { backend }
+    }
+
+    /// `SEARCH_BACKEND=meilisearch` selects Meilisearch; anything else uses Postgres.
+    pub fn from_env() -> Self {
+        Self::from_env_with_suffix(None)
+    }
+
+    /// The configured backend for a tenant; Meilisearch gets an index of its
+    /// own, `<MEILISEARCH_INDEX>_<slug>`.
+    pub fn for_tenant(slug: &str) -> Self {
+        Self::from_env_with_suffix(Some(slug))
+    }
+
+    fn from_env_with_suffix(suffix: Option<&str>) -> Self {
+        let backend = std::env::var("SEARCH_BACKEND")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match backend.as_str() {
+            "meilisearch" | "meili" => {
+                let mut config = MeilisearchConfig::from_env();
+                if let Some(suffix) = suffix {
+                    config.index = format!("{}_{}", config.index, suffix);
+                }
+                Self::new(Arc::new(MeilisearchBackend::new(config)))
+            }
+            _ => Self::new(Arc::new(PostgresSearch)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    /// Bring the index in line with the current state of one post.
+    ///
+    /// Best-effort: failures are logged so a search outage never fails writes.
+    pub async fn refresh_post(&self, db: &DatabaseConnection, post_id: i32) {
+        if !self.backend.needs_sync() {
+            return;
+        }
+
+        let result = match Post::find_by_id(post_id).one(db).await {
+            Ok(Some(p)) if !p.is_hidden => self.index_batch(db, &[p]).await,
+            Ok(_) => self.backend.remove_post(post_id).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            tracing::warn!(
+                post_id,
+                backend = self.name(),
+                "Failed to update search index: {}",
+                e
+            );
+        }
+    }
+
+    /// Rebuild the index from every visible post, returning how many were sent.
+    pub async fn reindex(&self, db: &DatabaseConnection) -> AppResult<u64> {
+        if !self.backend.needs_sync() {
+            return Ok(0);
+        }
+
+        self.backend.clear().await?;
+
+        let mut pages = Post::find()
+            .filter(post::Column::IsHidden.eq(false))
+            .order_by_asc(post::Column::Id)
+            .paginate(db, 500);
+        let mut indexed = 0u64;
+        while let Some(batch) = pages.fetch_and_next().await? {
+            self.index_batch(db, &batch).await?;
+            indexed += batch.len() as u64;
+        }
+        Ok(indexed)
+    }
+
+    async fn index_batch(&self, db: &DatabaseConnection, posts: &[PostModel]) -> AppResult<()> {
+        let ids: Vec<i32> = posts.iter().map(|p| p.id).collect();
+        let tags = TagService::new(db.clone()).get_tags_for_posts(&ids).await?;
+        self.backend.index_posts(posts, &tags).await
+    }
+}
+
+/// Result kinds returned by `GET /search/all`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchType {
+    Posts,
+    Comments,
+    Users,
+    Tags,
+    Forums,
+}
+
+impl SearchType {
+    pub const ALL: [SearchType; 5] = [
+        SearchType::Posts,
+        SearchType::Comments,
+        SearchType::Users,
+        SearchType::Tags,
+        SearchType::Forums,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "post" | "posts" => Some(Self::Posts),
+            "comment" | "comments" => Some(Self::Comments),
+            "user" | "users" => Some(Self::Users),
+            "tag" | "tags" => Some(Self::Tags),
+            "forum" | "forums" => Some(Self::Forums),
+            _ => None,
+        }
+    }
+}
+
+/// One group of results with the total number of matches.
+pub struct SearchGroup<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+}
+
+/// Grouped results; a group is `None` when filtered out by `type`.
+#[derive(Default)]
+pub struct SearchAllResults {
+    pub posts: Option<SearchGroup<PostModel>>,
+    pub comments: Option<SearchGroup<CommentModel>>,
+    pub users: Option<SearchGroup<UserModel>>,
+    pub tags: Option<SearchGroup<TagModel>>,
+    pub forums: Option<SearchGroup<ForumModel>>,
+}
+
+/// Weight of the author's karma when ranking posts (`POST_AUTHOR_KARMA_WEIGHT`).
+pub fn author_karma_weight() -> f64 {
+    std::env::var("POST_AUTHOR_KARMA_WEIGHT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0.2)
+}
+
+/// SQL fragment boosting a score by the karma of the author joined as `u`.
+pub fn karma_boost_sql(backend: DbBackend, weight: f64) -> String {
+    format!(
+        "(LN({} + 1) * {weight})",
+        sql::greatest(backend, "u.karma", "0")
+    )
+}
+
+/// Relevance of a full-text match on `vector` against `$1`, nudged by karma.
+fn text_rank_sql(backend: DbBackend, vector: &str, columns: &[&str], weight: f64) -> String {
+    format!(
+        "({} + {} * 0.05)",
+        sql::text_rank(backend, vector, columns, "$1"),
+        karma_boost_sql(backend, weight)
+    )
+}
+
+/// Rank a name column against the raw query `$1`: exact, then prefix, then substring.
+fn name_rank_sql(backend: DbBackend, column: &str) -> String {
+    format!(
+        "CASE WHEN LOWER({column}) = LOWER($1) THEN 3 \
+         WHEN LOWER({column}) LIKE {}{} THEN 2 \
+         ELSE 1 END",
+        sql::concat(backend, &["LOWER($2)", "'%'"]),
+        sql::like_escape(backend)
+    )
+}
+
+/// `column` contains the escaped query bound at `$2`, ignoring case.
+fn name_match_sql(backend: DbBackend, column: &str) -> String {
+    format!(
+        "{column} {} {}{}",
+        sql::ilike(backend),
+        sql::concat(backend, &["'%'", "$2", "'%'"]),
+        sql::like_escape(backend)
+    )
+}
+
+/// Escape `%`, `_` and `\` so user input is matched literally by `LIKE`.
+fn escape_like(query: &str) -> String {
+    let mut escaped = String::with_capacity(query.len());
+    for ch in query.chars() {
+        if matches!(ch, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+pub struct SearchService {
+    db: DatabaseConnection,
+    index: SearchIndex,
+}
+
+impl SearchService {
+    pub fn new(db: DatabaseConnection, index: SearchIndex) -> Self {
+        Self { db, index }
+    }
+
+    /// Search the given result types, returning at most `limit` items per
+    /// group. With `include_hidden`, hidden posts and comments and banned or
+    /// deactivated users match too.
+    pub async fn search_all(
+        &self,
+        query: &str,
+        types: &[SearchType],
+        limit: u64,
+        include_hidden: bool,
+    ) -> AppResult<SearchAllResults> {
+        let wanted = |t: SearchType| types.contains(&t);
+        let mut results = SearchAllResults::default();
+
+        if wanted(SearchType::Posts) {
+            let found = self
+                .search_posts(&PostSearchQuery {
+                    q: query.to_string(),
+                    forum_id: None,
+                    page: 1,
+                    per_page: limit,
+                    sort: "relevance".to_string(),
+                    filters: PostSearchFilters {
+                        include_hidden,
+                        ..Default::default()
+                    },
+                })
+                .await?;
+            results.posts = Some(SearchGroup {
+                items: found.posts,
+                total: found.total,
+            });
+        }
+        if wanted(SearchType::Comments) {
+            results.comments = Some(self.search_comments(query, limit, include_hidden).await?);
+        }
+        if wanted(SearchType::Users) {
+            results.users = Some(self.search_users(query, limit, include_hidden).await?);
+        }
+        if wanted(SearchType::Tags) {
+            results.tags = Some(self.search_tags(query, limit).await?);
+        }
+        if wanted(SearchType::Forums) {
+            results.forums = Some(self.search_forums(query, limit).await?);
+        }
+
+        Ok(results)
+    }
+
+    pub async fn search_posts(&self, query: &PostSearchQuery) -> AppResult<PostSearchResults> {
+        let mut query = query.clone();
+        if let Some(username) = query.filters.author.take() {
+            match UserService::new(self.db.clone())
+                .get_by_username(&username)
+                .await
+            {
+                Ok(user) => query.filters.author_id = Some(user.id),
+                // Unknown author: nothing can match
+                Err(AppError::NotFound) => return Ok(PostSearchResults::default()),
+                Err(e) => return Err(e),
+            }
+        }
+        if query.filters.include_hidden {
+            return postgres_search_posts(&self.db, &query).await;
+        }
+        self.index.backend.search_posts(&self.db, &query).await
+    }
+
+    /// Comments matching `query`; unless `include_hidden`, only visible
+    /// comments on visible posts.
+    async fn search_comments(
+        &self,
+        query: &str,
+        limit: u64,
+        include_hidden: bool,
+    ) -> AppResult<SearchGroup<CommentModel>> {
+        let backend = self.db.get_database_backend();
+        let vector = "to_tsvector('english', c.content)";
+        let columns = ["c.content"];
+        let mut filter = sql::text_match(backend, vector, &columns, "$1");
+        if !include_hidden {
+            filter.push_str(" AND c.is_hidden = FALSE AND p.is_hidden = FALSE");
+        }
+
+        let total = count(
+            &self.db,
+            &format!(
+                "SELECT COUNT(*) AS count FROM comments c \
+                     JOIN posts p ON p.id = c.post_id WHERE {filter}"
+            ),
+            vec![query.into()],
+        )
+        .await?;
+
+        let sql = format!(
+            "SELECT c.* FROM comments c \
+             JOIN posts p ON p.id = c.post_id \
+             JOIN users u ON u.id = c.user_id \
+             WHERE {filter} \
+             ORDER BY {} DESC, c.created_at DESC \
+             LIMIT $2",
+            text_rank_sql(backend, vector, &columns, author_karma_weight())
+        );
+        let items = CommentModel::find_by_statement(sql::statement(
+            backend,
+            &sql,
+            vec![query.into(), (limit as i64).into()],
+        ))
+        .all(&self.db)
+        .await?;
+
+        Ok(SearchGroup { items, total })
+    }
+
+    async fn search_users(
+        &self,
+        query: &str,
+        limit: u64,
+        include_banned: bool,
+    ) -> AppResult<SearchGroup<UserModel>> {
+        let backend = self.db.get_database_backend();
+        let mut filter = name_match_sql(backend, "u.username");
+        if !include_banned {
+            filter.push_str(" AND u.role <> 'banned' AND u.deactivated_at IS NULL");
+        }
+        let values: Vec<Value> = vec![query.into(), escape_like(query).into()];
+
+        let total = count(
+            &self.db,
+            &format!("SELECT COUNT(*) AS count FROM users u WHERE {filter}"),
+            values.clone(),
+        )
+        .await?;
+
+        let sql = format!(
+            "SELECT u.* FROM users u WHERE {filter} \
+             ORDER BY {} DESC, u.karma DESC, u.username ASC LIMIT $3",
+            name_rank_sql(backend, "u.username")
+        );
+        let items = self.find_all::<UserModel>(&sql, values, limit).await?;
+
+        Ok(SearchGroup { items, total })
+    }
+
+    async fn search_tags(&self, query: &str, limit: u64) -> AppResult<SearchGroup<TagModel>> {
+        let backend = self.db.get_database_backend();
+        let filter = format!(
+            "({} OR {})",
+            name_match_sql(backend, "t.name"),
+            name_match_sql(backend, "t.slug")
+        );
+        let values: Vec<Value> = vec![query.into(), escape_like(query).into()];
+
+        let total = count(
+            &self.db,
+            &format!("SELECT COUNT(*) AS count FROM tags t WHERE {filter}"),
+            values.clone(),
+        )
+        .await?;
+
+        let sql = format!(
+            "SELECT t.* FROM tags t WHERE {filter} \
+             ORDER BY {} DESC, t.name ASC LIMIT $3",
+            name_rank_sql(backend, "t.name")
+        );
+        let items = self.find_all::<TagModel>(&sql, values, limit).await?;
+
+        Ok(SearchGroup { items, total })
+    }
+
+    async fn search_forums(&self, query: &str, limit: u64) -> AppResult<SearchGroup<ForumModel>> {
+        let backend = self.db.get_database_backend();
+        let filter = format!(
+            "({} OR {})",
+            name_match_sql(backend, "f.name"),
+            name_match_sql(backend, "f.description")
+        );
+        let values: Vec<Value> = vec![query.into(), escape_like(query).into()];
+
+        let total = count(
+            &self.db,
+            &format!("SELECT COUNT(*) AS count FROM forums f WHERE {filter}"),
+            values.clone(),
+        )
+        .await?;
+
+        let sql = format!(
+            "SELECT f.* FROM forums f WHERE {filter} \
+             ORDER BY {} DESC, f.sort_order ASC LIMIT $3",
+            name_rank_sql(backend, "f.name")
+        );
+        let items = self.find_all::<ForumModel>(&sql, values, limit).await?;
+
+        Ok(SearchGroup { items, total })
+    }
+
+    /// Run a name search whose last bind parameter is the row limit.
+    async fn find_all<M: FromQueryResult>(
+        &self,
+        sql: &str,
+        mut values: Vec<Value>,
+        limit: u64,
+    ) -> AppResult<Vec<M>> {
+        values.push((limit as i64).into());
+        let rows =
+            M::find_by_statement(sql::statement(self.db.get_database_backend(), sql, values))
+                .all(&self.db)
+                .await?;
+        Ok(rows)
+    }
+}
+
+async fn count(db: &DatabaseConnection, sql: &str, values: Vec<Value>) -> AppResult<u64> {
+    let row = db
+        .query_one(sql::statement(db.get_database_backend(), sql, values))
+        .await?
+        .ok_or(AppError::Internal(anyhow::anyhow!("Count query failed")))?;
+    let total: i64 = row.try_get_by_index(0)?;
+    Ok(total as u64)
+}
+
+const POST_COLUMNS: &str = "p.id, p.user_id, p.forum_id, p.title, p.content, p.upvotes, \
+    p.downvotes, p.view_count, p.is_pinned, p.is_locked, p.is_hidden, p.created_at, \
+    p.updated_at, p.pinned_comment_id, p.url, p.flair_id, p.crosspost_of_id, p.slug, p.closed_by_author";
+
+/// Append the visibility, forum and advanced filters of `query` to a `WHERE`
+/// clause over `posts p`, binding every value as a parameter.
+fn push_post_filters(query: &PostSearchQuery, filter: &mut String, values: &mut Vec<Value>) {
+    if !query.filters.include_hidden {
+        filter.push_str(" AND p.is_hidden = FALSE");
+    }
+
+    let mut bind = |sql: &str, value: Value, filter: &mut String| {
+        values.push(value);
+        filter.push_str(&sql.replace("{}", &format!("${}", values.len())));
+    };
+    let f = &query.filters;
+
+    if let Some(fid) = query.forum_id {
+        bind(" AND p.forum_id = {}", fid.into(), filter);
+    }
+    if let Some(author_id) = f.author_id {
+        bind(" AND p.user_id = {}", author_id.into(), filter);
+    }
+    if let Some(tag) = &f.tag {
+        bind(
+            " AND EXISTS (SELECT 1 FROM post_tags pt JOIN tags t ON t.id = pt.tag_id \
+             WHERE pt.post_id = p.id AND (t.slug = {} OR LOWER(t.name) = LOWER({})))",
+            tag.as_str().into(),
+            filter,
+        );
+    }
+    if let Some(after) = f.created_after {
+        bind(" AND p.created_at >= {}", after.into(), filter);
+    }
+    if let Some(before) = f.created_before {
+        bind(" AND p.created_at < {}", before.into(), filter);
+    }
+    if let Some(min_score) = f.min_score {
+        bind(
+            " AND (p.upvotes - p.downvotes) >= {}",
+            min_score.into(),
+            filter,
+        );
+    }
+    if f.has_image {
+        filter.push_str(" AND (p.content LIKE '%![%](%' OR LOWER(p.content) LIKE '%<img%')");
+    }
+}
+
+async fn postgres_search_posts(
+    db: &DatabaseConnection,
+    query: &PostSearchQuery,
+) -> AppResult<PostSearchResults> {
+    let PostSearchQuery {
+        q,
+        page,
+        per_page,
+        sort,
+        ..
+    } = query;
+    let offset = page.saturating_sub(1) * per_page;
+    let weight = author_karma_weight();
+    let backend = db.get_database_backend();
+    let columns = ["p.title", "p.content"];
+
+    let order_clause = match sort.as_str() {
+        "new" => "p.created_at DESC".to_string(),
+        "top" => format!(
+            "((p.upvotes - p.downvotes) + {}) DESC, p.created_at DESC",
+            karma_boost_sql(backend, weight)
+        ),
+        _ => format!(
+            "{} DESC",
+            text_rank_sql(backend, "p.search_vector", &columns, weight)
+        ),
+    };
+
+    let mut filter = sql::text_match(backend, "p.search_vector", &columns, "$1");
+    let mut values: Vec<Value> = vec![q.as_str().into()];
+    push_post_filters(query, &mut filter, &mut values);
+
+    let count_sql = format!("SELECT COUNT(*) AS count FROM posts p WHERE {filter}");
+    let total = count(db, &count_sql, values.clone()).await?;
+
+    // The fuzzy fallback needs pg_trgm
+    if total == 0 && backend == DbBackend::Postgres {
+        let config = SearchConfig::from_env();
+        if config.fuzzy_fallback {
+            return fuzzy_search_posts(db, query, config.fuzzy_threshold).await;
+        }
+    }
+
+    let search_sql = format!(
+        "SELECT {POST_COLUMNS} FROM posts p \
+         JOIN users u ON u.id = p.user_id \
+         WHERE {filter} \
+         ORDER BY {order_clause} \
+         LIMIT ${} OFFSET ${}",
+        values.len() + 1,
+        values.len() + 2
+    );
+    values.push((*per_page as i64).into());
+    values.push((offset as i64).into());
+
+    let posts = PostModel::find_by_statement(sql::statement(backend, &search_sql, values))
+        .all(db)
+        .await?;
+
+    Ok(PostSearchResults {
+        posts,
+        total,
+        did_you_mean: None,
+    })
+}
+
+/// Trigram match on titles for queries with no full-text hits (typos,
+/// partial words). Uses the `pg_trgm` GIN index on `posts.title`.
+async fn fuzzy_search_posts(
+    db: &DatabaseConnection,
+    query: &PostSearchQuery,
+    threshold: f64,
+) -> AppResult<PostSearchResults> {
+    let offset = query.page.saturating_sub(1) * query.per_page;
+
+    let mut filter = "$1 <% p.title".to_string();
+    let mut values: Vec<Value> = vec![query.q.as_str().into()];
+    push_post_filters(query, &mut filter, &mut values);
+
+    // `<%` compares against this setting; SET LOCAL keeps it to this transaction.
+    let txn = db.begin().await?;
+    txn.execute_unprepared(&format!(
+        "SET LOCAL pg_trgm.word_similarity_threshold = {}",
+        threshold.clamp(0.0, 1.0)
+    ))
+    .await?;
+
+    let row = txn
+        .query_one(Statement::from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            format!("SELECT COUNT(*) AS count FROM posts p WHERE {filter}"),
+            values.clone(),
+        ))
+        .await?
+        .ok_or(AppError::Internal(anyhow::anyhow!("Count query failed")))?;
+    let total: i64 = row.try_get_by_index(0)?;
+
+    let search_sql = format!(
+        "SELECT {POST_COLUMNS} FROM posts p \
+         WHERE {filter} \
+         ORDER BY word_similarity($1, p.title) DESC, p.created_at DESC \
+         LIMIT ${} OFFSET ${}",
+        values.len() + 1,
+        values.len() + 2
+    );
+    values.push((query.per_page as i64).into());
+    values.push((offset as i64).into());
+
+    let posts = PostModel::find_by_statement(Statement::from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        &search_sql,
+        values,
+    ))
+    .all(&txn)
+    .await?;
+    txn.commit().await?;
+
+    let titles: Vec<&str> = posts.iter().map(|p| p.title.as_str()).collect();
+    let did_you_mean = suggest_correction(&query.q, &titles);
+
+    Ok(PostSearchResults {
+        posts,
+        total: total as u64,
+        did_you_mean,
+    })
+}
+
+/// Lowercased trigrams of a word, padded the way `pg_trgm` pads them.
+fn trigrams(word: &str) -> HashSet<[char; 3]> {
+    let padded: Vec<char> = format!("  {} ", word.to_lowercase()).chars().collect();
+    padded.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+fn word_similarity(a: &str, b: &str) -> f64 {
+    let (ta, tb) = (trigrams(a), trigrams(b));
+    let union = ta.union(&tb).count();
+    if union == 0 {
+        return 0.0;
+    }
+    ta.intersection(&tb).count() as f64 / union as f64
+}
+
+/// Replace each query word with the closest word from the matched titles and
+/// return the corrected query if anything changed.
+fn suggest_correction(query: &str, titles: &[&str]) -> Option<String> {
+    let vocabulary: HashSet<String> = titles
+        .iter()
+        .flat_map(|t| t.split(|c: char| !c.is_alphanumeric()))
+        .filter(|w| w.chars().count() > 1)
+        .map(|w| w.to_lowercase())
+        .collect();
+
+    let mut changed = false;
+    let corrected: Vec<String> = query
+        .split_whitespace()
+        .map(|word| {
+            let lower = word.to_lowercase();
+            if vocabulary.contains(&lower) {
+                return lower;
+            }
+            let best = vocabulary
+                .iter()
+                .map(|candidate| (word_similarity(&lower, candidate), candidate))
+                .filter(|(score, _)| *score >= 0.3)
+                .max_by(|a, b| a.0.total_cmp(&b.0).then_with(|| b.1.cmp(a.1)));
+            match best {
+                Some((_, candidate)) => {
+                    changed = true;
+                    candidate.clone()
+                }
+                None => lower,
+            }
+        })
+        .collect();
+
+    changed.then(|| corrected.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_search_type() {
+        assert_eq!(SearchType::parse("posts"), Some(SearchType::Posts));
+        assert_eq!(SearchType::parse(" User "), Some(SearchType::Users));
+        assert_eq!(SearchType::parse("everything"), None);
+    }
+
+    #[test]
+    fn test_suggest_correction_fixes_typos() {
+        let titles = ["Rust Programming Tutorial", "Async in practice"];
+        assert_eq!(
+            suggest_correction("rust programing", &titles).as_deref(),
+            Some("rust programming")
+        );
+        assert_eq!(
+            suggest_correction("Tutorail", &titles).as_deref(),
+            Some("tutorial")
+        );
+    }
+
+    #[test]
+    fn test_suggest_correction_none_when_nothing_changes() {
+        let titles = ["Rust Programming Tutorial"];
+        assert_eq!(suggest_correction("rust", &titles), None);
+        assert_eq!(suggest_correction("zzzz", &titles), None);
+        assert_eq!(suggest_correction("rust", &[]), None);
+    }
+
+    #[test]
+    fn test_post_filters_are_parameterized_in_order() {
+        let query = PostSearchQuery {
+            q: "rust".to_string(),
+            forum_id: Some(2),
+            page: 1,
+            per_page: 20,
+            sort: "relevance".to_string(),
+            filters: PostSearchFilters {
+                author_id: Some(7),
+                tag: Some("async".to_string()),
+                min_score: Some(3),
+                has_image: true,
+                ..Default::default()
+            },
+        };
+        let mut filter = "TRUE".to_string();
+        let mut values: Vec<Value> = vec!["rust".into()];
+        push_post_filters(&query, &mut filter, &mut values);
+
+        assert_eq!(values.len(), 5);
+        assert!(filter.contains("p.forum_id = $2"));
+        assert!(filter.contains("p.user_id = $3"));
+        assert!(filter.contains("t.slug = $4 OR LOWER(t.name) = LOWER($4)"));
+        assert!(filter.contains("(p.upvotes - p.downvotes) >= $5"));
+        assert!(filter.contains("<img"));
+        assert!(!filter.contains("async"));
+        assert!(filter.contains("p.is_hidden = FALSE"));
+
+        let mut query = query;
+        query.filters.include_hidden = true;
+        let mut filter = "TRUE".to_string();
+        push_post_filters(&query, &mut filter, &mut vec!["rust".into()]);
+        assert!(!filter.contains("is_hidden"));
+    }
+
+    #[test]
+    fn test_escape_like_wildcards() {
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+        assert_eq!(escape_like("rust"), "rust");
+    }
+
+    #[test]
+    fn test_name_rank_prefers_exact_then_prefix() {
+        let sql = name_rank_sql(DbBackend::Postgres, "t.name");
+        assert!(sql.contains("= LOWER($1) THEN 3"));
+        assert!(sql.contains("THEN 2"));
+    }
+}
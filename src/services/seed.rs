@@ -0,0 +1,159 @@
+//! Reproducible data for local development and demo environments. Every
+//! step is idempotent, so seeding an already seeded database is a no-op.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{post, Post, UserModel};
+use crate::services::bootstrap_admin::{ensure_admin, BootstrapAdminConfig};
+use crate::services::comment::CommentService;
+use crate::services::forum::ForumService;
+use crate::services::post::PostService;
+use crate::services::tag::TagService;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter};
+
+/// `(name, slug, description)`
+const FORUMS: &[(&str, &str, &str)] = &[
+    (
+        "Announcements",
+        "announcements",
+        "News and updates from the team",
+    ),
+    ("General", "general", "General discussion"),
+    ("Help", "help", "Questions and support"),
+    ("Off-topic", "off-topic", "Everything else"),
+];
+
+const TAGS: &[&str] = &["announcement", "discussion", "question", "meta"];
+
+struct DemoPost {
+    forum: &'static str,
+    title: &'static str,
+    content: &'static str,
+    tags: &'static [&'static str],
+    comment: Option<&'static str>,
+}
+
+const DEMO_POSTS: &[DemoPost] = &[
+    DemoPost {
+        forum: "announcements",
+        title: "Welcome to the forum",
+        content: "This is a demo community. Browse the forums, vote on posts \
+                  and join the discussion.\n\nPlease keep it friendly.",
+        tags: &["announcement", "meta"],
+        comment: Some("Feel free to introduce yourself here!"),
+    },
+    DemoPost {
+        forum: "general",
+        title: "What are you working on this week?",
+        content: "Share a project, a problem you solved or something you learned.",
+        tags: &["discussion"],
+        comment: None,
+    },
+    DemoPost {
+        forum: "help",
+        title: "How do I format code in a post?",
+        content: "Posts support Markdown. Wrap code in triple backticks:\n\n\
+                  ```rust\nfn main() {\n    println!(\"hello\");\n}\n```",
+        tags: &["question"],
+        comment: Some("Inline code works with single backticks too."),
+    },
+];
+
+#[derive(Debug)]
+pub struct SeedReport {
+    pub admin: UserModel,
+    /// Whether the admin account was created by this run
+    pub admin_created: bool,
+    pub forums_created: usize,
+    pub posts_created: usize,
+}
+
+pub struct SeedService {
+    db: DatabaseConnection,
+}
+
+impl SeedService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Create the admin, default forums and tags, plus sample posts when
+    /// `demo` is set.
+    pub async fn seed(&self, admin: &BootstrapAdminConfig, demo: bool) -> AppResult<SeedReport> {
+        let (admin, admin_created) = ensure_admin(&self.db, admin).await?;
+        let mut forums_created = 0;
+
+        let forums = ForumService::new(self.db.clone());
+        for (sort_order, (name, slug, description)) in FORUMS.iter().enumerate() {
+            match forums.get_by_slug(slug).await {
+                Ok(_) => {}
+                Err(AppError::NotFound) => {
+                    forums
+                        .create(name, description, slug, sort_order as i32, None)
+                        .await?;
+                    forums_created += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let tags = TagService::new(self.db.clone());
+        tags.get_or_create_tags(TAGS.iter().map(|t| t.to_string()).collect())
+            .await?;
+
+        let posts_created = if demo {
+            self.seed_posts(&admin, &forums, &tags).await?
+        } else {
+            0
+        };
+
+        Ok(SeedReport {
+            admin,
+            admin_created,
+            forums_created,
+            posts_created,
+        })
+    }
+
+    async fn seed_posts(
+        &self,
+        author: &UserModel,
+        forums: &ForumService,
+        tags: &TagService,
+    ) -> AppResult<usize> {
+        let posts = PostService::new(self.db.clone());
+        let comments = CommentService::new(self.db.clone());
+        let mut created = 0;
+
+        for demo in DEMO_POSTS {
+            let forum = forums.get_by_slug(demo.forum).await?;
+            let exists = Post::find()
+                .filter(post::Column::ForumId.eq(forum.id))
+                .filter(post::Column::Title.eq(demo.title))
+                .count(&self.db)
+                .await?
+                > 0;
+            if exists {
+                continue;
+            }
+
+            let post = posts
+                .create(author.id, forum.id, demo.title, demo.content, None, None)
+                .await?;
+            let tag_ids = tags
+                .get_or_create_tags(demo.tags.iter().map(|t| t.to_string()).collect())
+                .await?
+                .into_iter()
+                .map(|t| t.id)
+                .collect();
+            tags.set_post_tags(post.id, tag_ids).await?;
+            if let Some(comment) = demo.comment {
+                comments
+                    .create(post.id, author.id, None, comment, None)
+                    .await?;
+            }
+            created += 1;
+        }
+
+        Ok(created)
+    }
+}
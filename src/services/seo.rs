@@ -0,0 +1,238 @@
+//! Sitemaps: an index pointing at paginated child sitemaps of public
+//! forums, tags, posts and user profiles. Also the post summaries behind
+//! Open Graph tags and oEmbed.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{forum, post, tag, user, Forum, Post, Tag, User};
+use crate::utils::markdown::summarize_markdown;
+use crate::utils::slug::post_ref;
+use chrono::NaiveDateTime;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SitemapKind {
+    Forums,
+    Tags,
+    Posts,
+    Users,
+}
+
+impl SitemapKind {
+    pub const ALL: [SitemapKind; 4] = [
+        SitemapKind::Forums,
+        SitemapKind::Tags,
+        SitemapKind::Posts,
+        SitemapKind::Users,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SitemapKind::Forums => "forums",
+            SitemapKind::Tags => "tags",
+            SitemapKind::Posts => "posts",
+            SitemapKind::Users => "users",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == value)
+    }
+}
+
+/// One page of the site, as path segments under the site origin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapEntry {
+    pub segments: [String; 2],
+    pub lastmod: NaiveDateTime,
+}
+
+impl SitemapEntry {
+    fn new(kind: SitemapKind, id: String, lastmod: NaiveDateTime) -> Self {
+        Self {
+            segments: [kind.as_str().to_string(), id],
+            lastmod,
+        }
+    }
+}
+
+/// `path` under `site_url`, each segment percent-encoded.
+pub fn page_url(site_url: &str, path: &[String]) -> String {
+    match url::Url::parse(site_url) {
+        Ok(mut url) => {
+            if let Ok(mut segments) = url.path_segments_mut() {
+                segments.pop_if_empty().extend(path);
+            }
+            url.to_string()
+        }
+        Err(_) => format!("{}/{}", site_url, path.join("/")),
+    }
+}
+
+pub struct SitemapService {
+    db: DatabaseConnection,
+}
+
+impl SitemapService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// How many URLs of `kind` the sitemaps list.
+    pub async fn count(&self, kind: SitemapKind) -> AppResult<u64> {
+        let count = match kind {
+            SitemapKind::Forums => Forum::find().count(&self.db).await?,
+            SitemapKind::Tags => Tag::find().count(&self.db).await?,
+            SitemapKind::Posts => {
+                Post::find()
+                    .filter(post::Column::IsHidden.eq(false))
+                    .count(&self.db)
+                    .await?
+            }
+            SitemapKind::Users => {
+                User::find()
+                    .filter(user::Column::Role.ne("banned"))
+                    .count(&self.db)
+                    .await?
+            }
+        };
+        Ok(count)
+    }
+
+    /// Page `page` (from 1) of `kind`, oldest first so pages stay stable as
+    /// content is added.
+    pub async fn entries(
+        &self,
+        kind: SitemapKind,
+        page: u64,
+        page_size: u64,
+    ) -> AppResult<Vec<SitemapEntry>> {
+        let offset = page.saturating_sub(1) * page_size;
+        let entries = match kind {
+            SitemapKind::Forums => Forum::find()
+                .select_only()
+                .columns([forum::Column::Slug, forum::Column::UpdatedAt])
+                .order_by_asc(forum::Column::Id)
+                .offset(offset)
+                .limit(page_size)
+                .into_tuple::<(String, NaiveDateTime)>()
+                .all(&self.db)
+                .await?
+                .into_iter()
+                .map(|(slug, updated_at)| SitemapEntry::new(kind, slug, updated_at))
+                .collect(),
+            // Tags are never edited, so creation is their last change
+            SitemapKind::Tags => Tag::find()
+                .select_only()
+                .columns([tag::Column::Slug, tag::Column::CreatedAt])
+                .order_by_asc(tag::Column::Id)
+                .offset(offset)
+                .limit(page_size)
+                .into_tuple::<(String, NaiveDateTime)>()
+                .all(&self.db)
+                .await?
+                .into_iter()
+                .map(|(slug, created_at)| SitemapEntry::new(kind, slug, created_at))
+                .collect(),
+            SitemapKind::Posts => Post::find()
+                .select_only()
+                .columns([
+                    post::Column::Id,
+                    post::Column::Slug,
+                    post::Column::UpdatedAt,
+                ])
+                .filter(post::Column::IsHidden.eq(false))
+                .order_by_asc(post::Column::Id)
+                .offset(offset)
+                .limit(page_size)
+                .into_tuple::<(i32, Option<String>, NaiveDateTime)>()
+                .all(&self.db)
+                .await?
+                .into_iter()
+                .map(|(id, slug, updated_at)| {
+                    SitemapEntry::new(kind, post_ref(id, slug.as_deref()), updated_at)
+                })
+                .collect(),
+            SitemapKind::Users => User::find()
+                .select_only()
+                .columns([user::Column::Username, user::Column::UpdatedAt])
+                .filter(user::Column::Role.ne("banned"))
+                .order_by_asc(user::Column::Id)
+                .offset(offset)
+                .limit(page_size)
+                .into_tuple::<(String, NaiveDateTime)>()
+                .all(&self.db)
+                .await?
+                .into_iter()
+                .map(|(username, updated_at)| SitemapEntry::new(kind, username, updated_at))
+                .collect(),
+        };
+        Ok(entries)
+    }
+}
+
+/// Characters of post text shown in link previews.
+const EXCERPT_CHARS: usize = 200;
+
+/// What a link preview shows for a post.
+#[derive(Debug, Clone)]
+pub struct PostPreview {
+    pub id: i32,
+    pub slug: Option<String>,
+    pub title: String,
+    pub excerpt: String,
+    /// First image in the post, else the author's avatar
+    pub image: Option<String>,
+    /// Author's username, for their profile URL
+    pub author: String,
+    /// How the author's name is shown
+    pub author_name: String,
+    pub forum: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+pub struct LinkPreviewService {
+    db: DatabaseConnection,
+}
+
+impl LinkPreviewService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Hidden posts are not previewed, the same as they are left out of
+    /// the sitemap.
+    pub async fn post(&self, id: i32) -> AppResult<PostPreview> {
+        let post = Post::find_by_id(id)
+            .filter(post::Column::IsHidden.eq(false))
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let author = User::find_by_id(post.user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let forum = Forum::find_by_id(post.forum_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        let summary = summarize_markdown(&post.content, EXCERPT_CHARS);
+        let author_name = author.shown_name().to_string();
+        Ok(PostPreview {
+            id: post.id,
+            slug: post.slug,
+            title: post.title,
+            excerpt: summary.excerpt,
+            image: summary.image.or(author.avatar_url),
+            author: author.username,
+            author_name,
+            forum: forum.name,
+            created_at: post.created_at,
+            updated_at: post.updated_at,
+        })
+    }
+}
@@ -0,0 +1,290 @@
+//! Site-wide settings admins change at runtime.
+//!
+//! Each group of settings is a JSON document in `site_settings` under its
+//! own key. A group that was never saved reads as its `Default`, so a fresh
+//! install behaves as if every setting were at its default.
+//!
+//! Maintenance settings are read on every write request, so with Redis they
+//! are cached for `CACHE_TTL_MAINTENANCE` seconds and dropped on change.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{site_setting, SiteSetting};
+use crate::services::cache::CacheService;
+use sea_orm::{sea_query::OnConflict, DatabaseConnection, EntityTrait, Set};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+const REGISTRATION_KEY: &str = "registration";
+const MAINTENANCE_KEY: &str = "maintenance";
+
+const CACHE_KEY_MAINTENANCE: &str = "settings:maintenance";
+const CACHE_TTL_MAINTENANCE: u64 = 30;
+
+/// `Retry-After` sent during maintenance unless an admin sets another.
+pub const DEFAULT_MAINTENANCE_RETRY_AFTER: u32 = 300;
+
+/// Who may create an account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    /// Anyone
+    #[default]
+    Open,
+    /// Only people with a valid invite code
+    InviteOnly,
+    /// Nobody
+    Closed,
+}
+
+impl RegistrationMode {
+    pub const ALL: [RegistrationMode; 3] = [
+        RegistrationMode::Open,
+        RegistrationMode::InviteOnly,
+        RegistrationMode::Closed,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegistrationMode::Open => "open",
+            RegistrationMode::InviteOnly => "invite_only",
+            RegistrationMode::Closed => "closed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegistrationSettings {
+    pub mode: RegistrationMode,
+    /// When non-empty, only addresses at these domains (or their
+    /// subdomains) may register
+    pub allowed_email_domains: Vec<String>,
+    /// Addresses at these domains (or their subdomains) may never register
+    pub denied_email_domains: Vec<String>,
+}
+
+impl RegistrationSettings {
+    /// Fail unless `email`'s domain passes the deny and allow lists.
+    pub fn check_email(&self, email: &str) -> AppResult<()> {
+        let domain = email
+            .rsplit_once('@')
+            .map(|(_, d)| d.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        let listed = |list: &[String]| list.iter().any(|d| domain_matches(&domain, d));
+
+        if listed(&self.denied_email_domains)
+            || (!self.allowed_email_domains.is_empty() && !listed(&self.allowed_email_domains))
+        {
+            return Err(AppError::Validation(
+                "Registration with this email domain is not allowed".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// While enabled, writes from anyone but admins are refused with 503.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceSettings {
+    pub enabled: bool,
+    /// Shown to users instead of the default notice
+    pub message: Option<String>,
+    /// Sent as `Retry-After`
+    pub retry_after_seconds: u32,
+}
+
+impl Default for MaintenanceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            message: None,
+            retry_after_seconds: DEFAULT_MAINTENANCE_RETRY_AFTER,
+        }
+    }
+}
+
+/// Whether `domain` is `listed` or one of its subdomains.
+fn domain_matches(domain: &str, listed: &str) -> bool {
+    domain == listed
+        || domain
+            .strip_suffix(listed)
+            .is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// Lowercase a domain list, dropping blanks, duplicates and a leading `@`,
+/// and reject entries that aren't domain names.
+pub fn normalize_domains(domains: &[String]) -> AppResult<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::new();
+    for raw in domains {
+        let domain = raw.trim().trim_start_matches('@').to_ascii_lowercase();
+        if domain.is_empty() {
+            continue;
+        }
+        let valid = domain.len() <= 253
+            && domain.contains('.')
+            && domain.split('.').all(|label| {
+                !label.is_empty()
+                    && label.len() <= 63
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+        if !valid {
+            return Err(AppError::Validation(format!(
+                "Invalid email domain: {}",
+                raw.trim()
+            )));
+        }
+        if !normalized.contains(&domain) {
+            normalized.push(domain);
+        }
+    }
+    Ok(normalized)
+}
+
+pub struct SettingsService {
+    db: DatabaseConnection,
+    cache: Option<CacheService>,
+}
+
+impl SettingsService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db, cache: None }
+    }
+
+    pub fn with_cache(mut self, cache: Option<CacheService>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub async fn registration(&self) -> AppResult<RegistrationSettings> {
+        self.get(REGISTRATION_KEY).await
+    }
+
+    pub async fn set_registration(
+        &self,
+        settings: &RegistrationSettings,
+        admin_id: i32,
+    ) -> AppResult<()> {
+        self.set(REGISTRATION_KEY, settings, admin_id).await
+    }
+
+    pub async fn maintenance(&self) -> AppResult<MaintenanceSettings> {
+        if let Some(cache) = &self.cache {
+            if let Some(cached) = cache
+                .get::<MaintenanceSettings>(CACHE_KEY_MAINTENANCE)
+                .await
+            {
+                return Ok(cached);
+            }
+        }
+        let settings: MaintenanceSettings = self.get(MAINTENANCE_KEY).await?;
+        if let Some(cache) = &self.cache {
+            cache
+                .set(CACHE_KEY_MAINTENANCE, &settings, CACHE_TTL_MAINTENANCE)
+                .await;
+        }
+        Ok(settings)
+    }
+
+    pub async fn set_maintenance(
+        &self,
+        settings: &MaintenanceSettings,
+        admin_id: i32,
+    ) -> AppResult<()> {
+        self.set(MAINTENANCE_KEY, settings, admin_id).await?;
+        if let Some(cache) = &self.cache {
+            cache.invalidate(CACHE_KEY_MAINTENANCE).await;
+        }
+        Ok(())
+    }
+
+    async fn get<T: DeserializeOwned + Default>(&self, key: &str) -> AppResult<T> {
+        match SiteSetting::find_by_id(key.to_string())
+            .one(&self.db)
+            .await?
+        {
+            Some(row) => serde_json::from_value(row.value).map_err(|e| {
+                AppError::Internal(anyhow::anyhow!("Invalid {} settings: {}", key, e))
+            }),
+            None => Ok(T::default()),
+        }
+    }
+
+    async fn set<T: Serialize>(&self, key: &str, value: &T, admin_id: i32) -> AppResult<()> {
+        let value = serde_json::to_value(value).map_err(|e| AppError::Internal(e.into()))?;
+        SiteSetting::insert(site_setting::ActiveModel {
+            key: Set(key.to_string()),
+            value: Set(value),
+            updated_by: Set(Some(admin_id)),
+            updated_at: Set(chrono::Utc::now().naive_utc()),
+        })
+        .on_conflict(
+            OnConflict::column(site_setting::Column::Key)
+                .update_columns([
+                    site_setting::Column::Value,
+                    site_setting::Column::UpdatedBy,
+                    site_setting::Column::UpdatedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(&self.db)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(allowed: &[&str], denied: &[&str]) -> RegistrationSettings {
+        RegistrationSettings {
+            mode: RegistrationMode::Open,
+            allowed_email_domains: allowed.iter().map(|d| d.to_string()).collect(),
+            denied_email_domains: denied.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_email_domain_lists() {
+        let open = settings(&[], &[]);
+        assert!(open.check_email("a@anywhere.org").is_ok());
+
+        let denied = settings(&[], &["spam.example"]);
+        assert!(denied.check_email("a@spam.example").is_err());
+        assert!(denied.check_email("a@MAIL.Spam.Example").is_err());
+        assert!(denied.check_email("a@notspam.example").is_ok());
+
+        let allowed = settings(&["corp.example"], &["old.corp.example"]);
+        assert!(allowed.check_email("a@corp.example").is_ok());
+        assert!(allowed.check_email("a@eu.corp.example").is_ok());
+        assert!(allowed.check_email("a@old.corp.example").is_err());
+        assert!(allowed.check_email("a@gmail.com").is_err());
+    }
+
+    #[test]
+    fn test_normalize_domains() {
+        let raw = ["@Example.COM ", "", "example.com", "sub.example.org"].map(String::from);
+        assert_eq!(
+            normalize_domains(&raw).unwrap(),
+            vec!["example.com", "sub.example.org"]
+        );
+        assert!(normalize_domains(&["localhost".to_string()]).is_err());
+        assert!(normalize_domains(&["bad domain.com".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let parsed: RegistrationSettings =
+            serde_json::from_value(serde_json::json!({ "mode": "invite_only" })).unwrap();
+        assert_eq!(parsed.mode, RegistrationMode::InviteOnly);
+        assert!(parsed.denied_email_domains.is_empty());
+        assert_eq!(
+            RegistrationMode::parse("closed"),
+            Some(RegistrationMode::Closed)
+        );
+    }
+}
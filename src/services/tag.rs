@@ -1,8 +1,9 @@
 use crate::error::{AppError, AppResult};
 use crate::models::{post_tag, tag, PostModel, Tag, TagModel};
+use crate::utils::sql;
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
-    FromQueryResult, ModelTrait, QueryFilter, QueryOrder, Set, Statement,
+    FromQueryResult, ModelTrait, QueryFilter, QueryOrder, Set,
 };
 
 pub struct TagService {
@@ -57,8 +58,8 @@ impl TagService {
     pub async fn set_post_tags(&self, post_id: i32, tag_ids: Vec<i32>) -> AppResult<()> {
         // Delete existing tags
         self.db
-            .execute(Statement::from_sql_and_values(
-                sea_orm::DatabaseBackend::Postgres,
+            .execute(sql::statement(
+                self.db.get_database_backend(),
                 "DELETE FROM post_tags WHERE post_id = $1",
                 vec![post_id.into()],
             ))
@@ -79,8 +80,8 @@ impl TagService {
 
     /// Get tags for a single post.
     pub async fn get_post_tags(&self, post_id: i32) -> AppResult<Vec<TagModel>> {
-        let tags = TagModel::find_by_statement(Statement::from_sql_and_values(
-            sea_orm::DatabaseBackend::Postgres,
+        let tags = TagModel::find_by_statement(sql::statement(
+            self.db.get_database_backend(),
             "SELECT t.id, t.name, t.slug, t.created_at \
                 FROM tags t \
                 INNER JOIN post_tags pt ON pt.tag_id = t.id \
@@ -124,11 +125,7 @@ impl TagService {
 
         let rows = self
             .db
-            .query_all(Statement::from_sql_and_values(
-                sea_orm::DatabaseBackend::Postgres,
-                &sql,
-                values,
-            ))
+            .query_all(sql::statement(self.db.get_database_backend(), &sql, values))
             .await?;
 
         let mut map: HashMap<i32, Vec<String>> = HashMap::new();
@@ -169,8 +166,8 @@ impl TagService {
         // Count
         let count_result = self
             .db
-            .query_one(Statement::from_sql_and_values(
-                sea_orm::DatabaseBackend::Postgres,
+            .query_one(sql::statement(
+                self.db.get_database_backend(),
                 "SELECT COUNT(*) as count FROM posts p \
                     INNER JOIN post_tags pt ON pt.post_id = p.id \
                     WHERE pt.tag_id = $1 AND p.is_hidden = FALSE",
@@ -182,10 +179,10 @@ impl TagService {
         let total: i64 = count_result.try_get_by_index(0)?;
 
         // Fetch
-        let posts = PostModel::find_by_statement(Statement::from_sql_and_values(
-            sea_orm::DatabaseBackend::Postgres,
+        let posts = PostModel::find_by_statement(sql::statement(
+            self.db.get_database_backend(),
             "SELECT p.id, p.user_id, p.forum_id, p.title, p.content, p.upvotes, p.downvotes, \
-                p.view_count, p.is_pinned, p.is_locked, p.is_hidden, p.created_at, p.updated_at \
+                p.view_count, p.is_pinned, p.is_locked, p.is_hidden, p.created_at, p.updated_at, p.pinned_comment_id, p.url, p.flair_id, p.crosspost_of_id, p.slug, p.closed_by_author \
                 FROM posts p \
                 INNER JOIN post_tags pt ON pt.post_id = p.id \
                 WHERE pt.tag_id = $1 AND p.is_hidden = FALSE \
@@ -0,0 +1,357 @@
+//! Several independent communities in one deployment.
+//!
+//! Each tenant is a row in the `tenants` table of the default database and a
+//! Postgres schema, `tenant_<slug>`, holding a full copy of the tables.
+//! Requests are routed by `Host`: the tenant middleware swaps in the tenant's
+//! own database pool, cache namespace, notification hub, email queue, search
+//! index and view counter, so every service keeps working unchanged. Hosts
+//! not in the table get the default community.
+
+use crate::config::app::DatabaseSettings;
+use crate::config::database::get_schema_database;
+use crate::config::email::DigestConfig;
+use crate::config::federation::FederationConfig;
+use crate::config::tenancy::TenancyConfig;
+use crate::config::upload_cleanup::UploadCleanupConfig;
+use crate::config::websocket::WebSocketConfig;
+use crate::error::{AppError, AppResult};
+use crate::migration::Migrator;
+use crate::models::{tenant, Tenant, TenantModel};
+use crate::services::badge::BadgeAwarder;
+use crate::services::cache::CacheService;
+use crate::services::digest::DigestService;
+use crate::services::email::EmailService;
+use crate::services::emoji::EmojiCatalogLoader;
+use crate::services::federation::DeliveryQueue;
+use crate::services::post_fanout::PostFanoutQueue;
+use crate::services::post_stats::PostStatsBroadcaster;
+use crate::services::quiet_hours::HeldNotifications;
+use crate::services::search::SearchIndex;
+use crate::services::upload::UploadCleanup;
+use crate::services::view_counter::ViewCounter;
+use crate::utils::tenant::{is_valid_slug, normalize_host, schema_name};
+use crate::websocket::hub::NotificationHub;
+use dashmap::DashMap;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait,
+    QueryFilter, QueryOrder, Set, Statement,
+};
+use sea_orm_migration::MigratorTrait;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// How long the host -> tenant map is used before re-reading `tenants`, so
+/// tenants added with `xjy tenant add` are picked up without a restart.
+const HOSTS_TTL: Duration = Duration::from_secs(30);
+
+pub struct TenantService {
+    db: DatabaseConnection,
+}
+
+impl TenantService {
+    /// `db` is the default community's connection, where `tenants` lives.
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn list(&self) -> AppResult<Vec<TenantModel>> {
+        Ok(Tenant::find()
+            .order_by_asc(tenant::Column::Id)
+            .all(&self.db)
+            .await?)
+    }
+
+    pub async fn find_by_slug(&self, slug: &str) -> AppResult<Option<TenantModel>> {
+        Ok(Tenant::find()
+            .filter(tenant::Column::Slug.eq(slug))
+            .one(&self.db)
+            .await?)
+    }
+
+    /// Register a tenant and create its schema. Its tables are created by
+    /// `connect` with migrations enabled.
+    pub async fn create(&self, slug: &str, name: &str, host: &str) -> AppResult<TenantModel> {
+        if self.db.get_database_backend() != DbBackend::Postgres {
+            return Err(AppError::Validation(
+                "Tenants require PostgreSQL".to_string(),
+            ));
+        }
+        if !is_valid_slug(slug) {
+            return Err(AppError::Validation(
+                "Tenant slug must be 1-40 lowercase letters, digits or underscores".to_string(),
+            ));
+        }
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err(AppError::Validation(
+                "Tenant name must be 1-100 characters".to_string(),
+            ));
+        }
+        let host = normalize_host(host);
+        if host.is_empty() || host.len() > 255 {
+            return Err(AppError::Validation("Invalid tenant host".to_string()));
+        }
+
+        let taken = Tenant::find()
+            .filter(
+                tenant::Column::Slug
+                    .eq(slug)
+                    .or(tenant::Column::Host.eq(host.as_str())),
+            )
+            .one(&self.db)
+            .await?;
+        if let Some(existing) = taken {
+            return Err(AppError::Conflict(if existing.slug == slug {
+                format!("Tenant '{}' already exists", slug)
+            } else {
+                format!(
+                    "Host {} is already used by tenant '{}'",
+                    host, existing.slug
+                )
+            }));
+        }
+
+        let tenant = tenant::ActiveModel {
+            slug: Set(slug.to_string()),
+            name: Set(name.to_string()),
+            host: Set(host),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        self.create_schema(&tenant).await?;
+        Ok(tenant)
+    }
+
+    /// Create the tenant's schema if it is missing. Without it Postgres skips
+    /// the schema on the search path and tables would resolve to `public`.
+    pub async fn create_schema(&self, tenant: &TenantModel) -> AppResult<()> {
+        let sql = format!(
+            "CREATE SCHEMA IF NOT EXISTS \"{}\"",
+            schema_name(&tenant.slug)
+        );
+        self.db
+            .execute(Statement::from_string(DbBackend::Postgres, sql))
+            .await?;
+        Ok(())
+    }
+
+    /// Open a pool on the tenant's schema. With `migrate`, pending migrations
+    /// are applied first; otherwise a schema that is not fully migrated is
+    /// an error, since missing tables would fall through to `public`.
+    pub async fn connect(
+        &self,
+        tenant: &TenantModel,
+        settings: &DatabaseSettings,
+        max_connections: u32,
+        migrate: bool,
+    ) -> AppResult<DatabaseConnection> {
+        self.create_schema(tenant).await?;
+        let schema = schema_name(&tenant.slug);
+        let db = get_schema_database(settings, &schema, max_connections).await?;
+
+        if migrate {
+            Migrator::up(&db, None).await?;
+        } else if !self.is_migrated(&db, &schema).await? {
+            return Err(AppError::Internal(anyhow::anyhow!(
+                "Tenant '{}' has pending migrations; run `xjy migrate up --tenant {}`",
+                tenant.slug,
+                tenant.slug
+            )));
+        }
+        Ok(db)
+    }
+
+    async fn is_migrated(&self, db: &DatabaseConnection, schema: &str) -> AppResult<bool> {
+        // Checked in the schema itself: an unqualified lookup would find
+        // `public.seaql_migrations` when the tenant has none
+        let own_table = self
+            .db
+            .query_one(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "SELECT 1 FROM information_schema.tables \
+                 WHERE table_schema = $1 AND table_name = 'seaql_migrations'",
+                [schema.into()],
+            ))
+            .await?
+            .is_some();
+        Ok(own_table && Migrator::get_pending_migrations(db).await?.is_empty())
+    }
+}
+
+/// Everything request handlers get as extensions, for one tenant.
+pub struct TenantContext {
+    pub tenant: TenantModel,
+    pub db: DatabaseConnection,
+    pub hub: NotificationHub,
+    pub cache: Option<CacheService>,
+    pub email_service: EmailService,
+    pub search_index: SearchIndex,
+    pub view_counter: ViewCounter,
+}
+
+impl TenantContext {
+    /// Replace the default community's extensions with this tenant's.
+    pub fn apply(&self, extensions: &mut axum::http::Extensions) {
+        extensions.insert(self.db.clone());
+        extensions.insert(self.hub.clone());
+        extensions.insert(self.email_service.clone());
+        extensions.insert(self.search_index.clone());
+        extensions.insert(self.view_counter.clone());
+        if let Some(cache) = &self.cache {
+            extensions.insert(cache.clone());
+        }
+    }
+}
+
+/// Resolves hosts to tenants and sets up each tenant on its first request.
+#[derive(Clone)]
+pub struct TenantRegistry {
+    inner: Arc<RegistryInner>,
+}
+
+struct RegistryInner {
+    db: DatabaseConnection,
+    settings: DatabaseSettings,
+    config: TenancyConfig,
+    run_migrations: bool,
+    cache: Option<CacheService>,
+    email_service: EmailService,
+    /// Tenant files live under `<upload_dir>/tenants/<slug>/`
+    upload_dir: String,
+    hosts: RwLock<Option<(Instant, HashMap<String, TenantModel>)>>,
+    contexts: DashMap<String, Arc<OnceCell<Arc<TenantContext>>>>,
+}
+
+impl TenantRegistry {
+    /// `db`, `cache` and `email_service` are the default community's;
+    /// tenants share the Redis connection and email provider.
+    pub fn new(
+        db: DatabaseConnection,
+        settings: DatabaseSettings,
+        config: TenancyConfig,
+        run_migrations: bool,
+        cache: Option<CacheService>,
+        email_service: EmailService,
+        upload_dir: String,
+    ) -> Self {
+        Self {
+            inner: Arc::new(RegistryInner {
+                db,
+                settings,
+                config,
+                run_migrations,
+                cache,
+                email_service,
+                upload_dir,
+                hosts: RwLock::new(None),
+                contexts: DashMap::new(),
+            }),
+        }
+    }
+
+    /// The tenant served on `host` (already normalized), or `None` for the
+    /// default community.
+    pub async fn resolve(&self, host: &str) -> AppResult<Option<Arc<TenantContext>>> {
+        let Some(tenant) = self.tenant_for_host(host).await? else {
+            return Ok(None);
+        };
+        let cell = self
+            .inner
+            .contexts
+            .entry(tenant.slug.clone())
+            .or_default()
+            .clone();
+        // A failed setup leaves the cell empty, so the next request retries
+        let context = cell.get_or_try_init(|| self.build(tenant)).await?;
+        Ok(Some(context.clone()))
+    }
+
+    /// Tenants that have served a request since startup.
+    pub fn contexts(&self) -> Vec<Arc<TenantContext>> {
+        self.inner
+            .contexts
+            .iter()
+            .filter_map(|entry| entry.value().get().cloned())
+            .collect()
+    }
+
+    async fn tenant_for_host(&self, host: &str) -> AppResult<Option<TenantModel>> {
+        if let Some((loaded_at, hosts)) = &*self.inner.hosts.read().unwrap() {
+            if loaded_at.elapsed() < HOSTS_TTL {
+                return Ok(hosts.get(host).cloned());
+            }
+        }
+
+        let hosts: HashMap<String, TenantModel> = TenantService::new(self.inner.db.clone())
+            .list()
+            .await?
+            .into_iter()
+            .map(|t| (t.host.clone(), t))
+            .collect();
+        let found = hosts.get(host).cloned();
+        *self.inner.hosts.write().unwrap() = Some((Instant::now(), hosts));
+        Ok(found)
+    }
+
+    async fn build(&self, tenant: TenantModel) -> AppResult<Arc<TenantContext>> {
+        let inner = &self.inner;
+        let db = TenantService::new(inner.db.clone())
+            .connect(
+                &tenant,
+                &inner.settings,
+                inner.config.max_connections,
+                inner.run_migrations,
+            )
+            .await?;
+
+        let cache = inner
+            .cache
+            .as_ref()
+            .map(|cache| cache.with_prefix(format!("tenant:{}:", tenant.slug)));
+
+        let email_service = inner.email_service.for_host(&tenant.host);
+        if email_service.is_configured() {
+            email_service.spawn_worker(db.clone());
+            DigestService::new(db.clone(), email_service.clone(), DigestConfig::from_env())
+                .spawn_scheduler();
+        }
+
+        let view_counter = ViewCounter::from_env(cache.clone());
+        view_counter.spawn_flusher(db.clone());
+
+        let hub = NotificationHub::new();
+        HeldNotifications::new(db.clone(), hub.clone()).spawn_scheduler();
+        PostFanoutQueue::new(db.clone(), hub.clone()).spawn_worker();
+        PostStatsBroadcaster::new(db.clone(), hub.clone(), WebSocketConfig::from_env())
+            .spawn_scheduler();
+        BadgeAwarder::new(db.clone()).spawn_scheduler();
+        EmojiCatalogLoader::new(db.clone(), Some(tenant.slug.clone())).spawn_scheduler();
+
+        UploadCleanup::new(
+            db.clone(),
+            inner.upload_dir.clone(),
+            UploadCleanupConfig::from_env(),
+        )
+        .spawn_scheduler();
+
+        let federation = FederationConfig::from_env();
+        if federation.enabled {
+            DeliveryQueue::new(db.clone(), federation).spawn_worker();
+        }
+
+        tracing::info!("Tenant '{}' ready on {}", tenant.slug, tenant.host);
+        Ok(Arc::new(TenantContext {
+            search_index: SearchIndex::for_tenant(&tenant.slug),
+            tenant,
+            db,
+            hub,
+            cache,
+            email_service,
+            view_counter,
+        }))
+    }
+}
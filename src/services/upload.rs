@@ -1,14 +1,48 @@
+use crate::config::app::UploadSettings;
+use crate::config::upload_cleanup::UploadCleanupConfig;
 use crate::error::{AppError, AppResult};
-use std::path::Path;
+use crate::models::{comment, post, upload, user, Comment, Post, Upload, UploadModel, User};
+use crate::services::audit::{AuditEntry, AuditLogService};
+use crate::services::virus_scan::{ScanVerdict, VirusScanner};
+use crate::utils::{shutdown, tenant};
+use chrono::NaiveDateTime;
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::error::{LimitError, LimitErrorKind};
+use image::metadata::Orientation;
+use image::{
+    AnimationDecoder, DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader,
+    ImageResult, Limits,
+};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set,
+};
+use std::io::{Cursor, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct UploadConfig {
     pub upload_dir: String,
+    /// Largest accepted file, in bytes
+    pub max_file_size: usize,
 }
 
-pub const MAX_FILE_SIZE: usize = 5 * 1024 * 1024; // 5 MB
+impl From<&UploadSettings> for UploadConfig {
+    fn from(settings: &UploadSettings) -> Self {
+        Self {
+            upload_dir: settings.dir.clone(),
+            max_file_size: settings.max_file_size,
+        }
+    }
+}
 const ALLOWED_CONTENT_TYPES: &[&str] = &["image/jpeg", "image/png", "image/gif", "image/webp"];
 
 /// Validate file magic bytes match the declared content type.
@@ -26,59 +60,534 @@ fn validate_magic_bytes(data: &[u8], content_type: &str) -> bool {
     }
 }
 
+/// Uploads are streamed here first, on the same filesystem as their final
+/// place so they can be moved with a rename.
+const TEMP_DIR: &str = ".tmp";
+
+/// Bytes kept from the start of a streamed file for the magic bytes check.
+const HEAD_LEN: usize = 12;
+
+/// A file being received, written to `<upload_dir>/.tmp` chunk by chunk.
+/// The temp file is removed when this is dropped, unless it was saved with
+/// [`UploadService::save_upload`].
+pub struct TempUpload {
+    path: Option<PathBuf>,
+    file: fs::File,
+    head: Vec<u8>,
+    len: usize,
+    max_file_size: usize,
+}
+
+impl TempUpload {
+    pub async fn create(config: &UploadConfig) -> AppResult<Self> {
+        let dir = Path::new(&config.upload_dir).join(TEMP_DIR);
+        fs::create_dir_all(&dir).await.map_err(|e| {
+            AppError::Validation(format!("Failed to create upload directory: {}", e))
+        })?;
+        let path = dir.join(format!("{}.part", Uuid::new_v4()));
+        let file = fs::File::create(&path)
+            .await
+            .map_err(|e| AppError::Validation(format!("Failed to write file: {}", e)))?;
+        Ok(Self {
+            path: Some(path),
+            file,
+            head: Vec::with_capacity(HEAD_LEN),
+            len: 0,
+            max_file_size: config.max_file_size,
+        })
+    }
+
+    /// Append a chunk, failing with 413 as soon as the file grows past the
+    /// size limit.
+    pub async fn write(&mut self, chunk: &[u8]) -> AppResult<()> {
+        if self.len + chunk.len() > self.max_file_size {
+            return Err(AppError::PayloadTooLarge);
+        }
+        let missing = HEAD_LEN.saturating_sub(self.head.len()).min(chunk.len());
+        self.head.extend_from_slice(&chunk[..missing]);
+        self.len += chunk.len();
+        self.file
+            .write_all(chunk)
+            .await
+            .map_err(|e| AppError::Validation(format!("Failed to write file: {}", e)))
+    }
+}
+
+impl Drop for TempUpload {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            if let Err(e) = std::fs::remove_file(&path) {
+                tracing::warn!("Failed to remove temp upload {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// A file moved into place by [`UploadService::save_upload`].
+#[derive(Debug, Clone)]
+pub struct SavedUpload {
+    /// Public URL path, e.g. `/uploads/avatars/<uuid>.jpg`
+    pub url: String,
+    /// Relative to the upload directory, e.g. `avatars/<uuid>.jpg`
+    pub path: String,
+    /// In bytes
+    pub size: usize,
+}
+
 pub struct UploadService;
 
 impl UploadService {
-    /// Save an uploaded file to disk.
+    /// Run the virus scanner, when one is configured, over a received
+    /// file. An infected file is rejected and recorded in the audit log; so
+    /// is any file while the scanner fails, unless `UPLOAD_SCAN_FAIL_OPEN`.
+    pub async fn scan(
+        db: &DatabaseConnection,
+        upload: &mut TempUpload,
+        content_type: &str,
+        user_id: i32,
+    ) -> AppResult<()> {
+        let scanner = virus_scanner();
+        if !scanner.enabled() {
+            return Ok(());
+        }
+        upload
+            .file
+            .flush()
+            .await
+            .map_err(|e| AppError::Validation(format!("Failed to write file: {}", e)))?;
+        let path = upload.path.as_deref().expect("temp upload already saved");
+
+        match scanner.scan(path).await {
+            Ok(ScanVerdict::Clean) => Ok(()),
+            Ok(ScanVerdict::Infected(signature)) => {
+                tracing::warn!("Rejected upload by user {}: {}", user_id, signature);
+                AuditLogService::new(db.clone())
+                    .record(AuditEntry {
+                        actor_id: Some(user_id),
+                        action: "upload_rejected",
+                        detail: Some(format!(
+                            "signature: {}, content type: {}, size: {}",
+                            signature, content_type, upload.len
+                        )),
+                        ..Default::default()
+                    })
+                    .await;
+                Err(AppError::Validation(format!(
+                    "File rejected by virus scan: {}",
+                    signature
+                )))
+            }
+            Err(e) if scanner.fail_open() => {
+                tracing::warn!("Virus scan failed, accepting upload: {}", e);
+                Ok(())
+            }
+            Err(e) => Err(AppError::Internal(e.context("Virus scan failed"))),
+        }
+    }
+
+    /// Re-encode a received file (see [`reencode`]) and save it, under
+    /// `tenants/<slug>/` for a tenant, once its type checks out. The temp
+    /// file is removed either way.
     /// Returns the public URL path (e.g., `/uploads/avatars/uuid.jpg`).
-    pub async fn save_file(
+    pub async fn save_upload(
+        config: &UploadConfig,
+        mut upload: TempUpload,
+        content_type: &str,
+        subdirectory: &str,
+    ) -> AppResult<SavedUpload> {
+        let (file_path, path) =
+            destination(config, &upload.head, content_type, subdirectory).await?;
+        upload
+            .file
+            .flush()
+            .await
+            .map_err(|e| AppError::Validation(format!("Failed to write file: {}", e)))?;
+
+        let temp_path = upload.path.clone().expect("temp upload saved twice");
+        let data = fs::read(&temp_path)
+            .await
+            .map_err(|e| AppError::Validation(format!("Failed to read file: {}", e)))?;
+        let format = ImageFormat::from_mime_type(content_type)
+            .ok_or_else(|| AppError::Validation("Unsupported file type".to_string()))?;
+        let data = tokio::task::spawn_blocking(move || reencode(&data, format))
+            .await
+            .map_err(|e| AppError::Internal(e.into()))??;
+        write_file(file_path, path, &data).await
+    }
+
+    /// Save an image made by the server itself, such as an avatar
+    /// thumbnail, with the same type checks.
+    pub async fn save_generated(
         config: &UploadConfig,
         data: &[u8],
         content_type: &str,
         subdirectory: &str,
-    ) -> AppResult<String> {
-        // Validate size
-        if data.len() > MAX_FILE_SIZE {
-            return Err(AppError::PayloadTooLarge);
+    ) -> AppResult<SavedUpload> {
+        let head = &data[..data.len().min(HEAD_LEN)];
+        let (file_path, path) = destination(config, head, content_type, subdirectory).await?;
+        write_file(file_path, path, data).await
+    }
+
+    /// Add a saved file to the `uploads` table, or delete it if that fails
+    /// so no file goes untracked.
+    pub async fn record(
+        db: &DatabaseConnection,
+        config: &UploadConfig,
+        saved: &SavedUpload,
+        content_type: &str,
+        user_id: i32,
+    ) -> AppResult<UploadModel> {
+        let record = upload::ActiveModel {
+            user_id: Set(Some(user_id)),
+            path: Set(saved.path.clone()),
+            size: Set(saved.size as i64),
+            mime: Set(content_type.to_string()),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        match record.insert(db).await {
+            Ok(record) => Ok(record),
+            Err(e) => {
+                remove_file(&Path::new(&config.upload_dir).join(&saved.path)).await;
+                Err(e.into())
+            }
         }
+    }
 
-        // Validate content type
-        if !ALLOWED_CONTENT_TYPES.contains(&content_type) {
-            return Err(AppError::Validation(format!(
-                "Unsupported file type: {}. Allowed: jpeg, png, gif, webp",
-                content_type
-            )));
+    /// Record what refers to the file at `url`, e.g. `("user", id)` for an
+    /// avatar, so the cleanup job keeps it.
+    pub async fn link(
+        db: &DatabaseConnection,
+        url: &str,
+        entity_type: &str,
+        entity_id: i32,
+    ) -> AppResult<()> {
+        set_entity(db, url, Some((entity_type, entity_id))).await
+    }
+
+    /// Forget what refers to the file at `url` (e.g. a replaced avatar); the
+    /// cleanup job deletes it unless something else turns out to use it.
+    pub async fn release(db: &DatabaseConnection, url: &str) -> AppResult<()> {
+        set_entity(db, url, None).await
+    }
+}
+
+async fn write_file(file_path: PathBuf, path: String, data: &[u8]) -> AppResult<SavedUpload> {
+    if let Err(e) = fs::write(&file_path, data).await {
+        remove_file(&file_path).await;
+        return Err(AppError::Validation(format!("Failed to write file: {}", e)));
+    }
+    Ok(SavedUpload {
+        url: format!("/uploads/{}", path),
+        path,
+        size: data.len(),
+    })
+}
+
+async fn set_entity(
+    db: &DatabaseConnection,
+    url: &str,
+    entity: Option<(&str, i32)>,
+) -> AppResult<()> {
+    let Some(path) = url.strip_prefix("/uploads/") else {
+        return Ok(());
+    };
+    Upload::update_many()
+        .col_expr(
+            upload::Column::EntityType,
+            Expr::value(entity.map(|(t, _)| t.to_string())),
+        )
+        .col_expr(
+            upload::Column::EntityId,
+            Expr::value(entity.map(|(_, id)| id)),
+        )
+        .filter(upload::Column::Path.eq(path))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Remove a file, tolerating one that is already gone.
+async fn remove_file(path: &Path) -> bool {
+    match fs::remove_file(path).await {
+        Ok(()) => true,
+        Err(e) if e.kind() == ErrorKind::NotFound => true,
+        Err(e) => {
+            tracing::warn!("Failed to remove upload {}: {}", path.display(), e);
+            false
         }
+    }
+}
+
+/// Uploads looked at per cleanup run.
+const CLEANUP_BATCH: u64 = 500;
 
-        // Validate magic bytes match content type
-        if !validate_magic_bytes(data, content_type) {
-            return Err(AppError::Validation(
-                "File content does not match declared content type".to_string(),
-            ));
+/// Deletes uploads that nothing refers to.
+///
+/// Files are recorded unreferenced when uploaded, except avatars. Once an
+/// upload is older than `UPLOAD_ORPHAN_GRACE_SECONDS` the job looks for a
+/// user whose avatar it is, or a post or comment that mentions its file
+/// name. Found references are recorded and the file is kept from then on;
+/// otherwise the file and its row are deleted.
+#[derive(Clone)]
+pub struct UploadCleanup {
+    db: DatabaseConnection,
+    upload_dir: String,
+    config: UploadCleanupConfig,
+}
+
+impl UploadCleanup {
+    pub fn new(db: DatabaseConnection, upload_dir: String, config: UploadCleanupConfig) -> Self {
+        Self {
+            db,
+            upload_dir,
+            config,
         }
+    }
 
-        let ext = match content_type {
-            "image/jpeg" => "jpg",
-            "image/png" => "png",
-            "image/gif" => "gif",
-            "image/webp" => "webp",
-            _ => return Err(AppError::Validation("Unsupported file type".to_string())),
-        };
+    pub fn spawn_scheduler(&self) -> tokio::task::JoinHandle<()> {
+        let cleanup = self.clone();
+        shutdown::spawn(async move {
+            let mut ticker = tokio::time::interval(cleanup.config.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown::requested() => break,
+                }
+                match cleanup.run(chrono::Utc::now().naive_utc()).await {
+                    Ok(0) => {}
+                    Ok(deleted) => tracing::info!("Deleted {} orphaned uploads", deleted),
+                    Err(e) => tracing::warn!("Failed to clean up uploads: {}", e),
+                }
+            }
+        })
+    }
 
-        let filename = format!("{}.{}", Uuid::new_v4(), ext);
-        let dir = Path::new(&config.upload_dir).join(subdirectory);
+    /// Look at one batch of unreferenced uploads older than the grace period
+    /// at `now`. Returns how many were deleted.
+    pub async fn run(&self, now: NaiveDateTime) -> AppResult<u64> {
+        let cutoff = now - self.config.grace_period;
+        let candidates = Upload::find()
+            .filter(upload::Column::EntityType.is_null())
+            .filter(upload::Column::CreatedAt.lte(cutoff))
+            .order_by_asc(upload::Column::Id)
+            .limit(CLEANUP_BATCH)
+            .all(&self.db)
+            .await?;
 
-        fs::create_dir_all(&dir).await.map_err(|e| {
-            AppError::Validation(format!("Failed to create upload directory: {}", e))
-        })?;
+        let mut deleted = 0;
+        for candidate in candidates {
+            if let Some((entity_type, entity_id)) = self.find_reference(&candidate).await? {
+                let mut active: upload::ActiveModel = candidate.into();
+                active.entity_type = Set(Some(entity_type.to_string()));
+                active.entity_id = Set(Some(entity_id));
+                active.update(&self.db).await?;
+                continue;
+            }
+            // A file that can't be removed keeps its row, to be retried
+            if !remove_file(&Path::new(&self.upload_dir).join(&candidate.path)).await {
+                continue;
+            }
+            Upload::delete_by_id(candidate.id).exec(&self.db).await?;
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
 
-        let file_path = dir.join(&filename);
-        fs::write(&file_path, data)
-            .await
-            .map_err(|e| AppError::Validation(format!("Failed to write file: {}", e)))?;
+    /// An avatar (or its original), post or comment using the file. Markdown may link it by
+    /// relative or absolute URL, so matching is on the unique file name.
+    async fn find_reference(&self, upload: &UploadModel) -> AppResult<Option<(&'static str, i32)>> {
+        let name = upload.path.rsplit('/').next().unwrap_or(&upload.path);
+
+        if let Some(id) = User::find()
+            .select_only()
+            .column(user::Column::Id)
+            .filter(
+                user::Column::AvatarUrl
+                    .contains(name)
+                    .or(user::Column::AvatarOriginalUrl.contains(name)),
+            )
+            .into_tuple::<i32>()
+            .one(&self.db)
+            .await?
+        {
+            return Ok(Some(("user", id)));
+        }
+        if let Some(id) = Post::find()
+            .select_only()
+            .column(post::Column::Id)
+            .filter(post::Column::Content.contains(name))
+            .into_tuple::<i32>()
+            .one(&self.db)
+            .await?
+        {
+            return Ok(Some(("post", id)));
+        }
+        let comment = Comment::find()
+            .select_only()
+            .column(comment::Column::Id)
+            .filter(comment::Column::Content.contains(name))
+            .into_tuple::<i32>()
+            .one(&self.db)
+            .await?;
+        Ok(comment.map(|id| ("comment", id)))
+    }
+}
+
+fn virus_scanner() -> &'static VirusScanner {
+    static SCANNER: OnceLock<VirusScanner> = OnceLock::new();
+    SCANNER.get_or_init(VirusScanner::from_env)
+}
+
+/// Largest width or height accepted for an uploaded image.
+const MAX_IMAGE_DIMENSION: u32 = 8192;
+
+/// Memory a decoded image (all frames of a GIF) may take, so a small file
+/// can't claim a huge canvas.
+const MAX_DECODED_BYTES: u64 = 256 * 1024 * 1024;
+
+const JPEG_QUALITY: u8 = 90;
+
+/// Decode an image and encode it again in the same format. Only the pixels
+/// are carried over, so EXIF (GPS position, camera details), comments and
+/// other metadata are dropped, and a malformed file is rejected here rather
+/// than served to browsers. The EXIF orientation is applied to the pixels
+/// first, so photos still show the right way up. GIFs keep their frames;
+/// WebP is written lossless, and only its first frame is kept.
+fn reencode(data: &[u8], format: ImageFormat) -> AppResult<Vec<u8>> {
+    let mut out = Vec::new();
+    let result = match format {
+        ImageFormat::Gif => reencode_gif(data, &mut out),
+        _ => reencode_still(data, format, &mut out),
+    };
+    result.map_err(image_error)?;
+    Ok(out)
+}
+
+pub(crate) fn image_error(e: ImageError) -> AppError {
+    match e {
+        ImageError::Limits(_) => AppError::Validation(format!(
+            "Image is too large, at most {0}x{0} pixels are allowed",
+            MAX_IMAGE_DIMENSION
+        )),
+        e => AppError::Validation(format!("Invalid image: {}", e)),
+    }
+}
+
+fn reencode_still(data: &[u8], format: ImageFormat, out: &mut Vec<u8>) -> ImageResult<()> {
+    encode_image(&decode_image(data, format)?, format, out)
+}
+
+/// Decode a still image within the upload limits, turned upright.
+pub(crate) fn decode_image(data: &[u8], format: ImageFormat) -> ImageResult<DynamicImage> {
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    reader.limits(decode_limits());
+    let mut decoder = reader.into_decoder()?;
+    // Unreadable EXIF only costs the rotation
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Encode as JPEG, PNG or (for anything else) lossless WebP.
+pub(crate) fn encode_image(
+    image: &DynamicImage,
+    format: ImageFormat,
+    out: &mut Vec<u8>,
+) -> ImageResult<()> {
+    match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(out, JPEG_QUALITY)),
+        ImageFormat::Png => image.write_with_encoder(PngEncoder::new(out)),
+        _ => {
+            let image = if image.color().has_alpha() {
+                DynamicImage::ImageRgba8(image.to_rgba8())
+            } else {
+                DynamicImage::ImageRgb8(image.to_rgb8())
+            };
+            image.write_with_encoder(WebPEncoder::new_lossless(out))
+        }
+    }
+}
+
+fn reencode_gif(data: &[u8], out: &mut Vec<u8>) -> ImageResult<()> {
+    let mut decoder = GifDecoder::new(Cursor::new(data))?;
+    decoder.set_limits(decode_limits())?;
 
-        Ok(format!("/uploads/{}/{}", subdirectory, filename))
+    let mut frames = Vec::new();
+    let mut decoded_bytes = 0;
+    for frame in decoder.into_frames() {
+        let frame = frame?;
+        decoded_bytes += frame.buffer().len() as u64;
+        if decoded_bytes > MAX_DECODED_BYTES {
+            return Err(ImageError::Limits(LimitError::from_kind(
+                LimitErrorKind::InsufficientMemory,
+            )));
+        }
+        frames.push(frame);
     }
+
+    let mut encoder = GifEncoder::new_with_speed(out, 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(frames)
+}
+
+fn decode_limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODED_BYTES);
+    limits
+}
+
+/// Check the type of a file starting with `head`, and pick where it goes,
+/// as a filesystem path and relative to the upload directory.
+async fn destination(
+    config: &UploadConfig,
+    head: &[u8],
+    content_type: &str,
+    subdirectory: &str,
+) -> AppResult<(PathBuf, String)> {
+    // Validate content type
+    if !ALLOWED_CONTENT_TYPES.contains(&content_type) {
+        return Err(AppError::Validation(format!(
+            "Unsupported file type: {}. Allowed: jpeg, png, gif, webp",
+            content_type
+        )));
+    }
+
+    // Validate magic bytes match content type
+    if !validate_magic_bytes(head, content_type) {
+        return Err(AppError::Validation(
+            "File content does not match declared content type".to_string(),
+        ));
+    }
+
+    let ext = match content_type {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => return Err(AppError::Validation("Unsupported file type".to_string())),
+    };
+
+    let subdirectory = match tenant::current() {
+        Some(slug) => format!("tenants/{}/{}", slug, subdirectory),
+        None => subdirectory.to_string(),
+    };
+    let filename = format!("{}.{}", Uuid::new_v4(), ext);
+    let dir = Path::new(&config.upload_dir).join(&subdirectory);
+
+    fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| AppError::Validation(format!("Failed to create upload directory: {}", e)))?;
+
+    Ok((
+        dir.join(&filename),
+        format!("{}/{}", subdirectory, filename),
+    ))
 }
 
 #[cfg(test)]
@@ -131,6 +640,70 @@ mod tests {
         assert!(!validate_magic_bytes(&data, "application/pdf"));
     }
 
+    fn encode(image: &DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        image.write_to(&mut out, format).unwrap();
+        out.into_inner()
+    }
+
+    /// A JPEG of `image` with an APP1 segment tagging it "rotate 90° clockwise".
+    fn rotated_jpeg(image: &DynamicImage) -> Vec<u8> {
+        let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01".to_vec();
+        exif.extend_from_slice(&[0x01, 0x12, 0x00, 0x03, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0]);
+        let jpeg = encode(image, ImageFormat::Jpeg);
+        let mut tagged = jpeg[..2].to_vec();
+        tagged.extend_from_slice(&[0xFF, 0xE1]);
+        tagged.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        tagged.extend_from_slice(&exif);
+        tagged.extend_from_slice(&jpeg[2..]);
+        tagged
+    }
+
+    #[test]
+    fn reencode_strips_exif_and_applies_orientation() {
+        let jpeg = rotated_jpeg(&DynamicImage::new_rgb8(4, 2));
+        assert!(jpeg.windows(4).any(|w| w == b"Exif"));
+
+        let clean = reencode(&jpeg, ImageFormat::Jpeg).unwrap();
+        assert!(!clean.windows(4).any(|w| w == b"Exif"));
+        let image = image::load_from_memory_with_format(&clean, ImageFormat::Jpeg).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 4));
+    }
+
+    #[test]
+    fn reencode_keeps_format_and_pixels() {
+        let mut image = image::RgbaImage::new(3, 3);
+        image.put_pixel(1, 1, image::Rgba([255, 0, 0, 128]));
+        let image = DynamicImage::ImageRgba8(image);
+
+        for format in [ImageFormat::Png, ImageFormat::WebP, ImageFormat::Gif] {
+            let clean = reencode(&encode(&image, format), format).unwrap();
+            let decoded = image::load_from_memory_with_format(&clean, format).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (3, 3), "{:?}", format);
+            if format != ImageFormat::Gif {
+                assert_eq!(decoded.to_rgba8().get_pixel(1, 1).0, [255, 0, 0, 128]);
+            }
+        }
+    }
+
+    #[test]
+    fn reencode_rejects_broken_and_oversized_images() {
+        let png = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+        assert!(matches!(
+            reencode(&png, ImageFormat::Png),
+            Err(AppError::Validation(msg)) if msg.starts_with("Invalid image")
+        ));
+
+        let wide = encode(
+            &DynamicImage::new_luma8(MAX_IMAGE_DIMENSION + 1, 1),
+            ImageFormat::Png,
+        );
+        assert!(matches!(
+            reencode(&wide, ImageFormat::Png),
+            Err(AppError::Validation(msg)) if msg.starts_with("Image is too large")
+        ));
+    }
+
     #[test]
     fn too_short_data_rejected() {
         assert!(!validate_magic_bytes(&[0xFF, 0xD8], "image/jpeg"));
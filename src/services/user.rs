@@ -1,8 +1,46 @@
 use crate::{
     error::{AppError, AppResult},
-    models::{user, User, UserModel},
+    models::{user, username_history, User, UserModel, UsernameHistory},
+    services::{account_deletion::DELETED_USERNAME, upload::UploadService},
+    utils::sql,
 };
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+
+/// Changes to a profile. `display_name`, `bio` and `avatar_url` are
+/// replaced; the other settings are left unchanged when `None`.
+#[derive(Debug)]
+pub struct ProfileUpdate<'a> {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub auto_watch: Option<bool>,
+    pub locale: Option<&'a str>,
+    pub digest_frequency: Option<&'a str>,
+    pub is_private: Option<bool>,
+    pub show_presence: Option<bool>,
+}
+
+/// Changes to the user's preferences; each is left unchanged when `None`.
+/// `content_language` is cleared with `Some(None)`.
+#[derive(Debug, Default)]
+pub struct PreferencesUpdate<'a> {
+    pub timezone: Option<&'a str>,
+    pub locale: Option<&'a str>,
+    pub content_language: Option<Option<String>>,
+    pub show_nsfw: Option<bool>,
+    pub default_post_sort: Option<&'a str>,
+}
+
+/// A user found by a current or former username.
+#[derive(Debug)]
+pub struct ResolvedUser {
+    pub user: UserModel,
+    /// The name looked up, if it is one the user has since changed away from
+    pub renamed_from: Option<String>,
+}
 
 pub struct UserService {
     db: DatabaseConnection,
@@ -13,50 +51,313 @@ impl UserService {
         Self { db }
     }
 
+    pub async fn get_by_id(&self, user_id: i32) -> AppResult<UserModel> {
+        User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)
+    }
+
+    /// The active user with `username`, or who last had it.
     pub async fn get_by_username(&self, username: &str) -> AppResult<UserModel> {
-        User::find()
+        Ok(self.resolve_username(username).await?.user)
+    }
+
+    /// Look up a username, falling back to former names so old profile
+    /// links keep working. Deactivated users aren't found.
+    pub async fn resolve_username(&self, username: &str) -> AppResult<ResolvedUser> {
+        let resolved = match User::find()
             .filter(user::Column::Username.eq(username))
             .one(&self.db)
             .await?
-            .ok_or(AppError::NotFound)
+        {
+            Some(user) => ResolvedUser {
+                user,
+                renamed_from: None,
+            },
+            None => {
+                let former = UsernameHistory::find()
+                    .filter(username_history::Column::Username.eq(username))
+                    .one(&self.db)
+                    .await?
+                    .ok_or(AppError::NotFound)?;
+                ResolvedUser {
+                    user: self.get_by_id(former.user_id).await?,
+                    renamed_from: Some(former.username),
+                }
+            }
+        };
+        if resolved.user.deactivated_at.is_some() {
+            return Err(AppError::NotFound);
+        }
+        Ok(resolved)
+    }
+
+    /// Rename a user, keeping the old name as theirs for lookups. Names held
+    /// or formerly held by others are taken; the user may go back to one of
+    /// their own. Renames are limited to one per `interval_days`.
+    pub async fn change_username(
+        &self,
+        user_id: i32,
+        username: &str,
+        interval_days: i64,
+    ) -> AppResult<UserModel> {
+        let txn = self.db.begin().await?;
+        let existing = User::find_by_id(user_id)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if existing.username == username {
+            return Err(AppError::Validation(
+                "That is already your username".to_string(),
+            ));
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        if interval_days > 0 {
+            let last = UsernameHistory::find()
+                .filter(username_history::Column::UserId.eq(user_id))
+                .order_by_desc(username_history::Column::ChangedAt)
+                .one(&txn)
+                .await?;
+            if let Some(last) = last {
+                let allowed_at = last.changed_at + chrono::Duration::days(interval_days);
+                if allowed_at > now {
+                    return Err(AppError::TooManyRequests {
+                        retry_after_seconds: (allowed_at - now).num_seconds().max(1) as u64,
+                    });
+                }
+            }
+        }
+
+        let held = User::find()
+            .filter(user::Column::Username.eq(username))
+            .count(&txn)
+            .await?;
+        let former = UsernameHistory::find()
+            .filter(username_history::Column::Username.eq(username))
+            .one(&txn)
+            .await?;
+        if held > 0
+            || former.as_ref().is_some_and(|f| f.user_id != user_id)
+            || username == DELETED_USERNAME
+        {
+            return Err(AppError::Conflict("Username is already taken".to_string()));
+        }
+        if let Some(former) = former {
+            UsernameHistory::delete_by_id(former.id).exec(&txn).await?;
+        }
+
+        username_history::ActiveModel {
+            user_id: Set(user_id),
+            username: Set(existing.username.clone()),
+            changed_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        let mut active: user::ActiveModel = existing.into();
+        active.username = Set(username.to_string());
+        active.updated_at = Set(now);
+        let updated = active.update(&txn).await?;
+        txn.commit().await?;
+        Ok(updated)
     }
 
     pub async fn update_profile(
         &self,
         user_id: i32,
-        bio: Option<String>,
-        avatar_url: Option<String>,
+        update: ProfileUpdate<'_>,
     ) -> AppResult<UserModel> {
+        let ProfileUpdate {
+            display_name,
+            bio,
+            avatar_url,
+            auto_watch,
+            locale,
+            digest_frequency,
+            is_private,
+            show_presence,
+        } = update;
         let existing = User::find_by_id(user_id)
             .one(&self.db)
             .await?
             .ok_or(AppError::NotFound)?;
 
         let now = chrono::Utc::now().naive_utc();
+        let previous_avatar = existing.avatar_url.clone();
+        let previous_original = existing.avatar_original_url.clone();
 
         let mut active: user::ActiveModel = existing.into();
+        active.display_name = sea_orm::ActiveValue::Set(display_name);
         active.bio = sea_orm::ActiveValue::Set(bio);
+        // An avatar set by URL wasn't cropped from the uploaded original
+        if avatar_url != previous_avatar {
+            active.avatar_original_url = sea_orm::ActiveValue::Set(None);
+        }
         active.avatar_url = sea_orm::ActiveValue::Set(avatar_url);
+        if let Some(auto_watch) = auto_watch {
+            active.auto_watch = sea_orm::ActiveValue::Set(auto_watch);
+        }
+        if let Some(locale) = locale {
+            active.locale = sea_orm::ActiveValue::Set(locale.to_string());
+        }
+        if let Some(frequency) = digest_frequency {
+            active.digest_frequency = sea_orm::ActiveValue::Set(frequency.to_string());
+        }
+        if let Some(is_private) = is_private {
+            active.is_private = sea_orm::ActiveValue::Set(is_private);
+        }
+        if let Some(show_presence) = show_presence {
+            active.show_presence = sea_orm::ActiveValue::Set(show_presence);
+        }
         active.updated_at = sea_orm::ActiveValue::Set(now);
 
         let updated = active.update(&self.db).await?;
+        self.track_avatar(user_id, previous_avatar, updated.avatar_url.as_deref())
+            .await?;
+        self.track_avatar(
+            user_id,
+            previous_original,
+            updated.avatar_original_url.as_deref(),
+        )
+        .await?;
         Ok(updated)
     }
 
-    /// Update only the avatar URL (used by upload handler).
-    pub async fn update_avatar_url(&self, user_id: i32, url: &str) -> AppResult<UserModel> {
+    /// Deactivate an account: its profile stops resolving and its visible
+    /// posts and comments are hidden until [`reactivate`](Self::reactivate).
+    pub async fn deactivate(&self, user_id: i32) -> AppResult<()> {
+        let now = chrono::Utc::now().naive_utc();
+        let txn = self.db.begin().await?;
+        User::update_many()
+            .col_expr(user::Column::DeactivatedAt, Expr::value(now))
+            .filter(user::Column::Id.eq(user_id))
+            .exec(&txn)
+            .await?;
+        for table in ["posts", "comments"] {
+            txn.execute(sql::statement(
+                txn.get_database_backend(),
+                format!(
+                    "UPDATE {} SET is_hidden = TRUE, hidden_with_author = TRUE \
+                        WHERE user_id = $1 AND is_hidden = FALSE",
+                    table
+                ),
+                [user_id.into()],
+            ))
+            .await?;
+        }
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Bring a deactivated account back, showing the content that
+    /// deactivating hid. Content moderators hid in the meantime stays hidden.
+    pub async fn reactivate(&self, user_id: i32) -> AppResult<()> {
+        let txn = self.db.begin().await?;
+        User::update_many()
+            .col_expr(
+                user::Column::DeactivatedAt,
+                Expr::value(Option::<chrono::NaiveDateTime>::None),
+            )
+            .filter(user::Column::Id.eq(user_id))
+            .exec(&txn)
+            .await?;
+        for table in ["posts", "comments"] {
+            txn.execute(sql::statement(
+                txn.get_database_backend(),
+                format!(
+                    "UPDATE {} SET is_hidden = FALSE, hidden_with_author = FALSE \
+                        WHERE user_id = $1 AND hidden_with_author = TRUE",
+                    table
+                ),
+                [user_id.into()],
+            ))
+            .await?;
+        }
+        txn.commit().await?;
+        Ok(())
+    }
+
+    pub async fn update_preferences(
+        &self,
+        user_id: i32,
+        update: PreferencesUpdate<'_>,
+    ) -> AppResult<UserModel> {
+        let existing = User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        let mut active: user::ActiveModel = existing.into();
+        if let Some(timezone) = update.timezone {
+            active.timezone = sea_orm::ActiveValue::Set(timezone.to_string());
+        }
+        if let Some(locale) = update.locale {
+            active.locale = sea_orm::ActiveValue::Set(locale.to_string());
+        }
+        if let Some(content_language) = update.content_language {
+            active.content_language = sea_orm::ActiveValue::Set(content_language);
+        }
+        if let Some(show_nsfw) = update.show_nsfw {
+            active.show_nsfw = sea_orm::ActiveValue::Set(show_nsfw);
+        }
+        if let Some(sort) = update.default_post_sort {
+            active.default_post_sort = sea_orm::ActiveValue::Set(sort.to_string());
+        }
+        active.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().naive_utc());
+
+        Ok(active.update(&self.db).await?)
+    }
+
+    /// Update only the avatar, and the original it was cropped from (used
+    /// by the upload handlers).
+    pub async fn update_avatar_url(
+        &self,
+        user_id: i32,
+        url: &str,
+        original_url: &str,
+    ) -> AppResult<UserModel> {
         let existing = User::find_by_id(user_id)
             .one(&self.db)
             .await?
             .ok_or(AppError::NotFound)?;
 
         let now = chrono::Utc::now().naive_utc();
+        let previous_avatar = existing.avatar_url.clone();
+        let previous_original = existing.avatar_original_url.clone();
 
         let mut active: user::ActiveModel = existing.into();
         active.avatar_url = sea_orm::ActiveValue::Set(Some(url.to_string()));
+        active.avatar_original_url = sea_orm::ActiveValue::Set(Some(original_url.to_string()));
         active.updated_at = sea_orm::ActiveValue::Set(now);
 
         let updated = active.update(&self.db).await?;
+        self.track_avatar(user_id, previous_avatar, Some(url))
+            .await?;
+        self.track_avatar(user_id, previous_original, Some(original_url))
+            .await?;
         Ok(updated)
     }
+
+    /// Keep the `uploads` table in step with an avatar (or avatar original)
+    /// change: the new file is kept, the replaced one left to the cleanup
+    /// job.
+    async fn track_avatar(
+        &self,
+        user_id: i32,
+        previous: Option<String>,
+        current: Option<&str>,
+    ) -> AppResult<()> {
+        if previous.as_deref() == current {
+            return Ok(());
+        }
+        if let Some(previous) = previous {
+            UploadService::release(&self.db, &previous).await?;
+        }
+        if let Some(current) = current {
+            UploadService::link(&self.db, current, "user", user_id).await?;
+        }
+        Ok(())
+    }
 }
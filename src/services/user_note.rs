@@ -0,0 +1,109 @@
+//! Private staff notes on user accounts, so context about past incidents
+//! stays with the account whoever handles it next.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{user, user_note, User, UserNote, UserNoteModel};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+};
+use std::collections::HashMap;
+
+/// A note with its author's names, if the author still exists.
+pub struct UserNoteWithAuthor {
+    pub note: UserNoteModel,
+    pub author_username: Option<String>,
+    pub author_display_name: Option<String>,
+}
+
+pub struct UserNoteService {
+    db: DatabaseConnection,
+}
+
+impl UserNoteService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Notes on a user, newest first.
+    pub async fn list(&self, user_id: i32) -> AppResult<Vec<UserNoteWithAuthor>> {
+        self.ensure_user(user_id).await?;
+        let notes = UserNote::find()
+            .filter(user_note::Column::UserId.eq(user_id))
+            .order_by_desc(user_note::Column::CreatedAt)
+            .order_by_desc(user_note::Column::Id)
+            .all(&self.db)
+            .await?;
+
+        let author_ids: Vec<i32> = notes.iter().filter_map(|n| n.author_id).collect();
+        let authors: HashMap<i32, user::Model> = if author_ids.is_empty() {
+            HashMap::new()
+        } else {
+            User::find()
+                .filter(user::Column::Id.is_in(author_ids))
+                .all(&self.db)
+                .await?
+                .into_iter()
+                .map(|u| (u.id, u))
+                .collect()
+        };
+
+        Ok(notes
+            .into_iter()
+            .map(|note| {
+                let author = note.author_id.and_then(|id| authors.get(&id));
+                UserNoteWithAuthor {
+                    author_username: author.map(|a| a.username.clone()),
+                    author_display_name: author.and_then(|a| a.display_name.clone()),
+                    note,
+                }
+            })
+            .collect())
+    }
+
+    pub async fn create(
+        &self,
+        user_id: i32,
+        author_id: i32,
+        body: &str,
+    ) -> AppResult<UserNoteModel> {
+        self.ensure_user(user_id).await?;
+        let note = user_note::ActiveModel {
+            user_id: Set(user_id),
+            author_id: Set(Some(author_id)),
+            body: Set(body.to_string()),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(note)
+    }
+
+    /// Delete a note. Only its author can, unless `any_author` is set.
+    pub async fn delete(
+        &self,
+        user_id: i32,
+        note_id: i32,
+        requester_id: i32,
+        any_author: bool,
+    ) -> AppResult<()> {
+        let note = UserNote::find_by_id(note_id)
+            .filter(user_note::Column::UserId.eq(user_id))
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if !any_author && note.author_id != Some(requester_id) {
+            return Err(AppError::Forbidden);
+        }
+        UserNote::delete_by_id(note.id).exec(&self.db).await?;
+        Ok(())
+    }
+
+    async fn ensure_user(&self, user_id: i32) -> AppResult<()> {
+        User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        Ok(())
+    }
+}
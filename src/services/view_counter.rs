@@ -0,0 +1,231 @@
+//! Post view counting.
+//!
+//! Views are deduplicated per viewer (user id, or IP for anonymous readers)
+//! over `VIEW_DEDUP_WINDOW_SECONDS`, via Redis when available and an
+//! in-process map otherwise. Counted views accumulate in a Redis hash (shared
+//! by every instance) or in memory, and are written to `posts.view_count` in
+//! one statement by a background task every `VIEW_FLUSH_INTERVAL_SECONDS`,
+//! or sooner once `VIEW_FLUSH_THRESHOLD` are pending in memory.
+
+use crate::{
+    config::views::ViewConfig, error::AppResult, services::cache::CacheService,
+    services::post::PostService, utils::shutdown,
+};
+use dashmap::DashMap;
+use sea_orm::DatabaseConnection;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Redis hash of pending view increments, keyed by post id.
+const PENDING_KEY: &str = "views:pending";
+
+/// Who is viewing a post, for deduplication.
+#[derive(Debug, Clone, Copy)]
+pub enum Viewer {
+    User(i32),
+    Ip(IpAddr),
+}
+
+impl Viewer {
+    fn key(&self, post_id: i32) -> String {
+        match self {
+            Viewer::User(id) => format!("views:{}:u:{}", post_id, id),
+            Viewer::Ip(ip) => format!("views:{}:ip:{}", post_id, ip),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ViewCounter {
+    config: ViewConfig,
+    cache: Option<CacheService>,
+    /// Dedup keys and when they were first seen, used without Redis
+    seen: Arc<DashMap<String, Instant>>,
+    /// Views counted but not yet written, by post id
+    pending: Arc<DashMap<i32, i64>>,
+    pending_total: Arc<AtomicU64>,
+}
+
+impl ViewCounter {
+    pub fn new(config: ViewConfig, cache: Option<CacheService>) -> Self {
+        Self {
+            config,
+            cache,
+            seen: Arc::new(DashMap::new()),
+            pending: Arc::new(DashMap::new()),
+            pending_total: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn from_env(cache: Option<CacheService>) -> Self {
+        Self::new(ViewConfig::from_env(), cache)
+    }
+
+    /// Count a view unless this viewer already viewed the post within the
+    /// dedup window. Returns whether the view was counted.
+    pub async fn record_view(
+        &self,
+        db: &DatabaseConnection,
+        post_id: i32,
+        viewer: Viewer,
+    ) -> AppResult<bool> {
+        if !self.first_view(post_id, viewer).await {
+            return Ok(false);
+        }
+
+        if let Some(cache) = &self.cache {
+            if cache
+                .hincr(PENDING_KEY, &post_id.to_string(), 1)
+                .await
+                .is_some()
+            {
+                return Ok(true);
+            }
+        }
+
+        self.add_pending(post_id, 1);
+        if self.pending_total.load(Ordering::Relaxed) >= self.config.flush_threshold {
+            self.flush(db).await?;
+        }
+        Ok(true)
+    }
+
+    /// Views counted for a post that have not been written yet.
+    pub async fn pending_for(&self, post_id: i32) -> i64 {
+        let mut pending = self.pending.get(&post_id).map_or(0, |n| *n);
+        if let Some(cache) = &self.cache {
+            pending += cache
+                .hget_i64(PENDING_KEY, &post_id.to_string())
+                .await
+                .unwrap_or(0);
+        }
+        pending
+    }
+
+    /// Write every pending view to the database, returning how many were written.
+    pub async fn flush(&self, db: &DatabaseConnection) -> AppResult<u64> {
+        let mut increments: HashMap<i32, i64> = HashMap::new();
+        let post_ids: Vec<i32> = self.pending.iter().map(|e| *e.key()).collect();
+        for id in post_ids {
+            if let Some((id, n)) = self.pending.remove(&id) {
+                self.pending_total.fetch_sub(n as u64, Ordering::Relaxed);
+                *increments.entry(id).or_insert(0) += n;
+            }
+        }
+        if let Some(cache) = &self.cache {
+            if let Some(fields) = cache.take_hash(PENDING_KEY).await {
+                merge_pending(&mut increments, fields);
+            }
+        }
+
+        let increments: Vec<(i32, i64)> = increments.into_iter().collect();
+        let written: i64 = increments.iter().map(|(_, n)| n).sum();
+        if let Err(e) = PostService::new(db.clone())
+            .add_view_counts(&increments)
+            .await
+        {
+            // Keep them in memory so the next flush retries
+            for (id, n) in increments {
+                self.add_pending(id, n);
+            }
+            return Err(e);
+        }
+
+        self.prune_seen();
+        Ok(written as u64)
+    }
+
+    /// Flush pending views every `VIEW_FLUSH_INTERVAL_SECONDS` until
+    /// shutdown, which does a final flush of its own.
+    pub fn spawn_flusher(&self, db: DatabaseConnection) -> tokio::task::JoinHandle<()> {
+        let counter = self.clone();
+        shutdown::spawn(async move {
+            let mut ticker = tokio::time::interval(counter.config.flush_interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown::requested() => break,
+                }
+                if let Err(e) = counter.flush(&db).await {
+                    tracing::warn!("Failed to flush view counts: {}", e);
+                }
+            }
+        })
+    }
+
+    fn add_pending(&self, post_id: i32, n: i64) {
+        *self.pending.entry(post_id).or_insert(0) += n;
+        self.pending_total.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    async fn first_view(&self, post_id: i32, viewer: Viewer) -> bool {
+        let window = self.config.dedup_window;
+        if window.is_zero() {
+            return true;
+        }
+
+        let key = viewer.key(post_id);
+        if let Some(cache) = &self.cache {
+            if let Some(first) = cache.set_nx(&key, window.as_secs()).await {
+                return first;
+            }
+        }
+
+        let now = Instant::now();
+        let mut first = true;
+        self.seen
+            .entry(key)
+            .and_modify(|seen_at| {
+                if now.duration_since(*seen_at) < window {
+                    first = false;
+                } else {
+                    *seen_at = now;
+                }
+            })
+            .or_insert(now);
+        first
+    }
+
+    fn prune_seen(&self) {
+        let window = self.config.dedup_window;
+        self.seen.retain(|_, seen_at| seen_at.elapsed() < window);
+    }
+}
+
+/// Fold Redis hash fields (`post_id -> views`) into `increments`, skipping
+/// anything that is not a post id.
+fn merge_pending(increments: &mut HashMap<i32, i64>, fields: Vec<(String, i64)>) {
+    for (field, n) in fields {
+        if let Ok(id) = field.parse::<i32>() {
+            if n > 0 {
+                *increments.entry(id).or_insert(0) += n;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_pending_adds_to_memory_counts() {
+        let mut increments = HashMap::from([(1, 2)]);
+        merge_pending(
+            &mut increments,
+            vec![
+                ("1".to_string(), 3),
+                ("7".to_string(), 1),
+                ("bogus".to_string(), 5),
+                ("8".to_string(), 0),
+            ],
+        );
+        assert_eq!(increments.len(), 2);
+        assert_eq!(increments[&1], 5);
+        assert_eq!(increments[&7], 1);
+    }
+}
@@ -0,0 +1,154 @@
+//! Virus scanning of uploads.
+//!
+//! With `UPLOAD_SCAN_CLAMD` (a clamd TCP address) or `UPLOAD_SCAN_COMMAND`
+//! (e.g. `clamdscan --no-summary`) set, each upload is scanned as received,
+//! before it is checked and re-encoded. The upload service rejects infected
+//! files and records them in the audit log.
+
+use crate::config::virus_scan::{ScanBackend, VirusScanConfig};
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+
+/// Bytes sent to clamd per `INSTREAM` chunk.
+const CHUNK_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    Clean,
+    /// Name of the signature that matched
+    Infected(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct VirusScanner {
+    config: VirusScanConfig,
+}
+
+impl VirusScanner {
+    pub fn new(config: VirusScanConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(VirusScanConfig::from_env())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.backend.is_some()
+    }
+
+    /// Whether uploads are accepted when the scanner fails.
+    pub fn fail_open(&self) -> bool {
+        self.config.fail_open
+    }
+
+    /// Scan the file at `path`; an error means the scanner gave no answer.
+    pub async fn scan(&self, path: &Path) -> Result<ScanVerdict> {
+        let scan = async {
+            match &self.config.backend {
+                None => Ok(ScanVerdict::Clean),
+                Some(ScanBackend::Clamd(addr)) => scan_with_clamd(addr, path).await,
+                Some(ScanBackend::Command(command)) => scan_with_command(command, path).await,
+            }
+        };
+        tokio::time::timeout(self.config.timeout, scan)
+            .await
+            .map_err(|_| anyhow!("Virus scan timed out"))?
+    }
+}
+
+async fn scan_with_clamd(addr: &str, path: &Path) -> Result<ScanVerdict> {
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_all(b"zINSTREAM\0").await?;
+
+    let mut file = fs::File::open(path).await?;
+    let mut chunk = vec![0; CHUNK_LEN];
+    loop {
+        let len = file.read(&mut chunk).await?;
+        if len == 0 {
+            break;
+        }
+        stream.write_all(&(len as u32).to_be_bytes()).await?;
+        stream.write_all(&chunk[..len]).await?;
+    }
+    stream.write_all(&0u32.to_be_bytes()).await?;
+
+    // clamd closes the connection after its reply
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await?;
+    parse_clamd_reply(&String::from_utf8_lossy(&reply))
+}
+
+/// `stream: OK`, `stream: <signature> FOUND`, or an error such as
+/// `INSTREAM size limit exceeded. ERROR`.
+fn parse_clamd_reply(reply: &str) -> Result<ScanVerdict> {
+    let reply = reply.trim_end_matches(['\0', '\n']).trim();
+    let result = reply.strip_prefix("stream:").map_or(reply, str::trim);
+    if result == "OK" {
+        Ok(ScanVerdict::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(ScanVerdict::Infected(signature.trim().to_string()))
+    } else {
+        bail!("clamd replied: {}", reply)
+    }
+}
+
+async fn scan_with_command(command: &[String], path: &Path) -> Result<ScanVerdict> {
+    let output = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(path)
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    match output.status.code() {
+        Some(0) => Ok(ScanVerdict::Clean),
+        Some(1) => Ok(ScanVerdict::Infected(command_signature(
+            &String::from_utf8_lossy(&output.stdout),
+        ))),
+        _ => bail!(
+            "{} failed: {}",
+            command[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+}
+
+/// The signature from clamscan-style output, `<path>: <signature> FOUND`.
+fn command_signature(stdout: &str) -> String {
+    stdout
+        .lines()
+        .find_map(|line| line.trim().strip_suffix(" FOUND"))
+        .map(|line| line.rsplit(": ").next().unwrap_or(line).to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamd_replies() {
+        assert_eq!(
+            parse_clamd_reply("stream: OK\0").unwrap(),
+            ScanVerdict::Clean
+        );
+        assert_eq!(
+            parse_clamd_reply("stream: Eicar-Test-Signature FOUND\0").unwrap(),
+            ScanVerdict::Infected("Eicar-Test-Signature".to_string())
+        );
+        assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+    }
+
+    #[test]
+    fn command_output() {
+        assert_eq!(
+            command_signature("/tmp/x.part: Win.Test.EICAR_HDB-1 FOUND\n"),
+            "Win.Test.EICAR_HDB-1"
+        );
+        assert_eq!(command_signature(""), "unknown");
+    }
+}
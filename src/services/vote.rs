@@ -1,10 +1,10 @@
 use crate::{
     error::{AppError, AppResult},
     models::{vote, Comment, Post, Vote},
+    utils::sql,
 };
 use sea_orm::{
-    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, Statement,
-    TransactionTrait,
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, TransactionTrait,
 };
 
 pub struct VoteService {
@@ -74,17 +74,24 @@ impl VoteService {
                 .exec(&txn)
                 .await?;
         } else {
-            txn.execute(Statement::from_sql_and_values(
-                sea_orm::DatabaseBackend::Postgres,
-                "INSERT INTO votes (user_id, target_type, target_id, value, created_at)
-                 VALUES ($1, $2, $3, $4, NOW())
-                 ON CONFLICT (user_id, target_type, target_id)
-                 DO UPDATE SET value = EXCLUDED.value",
+            let backend = txn.get_database_backend();
+            txn.execute(sql::statement(
+                backend,
+                format!(
+                    "INSERT INTO votes (user_id, target_type, target_id, value, created_at)
+                     VALUES ($1, $2, $3, $4, $5) {}",
+                    sql::upsert(
+                        backend,
+                        &["user_id", "target_type", "target_id"],
+                        &["value"]
+                    )
+                ),
                 vec![
                     user_id.into(),
                     target_type.into(),
                     target_id.into(),
                     value.into(),
+                    chrono::Utc::now().naive_utc().into(),
                 ],
             ))
             .await?;
@@ -123,16 +130,19 @@ impl VoteService {
         let delta_down = new_down - old_down;
 
         if delta_up != 0 || delta_down != 0 {
-            let sql = format!(
+            let backend = conn.get_database_backend();
+            let query = format!(
                 "UPDATE {table}
-                 SET upvotes = GREATEST(upvotes + $1, 0),
-                     downvotes = GREATEST(downvotes + $2, 0)
-                 WHERE id = $3"
+                 SET upvotes = {},
+                     downvotes = {}
+                 WHERE id = $3",
+                sql::greatest(backend, "upvotes + $1", "0"),
+                sql::greatest(backend, "downvotes + $2", "0")
             );
 
-            conn.execute(Statement::from_sql_and_values(
-                sea_orm::DatabaseBackend::Postgres,
-                &sql,
+            conn.execute(sql::statement(
+                backend,
+                &query,
                 vec![delta_up.into(), delta_down.into(), target_id.into()],
             ))
             .await?;
@@ -0,0 +1,76 @@
+use crate::{
+    error::{AppError, AppResult},
+    models::{watched_post, Post, User, WatchedPost},
+    utils::sql,
+};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect,
+};
+
+pub struct WatchService {
+    db: DatabaseConnection,
+}
+
+impl WatchService {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn watch(&self, user_id: i32, post_id: i32) -> AppResult<bool> {
+        Post::find_by_id(post_id)
+            .one(&self.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        let backend = self.db.get_database_backend();
+        self.db
+            .execute(sql::statement(
+                backend,
+                format!(
+                    "INSERT INTO watched_posts (user_id, post_id, created_at)
+                     VALUES ($1, $2, $3) {}",
+                    sql::upsert(backend, &["user_id", "post_id"], &[])
+                ),
+                vec![
+                    user_id.into(),
+                    post_id.into(),
+                    chrono::Utc::now().naive_utc().into(),
+                ],
+            ))
+            .await?;
+        Ok(true)
+    }
+
+    pub async fn unwatch(&self, user_id: i32, post_id: i32) -> AppResult<bool> {
+        WatchedPost::delete_many()
+            .filter(watched_post::Column::UserId.eq(user_id))
+            .filter(watched_post::Column::PostId.eq(post_id))
+            .exec(&self.db)
+            .await?;
+        Ok(false)
+    }
+
+    /// Watch the thread a user just commented on, unless they opted out.
+    pub async fn auto_watch(&self, user_id: i32, post_id: i32) -> AppResult<()> {
+        let enabled = User::find_by_id(user_id)
+            .one(&self.db)
+            .await?
+            .is_some_and(|u| u.auto_watch);
+        if enabled {
+            self.watch(user_id, post_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Ids of every user watching a post.
+    pub async fn watchers(&self, post_id: i32) -> AppResult<Vec<i32>> {
+        let ids = WatchedPost::find()
+            .select_only()
+            .column(watched_post::Column::UserId)
+            .filter(watched_post::Column::PostId.eq(post_id))
+            .into_tuple::<i32>()
+            .all(&self.db)
+            .await?;
+        Ok(ids)
+    }
+}
@@ -0,0 +1,110 @@
+//! The custom emoji catalogs used when rendering Markdown.
+//!
+//! Rendering is synchronous, so each community's catalog is kept in memory:
+//! loaded from its database at startup, replaced whenever admins change it,
+//! and reloaded periodically to pick up changes made on other instances.
+
+use crate::utils::tenant;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Shortcode to image URL.
+pub type EmojiCatalog = HashMap<String, String>;
+
+/// Catalogs by tenant slug, `None` being the default community.
+fn catalogs() -> &'static RwLock<HashMap<Option<String>, Arc<EmojiCatalog>>> {
+    static CATALOGS: OnceLock<RwLock<HashMap<Option<String>, Arc<EmojiCatalog>>>> = OnceLock::new();
+    CATALOGS.get_or_init(Default::default)
+}
+
+/// The custom emoji of the community the current request is for.
+pub fn catalog() -> Arc<EmojiCatalog> {
+    catalogs()
+        .read()
+        .unwrap()
+        .get(&tenant::current())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Replace a community's catalog, e.g. after an emoji was added.
+pub fn set_catalog(tenant: Option<String>, catalog: EmojiCatalog) {
+    catalogs()
+        .write()
+        .unwrap()
+        .insert(tenant, Arc::new(catalog));
+}
+
+/// Shortcodes are 2-32 lowercase letters, digits and underscores.
+pub fn is_valid_shortcode(shortcode: &str) -> bool {
+    (2..=32).contains(&shortcode.len())
+        && shortcode
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Split `text` around the `:shortcode:`s found in `catalog`, as plain text
+/// and `(shortcode, url)` pieces. Unknown shortcodes stay text.
+pub fn split<'a>(text: &'a str, catalog: &'a EmojiCatalog) -> Vec<Piece<'a>> {
+    let mut pieces = Vec::new();
+    let mut plain_start = 0;
+    let mut search_from = 0;
+    while let Some(open) = text[search_from..].find(':').map(|i| search_from + i) {
+        let Some(close) = text[open + 1..].find(':').map(|i| open + 1 + i) else {
+            break;
+        };
+        let shortcode = &text[open + 1..close];
+        match catalog.get(shortcode) {
+            Some(url) if is_valid_shortcode(shortcode) => {
+                if plain_start < open {
+                    pieces.push(Piece::Text(&text[plain_start..open]));
+                }
+                pieces.push(Piece::Emoji(shortcode, url));
+                plain_start = close + 1;
+                search_from = close + 1;
+            }
+            // The closing colon may open the next shortcode
+            _ => search_from = close,
+        }
+    }
+    if plain_start < text.len() {
+        pieces.push(Piece::Text(&text[plain_start..]));
+    }
+    pieces
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Piece<'a> {
+    Text(&'a str),
+    /// Shortcode and image URL
+    Emoji(&'a str, &'a str),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shortcodes_are_split_out_of_text() {
+        let catalog =
+            EmojiCatalog::from([("blob".to_string(), "/uploads/emoji/b.png".to_string())]);
+        assert_eq!(
+            split("at 10:30 :blob::blob: :nope: ok", &catalog),
+            vec![
+                Piece::Text("at 10:30 "),
+                Piece::Emoji("blob", "/uploads/emoji/b.png"),
+                Piece::Emoji("blob", "/uploads/emoji/b.png"),
+                Piece::Text(" :nope: ok"),
+            ]
+        );
+        assert_eq!(split("plain", &catalog), vec![Piece::Text("plain")]);
+    }
+
+    #[test]
+    fn test_shortcode_rules() {
+        assert!(is_valid_shortcode("party_parrot2"));
+        assert!(!is_valid_shortcode("x"));
+        assert!(!is_valid_shortcode("Upper"));
+        assert!(!is_valid_shortcode("with space"));
+    }
+}
@@ -0,0 +1,367 @@
+//! Translated API messages.
+//!
+//! Each request runs in a locale scope set by
+//! [`locale_middleware`](crate::middleware::locale::locale_middleware): the
+//! first supported language in `Accept-Language`, replaced by the user's own
+//! `locale` once the request is authenticated. Handlers look messages up by
+//! code with [`t`]. Errors keep their English messages in code, so
+//! [`translate`] finds the entry for an English message; messages not in the
+//! catalog are sent untranslated.
+
+use crate::services::email_template::Locale;
+use std::cell::Cell;
+use std::future::Future;
+
+tokio::task_local! {
+    static LOCALE: Cell<Locale>;
+}
+
+/// Code, English, Chinese.
+const CATALOG: &[(&str, &str, &str)] = &[
+    // Errors
+    ("unauthorized", "Unauthorized", "未登录或登录已失效"),
+    ("invalid_token", "Invalid token", "令牌无效"),
+    ("not_found", "Resource not found", "资源不存在"),
+    ("route_not_found", "Route not found", "路由不存在"),
+    ("forbidden", "Forbidden", "没有权限"),
+    ("database_error", "Database error", "数据库错误"),
+    ("internal_error", "Internal server error", "服务器内部错误"),
+    ("file_too_large", "File too large", "文件过大"),
+    ("request_too_large", "Request body too large", "请求体过大"),
+    (
+        "method_not_allowed",
+        "Method not allowed",
+        "不支持的请求方法",
+    ),
+    ("too_many_requests", "Too many requests", "请求过于频繁"),
+    (
+        "maintenance",
+        "The site is down for maintenance and is read-only for now. Please try again later.",
+        "站点正在维护，暂时只读，请稍后再试。",
+    ),
+    (
+        "username_taken",
+        "Username is already taken",
+        "用户名已被占用",
+    ),
+    ("email_taken", "Email is already registered", "邮箱已被注册"),
+    (
+        "username_or_email_taken",
+        "Username or email already exists",
+        "用户名或邮箱已存在",
+    ),
+    (
+        "forum_name_taken",
+        "A forum with this name already exists",
+        "已有同名板块",
+    ),
+    (
+        "forum_slug_taken",
+        "A forum with this slug already exists",
+        "板块标识已被占用",
+    ),
+    (
+        "tag_name_taken",
+        "A tag with this name already exists",
+        "已有同名标签",
+    ),
+    (
+        "tag_slug_taken",
+        "A tag with this slug already exists",
+        "标签标识已被占用",
+    ),
+    ("tag_exists", "Tag already exists", "标签已存在"),
+    (
+        "flair_name_taken",
+        "A flair with this name already exists",
+        "本板块已有同名帖子分类",
+    ),
+    (
+        "flair_not_in_forum",
+        "Flair is not available in this forum",
+        "该板块没有这个帖子分类",
+    ),
+    (
+        "upload_attached",
+        "Upload is already attached to a post",
+        "该文件已附加到帖子",
+    ),
+    (
+        "already_crossposted",
+        "Post is already crossposted to this forum",
+        "该帖子已转发到这个板块",
+    ),
+    (
+        "post_slug_taken",
+        "A post with this slug already exists in the forum",
+        "该板块已有使用此链接名的帖子",
+    ),
+    (
+        "already_in_forum",
+        "Post is already in this forum",
+        "帖子已在这个板块中",
+    ),
+    (
+        "emoji_shortcode_taken",
+        "An emoji with this shortcode already exists",
+        "已有同名表情",
+    ),
+    (
+        "emoji_shortcode_invalid",
+        "Shortcode must be 2-32 lowercase letters, digits or underscores",
+        "表情名须为 2-32 个小写字母、数字或下划线",
+    ),
+    (
+        "already_reported",
+        "You have already reported this",
+        "你已经举报过了",
+    ),
+    (
+        "already_voted",
+        "You have already voted on this",
+        "你已经投过票了",
+    ),
+    ("already_exists", "Already exists", "已存在"),
+    (
+        "already_appealed",
+        "You have already appealed this action",
+        "你已经对该处理提出过申诉",
+    ),
+    (
+        "report_resolved",
+        "Report is already resolved",
+        "举报已处理",
+    ),
+    ("user_not_found", "User not found", "用户不存在"),
+    ("forum_not_found", "Forum not found", "板块不存在"),
+    ("post_not_found", "Post not found", "帖子不存在"),
+    ("comment_not_found", "Comment not found", "评论不存在"),
+    (
+        "parent_comment_not_found",
+        "Parent comment not found",
+        "被回复的评论不存在",
+    ),
+    (
+        "quoted_comment_not_found",
+        "Quoted comment not found",
+        "被引用的评论不存在",
+    ),
+    (
+        "quoted_comment_elsewhere",
+        "Quoted comment belongs to a different post",
+        "被引用的评论不在这个帖子下",
+    ),
+    ("post_locked", "Post is locked", "帖子已锁定"),
+    (
+        "post_closed_by_author",
+        "Post is closed to new comments by its author",
+        "作者已关闭该帖子的评论",
+    ),
+    (
+        "max_comment_depth",
+        "Maximum comment nesting depth reached",
+        "评论嵌套层数已达上限",
+    ),
+    (
+        "max_tags",
+        "Maximum 5 tags allowed",
+        "最多只能添加 5 个标签",
+    ),
+    (
+        "cannot_follow_self",
+        "Cannot follow yourself",
+        "不能关注自己",
+    ),
+    (
+        "cannot_unfollow_self",
+        "Cannot unfollow yourself",
+        "不能取消关注自己",
+    ),
+    (
+        "invalid_vote",
+        "Vote value must be -1, 0 or 1",
+        "投票值必须为 -1、0 或 1",
+    ),
+    (
+        "invalid_invite_code",
+        "Invalid or expired invite code",
+        "邀请码无效或已过期",
+    ),
+    (
+        "email_domain_not_allowed",
+        "Registration with this email domain is not allowed",
+        "不允许使用该邮箱域名注册",
+    ),
+    (
+        "password_breached",
+        "This password has appeared in a data breach; please choose another",
+        "该密码曾出现在数据泄露中，请换一个",
+    ),
+    (
+        "invalid_verification_token",
+        "Invalid verification token",
+        "验证链接无效",
+    ),
+    (
+        "verification_token_expired",
+        "Verification token has expired",
+        "验证链接已过期",
+    ),
+    ("invalid_reset_token", "Invalid reset token", "重置链接无效"),
+    (
+        "reset_token_expired",
+        "Reset token has expired",
+        "重置链接已过期",
+    ),
+    ("no_file", "No file provided", "未提供文件"),
+    (
+        "unsupported_file_type",
+        "Unsupported file type",
+        "不支持的文件类型",
+    ),
+    (
+        "invalid_search_query",
+        "Search query must be 1-200 characters",
+        "搜索词长度必须为 1-200 个字符",
+    ),
+    ("empty_content", "content must not be empty", "内容不能为空"),
+    ("password_incorrect", "Password is incorrect", "密码不正确"),
+    // Responses
+    ("logged_out", "Logout successful", "已退出登录"),
+    (
+        "account_deactivated",
+        "Account deactivated; log in again to reactivate it",
+        "账号已停用，重新登录即可恢复",
+    ),
+    (
+        "password_changed",
+        "Password changed successfully",
+        "密码已修改",
+    ),
+    (
+        "email_verified",
+        "Email verified successfully",
+        "邮箱已验证",
+    ),
+    (
+        "sessions_invalidated",
+        "User sessions invalidated",
+        "用户的登录会话已全部失效",
+    ),
+    ("account_deleted", "Account deleted", "账号已删除"),
+    ("user_deleted", "User deleted", "用户已删除"),
+    ("forum_deleted", "Forum deleted", "板块已删除"),
+    ("post_deleted", "Post deleted", "帖子已删除"),
+    (
+        "post_deleted_by_admin",
+        "Post deleted by admin",
+        "帖子已由管理员删除",
+    ),
+    ("comment_deleted", "Comment deleted", "评论已删除"),
+    (
+        "comment_deleted_by_admin",
+        "Comment deleted by admin",
+        "评论已由管理员删除",
+    ),
+    ("tag_deleted", "Tag deleted successfully", "标签已删除"),
+    ("flair_deleted", "Flair deleted", "帖子分类已删除"),
+    ("emoji_deleted", "Emoji deleted", "表情已删除"),
+    ("note_deleted", "Note deleted", "备注已删除"),
+    (
+        "notification_read",
+        "Notification marked as read",
+        "通知已标为已读",
+    ),
+    ("notification_deleted", "Notification deleted", "通知已删除"),
+    (
+        "follow_request_approved",
+        "Follow request approved",
+        "已批准关注请求",
+    ),
+    (
+        "follow_request_denied",
+        "Follow request denied",
+        "已拒绝关注请求",
+    ),
+    ("claim_released", "Claim released", "已释放认领"),
+    ("badge_awarded", "Badge awarded", "徽章已授予"),
+    (
+        "badge_already_held",
+        "User already holds the badge",
+        "用户已持有该徽章",
+    ),
+    ("badge_revoked", "Badge revoked", "徽章已收回"),
+    ("badge_deleted", "Badge deleted", "徽章已删除"),
+];
+
+fn localized(entry: &(&'static str, &'static str, &'static str), locale: Locale) -> &'static str {
+    match locale {
+        Locale::En => entry.1,
+        Locale::Zh => entry.2,
+    }
+}
+
+/// Run `f` with `locale` as the request's locale.
+pub async fn scope<F: Future>(locale: Locale, f: F) -> F::Output {
+    LOCALE.scope(Cell::new(locale), f).await
+}
+
+/// The current request's locale; English outside of a request.
+pub fn current() -> Locale {
+    LOCALE.try_with(Cell::get).unwrap_or(Locale::En)
+}
+
+/// Switch the current request to `locale`, e.g. the user's preference.
+pub fn prefer(locale: Locale) {
+    let _ = LOCALE.try_with(|current| current.set(locale));
+}
+
+/// The message for `code` in the current locale.
+pub fn t(code: &'static str) -> &'static str {
+    match CATALOG.iter().find(|entry| entry.0 == code) {
+        Some(entry) => localized(entry, current()),
+        None => {
+            debug_assert!(false, "no message for {}", code);
+            code
+        }
+    }
+}
+
+/// The code and current translation of an English message, if catalogued.
+pub fn translate(message: &str) -> Option<(&'static str, &'static str)> {
+    CATALOG
+        .iter()
+        .find(|entry| entry.1 == message)
+        .map(|entry| (entry.0, localized(entry, current())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_and_messages_are_unique() {
+        for (i, entry) in CATALOG.iter().enumerate() {
+            for other in &CATALOG[i + 1..] {
+                assert_ne!(entry.0, other.0, "duplicate code");
+                assert_ne!(entry.1, other.1, "duplicate message");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_messages_follow_the_request_locale() {
+        assert_eq!(t("post_deleted"), "Post deleted");
+        scope(Locale::Zh, async {
+            assert_eq!(t("post_deleted"), "帖子已删除");
+            assert_eq!(
+                translate("Username is already taken"),
+                Some(("username_taken", "用户名已被占用"))
+            );
+            assert_eq!(translate("Something else"), None);
+            prefer(Locale::En);
+            assert_eq!(t("post_deleted"), "Post deleted");
+        })
+        .await;
+    }
+}
@@ -1,3 +1,4 @@
+use super::tenant;
 use anyhow::Result;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
@@ -35,9 +36,22 @@ pub struct Claims {
     pub iat: usize,  // issued at
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token_type: Option<String>, // "access" or "refresh"
+    /// users.token_version at issue time; tokens from older versions are rejected
+    #[serde(default)]
+    pub ver: i32,
+    /// Admin acting as `sub`; set only on impersonation tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<i32>,
+    /// Impersonation mode: "read_only" or "full"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imp: Option<String>,
+    /// Tenant slug the token was issued on; tokens only work on that
+    /// tenant's host
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ten: Option<String>,
 }
 
-pub fn encode_access_token(user_id: &str) -> Result<String> {
+pub fn encode_access_token(user_id: &str, token_version: i32) -> Result<String> {
     let config = get_config();
     let now = chrono::Utc::now().timestamp() as usize;
     let claims = Claims {
@@ -45,6 +59,10 @@ pub fn encode_access_token(user_id: &str) -> Result<String> {
         exp: now + config.access_token_expiry as usize,
         iat: now,
         token_type: Some("access".to_string()),
+        ver: token_version,
+        act: None,
+        imp: None,
+        ten: tenant::current(),
     };
 
     encode(
@@ -55,7 +73,7 @@ pub fn encode_access_token(user_id: &str) -> Result<String> {
     .map_err(|e| anyhow::anyhow!("Failed to encode access token: {}", e))
 }
 
-pub fn encode_refresh_token(user_id: &str) -> Result<String> {
+pub fn encode_refresh_token(user_id: &str, token_version: i32) -> Result<String> {
     let config = get_config();
     let now = chrono::Utc::now().timestamp() as usize;
     let claims = Claims {
@@ -63,6 +81,10 @@ pub fn encode_refresh_token(user_id: &str) -> Result<String> {
         exp: now + config.refresh_token_expiry as usize,
         iat: now,
         token_type: Some("refresh".to_string()),
+        ver: token_version,
+        act: None,
+        imp: None,
+        ten: tenant::current(),
     };
 
     encode(
@@ -73,16 +95,55 @@ pub fn encode_refresh_token(user_id: &str) -> Result<String> {
     .map_err(|e| anyhow::anyhow!("Failed to encode refresh token: {}", e))
 }
 
+pub fn impersonation_token_expiry_seconds() -> u64 {
+    get_config().impersonation_token_expiry
+}
+
+/// Short-lived access token letting `impersonator_id` act as `user_id`.
+/// `mode` is "read_only" or "full".
+pub fn encode_impersonation_token(
+    user_id: &str,
+    token_version: i32,
+    impersonator_id: i32,
+    mode: &str,
+) -> Result<String> {
+    let config = get_config();
+    let now = chrono::Utc::now().timestamp() as usize;
+    let claims = Claims {
+        sub: user_id.to_owned(),
+        exp: now + config.impersonation_token_expiry as usize,
+        iat: now,
+        token_type: Some("access".to_string()),
+        ver: token_version,
+        act: Some(impersonator_id),
+        imp: Some(mode.to_string()),
+        ten: tenant::current(),
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.secret.as_bytes()),
+    )
+    .map_err(|e| anyhow::anyhow!("Failed to encode impersonation token: {}", e))
+}
+
 pub fn decode_jwt(token: &str) -> Result<Claims> {
     let config = get_config();
 
-    decode::<Claims>(
+    let claims = decode::<Claims>(
         token,
         &DecodingKey::from_secret(config.secret.as_bytes()),
         &Validation::default(),
     )
     .map(|data| data.claims)
-    .map_err(|e| anyhow::anyhow!("Failed to decode JWT: {}", e))
+    .map_err(|e| anyhow::anyhow!("Failed to decode JWT: {}", e))?;
+
+    // User ids are only unique within a tenant
+    if claims.ten != tenant::current() {
+        anyhow::bail!("JWT was issued for another tenant");
+    }
+    Ok(claims)
 }
 
 pub fn hash_refresh_token(token: &str) -> String {
@@ -122,7 +183,7 @@ mod tests {
     #[test]
     fn encode_decode_round_trip() {
         ensure_config();
-        let token = encode_access_token("42").unwrap();
+        let token = encode_access_token("42", 0).unwrap();
         let claims = decode_jwt(&token).unwrap();
         assert_eq!(claims.sub, "42");
         assert!(claims.exp > claims.iat);
@@ -132,7 +193,7 @@ mod tests {
     #[test]
     fn refresh_token_encode_decode() {
         ensure_config();
-        let token = encode_refresh_token("42").unwrap();
+        let token = encode_refresh_token("42", 0).unwrap();
         let claims = decode_jwt(&token).unwrap();
         assert_eq!(claims.sub, "42");
         assert!(claims.exp > claims.iat);
@@ -142,7 +203,7 @@ mod tests {
     #[test]
     fn tampered_token_fails() {
         ensure_config();
-        let token = encode_access_token("42").unwrap();
+        let token = encode_access_token("42", 0).unwrap();
         // Flip a character in the middle of the token
         let mut chars: Vec<char> = token.chars().collect();
         let mid = chars.len() / 2;
@@ -161,6 +222,10 @@ mod tests {
             exp: now - 3600, // expired 1 hour ago
             iat: now - 7200,
             token_type: Some("access".to_string()),
+            ver: 0,
+            act: None,
+            imp: None,
+            ten: None,
         };
         let token = encode(
             &Header::default(),
@@ -171,6 +236,46 @@ mod tests {
         assert!(decode_jwt(&token).is_err());
     }
 
+    #[test]
+    fn token_version_round_trips() {
+        ensure_config();
+        let token = encode_access_token("42", 7).unwrap();
+        assert_eq!(decode_jwt(&token).unwrap().ver, 7);
+    }
+
+    #[test]
+    fn impersonation_token_carries_actor() {
+        ensure_config();
+        let token = encode_impersonation_token("42", 3, 1, "read_only").unwrap();
+        let claims = decode_jwt(&token).unwrap();
+        assert_eq!(claims.sub, "42");
+        assert_eq!(claims.act, Some(1));
+        assert_eq!(claims.imp.as_deref(), Some("read_only"));
+        assert!(is_access_token(&claims));
+
+        let plain = decode_jwt(&encode_access_token("42", 3).unwrap()).unwrap();
+        assert_eq!(plain.act, None);
+    }
+
+    #[tokio::test]
+    async fn token_only_works_on_its_tenant() {
+        ensure_config();
+        let token = tenant::scope("acme".to_string(), async {
+            encode_access_token("42", 0).unwrap()
+        })
+        .await;
+        assert!(decode_jwt(&token).is_err());
+
+        let other = tenant::scope("other".to_string(), async { decode_jwt(&token).is_ok() });
+        assert!(!other.await);
+        let same = tenant::scope("acme".to_string(), async { decode_jwt(&token).unwrap() });
+        assert_eq!(same.await.ten.as_deref(), Some("acme"));
+
+        let default = encode_access_token("42", 0).unwrap();
+        let in_tenant = tenant::scope("acme".to_string(), async { decode_jwt(&default).is_ok() });
+        assert!(!in_tenant.await);
+    }
+
     #[test]
     fn empty_token_fails() {
         ensure_config();
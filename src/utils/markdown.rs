@@ -1,76 +1,469 @@
+use crate::utils::emoji::{self, EmojiCatalog, Piece};
+use crate::utils::url_sign::{image_proxy_path, sign_url, url_signing_secret};
 use ammonia::{Builder, UrlRelative};
-use comrak::{markdown_to_html, Options};
+use comrak::adapters::SyntaxHighlighterAdapter;
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{format_html_with_plugins, parse_document, Arena, Options, Plugins};
 use std::borrow::Cow;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
 
 /// Render raw Markdown to sanitized HTML.
 ///
 /// Uses comrak for GFM-compatible parsing (tables, task lists, strikethrough,
-/// autolink, etc.) and ammonia for XSS-safe HTML sanitization.
+/// footnotes, autolink, etc.), syntect for highlighting fenced code, and
+/// ammonia for XSS-safe HTML sanitization. `>!spoilers!<` are marked for
+/// clients to hide and `:shortcode:`s of custom emoji become images.
 pub fn render_markdown(raw: &str) -> String {
     let mut options = Options::default();
     options.extension.strikethrough = true;
     options.extension.table = true;
     options.extension.autolink = true;
     options.extension.tasklist = true;
+    options.extension.footnotes = true;
     options.extension.superscript = true;
     options.extension.description_lists = true;
     options.render.unsafe_ = true; // let comrak emit raw HTML; ammonia will sanitize
 
-    let html = markdown_to_html(raw, &options);
-    sanitize_html(&html)
-}
-
-fn sanitize_html(html: &str) -> String {
-    let extra_tags: HashSet<&str> = [
-        "h1",
-        "h2",
-        "h3",
-        "h4",
-        "h5",
-        "h6",
-        "pre",
-        "code",
-        "blockquote",
-        "hr",
-        "table",
-        "thead",
-        "tbody",
-        "tfoot",
-        "tr",
-        "th",
-        "td",
-        "img",
-        "input",
-        "del",
-        "s",
-        "details",
-        "summary",
-        "sup",
-        "sub",
-        "dd",
-        "dl",
-        "dt",
-    ]
-    .iter()
-    .copied()
-    .collect();
+    let mut plugins = Plugins::default();
+    plugins.render.codefence_syntax_highlighter = Some(&CodeHighlighter);
+
+    let arena = Arena::new();
+    let root = parse_document(&arena, raw, &options);
+    mark_spoilers(&arena, root);
+    expand_emoji(&arena, root, &emoji::catalog());
+
+    let mut html = Vec::new();
+    format_html_with_plugins(root, &options, &mut html, &plugins)
+        .expect("writing HTML to a Vec can't fail");
+    let html = String::from_utf8(html).expect("comrak renders UTF-8");
+    let policy = LinkPolicy::from_env();
+    decorate_links(&sanitize_html(&html, &policy), &policy)
+}
+
+/// Wraps inline spoiler text; clients hide it until clicked.
+const SPOILER_OPEN: &str = "<span class=\"spoiler\">";
+const SPOILER_CLOSE: &str = "</span>";
+
+/// Wrap text between `>!` and `!<` in a spoiler `<span>`. Both markers have
+/// to be in the same paragraph, heading or other inline container, and
+/// outside code; an unclosed `>!` stays text.
+fn mark_spoilers<'a>(arena: &'a Arena<AstNode<'a>>, root: &'a AstNode<'a>) {
+    unwrap_spoiler_quotes(root);
+    let containers: Vec<&AstNode> = root
+        .descendants()
+        .filter(|node| node.children().any(|c| text_of(c).is_some()))
+        .collect();
+    for container in containers {
+        let mut opener: Option<&AstNode> = None;
+        let mut next = container.first_child();
+        while let Some(node) = next {
+            next = node.next_sibling();
+            let Some(literal) = text_of(node) else {
+                continue;
+            };
+            let (marker, html) = match opener {
+                None => (">!", SPOILER_OPEN),
+                Some(_) => ("!<", SPOILER_CLOSE),
+            };
+            let Some(at) = literal.find(marker) else {
+                continue;
+            };
+            let (before, after) = (&literal[..at], &literal[at + marker.len()..]);
+            if !before.is_empty() {
+                node.insert_before(arena.alloc(NodeValue::Text(before.to_string()).into()));
+            }
+            let tag = arena.alloc(NodeValue::HtmlInline(html.to_string()).into());
+            node.insert_before(tag);
+            if !after.is_empty() {
+                let rest = arena.alloc(NodeValue::Text(after.to_string()).into());
+                node.insert_before(rest);
+                next = Some(rest);
+            }
+            node.detach();
+            opener = opener.is_none().then_some(tag);
+        }
+        if let Some(tag) = opener {
+            tag.data.borrow_mut().value = NodeValue::Text(">!".to_string());
+        }
+    }
+}
+
+/// A line starting `>!` parses as a block quote of text starting with `!`.
+/// Put such quotes back to text when they hold a `!<`, so
+/// [`mark_spoilers`] finds the spoiler. `> !` with a space stays a quote.
+fn unwrap_spoiler_quotes<'a>(root: &'a AstNode<'a>) {
+    let quotes: Vec<&AstNode> = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::BlockQuote))
+        .collect();
+    for quote in quotes {
+        let Some(paragraph) = quote.first_child() else {
+            continue;
+        };
+        let Some(first) = paragraph.first_child() else {
+            continue;
+        };
+        let quote_start = quote.data.borrow().sourcepos.start;
+        let paragraph_start = paragraph.data.borrow().sourcepos.start;
+        let closed = paragraph
+            .descendants()
+            .any(|n| text_of(n).is_some_and(|t| t.contains("!<")));
+        if !matches!(paragraph.data.borrow().value, NodeValue::Paragraph)
+            || paragraph_start.line != quote_start.line
+            || paragraph_start.column != quote_start.column + 1
+            || !text_of(first).is_some_and(|t| t.starts_with('!'))
+            || !closed
+        {
+            continue;
+        }
+        if let NodeValue::Text(literal) = &mut first.data.borrow_mut().value {
+            literal.insert(0, '>');
+        }
+        while let Some(child) = quote.first_child() {
+            quote.insert_before(child);
+        }
+        quote.detach();
+    }
+}
+
+fn text_of(node: &AstNode) -> Option<String> {
+    match &node.data.borrow().value {
+        NodeValue::Text(literal) => Some(literal.clone()),
+        _ => None,
+    }
+}
+
+/// Replace the `:shortcode:`s in text with the custom emoji's image. Text
+/// in links keeps them, as does code, which isn't text to comrak.
+fn expand_emoji<'a>(arena: &'a Arena<AstNode<'a>>, root: &'a AstNode<'a>, catalog: &EmojiCatalog) {
+    if catalog.is_empty() {
+        return;
+    }
+    let texts: Vec<&AstNode> = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Text(_)))
+        .filter(|node| {
+            !node.ancestors().any(|a| {
+                matches!(
+                    a.data.borrow().value,
+                    NodeValue::Link(_) | NodeValue::Image(_)
+                )
+            })
+        })
+        .collect();
+    for node in texts {
+        let literal = match &node.data.borrow().value {
+            NodeValue::Text(literal) => literal.clone(),
+            _ => continue,
+        };
+        let pieces = emoji::split(&literal, catalog);
+        if !pieces.iter().any(|p| matches!(p, Piece::Emoji(..))) {
+            continue;
+        }
+        for piece in pieces {
+            let value = match piece {
+                Piece::Text(text) => NodeValue::Text(text.to_string()),
+                Piece::Emoji(shortcode, url) => NodeValue::HtmlInline(format!(
+                    "<img class=\"emoji\" src=\"{}\" alt=\":{}:\" title=\":{}:\">",
+                    escape_attr(url),
+                    shortcode,
+                    shortcode
+                )),
+            };
+            node.insert_before(arena.alloc(value.into()));
+        }
+        node.detach();
+    }
+}
+
+/// Prefix of the classes on highlighted code tokens, e.g. `hl-keyword`.
+/// Clients style them with a syntect theme exported with this prefix.
+const HIGHLIGHT_CLASS_PREFIX: &str = "hl-";
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// Highlights fenced code blocks with a known language as `<span>`s with
+/// prefixed classes; other blocks are left plain. The `<code>` keeps
+/// comrak's `language-*` class.
+struct CodeHighlighter;
+
+impl SyntaxHighlighterAdapter for CodeHighlighter {
+    fn write_highlighted(
+        &self,
+        output: &mut dyn Write,
+        lang: Option<&str>,
+        code: &str,
+    ) -> io::Result<()> {
+        let syntaxes = syntax_set();
+        let syntax = lang
+            .filter(|l| !l.is_empty())
+            .and_then(|l| syntaxes.find_syntax_by_token(l));
+        let Some(syntax) = syntax else {
+            return comrak::html::escape(output, code.as_bytes());
+        };
+
+        let mut generator = ClassedHTMLGenerator::new_with_class_style(
+            syntax,
+            syntaxes,
+            ClassStyle::SpacedPrefixed {
+                prefix: HIGHLIGHT_CLASS_PREFIX,
+            },
+        );
+        for line in LinesWithEndings::from(code) {
+            if generator
+                .parse_html_for_line_which_includes_newline(line)
+                .is_err()
+            {
+                return comrak::html::escape(output, code.as_bytes());
+            }
+        }
+        output.write_all(generator.finalize().as_bytes())
+    }
+
+    fn write_pre_tag(
+        &self,
+        output: &mut dyn Write,
+        attributes: HashMap<String, String>,
+    ) -> io::Result<()> {
+        comrak::html::write_opening_tag(output, "pre", attributes)
+    }
+
+    fn write_code_tag(
+        &self,
+        output: &mut dyn Write,
+        attributes: HashMap<String, String>,
+    ) -> io::Result<()> {
+        comrak::html::write_opening_tag(output, "code", attributes)
+    }
+}
+
+/// HTML for the comment a reply quotes: the saved excerpt in a blockquote,
+/// ending in a permalink to the comment on post `post_id` while it exists.
+pub fn render_quote(excerpt: &str, post_id: i32, quoted_comment_id: Option<i32>) -> String {
+    let excerpt = escape_attr(excerpt)
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    match quoted_comment_id {
+        Some(id) => format!(
+            "<blockquote class=\"quote\"><p>{}</p><p class=\"quote-source\"><a href=\"/posts/{}#comment-{}\">#{}</a></p></blockquote>",
+            excerpt, post_id, id, id
+        ),
+        None => format!("<blockquote class=\"quote\"><p>{}</p></blockquote>", excerpt),
+    }
+}
+
+/// An image URL as rendered Markdown would load it: through the image proxy
+/// when that is enabled and the image is external.
+pub fn proxied_image_url(src: &str) -> String {
+    LinkPolicy::from_env()
+        .proxied_image_src(src)
+        .unwrap_or_else(|| src.to_string())
+}
+
+/// Plain text and lead image of a post, for link previews.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownSummary {
+    /// Text content with whitespace collapsed, cut at a word boundary
+    pub excerpt: String,
+    /// URL of the first image, with upload paths normalized as when rendered
+    pub image: Option<String>,
+}
+
+/// Summarize raw Markdown without rendering it: the first `max_chars`
+/// characters of its text, ending in `…` if cut, and its first image.
+pub fn summarize_markdown(raw: &str, max_chars: usize) -> MarkdownSummary {
+    let arena = Arena::new();
+    let root = parse_document(&arena, raw, &Options::default());
+    mark_spoilers(&arena, root);
+
+    let mut text = String::new();
+    let mut image = None;
+    let mut in_spoiler = false;
+    for node in root.descendants() {
+        let in_image = node
+            .parent()
+            .is_some_and(|p| matches!(p.data.borrow().value, NodeValue::Image(_)));
+        match &node.data.borrow().value {
+            // Previews must not give spoilers away
+            NodeValue::HtmlInline(html) if html == SPOILER_OPEN => in_spoiler = true,
+            NodeValue::HtmlInline(html) if html == SPOILER_CLOSE => in_spoiler = false,
+            _ if in_spoiler => {}
+            NodeValue::Image(link) if image.is_none() => {
+                image = normalize_relative_url(&link.url).map(Cow::into_owned);
+            }
+            // Alt text describes the image, it is not part of the prose
+            NodeValue::Text(literal) if !in_image => text.push_str(literal),
+            NodeValue::Code(code) => text.push_str(&code.literal),
+            NodeValue::CodeBlock(block) => {
+                text.push(' ');
+                text.push_str(&block.literal);
+            }
+            NodeValue::SoftBreak | NodeValue::LineBreak => text.push(' '),
+            // Keep adjacent blocks from running together
+            NodeValue::Paragraph | NodeValue::Heading(_) | NodeValue::Item(_) => text.push(' '),
+            _ => {}
+        }
+    }
+
+    MarkdownSummary {
+        excerpt: truncate_words(
+            &text.split_whitespace().collect::<Vec<_>>().join(" "),
+            max_chars,
+        ),
+        image,
+    }
+}
+
+fn truncate_words(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    // Back up to the last space unless that would drop most of the text
+    let end = match cut.rfind(' ') {
+        Some(i) if i >= cut.len() / 2 => i,
+        _ => cut.len(),
+    };
+    format!("{}…", cut[..end].trim_end())
+}
+
+/// Tags allowed in rendered markdown when `MARKDOWN_ALLOWED_TAGS` is not
+/// configured.
+const DEFAULT_ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "dd",
+    "del",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "input",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "samp",
+    "section",
+    "small",
+    "span",
+    "strike",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+    "var",
+];
+
+/// Tags that can execute script, load foreign documents or submit data.
+/// These are never allowed, even if listed in `MARKDOWN_ALLOWED_TAGS`.
+const FORBIDDEN_TAGS: &[&str] = &[
+    "script", "style", "iframe", "frame", "frameset", "object", "embed", "applet", "form",
+    "button", "textarea", "select", "link", "meta", "base", "svg", "math", "template",
+];
+
+fn sanitize_html(html: &str, policy: &LinkPolicy) -> String {
+    let configured = std::env::var("MARKDOWN_ALLOWED_TAGS").ok();
+    let tags = allowed_tags(configured.as_deref());
+    sanitize_html_with_tags(html, &tags, policy)
+}
+
+/// Parse a comma-separated allowlist, falling back to the defaults when
+/// unset or empty. Forbidden tags are silently dropped.
+fn allowed_tags(raw: Option<&str>) -> Vec<String> {
+    let configured: Vec<String> = raw
+        .unwrap_or_default()
+        .split(',')
+        .map(|t| t.trim().to_ascii_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let tags = if configured.is_empty() {
+        DEFAULT_ALLOWED_TAGS.iter().map(|t| t.to_string()).collect()
+    } else {
+        configured
+    };
+
+    tags.into_iter()
+        .filter(|t| !FORBIDDEN_TAGS.contains(&t.as_str()))
+        .collect()
+}
+
+fn sanitize_html_with_tags(html: &str, tags: &[String], policy: &LinkPolicy) -> String {
+    let allowed_tags: HashSet<&str> = tags.iter().map(String::as_str).collect();
 
     let url_schemes: HashSet<&str> = ["http", "https", "mailto"].iter().copied().collect();
 
     let mut builder = Builder::default();
-    builder.add_tags(&extra_tags);
+    builder.tags(allowed_tags);
 
-    builder.add_tag_attributes("a", &["href", "title"]);
-    builder.add_tag_attributes("img", &["src", "alt", "title"]);
+    builder.add_tag_attributes("a", &["href", "title", "id", "class"]);
+    builder.add_tag_attributes("img", &["src", "alt", "title", "class"]);
     builder.add_tag_attributes("code", &["class"]);
+    builder.add_tag_attributes("span", &["class"]);
+    builder.add_tag_attributes("sup", &["class"]);
+    builder.add_tag_attributes("section", &["class"]);
+    builder.add_tag_attributes("li", &["id"]);
+    builder.add_tag_attributes("details", &["open"]);
     builder.add_tag_attributes("input", &["type", "checked", "disabled"]);
     builder.add_tag_attributes("td", &["align"]);
     builder.add_tag_attributes("th", &["align"]);
 
     builder.url_schemes(url_schemes);
     builder.url_relative(UrlRelative::Custom(Box::new(normalize_relative_url)));
-    builder.link_rel(Some("noopener noreferrer"));
+    // `rel`/`target` are not allowlisted; decorate_links adds them afterwards.
+    builder.link_rel(None);
+
+    let policy = policy.clone();
+    builder.attribute_filter(move |element, attribute, value| match attribute {
+        "src" if element == "img" => match policy.proxied_image_src(value) {
+            Some(proxied) => Some(Cow::Owned(proxied)),
+            None => Some(Cow::Borrowed(value)),
+        },
+        "class" => allowed_classes(element, value).map(Cow::Owned),
+        // Only footnote anchors, so posts can't take over the page's ids
+        "id" if value.starts_with("fn-") || value.starts_with("fnref-") => {
+            Some(Cow::Borrowed(value))
+        }
+        "id" => None,
+        _ => Some(Cow::Borrowed(value)),
+    });
 
     builder
         .clean(html)
@@ -79,6 +472,23 @@ fn sanitize_html(html: &str) -> String {
         .to_string()
 }
 
+/// The classes of `value` that rendered Markdown itself produces on
+/// `element`: code languages, highlighted tokens, footnotes, spoilers and
+/// emoji. Others are dropped, so posts can't borrow the page's styles.
+fn allowed_classes(element: &str, value: &str) -> Option<String> {
+    let allowed = |class: &str| match element {
+        "code" => class.starts_with("language-"),
+        "span" => class.starts_with(HIGHLIGHT_CLASS_PREFIX) || class == "spoiler",
+        "a" => class == "footnote-backref",
+        "sup" => class == "footnote-ref",
+        "section" => class == "footnotes",
+        "img" => class == "emoji",
+        _ => false,
+    };
+    let classes: Vec<&str> = value.split_whitespace().filter(|c| allowed(c)).collect();
+    (!classes.is_empty()).then(|| classes.join(" "))
+}
+
 fn normalize_relative_url(url: &str) -> Option<Cow<'_, str>> {
     normalize_upload_url(url, markdown_upload_base_url().as_deref())
 }
@@ -106,6 +516,174 @@ fn normalize_upload_url<'a>(url: &'a str, upload_base_url: Option<&str>) -> Opti
     Some(Cow::Borrowed(url))
 }
 
+/// How links and images in sanitized HTML are rewritten.
+#[derive(Clone)]
+struct LinkPolicy {
+    /// Hosts treated as internal (no `nofollow`, same tab, not proxied)
+    internal_hosts: Vec<String>,
+    /// When set, external links are routed through `{base}/out?url=..&sig=..`
+    redirect: Option<SignedEndpoint>,
+    /// When set, external images are loaded through `{base}/img/{sig}/{url}`
+    image_proxy: Option<SignedEndpoint>,
+}
+
+#[derive(Clone)]
+struct SignedEndpoint {
+    base_url: String,
+    secret: Vec<u8>,
+}
+
+impl LinkPolicy {
+    fn from_env() -> Self {
+        let internal_hosts = std::env::var("MARKDOWN_INTERNAL_HOSTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|h| h.trim().to_ascii_lowercase())
+            .filter(|h| !h.is_empty())
+            .collect();
+
+        let endpoint = || SignedEndpoint {
+            base_url: markdown_upload_base_url()
+                .map(|b| b.trim_end_matches('/').to_string())
+                .unwrap_or_default(),
+            secret: url_signing_secret(),
+        };
+
+        Self {
+            internal_hosts,
+            redirect: parse_bool_env("OUTBOUND_REDIRECT_ENABLED", false).then(endpoint),
+            image_proxy: parse_bool_env("IMAGE_PROXY_ENABLED", false).then(endpoint),
+        }
+    }
+
+    fn proxied_image_src(&self, src: &str) -> Option<String> {
+        let proxy = self.image_proxy.as_ref()?;
+        if !self.is_external(src) {
+            return None;
+        }
+        Some(format!(
+            "{}{}",
+            proxy.base_url,
+            image_proxy_path(&proxy.secret, src)
+        ))
+    }
+
+    fn is_external(&self, href: &str) -> bool {
+        let Ok(url) = url::Url::parse(href) else {
+            // Relative URLs point back at this site.
+            return false;
+        };
+        if !matches!(url.scheme(), "http" | "https") {
+            return false;
+        }
+        match url.host_str() {
+            Some(host) => !self
+                .internal_hosts
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(host)),
+            None => false,
+        }
+    }
+}
+
+/// Add `rel`/`target` to every anchor in sanitized HTML, rewriting external
+/// hrefs through the outbound redirect when enabled.
+///
+/// Relies on the input being ammonia output: every attribute is double-quoted
+/// and `"` inside values is escaped, so a `>` outside quotes ends the tag.
+fn decorate_links(html: &str, policy: &LinkPolicy) -> String {
+    let mut out = String::with_capacity(html.len() + 64);
+    let mut rest = html;
+
+    while let Some(pos) = rest.find("<a ") {
+        out.push_str(&rest[..pos]);
+        let tag_src = &rest[pos..];
+        let Some(end) = find_tag_end(tag_src) else {
+            break;
+        };
+        out.push_str(&decorate_anchor(&tag_src[..end], policy));
+        out.push('>');
+        rest = &tag_src[end + 1..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn find_tag_end(tag_src: &str) -> Option<usize> {
+    let mut in_quotes = false;
+    for (i, c) in tag_src.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '>' if !in_quotes => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `tag` is the anchor start tag without its closing `>`.
+fn decorate_anchor(tag: &str, policy: &LinkPolicy) -> String {
+    const HREF: &str = " href=\"";
+
+    let href_span = tag.find(HREF).and_then(|start| {
+        let value_start = start + HREF.len();
+        tag[value_start..]
+            .find('"')
+            .map(|len| (value_start, value_start + len))
+    });
+
+    let Some((value_start, value_end)) = href_span else {
+        return format!("{} rel=\"noopener noreferrer\"", tag);
+    };
+
+    let href = unescape_attr(&tag[value_start..value_end]);
+    if !policy.is_external(&href) {
+        return format!("{} rel=\"noopener noreferrer\"", tag);
+    }
+
+    let new_href = match &policy.redirect {
+        Some(redirect) => format!(
+            "{}/out?url={}&sig={}",
+            redirect.base_url,
+            url::form_urlencoded::byte_serialize(href.as_bytes()).collect::<String>(),
+            sign_url(&redirect.secret, &href)
+        ),
+        None => href,
+    };
+
+    format!(
+        "{}{}{} rel=\"nofollow noopener noreferrer\" target=\"_blank\"",
+        &tag[..value_start],
+        escape_attr(&new_href),
+        &tag[value_end..]
+    )
+}
+
+fn unescape_attr(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&")
+}
+
+fn escape_attr(value: &str) -> String {
+    value.replace('&', "&amp;").replace('"', "&quot;")
+}
+
+fn parse_bool_env(var_name: &str, default: bool) -> bool {
+    std::env::var(var_name)
+        .ok()
+        .and_then(|value| match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "y" | "on" => Some(true),
+            "0" | "false" | "no" | "n" | "off" => Some(false),
+            _ => None,
+        })
+        .unwrap_or(default)
+}
+
 fn markdown_upload_base_url() -> Option<String> {
     std::env::var("MARKDOWN_UPLOAD_BASE_URL")
         .ok()
@@ -176,8 +754,88 @@ mod tests {
     fn code_block_with_language() {
         let md = "```rust\nfn main() {}\n```";
         let html = render_markdown(md);
-        assert!(html.contains("<code"));
-        assert!(html.contains("fn main()"));
+        assert!(html.starts_with("<pre><code class=\"language-rust\">"));
+        // Highlighted tokens carry prefixed classes
+        assert!(html.contains("<span class=\"hl-"));
+        assert!(html.contains(">fn</span>"));
+        assert!(html.contains("main"));
+
+        // Unknown languages stay plain and escaped
+        let html = render_markdown("```nosuchlang\n<b>x</b>\n```");
+        assert!(html.contains("<code class=\"language-nosuchlang\">&lt;b&gt;x&lt;/b&gt;"));
+        assert!(!html.contains("<span"));
+    }
+
+    #[test]
+    fn user_classes_and_ids_are_dropped() {
+        let html = render_markdown(
+            "<span class=\"hl-keyword admin-badge\" id=\"login\">x</span><li id=\"fn-1\">y</li>",
+        );
+        assert!(html.contains("<span class=\"hl-keyword\">x</span>"));
+        assert!(!html.contains("login"));
+        assert!(html.contains("<li id=\"fn-1\">"));
+    }
+
+    #[test]
+    fn footnotes_keep_their_links() {
+        let html = render_markdown("Claim[^1]\n\n[^1]: Source");
+        assert!(html.contains("<sup class=\"footnote-ref\"><a href=\"#fn-1\" id=\"fnref-1\""));
+        assert!(html.contains("<section class=\"footnotes\">"));
+        assert!(html.contains("<li id=\"fn-1\">"));
+        assert!(html.contains("class=\"footnote-backref\""));
+    }
+
+    #[test]
+    fn custom_emoji_are_expanded_outside_code_and_links() {
+        emoji::set_catalog(
+            None,
+            EmojiCatalog::from([("blob".to_string(), "/uploads/emoji/b.png".to_string())]),
+        );
+        let html = render_markdown("Hi :blob:! `:blob:` [:blob:](https://example.com) :other:");
+        assert!(html.contains(
+            "Hi <img class=\"emoji\" src=\"/uploads/emoji/b.png\" alt=\":blob:\" title=\":blob:\">!"
+        ));
+        assert!(html.contains("<code>:blob:</code>"));
+        assert!(html.contains(">:blob:</a>"));
+        assert!(html.contains(":other:"));
+        assert!(render_markdown("<img class=\"admin\" src=\"/x.png\">").contains("<img src"));
+    }
+
+    #[test]
+    fn spoilers_are_wrapped_for_hiding() {
+        let html = render_markdown("The butler >!did **it**!< after all");
+        assert_eq!(
+            html,
+            "<p>The butler <span class=\"spoiler\">did <strong>it</strong></span> after all</p>"
+        );
+        let html = render_markdown(">!Whole line!<\n\n> !Just a quote!<");
+        assert!(html.contains("<p><span class=\"spoiler\">Whole line</span></p>"));
+        assert!(html.contains("<blockquote>\n<p>!Just a quote!&lt;</p>"));
+        let html = render_markdown("`>!code!<` and >!unclosed");
+        assert!(html.contains("<code>&gt;!code!&lt;</code> and &gt;!unclosed"));
+    }
+
+    #[test]
+    fn details_survive_sanitizing() {
+        let html = render_markdown(
+            "<details open onclick=\"x()\">\n<summary>Ending</summary>\n\nIt was **him**.\n\n</details>",
+        );
+        assert!(html.contains("<details open=\"\">\n<summary>Ending</summary>"));
+        assert!(html.contains("<p>It was <strong>him</strong>.</p>\n</details>"));
+        assert!(!html.contains("onclick"));
+    }
+
+    #[test]
+    fn quotes_are_escaped_and_link_back() {
+        assert_eq!(
+            render_quote("a <b> & \"c\"", 3, Some(7)),
+            "<blockquote class=\"quote\"><p>a &lt;b&gt; &amp; &quot;c&quot;</p>\
+             <p class=\"quote-source\"><a href=\"/posts/3#comment-7\">#7</a></p></blockquote>"
+        );
+        assert_eq!(
+            render_quote("gone", 3, None),
+            "<blockquote class=\"quote\"><p>gone</p></blockquote>"
+        );
     }
 
     #[test]
@@ -206,7 +864,8 @@ mod tests {
 
     #[test]
     fn root_relative_upload_image_respects_config_base_url() {
-        let normalized = normalize_upload_url("/uploads/images/a.webp", Some("https://api.example.com"));
+        let normalized =
+            normalize_upload_url("/uploads/images/a.webp", Some("https://api.example.com"));
         assert_eq!(
             normalized.unwrap(),
             "https://api.example.com/uploads/images/a.webp"
@@ -215,10 +874,175 @@ mod tests {
 
     #[test]
     fn configured_base_url_trims_trailing_slash() {
-        let normalized = normalize_upload_url("uploads/images/a.webp", Some("https://api.example.com/"));
+        let normalized =
+            normalize_upload_url("uploads/images/a.webp", Some("https://api.example.com/"));
         assert_eq!(
             normalized.unwrap(),
             "https://api.example.com/uploads/images/a.webp"
         );
     }
+
+    #[test]
+    fn xss_dangerous_protocols_removed() {
+        let html =
+            render_markdown("[a](data:text/html;base64,PHNjcmlwdD4=) <a href=\"vbscript:x\">b</a>");
+        assert!(!html.contains("data:"));
+        assert!(!html.contains("vbscript:"));
+    }
+
+    #[test]
+    fn xss_iframe_and_style_removed() {
+        let html =
+            render_markdown("<iframe src=\"https://evil.test\"></iframe><style>body{}</style>ok");
+        assert!(!html.contains("<iframe"));
+        assert!(!html.contains("<style"));
+        assert!(html.contains("ok"));
+    }
+
+    #[test]
+    fn allowed_tags_default_when_unset_or_empty() {
+        assert_eq!(allowed_tags(None).len(), DEFAULT_ALLOWED_TAGS.len());
+        assert_eq!(allowed_tags(Some(" , ")).len(), DEFAULT_ALLOWED_TAGS.len());
+    }
+
+    #[test]
+    fn allowed_tags_config_drops_forbidden_tags() {
+        let tags = allowed_tags(Some("pre, Script ,iframe,code"));
+        assert_eq!(tags, vec!["pre".to_string(), "code".to_string()]);
+    }
+
+    #[test]
+    fn custom_allowlist_strips_unlisted_tags() {
+        let tags = allowed_tags(Some("pre,code"));
+        let html = sanitize_html_with_tags(
+            "<table><tr><td>x</td></tr></table><pre>y</pre>",
+            &tags,
+            &policy(&[], false),
+        );
+        assert!(!html.contains("<table>"));
+        assert!(html.contains("<pre>y</pre>"));
+    }
+
+    fn endpoint() -> SignedEndpoint {
+        SignedEndpoint {
+            base_url: "https://api.example.com".to_string(),
+            secret: b"secret".to_vec(),
+        }
+    }
+
+    fn policy(internal: &[&str], redirect: bool) -> LinkPolicy {
+        LinkPolicy {
+            internal_hosts: internal.iter().map(|h| h.to_string()).collect(),
+            redirect: redirect.then(endpoint),
+            image_proxy: redirect.then(endpoint),
+        }
+    }
+
+    #[test]
+    fn external_link_gets_nofollow_and_blank_target() {
+        let html = decorate_links(
+            "<a href=\"https://other.test/x\">x</a>",
+            &policy(&["forum.test"], false),
+        );
+        assert_eq!(
+            html,
+            "<a href=\"https://other.test/x\" rel=\"nofollow noopener noreferrer\" target=\"_blank\">x</a>"
+        );
+    }
+
+    #[test]
+    fn internal_and_relative_links_stay_in_tab() {
+        let p = policy(&["forum.test"], true);
+        let html = decorate_links(
+            "<a href=\"https://FORUM.test/a\">a</a><a href=\"/posts/1\">b</a><a href=\"mailto:x@y.z\">c</a>",
+            &p,
+        );
+        assert_eq!(html.matches("rel=\"noopener noreferrer\"").count(), 3);
+        assert!(!html.contains("nofollow"));
+        assert!(!html.contains("/out?"));
+    }
+
+    #[test]
+    fn external_link_is_routed_through_signed_redirect() {
+        let html = decorate_links(
+            "<a href=\"https://other.test/?a=1&amp;b=2\" title=\"t\">x</a>",
+            &policy(&[], true),
+        );
+        let sig = sign_url(b"secret", "https://other.test/?a=1&b=2");
+        assert!(html.contains(&format!(
+            "href=\"https://api.example.com/out?url=https%3A%2F%2Fother.test%2F%3Fa%3D1%26b%3D2&amp;sig={}\"",
+            sig
+        )));
+        assert!(html.contains("title=\"t\""));
+    }
+
+    #[test]
+    fn quoted_gt_does_not_end_anchor_tag() {
+        let html = decorate_links(
+            "<a href=\"https://other.test/\" title=\"a > b\">x</a>",
+            &policy(&[], false),
+        );
+        assert!(html.ends_with("target=\"_blank\">x</a>"));
+    }
+
+    #[test]
+    fn rendered_user_rel_and_target_are_replaced() {
+        let html = render_markdown("<a href=\"/x\" rel=\"opener\" target=\"_top\">x</a>");
+        assert!(!html.contains("opener\""));
+        assert!(!html.contains("_top"));
+        assert!(html.contains("rel=\"noopener noreferrer\""));
+    }
+
+    #[test]
+    fn external_image_is_rewritten_through_proxy() {
+        let html = sanitize_html_with_tags(
+            "<img src=\"https://other.test/cat.png\"><img src=\"https://forum.test/a.png\"><img src=\"/uploads/images/b.png\">",
+            &allowed_tags(None),
+            &policy(&["forum.test"], true),
+        );
+        let proxied = format!(
+            "<img src=\"https://api.example.com{}\">",
+            image_proxy_path(b"secret", "https://other.test/cat.png")
+        );
+        assert!(html.contains(&proxied));
+        assert!(html.contains("<img src=\"https://forum.test/a.png\">"));
+        assert!(html.contains("<img src=\"/uploads/images/b.png\">"));
+    }
+
+    #[test]
+    fn images_untouched_when_proxy_disabled() {
+        let html = sanitize_html_with_tags(
+            "<img src=\"https://other.test/cat.png\">",
+            &allowed_tags(None),
+            &policy(&[], false),
+        );
+        assert_eq!(html, "<img src=\"https://other.test/cat.png\">");
+    }
+
+    #[test]
+    fn summary_collects_text_and_first_image() {
+        let summary = summarize_markdown(
+            "# Title\n\nSome *emphasis* and `code`.\n\n![alt text](uploads/images/a.png)\n![b](https://x.test/b.png)\n\n- one\n- two",
+            200,
+        );
+        assert_eq!(summary.excerpt, "Title Some emphasis and code. one two");
+        assert_eq!(summary.image.as_deref(), Some("/uploads/images/a.png"));
+        assert_eq!(summarize_markdown("no images", 200).image, None);
+    }
+
+    #[test]
+    fn summary_leaves_out_spoilers() {
+        let summary = summarize_markdown("It was >!the *butler*!<, obviously", 200);
+        assert_eq!(summary.excerpt, "It was , obviously");
+    }
+
+    #[test]
+    fn summary_is_cut_at_a_word_boundary() {
+        let summary = summarize_markdown("alpha beta gamma delta", 14);
+        assert_eq!(summary.excerpt, "alpha beta…");
+        assert_eq!(
+            summarize_markdown("你好世界你好世界", 5).excerpt,
+            "你好世界…"
+        );
+    }
 }
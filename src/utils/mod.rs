@@ -1,8 +1,18 @@
 pub mod cookie;
+pub mod emoji;
+pub mod http_signature;
+pub mod i18n;
 pub mod jwt;
 pub mod markdown;
 pub mod password;
 pub mod pow;
+pub mod shutdown;
+pub mod slug;
+pub mod sql;
+pub mod tenant;
+pub mod time;
+pub mod tls;
+pub mod url_sign;
 
 pub use jwt::{encode_access_token, encode_refresh_token};
 pub use markdown::render_markdown;
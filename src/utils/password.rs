@@ -1,4 +1,7 @@
+use crate::error::{AppError, AppResult};
 use anyhow::{Context, Result};
+use sha1::{Digest, Sha1};
+use std::time::Duration;
 
 /// Hash a password using bcrypt
 pub fn hash_password(password: &str) -> Result<String> {
@@ -10,6 +13,119 @@ pub fn verify_password(password: &str, hash: &str) -> Result<bool> {
     bcrypt::verify(password, hash).context("Failed to verify password")
 }
 
+/// What to do when a password shows up in the HaveIBeenPwned corpus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreachCheckMode {
+    Off,
+    Warn,
+    Reject,
+}
+
+#[derive(Debug, Clone)]
+pub struct BreachCheckConfig {
+    pub mode: BreachCheckMode,
+    pub api_url: String,
+    pub timeout: Duration,
+}
+
+impl BreachCheckConfig {
+    pub fn from_env() -> Self {
+        let mode = match std::env::var("PASSWORD_BREACH_CHECK")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "warn" => BreachCheckMode::Warn,
+            "reject" | "1" | "true" | "on" => BreachCheckMode::Reject,
+            _ => BreachCheckMode::Off,
+        };
+
+        let api_url = std::env::var("PASSWORD_BREACH_API_URL")
+            .ok()
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| "https://api.pwnedpasswords.com/range".to_string());
+
+        let timeout_ms: u64 = std::env::var("PASSWORD_BREACH_CHECK_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2000);
+
+        Self {
+            mode,
+            api_url,
+            timeout: Duration::from_millis(timeout_ms),
+        }
+    }
+}
+
+/// Check a new password against the HaveIBeenPwned range API.
+///
+/// Only the first 5 hex chars of the SHA-1 are sent (k-anonymity). Network
+/// failures never block the user: the check fails open with a warning.
+pub async fn check_password_not_breached(password: &str) -> AppResult<()> {
+    let config = BreachCheckConfig::from_env();
+    if config.mode == BreachCheckMode::Off {
+        return Ok(());
+    }
+
+    let hash = sha1_hex_upper(password);
+    let (prefix, suffix) = hash.split_at(5);
+
+    let count = match fetch_range(&config, prefix).await {
+        Ok(body) => breach_count(&body, suffix),
+        Err(e) => {
+            tracing::warn!("Password breach check unavailable: {e}");
+            return Ok(());
+        }
+    };
+
+    if count == 0 {
+        return Ok(());
+    }
+
+    match config.mode {
+        BreachCheckMode::Reject => Err(AppError::Validation(
+            "This password has appeared in a data breach; please choose another".to_string(),
+        )),
+        _ => {
+            tracing::warn!(count, "Accepted password that appears in breach corpus");
+            Ok(())
+        }
+    }
+}
+
+async fn fetch_range(config: &BreachCheckConfig, prefix: &str) -> Result<String> {
+    let client = reqwest::Client::builder().timeout(config.timeout).build()?;
+    let body = client
+        .get(format!("{}/{}", config.api_url, prefix))
+        .header("Add-Padding", "true")
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(body)
+}
+
+fn sha1_hex_upper(password: &str) -> String {
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+/// Parse a range response (`SUFFIX:COUNT` per line) for our hash suffix.
+/// Padding entries have a count of 0 and are ignored naturally.
+fn breach_count(body: &str, suffix: &str) -> u64 {
+    body.lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(s, _)| s.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -44,4 +160,24 @@ mod tests {
         assert!(verify_password("same_password", &hash1).unwrap());
         assert!(verify_password("same_password", &hash2).unwrap());
     }
+
+    #[test]
+    fn sha1_hex_matches_known_vector() {
+        // SHA-1("password")
+        assert_eq!(
+            sha1_hex_upper("password"),
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+        );
+    }
+
+    #[test]
+    fn breach_count_finds_suffix() {
+        let body = "0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:3861493\r\n011053FD0102E94D6AE2F8B83D76FAF94F6:0";
+        assert_eq!(
+            breach_count(body, "1E4C9B93F3F0682250B6CF8331B7EE68FD8"),
+            3861493
+        );
+        assert_eq!(breach_count(body, "011053FD0102E94D6AE2F8B83D76FAF94F6"), 0);
+        assert_eq!(breach_count(body, "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"), 0);
+    }
 }
@@ -3,10 +3,43 @@ use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 type HmacSha256 = Hmac<Sha256>;
 
+/// Actions that can be guarded by a PoW challenge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowAction {
+    Vote,
+    Register,
+    CreatePost,
+    Report,
+}
+
+impl PowAction {
+    pub const ALL: [PowAction; 4] = [
+        PowAction::Vote,
+        PowAction::Register,
+        PowAction::CreatePost,
+        PowAction::Report,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PowAction::Vote => "vote",
+            PowAction::Register => "register",
+            PowAction::CreatePost => "create_post",
+            PowAction::Report => "report",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PowChallenge {
     pub v: u8,
@@ -24,8 +57,17 @@ pub struct PowChallenge {
 pub struct PowConfig {
     pub secret: Vec<u8>,
     pub ttl_seconds: i64,
+    /// Base difficulty, before auto-tuning
     pub difficulty: u8,
     pub version: u8,
+    /// Actions that need a solved challenge
+    pub required_actions: Vec<PowAction>,
+    /// Verified submissions of one action per window before its difficulty
+    /// goes up; 0 turns auto-tuning off
+    pub autotune_threshold: u32,
+    pub autotune_window_seconds: i64,
+    /// Auto-tuning never goes above this
+    pub max_difficulty: u8,
 }
 
 impl PowConfig {
@@ -50,13 +92,169 @@ impl PowConfig {
             .and_then(|v| v.parse().ok())
             .unwrap_or(20);
 
+        let required_actions = match std::env::var("POW_REQUIRED_ACTIONS") {
+            Ok(raw) => parse_actions(&raw),
+            Err(_) => vec![PowAction::Vote],
+        };
+
+        let autotune_threshold: u32 = std::env::var("POW_AUTOTUNE_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let autotune_window_seconds: i64 = std::env::var("POW_AUTOTUNE_WINDOW_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|w| *w > 0)
+            .unwrap_or(60);
+
+        let max_difficulty: u8 = std::env::var("POW_MAX_DIFFICULTY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(24)
+            .max(difficulty);
+
         Ok(Self {
             secret: secret.into_bytes(),
             ttl_seconds,
             difficulty,
             version: 1,
+            required_actions,
+            autotune_threshold,
+            autotune_window_seconds,
+            max_difficulty,
         })
     }
+
+    pub fn requires(&self, action: PowAction) -> bool {
+        self.required_actions.contains(&action)
+    }
+
+    /// The difficulty to issue for `action` now: the base difficulty plus
+    /// one bit each time recent traffic doubles past the threshold.
+    pub fn difficulty_for(&self, action: PowAction) -> u8 {
+        let recent = activity().recent(action, self.autotune_window_seconds, now_epoch_seconds());
+        tuned_difficulty(
+            self.difficulty,
+            self.max_difficulty,
+            self.autotune_threshold,
+            recent,
+        )
+    }
+}
+
+/// Comma-separated action names; unknown names are skipped with a warning.
+fn parse_actions(raw: &str) -> Vec<PowAction> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| {
+            let action = PowAction::parse(name);
+            if action.is_none() {
+                tracing::warn!("Unknown action '{}' in POW_REQUIRED_ACTIONS", name);
+            }
+            action
+        })
+        .collect()
+}
+
+fn tuned_difficulty(base: u8, max: u8, threshold: u32, recent: u32) -> u8 {
+    if threshold == 0 || recent < threshold {
+        return base;
+    }
+    let extra = (recent / threshold).ilog2() + 1;
+    base.saturating_add(extra.min(u8::MAX as u32) as u8)
+        .min(max)
+}
+
+/// Per-second counts of verified submissions for each action, and the
+/// salts of challenges already spent. Kept in process, so with several
+/// instances each tunes and deduplicates on its own.
+#[derive(Default)]
+struct PowActivity {
+    counts: Mutex<HashMap<PowAction, VecDeque<(i64, u32)>>>,
+    spent: Mutex<HashMap<String, i64>>,
+}
+
+fn activity() -> &'static PowActivity {
+    static ACTIVITY: OnceLock<PowActivity> = OnceLock::new();
+    ACTIVITY.get_or_init(PowActivity::default)
+}
+
+impl PowActivity {
+    fn record(&self, action: PowAction, now: i64) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let seconds = counts.entry(action).or_default();
+        match seconds.back_mut() {
+            Some((second, count)) if *second == now => *count += 1,
+            _ => seconds.push_back((now, 1)),
+        }
+        // Nobody tunes on more than a day of history
+        while seconds
+            .front()
+            .is_some_and(|(second, _)| *second <= now - 86_400)
+        {
+            seconds.pop_front();
+        }
+    }
+
+    fn recent(&self, action: PowAction, window_seconds: i64, now: i64) -> u32 {
+        let counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        counts.get(&action).map_or(0, |seconds| {
+            seconds
+                .iter()
+                .filter(|(second, _)| *second > now - window_seconds)
+                .map(|(_, count)| *count)
+                .sum()
+        })
+    }
+
+    /// Mark a challenge as used; false if it already was.
+    fn spend(&self, salt: &str, expires_at: i64, now: i64) -> bool {
+        let mut spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+        spent.retain(|_, expiry| *expiry >= now);
+        spent.insert(salt.to_string(), expires_at).is_none()
+    }
+}
+
+/// Check the solved challenge a request carries when `action` requires one.
+/// The challenge must have been issued for this user (0 when anonymous),
+/// action and target, and each challenge is good for one request.
+pub fn require_pow(
+    cfg: &PowConfig,
+    action: PowAction,
+    target_type: &str,
+    target_id: i32,
+    user_id: i32,
+    pow_token: Option<&str>,
+    pow_nonce: Option<&str>,
+) -> AppResult<()> {
+    if !cfg.requires(action) {
+        return Ok(());
+    }
+    let (Some(token), Some(nonce)) = (pow_token, pow_nonce) else {
+        return Err(AppError::Validation(format!(
+            "pow_token and pow_nonce are required for {}",
+            action.as_str()
+        )));
+    };
+
+    let challenge = verify_and_decode_challenge(&cfg.secret, token)?;
+    if challenge.user_id != user_id
+        || challenge.action != action.as_str()
+        || challenge.target_type != target_type
+        || challenge.target_id != target_id
+    {
+        return Err(AppError::Validation("pow_token mismatch".to_string()));
+    }
+    validate_pow_solution(&challenge, nonce)?;
+
+    let now = now_epoch_seconds();
+    if !activity().spend(&challenge.salt, challenge.expires_at, now) {
+        return Err(AppError::Validation("pow_token already used".to_string()));
+    }
+    activity().record(action, now);
+    Ok(())
 }
 
 pub fn now_epoch_seconds() -> i64 {
@@ -220,6 +418,40 @@ mod tests {
         assert!(!super::has_leading_zero_bits(&c, 10));
     }
 
+    #[test]
+    fn difficulty_rises_with_traffic() {
+        assert_eq!(super::tuned_difficulty(20, 24, 0, 1000), 20);
+        assert_eq!(super::tuned_difficulty(20, 24, 10, 9), 20);
+        assert_eq!(super::tuned_difficulty(20, 24, 10, 10), 21);
+        assert_eq!(super::tuned_difficulty(20, 24, 10, 39), 22);
+        assert_eq!(super::tuned_difficulty(20, 24, 10, 40), 23);
+        assert_eq!(super::tuned_difficulty(20, 24, 10, 100_000), 24);
+    }
+
+    #[test]
+    fn activity_counts_recent_submissions() {
+        let activity = super::PowActivity::default();
+        let action = super::PowAction::Report;
+        activity.record(action, 100);
+        activity.record(action, 100);
+        activity.record(action, 150);
+        assert_eq!(activity.recent(action, 60, 155), 3);
+        assert_eq!(activity.recent(action, 10, 155), 1);
+        assert_eq!(activity.recent(super::PowAction::Vote, 60, 155), 0);
+
+        assert!(activity.spend("salt", 200, 150));
+        assert!(!activity.spend("salt", 200, 160));
+        assert!(activity.spend("salt", 300, 201));
+    }
+
+    #[test]
+    fn parse_actions_skips_unknown() {
+        assert_eq!(
+            super::parse_actions("vote, create_post,bogus,"),
+            vec![super::PowAction::Vote, super::PowAction::CreatePost]
+        );
+    }
+
     #[test]
     fn pow_config_falls_back_to_jwt_secret() {
         let _guard = env_lock().lock().unwrap();
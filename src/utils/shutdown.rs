@@ -0,0 +1,68 @@
+//! Process-wide shutdown. Background jobs and WebSocket connections are
+//! spawned through here; on shutdown they stop at their next idle point and
+//! `drain` waits for the ones still working.
+
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+struct Shutdown {
+    token: CancellationToken,
+    tasks: TaskTracker,
+}
+
+fn state() -> &'static Shutdown {
+    static STATE: OnceLock<Shutdown> = OnceLock::new();
+    STATE.get_or_init(|| Shutdown {
+        token: CancellationToken::new(),
+        tasks: TaskTracker::new(),
+    })
+}
+
+/// Spawn a task that shutdown waits for.
+pub fn spawn<F>(task: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    state().tasks.spawn(task)
+}
+
+/// Have shutdown wait for a future spawned elsewhere.
+pub fn track<F: Future>(task: F) -> impl Future<Output = F::Output> {
+    state().tasks.track_future(task)
+}
+
+/// Resolves once shutdown starts; loops select on it between iterations.
+pub async fn requested() {
+    state().token.cancelled().await
+}
+
+/// Stop background loops at their next idle point.
+pub fn trigger() {
+    state().token.cancel();
+}
+
+/// Wait up to `timeout` for tracked tasks to finish. Returns how many
+/// were still running when it gave up.
+pub async fn drain(timeout: Duration) -> usize {
+    let tasks = &state().tasks;
+    tasks.close();
+    match tokio::time::timeout(timeout, tasks.wait()).await {
+        Ok(()) => 0,
+        Err(_) => tasks.len(),
+    }
+}
+
+/// How long to wait for background work on shutdown,
+/// `SHUTDOWN_TIMEOUT_SECONDS` (default 30).
+pub fn drain_timeout() -> Duration {
+    let seconds = std::env::var("SHUTDOWN_TIMEOUT_SECONDS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(30);
+    Duration::from_secs(seconds)
+}
@@ -0,0 +1,98 @@
+//! URL slugs for posts: `/posts/42-hello-world`.
+
+/// Longest post slug, in characters.
+pub const MAX_SLUG_CHARS: usize = 80;
+
+/// Lowercase `title` with each run of other characters than letters and
+/// digits turned into one `-`, cut to [`MAX_SLUG_CHARS`]. Letters of any
+/// script are kept. Titles without any give `post`.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    let mut chars = 0;
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if chars == MAX_SLUG_CHARS {
+            break;
+        }
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if slug.is_empty() || slug.ends_with('-') {
+            continue;
+        } else {
+            slug.push('-');
+        }
+        chars += 1;
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "post".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// `slug` made unique among `taken`, by appending `-2`, `-3`, …
+pub fn unique_slug(slug: &str, taken: &[String]) -> String {
+    if !taken.iter().any(|t| t == slug) {
+        return slug.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}-{}", slug, n))
+        .find(|candidate| !taken.contains(candidate))
+        .expect("some suffix is free")
+}
+
+/// Path segment for a post: `42-some-slug`, or just `42` for posts made
+/// before slugs.
+pub fn post_ref(id: i32, slug: Option<&str>) -> String {
+    match slug {
+        Some(slug) => format!("{}-{}", id, slug),
+        None => id.to_string(),
+    }
+}
+
+/// The post ID of a path segment that is either `42` or `42-some-slug`.
+/// The slug isn't checked, so links keep working when it changes.
+pub fn parse_post_ref(segment: &str) -> Option<i32> {
+    let id = segment.split_once('-').map_or(segment, |(id, _)| id);
+    if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    id.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_titles_become_slugs() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(
+            slugify("  Rust 2024 -- what's new?  "),
+            "rust-2024-what-s-new"
+        );
+        assert_eq!(slugify("你好 世界"), "你好-世界");
+        assert_eq!(slugify("???"), "post");
+        assert_eq!(slugify(&"a".repeat(100)).chars().count(), MAX_SLUG_CHARS);
+        assert_eq!(slugify(&format!("{} b", "a".repeat(79))), "a".repeat(79));
+    }
+
+    #[test]
+    fn test_taken_slugs_get_a_suffix() {
+        let taken = vec!["hello".to_string(), "hello-2".to_string()];
+        assert_eq!(unique_slug("hello", &taken), "hello-3");
+        assert_eq!(unique_slug("other", &taken), "other");
+    }
+
+    #[test]
+    fn test_post_refs_are_ids_with_optional_slugs() {
+        assert_eq!(parse_post_ref("42"), Some(42));
+        assert_eq!(parse_post_ref("42-hello-world"), Some(42));
+        assert_eq!(parse_post_ref("42-"), Some(42));
+        assert_eq!(parse_post_ref("hello-42"), None);
+        assert_eq!(parse_post_ref("+42"), None);
+        assert_eq!(parse_post_ref(""), None);
+        assert_eq!(parse_post_ref(&post_ref(42, Some("hi"))), Some(42));
+        assert_eq!(post_ref(42, None), "42");
+    }
+}
@@ -0,0 +1,260 @@
+//! SQL fragments that differ between the supported backends.
+//!
+//! Raw queries are written once with `$n` placeholders and built through
+//! [`statement`], which rewrites them for MySQL, and pull anything
+//! backend-specific from here. SQLite is meant for local development and
+//! tests: full-text search there is a plain case-insensitive substring
+//! match. MySQL searches through `FULLTEXT` indexes instead of `tsvector`.
+
+use sea_orm::{DbBackend, Statement, Value};
+
+/// A raw statement written with Postgres-style `$n` placeholders.
+///
+/// Postgres and SQLite bind `$n` directly. MySQL only understands positional
+/// `?`, so each `$n` is replaced in order and its value repeated as needed.
+pub fn statement<S, I>(backend: DbBackend, sql: S, values: I) -> Statement
+where
+    S: Into<String>,
+    I: IntoIterator<Item = Value>,
+{
+    let sql = sql.into();
+    let values: Vec<Value> = values.into_iter().collect();
+    if backend != DbBackend::MySql {
+        return Statement::from_sql_and_values(backend, sql, values);
+    }
+
+    let (sql, order) = positional(&sql);
+    let values = order
+        .into_iter()
+        .map(|i| values[i].clone())
+        .collect::<Vec<_>>();
+    Statement::from_sql_and_values(backend, sql, values)
+}
+
+/// `sql` with every `$n` outside string literals replaced by `?`, plus the
+/// zero-based index of the value each `?` refers to.
+fn positional(sql: &str) -> (String, Vec<usize>) {
+    let mut out = String::with_capacity(sql.len());
+    let mut order = Vec::new();
+    let mut chars = sql.chars().peekable();
+    let mut in_string = false;
+    while let Some(ch) = chars.next() {
+        if ch == '\'' {
+            in_string = !in_string;
+        } else if ch == '$' && !in_string && chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+            let mut n = 0usize;
+            while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+                n = n * 10 + d as usize;
+                chars.next();
+            }
+            out.push('?');
+            order.push(n - 1);
+            continue;
+        }
+        out.push(ch);
+    }
+    (out, order)
+}
+
+/// Hours since the timestamp `column`, as a float.
+pub fn age_hours(backend: DbBackend, column: &str) -> String {
+    match backend {
+        DbBackend::Sqlite => format!("((julianday('now') - julianday({column})) * 24.0)"),
+        DbBackend::MySql => format!("(TIMESTAMPDIFF(SECOND, {column}, UTC_TIMESTAMP()) / 3600.0)"),
+        _ => format!("(EXTRACT(EPOCH FROM (NOW() - {column})) / 3600.0)"),
+    }
+}
+
+/// The larger of two numeric expressions.
+pub fn greatest(backend: DbBackend, a: &str, b: &str) -> String {
+    match backend {
+        DbBackend::Sqlite => format!("MAX({a}, {b})"),
+        _ => format!("GREATEST({a}, {b})"),
+    }
+}
+
+/// Sum of an integer expression as a 64-bit integer, 0 over no rows. MySQL
+/// would return a `DECIMAL`.
+pub fn sum_int(backend: DbBackend, expr: &str) -> String {
+    match backend {
+        DbBackend::MySql => format!("CAST(COALESCE(SUM({expr}), 0) AS SIGNED)"),
+        _ => format!("COALESCE(SUM({expr}), 0)"),
+    }
+}
+
+/// String concatenation of `parts`; `||` is a logical OR on MySQL.
+pub fn concat(backend: DbBackend, parts: &[&str]) -> String {
+    match backend {
+        DbBackend::MySql => format!("CONCAT({})", parts.join(", ")),
+        _ => format!("({})", parts.join(" || ")),
+    }
+}
+
+/// Case-insensitive `LIKE`. SQLite's `LIKE` and MySQL's default collation
+/// already ignore case.
+pub fn ilike(backend: DbBackend) -> &'static str {
+    match backend {
+        DbBackend::Postgres => "ILIKE",
+        _ => "LIKE",
+    }
+}
+
+/// `ESCAPE` clause making `\` the `LIKE` escape character. MySQL uses it by
+/// default and would read `'\'` as an unterminated string.
+pub fn like_escape(backend: DbBackend) -> &'static str {
+    match backend {
+        DbBackend::MySql => "",
+        _ => " ESCAPE '\\'",
+    }
+}
+
+/// Whether the text `columns` match the search terms bound at `query`.
+/// Postgres compares `vector` (a `tsvector` expression over the same
+/// columns); MySQL needs a `FULLTEXT` index over exactly these columns.
+pub fn text_match(backend: DbBackend, vector: &str, columns: &[&str], query: &str) -> String {
+    match backend {
+        DbBackend::Sqlite => format!(
+            "instr(LOWER({}), LOWER({query})) > 0",
+            columns.join(" || ' ' || ")
+        ),
+        DbBackend::MySql => mysql_match(columns, query),
+        _ => format!("{vector} @@ plainto_tsquery('english', {query})"),
+    }
+}
+
+/// Relevance of a [`text_match`]; constant on SQLite, where any match is as
+/// good as another.
+pub fn text_rank(backend: DbBackend, vector: &str, columns: &[&str], query: &str) -> String {
+    match backend {
+        DbBackend::Sqlite => "1.0".to_string(),
+        DbBackend::MySql => mysql_match(columns, query),
+        _ => format!("ts_rank({vector}, plainto_tsquery('english', {query}))"),
+    }
+}
+
+fn mysql_match(columns: &[&str], query: &str) -> String {
+    format!(
+        "MATCH({}) AGAINST ({query} IN NATURAL LANGUAGE MODE)",
+        columns.join(", ")
+    )
+}
+
+/// Row lock for claiming work from a queue; SQLite serialises writers anyway.
+pub fn skip_locked(backend: DbBackend) -> &'static str {
+    match backend {
+        DbBackend::Sqlite => "",
+        _ => "FOR UPDATE SKIP LOCKED",
+    }
+}
+
+/// `ON CONFLICT (conflict) DO UPDATE SET ...` for each of `update`, taking
+/// the proposed row's value, or `DO NOTHING` when `update` is empty. MySQL
+/// has no conflict target and resolves against any unique key.
+pub fn upsert(backend: DbBackend, conflict: &[&str], update: &[&str]) -> String {
+    match backend {
+        DbBackend::MySql => {
+            // Assigning a column to itself is MySQL's insert-or-ignore
+            let sets: Vec<String> = if update.is_empty() {
+                vec![format!("{0} = {0}", conflict[0])]
+            } else {
+                update
+                    .iter()
+                    .map(|c| format!("{c} = VALUES({c})"))
+                    .collect()
+            };
+            format!("ON DUPLICATE KEY UPDATE {}", sets.join(", "))
+        }
+        _ if update.is_empty() => format!("ON CONFLICT ({}) DO NOTHING", conflict.join(", ")),
+        _ => {
+            let sets: Vec<String> = update
+                .iter()
+                .map(|c| format!("{c} = EXCLUDED.{c}"))
+                .collect();
+            format!(
+                "ON CONFLICT ({}) DO UPDATE SET {}",
+                conflict.join(", "),
+                sets.join(", ")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_postgres_fragments() {
+        assert_eq!(
+            age_hours(DbBackend::Postgres, "p.created_at"),
+            "(EXTRACT(EPOCH FROM (NOW() - p.created_at)) / 3600.0)"
+        );
+        assert_eq!(greatest(DbBackend::Postgres, "a", "0"), "GREATEST(a, 0)");
+        assert_eq!(
+            sum_int(DbBackend::Postgres, "a - b"),
+            "COALESCE(SUM(a - b), 0)"
+        );
+        assert_eq!(
+            text_match(DbBackend::Postgres, "p.search_vector", &["p.title"], "$1"),
+            "p.search_vector @@ plainto_tsquery('english', $1)"
+        );
+        assert_eq!(
+            upsert(DbBackend::Postgres, &["a", "b"], &["c"]),
+            "ON CONFLICT (a, b) DO UPDATE SET c = EXCLUDED.c"
+        );
+    }
+
+    #[test]
+    fn test_sqlite_fragments() {
+        assert_eq!(greatest(DbBackend::Sqlite, "a", "0"), "MAX(a, 0)");
+        assert_eq!(ilike(DbBackend::Sqlite), "LIKE");
+        assert_eq!(
+            text_match(DbBackend::Sqlite, "p.search_vector", &["p.title"], "$1"),
+            "instr(LOWER(p.title), LOWER($1)) > 0"
+        );
+        assert_eq!(
+            upsert(DbBackend::Sqlite, &["a", "b"], &[]),
+            "ON CONFLICT (a, b) DO NOTHING"
+        );
+    }
+
+    #[test]
+    fn test_mysql_fragments() {
+        assert_eq!(
+            concat(DbBackend::MySql, &["'%'", "$2", "'%'"]),
+            "CONCAT('%', $2, '%')"
+        );
+        assert_eq!(like_escape(DbBackend::MySql), "");
+        assert_eq!(
+            sum_int(DbBackend::MySql, "a"),
+            "CAST(COALESCE(SUM(a), 0) AS SIGNED)"
+        );
+        assert_eq!(
+            text_match(DbBackend::MySql, "", &["p.title", "p.content"], "$1"),
+            "MATCH(p.title, p.content) AGAINST ($1 IN NATURAL LANGUAGE MODE)"
+        );
+        assert_eq!(
+            upsert(DbBackend::MySql, &["a", "b"], &[]),
+            "ON DUPLICATE KEY UPDATE a = a"
+        );
+        assert_eq!(
+            upsert(DbBackend::MySql, &["a"], &["b", "c"]),
+            "ON DUPLICATE KEY UPDATE b = VALUES(b), c = VALUES(c)"
+        );
+    }
+
+    #[test]
+    fn test_mysql_placeholders() {
+        let stmt = statement(
+            DbBackend::MySql,
+            "SELECT '$1' WHERE a = $2 OR b = $1 LIMIT $10",
+            (1..=10).map(Value::from),
+        );
+        assert_eq!(stmt.sql, "SELECT '$1' WHERE a = ? OR b = ? LIMIT ?");
+        let values = stmt.values.unwrap().0;
+        assert_eq!(values, vec![2.into(), 1.into(), 10.into()]);
+
+        let stmt = statement(DbBackend::Postgres, "SELECT $1", [Value::from(1)]);
+        assert_eq!(stmt.sql, "SELECT $1");
+    }
+}
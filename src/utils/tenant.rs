@@ -0,0 +1,80 @@
+//! The tenant a request is being served for. Set by the tenant middleware
+//! for the duration of the request; requests for the default community, and
+//! everything outside a request, see `None`.
+
+use std::future::Future;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// Slug of the tenant the current request belongs to.
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|slug| slug.clone()).ok()
+}
+
+/// Run `f` as a request for tenant `slug`.
+pub async fn scope<F: Future>(slug: String, f: F) -> F::Output {
+    CURRENT.scope(slug, f).await
+}
+
+/// Postgres schema holding a tenant's tables.
+pub fn schema_name(slug: &str) -> String {
+    format!("tenant_{slug}")
+}
+
+/// Slugs end up in schema names, so only lowercase letters, digits and `_`.
+pub fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug.len() <= 40
+        && slug
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+/// Lowercase host name without port or trailing dot, e.g.
+/// `Forum.Example.com:8443` -> `forum.example.com`.
+pub fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    let host = if host.starts_with('[') {
+        // IPv6 literal, possibly followed by a port
+        host.split_inclusive(']').next().unwrap_or(host)
+    } else {
+        host.split(':').next().unwrap_or(host)
+    };
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_hosts() {
+        assert_eq!(
+            normalize_host("Forum.Example.com:8443"),
+            "forum.example.com"
+        );
+        assert_eq!(normalize_host("example.com."), "example.com");
+        assert_eq!(normalize_host("[::1]:3000"), "[::1]");
+        assert_eq!(normalize_host("localhost"), "localhost");
+    }
+
+    #[test]
+    fn validates_slugs() {
+        assert!(is_valid_slug("rust_cn2"));
+        assert!(!is_valid_slug(""));
+        assert!(!is_valid_slug("Rust"));
+        assert!(!is_valid_slug("a-b"));
+        assert!(!is_valid_slug("x\"; DROP"));
+        assert!(!is_valid_slug(&"a".repeat(41)));
+    }
+
+    #[tokio::test]
+    async fn current_is_scoped_to_the_request() {
+        assert_eq!(current(), None);
+        let inside = scope("acme".to_string(), async { current() }).await;
+        assert_eq!(inside.as_deref(), Some("acme"));
+        assert_eq!(current(), None);
+    }
+}
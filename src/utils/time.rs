@@ -0,0 +1,46 @@
+//! Timestamps in API responses.
+//!
+//! Times are stored as naive UTC, and `NaiveDateTime`'s own formats carry no
+//! offset. Response fields serialize through these functions instead, as RFC
+//! 3339 with a `Z` suffix, e.g. `2026-10-18T09:05:01.123456Z`. Fractional
+//! seconds are kept so a timestamp can be sent back as a cursor.
+
+use chrono::{NaiveDateTime, SecondsFormat};
+use serde::Serializer;
+
+/// Format a naive UTC time as RFC 3339.
+pub fn rfc3339(at: NaiveDateTime) -> String {
+    at.and_utc().to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// `serialize_with` for `NaiveDateTime` fields.
+pub fn serialize<S: Serializer>(at: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&rfc3339(*at))
+}
+
+/// `serialize_with` for `Option<NaiveDateTime>` fields.
+pub fn serialize_option<S: Serializer>(
+    at: &Option<NaiveDateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match at {
+        Some(at) => serialize(at, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc3339_is_utc() {
+        let at = chrono::NaiveDate::from_ymd_opt(2026, 10, 18)
+            .unwrap()
+            .and_hms_opt(9, 5, 1)
+            .unwrap();
+        assert_eq!(rfc3339(at), "2026-10-18T09:05:01Z");
+        let at = at + chrono::Duration::microseconds(250);
+        assert_eq!(rfc3339(at), "2026-10-18T09:05:01.000250Z");
+    }
+}
@@ -0,0 +1,119 @@
+//! An `axum::serve` listener that terminates TLS, with the certificate
+//! swapped in place when the files on disk are rotated.
+
+use crate::config::tls::TlsConfig;
+use arc_swap::ArcSwap;
+use axum::serve::Listener;
+use rustls::ServerConfig;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+/// Slow or stalled handshakes are dropped after this long.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct TlsListener {
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    /// Accept on `tcp`, loading the certificate now and watching it for
+    /// rotation per `config.reload_interval`.
+    pub fn bind(tcp: TcpListener, config: &TlsConfig) -> anyhow::Result<Self> {
+        let local_addr = tcp.local_addr()?;
+        let server_config = Arc::new(ArcSwap::new(config.load()?));
+        if !config.reload_interval.is_zero() {
+            spawn_reloader(config.clone(), server_config.clone());
+        }
+
+        // Handshakes run on their own tasks so a slow client can't hold up
+        // the accept loop
+        let (tx, incoming) = mpsc::channel(64);
+        tokio::spawn(async move {
+            while !tx.is_closed() {
+                let (stream, addr) = match tcp.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        tracing::warn!("Failed to accept connection: {}", e);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+                let acceptor = TlsAcceptor::from(server_config.load_full());
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, addr)).await;
+                        }
+                        Ok(Err(e)) => tracing::debug!("TLS handshake with {} failed: {}", addr, e),
+                        Err(_) => tracing::debug!("TLS handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            incoming,
+            local_addr,
+        })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        self.incoming
+            .recv()
+            .await
+            .expect("the accept loop outlives the listener")
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+fn modified(config: &TlsConfig) -> Option<(SystemTime, SystemTime)> {
+    let cert = std::fs::metadata(&config.cert_path).ok()?.modified().ok()?;
+    let key = std::fs::metadata(&config.key_path).ok()?.modified().ok()?;
+    Some((cert, key))
+}
+
+/// Reload the certificate whenever either file changes. A pair that fails
+/// to load (e.g. the key was written before the certificate) keeps the
+/// current one and is retried on the next check.
+fn spawn_reloader(config: TlsConfig, current: Arc<ArcSwap<ServerConfig>>) {
+    tokio::spawn(async move {
+        let mut loaded = modified(&config);
+        let mut ticker = tokio::time::interval(config.reload_interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let seen = modified(&config);
+            if seen.is_none() || seen == loaded {
+                continue;
+            }
+            match config.load() {
+                Ok(server_config) => {
+                    current.store(server_config);
+                    loaded = seen;
+                    tracing::info!(
+                        "Reloaded TLS certificate from {}",
+                        config.cert_path.display()
+                    );
+                }
+                Err(e) => tracing::warn!("Failed to reload TLS certificate: {:#}", e),
+            }
+        }
+    });
+}
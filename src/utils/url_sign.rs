@@ -0,0 +1,132 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Secret used to sign URLs we redirect or proxy to.
+///
+/// `URL_SIGNING_SECRET` is optional and falls back to `JWT_SECRET`, the same
+/// way `POW_SECRET` does.
+pub fn url_signing_secret() -> Vec<u8> {
+    std::env::var("URL_SIGNING_SECRET")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .or_else(|| std::env::var("JWT_SECRET").ok())
+        .unwrap_or_default()
+        .into_bytes()
+}
+
+/// Sign a URL so that endpoints such as `/out` only follow links we generated.
+pub fn sign_url(secret: &[u8], url: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(url.as_bytes());
+    URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+
+pub fn verify_url_signature(secret: &[u8], url: &str, signature: &str) -> bool {
+    let Ok(sig) = URL_SAFE_NO_PAD.decode(signature) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(url.as_bytes());
+    mac.verify_slice(&sig).is_ok()
+}
+
+/// Build the `/img/{signature}/{encoded_url}` path for a remote image.
+pub fn image_proxy_path(secret: &[u8], url: &str) -> String {
+    format!(
+        "/img/{}/{}",
+        sign_url(secret, url),
+        URL_SAFE_NO_PAD.encode(url.as_bytes())
+    )
+}
+
+/// Decode the `{encoded_url}` segment produced by `image_proxy_path`.
+pub fn decode_image_proxy_url(encoded: &str) -> Option<String> {
+    let bytes = URL_SAFE_NO_PAD.decode(encoded).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Token for a one-click unsubscribe link: `{user_id}.{category}.{signature}`.
+/// It never expires, since old emails should still let people opt out.
+pub fn unsubscribe_token(secret: &[u8], user_id: i32, category: &str) -> String {
+    let payload = format!("{}.{}", user_id, category);
+    // Prefixed so a signature can't be reused between purposes
+    let signature = sign_url(secret, &format!("unsubscribe:{}", payload));
+    format!("{}.{}", payload, signature)
+}
+
+/// Check an unsubscribe token and return the user id and category.
+pub fn parse_unsubscribe_token(secret: &[u8], token: &str) -> Option<(i32, String)> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    if !verify_url_signature(secret, &format!("unsubscribe:{}", payload), signature) {
+        return None;
+    }
+    let (user_id, category) = payload.split_once('.')?;
+    Some((user_id.parse().ok()?, category.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsubscribe_token_roundtrip() {
+        let token = unsubscribe_token(b"secret", 42, "digest");
+        assert!(token.starts_with("42.digest."));
+        assert_eq!(
+            parse_unsubscribe_token(b"secret", &token),
+            Some((42, "digest".to_string()))
+        );
+        assert_eq!(parse_unsubscribe_token(b"other", &token), None);
+        let forged = token.replacen("42.", "43.", 1);
+        assert_eq!(parse_unsubscribe_token(b"secret", &forged), None);
+        // An /out signature for the same text is not an unsubscribe token
+        let sig = sign_url(b"secret", "42.digest");
+        assert_eq!(
+            parse_unsubscribe_token(b"secret", &format!("42.digest.{}", sig)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify_roundtrip() {
+        let sig = sign_url(b"secret", "https://example.com/a?b=1");
+        assert!(verify_url_signature(
+            b"secret",
+            "https://example.com/a?b=1",
+            &sig
+        ));
+    }
+
+    #[test]
+    fn test_verify_rejects_tampered_url_or_key() {
+        let sig = sign_url(b"secret", "https://example.com/");
+        assert!(!verify_url_signature(
+            b"secret",
+            "https://evil.example/",
+            &sig
+        ));
+        assert!(!verify_url_signature(
+            b"other",
+            "https://example.com/",
+            &sig
+        ));
+        assert!(!verify_url_signature(
+            b"secret",
+            "https://example.com/",
+            "not base64!"
+        ));
+    }
+
+    #[test]
+    fn test_image_proxy_path_roundtrip() {
+        let url = "https://example.com/cat.png?size=2";
+        let path = image_proxy_path(b"secret", url);
+        let (sig, encoded) = path.trim_start_matches("/img/").split_once('/').unwrap();
+        let decoded = decode_image_proxy_url(encoded).unwrap();
+        assert_eq!(decoded, url);
+        assert!(verify_url_signature(b"secret", &decoded, sig));
+    }
+}
@@ -1,16 +1,113 @@
+use crate::config::websocket::WebSocketConfig;
+use crate::websocket::protocol::ServerMessage;
 use dashmap::DashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc,
 };
-use tokio::sync::mpsc;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::Notify;
 
-pub type WsSender = mpsc::UnboundedSender<String>;
+pub type WsSender = mpsc::Sender<String>;
+
+/// A socket's end of the hub.
+pub struct Subscription {
+    pub conn_id: u64,
+    /// Envelopes to send, in order
+    pub messages: mpsc::Receiver<String>,
+    /// Notified when the socket fell so far behind that the hub dropped it
+    pub overflowed: Arc<Notify>,
+}
+
+/// One socket of a user.
+struct Connection {
+    id: u64,
+    sender: WsSender,
+    /// Messages sent so far, which numbers the next one
+    sent: u64,
+    /// Posts whose channels the socket subscribed to
+    posts: HashSet<i32>,
+    /// Latest stats of each post that didn't fit in the queue
+    stale_stats: HashMap<i32, ServerMessage>,
+    overflowed: Arc<Notify>,
+}
+
+impl Connection {
+    /// Queue `message` for the socket, returning false if it is gone. When
+    /// the queue is full, post stats wait to be replaced by newer ones and
+    /// anything else drops the socket: the client missed a message it can't
+    /// get back.
+    fn send(&mut self, message: &ServerMessage) -> bool {
+        if !self.flush_stale_stats() {
+            return false;
+        }
+        match self.try_send(message) {
+            Ok(()) => true,
+            Err(TrySendError::Closed(_)) => false,
+            Err(TrySendError::Full(_)) => match message.stats_post() {
+                Some(post_id) => {
+                    let message = match self.stale_stats.remove(&post_id) {
+                        Some(older) => message.clone().merge_stats(&older),
+                        None => message.clone(),
+                    };
+                    self.stale_stats.insert(post_id, message);
+                    true
+                }
+                None => {
+                    self.overflowed.notify_one();
+                    false
+                }
+            },
+        }
+    }
+
+    fn try_send(&mut self, message: &ServerMessage) -> Result<(), TrySendError<String>> {
+        self.sender.try_send(message.envelope(self.sent + 1))?;
+        self.sent += 1;
+        Ok(())
+    }
+
+    /// Queue held back stats as far as they fit, returning false if the
+    /// socket has closed.
+    fn flush_stale_stats(&mut self) -> bool {
+        let post_ids: Vec<i32> = self.stale_stats.keys().copied().collect();
+        for post_id in post_ids {
+            let message = self.stale_stats[&post_id].clone();
+            match self.try_send(&message) {
+                Ok(()) => {
+                    self.stale_stats.remove(&post_id);
+                }
+                Err(TrySendError::Full(_)) => return true,
+                Err(TrySendError::Closed(_)) => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Why a socket can't subscribe to a post channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscribeError {
+    /// The socket has closed
+    Closed,
+    /// It already has `MAX_POST_CHANNELS` subscriptions
+    TooMany,
+}
+
+/// Post channels one socket may subscribe to at once.
+pub const MAX_POST_CHANNELS: usize = 50;
 
 #[derive(Clone)]
 pub struct NotificationHub {
-    connections: Arc<DashMap<i32, Vec<(u64, WsSender)>>>,
+    connections: Arc<DashMap<i32, Vec<Connection>>>,
     next_conn_id: Arc<AtomicU64>,
+    send_queue_size: usize,
+    /// Sockets subscribed to each post, as `(user_id, conn_id)`
+    post_channels: Arc<DashMap<i32, Vec<(i32, u64)>>>,
+    /// Posts with watchers whose stats changed since the last broadcast,
+    /// with the net change in score
+    changed_posts: Arc<DashMap<i32, i64>>,
 }
 
 impl Default for NotificationHub {
@@ -21,40 +118,365 @@ impl Default for NotificationHub {
 
 impl NotificationHub {
     pub fn new() -> Self {
+        Self::with_send_queue_size(WebSocketConfig::from_env().send_queue_size)
+    }
+
+    /// A hub queuing at most `send_queue_size` messages per socket.
+    pub fn with_send_queue_size(send_queue_size: usize) -> Self {
         Self {
             connections: Arc::new(DashMap::new()),
             next_conn_id: Arc::new(AtomicU64::new(1)),
+            send_queue_size,
+            post_channels: Arc::new(DashMap::new()),
+            changed_posts: Arc::new(DashMap::new()),
         }
     }
 
-    pub fn subscribe(&self, user_id: i32) -> (u64, mpsc::UnboundedReceiver<String>) {
+    pub fn subscribe(&self, user_id: i32) -> Subscription {
         let conn_id = self.next_conn_id.fetch_add(1, Ordering::Relaxed);
-        let (tx, rx) = mpsc::unbounded_channel();
+        let (tx, rx) = mpsc::channel(self.send_queue_size);
+        let overflowed = Arc::new(Notify::new());
         self.connections
             .entry(user_id)
             .or_default()
-            .push((conn_id, tx));
-        (conn_id, rx)
+            .push(Connection {
+                id: conn_id,
+                sender: tx,
+                sent: 0,
+                posts: HashSet::new(),
+                stale_stats: HashMap::new(),
+                overflowed: overflowed.clone(),
+            });
+        Subscription {
+            conn_id,
+            messages: rx,
+            overflowed,
+        }
     }
 
     pub fn unsubscribe(&self, user_id: i32, conn_id: u64) {
-        if let Some(mut senders) = self.connections.get_mut(&user_id) {
-            senders.retain(|(id, _)| *id != conn_id);
-            if senders.is_empty() {
-                drop(senders);
+        let mut posts = HashSet::new();
+        if let Some(mut connections) = self.connections.get_mut(&user_id) {
+            connections.retain_mut(|conn| {
+                if conn.id == conn_id {
+                    posts = std::mem::take(&mut conn.posts);
+                }
+                conn.id != conn_id
+            });
+            if connections.is_empty() {
+                drop(connections);
                 self.connections.remove(&user_id);
             }
         }
+        for post_id in posts {
+            self.leave_post_channel(post_id, user_id, conn_id);
+        }
+    }
+
+    /// Subscribe a socket to a post's stats.
+    pub fn subscribe_post(
+        &self,
+        user_id: i32,
+        conn_id: u64,
+        post_id: i32,
+    ) -> Result<(), SubscribeError> {
+        {
+            let mut connections = self
+                .connections
+                .get_mut(&user_id)
+                .ok_or(SubscribeError::Closed)?;
+            let conn = connections
+                .iter_mut()
+                .find(|conn| conn.id == conn_id)
+                .ok_or(SubscribeError::Closed)?;
+            if conn.posts.contains(&post_id) {
+                return Ok(());
+            }
+            if conn.posts.len() >= MAX_POST_CHANNELS {
+                return Err(SubscribeError::TooMany);
+            }
+            conn.posts.insert(post_id);
+        }
+        self.post_channels
+            .entry(post_id)
+            .or_default()
+            .push((user_id, conn_id));
+        // The viewer count changed, and the new subscriber wants the stats
+        self.post_changed(post_id, 0);
+        Ok(())
+    }
+
+    pub fn unsubscribe_post(&self, user_id: i32, conn_id: u64, post_id: i32) {
+        let removed = self
+            .connections
+            .get_mut(&user_id)
+            .and_then(|mut connections| {
+                let conn = connections.iter_mut().find(|conn| conn.id == conn_id)?;
+                Some(conn.posts.remove(&post_id))
+            });
+        if removed == Some(true) {
+            self.leave_post_channel(post_id, user_id, conn_id);
+        }
+    }
+
+    fn leave_post_channel(&self, post_id: i32, user_id: i32, conn_id: u64) {
+        if let Some(mut subscribers) = self.post_channels.get_mut(&post_id) {
+            subscribers.retain(|&subscriber| subscriber != (user_id, conn_id));
+            if subscribers.is_empty() {
+                drop(subscribers);
+                self.post_channels.remove(&post_id);
+                return;
+            }
+        }
+        self.post_changed(post_id, 0);
+    }
+
+    /// Note that a post's stats changed, its score by `score_delta`. Only
+    /// watched posts are noted; the next broadcast sends their stats.
+    pub fn post_changed(&self, post_id: i32, score_delta: i64) {
+        if self.post_channels.contains_key(&post_id) {
+            *self.changed_posts.entry(post_id).or_default() += score_delta;
+        }
+    }
+
+    /// The posts noted by `post_changed` since the last call, with their net
+    /// change in score.
+    pub fn take_changed_posts(&self) -> Vec<(i32, i64)> {
+        let post_ids: Vec<i32> = self.changed_posts.iter().map(|e| *e.key()).collect();
+        post_ids
+            .into_iter()
+            .filter_map(|post_id| self.changed_posts.remove(&post_id))
+            .collect()
+    }
+
+    /// How many users are watching a post.
+    pub fn viewer_count(&self, post_id: i32) -> usize {
+        self.post_channels.get(&post_id).map_or(0, |subscribers| {
+            subscribers
+                .iter()
+                .map(|&(user_id, _)| user_id)
+                .collect::<HashSet<_>>()
+                .len()
+        })
+    }
+
+    /// Send to every socket subscribed to a post.
+    pub fn send_to_post(&self, post_id: i32, message: &ServerMessage) {
+        let subscribers = match self.post_channels.get(&post_id) {
+            Some(subscribers) => subscribers.clone(),
+            None => return,
+        };
+        for (user_id, conn_id) in subscribers {
+            self.send_to_connection(user_id, conn_id, message);
+        }
+    }
+
+    /// Drop every connection's channel, so each socket sends a close frame
+    /// and disconnects. Used on shutdown.
+    pub fn close_all(&self) {
+        self.connections.clear();
+        self.post_channels.clear();
+    }
+
+    pub fn send_to_user(&self, user_id: i32, message: &ServerMessage) {
+        self.send_where(user_id, message, |_| true);
     }
 
-    pub fn send_to_user(&self, user_id: i32, message: &str) {
-        if let Some(mut senders) = self.connections.get_mut(&user_id) {
-            // Remove closed channels while sending
-            senders.retain(|(_, sender)| sender.send(message.to_string()).is_ok());
-            if senders.is_empty() {
-                drop(senders);
+    /// Send to one of the user's sockets, e.g. a reply to its client.
+    pub fn send_to_connection(&self, user_id: i32, conn_id: u64, message: &ServerMessage) {
+        self.send_where(user_id, message, |conn| conn.id == conn_id);
+    }
+
+    fn send_where(
+        &self,
+        user_id: i32,
+        message: &ServerMessage,
+        wanted: impl Fn(&Connection) -> bool,
+    ) {
+        let mut gone = Vec::new();
+        if let Some(mut connections) = self.connections.get_mut(&user_id) {
+            // Remove closed and overflowed sockets while sending
+            connections.retain_mut(|conn| {
+                let kept = !wanted(conn) || conn.send(message);
+                if !kept {
+                    gone.push((conn.id, std::mem::take(&mut conn.posts)));
+                }
+                kept
+            });
+            if connections.is_empty() {
+                drop(connections);
                 self.connections.remove(&user_id);
             }
         }
+        for (conn_id, posts) in gone {
+            for post_id in posts {
+                self.leave_post_channel(post_id, user_id, conn_id);
+            }
+        }
+    }
+
+    /// Queue post stats held back from sockets that were behind, as far as
+    /// their queues now have room.
+    pub fn flush_stale_stats(&self) {
+        for mut connections in self.connections.iter_mut() {
+            for conn in connections.iter_mut() {
+                if !conn.stale_stats.is_empty() {
+                    // A closed socket is removed when it unsubscribes
+                    conn.flush_stale_stats();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::protocol::PostStats;
+    use serde_json::{json, Value};
+
+    fn id_of(message: Option<String>) -> Value {
+        serde_json::from_str::<Value>(&message.unwrap()).unwrap()["id"].clone()
+    }
+
+    #[tokio::test]
+    async fn close_all_ends_every_connection() {
+        let hub = NotificationHub::new();
+        let mut first = hub.subscribe(1).messages;
+        let mut second = hub.subscribe(2).messages;
+
+        let hello = ServerMessage::new("hello", json!({}));
+        hub.send_to_user(1, &hello);
+        assert_eq!(
+            first.recv().await.as_deref(),
+            Some(hello.envelope(1).as_str())
+        );
+
+        hub.close_all();
+        assert_eq!(first.recv().await, None);
+        assert_eq!(second.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn each_connection_numbers_its_messages() {
+        let hub = NotificationHub::new();
+        let first = hub.subscribe(1);
+        let (first_id, mut first) = (first.conn_id, first.messages);
+        let mut second = hub.subscribe(1).messages;
+
+        let hello = ServerMessage::new("hello", json!({}));
+        hub.send_to_connection(1, first_id, &ServerMessage::ack(1));
+        hub.send_to_user(1, &hello);
+        assert_eq!(id_of(first.recv().await), 1);
+        assert_eq!(id_of(first.recv().await), 2);
+        assert_eq!(id_of(second.recv().await), 1);
+        assert!(second.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn post_channels_count_viewers_and_coalesce_changes() {
+        let hub = NotificationHub::new();
+        let Subscription {
+            conn_id: first,
+            messages: mut first_rx,
+            ..
+        } = hub.subscribe(1);
+        let second_sub = hub.subscribe(1);
+        let second = second_sub.conn_id;
+        let Subscription {
+            conn_id: third,
+            messages: mut third_rx,
+            ..
+        } = hub.subscribe(2);
+
+        // Unwatched posts are not noted
+        hub.post_changed(10, 1);
+        assert!(hub.take_changed_posts().is_empty());
+
+        hub.subscribe_post(1, first, 10).unwrap();
+        hub.subscribe_post(1, second, 10).unwrap();
+        hub.subscribe_post(2, third, 10).unwrap();
+        assert_eq!(hub.viewer_count(10), 2);
+        hub.post_changed(10, 1);
+        hub.post_changed(10, -2);
+        assert_eq!(hub.take_changed_posts(), vec![(10, -1)]);
+        assert!(hub.take_changed_posts().is_empty());
+
+        hub.send_to_post(10, &ServerMessage::new("hello", json!({})));
+        assert_eq!(id_of(first_rx.recv().await), 1);
+        assert_eq!(id_of(third_rx.recv().await), 1);
+
+        // Closing a socket leaves its channels
+        hub.unsubscribe(2, third);
+        assert_eq!(hub.viewer_count(10), 1);
+        assert_eq!(hub.take_changed_posts(), vec![(10, 0)]);
+        hub.unsubscribe_post(1, first, 10);
+        hub.unsubscribe_post(1, second, 10);
+        assert_eq!(hub.viewer_count(10), 0);
+
+        for post_id in 0..MAX_POST_CHANNELS as i32 {
+            hub.subscribe_post(1, first, post_id).unwrap();
+        }
+        assert_eq!(
+            hub.subscribe_post(1, first, -1),
+            Err(SubscribeError::TooMany)
+        );
+        assert_eq!(hub.subscribe_post(3, 99, 1), Err(SubscribeError::Closed));
+    }
+
+    fn stats(score_delta: i64) -> ServerMessage {
+        ServerMessage::post_stats(&PostStats {
+            post_id: 10,
+            score: 0,
+            score_delta,
+            comment_count: 0,
+            viewer_count: 1,
+        })
+    }
+
+    #[tokio::test]
+    async fn full_queues_hold_only_the_latest_stats() {
+        let hub = NotificationHub::with_send_queue_size(1);
+        let mut sub = hub.subscribe(1);
+
+        hub.send_to_user(1, &stats(1));
+        hub.send_to_user(1, &stats(2));
+        hub.send_to_user(1, &stats(3));
+        let first: Value = serde_json::from_str(&sub.messages.recv().await.unwrap()).unwrap();
+        assert_eq!(first["payload"]["score_delta"], 1);
+        assert!(sub.messages.try_recv().is_err());
+
+        // Once there is room the held stats go out, merged and numbered on
+        hub.flush_stale_stats();
+        let merged: Value = serde_json::from_str(&sub.messages.recv().await.unwrap()).unwrap();
+        assert_eq!(merged["id"], 2);
+        assert_eq!(merged["payload"]["score_delta"], 5);
+        hub.flush_stale_stats();
+        assert!(sub.messages.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn full_queues_drop_sockets_that_would_miss_messages() {
+        let hub = NotificationHub::with_send_queue_size(1);
+        let mut slow = hub.subscribe(1);
+        let mut fast = hub.subscribe(1);
+        hub.subscribe_post(1, slow.conn_id, 10).unwrap();
+
+        let hello = ServerMessage::new("hello", json!({}));
+        hub.send_to_user(1, &hello);
+        assert_eq!(id_of(fast.messages.recv().await), 1);
+        hub.send_to_user(1, &hello);
+
+        // The slow socket is told to close and leaves its channels
+        tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            slow.overflowed.notified(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(id_of(slow.messages.recv().await), 1);
+        assert_eq!(slow.messages.recv().await, None);
+        assert_eq!(hub.viewer_count(10), 0);
+        assert_eq!(id_of(fast.messages.recv().await), 2);
     }
 }
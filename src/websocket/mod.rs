@@ -1,2 +1,3 @@
 pub mod hub;
 pub mod notification;
+pub mod protocol;
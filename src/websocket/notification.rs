@@ -1,51 +1,295 @@
+//! The notification socket at `/ws`.
+//!
+//! Clients authenticate with their access token in one of three ways:
+//! offering it as a subprotocol after `bearer`
+//! (`Sec-WebSocket-Protocol: bearer, <jwt>`), sending an `auth` message (see [`protocol`](crate::websocket::protocol)) first,
+//! or, deprecated because URLs end up in proxy logs, `?token=<jwt>`. A
+//! socket that has not authenticated within the handshake timeout is closed.
+//!
+//! Clients may send a burst of messages, then a few per second; messages
+//! beyond that are answered with a `rate_limited` error and otherwise
+//! ignored. A socket whose send queue fills up is closed with 1013 (try
+//! again later) rather than buffering without bound.
+
+use crate::config::websocket::WebSocketConfig;
 use crate::error::AppError;
+use crate::models::Post;
+use crate::services::presence;
 use crate::utils::jwt::decode_jwt;
-use crate::websocket::hub::NotificationHub;
+use crate::utils::{shutdown, tenant};
+use crate::websocket::hub::{NotificationHub, SubscribeError, MAX_POST_CHANNELS};
+use crate::websocket::protocol::{ClientMessage, ClientRequest, ServerMessage};
 use axum::{
     extract::{
-        ws::{Message, WebSocket},
+        ws::{close_code, CloseFrame, Message, WebSocket},
         Query, WebSocketUpgrade,
     },
-    response::IntoResponse,
+    http::{header, HeaderMap, HeaderValue},
+    response::Response,
     Extension,
 };
 use futures_util::{SinkExt, StreamExt};
+use governor::{Quota, RateLimiter};
+use sea_orm::{DatabaseConnection, EntityTrait};
 use serde::Deserialize;
 
+/// Subprotocol offered just before the token.
+const BEARER_PROTOCOL: &str = "bearer";
+
+/// Largest client message; none of them need more.
+const MAX_MESSAGE_SIZE: usize = 16 * 1024;
+
 #[derive(Deserialize)]
 pub struct WsQuery {
-    pub token: String,
+    /// Deprecated, use the `bearer` subprotocol or an auth message
+    pub token: Option<String>,
 }
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     Query(query): Query<WsQuery>,
+    headers: HeaderMap,
+    Extension(db): Extension<DatabaseConnection>,
     Extension(hub): Extension<NotificationHub>,
-) -> Result<impl IntoResponse, AppError> {
-    let claims = decode_jwt(&query.token).map_err(|_| AppError::Unauthorized)?;
-    let user_id: i32 = claims.sub.parse().map_err(|_| AppError::Unauthorized)?;
+) -> Result<Response, AppError> {
+    let deprecated = query.token.is_some();
+    let user_id = protocol_token(&headers)
+        .or(query.token)
+        .map(|token| authenticate(&token))
+        .transpose()?;
+
+    // The socket outlives the request, so it takes the tenant along
+    let tenant = tenant::current();
+    let config = WebSocketConfig::from_env();
+    let mut response = ws
+        .protocols([BEARER_PROTOCOL])
+        .max_message_size(MAX_MESSAGE_SIZE)
+        .on_upgrade(move |socket| {
+            shutdown::track(async move {
+                let session = handle_socket(socket, user_id, db, hub, config);
+                match tenant {
+                    Some(slug) => tenant::scope(slug, session).await,
+                    None => session.await,
+                }
+            })
+        });
+    if deprecated {
+        response
+            .headers_mut()
+            .insert("deprecation", HeaderValue::from_static("true"));
+    }
+    Ok(response)
+}
+
+/// The token offered after `bearer` in `Sec-WebSocket-Protocol`.
+fn protocol_token(headers: &HeaderMap) -> Option<String> {
+    let protocols = headers
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim);
+    protocols
+        .skip_while(|protocol| *protocol != BEARER_PROTOCOL)
+        .nth(1)
+        .map(str::to_string)
+}
+
+/// The user a token belongs to.
+fn authenticate(token: &str) -> Result<i32, AppError> {
+    let claims = decode_jwt(token).map_err(|_| AppError::Unauthorized)?;
+    let user_id = claims.sub.parse().map_err(|_| AppError::Unauthorized)?;
+    // Impersonated sessions are audited per request, which a socket can't be
+    if claims.act.is_some() {
+        return Err(AppError::Forbidden);
+    }
+    Ok(user_id)
+}
+
+/// Wait for the auth message of a socket opened without a token. Returns
+/// the user and the message's id, or the frame to close the socket with
+/// (`None` if the client went away).
+async fn await_auth(
+    socket: &mut WebSocket,
+    config: WebSocketConfig,
+) -> Result<(i32, Option<u64>), Option<CloseFrame>> {
+    let policy = |reason: &'static str| {
+        Some(CloseFrame {
+            code: close_code::POLICY,
+            reason: reason.into(),
+        })
+    };
+    let first_text = async {
+        while let Some(Ok(msg)) = socket.recv().await {
+            match msg {
+                Message::Text(text) => return Some(text),
+                Message::Close(_) => return None,
+                _ => continue,
+            }
+        }
+        None
+    };
+
+    let text = tokio::select! {
+        text = tokio::time::timeout(config.auth_timeout, first_text) => match text {
+            Ok(Some(text)) => text,
+            Ok(None) => return Err(None),
+            Err(_) => return Err(policy("Authentication timed out")),
+        },
+        _ = shutdown::requested() => return Err(None),
+    };
+    let message = ClientMessage::parse(&text);
+    match message.request {
+        Ok(ClientRequest::Auth { token }) => authenticate(&token)
+            .map(|user_id| (user_id, message.id))
+            .map_err(|e| match e {
+                AppError::Forbidden => policy("Impersonated sessions can't use WebSocket"),
+                _ => policy("Unauthorized"),
+            }),
+        _ => Err(policy("Expected an auth message")),
+    }
+}
+
+/// Reply to a message from the client of socket `conn_id`.
+async fn answer(
+    db: &DatabaseConnection,
+    hub: &NotificationHub,
+    user_id: i32,
+    conn_id: u64,
+    text: &str,
+) {
+    let message = ClientMessage::parse(text);
+    let id = message.id;
+    let reply = match message.request {
+        Ok(ClientRequest::Auth { .. }) => Some(ServerMessage::error(
+            id,
+            "already_authenticated",
+            "This socket is already authenticated",
+        )),
+        Ok(ClientRequest::Ping) => id.map(ServerMessage::ack),
+        Ok(ClientRequest::Subscribe { post_id }) if !visible_post(db, post_id).await => {
+            Some(ServerMessage::error(id, "not_found", "Post not found"))
+        }
+        Ok(ClientRequest::Subscribe { post_id }) => {
+            match hub.subscribe_post(user_id, conn_id, post_id) {
+                Ok(()) => id.map(ServerMessage::ack),
+                Err(SubscribeError::TooMany) => Some(ServerMessage::error(
+                    id,
+                    "too_many_subscriptions",
+                    &format!("At most {} channels per socket", MAX_POST_CHANNELS),
+                )),
+                Err(SubscribeError::Closed) => None,
+            }
+        }
+        Ok(ClientRequest::Unsubscribe { post_id }) => {
+            hub.unsubscribe_post(user_id, conn_id, post_id);
+            id.map(ServerMessage::ack)
+        }
+        Err(error) => Some(error),
+    };
+    if let Some(reply) = reply {
+        hub.send_to_connection(user_id, conn_id, &reply);
+    }
+}
 
-    Ok(ws.on_upgrade(move |socket| handle_socket(socket, user_id, hub)))
+/// Whether a post exists and isn't hidden.
+async fn visible_post(db: &DatabaseConnection, post_id: i32) -> bool {
+    matches!(
+        Post::find_by_id(post_id).one(db).await,
+        Ok(Some(post)) if !post.is_hidden
+    )
 }
 
-async fn handle_socket(socket: WebSocket, user_id: i32, hub: NotificationHub) {
+async fn handle_socket(
+    mut socket: WebSocket,
+    user_id: Option<i32>,
+    db: DatabaseConnection,
+    hub: NotificationHub,
+    config: WebSocketConfig,
+) {
+    let (user_id, auth_message) = match user_id {
+        Some(user_id) => (user_id, None),
+        None => match await_auth(&mut socket, config).await {
+            Ok((user_id, id)) => (user_id, Some(id)),
+            Err(close) => {
+                if close.is_some() {
+                    let _ = socket.send(Message::Close(close)).await;
+                }
+                return;
+            }
+        },
+    };
+
     let (mut ws_sender, mut ws_receiver) = socket.split();
-    let (conn_id, mut rx) = hub.subscribe(user_id);
+    let subscription = hub.subscribe(user_id);
+    let conn_id = subscription.conn_id;
+    if let Some(id) = auth_message {
+        hub.send_to_connection(user_id, conn_id, &ServerMessage::auth_ok(user_id, id));
+    }
 
     tracing::info!("WebSocket connected for user {}", user_id);
 
     let mut send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            if ws_sender.send(Message::Text(msg.into())).await.is_err() {
-                break;
+        let mut rx = subscription.messages;
+        let close = loop {
+            tokio::select! {
+                biased;
+                _ = subscription.overflowed.notified() => {
+                    tracing::info!("Closing WebSocket of user {}: send queue full", user_id);
+                    break CloseFrame {
+                        code: close_code::AGAIN,
+                        reason: "Too slow to keep up".into(),
+                    };
+                }
+                msg = rx.recv() => match msg {
+                    Some(msg) => {
+                        if ws_sender.send(Message::Text(msg.into())).await.is_err() {
+                            return;
+                        }
+                    }
+                    // The hub dropped the channel: the server is shutting down
+                    None => break CloseFrame {
+                        code: close_code::AWAY,
+                        reason: "Server shutting down".into(),
+                    },
+                },
+            }
+        };
+        let _ = ws_sender.send(Message::Close(Some(close))).await;
+    });
+
+    // An open socket keeps the user online
+    let heartbeat_db = db.clone();
+    let heartbeat = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(presence::TOUCH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = presence::touch(&heartbeat_db, user_id).await {
+                tracing::warn!("Failed to update last seen of user {}: {}", user_id, e);
             }
         }
     });
 
+    let replies = hub.clone();
+    let limiter = RateLimiter::direct(
+        Quota::per_second(config.client_messages_per_second)
+            .allow_burst(config.client_message_burst),
+    );
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = ws_receiver.next().await {
-            if let Message::Close(_) = msg {
-                break;
+            match msg {
+                // Still replied to, so a client waiting on its id isn't stuck
+                Message::Text(text) if limiter.check().is_err() => {
+                    let id = ClientMessage::parse(&text).id;
+                    replies.send_to_connection(
+                        user_id,
+                        conn_id,
+                        &ServerMessage::error(id, "rate_limited", "Too many messages"),
+                    );
+                }
+                Message::Text(text) => answer(&db, &replies, user_id, conn_id, &text).await,
+                Message::Close(_) => break,
+                _ => {}
             }
         }
     });
@@ -59,6 +303,7 @@ async fn handle_socket(socket: WebSocket, user_id: i32, hub: NotificationHub) {
         },
     }
 
+    heartbeat.abort();
     hub.unsubscribe(user_id, conn_id);
     let _ = send_task.await;
     let _ = recv_task.await;
@@ -0,0 +1,304 @@
+//! The JSON messages exchanged over `/ws`.
+//!
+//! Every server message is an envelope
+//! `{"v": 1, "type": "...", "id": 1, "payload": {...}}`. `id` counts the
+//! messages sent on the connection, starting at 1, so a gap means the client
+//! missed some and should reload from the REST API. Client messages are
+//! `{"type": "...", "id": ..., "payload": {...}}`; when one carries an `id`,
+//! the server replies to it exactly once, with that id as the reply's
+//! `payload.reply_to`.
+
+use crate::models::NotificationModel;
+use crate::utils::time::rfc3339;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Version of the envelope, sent as `v` in every server message.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// What `post_stats` reports about a post.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostStats {
+    pub post_id: i32,
+    /// Upvotes minus downvotes
+    pub score: i64,
+    pub score_delta: i64,
+    pub comment_count: u64,
+    /// Users with the post's channel open
+    pub viewer_count: usize,
+}
+
+/// A message for the client, numbered as it is sent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerMessage {
+    pub kind: &'static str,
+    pub payload: Value,
+}
+
+impl ServerMessage {
+    pub fn new(kind: &'static str, payload: Value) -> Self {
+        Self { kind, payload }
+    }
+
+    /// A new notification.
+    pub fn notification(n: &NotificationModel) -> Self {
+        Self::new(
+            "notification",
+            json!({
+                "id": n.id,
+                "kind": &n.kind,
+                "message": &n.message,
+                "target_type": &n.target_type,
+                "target_id": n.target_id,
+                "created_at": rfc3339(n.created_at),
+            }),
+        )
+    }
+
+    /// `count` notifications held during quiet hours, sent in one message.
+    pub fn notification_summary(count: u64, unread_count: u64) -> Self {
+        Self::new(
+            "notification_summary",
+            json!({ "count": count, "unread_count": unread_count }),
+        )
+    }
+
+    /// A socket that sent an auth message is now signed in as `user_id`.
+    pub fn auth_ok(user_id: i32, reply_to: Option<u64>) -> Self {
+        Self::new(
+            "auth_ok",
+            json!({ "user_id": user_id, "reply_to": reply_to }),
+        )
+    }
+
+    /// Live stats of a post, sent to its channel at most once per broadcast
+    /// interval. `score_delta` is the change in score since the last one.
+    pub fn post_stats(stats: &PostStats) -> Self {
+        Self::new(
+            "post_stats",
+            json!({
+                "post_id": stats.post_id,
+                "score": stats.score,
+                "score_delta": stats.score_delta,
+                "comment_count": stats.comment_count,
+                "viewer_count": stats.viewer_count,
+            }),
+        )
+    }
+
+    /// A client message was handled.
+    pub fn ack(reply_to: u64) -> Self {
+        Self::new("ack", json!({ "reply_to": reply_to }))
+    }
+
+    /// A client message was refused. `code` is stable for clients to match
+    /// on; `message` is for people.
+    pub fn error(reply_to: Option<u64>, code: &str, message: &str) -> Self {
+        Self::new(
+            "error",
+            json!({ "reply_to": reply_to, "code": code, "message": message }),
+        )
+    }
+
+    /// The post whose stats this is, for `post_stats`. A socket that can't
+    /// keep up gets only the latest stats of each post.
+    pub fn stats_post(&self) -> Option<i32> {
+        if self.kind != "post_stats" {
+            return None;
+        }
+        self.payload["post_id"].as_i64().map(|id| id as i32)
+    }
+
+    /// Newer `post_stats` replacing `older` ones for the same post, keeping
+    /// the score change since the older ones' predecessor.
+    pub fn merge_stats(mut self, older: &ServerMessage) -> Self {
+        let delta = |m: &ServerMessage| m.payload["score_delta"].as_i64().unwrap_or(0);
+        self.payload["score_delta"] = json!(delta(&self) + delta(older));
+        self
+    }
+
+    /// The envelope as sent, numbered `id`.
+    pub fn envelope(&self, id: u64) -> String {
+        json!({
+            "v": PROTOCOL_VERSION,
+            "type": self.kind,
+            "id": id,
+            "payload": &self.payload,
+        })
+        .to_string()
+    }
+}
+
+/// The post of a `post:<id>` channel.
+fn post_channel(channel: &str) -> Option<i32> {
+    channel.strip_prefix("post:")?.parse().ok()
+}
+
+/// A message from the client, before its payload is checked against its
+/// type.
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    #[serde(rename = "type")]
+    kind: String,
+    id: Option<u64>,
+    #[serde(default)]
+    payload: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthPayload {
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChannelPayload {
+    channel: String,
+}
+
+/// What the client asked for.
+#[derive(Debug, PartialEq)]
+pub enum ClientRequest {
+    Auth {
+        token: String,
+    },
+    /// Does nothing; the ack tells the client the connection works
+    Ping,
+    /// Receive `post_stats` for a post, from channel `post:<id>`
+    Subscribe {
+        post_id: i32,
+    },
+    Unsubscribe {
+        post_id: i32,
+    },
+}
+
+/// A client message: its `id`, if it gave one, and the request, or the
+/// error to reply with.
+#[derive(Debug, PartialEq)]
+pub struct ClientMessage {
+    pub id: Option<u64>,
+    pub request: Result<ClientRequest, ServerMessage>,
+}
+
+impl ClientMessage {
+    pub fn parse(text: &str) -> Self {
+        let envelope: Envelope = match serde_json::from_str(text) {
+            Ok(envelope) => envelope,
+            Err(_) => {
+                return Self {
+                    id: None,
+                    request: Err(ServerMessage::error(
+                        None,
+                        "invalid_message",
+                        "Expected {\"type\": ..., \"id\": ..., \"payload\": {...}}",
+                    )),
+                }
+            }
+        };
+
+        let id = envelope.id;
+        let invalid_payload = || ServerMessage::error(id, "invalid_payload", "Invalid payload");
+        let request = match envelope.kind.as_str() {
+            "auth" => serde_json::from_value::<AuthPayload>(envelope.payload)
+                .map(|p| ClientRequest::Auth { token: p.token })
+                .map_err(|_| invalid_payload()),
+            "ping" => Ok(ClientRequest::Ping),
+            "subscribe" | "unsubscribe" => {
+                let post_id = serde_json::from_value::<ChannelPayload>(envelope.payload)
+                    .map_err(|_| invalid_payload())
+                    .and_then(|p| {
+                        post_channel(&p.channel).ok_or_else(|| {
+                            ServerMessage::error(
+                                id,
+                                "unknown_channel",
+                                &format!("Unknown channel: {}", p.channel),
+                            )
+                        })
+                    });
+                post_id.map(|post_id| match envelope.kind.as_str() {
+                    "subscribe" => ClientRequest::Subscribe { post_id },
+                    _ => ClientRequest::Unsubscribe { post_id },
+                })
+            }
+            kind => Err(ServerMessage::error(
+                id,
+                "unknown_type",
+                &format!("Unknown message type: {}", kind),
+            )),
+        };
+        Self { id, request }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelopes_are_versioned_and_numbered() {
+        let message: Value = serde_json::from_str(&ServerMessage::ack(7).envelope(3)).unwrap();
+        assert_eq!(
+            message,
+            json!({ "v": 1, "type": "ack", "id": 3, "payload": { "reply_to": 7 } })
+        );
+    }
+
+    #[test]
+    fn stats_of_a_post_merge() {
+        let stats = |score, score_delta| {
+            ServerMessage::post_stats(&PostStats {
+                post_id: 5,
+                score,
+                score_delta,
+                comment_count: 0,
+                viewer_count: 1,
+            })
+        };
+        let merged = stats(3, 1).merge_stats(&stats(2, 2));
+        assert_eq!(merged.stats_post(), Some(5));
+        assert_eq!(merged.payload["score"], 3);
+        assert_eq!(merged.payload["score_delta"], 3);
+        assert_eq!(ServerMessage::ack(1).stats_post(), None);
+    }
+
+    #[test]
+    fn client_messages_are_checked_against_their_type() {
+        let auth = ClientMessage::parse(r#"{"type": "auth", "id": 1, "payload": {"token": "t"}}"#);
+        assert_eq!(auth.id, Some(1));
+        assert_eq!(
+            auth.request,
+            Ok(ClientRequest::Auth {
+                token: "t".to_string()
+            })
+        );
+
+        let code = |text: &str| {
+            let parsed = ClientMessage::parse(text);
+            let error = parsed.request.unwrap_err();
+            (
+                parsed.id,
+                error.payload["code"].as_str().unwrap().to_string(),
+            )
+        };
+        assert_eq!(code("not json"), (None, "invalid_message".to_string()));
+        assert_eq!(
+            code(r#"{"type": "auth", "id": 2}"#),
+            (Some(2), "invalid_payload".to_string())
+        );
+        assert_eq!(
+            code(r#"{"type": "dance", "id": 3}"#),
+            (Some(3), "unknown_type".to_string())
+        );
+        assert_eq!(
+            code(r#"{"type": "subscribe", "id": 4, "payload": {"channel": "forum:1"}}"#),
+            (Some(4), "unknown_channel".to_string())
+        );
+
+        let subscribe =
+            ClientMessage::parse(r#"{"type": "unsubscribe", "payload": {"channel": "post:42"}}"#);
+        assert_eq!(
+            subscribe.request,
+            Ok(ClientRequest::Unsubscribe { post_id: 42 })
+        );
+    }
+}
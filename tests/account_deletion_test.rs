@@ -0,0 +1,138 @@
+mod common;
+
+use serde_json::Value;
+use xjy::config::auth::DeletedUserContent;
+use xjy::services::account_deletion::AccountDeletionService;
+
+async fn create_post(app: &common::TestApp, token: &str, forum_id: i32) -> i64 {
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Soon orphaned",
+            "content": "Content",
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["id"].as_i64().unwrap()
+}
+
+async fn post_author(app: &common::TestApp, post_id: i64) -> Option<i64> {
+    let resp = app
+        .client
+        .get(app.url(&format!("/posts/{}", post_id)))
+        .send()
+        .await
+        .unwrap();
+    if resp.status() == 404 {
+        return None;
+    }
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["user_id"].as_i64()
+}
+
+#[tokio::test]
+async fn deleted_users_content_moves_to_the_deleted_account() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin) = common::create_test_user(&app, "deleter").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (leaver_id, leaver) = common::create_test_user(&app, "leaver").await;
+    let (removed_id, removed) = common::create_test_user(&app, "removed").await;
+    let slug = common::create_test_forum(&app, &admin).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let first = create_post(&app, &leaver, forum_id).await;
+    let second = create_post(&app, &removed, forum_id).await;
+
+    let delete_account = |password: &'static str| {
+        app.client
+            .delete(app.url("/auth/account"))
+            .bearer_auth(&leaver)
+            .json(&serde_json::json!({ "password": password }))
+            .send()
+    };
+    assert_eq!(
+        delete_account("wrong_password").await.unwrap().status(),
+        400
+    );
+    let resp = delete_account("test_password_123").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&leaver)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+
+    // The post stays, under an account nobody can use
+    let placeholder = post_author(&app, first).await.unwrap();
+    assert_ne!(placeholder, leaver_id as i64);
+    let resp = app
+        .client
+        .get(app.url("/users/[deleted]"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .json(&serde_json::json!({
+            "username": "[deleted]",
+            "email": "imposter@test.com",
+            "password": "test_password_123",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // Admins can delete others; everyone shares the one account
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/users/{}", removed_id)))
+        .bearer_auth(&removed)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/users/{}", removed_id)))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(post_author(&app, second).await, Some(placeholder));
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/users/{}", placeholder)))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn deployments_can_delete_content_with_the_user() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin) = common::create_test_user(&app, "purger").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (user_id, token) = common::create_test_user(&app, "purged").await;
+    let slug = common::create_test_forum(&app, &admin).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let post_id = create_post(&app, &token, forum_id).await;
+
+    AccountDeletionService::new(app.db.clone())
+        .delete(user_id, DeletedUserContent::Delete)
+        .await
+        .unwrap();
+    assert_eq!(post_author(&app, post_id).await, None);
+}
@@ -1,5 +1,6 @@
 mod common;
 
+use sea_orm::{ConnectionTrait, Statement};
 use serde_json::Value;
 
 #[tokio::test]
@@ -23,6 +24,8 @@ async fn get_platform_stats() {
     assert!(body["data"]["total_users"].is_number());
     assert!(body["data"]["total_posts"].is_number());
     assert!(body["data"]["total_comments"].is_number());
+    // No Redis in tests, so no cache metrics
+    assert_eq!(body["data"]["cache"], serde_json::json!([]));
 }
 
 #[tokio::test]
@@ -110,7 +113,7 @@ async fn list_users_pagination() {
     let page2 = body["data"]["items"]
         .as_array()
         .expect("Expected items in page 2");
-    assert!(page2.len() > 0);
+    assert!(!page2.is_empty());
 }
 
 #[tokio::test]
@@ -335,6 +338,186 @@ async fn admin_delete_nonexistent_comment_returns_404() {
     assert_eq!(resp.status(), 404);
 }
 
+#[tokio::test]
+async fn stats_timeseries_buckets_counts() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "admin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (old_id, _) = common::create_test_user(&app, "olduser").await;
+    let (_, user_token) = common::create_test_user(&app, "poster").await;
+
+    // Back-date one signup by three days
+    app.db
+        .execute(Statement::from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            "UPDATE users SET created_at = created_at - INTERVAL '3 days' WHERE id = $1",
+            [old_id.into()],
+        ))
+        .await
+        .unwrap();
+
+    let slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({
+            "title": "Counted post",
+            "content": "Content",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let get = |query: &str| {
+        app.client
+            .get(app.url(&format!("/admin/stats/timeseries{}", query)))
+            .bearer_auth(&admin_token)
+            .send()
+    };
+
+    let resp = get("?metric=signups").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["interval"], "day");
+    let points = body["data"]["points"].as_array().unwrap();
+    assert_eq!(points.len(), 30);
+    let today = chrono::Utc::now().date_naive();
+    assert_eq!(points[29]["bucket"], today.to_string());
+    assert_eq!(points[29]["count"], 2);
+    assert_eq!(points[26]["count"], 1);
+    assert_eq!(points[27]["count"], 0);
+
+    let resp = get("?metric=active_users&interval=week").await.unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let points = body["data"]["points"].as_array().unwrap();
+    assert_eq!(points.len(), 12);
+    // The admin (forum) doesn't count; the poster does
+    assert_eq!(points[11]["count"], 1);
+
+    let from = today - chrono::Duration::days(1);
+    let resp = get(&format!("?metric=posts&from={}&to={}", from, today))
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["points"][0]["count"], 0);
+    assert_eq!(body["data"]["points"][1]["count"], 1);
+
+    for bad in [
+        "?metric=votes",
+        "?metric=posts&interval=hour",
+        "?metric=posts&from=yesterday",
+        "?metric=posts&from=2026-02-01&to=2026-01-01",
+        "?metric=posts&from=2020-01-01&to=2026-01-01",
+    ] {
+        let resp = get(bad).await.unwrap();
+        assert_eq!(resp.status(), 400, "{}", bad);
+    }
+
+    let resp = app
+        .client
+        .get(app.url("/admin/stats/timeseries?metric=posts"))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+}
+
+#[tokio::test]
+async fn export_streams_csv_and_json() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "admin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (mod_id, mod_token) = common::create_test_user(&app, "mod").await;
+    common::make_moderator(&app.db, mod_id).await;
+    let (_, user_token) = common::create_test_user(&app, "exported").await;
+
+    let slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({
+            "title": "=SUM(A1:A2), or so",
+            "content": "Line one\nline two",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let export = |path: &str, token: &str| {
+        app.client
+            .get(app.url(&format!("/admin/export/{}", path)))
+            .bearer_auth(token)
+            .send()
+    };
+
+    let resp = export("users", &admin_token).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "text/csv; charset=utf-8");
+    assert!(resp.headers()["content-disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment; filename=\"users-"));
+    let csv = resp.text().await.unwrap();
+    let lines: Vec<&str> = csv.split("\r\n").collect();
+    assert!(lines[0].starts_with("id,username,email,role,"));
+    assert!(!csv.contains("password"));
+    // Three users plus the header and the trailing line break
+    assert_eq!(lines.len(), 5);
+    assert!(lines[1].contains(",admin,"));
+
+    let resp = export("posts", &admin_token).await.unwrap();
+    let csv = resp.text().await.unwrap();
+    assert!(csv.contains("\"'=SUM(A1:A2), or so\""));
+    assert!(csv.contains("\"Line one\nline two\""));
+
+    let resp = export("posts?format=json", &admin_token).await.unwrap();
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["title"], "=SUM(A1:A2), or so");
+    assert_eq!(body[0]["forum_id"].as_i64(), Some(forum_id as i64));
+
+    let resp = export("reports?format=json", &admin_token).await.unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body, serde_json::json!([]));
+
+    let resp = export("users?format=xml", &admin_token).await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = export("votes", &admin_token).await.unwrap();
+    assert_eq!(resp.status(), 404);
+    let resp = export("users", &mod_token).await.unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/admin/audit-log?actor_id={}", admin_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let exports: Vec<_> = body["data"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|e| e["action"] == "data_exported")
+        .map(|e| e["detail"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        exports,
+        ["reports.json", "posts.json", "posts.csv", "users.csv"]
+    );
+}
+
 #[tokio::test]
 async fn admin_requires_auth() {
     let app = common::spawn_app().await;
@@ -368,3 +551,131 @@ async fn update_nonexistent_user_role_returns_404() {
 
     assert_eq!(resp.status(), 404);
 }
+
+#[tokio::test]
+async fn moderator_can_delete_post_but_not_manage_users() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "admin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (mod_id, mod_token) = common::create_test_user(&app, "mod").await;
+    common::make_moderator(&app.db, mod_id).await;
+    let (user_id, user_token) = common::create_test_user(&app, "user").await;
+
+    let forum_slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &forum_slug).await;
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({
+            "title": "Moderated Post",
+            "content": "Moderator will remove this",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/posts/{}", post_id)))
+        .bearer_auth(&mod_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/users/{}/role", user_id)))
+        .bearer_auth(&mod_token)
+        .json(&serde_json::json!({ "role": "admin" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = app
+        .client
+        .get(app.url("/admin/stats"))
+        .bearer_auth(&mod_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+}
+
+#[tokio::test]
+async fn role_change_invalidates_existing_tokens() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "admin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (user_id, user_token) = common::create_test_user(&app, "target").await;
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/users/{}/role", user_id)))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "role": "moderator" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+}
+
+#[tokio::test]
+async fn force_logout_invalidates_existing_tokens() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "admin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (user_id, user_token) = common::create_test_user(&app, "target").await;
+
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .post(app.url(&format!("/admin/users/{}/logout", user_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+
+    // Admin's own session is untouched
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
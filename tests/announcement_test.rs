@@ -0,0 +1,240 @@
+mod common;
+
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter, Statement};
+use serde_json::Value;
+
+async fn announcement_notifications(app: &common::TestApp, token: &str) -> Vec<Value> {
+    let resp = app
+        .client
+        .get(app.url("/notifications"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|n| n["kind"] == "announcement")
+        .cloned()
+        .collect()
+}
+
+async fn active_titles(app: &common::TestApp, query: &str) -> Vec<String> {
+    let resp = app
+        .client
+        .get(app.url(&format!("/announcements/active{}", query)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["title"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn announcements_notify_targeted_users() {
+    // Queue emails without delivering them
+    std::env::set_var("SMTP_HOST", "127.0.0.1");
+    std::env::set_var("SMTP_PORT", "1");
+    std::env::set_var("SMTP_USERNAME", "mailer@test.com");
+    std::env::set_var("SMTP_PASSWORD", "secret");
+    let app = common::spawn_app().await;
+
+    let (admin_id, admin_token) = common::create_test_user(&app, "announcer").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (_reader_id, reader_token) = common::create_test_user(&app, "reader").await;
+    let (member_id, member_token) = common::create_test_user(&app, "member").await;
+    app.db
+        .execute(Statement::from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            "UPDATE users SET email_verified = FALSE WHERE id = $1",
+            vec![member_id.into()],
+        ))
+        .await
+        .unwrap();
+
+    let slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&member_token)
+        .json(&serde_json::json!({
+            "title": "Member post",
+            "content": "Makes me a member of the forum",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // Regular users can't publish
+    let resp = app
+        .client
+        .post(app.url("/admin/announcements"))
+        .bearer_auth(&reader_token)
+        .json(&serde_json::json!({ "title": "Hi", "body": "Hello" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = app
+        .client
+        .post(app.url("/admin/announcements"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "title": "Hi", "body": "Hello", "expires_at": "soon" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // Everyone but the author is notified, and verified users emailed
+    let resp = app
+        .client
+        .post(app.url("/admin/announcements"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({
+            "title": "Scheduled maintenance",
+            "body": "We'll be down for an hour tonight.",
+            "send_email": true,
+            "expires_at": "2999-01-01T00:00:00Z"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let global_id = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["recipient_count"], 2);
+    assert_eq!(body["data"]["forum_id"], Value::Null);
+
+    let notifications = announcement_notifications(&app, &reader_token).await;
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0]["message"], "Scheduled maintenance");
+    assert_eq!(notifications[0]["target_type"], "announcement");
+    assert_eq!(notifications[0]["target_id"].as_i64(), Some(global_id));
+    assert!(announcement_notifications(&app, &admin_token)
+        .await
+        .is_empty());
+
+    let emails = xjy::models::EmailOutbox::find()
+        .filter(xjy::models::email_outbox::Column::Kind.eq("announcement"))
+        .all(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(emails.len(), 1);
+    assert!(emails[0].to_address.starts_with("reader_"));
+    assert!(emails[0].subject.contains("Scheduled maintenance"));
+
+    // Forum announcements only reach its members
+    let resp = app
+        .client
+        .post(app.url("/admin/announcements"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({
+            "title": "Forum rules updated",
+            "body": "Please reread the rules.",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let forum_announcement_id = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["recipient_count"], 1);
+    assert_eq!(
+        announcement_notifications(&app, &member_token).await.len(),
+        2
+    );
+    assert_eq!(
+        announcement_notifications(&app, &reader_token).await.len(),
+        1
+    );
+    let email_count = xjy::models::EmailOutbox::find()
+        .filter(xjy::models::email_outbox::Column::Kind.eq("announcement"))
+        .count(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(email_count, 1);
+
+    let resp = app
+        .client
+        .post(app.url("/admin/announcements"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "title": "Hi", "body": "Hello", "forum_id": 999999 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // Active listing includes forum announcements only when asked
+    assert_eq!(active_titles(&app, "").await, vec!["Scheduled maintenance"]);
+    assert_eq!(
+        active_titles(&app, &format!("?forum_id={}", forum_id)).await,
+        vec!["Forum rules updated", "Scheduled maintenance"]
+    );
+
+    // Editing doesn't notify again; expired announcements drop out
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/announcements/{}", global_id)))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({
+            "title": "Maintenance finished",
+            "body": "We're back.",
+            "expires_at": "2000-01-01T00:00:00Z"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["title"], "Maintenance finished");
+    assert_eq!(
+        announcement_notifications(&app, &reader_token).await.len(),
+        1
+    );
+    assert!(active_titles(&app, "").await.is_empty());
+
+    let resp = app
+        .client
+        .get(app.url("/admin/announcements"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 2);
+
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/announcements/{}", forum_announcement_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/announcements/{}", forum_announcement_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    assert!(active_titles(&app, &format!("?forum_id={}", forum_id))
+        .await
+        .is_empty());
+}
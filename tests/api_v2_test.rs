@@ -0,0 +1,121 @@
+mod common;
+
+use serde_json::Value;
+
+fn v2_url(app: &common::TestApp, path: &str) -> String {
+    format!("{}/api/v2{}", app.addr, path)
+}
+
+#[tokio::test]
+async fn test_v2_uses_created_and_no_content() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "v2_user").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let post = serde_json::json!({
+        "forum_id": forum_id,
+        "title": "Versioned",
+        "content": "Created twice",
+    });
+
+    // v1 is unchanged
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .json(&post)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .post(v2_url(&app, "/posts"))
+        .bearer_auth(&token)
+        .json(&post)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+
+    // Replayed retries keep the v2 status
+    for replayed in [false, true] {
+        let resp = app
+            .client
+            .post(v2_url(&app, "/comments"))
+            .bearer_auth(&token)
+            .header("Idempotency-Key", format!("v2-comment-{}", post_id))
+            .json(&serde_json::json!({ "post_id": post_id, "content": "Hi" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 201);
+        assert_eq!(resp.headers().contains_key("idempotent-replayed"), replayed);
+    }
+
+    // Reads and other writes keep 200
+    let resp = app
+        .client
+        .get(v2_url(&app, &format!("/posts/{}", post_id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .delete(v2_url(&app, &format!("/posts/{}", post_id)))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+    assert!(resp.headers().get("content-type").is_none());
+    assert!(resp.bytes().await.unwrap().is_empty());
+
+    let resp = app
+        .client
+        .get(v2_url(&app, &format!("/posts/{}", post_id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_v2_reports_duplicates_as_conflicts() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "v2_admin").await;
+    common::make_admin(&app.db, user_id).await;
+    let forum = serde_json::json!({
+        "name": "Versioned forum",
+        "slug": format!("v2-forum-{}", user_id),
+        "description": "Created once",
+    });
+
+    let resp = app
+        .client
+        .post(v2_url(&app, "/forums"))
+        .bearer_auth(&token)
+        .json(&forum)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let resp = app
+        .client
+        .post(v2_url(&app, "/forums"))
+        .bearer_auth(&token)
+        .json(&forum)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["error"].is_string());
+}
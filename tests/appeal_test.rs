@@ -0,0 +1,356 @@
+mod common;
+
+use sea_orm::EntityTrait;
+use serde_json::Value;
+
+async fn post_and_report(
+    app: &common::TestApp,
+    author_token: &str,
+    reporter_token: &str,
+    forum_id: i32,
+) -> i64 {
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(author_token)
+        .json(&serde_json::json!({
+            "title": "Reported post",
+            "content": "Content",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+
+    let resp = app
+        .client
+        .post(app.url("/reports"))
+        .bearer_auth(reporter_token)
+        .json(&serde_json::json!({
+            "target_type": "post",
+            "target_id": post_id,
+            "reason": "harassment"
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["id"].as_i64().unwrap()
+}
+
+async fn resolve_report(app: &common::TestApp, token: &str, report_id: i64, action: &str) {
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/reports/{}/resolve", report_id)))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "action": action }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+async fn login(app: &common::TestApp, user_id: i32) -> String {
+    let user = xjy::models::User::find_by_id(user_id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({
+            "username": user.username,
+            "password": "test_password_123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["token"].as_str().unwrap().to_string()
+}
+
+async fn create_appeal(app: &common::TestApp, token: &str, body: Value) -> reqwest::Response {
+    app.client
+        .post(app.url("/appeals"))
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn banned_user_appeal_is_accepted_and_unbanned() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "appealadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (mod_id, mod_token) = common::create_test_user(&app, "appealmod").await;
+    common::make_moderator(&app.db, mod_id).await;
+    let (author_id, author_token) = common::create_test_user(&app, "appellant").await;
+    let (_, reporter_token) = common::create_test_user(&app, "appealreporter").await;
+
+    let slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let report_id = post_and_report(&app, &author_token, &reporter_token, forum_id).await;
+    resolve_report(&app, &mod_token, report_id, "ban_user").await;
+
+    // Banned users can't use the site but can still reach their appeals
+    let token = login(&app, author_id).await;
+    let resp = app
+        .client
+        .get(app.url("/notifications"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = create_appeal(&app, &token, serde_json::json!({ "reason": "" })).await;
+    assert_eq!(resp.status(), 400);
+
+    let resp = create_appeal(
+        &app,
+        &token,
+        serde_json::json!({ "reason": "It was satire, not abuse" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let appeal_id = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["status"], "pending");
+
+    let resp = create_appeal(
+        &app,
+        &token,
+        serde_json::json!({ "reason": "Please, again" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 409);
+
+    // Appeals are reviewed by admins, not moderators
+    let resp = app
+        .client
+        .get(app.url("/admin/appeals"))
+        .bearer_auth(&mod_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = app
+        .client
+        .get(app.url("/mod/queue?type=appeal"))
+        .bearer_auth(&mod_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = app
+        .client
+        .get(app.url("/mod/queue?type=appeal"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 1);
+    assert_eq!(
+        body["data"]["items"][0]["item_id"].as_i64(),
+        Some(appeal_id)
+    );
+    assert_eq!(body["data"]["items"][0]["target_type"], "user");
+
+    let resp = app
+        .client
+        .post(app.url(&format!("/admin/appeals/{}/comments", appeal_id)))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "body": "Looks like a misunderstanding" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/admin/appeals/{}", appeal_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["appeal"]["reason"], "It was satire, not abuse");
+    assert_eq!(body["data"]["action"], Value::Null);
+    assert_eq!(
+        body["data"]["comments"][0]["body"],
+        "Looks like a misunderstanding"
+    );
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/appeals/{}/resolve", appeal_id)))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "decision": "maybe" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/appeals/{}/resolve", appeal_id)))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "decision": "accept", "note": "Ban lifted" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["status"], "accepted");
+
+    // Unbanned: the same token works again and the decision is waiting
+    let resp = app
+        .client
+        .get(app.url("/notifications"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let decided: Vec<_> = body["data"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|n| n["kind"] == "appeal_decided")
+        .collect();
+    assert_eq!(decided.len(), 1);
+    assert_eq!(
+        decided[0]["message"],
+        "Your appeal was accepted: Ban lifted"
+    );
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/appeals/{}/resolve", appeal_id)))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "decision": "decline" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn warning_appeal_can_be_declined() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "appealadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (author_id, author_token) = common::create_test_user(&app, "warned").await;
+    let (_, reporter_token) = common::create_test_user(&app, "appealreporter").await;
+
+    let slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let report_id = post_and_report(&app, &author_token, &reporter_token, forum_id).await;
+    resolve_report(&app, &admin_token, report_id, "warn_user").await;
+    let other_report = post_and_report(&app, &author_token, &reporter_token, forum_id).await;
+    resolve_report(&app, &admin_token, other_report, "hide_content").await;
+
+    let actions = xjy::models::ModerationAction::find()
+        .all(&app.db)
+        .await
+        .unwrap();
+    let warning = actions.iter().find(|a| a.action == "warn_user").unwrap();
+    let hidden = actions.iter().find(|a| a.action == "hide_content").unwrap();
+    assert_eq!(warning.user_id, author_id);
+
+    // Without a ban there's nothing to appeal unless an action is named
+    let resp = create_appeal(&app, &author_token, serde_json::json!({ "reason": "Why?" })).await;
+    assert_eq!(resp.status(), 400);
+
+    let resp = create_appeal(
+        &app,
+        &author_token,
+        serde_json::json!({ "moderation_action_id": hidden.id, "reason": "Why?" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 400);
+
+    let resp = create_appeal(
+        &app,
+        &reporter_token,
+        serde_json::json!({ "moderation_action_id": warning.id, "reason": "Not mine" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 404);
+
+    let resp = create_appeal(
+        &app,
+        &author_token,
+        serde_json::json!({ "moderation_action_id": warning.id, "reason": "I was quoting" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let appeal_id = body["data"]["id"].as_i64().unwrap();
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/admin/appeals/{}", appeal_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["action"]["action"], "warn_user");
+    assert_eq!(
+        body["data"]["action"]["report_id"].as_i64(),
+        Some(report_id)
+    );
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/appeals/{}/resolve", appeal_id)))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "decision": "decline" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // A decided action can't be appealed again
+    let resp = create_appeal(
+        &app,
+        &author_token,
+        serde_json::json!({ "moderation_action_id": warning.id, "reason": "Again" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 409);
+
+    let resp = app
+        .client
+        .get(app.url("/appeals"))
+        .bearer_auth(&author_token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"][0]["status"], "declined");
+
+    let resp = app
+        .client
+        .get(app.url("/admin/appeals?status=declined"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 1);
+}
@@ -89,6 +89,36 @@ async fn register_duplicate_email_fails() {
     assert!(body["error"].is_string());
 }
 
+#[tokio::test]
+async fn register_reports_invalid_fields() {
+    let app = common::spawn_app().await;
+
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .json(&serde_json::json!({
+            "username": "ab",
+            "email": "not-an-email",
+            "password": "password_123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["fields"],
+        serde_json::json!({
+            "email": ["must be a valid email address"],
+            "username": ["must be between 3 and 50 characters"],
+        })
+    );
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("username: must be between 3 and 50 characters"));
+}
+
 #[tokio::test]
 async fn login_wrong_password_fails() {
     let app = common::spawn_app().await;
@@ -117,6 +147,17 @@ async fn change_password() {
     let app = common::spawn_app().await;
     let (_user_id, token) = common::create_test_user(&app, "dave").await;
 
+    // Username has a counter suffix, get it from /me first
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let actual_username = body["data"]["username"].as_str().unwrap().to_string();
+
     let resp = app
         .client
         .put(app.url("/auth/password"))
@@ -130,8 +171,7 @@ async fn change_password() {
         .unwrap();
     assert_eq!(resp.status(), 200);
 
-    // Login with new password
-    // Note: Username now has counter suffix, get it from /me first
+    // Tokens issued before the change are no longer accepted
     let resp = app
         .client
         .get(app.url("/auth/me"))
@@ -139,9 +179,9 @@ async fn change_password() {
         .send()
         .await
         .unwrap();
-    let body: Value = resp.json().await.unwrap();
-    let actual_username = body["data"]["username"].as_str().unwrap();
+    assert_eq!(resp.status(), 401);
 
+    // Login with new password
     let resp = app
         .client
         .post(app.url("/auth/login"))
@@ -0,0 +1,239 @@
+mod common;
+
+use sea_orm::{ConnectionTrait, Statement};
+use serde_json::Value;
+use xjy::services::badge::BadgeAwarder;
+
+async fn username_of(app: &common::TestApp, token: &str) -> String {
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["username"].as_str().unwrap().to_string()
+}
+
+async fn profile_badges(app: &common::TestApp, token: &str) -> Vec<String> {
+    let username = username_of(app, token).await;
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}", username)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["badges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| b["slug"].as_str().unwrap().to_string())
+        .collect()
+}
+
+async fn execute(app: &common::TestApp, sql: String) {
+    app.db
+        .execute(Statement::from_string(app.db.get_database_backend(), sql))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn builtin_badges_are_awarded_by_their_rules() {
+    let app = common::spawn_app().await;
+    let (author_id, author) = common::create_test_user(&app, "badged").await;
+    common::make_admin(&app.db, author_id).await;
+    let (veteran_id, veteran) = common::create_test_user(&app, "veteran").await;
+    let (_, newcomer) = common::create_test_user(&app, "newcomer").await;
+    let slug = common::create_test_forum(&app, &author).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&author)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "My first post",
+            "content": "Content",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    execute(
+        &app,
+        format!(
+            "UPDATE posts SET upvotes = 100 WHERE user_id = {}",
+            author_id
+        ),
+    )
+    .await;
+    execute(
+        &app,
+        format!(
+            "UPDATE users SET created_at = '2020-01-01 00:00:00' WHERE id = {}",
+            veteran_id
+        ),
+    )
+    .await;
+
+    let awarder = BadgeAwarder::new(app.db.clone());
+    assert_eq!(awarder.award_all().await.unwrap(), 3);
+    // Already held badges aren't awarded again
+    assert_eq!(awarder.award_all().await.unwrap(), 0);
+
+    assert_eq!(
+        profile_badges(&app, &author).await,
+        vec!["first-post", "upvotes-100"]
+    );
+    assert_eq!(
+        profile_badges(&app, &veteran).await,
+        vec!["one-year-member"]
+    );
+    assert!(profile_badges(&app, &newcomer).await.is_empty());
+
+    let resp = app.client.get(app.url("/badges")).send().await.unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let badges = body["data"].as_array().unwrap();
+    assert_eq!(badges.len(), 3);
+    assert!(badges.iter().all(|b| b["is_builtin"] == true));
+
+    // Built-in badges can't be awarded by hand
+    let resp = app
+        .client
+        .post(app.url(&format!("/admin/users/{}/badges", veteran_id)))
+        .bearer_auth(&author)
+        .json(&serde_json::json!({ "badge_id": badges[0]["id"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn admins_award_and_revoke_custom_badges() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin) = common::create_test_user(&app, "badgeadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (user_id, user) = common::create_test_user(&app, "honoree").await;
+
+    let create = serde_json::json!({
+        "slug": "bug-hunter",
+        "name": "Bug Hunter",
+        "description": "Reported a security issue",
+    });
+    let resp = app
+        .client
+        .post(app.url("/admin/badges"))
+        .bearer_auth(&user)
+        .json(&create)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = app
+        .client
+        .post(app.url("/admin/badges"))
+        .bearer_auth(&admin)
+        .json(&create)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let badge_id = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["is_builtin"], false);
+
+    let resp = app
+        .client
+        .post(app.url("/admin/badges"))
+        .bearer_auth(&admin)
+        .json(&create)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+    // Slugs of built-in badges are reserved even before they exist
+    let resp = app
+        .client
+        .post(app.url("/admin/badges"))
+        .bearer_auth(&admin)
+        .json(&serde_json::json!({ "slug": "first-post", "name": "Imposter" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+
+    let award = |badge_id: i64| {
+        app.client
+            .post(app.url(&format!("/admin/users/{}/badges", user_id)))
+            .bearer_auth(&admin)
+            .json(&serde_json::json!({ "badge_id": badge_id }))
+            .send()
+    };
+    let resp = award(badge_id).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"], "Badge awarded");
+    let resp = award(badge_id).await.unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"], "User already holds the badge");
+    assert_eq!(award(badge_id + 1000).await.unwrap().status(), 404);
+
+    assert_eq!(profile_badges(&app, &user).await, vec!["bug-hunter"]);
+    let resp = app
+        .client
+        .get(app.url("/notifications"))
+        .bearer_auth(&user)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let awarded: Vec<&Value> = body["data"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|n| n["kind"] == "badge_awarded")
+        .collect();
+    assert_eq!(awarded.len(), 1);
+    assert_eq!(awarded[0]["target_id"], badge_id);
+
+    let revoke = format!("/admin/users/{}/badges/{}", user_id, badge_id);
+    let resp = app
+        .client
+        .delete(app.url(&revoke))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(profile_badges(&app, &user).await.is_empty());
+    let resp = app
+        .client
+        .delete(app.url(&revoke))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    // Deleting a badge takes it from its holders
+    assert_eq!(award(badge_id).await.unwrap().status(), 200);
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/badges/{}", badge_id)))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(profile_badges(&app, &user).await.is_empty());
+    let resp = app.client.get(app.url("/badges")).send().await.unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"].as_array().unwrap().is_empty());
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use serde_json::Value;
+
+async fn error_of(resp: reqwest::Response) -> (u16, String) {
+    let status = resp.status().as_u16();
+    assert!(resp.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("application/json"));
+    let body: Value = resp.json().await.unwrap();
+    (status, body["error"].as_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn test_body_limits_and_rejections_use_the_error_body() {
+    std::env::set_var("BODY_LIMIT_AUTH", "2048");
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "limits").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let long = "x".repeat(8 * 1024);
+
+    // Small limit for auth
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({ "username": long, "password": "password_123" }))
+        .send()
+        .await
+        .unwrap();
+    let (status, error) = error_of(resp).await;
+    assert_eq!(status, 413);
+    assert_eq!(error, "Request body too large");
+
+    // The same body fits the limit for posts
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "forum_id": forum_id, "title": "Long", "content": long }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .header("content-type", "application/json")
+        .body("{\"title\": ")
+        .send()
+        .await
+        .unwrap();
+    let (status, error) = error_of(resp).await;
+    assert_eq!(status, 400);
+    assert!(error.contains("JSON"), "{}", error);
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "forum_id": forum_id }))
+        .send()
+        .await
+        .unwrap();
+    let (status, error) = error_of(resp).await;
+    assert_eq!(status, 422);
+    assert!(error.contains("title"), "{}", error);
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .body("title=Form")
+        .send()
+        .await
+        .unwrap();
+    let (status, _) = error_of(resp).await;
+    assert_eq!(status, 415);
+}
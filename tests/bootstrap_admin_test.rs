@@ -0,0 +1,72 @@
+mod common;
+
+use xjy::services::bootstrap_admin::{ensure_bootstrap_admin, BootstrapAdminConfig};
+
+async fn role_of(app: &common::TestApp, token: &str) -> String {
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    body["data"]["role"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_admin_from_env_is_promoted_idempotently() {
+    let app = common::spawn_app().await;
+    let (_, token) = common::create_test_user(&app, "founder").await;
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let me: serde_json::Value = resp.json().await.unwrap();
+    let email = me["data"]["email"].as_str().unwrap().to_string();
+
+    // A password is required alongside the email
+    std::env::set_var("ADMIN_EMAIL", &email);
+    std::env::remove_var("ADMIN_PASSWORD");
+    assert!(BootstrapAdminConfig::from_env().is_err());
+    std::env::set_var("ADMIN_PASSWORD", "short");
+    assert!(BootstrapAdminConfig::from_env().is_err());
+
+    std::env::set_var("ADMIN_PASSWORD", "bootstrap-admin-password");
+    ensure_bootstrap_admin(&app.db).await.unwrap();
+    assert_eq!(role_of(&app, &token).await, "admin");
+
+    // Running again changes nothing, and an existing account keeps its
+    // own password
+    ensure_bootstrap_admin(&app.db).await.unwrap();
+    assert_eq!(role_of(&app, &token).await, "admin");
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({
+            "username": me["data"]["username"],
+            "password": "bootstrap-admin-password"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+
+    // With an admin in place, another ADMIN_EMAIL is not created
+    std::env::set_var("ADMIN_EMAIL", "second_admin@example.com");
+    ensure_bootstrap_admin(&app.db).await.unwrap();
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({
+            "username": "admin",
+            "password": "bootstrap-admin-password"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+}
@@ -0,0 +1,207 @@
+mod common;
+
+use axum::{extract::State, routing::post, Form, Json, Router};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Stand-in for the provider's `siteverify`: accepts the response "good"
+/// and records every form it is sent.
+#[derive(Clone, Default)]
+struct MockSiteVerify {
+    requests: Arc<Mutex<Vec<HashMap<String, String>>>>,
+}
+
+async fn siteverify(
+    State(mock): State<MockSiteVerify>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Json<Value> {
+    let success = form.get("response").map(String::as_str) == Some("good");
+    mock.requests.lock().unwrap().push(form);
+    if success {
+        Json(serde_json::json!({ "success": true }))
+    } else {
+        Json(serde_json::json!({
+            "success": false,
+            "error-codes": ["invalid-input-response"]
+        }))
+    }
+}
+
+async fn spawn_mock() -> (MockSiteVerify, String) {
+    let mock = MockSiteVerify::default();
+    let app = Router::new()
+        .route("/siteverify", post(siteverify))
+        .with_state(mock.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (mock, format!("http://{}/siteverify", addr))
+}
+
+async fn register(app: &common::TestApp, username: &str, captcha_token: Option<&str>) -> Value {
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .json(&serde_json::json!({
+            "username": username,
+            "email": format!("{}@test.com", username),
+            "password": "test_password_123",
+            "captcha_token": captcha_token,
+        }))
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status().as_u16();
+    let mut body: Value = resp.json().await.unwrap();
+    body["status"] = status.into();
+    body
+}
+
+async fn login(
+    app: &common::TestApp,
+    username: &str,
+    password: &str,
+    captcha_token: Option<&str>,
+) -> (u16, Value) {
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({
+            "username": username,
+            "password": password,
+            "captcha_token": captcha_token,
+        }))
+        .send()
+        .await
+        .unwrap();
+    (resp.status().as_u16(), resp.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn configured_actions_require_captcha() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "capadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (_, user_token) = common::create_test_user(&app, "capuser").await;
+    let slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({
+            "title": "Captcha post",
+            "content": "Content",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+
+    // Read on every request; this binary runs on its own so other tests
+    // keep CAPTCHA off.
+    let (mock, url) = spawn_mock().await;
+    std::env::set_var("CAPTCHA_PROVIDER", "hcaptcha");
+    std::env::set_var("CAPTCHA_SECRET", "test-captcha-secret");
+    std::env::set_var("CAPTCHA_SITE_KEY", "test-site-key");
+    std::env::set_var("CAPTCHA_VERIFY_URL", &url);
+    std::env::set_var("CAPTCHA_REQUIRED_ACTIONS", "register,login,report");
+    std::env::set_var("CAPTCHA_LOGIN_AFTER_FAILURES", "2");
+
+    // The policy endpoint tells clients which actions need one
+    let resp = app
+        .client
+        .get(app.url("/pow/challenge"))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["captcha"]["provider"], "hcaptcha");
+    assert_eq!(body["data"]["captcha"]["site_key"], "test-site-key");
+    assert_eq!(body["data"]["captcha"]["login_required"], true);
+    let actions = body["data"]["actions"].as_array().unwrap();
+    let captcha_required = |name: &str| {
+        actions.iter().find(|a| a["action"] == name).unwrap()["captcha_required"].clone()
+    };
+    assert_eq!(captcha_required("register"), true);
+    assert_eq!(captcha_required("report"), true);
+    assert_eq!(captcha_required("create_post"), false);
+
+    // Registration
+    let body = register(&app, "capnew", None).await;
+    assert_eq!(body["status"], 400);
+    assert!(body["error"].as_str().unwrap().contains("captcha_token"));
+    let body = register(&app, "capnew", Some("bad")).await;
+    assert_eq!(body["status"], 400);
+    assert_eq!(body["error"], "Captcha verification failed");
+    let body = register(&app, "capnew", Some("good")).await;
+    assert_eq!(body["status"], 200);
+    {
+        let requests = mock.requests.lock().unwrap();
+        let last = requests.last().unwrap();
+        assert_eq!(last["secret"], "test-captcha-secret");
+        assert_eq!(last["sitekey"], "test-site-key");
+    }
+
+    // Login only asks once the username has failed twice
+    let (status, _) = login(&app, "capnew", "wrong_password_1", None).await;
+    assert_eq!(status, 401);
+    let (status, _) = login(&app, "capnew", "wrong_password_2", None).await;
+    assert_eq!(status, 401);
+    let (status, body) = login(&app, "capnew", "test_password_123", None).await;
+    assert_eq!(status, 400);
+    assert!(body["error"].as_str().unwrap().contains("captcha_token"));
+    let (status, _) = login(&app, "capnew", "test_password_123", Some("bad")).await;
+    assert_eq!(status, 400);
+    let (status, _) = login(&app, "capnew", "test_password_123", Some("good")).await;
+    assert_eq!(status, 200);
+    // A successful login resets the count
+    let (status, _) = login(&app, "capnew", "test_password_123", None).await;
+    assert_eq!(status, 200);
+
+    // Reports
+    let report = |captcha_token: Option<&'static str>| {
+        app.client
+            .post(app.url("/reports"))
+            .bearer_auth(&user_token)
+            .json(&serde_json::json!({
+                "target_type": "post",
+                "target_id": post_id,
+                "reason": "spam",
+                "captcha_token": captcha_token,
+            }))
+            .send()
+    };
+    assert_eq!(report(None).await.unwrap().status(), 400);
+    assert_eq!(report(Some("good")).await.unwrap().status(), 200);
+
+    // Actions not listed are left alone
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({
+            "title": "No captcha needed",
+            "content": "Content",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    for var in [
+        "CAPTCHA_PROVIDER",
+        "CAPTCHA_SECRET",
+        "CAPTCHA_SITE_KEY",
+        "CAPTCHA_VERIFY_URL",
+        "CAPTCHA_REQUIRED_ACTIONS",
+        "CAPTCHA_LOGIN_AFTER_FAILURES",
+    ] {
+        std::env::remove_var(var);
+    }
+}
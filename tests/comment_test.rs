@@ -160,3 +160,329 @@ async fn update_and_delete_comment() {
         .unwrap();
     assert_eq!(resp.status(), 200);
 }
+
+#[tokio::test]
+async fn edit_records_revision_visible_to_moderators() {
+    let app = common::spawn_app().await;
+    let (token, post_id) = setup(&app).await;
+
+    let resp = app
+        .client
+        .post(app.url("/comments"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "post_id": post_id,
+            "content": "First draft"
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let comment_id = body["data"]["id"].as_i64().unwrap();
+    assert!(body["data"]["edited_at"].is_null());
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/comments/{}", comment_id)))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "content": "Second draft" }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"]["edited_at"].is_string());
+
+    // Regular users cannot see edit history
+    let (_, user_token) = common::create_test_user(&app, "revisionreader").await;
+    let resp = app
+        .client
+        .get(app.url(&format!("/comments/{}/revisions", comment_id)))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let (mod_id, mod_token) = common::create_test_user(&app, "revisionmod").await;
+    common::make_moderator(&app.db, mod_id).await;
+    let resp = app
+        .client
+        .get(app.url(&format!("/comments/{}/revisions", comment_id)))
+        .bearer_auth(&mod_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let revisions = body["data"].as_array().unwrap();
+    assert_eq!(revisions.len(), 1);
+    assert_eq!(revisions[0]["content"], "First draft");
+}
+
+#[tokio::test]
+async fn get_comment_with_context() {
+    let app = common::spawn_app().await;
+    let (token, post_id) = setup(&app).await;
+
+    let mut parent_id: Option<i64> = None;
+    let mut ids = Vec::new();
+    for i in 0..3 {
+        let resp = app
+            .client
+            .post(app.url("/comments"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({
+                "post_id": post_id,
+                "parent_id": parent_id,
+                "content": format!("Level {}", i)
+            }))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        let id = body["data"]["id"].as_i64().unwrap();
+        ids.push(id);
+        parent_id = Some(id);
+    }
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/comments/{}", ids[1])))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["comment"]["id"], ids[1]);
+    let ancestors = body["data"]["ancestors"].as_array().unwrap();
+    assert_eq!(ancestors.len(), 1);
+    assert_eq!(ancestors[0]["id"], ids[0]);
+    let children = body["data"]["children"].as_array().unwrap();
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0]["id"], ids[2]);
+    assert_eq!(body["data"]["children_total"], 1);
+
+    let resp = app
+        .client
+        .get(app.url("/comments/999999"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn pinned_comment_is_listed_first() {
+    let app = common::spawn_app().await;
+    let (token, post_id) = setup(&app).await;
+
+    let mut ids = Vec::new();
+    for i in 0..2 {
+        let resp = app
+            .client
+            .post(app.url("/comments"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({
+                "post_id": post_id,
+                "content": format!("Comment {}", i)
+            }))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        ids.push(body["data"]["id"].as_i64().unwrap());
+    }
+
+    // Another user cannot pin on someone else's post
+    let (_, other_token) = common::create_test_user(&app, "pinoutsider").await;
+    let resp = app
+        .client
+        .put(app.url(&format!("/posts/{}/pin-comment/{}", post_id, ids[1])))
+        .bearer_auth(&other_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/posts/{}/pin-comment/{}", post_id, ids[1])))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["pinned_comment_id"], ids[1]);
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/posts/{}/comments", post_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"][0]["id"], ids[1]);
+
+    // Calling again unpins
+    let resp = app
+        .client
+        .put(app.url(&format!("/posts/{}/pin-comment/{}", post_id, ids[1])))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"]["pinned_comment_id"].is_null());
+}
+
+#[tokio::test]
+async fn quote_reply_keeps_an_excerpt_of_the_quoted_comment() {
+    let app = common::spawn_app().await;
+    let (token, post_id) = setup(&app).await;
+
+    let create = |content: &str, quoted: Option<i64>| {
+        app.client
+            .post(app.url("/comments"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({
+                "post_id": post_id,
+                "content": content,
+                "quoted_comment_id": quoted,
+            }))
+            .send()
+    };
+    let resp = create("The **original** <point>", None).await.unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let quoted_id = body["data"]["id"].as_i64().unwrap();
+    assert!(body["data"]["quote"].is_null());
+
+    let resp = create("Agreed", Some(quoted_id)).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let reply_id = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["quote"]["comment_id"], quoted_id);
+    assert_eq!(body["data"]["quote"]["excerpt"], "The original");
+    let html = body["data"]["content_html"].as_str().unwrap();
+    assert!(html.starts_with(&format!(
+        "<blockquote class=\"quote\"><p>The original</p><p class=\"quote-source\"><a href=\"/posts/{}#comment-{}\">",
+        post_id, quoted_id
+    )));
+    assert!(html.ends_with("<p>Agreed</p>"));
+
+    // Unknown comments can't be quoted
+    let resp = create("Huh", Some(999_999)).await.unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // The excerpt outlives edits and deletion of the quoted comment
+    let resp = app
+        .client
+        .delete(app.url(&format!("/comments/{}", quoted_id)))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .get(app.url(&format!("/posts/{}/comments", post_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let reply = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["id"] == reply_id)
+        .unwrap();
+    assert!(reply["quote"]["comment_id"].is_null());
+    assert_eq!(reply["quote"]["excerpt"], "The original");
+    assert!(reply["content_html"]
+        .as_str()
+        .unwrap()
+        .starts_with("<blockquote class=\"quote\"><p>The original</p></blockquote>"));
+}
+
+#[tokio::test]
+async fn authors_close_their_threads_and_moderators_reopen_them() {
+    let app = common::spawn_app().await;
+    let (moderator, setup_post_id) = setup(&app).await;
+    let (_, author) = common::create_test_user(&app, "threadauthor").await;
+    let (_, other) = common::create_test_user(&app, "threadother").await;
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/posts/{}", setup_post_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let forum_id = body["data"]["forum_id"].as_i64().unwrap();
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&author)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "My thread",
+            "content": "Content"
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["closed_by_author"], false);
+
+    let close = |token: &str| {
+        app.client
+            .put(app.url(&format!("/posts/{}/close", post_id)))
+            .bearer_auth(token)
+            .send()
+    };
+    let comment = |token: &str| {
+        app.client
+            .post(app.url("/comments"))
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "post_id": post_id, "content": "Reply" }))
+            .send()
+    };
+
+    assert_eq!(close(&other).await.unwrap().status(), 403);
+    let resp = close(&author).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["closed_by_author"], true);
+    assert_eq!(body["data"]["is_locked"], false);
+
+    for token in [&other, &author, &moderator] {
+        assert_eq!(comment(token).await.unwrap().status(), 400);
+    }
+
+    // Moderators can reopen a thread the author closed
+    let resp = close(&moderator).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["closed_by_author"], false);
+    assert_eq!(comment(&other).await.unwrap().status(), 200);
+
+    // The moderators' lock stays theirs
+    let resp = app
+        .client
+        .put(app.url(&format!("/posts/{}/lock", post_id)))
+        .bearer_auth(&author)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = app
+        .client
+        .put(app.url(&format!("/posts/{}/lock", post_id)))
+        .bearer_auth(&moderator)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(comment(&other).await.unwrap().status(), 400);
+}
@@ -0,0 +1,156 @@
+//! Flows shared by the tests that run against a non-default backend.
+
+use super::TestApp;
+use serde_json::Value;
+
+async fn get(app: &TestApp, path: &str, token: &str) -> Value {
+    let resp = app
+        .client
+        .get(app.url(path))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200, "GET {}", path);
+    resp.json().await.unwrap()
+}
+
+async fn post(app: &TestApp, path: &str, token: &str, body: Value) -> Value {
+    let resp = app
+        .client
+        .post(app.url(path))
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200, "POST {}", path);
+    resp.json().await.unwrap()
+}
+
+/// Posts, votes, rankings, search, upserts and stats: everything that uses
+/// backend-specific SQL, for the tests that swap the database backend.
+pub async fn core_flows(app: &TestApp) {
+    let (admin_id, admin_token) = super::create_test_user(app, "sqladmin").await;
+    super::make_admin(&app.db, admin_id).await;
+    let (user_id, user_token) = super::create_test_user(app, "sqluser").await;
+    let slug = super::create_test_forum(app, &admin_token).await;
+    let forum_id = super::get_forum_id(app, &slug).await;
+
+    let body = post(
+        app,
+        "/posts",
+        &user_token,
+        serde_json::json!({
+            "title": "Lightweight databases",
+            "content": "Running the forum on SQLite",
+            "forum_id": forum_id,
+            "tags": ["sqlite"]
+        }),
+    )
+    .await;
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    post(
+        app,
+        "/posts",
+        &admin_token,
+        serde_json::json!({
+            "title": "Another post",
+            "content": "Nothing to see",
+            "forum_id": forum_id
+        }),
+    )
+    .await;
+
+    // Votes: upsert plus clamped counters
+    post(
+        app,
+        &format!("/posts/{}/vote", post_id),
+        &admin_token,
+        serde_json::json!({ "value": 1 }),
+    )
+    .await;
+    post(
+        app,
+        &format!("/posts/{}/vote", post_id),
+        &admin_token,
+        serde_json::json!({ "value": -1 }),
+    )
+    .await;
+    let body = get(app, &format!("/posts/{}", post_id), &user_token).await;
+    assert_eq!(body["data"]["upvotes"], 0);
+    assert_eq!(body["data"]["downvotes"], 1);
+
+    // Hot ranking uses the time-decay expression
+    for sort in ["hot", "top", "new"] {
+        let body = get(
+            app,
+            &format!("/forums/{}/posts?sort={}", forum_id, sort),
+            &user_token,
+        )
+        .await;
+        assert_eq!(body["data"]["total"], 2, "{}", sort);
+    }
+
+    // Substring matching on SQLite, FULLTEXT on MySQL
+    let body = get(app, "/search?q=sqlite", &user_token).await;
+    let items = body["data"]["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["id"], post_id);
+    let body = get(app, "/search/all?q=SQLUSER", &user_token).await;
+    assert_eq!(body["data"]["users"]["total"], 1);
+    assert_eq!(body["data"]["tags"]["total"], 0);
+
+    let body = get(app, "/tags/sqlite/posts", &user_token).await;
+    assert_eq!(body["data"]["total"], 1);
+
+    // Insert-or-ignore and upsert relations, twice over
+    for _ in 0..2 {
+        for path in [
+            format!("/posts/{}/bookmark", post_id),
+            format!("/users/{}/follow", user_id),
+            format!("/posts/{}/read", post_id),
+        ] {
+            let resp = app
+                .client
+                .put(app.url(&path))
+                .bearer_auth(&admin_token)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), 200, "PUT {}", path);
+        }
+    }
+    let body = get(app, &format!("/users/{}/followers", user_id), &user_token).await;
+    assert_eq!(body["data"]["total"], 1);
+
+    // Profile stats sum scores; the activity feed is a UNION
+    let body = get(app, "/auth/me", &user_token).await;
+    let username = body["data"]["username"].as_str().unwrap().to_string();
+    let body = get(app, &format!("/users/{}", username), &user_token).await;
+    let stats = &body["data"]["stats"];
+    assert_eq!(stats["post_count"], 1);
+    assert_eq!(stats["post_karma"], -1);
+    assert_eq!(stats["follower_count"], 1);
+    assert_eq!(stats["top_tags"][0]["name"], "sqlite");
+    let body = get(app, &format!("/users/{}/activity", username), &user_token).await;
+    assert_eq!(body["data"]["items"][0]["id"], post_id);
+
+    // Badge rules are raw SQL conditions and awards a batch upsert
+    let awarder = xjy::services::badge::BadgeAwarder::new(app.db.clone());
+    assert_eq!(awarder.award_all().await.unwrap(), 2);
+    assert_eq!(awarder.award_all().await.unwrap(), 0);
+    let body = get(app, &format!("/users/{}", username), &user_token).await;
+    assert_eq!(body["data"]["badges"][0]["slug"], "first-post");
+
+    // Recursive bucket series instead of generate_series
+    let body = get(
+        app,
+        "/admin/stats/timeseries?metric=posts&interval=week",
+        &admin_token,
+    )
+    .await;
+    let points = body["data"]["points"].as_array().unwrap();
+    assert_eq!(points.len(), 12);
+    assert_eq!(points[11]["count"], 2);
+}
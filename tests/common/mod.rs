@@ -1,7 +1,9 @@
 #![allow(dead_code)]
 
+pub mod flows;
+
 use reqwest::Client;
-use sea_orm::{ConnectionTrait, DatabaseConnection, Statement};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement};
 use sea_orm_migration::MigratorTrait;
 use std::net::SocketAddr;
 use std::sync::{
@@ -47,12 +49,18 @@ pub async fn spawn_app() -> TestApp {
     let database_url = std::env::var("TEST_DATABASE_URL")
         .unwrap_or_else(|_| std::env::var("DATABASE_URL").expect("DATABASE_URL must be set"));
 
-    let db = sea_orm::Database::connect(&database_url)
+    let settings = xjy::config::app::DatabaseSettings {
+        url: Some(database_url),
+        ..Default::default()
+    };
+    let db = xjy::config::database::get_database(&settings)
         .await
         .expect("Failed to connect to test database");
+    let backend = db.get_database_backend();
 
-    // Run migrations only once globally (using atomic bool for thread safety)
-    if !MIGRATIONS_RAN.swap(true, Ordering::SeqCst) {
+    // Run migrations only once globally (using atomic bool for thread safety);
+    // an in-memory SQLite database starts empty every time
+    if backend == DbBackend::Sqlite || !MIGRATIONS_RAN.swap(true, Ordering::SeqCst) {
         // Migrations haven't run yet, run them now
         xjy::migration::Migrator::up(&db, None)
             .await
@@ -62,23 +70,58 @@ pub async fn spawn_app() -> TestApp {
     // Clean data tables (reverse dependency order)
     cleanup_tables(&db).await;
 
-    let hub = xjy::websocket::hub::NotificationHub::new();
-    let upload_config = xjy::services::upload::UploadConfig {
-        upload_dir: "./test_uploads".to_string(),
-    };
-    let email_service = xjy::services::email::EmailService::from_env();
+    let app = with_services(base_router(), db.clone());
+    let addr_str = serve(app).await;
+    let client = Client::new();
 
-    let app = axum::Router::new()
-        .route("/", axum::routing::get(|| async { "ok" }))
+    TestApp {
+        addr: addr_str,
+        db,
+        client,
+    }
+}
+
+/// The API routes with the middleware `spawn_app` adds to them.
+pub fn base_router() -> axum::Router {
+    axum::Router::new()
         .merge(xjy::routes::create_routes())
         .layer(axum::middleware::from_fn(
             xjy::middleware::security::security_headers_middleware,
         ))
-        .layer(axum::extract::Extension(db.clone()))
+        .layer(axum::middleware::from_fn_with_state(
+            xjy::middleware::cors::CorsState::default(),
+            xjy::middleware::cors::cors_middleware,
+        ))
+}
+
+/// Add the services handlers take as extensions, using `db`.
+pub fn with_services(app: axum::Router, db: DatabaseConnection) -> axum::Router {
+    let hub = xjy::websocket::hub::NotificationHub::new();
+    let mut app_config = xjy::config::app::AppConfig::default();
+    app_config.uploads.dir = "./test_uploads".to_string();
+    let shared_config = xjy::config::app::SharedConfig::new(app_config);
+    let email_service = xjy::services::email::EmailService::from_env();
+    let image_proxy = xjy::services::image_proxy::ImageProxy::from_env();
+    let search_index = xjy::services::search::SearchIndex::from_env();
+    let view_counter = xjy::services::view_counter::ViewCounter::from_env(None);
+    xjy::services::post_stats::PostStatsBroadcaster::new(
+        db.clone(),
+        hub.clone(),
+        xjy::config::websocket::WebSocketConfig::from_env(),
+    )
+    .spawn_scheduler();
+
+    app.layer(axum::extract::Extension(db))
         .layer(axum::extract::Extension(hub))
-        .layer(axum::extract::Extension(upload_config))
-        .layer(axum::extract::Extension(email_service));
+        .layer(axum::extract::Extension(shared_config))
+        .layer(axum::extract::Extension(email_service))
+        .layer(axum::extract::Extension(image_proxy))
+        .layer(axum::extract::Extension(search_index))
+        .layer(axum::extract::Extension(view_counter))
+}
 
+/// Serve `app` on a random port, returning its base URL.
+pub async fn serve(app: axum::Router) -> String {
     let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
         .await
         .expect("Failed to bind random port");
@@ -93,40 +136,58 @@ pub async fn spawn_app() -> TestApp {
         .unwrap();
     });
 
-    let addr_str = format!("http://{}", addr);
-    let client = Client::new();
-
-    TestApp {
-        addr: addr_str,
-        db,
-        client,
-    }
+    format!("http://{}", addr)
 }
 
 async fn cleanup_tables(db: &DatabaseConnection) {
     let tables = [
         "refresh_tokens",
+        "announcements",
+        "appeal_comments",
+        "appeals",
+        "user_notes",
+        "user_badges",
+        "username_history",
+        "badges",
+        "custom_emoji",
+        "audit_log",
+        "mod_queue_claims",
+        "moderation_actions",
+        "email_digests",
+        "email_opt_outs",
+        "email_outbox",
+        "federation_deliveries",
+        "federation_followers",
+        "federation_actors",
         "post_tags",
+        "post_attachments",
         "tags",
         "bookmarks",
+        "watched_posts",
+        "post_reads",
+        "post_fanouts",
         "follows",
         "votes",
         "notifications",
         "reports",
+        "comment_revisions",
         "comments",
         "posts",
+        "link_previews",
+        "post_flairs",
         "forums",
         "users",
+        "invite_codes",
+        "site_settings",
     ];
 
+    let backend = db.get_database_backend();
     for table in tables {
-        let sql = format!("TRUNCATE TABLE {} CASCADE", table);
-        let _ = db
-            .execute(Statement::from_string(
-                sea_orm::DatabaseBackend::Postgres,
-                sql,
-            ))
-            .await;
+        let sql = match backend {
+            DbBackend::Sqlite | DbBackend::MySql => format!("DELETE FROM {}", table),
+            _ => format!("TRUNCATE TABLE {} CASCADE", table),
+        };
+        let _ = db.execute(Statement::from_string(backend, sql)).await;
     }
 }
 
@@ -165,16 +226,20 @@ pub async fn create_test_user(app: &TestApp, username_prefix: &str) -> (i32, Str
         );
     }
 
-    let user_id = body["data"]["user_id"].as_i64().expect(&format!(
-        "Response missing user_id for user '{}': {:?}",
-        unique_username, body
-    )) as i32;
+    let user_id = body["data"]["user_id"].as_i64().unwrap_or_else(|| {
+        panic!(
+            "Response missing user_id for user '{}': {:?}",
+            unique_username, body
+        )
+    }) as i32;
     let token = body["data"]["token"]
         .as_str()
-        .expect(&format!(
-            "Response missing token for user '{}': {:?}",
-            unique_username, body
-        ))
+        .unwrap_or_else(|| {
+            panic!(
+                "Response missing token for user '{}': {:?}",
+                unique_username, body
+            )
+        })
         .to_string();
     (user_id, token)
 }
@@ -221,6 +286,17 @@ pub async fn make_admin(db: &DatabaseConnection, user_id: i32) {
     .expect("Failed to make user admin");
 }
 
+/// Make a user moderator by directly updating the database.
+pub async fn make_moderator(db: &DatabaseConnection, user_id: i32) {
+    db.execute(Statement::from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        "UPDATE users SET role = 'moderator' WHERE id = $1",
+        vec![user_id.into()],
+    ))
+    .await
+    .expect("Failed to make user moderator");
+}
+
 /// Get forum_id from slug.
 pub async fn get_forum_id(app: &TestApp, slug: &str) -> i32 {
     let resp = app
@@ -0,0 +1,129 @@
+mod common;
+
+use serde_json::Value;
+
+async fn pow_required(app: &common::TestApp, action: &str) -> bool {
+    let body: Value = app
+        .client
+        .get(app.url("/pow/challenge"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    body["data"]["actions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|a| a["action"] == action)
+        .unwrap()["required"]
+        .as_bool()
+        .unwrap()
+}
+
+async fn allowed_origin(app: &common::TestApp, origin: &str) -> Option<String> {
+    let resp = app
+        .client
+        .get(app.url("/forums"))
+        .header("origin", origin)
+        .send()
+        .await
+        .unwrap();
+    resp.headers()
+        .get("access-control-allow-origin")
+        .map(|v| v.to_str().unwrap().to_string())
+}
+
+async fn reload(app: &common::TestApp, token: &str) -> (u16, Value) {
+    let resp = app
+        .client
+        .post(app.url("/admin/config/reload"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    (resp.status().as_u16(), resp.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn reload_applies_runtime_settings_from_the_config_file() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "cfgadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (_, user_token) = common::create_test_user(&app, "cfguser").await;
+
+    let path = std::env::temp_dir().join(format!("xjy-reload-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+        [server]
+        port = 4321
+        cors_origins = "https://a.example"
+
+        [uploads]
+        max_file_size = 1024
+
+        [pow]
+        required_actions = "vote,report"
+        "#,
+    )
+    .unwrap();
+    // This binary runs on its own, so other tests never see the file
+    std::env::set_var("CONFIG_FILE", &path);
+
+    assert!(!pow_required(&app, "report").await);
+    assert_eq!(
+        allowed_origin(&app, "https://b.example").await.as_deref(),
+        Some("*")
+    );
+
+    let (status, _) = reload(&app, &user_token).await;
+    assert_eq!(status, 403);
+
+    let (status, body) = reload(&app, &admin_token).await;
+    assert_eq!(status, 200);
+    let applied = body["data"]["applied"].as_array().unwrap();
+    for name in [
+        "CORS_ORIGINS",
+        "POW_REQUIRED_ACTIONS",
+        "UPLOAD_MAX_FILE_SIZE",
+    ] {
+        assert!(applied.iter().any(|n| n == name), "{name} not applied");
+    }
+    assert!(body["data"]["restart_required"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|n| n == "PORT"));
+
+    assert!(pow_required(&app, "report").await);
+    assert_eq!(
+        allowed_origin(&app, "https://a.example").await.as_deref(),
+        Some("https://a.example")
+    );
+    assert_eq!(allowed_origin(&app, "https://b.example").await, None);
+
+    // A broken file changes nothing
+    std::fs::write(&path, "[server]\nport = \"eighty\"\n").unwrap();
+    let (status, body) = reload(&app, &admin_token).await;
+    assert_eq!(status, 400);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("Invalid configuration"));
+    assert!(pow_required(&app, "report").await);
+
+    // Settings dropped from the file fall back to their defaults
+    std::fs::write(&path, "").unwrap();
+    let (status, _) = reload(&app, &admin_token).await;
+    assert_eq!(status, 200);
+    assert!(!pow_required(&app, "report").await);
+    assert_eq!(
+        allowed_origin(&app, "https://b.example").await.as_deref(),
+        Some("*")
+    );
+
+    std::env::remove_var("CONFIG_FILE");
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use sea_orm::{ConnectionTrait, Statement};
+use serde_json::Value;
+
+async fn post_json(app: &common::TestApp, token: &str, path: &str, body: Value) -> Value {
+    let resp = app
+        .client
+        .post(app.url(path))
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200, "POST {}", path);
+    resp.json().await.unwrap()
+}
+
+async fn get_json(app: &common::TestApp, path: &str) -> (u16, Value) {
+    let resp = app.client.get(app.url(path)).send().await.unwrap();
+    let status = resp.status().as_u16();
+    (status, resp.json().await.unwrap())
+}
+
+async fn forum_post_ids(app: &common::TestApp, forum_id: i64) -> Vec<i64> {
+    let (_, body) = get_json(app, &format!("/forums/{}/posts", forum_id)).await;
+    body["data"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["id"].as_i64().unwrap())
+        .collect()
+}
+
+async fn comment_count(app: &common::TestApp, post_id: i64) -> usize {
+    let (_, body) = get_json(app, &format!("/posts/{}/comments", post_id)).await;
+    body["data"].as_array().unwrap().len()
+}
+
+#[tokio::test]
+async fn deactivated_accounts_vanish_until_their_owner_logs_in() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "leaving").await;
+    common::make_admin(&app.db, user_id).await;
+    let (_, other) = common::create_test_user(&app, "staying").await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await as i64;
+    let me: Value = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let username = me["data"]["username"].as_str().unwrap().to_string();
+
+    let new_post = |title: &'static str, token: String| {
+        let app = &app;
+        async move {
+            let body = post_json(
+                app,
+                &token,
+                "/posts",
+                serde_json::json!({ "forum_id": forum_id, "title": title, "content": "Content" }),
+            )
+            .await;
+            body["data"]["id"].as_i64().unwrap()
+        }
+    };
+    let mine = new_post("Mine", token.clone()).await;
+    let moderated = new_post("Moderated", token.clone()).await;
+    let theirs = new_post("Theirs", other.clone()).await;
+    post_json(
+        &app,
+        &token,
+        "/comments",
+        serde_json::json!({ "post_id": theirs, "content": "My comment" }),
+    )
+    .await;
+    // Hidden by a moderator before the user left
+    app.db
+        .execute(Statement::from_string(
+            app.db.get_database_backend(),
+            format!("UPDATE posts SET is_hidden = TRUE WHERE id = {}", moderated),
+        ))
+        .await
+        .unwrap();
+
+    let deactivate = |password: &'static str| {
+        app.client
+            .post(app.url("/auth/deactivate"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "password": password }))
+            .send()
+    };
+    assert_eq!(deactivate("wrong_password").await.unwrap().status(), 400);
+    assert_eq!(deactivate("test_password_123").await.unwrap().status(), 200);
+
+    // Sessions end, and the profile and content disappear
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    let (status, _) = get_json(&app, &format!("/users/{}", username)).await;
+    assert_eq!(status, 404);
+    assert_eq!(forum_post_ids(&app, forum_id).await, vec![theirs]);
+    assert_eq!(comment_count(&app, theirs).await, 0);
+
+    // Replies don't notify them while they're away
+    post_json(
+        &app,
+        &other,
+        "/comments",
+        serde_json::json!({ "post_id": mine, "content": "Anyone there?" }),
+    )
+    .await;
+    let held = app
+        .db
+        .query_one(Statement::from_string(
+            app.db.get_database_backend(),
+            format!(
+                "SELECT COUNT(*) AS count FROM notifications WHERE user_id = {}",
+                user_id
+            ),
+        ))
+        .await
+        .unwrap()
+        .unwrap()
+        .try_get::<i64>("", "count")
+        .unwrap();
+    assert_eq!(held, 0);
+
+    // Logging in brings everything back but what a moderator hid
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({
+            "username": username,
+            "password": "test_password_123",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let (status, _) = get_json(&app, &format!("/users/{}", username)).await;
+    assert_eq!(status, 200);
+    let mut ids = forum_post_ids(&app, forum_id).await;
+    ids.sort();
+    assert_eq!(ids, vec![mine, theirs]);
+    assert_eq!(comment_count(&app, theirs).await, 1);
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, Statement};
+use serde_json::Value;
+
+fn at(s: &str) -> chrono::NaiveDateTime {
+    chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+#[tokio::test]
+async fn weekly_digest_lists_followed_posts_once() {
+    // Queue emails without delivering them
+    std::env::set_var("SMTP_HOST", "127.0.0.1");
+    std::env::set_var("SMTP_PORT", "1");
+    std::env::set_var("SMTP_USERNAME", "mailer@test.com");
+    std::env::set_var("SMTP_PASSWORD", "secret");
+    let app = common::spawn_app().await;
+    let digests = xjy::services::digest::DigestService::new(
+        app.db.clone(),
+        xjy::services::email::EmailService::from_env(),
+        xjy::config::email::DigestConfig::from_env(),
+    );
+
+    let (reader_id, reader_token) = common::create_test_user(&app, "digestreader").await;
+    let (author_id, author_token) = common::create_test_user(&app, "digestauthor").await;
+    common::make_admin(&app.db, author_id).await;
+    let slug = common::create_test_forum(&app, &author_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let resp = app
+        .client
+        .put(app.url("/auth/profile"))
+        .bearer_auth(&reader_token)
+        .json(&serde_json::json!({ "digest_frequency": "hourly" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = app
+        .client
+        .put(app.url("/auth/profile"))
+        .bearer_auth(&reader_token)
+        .json(&serde_json::json!({ "digest_frequency": "weekly" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/users/{}/follow", author_id)))
+        .bearer_auth(&reader_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let mut post_ids = Vec::new();
+    for title in ["Digest worthy", "Last week's news"] {
+        let resp = app
+            .client
+            .post(app.url("/posts"))
+            .bearer_auth(&author_token)
+            .json(&serde_json::json!({
+                "forum_id": forum_id,
+                "title": title,
+                "content": "Content"
+            }))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        post_ids.push(body["data"]["id"].as_i64().unwrap() as i32);
+    }
+
+    // One post in the week of 2026-10-12, one the week before
+    for (id, created_at) in post_ids
+        .iter()
+        .zip(["2026-10-14 12:00:00", "2026-10-07 12:00:00"])
+    {
+        app.db
+            .execute(Statement::from_sql_and_values(
+                sea_orm::DatabaseBackend::Postgres,
+                "UPDATE posts SET created_at = $1 WHERE id = $2",
+                [at(created_at).into(), (*id).into()],
+            ))
+            .await
+            .unwrap();
+    }
+
+    let now = at("2026-10-20 09:00:00");
+    assert_eq!(digests.send_due(now).await.unwrap(), 1);
+
+    let reader = xjy::models::User::find_by_id(reader_id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let queued = xjy::models::EmailOutbox::find()
+        .filter(xjy::models::email_outbox::Column::Kind.eq("digest"))
+        .all(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].to_address, reader.email);
+    assert_eq!(queued[0].subject, "Your weekly digest");
+    assert!(queued[0].body.contains("Digest worthy"));
+    assert!(!queued[0].body.contains("Last week's news"));
+
+    // The period is recorded, so a second check sends nothing
+    assert_eq!(digests.send_due(now).await.unwrap(), 0);
+    let history = xjy::models::EmailDigest::find().all(&app.db).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].user_id, reader_id);
+    assert_eq!(history[0].period_start, at("2026-10-12 00:00:00"));
+    assert_eq!(history[0].period_end, at("2026-10-19 00:00:00"));
+    assert_eq!(history[0].post_count, 1);
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::Value;
+
+async fn email_of(app: &common::TestApp, user_id: i32) -> String {
+    xjy::models::User::find_by_id(user_id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap()
+        .email
+}
+
+async fn forgot_password(app: &common::TestApp, email: &str) {
+    let resp = app
+        .client
+        .post(app.url("/auth/forgot-password"))
+        .json(&serde_json::json!({ "email": email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+async fn outbox_for(app: &common::TestApp, email: &str) -> Vec<xjy::models::EmailOutboxModel> {
+    xjy::models::EmailOutbox::find()
+        .filter(xjy::models::email_outbox::Column::ToAddress.eq(email))
+        .all(&app.db)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn bounces_and_complaints_stop_email_to_the_address() {
+    // Queue emails without delivering them
+    std::env::set_var("SMTP_HOST", "127.0.0.1");
+    std::env::set_var("SMTP_PORT", "1");
+    std::env::set_var("SMTP_USERNAME", "mailer@test.com");
+    std::env::set_var("SMTP_PASSWORD", "secret");
+    std::env::set_var("EMAIL_WEBHOOK_SECRET", "hook-secret");
+    let app = common::spawn_app().await;
+
+    let (admin_id, admin_token) = common::create_test_user(&app, "bounceadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (bounced_id, _) = common::create_test_user(&app, "bounced").await;
+    let (complainer_id, _) = common::create_test_user(&app, "complainer").await;
+    let bounced = email_of(&app, bounced_id).await;
+    let complainer = email_of(&app, complainer_id).await;
+
+    forgot_password(&app, &bounced).await;
+    assert_eq!(outbox_for(&app, &bounced).await[0].status, "pending");
+
+    let events = serde_json::json!([
+        { "email": &bounced, "event": "bounce", "type": "bounce", "reason": "550 No such user" },
+        { "email": &complainer, "event": "delivered" },
+    ]);
+    let resp = app
+        .client
+        .post(app.url("/webhooks/email/sendgrid?token=wrong"))
+        .json(&events)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+
+    let resp = app
+        .client
+        .post(app.url("/webhooks/email/sendgrid?token=hook-secret"))
+        .json(&events)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["marked"], 1);
+
+    // The queued email is dropped and no new ones are sent
+    let queued = outbox_for(&app, &bounced).await;
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].status, "failed");
+    forgot_password(&app, &bounced).await;
+    assert_eq!(outbox_for(&app, &bounced).await.len(), 1);
+
+    // SES complaint via SNS, which posts JSON as text/plain
+    let complaint = serde_json::json!({
+        "notificationType": "Complaint",
+        "complaint": { "complainedRecipients": [{ "emailAddress": complainer.to_uppercase() }] },
+    });
+    let envelope = serde_json::json!({
+        "Type": "Notification",
+        "MessageId": "1",
+        "Message": complaint.to_string(),
+    });
+    let resp = app
+        .client
+        .post(app.url("/webhooks/email/ses?token=hook-secret"))
+        .header("content-type", "text/plain; charset=UTF-8")
+        .body(envelope.to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["marked"], 1);
+
+    // Subscription confirmations are only followed to AWS
+    let confirmation = serde_json::json!({
+        "Type": "SubscriptionConfirmation",
+        "SubscribeURL": "http://127.0.0.1:1/confirm",
+    });
+    let resp = app
+        .client
+        .post(app.url("/webhooks/email/ses?token=hook-secret"))
+        .body(confirmation.to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = app
+        .client
+        .get(app.url("/admin/users?undeliverable=true"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let items = body["data"]["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    let reason = |id: i32| {
+        items.iter().find(|u| u["id"] == id).unwrap()["email_undeliverable"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(reason(bounced_id), "bounce");
+    assert_eq!(reason(complainer_id), "complaint");
+
+    let resp = app
+        .client
+        .get(app.url("/admin/users?undeliverable=false"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let items = body["data"]["items"].as_array().unwrap();
+    assert!(items.iter().all(|u| u["email_undeliverable"].is_null()));
+    assert!(items.iter().any(|u| u["id"] == admin_id));
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::Value;
+
+async fn preferences(app: &common::TestApp, token: &str) -> Value {
+    let resp = app
+        .client
+        .get(app.url("/auth/email-preferences"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["categories"].clone()
+}
+
+fn enabled(categories: &Value, name: &str) -> bool {
+    categories
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["category"] == name)
+        .unwrap()["enabled"]
+        .as_bool()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn unsubscribe_links_and_preferences() {
+    // Queue emails without delivering them
+    std::env::set_var("SMTP_HOST", "127.0.0.1");
+    std::env::set_var("SMTP_PORT", "1");
+    std::env::set_var("SMTP_USERNAME", "mailer@test.com");
+    std::env::set_var("SMTP_PASSWORD", "secret");
+    std::env::set_var("PUBLIC_API_URL", "https://api.forum.test/");
+    let app = common::spawn_app().await;
+    let email_service = xjy::services::email::EmailService::from_env();
+
+    let (user_id, token) = common::create_test_user(&app, "unsubscriber").await;
+    let user = xjy::models::User::find_by_id(user_id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let categories = preferences(&app, &token).await;
+    assert!(enabled(&categories, "account"));
+    assert!(enabled(&categories, "digest"));
+
+    // Account emails can't be turned off; unknown categories are rejected
+    for change in [
+        serde_json::json!({ "account": false }),
+        serde_json::json!({ "newsletter": false }),
+    ] {
+        let resp = app
+            .client
+            .put(app.url("/auth/email-preferences"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "categories": change }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400);
+    }
+
+    // Every email carries a signed unsubscribe link
+    let resp = app
+        .client
+        .post(app.url("/auth/forgot-password"))
+        .json(&serde_json::json!({ "email": &user.email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let email = xjy::models::EmailOutbox::find()
+        .filter(xjy::models::email_outbox::Column::ToAddress.eq(user.email.as_str()))
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let link = email.unsubscribe_url.unwrap();
+    assert!(link.starts_with("https://api.forum.test/api/v1/email/unsubscribe?token="));
+    assert!(email.body.contains(&link));
+    let unsubscribe_token = link.split("token=").nth(1).unwrap();
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/email/unsubscribe?token={}x", unsubscribe_token)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // One-click POST from the mail client, no login needed
+    let resp = app
+        .client
+        .post(app.url(&format!("/email/unsubscribe?token={}", unsubscribe_token)))
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["data"]["unsubscribed"],
+        serde_json::json!(["digest", "announcement"])
+    );
+    assert!(!enabled(&preferences(&app, &token).await, "digest"));
+
+    // Digests are no longer queued for the user
+    let item = xjy::services::email_template::DigestItem {
+        post_id: 1,
+        title: "Hello".to_string(),
+        author: "someone".to_string(),
+        forum: "General".to_string(),
+        score: 1,
+    };
+    email_service
+        .send_digest(&app.db, &user, true, std::slice::from_ref(&item))
+        .await
+        .unwrap();
+    let digests = xjy::models::EmailOutbox::find()
+        .filter(xjy::models::email_outbox::Column::Kind.eq("digest"))
+        .all(&app.db)
+        .await
+        .unwrap();
+    assert!(digests.is_empty());
+
+    // Turning the category back on
+    let resp = app
+        .client
+        .put(app.url("/auth/email-preferences"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "categories": { "digest": true } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert!(enabled(&body["data"]["categories"], "digest"));
+    email_service
+        .send_digest(&app.db, &user, true, &[item])
+        .await
+        .unwrap();
+    let digest = xjy::models::EmailOutbox::find()
+        .filter(xjy::models::email_outbox::Column::Kind.eq("digest"))
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(digest.unsubscribe_url.unwrap().contains(".digest."));
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use axum::{extract::State, http::HeaderMap, http::StatusCode, routing::post, Json, Router};
+use sea_orm::EntityTrait;
+use std::sync::{Arc, Mutex};
+
+/// Authorization header and JSON body of a request to the mock.
+type RecordedRequest = (Option<String>, serde_json::Value);
+
+/// Stand-in for the SendGrid API: answers with the queued status codes in
+/// order and records every request body and auth header.
+#[derive(Clone, Default)]
+struct MockSendGrid {
+    responses: Arc<Mutex<Vec<StatusCode>>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+async fn mail_send(
+    State(mock): State<MockSendGrid>,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> (StatusCode, HeaderMap) {
+    let auth = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    mock.requests.lock().unwrap().push((auth, body));
+    let status = mock.responses.lock().unwrap().remove(0);
+    let mut response_headers = HeaderMap::new();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let reset = chrono::Utc::now().timestamp() + 120;
+        response_headers.insert("x-ratelimit-reset", reset.to_string().parse().unwrap());
+    }
+    (status, response_headers)
+}
+
+async fn spawn_mock() -> (MockSendGrid, String) {
+    let mock = MockSendGrid::default();
+    let app = Router::new()
+        .route("/v3/mail/send", post(mail_send))
+        .with_state(mock.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (mock, format!("http://{}", addr))
+}
+
+async fn request_reset(app: &common::TestApp, email: &str) -> xjy::models::EmailOutboxModel {
+    let resp = app
+        .client
+        .post(app.url("/auth/forgot-password"))
+        .json(&serde_json::json!({ "email": email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    xjy::models::EmailOutbox::find()
+        .all(&app.db)
+        .await
+        .unwrap()
+        .into_iter()
+        .max_by_key(|e| e.id)
+        .unwrap()
+}
+
+async fn reload(app: &common::TestApp, id: i32) -> xjy::models::EmailOutboxModel {
+    xjy::models::EmailOutbox::find_by_id(id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn sendgrid_provider_delivers_and_maps_errors() {
+    let (mock, url) = spawn_mock().await;
+    std::env::set_var("EMAIL_PROVIDER", "sendgrid");
+    std::env::set_var("SENDGRID_API_KEY", "sg-test-key");
+    std::env::set_var("SENDGRID_API_URL", &url);
+    std::env::set_var("EMAIL_FROM", "Forum <noreply@forum.test>");
+    let app = common::spawn_app().await;
+    let email_service = xjy::services::email::EmailService::from_env();
+    assert_eq!(email_service.provider_name(), Some("sendgrid"));
+
+    let (user_id, _token) = common::create_test_user(&app, "gridded").await;
+    let address = xjy::models::User::find_by_id(user_id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap()
+        .email;
+
+    // Accepted
+    mock.responses.lock().unwrap().push(StatusCode::ACCEPTED);
+    let email = request_reset(&app, &address).await;
+    email_service.process_queue(&app.db).await.unwrap();
+    let email = reload(&app, email.id).await;
+    assert_eq!(email.status, "sent");
+    {
+        let requests = mock.requests.lock().unwrap();
+        let (auth, body) = requests.last().unwrap();
+        assert_eq!(auth.as_deref(), Some("Bearer sg-test-key"));
+        assert_eq!(body["personalizations"][0]["to"][0]["email"], address);
+        assert_eq!(body["from"]["email"], "noreply@forum.test");
+        assert_eq!(body["from"]["name"], "Forum");
+        assert_eq!(body["content"][0]["type"], "text/plain");
+        assert_eq!(body["content"][1]["type"], "text/html");
+        let unsubscribe = body["headers"]["List-Unsubscribe"].as_str().unwrap();
+        assert!(unsubscribe.contains("/api/v1/email/unsubscribe?token="));
+        assert_eq!(
+            body["headers"]["List-Unsubscribe-Post"],
+            "List-Unsubscribe=One-Click"
+        );
+    }
+
+    // Throttled: retried later without using up an attempt
+    mock.responses
+        .lock()
+        .unwrap()
+        .push(StatusCode::TOO_MANY_REQUESTS);
+    let email = request_reset(&app, &address).await;
+    email_service.process_queue(&app.db).await.unwrap();
+    let email = reload(&app, email.id).await;
+    assert_eq!(email.status, "pending");
+    assert_eq!(email.attempts, 0);
+    assert!(email.next_attempt_at > chrono::Utc::now().naive_utc() + chrono::Duration::seconds(60));
+
+    // Rejected: failed at once, not retried
+    mock.responses.lock().unwrap().push(StatusCode::BAD_REQUEST);
+    let rejected = request_reset(&app, &address).await;
+    email_service.process_queue(&app.db).await.unwrap();
+    let rejected = reload(&app, rejected.id).await;
+    assert_eq!(rejected.status, "failed");
+    assert_eq!(rejected.attempts, 1);
+    assert!(rejected.last_error.unwrap().contains("400"));
+}
@@ -0,0 +1,209 @@
+mod common;
+
+use sea_orm::EntityTrait;
+use serde_json::Value;
+
+#[tokio::test]
+async fn failed_emails_are_retried_then_listed_for_admins() {
+    // Point SMTP at a closed port so every delivery fails, and retry at once
+    std::env::set_var("SMTP_HOST", "127.0.0.1");
+    std::env::set_var("SMTP_PORT", "1");
+    std::env::set_var("SMTP_USERNAME", "mailer@test.com");
+    std::env::set_var("SMTP_PASSWORD", "secret");
+    std::env::set_var("EMAIL_MAX_ATTEMPTS", "2");
+    std::env::set_var("EMAIL_RETRY_BASE_SECONDS", "0");
+    let app = common::spawn_app().await;
+    let email_service = xjy::services::email::EmailService::from_env();
+
+    let (admin_id, admin_token) = common::create_test_user(&app, "mailadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (user_id, _token) = common::create_test_user(&app, "forgetful").await;
+    let address = xjy::models::User::find_by_id(user_id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap()
+        .email;
+
+    let resp = app
+        .client
+        .post(app.url("/auth/forgot-password"))
+        .json(&serde_json::json!({ "email": &address }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // Queued rather than sent inline
+    let queued = xjy::models::EmailOutbox::find().all(&app.db).await.unwrap();
+    let email = queued
+        .iter()
+        .find(|e| e.to_address == address)
+        .expect("reset email queued");
+    assert_eq!(email.kind, "password_reset");
+    assert_eq!(email.status, "pending");
+    let email_id = email.id;
+
+    // First failure schedules a retry
+    email_service.process_queue(&app.db).await.unwrap();
+    let email = xjy::models::EmailOutbox::find_by_id(email_id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(email.status, "pending");
+    assert_eq!(email.attempts, 1);
+    assert!(email.last_error.is_some());
+
+    // Second failure exhausts the attempts
+    email_service.process_queue(&app.db).await.unwrap();
+    let email = xjy::models::EmailOutbox::find_by_id(email_id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(email.status, "failed");
+    assert_eq!(email.attempts, 2);
+
+    let resp = app
+        .client
+        .get(app.url("/admin/emails"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let items = body["data"]["items"].as_array().unwrap();
+    let listed = items
+        .iter()
+        .find(|e| e["id"] == email_id)
+        .expect("failed email listed");
+    assert_eq!(listed["to_address"], address);
+    assert_eq!(listed["attempts"], 2);
+    assert!(listed["last_error"].is_string());
+
+    let resp = app
+        .client
+        .get(app.url("/admin/emails?status=bogus"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = app
+        .client
+        .post(app.url(&format!("/admin/emails/{}/retry", email_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["status"], "pending");
+    assert_eq!(body["data"]["attempts"], 0);
+
+    // Only failed emails can be retried
+    let resp = app
+        .client
+        .post(app.url(&format!("/admin/emails/{}/retry", email_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // Regular users cannot see the queue
+    let (_other_id, other_token) = common::create_test_user(&app, "nosy").await;
+    let resp = app
+        .client
+        .get(app.url("/admin/emails"))
+        .bearer_auth(&other_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+}
+
+#[tokio::test]
+async fn emails_use_the_recipients_locale() {
+    std::env::set_var("SMTP_HOST", "127.0.0.1");
+    std::env::set_var("SMTP_PORT", "1");
+    std::env::set_var("SMTP_USERNAME", "mailer@test.com");
+    std::env::set_var("SMTP_PASSWORD", "secret");
+    let app = common::spawn_app().await;
+
+    // Locale comes from Accept-Language when not given explicitly
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .header("Accept-Language", "fr-FR, zh-CN;q=0.8")
+        .json(&serde_json::json!({
+            "username": "lingua",
+            "email": "lingua@test.com",
+            "password": "test_password_123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["locale"], "zh");
+
+    let forgot = || async {
+        let resp = app
+            .client
+            .post(app.url("/auth/forgot-password"))
+            .json(&serde_json::json!({ "email": "lingua@test.com" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        xjy::models::EmailOutbox::find()
+            .all(&app.db)
+            .await
+            .unwrap()
+            .into_iter()
+            .max_by_key(|e| e.id)
+            .unwrap()
+    };
+
+    let email = forgot().await;
+    assert_eq!(email.subject, "重置你的密码");
+    assert!(email.body.contains("/reset-password?token="));
+    assert!(email.html_body.unwrap().contains("<a href="));
+
+    let resp = app
+        .client
+        .put(app.url("/auth/profile"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "locale": "fr" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = app
+        .client
+        .put(app.url("/auth/profile"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "locale": "en-US" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let email = forgot().await;
+    assert_eq!(email.subject, "Reset your password");
+}
@@ -0,0 +1,252 @@
+mod common;
+
+use rsa::pkcs8::{EncodePublicKey, LineEnding};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use sha2::{Digest, Sha256};
+use xjy::models::{
+    federation_actor, federation_delivery, federation_follower, FederationDelivery,
+    FederationFollower,
+};
+use xjy::utils::http_signature;
+
+const REMOTE_ACTOR: &str = "https://remote.test/users/alice";
+
+fn init_env() {
+    std::env::set_var("FEDERATION_ENABLED", "true");
+    std::env::set_var("SITE_URL", "https://forum.test");
+    std::env::set_var("PUBLIC_API_URL", "https://api.forum.test");
+}
+
+/// A remote actor as if its document had been fetched already.
+async fn remote_actor(app: &common::TestApp) -> RsaPrivateKey {
+    let key = RsaPrivateKey::new(&mut rsa::rand_core::OsRng, 1024).unwrap();
+    let pem = RsaPublicKey::from(&key)
+        .to_public_key_pem(LineEnding::LF)
+        .unwrap();
+    federation_actor::ActiveModel {
+        actor_hash: Set(Sha256::digest(REMOTE_ACTOR)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()),
+        actor_id: Set(REMOTE_ACTOR.to_string()),
+        inbox: Set(format!("{}/inbox", REMOTE_ACTOR)),
+        shared_inbox: Set(Some("https://remote.test/inbox".to_string())),
+        public_key_pem: Set(pem),
+        fetched_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .insert(&app.db)
+    .await
+    .unwrap();
+    key
+}
+
+/// POST `activity` to `path`, signed with `key` unless it is `None`.
+async fn deliver(
+    app: &common::TestApp,
+    key: Option<&RsaPrivateKey>,
+    path: &str,
+    activity: &serde_json::Value,
+) -> u16 {
+    let body = activity.to_string();
+    let host = app.addr.trim_start_matches("http://");
+    let date = http_signature::http_date(chrono::Utc::now());
+    let digest = http_signature::digest(body.as_bytes());
+    let mut request = app
+        .client
+        .post(format!("{}{}", app.addr, path))
+        .header("content-type", "application/activity+json")
+        .header("date", &date)
+        .header("digest", &digest);
+    if let Some(key) = key {
+        let signature = http_signature::sign(
+            key,
+            &format!("{}#main-key", REMOTE_ACTOR),
+            "POST",
+            path,
+            &[("host", host), ("date", &date), ("digest", &digest)],
+        );
+        request = request.header("signature", signature);
+    }
+    request.body(body).send().await.unwrap().status().as_u16()
+}
+
+async fn get_activity(app: &common::TestApp, path: &str) -> (u16, serde_json::Value) {
+    let resp = app
+        .client
+        .get(format!("{}{}", app.addr, path))
+        .header("accept", "application/activity+json")
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status().as_u16();
+    let content_type = resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let body = resp.json().await.unwrap_or_default();
+    if status == 200 {
+        assert!(content_type.starts_with("application/"), "{}", content_type);
+    }
+    (status, body)
+}
+
+async fn deliveries(app: &common::TestApp) -> Vec<(String, serde_json::Value)> {
+    FederationDelivery::find()
+        .order_by_asc(federation_delivery::Column::Id)
+        .all(&app.db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.inbox, serde_json::from_str(&d.activity).unwrap()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_forums_are_discoverable_actors() {
+    init_env();
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "ap_admin").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+
+    let resp = app
+        .client
+        .get(format!("{}/.well-known/webfinger", app.addr))
+        .query(&[("resource", format!("acct:{}@api.forum.test", slug))])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers()["content-type"].to_str().unwrap(),
+        "application/jrd+json"
+    );
+    let jrd: serde_json::Value = resp.json().await.unwrap();
+    let actor_url = format!("https://api.forum.test/ap/forums/{}", slug);
+    assert_eq!(jrd["links"][0]["rel"], "self");
+    assert_eq!(jrd["links"][0]["href"], actor_url);
+
+    let resp = app
+        .client
+        .get(format!("{}/.well-known/webfinger", app.addr))
+        .query(&[("resource", format!("acct:{}@elsewhere.test", slug))])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    let (status, actor) = get_activity(&app, &format!("/ap/forums/{}", slug)).await;
+    assert_eq!(status, 200);
+    assert_eq!(actor["type"], "Group");
+    assert_eq!(actor["id"], actor_url);
+    assert_eq!(actor["preferredUsername"], slug);
+    assert_eq!(actor["inbox"], format!("{}/inbox", actor_url));
+    assert_eq!(actor["publicKey"]["id"], format!("{}#main-key", actor_url));
+    assert!(actor["publicKey"]["publicKeyPem"]
+        .as_str()
+        .unwrap()
+        .starts_with("-----BEGIN PUBLIC KEY-----"));
+
+    assert_eq!(get_activity(&app, "/ap/users/missing_user").await.0, 404);
+}
+
+#[tokio::test]
+async fn test_remote_follows_receive_new_posts() {
+    init_env();
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "ap_author").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let key = remote_actor(&app).await;
+
+    let inbox = format!("/ap/forums/{}/inbox", slug);
+    let forum_url = format!("https://api.forum.test/ap/forums/{}", slug);
+    let follow = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": "https://remote.test/follows/1",
+        "type": "Follow",
+        "actor": REMOTE_ACTOR,
+        "object": forum_url,
+    });
+
+    // Unsigned, or signed by someone other than the actor
+    assert_eq!(deliver(&app, None, &inbox, &follow).await, 401);
+    let mut spoofed = follow.clone();
+    spoofed["actor"] = serde_json::json!("https://remote.test/users/mallory");
+    assert_eq!(deliver(&app, Some(&key), &inbox, &spoofed).await, 403);
+    assert_eq!(deliver(&app, Some(&key), &inbox, &follow).await, 202);
+
+    let followers = FederationFollower::find()
+        .filter(federation_follower::Column::ActorType.eq("forum"))
+        .filter(federation_follower::Column::LocalId.eq(forum_id))
+        .all(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(followers.len(), 1);
+    assert_eq!(followers[0].follower_id, REMOTE_ACTOR);
+
+    let queued = deliveries(&app).await;
+    assert_eq!(queued.len(), 1);
+    let (accept_inbox, accept) = &queued[0];
+    assert_eq!(accept_inbox, "https://remote.test/users/alice/inbox");
+    assert_eq!(accept["type"], "Accept");
+    assert_eq!(accept["actor"], forum_url);
+    assert_eq!(accept["object"]["id"], "https://remote.test/follows/1");
+
+    let (_, collection) = get_activity(&app, &format!("/ap/forums/{}/followers", slug)).await;
+    assert_eq!(collection["totalItems"], 1);
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Hello fediverse",
+            "content": "Posted **everywhere**",
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    let post_url = format!("https://api.forum.test/ap/posts/{}", post_id);
+
+    let queued = deliveries(&app).await;
+    assert_eq!(queued.len(), 2);
+    let (announce_inbox, announce) = &queued[1];
+    assert_eq!(announce_inbox, "https://remote.test/inbox");
+    assert_eq!(announce["type"], "Announce");
+    assert_eq!(announce["actor"], forum_url);
+    assert_eq!(announce["object"], post_url);
+
+    let (status, article) = get_activity(&app, &format!("/ap/posts/{}", post_id)).await;
+    assert_eq!(status, 200);
+    assert_eq!(article["type"], "Article");
+    assert_eq!(article["name"], "Hello fediverse");
+    assert_eq!(article["audience"], forum_url);
+    assert!(article["content"]
+        .as_str()
+        .unwrap()
+        .contains("<strong>everywhere</strong>"));
+
+    let (_, outbox) = get_activity(&app, &format!("/ap/forums/{}/outbox", slug)).await;
+    assert_eq!(outbox["totalItems"], 1);
+    assert_eq!(outbox["orderedItems"][0]["object"], post_url);
+
+    let undo = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": "https://remote.test/follows/1/undo",
+        "type": "Undo",
+        "actor": REMOTE_ACTOR,
+        "object": follow,
+    });
+    assert_eq!(deliver(&app, Some(&key), &inbox, &undo).await, 202);
+    let (_, collection) = get_activity(&app, &format!("/ap/forums/{}/followers", slug)).await;
+    assert_eq!(collection["totalItems"], 0);
+}
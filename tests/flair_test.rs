@@ -0,0 +1,210 @@
+mod common;
+
+use serde_json::Value;
+
+async fn create_flair(app: &common::TestApp, token: &str, forum_id: i64, name: &str) -> i64 {
+    let resp = app
+        .client
+        .post(app.url(&format!("/admin/forums/{}/flairs", forum_id)))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "name": name, "color": "#1f6feb" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["id"].as_i64().unwrap()
+}
+
+async fn listed_titles(app: &common::TestApp, forum_id: i64, query: &str) -> Vec<String> {
+    let resp = app
+        .client
+        .get(app.url(&format!("/forums/{}/posts{}", forum_id, query)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["title"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn admins_manage_flairs_per_forum() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin) = common::create_test_user(&app, "flairadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (_, user) = common::create_test_user(&app, "flairuser").await;
+    let slug = common::create_test_forum(&app, &admin).await;
+    let forum_id = common::get_forum_id(&app, &slug).await as i64;
+    let url = format!("/admin/forums/{}/flairs", forum_id);
+
+    let resp = app
+        .client
+        .post(app.url(&url))
+        .bearer_auth(&user)
+        .json(&serde_json::json!({ "name": "Question" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let flair_id = create_flair(&app, &admin, forum_id, "Question").await;
+    let resp = app
+        .client
+        .post(app.url(&url))
+        .bearer_auth(&admin)
+        .json(&serde_json::json!({ "name": "Question" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+    let resp = app
+        .client
+        .post(app.url(&url))
+        .bearer_auth(&admin)
+        .json(&serde_json::json!({ "name": "News", "color": "blue" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/flairs/{}", flair_id)))
+        .bearer_auth(&admin)
+        .json(&serde_json::json!({ "name": "Help", "color": "#ff0000" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/forums/{}/flairs", forum_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let flairs = body["data"].as_array().unwrap();
+    assert_eq!(flairs.len(), 1);
+    assert_eq!(flairs[0]["name"], "Help");
+    assert_eq!(flairs[0]["color"], "#ff0000");
+
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/flairs/{}", flair_id)))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/flairs/{}", flair_id)))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn posts_carry_a_flair_of_their_forum() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin) = common::create_test_user(&app, "flairmod").await;
+    common::make_admin(&app.db, admin_id).await;
+    let slug = common::create_test_forum(&app, &admin).await;
+    let forum_id = common::get_forum_id(&app, &slug).await as i64;
+    let other_slug = common::create_test_forum(&app, &admin).await;
+    let other_forum_id = common::get_forum_id(&app, &other_slug).await as i64;
+    let question = create_flair(&app, &admin, forum_id, "Question").await;
+    let elsewhere = create_flair(&app, &admin, other_forum_id, "Elsewhere").await;
+
+    let create = |title: &str, flair_id: Option<i64>| {
+        app.client
+            .post(app.url("/posts"))
+            .bearer_auth(&admin)
+            .json(&serde_json::json!({
+                "forum_id": forum_id,
+                "title": title,
+                "content": "Content",
+                "flair_id": flair_id,
+            }))
+            .send()
+    };
+    let resp = create("Wrong forum", Some(elsewhere)).await.unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = create("Asking", Some(question)).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["flair"]["id"], question);
+    assert_eq!(body["data"]["flair"]["name"], "Question");
+    assert_eq!(create("Plain", None).await.unwrap().status(), 200);
+
+    assert_eq!(
+        listed_titles(&app, forum_id, &format!("?flair={}", question)).await,
+        vec!["Asking"]
+    );
+    assert_eq!(
+        listed_titles(&app, forum_id, &format!("?flair={}&sort=top", question)).await,
+        vec!["Asking"]
+    );
+    assert_eq!(listed_titles(&app, forum_id, "").await.len(), 2);
+
+    // Editing without a flair removes it
+    let resp = app
+        .client
+        .put(app.url(&format!("/posts/{}", post_id)))
+        .bearer_auth(&admin)
+        .json(&serde_json::json!({ "title": "Asking", "content": "Edited" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"]["flair"].is_null());
+    assert!(
+        listed_titles(&app, forum_id, &format!("?flair={}", question))
+            .await
+            .is_empty()
+    );
+
+    // Deleting a flair leaves its posts unflaired
+    let resp = app
+        .client
+        .put(app.url(&format!("/posts/{}", post_id)))
+        .bearer_auth(&admin)
+        .json(&serde_json::json!({
+            "title": "Asking",
+            "content": "Edited",
+            "flair_id": question,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/flairs/{}", question)))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .get(app.url(&format!("/posts/{}", post_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"]["flair_id"].is_null());
+    assert!(body["data"]["flair"].is_null());
+}
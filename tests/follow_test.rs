@@ -59,3 +59,344 @@ async fn self_follow_error() {
         .unwrap();
     assert!(resp.status().is_client_error());
 }
+
+#[tokio::test]
+async fn follow_lists_show_the_requesters_follow_state() {
+    let app = common::spawn_app().await;
+    let (celebrity_id, celebrity) = common::create_test_user(&app, "celebrity").await;
+    let (fan_id, fan) = common::create_test_user(&app, "fan").await;
+    let (_, friend) = common::create_test_user(&app, "friend").await;
+
+    for token in [&fan, &friend] {
+        let resp = app
+            .client
+            .put(app.url(&format!("/users/{}/follow", celebrity_id)))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+    // Mutual with the fan only
+    let resp = app
+        .client
+        .put(app.url(&format!("/users/{}/follow", fan_id)))
+        .bearer_auth(&celebrity)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let followers = |token: Option<&str>, page: u64| {
+        let mut req = app.client.get(app.url(&format!(
+            "/users/{}/followers?page={}&per_page=1",
+            celebrity_id, page
+        )));
+        if let Some(token) = token {
+            req = req.bearer_auth(token);
+        }
+        async move { req.send().await.unwrap().json::<Value>().await.unwrap() }
+    };
+
+    // Newest first, one per page
+    let body = followers(Some(&celebrity), 1).await;
+    assert_eq!(body["data"]["total"], 2);
+    assert_eq!(body["data"]["total_pages"], 2);
+    let item = &body["data"]["items"][0];
+    assert!(item["username"].as_str().unwrap().starts_with("friend"));
+    assert_eq!(item["follows_you"], true);
+    assert_eq!(item["followed_by_you"], false);
+
+    let body = followers(Some(&celebrity), 2).await;
+    let item = &body["data"]["items"][0];
+    assert_eq!(item["id"], fan_id);
+    assert_eq!(item["follows_you"], true);
+    assert_eq!(item["followed_by_you"], true);
+
+    // From the fan's point of view the friend is a stranger
+    let body = followers(Some(&fan), 1).await;
+    let item = &body["data"]["items"][0];
+    assert_eq!(item["follows_you"], false);
+    assert_eq!(item["followed_by_you"], false);
+
+    // Anonymous requests get no flags
+    let body = followers(None, 1).await;
+    assert!(body["data"]["items"][0].get("follows_you").is_none());
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}/following", fan_id)))
+        .bearer_auth(&celebrity)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 1);
+    let item = &body["data"]["items"][0];
+    assert_eq!(item["id"], celebrity_id);
+    assert_eq!(item["follows_you"], false);
+    assert_eq!(item["followed_by_you"], false);
+}
+
+async fn notification_kinds(app: &common::TestApp, token: &str) -> Vec<String> {
+    let resp = app
+        .client
+        .get(app.url("/notifications"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| n["kind"].as_str().unwrap().to_string())
+        .collect()
+}
+
+async fn set_private(app: &common::TestApp, token: &str, is_private: bool) {
+    let resp = app
+        .client
+        .put(app.url("/auth/profile"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "is_private": is_private }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["is_private"], is_private);
+}
+
+#[tokio::test]
+async fn private_profiles_approve_follow_requests() {
+    let app = common::spawn_app().await;
+    let (private_id, private) = common::create_test_user(&app, "private").await;
+    let (_, approved) = common::create_test_user(&app, "approved").await;
+    let (_, denied) = common::create_test_user(&app, "denied").await;
+    let (_, waiting) = common::create_test_user(&app, "waiting").await;
+    set_private(&app, &private, true).await;
+
+    let follow = |token: &str| {
+        app.client
+            .put(app.url(&format!("/users/{}/follow", private_id)))
+            .bearer_auth(token.to_string())
+            .send()
+    };
+    for token in [&approved, &denied, &waiting] {
+        let body: Value = follow(token).await.unwrap().json().await.unwrap();
+        assert_eq!(body["data"]["following"], false);
+        assert_eq!(body["data"]["requested"], true);
+    }
+    // Asking again doesn't notify again
+    follow(&approved).await.unwrap();
+    let kinds = notification_kinds(&app, &private).await;
+    assert_eq!(kinds.iter().filter(|k| *k == "follow_request").count(), 3);
+
+    let followers = app
+        .client
+        .get(app.url(&format!("/users/{}/followers", private_id)))
+        .bearer_auth(&approved)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = followers.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 0);
+
+    let resp = app
+        .client
+        .get(app.url("/follow-requests"))
+        .bearer_auth(&private)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 3);
+    let request_id = |prefix: &str| {
+        body["data"]["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|r| {
+                r["requester"]["username"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with(prefix)
+            })
+            .unwrap()["id"]
+            .as_i64()
+            .unwrap()
+    };
+    let (approve_id, deny_id) = (request_id("approved"), request_id("denied"));
+
+    // Only the target decides
+    let resp = app
+        .client
+        .post(app.url(&format!("/follow-requests/{}/approve", approve_id)))
+        .bearer_auth(&approved)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    for (id, action) in [(approve_id, "approve"), (deny_id, "deny")] {
+        let resp = app
+            .client
+            .post(app.url(&format!("/follow-requests/{}/{}", id, action)))
+            .bearer_auth(&private)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+    assert!(notification_kinds(&app, &approved)
+        .await
+        .contains(&"follow_request_approved".to_string()));
+    assert!(notification_kinds(&app, &denied)
+        .await
+        .contains(&"follow_request_denied".to_string()));
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}/followers", private_id)))
+        .bearer_auth(&waiting)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 1);
+    assert!(body["data"]["items"][0]["username"]
+        .as_str()
+        .unwrap()
+        .starts_with("approved"));
+
+    // Going public approves the rest
+    set_private(&app, &private, false).await;
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}/followers", private_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 2);
+    assert!(notification_kinds(&app, &waiting)
+        .await
+        .contains(&"follow_request_approved".to_string()));
+
+    // A withdrawn request is gone
+    set_private(&app, &private, true).await;
+    let body: Value = follow(&denied).await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"]["requested"], true);
+    let resp = app
+        .client
+        .delete(app.url(&format!("/users/{}/follow", private_id)))
+        .bearer_auth(&denied)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["requested"], false);
+    let resp = app
+        .client
+        .get(app.url("/follow-requests"))
+        .bearer_auth(&private)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 0);
+}
+
+#[tokio::test]
+async fn followers_are_notified_of_new_posts_from_the_queue() {
+    use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+    use xjy::models::{post_fanout, PostFanout};
+    use xjy::services::post_fanout::PostFanoutQueue;
+    use xjy::websocket::hub::NotificationHub;
+
+    let app = common::spawn_app().await;
+    let (author_id, author) = common::create_test_user(&app, "author").await;
+    common::make_admin(&app.db, author_id).await;
+    let slug = common::create_test_forum(&app, &author).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let (_, fan) = common::create_test_user(&app, "fan").await;
+    let (_, other_fan) = common::create_test_user(&app, "other_fan").await;
+    let (_, stranger) = common::create_test_user(&app, "stranger").await;
+    for token in [&fan, &other_fan] {
+        let resp = app
+            .client
+            .post(app.url(&format!("/users/{}/follow", author_id)))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    let create_post = |token: &str, title: &str| {
+        app.client
+            .post(app.url("/posts"))
+            .bearer_auth(token)
+            .json(&serde_json::json!({
+                "forum_id": forum_id,
+                "title": title,
+                "content": "Fresh content",
+            }))
+            .send()
+    };
+    let resp = create_post(&author, "Fresh post").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap() as i32;
+
+    // Queued rather than sent while creating the post
+    let queued = || {
+        PostFanout::find()
+            .filter(post_fanout::Column::PostId.eq(post_id))
+            .count(&app.db)
+    };
+    assert_eq!(queued().await.unwrap(), 1);
+
+    // Nobody follows the stranger, so nothing is queued for their post
+    let resp = create_post(&stranger, "Quiet post").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let quiet_id = body["data"]["id"].as_i64().unwrap() as i32;
+    let quiet = PostFanout::find()
+        .filter(post_fanout::Column::PostId.eq(quiet_id))
+        .count(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(quiet, 0);
+
+    let sent = PostFanoutQueue::new(app.db.clone(), NotificationHub::new())
+        .process_queue()
+        .await
+        .unwrap();
+    assert!(sent >= 2, "{}", sent);
+    assert_eq!(queued().await.unwrap(), 0);
+
+    for token in [&fan, &other_fan] {
+        let resp = app
+            .client
+            .get(app.url("/notifications?type=new_post_from_follow"))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        let items = body["data"]["items"].as_array().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["target_type"], "post");
+        assert_eq!(items[0]["target_id"], post_id);
+    }
+    assert!(!notification_kinds(&app, &stranger)
+        .await
+        .contains(&"new_post_from_follow".to_string()));
+    assert!(!notification_kinds(&app, &author)
+        .await
+        .contains(&"new_post_from_follow".to_string()));
+}
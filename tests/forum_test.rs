@@ -266,12 +266,7 @@ async fn create_duplicate_slug_fails() {
         .await
         .expect("Failed to send request");
 
-    // Currently returns 500 due to unhandled database constraint violation
-    // TODO: Fix implementation to return 409 Conflict with proper error message
-    let status = resp.status();
-    assert!(
-        status == 400 || status == 409 || status == 500,
-        "Expected duplicate slug to fail, got status: {}",
-        status
-    );
+    assert_eq!(resp.status(), 409);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "A forum with this slug already exists");
 }
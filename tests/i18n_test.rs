@@ -0,0 +1,135 @@
+mod common;
+
+use serde_json::Value;
+
+#[tokio::test]
+async fn errors_carry_codes_and_follow_accept_language() {
+    let app = common::spawn_app().await;
+
+    let resp = app
+        .client
+        .get(app.url("/posts/999999999"))
+        .header("accept-language", "zh-CN,zh;q=0.9,en;q=0.8")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    assert_eq!(resp.headers()["content-language"], "zh");
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "not_found");
+    assert_eq!(body["error"], "资源不存在");
+
+    // Unsupported languages fall back to English
+    let resp = app
+        .client
+        .get(app.url("/nope"))
+        .header("accept-language", "fr")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-language"], "en");
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "route_not_found");
+    assert_eq!(body["error"], "Route not found");
+
+    // Catalogued messages get their own code, others the variant's
+    let (_, token) = common::create_test_user(&app, "polyglot").await;
+    let username = {
+        let resp = app
+            .client
+            .get(app.url("/auth/me"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        body["data"]["username"].as_str().unwrap().to_string()
+    };
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .header("accept-language", "zh")
+        .json(&serde_json::json!({
+            "username": username,
+            "email": "another-polyglot@test.com",
+            "password": "test_password_123",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "username_or_email_taken");
+    assert_eq!(body["error"], "用户名或邮箱已存在");
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}/posts?sort=sideways", username)))
+        .header("accept-language", "zh")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "validation_failed");
+}
+
+#[tokio::test]
+async fn signed_in_users_get_messages_in_their_locale() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "zhreader").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let resp = app
+        .client
+        .put(app.url("/auth/preferences"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "locale": "zh" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Short lived",
+            "content": "Content",
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+
+    // The user's locale wins over the browser's
+    let resp = app
+        .client
+        .delete(app.url(&format!("/posts/{}", post_id)))
+        .bearer_auth(&token)
+        .header("accept-language", "en")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-language"], "zh");
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"], "帖子已删除");
+
+    let resp = app
+        .client
+        .post(app.url(&format!("/users/{}/follow", user_id)))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "cannot_follow_self");
+    assert_eq!(body["error"], "不能关注自己");
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use serde_json::Value;
+
+#[tokio::test]
+async fn admin_impersonation_is_scoped_and_audited() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "impadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (other_admin_id, _) = common::create_test_user(&app, "impadmin2").await;
+    common::make_admin(&app.db, other_admin_id).await;
+    let (mod_id, mod_token) = common::create_test_user(&app, "impmod").await;
+    common::make_moderator(&app.db, mod_id).await;
+    let (user_id, _) = common::create_test_user(&app, "impuser").await;
+
+    let impersonate = |target: i32, token: String, body: Option<Value>| {
+        let app = &app;
+        async move {
+            let mut req = app
+                .client
+                .post(app.url(&format!("/admin/impersonate/{}", target)))
+                .bearer_auth(token);
+            if let Some(body) = body {
+                req = req.json(&body);
+            }
+            req.send().await.unwrap()
+        }
+    };
+
+    // Only admins, and never against another admin
+    let resp = impersonate(user_id, mod_token.clone(), None).await;
+    assert_eq!(resp.status(), 403);
+    let resp = impersonate(other_admin_id, admin_token.clone(), None).await;
+    assert_eq!(resp.status(), 400);
+    let resp = impersonate(
+        user_id,
+        admin_token.clone(),
+        Some(serde_json::json!({ "mode": "god" })),
+    )
+    .await;
+    assert_eq!(resp.status(), 400);
+
+    // Read-only by default
+    let resp = impersonate(user_id, admin_token.clone(), None).await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["mode"], "read_only");
+    assert_eq!(body["data"]["user"]["id"].as_i64(), Some(user_id as i64));
+    let read_only_token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&read_only_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers()["x-impersonated-by"].to_str().unwrap(),
+        admin_id.to_string()
+    );
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["id"].as_i64(), Some(user_id as i64));
+
+    let resp = app
+        .client
+        .put(app.url("/notifications/read-all"))
+        .bearer_auth(&read_only_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    // Full mode allows writes
+    let resp = impersonate(
+        user_id,
+        admin_token.clone(),
+        Some(serde_json::json!({ "mode": "full" })),
+    )
+    .await;
+    let body: Value = resp.json().await.unwrap();
+    let full_token = body["data"]["token"].as_str().unwrap().to_string();
+    let resp = app
+        .client
+        .put(app.url("/notifications/read-all"))
+        .bearer_auth(&full_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // The impersonated user has no admin powers
+    let resp = app
+        .client
+        .get(app.url("/admin/users"))
+        .bearer_auth(&full_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/admin/audit-log?actor_id={}", admin_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let entries = body["data"]["items"].as_array().unwrap();
+    let summary: Vec<(String, Option<String>, Option<i64>)> = entries
+        .iter()
+        .map(|e| {
+            (
+                e["action"].as_str().unwrap().to_string(),
+                e["path"].as_str().map(str::to_string),
+                e["status"].as_i64(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                "impersonated_request".to_string(),
+                Some("/api/v1/admin/users".to_string()),
+                Some(403)
+            ),
+            (
+                "impersonated_request".to_string(),
+                Some("/api/v1/notifications/read-all".to_string()),
+                Some(200)
+            ),
+            ("impersonation_started".to_string(), None, None),
+            (
+                "impersonated_request".to_string(),
+                Some("/api/v1/notifications/read-all".to_string()),
+                Some(403)
+            ),
+            (
+                "impersonated_request".to_string(),
+                Some("/api/v1/auth/me".to_string()),
+                Some(200)
+            ),
+            ("impersonation_started".to_string(), None, None),
+        ]
+    );
+    assert!(entries
+        .iter()
+        .all(|e| e["target_user_id"].as_i64() == Some(user_id as i64)));
+
+    // Tokens die with the admin's privileges
+    common::make_moderator(&app.db, admin_id).await;
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&read_only_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+}
@@ -247,7 +247,7 @@ async fn post_with_comments_and_votes() {
 
     let body: Value = resp.json().await.unwrap();
     let comments = body["data"].as_array().unwrap();
-    assert!(comments.len() > 0);
+    assert!(!comments.is_empty());
 }
 
 /// Report and moderation workflow
@@ -426,7 +426,7 @@ async fn social_interaction_workflow() {
         panic!("Unexpected response structure: {}", body);
     };
 
-    assert!(bookmarks.len() > 0);
+    assert!(!bookmarks.is_empty());
 
     // Verify follow relationship
     let resp = app
@@ -448,7 +448,7 @@ async fn social_interaction_workflow() {
         panic!("Unexpected response structure: {}", body);
     };
 
-    assert!(following.len() > 0);
+    assert!(!following.is_empty());
 }
 
 /// Cascade deletion verification
@@ -842,7 +842,7 @@ async fn tag_filtering_workflow() {
         panic!("Unexpected response structure: {}", body);
     };
 
-    assert!(posts.len() > 0);
+    assert!(!posts.is_empty());
 }
 
 /// User profile completeness workflow
@@ -994,7 +994,7 @@ async fn pagination_workflow() {
         panic!("Unexpected response structure: {}", body);
     };
 
-    assert!(page2.len() > 0);
+    assert!(!page2.is_empty());
 
     // Verify pages are different
     let page1_ids: Vec<i64> = page1.iter().filter_map(|p| p["id"].as_i64()).collect();
@@ -1078,3 +1078,78 @@ async fn logout_workflow() {
         status
     );
 }
+
+#[tokio::test]
+async fn health_probes_report_dependencies() {
+    let app = common::spawn_app().await;
+
+    let resp = app
+        .client
+        .get(format!("{}/healthz", app.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+
+    let resp = app
+        .client
+        .get(format!("{}/readyz", app.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["database"]["ok"], true);
+    assert!(body["database"]["latency_ms"].is_u64());
+    let pool = &body["pool"];
+    assert_eq!(pool["max_connections"], 10);
+    assert!(pool["size"].as_u64().unwrap() >= 1);
+    assert_eq!(
+        pool["in_use"].as_u64().unwrap() + pool["idle"].as_u64().unwrap(),
+        pool["size"].as_u64().unwrap()
+    );
+    assert!(pool["utilization"].is_f64());
+    assert_eq!(body["migrations"]["pending"], 0);
+    // No Redis in tests
+    assert!(body.get("redis").is_none());
+}
+
+#[tokio::test]
+async fn unknown_routes_return_json_errors() {
+    let app = common::spawn_app().await;
+
+    for path in ["/nope", "/api/v1/nope", "/api/v2/posts/1/nope"] {
+        let resp = app
+            .client
+            .get(format!("{}{}", app.addr, path))
+            .header("x-request-id", "req-404")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 404, "{}", path);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["error"], "Route not found");
+        assert_eq!(body["request_id"], "req-404");
+    }
+
+    let resp = app.client.patch(app.url("/search")).send().await.unwrap();
+    assert_eq!(resp.status(), 405);
+    assert!(resp.headers()["allow"].to_str().unwrap().contains("GET"));
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "Method not allowed");
+    assert!(body.get("request_id").is_none());
+
+    // Handlers' own 404s are untouched
+    let resp = app
+        .client
+        .get(app.url("/posts/999999999"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "Resource not found");
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use serde_json::Value;
+
+async fn set_maintenance(app: &common::TestApp, token: &str, maintenance: Value) -> Value {
+    let resp = app
+        .client
+        .put(app.url("/admin/settings"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "maintenance": maintenance }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["maintenance"].clone()
+}
+
+async fn create_post(app: &common::TestApp, token: &str, forum_id: i32) -> reqwest::Response {
+    app.client
+        .post(app.url("/posts"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Maintenance post",
+            "content": "Written during maintenance"
+        }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn maintenance_mode_blocks_non_admin_writes() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "mntadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (_, user_token) = common::create_test_user(&app, "mntuser").await;
+    let slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let data = set_maintenance(
+        &app,
+        &admin_token,
+        serde_json::json!({ "enabled": true, "message": "Upgrading", "retry_after_seconds": 120 }),
+    )
+    .await;
+    assert_eq!(data["enabled"], true);
+    assert_eq!(data["retry_after_seconds"], 120);
+
+    // Non-admin writes are refused with a friendly message
+    let resp = create_post(&app, &user_token, forum_id).await;
+    assert_eq!(resp.status(), 503);
+    assert_eq!(resp.headers()["retry-after"], "120");
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "Upgrading");
+
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .json(&serde_json::json!({
+            "username": "mntnewbie",
+            "email": "mntnewbie@test.com",
+            "password": "test_password_123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 503);
+
+    // Reads, logging in and admin writes still work
+    let resp = app
+        .client
+        .get(app.url("/forums"))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    let me: Value = resp.json().await.unwrap();
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({
+            "username": me["data"]["username"],
+            "password": "test_password_123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = create_post(&app, &admin_token, forum_id).await;
+    assert_eq!(resp.status(), 200);
+
+    // Clearing the message restores the default notice
+    set_maintenance(&app, &admin_token, serde_json::json!({ "message": "" })).await;
+    let resp = create_post(&app, &user_token, forum_id).await;
+    assert_eq!(resp.status(), 503);
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("maintenance"));
+
+    set_maintenance(&app, &admin_token, serde_json::json!({ "enabled": false })).await;
+    let resp = create_post(&app, &user_token, forum_id).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn maintenance_settings_are_validated() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "mntvalid").await;
+    common::make_admin(&app.db, admin_id).await;
+
+    let resp = app
+        .client
+        .put(app.url("/admin/settings"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "maintenance": { "retry_after_seconds": 0 } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
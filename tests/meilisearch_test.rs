@@ -0,0 +1,140 @@
+mod common;
+
+use axum::{
+    extract::{Path, State},
+    routing, Json, Router,
+};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+type Documents = Arc<Mutex<BTreeMap<i64, Value>>>;
+
+/// Minimal stand-in for the Meilisearch index API: stores documents and
+/// answers searches with a case-insensitive substring match on the title.
+async fn spawn_fake_meilisearch() -> (String, Documents) {
+    let docs: Documents = Arc::new(Mutex::new(BTreeMap::new()));
+
+    let app = Router::new()
+        .route(
+            "/indexes/posts/settings",
+            routing::patch(|| async { Json(serde_json::json!({ "taskUid": 1 })) }),
+        )
+        .route(
+            "/indexes/posts/documents",
+            routing::post(
+                |State(docs): State<Documents>, Json(body): Json<Vec<Value>>| async move {
+                    let mut docs = docs.lock().unwrap();
+                    for doc in body {
+                        docs.insert(doc["id"].as_i64().unwrap(), doc);
+                    }
+                    Json(serde_json::json!({ "taskUid": 2 }))
+                },
+            )
+            .delete(|State(docs): State<Documents>| async move {
+                docs.lock().unwrap().clear();
+                Json(serde_json::json!({ "taskUid": 3 }))
+            }),
+        )
+        .route(
+            "/indexes/posts/documents/{id}",
+            routing::delete(
+                |State(docs): State<Documents>, Path(id): Path<i64>| async move {
+                    docs.lock().unwrap().remove(&id);
+                    Json(serde_json::json!({ "taskUid": 4 }))
+                },
+            ),
+        )
+        .route(
+            "/indexes/posts/search",
+            routing::post(
+                |State(docs): State<Documents>, Json(body): Json<Value>| async move {
+                    let q = body["q"].as_str().unwrap_or("").to_lowercase();
+                    let hits: Vec<Value> = docs
+                        .lock()
+                        .unwrap()
+                        .values()
+                        .filter(|d| d["title"].as_str().unwrap().to_lowercase().contains(&q))
+                        .map(|d| serde_json::json!({ "id": d["id"] }))
+                        .collect();
+                    Json(serde_json::json!({
+                        "estimatedTotalHits": hits.len(),
+                        "hits": hits,
+                    }))
+                },
+            ),
+        )
+        .with_state(docs.clone());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}", addr), docs)
+}
+
+#[tokio::test]
+async fn meilisearch_backend_syncs_posts_and_reindexes() {
+    let (url, docs) = spawn_fake_meilisearch().await;
+    std::env::set_var("SEARCH_BACKEND", "meilisearch");
+    std::env::set_var("MEILISEARCH_URL", url);
+    let app = common::spawn_app().await;
+
+    let (admin_id, admin_token) = common::create_test_user(&app, "meiliadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let forum_slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &forum_slug).await;
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({
+            "title": "Typo tolerant search",
+            "content": "Indexed in Meilisearch",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    assert!(docs.lock().unwrap().contains_key(&post_id));
+
+    let resp = app
+        .client
+        .get(app.url("/search?q=tolerant"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 1);
+    assert_eq!(body["data"]["items"][0]["id"], post_id);
+
+    // Rebuild from the database after the index was lost
+    docs.lock().unwrap().clear();
+    let resp = app
+        .client
+        .post(app.url("/admin/search/reindex"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["backend"], "meilisearch");
+    assert_eq!(body["data"]["indexed"], 1);
+    assert!(docs.lock().unwrap().contains_key(&post_id));
+
+    let resp = app
+        .client
+        .delete(app.url(&format!("/posts/{}", post_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(!docs.lock().unwrap().contains_key(&post_id));
+}
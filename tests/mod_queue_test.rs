@@ -0,0 +1,211 @@
+mod common;
+
+use serde_json::Value;
+
+async fn queue(app: &common::TestApp, token: &str, query: &str) -> Value {
+    let resp = app
+        .client
+        .get(app.url(&format!("/mod/queue{}", query)))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"].clone()
+}
+
+async fn report_post(app: &common::TestApp, token: &str, post_id: i64, reason: &str) -> i64 {
+    let resp = app
+        .client
+        .post(app.url("/reports"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "target_type": "post",
+            "target_id": post_id,
+            "reason": reason
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["id"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn moderators_claim_queue_items() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "queueadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (mod_a_id, mod_a_token) = common::create_test_user(&app, "moda").await;
+    common::make_moderator(&app.db, mod_a_id).await;
+    let (mod_b_id, mod_b_token) = common::create_test_user(&app, "modb").await;
+    common::make_moderator(&app.db, mod_b_id).await;
+    let (user_id, user_token) = common::create_test_user(&app, "queueuser").await;
+    let (_, reporter_token) = common::create_test_user(&app, "queuereporter").await;
+
+    let slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({
+            "title": "Questionable post",
+            "content": "Buy now",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+
+    let spam = report_post(&app, &user_token, post_id, "spam").await;
+    let other = report_post(&app, &reporter_token, post_id, "other").await;
+
+    // Regular users can't see the queue
+    let resp = app
+        .client
+        .get(app.url("/mod/queue"))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let data = queue(&app, &mod_a_token, "").await;
+    assert_eq!(data["total"], 2);
+    let items = data["items"].as_array().unwrap();
+    assert_eq!(items[0]["item_type"], "report");
+    assert_eq!(items[0]["item_id"].as_i64(), Some(spam));
+    assert_eq!(items[0]["summary"], "spam");
+    assert_eq!(items[0]["target_id"].as_i64(), Some(post_id));
+    assert_eq!(items[0]["claimed_by"], Value::Null);
+
+    let resp = app
+        .client
+        .get(app.url("/mod/queue?type=automod"))
+        .bearer_auth(&mod_a_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // Moderator A claims the spam report; B can neither claim nor resolve it
+    let claim_url = app.url(&format!("/mod/queue/report/{}/claim", spam));
+    let resp = app
+        .client
+        .post(&claim_url)
+        .bearer_auth(&mod_a_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["claimed_by"].as_i64(), Some(mod_a_id as i64));
+
+    let resp = app
+        .client
+        .post(&claim_url)
+        .bearer_auth(&mod_b_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/reports/{}/resolve", spam)))
+        .bearer_auth(&mod_b_token)
+        .json(&serde_json::json!({ "action": "dismiss" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+
+    let resp = app
+        .client
+        .delete(&claim_url)
+        .bearer_auth(&mod_b_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+
+    let mine = queue(&app, &mod_a_token, "?claim=mine").await;
+    assert_eq!(mine["total"], 1);
+    assert!(mine["items"][0]["claimed_by_username"]
+        .as_str()
+        .unwrap()
+        .starts_with("moda_"));
+    assert!(mine["items"][0]["claim_expires_at"].is_string());
+    let unclaimed = queue(&app, &mod_b_token, "?claim=unclaimed").await;
+    assert_eq!(unclaimed["total"], 1);
+    assert_eq!(unclaimed["items"][0]["item_id"].as_i64(), Some(other));
+
+    // Admins can reassign; the new holder can act and the item leaves the queue
+    let resp = app
+        .client
+        .put(app.url(&format!("/mod/queue/report/{}/assign", spam)))
+        .bearer_auth(&mod_a_token)
+        .json(&serde_json::json!({ "user_id": mod_b_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/mod/queue/report/{}/assign", spam)))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "user_id": user_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/mod/queue/report/{}/assign", spam)))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "user_id": mod_b_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/admin/reports/{}/resolve", spam)))
+        .bearer_auth(&mod_b_token)
+        .json(&serde_json::json!({ "action": "dismiss" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let data = queue(&app, &mod_a_token, "?type=report").await;
+    assert_eq!(data["total"], 1);
+    assert_eq!(data["items"][0]["item_id"].as_i64(), Some(other));
+
+    // Handled items can't be claimed; releasing an unclaimed item is fine
+    let resp = app
+        .client
+        .post(&claim_url)
+        .bearer_auth(&mod_a_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    let resp = app
+        .client
+        .delete(app.url(&format!("/mod/queue/report/{}/claim", other)))
+        .bearer_auth(&mod_a_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
@@ -0,0 +1,19 @@
+mod common;
+
+/// The same app against the MySQL database at `MYSQL_TEST_DATABASE_URL`
+/// (run by CI against a MySQL service). Skipped when it isn't set.
+async fn spawn_mysql_app() -> Option<common::TestApp> {
+    let url = std::env::var("MYSQL_TEST_DATABASE_URL").ok()?;
+    std::env::set_var("TEST_DATABASE_URL", url);
+    std::env::set_var("POW_REQUIRED_ACTIONS", "");
+    Some(common::spawn_app().await)
+}
+
+#[tokio::test]
+async fn core_flows_run_on_mysql() {
+    let Some(app) = spawn_mysql_app().await else {
+        eprintln!("MYSQL_TEST_DATABASE_URL not set; skipping");
+        return;
+    };
+    common::flows::core_flows(&app).await;
+}
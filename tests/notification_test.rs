@@ -135,7 +135,7 @@ async fn mark_notification_read() {
             .unwrap();
 
         let body: Value = resp.json().await.unwrap();
-        assert_eq!(body["data"]["read"].as_bool().unwrap(), true);
+        assert!(body["data"]["read"].as_bool().unwrap());
     }
 }
 
@@ -318,3 +318,417 @@ async fn notifications_ordered_by_created_at() {
         assert!(first_time >= second_time);
     }
 }
+
+#[tokio::test]
+async fn watched_post_notifies_on_new_comments() {
+    let app = common::spawn_app().await;
+    let (author_id, author_token) = common::create_test_user(&app, "watchauthor").await;
+    common::make_admin(&app.db, author_id).await;
+    let (_, watcher_token) = common::create_test_user(&app, "watcher").await;
+    let (_, commenter_token) = common::create_test_user(&app, "commenter").await;
+    let (_, quiet_token) = common::create_test_user(&app, "quiet").await;
+
+    let forum_slug = common::create_test_forum(&app, &author_token).await;
+    let forum_id = common::get_forum_id(&app, &forum_slug).await;
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&author_token)
+        .json(&serde_json::json!({
+            "title": "Watch me",
+            "content": "Content",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+
+    let resp = app
+        .client
+        .post(app.url(&format!("/posts/{}/watch", post_id)))
+        .bearer_auth(&watcher_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["watching"], true);
+
+    // Opt out of auto-watching before commenting
+    let resp = app
+        .client
+        .put(app.url("/auth/profile"))
+        .bearer_auth(&quiet_token)
+        .json(&serde_json::json!({ "auto_watch": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let comment = |token: &str| {
+        app.client
+            .post(app.url("/comments"))
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "post_id": post_id, "content": "A comment" }))
+            .send()
+    };
+    comment(&quiet_token).await.unwrap();
+    comment(&commenter_token).await.unwrap();
+
+    let watched_kinds = |token: String| {
+        let req = app.client.get(app.url("/notifications")).bearer_auth(token);
+        async move {
+            let body: Value = req.send().await.unwrap().json().await.unwrap();
+            get_notifications(&body)
+                .into_iter()
+                .filter(|n| n["kind"] == "comment_on_watched_post")
+                .count()
+        }
+    };
+    // The explicit watcher hears about both comments
+    assert_eq!(watched_kinds(watcher_token.clone()).await, 2);
+    // The first commenter auto-watched; the opted-out one did not
+    assert_eq!(watched_kinds(commenter_token.clone()).await, 0);
+    comment(&watcher_token).await.unwrap();
+    assert_eq!(watched_kinds(commenter_token.clone()).await, 1);
+    assert_eq!(watched_kinds(quiet_token.clone()).await, 0);
+    // The author is notified through comment_on_post instead
+    assert_eq!(watched_kinds(author_token.clone()).await, 0);
+
+    let resp = app
+        .client
+        .delete(app.url(&format!("/posts/{}/watch", post_id)))
+        .bearer_auth(&watcher_token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["watching"], false);
+    comment(&commenter_token).await.unwrap();
+    assert_eq!(watched_kinds(watcher_token.clone()).await, 2);
+}
+
+#[tokio::test]
+async fn filter_and_delete_notifications() {
+    let app = common::spawn_app().await;
+    let (author_id, author) = common::create_test_user(&app, "author").await;
+    let (_, commenter) = common::create_test_user(&app, "commenter").await;
+    common::make_admin(&app.db, author_id).await;
+    let forum_slug = common::create_test_forum(&app, &author).await;
+    let forum_id = common::get_forum_id(&app, &forum_slug).await;
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&author)
+        .json(&serde_json::json!({
+            "title": "Filtered",
+            "content": "Content",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+
+    for content in ["First", "Second"] {
+        let resp = app
+            .client
+            .post(app.url("/comments"))
+            .bearer_auth(&commenter)
+            .json(&serde_json::json!({ "post_id": post_id, "content": content }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+    // The author's own comment doesn't notify them, the reply to it does twice
+    let resp = app
+        .client
+        .post(app.url("/comments"))
+        .bearer_auth(&author)
+        .json(&serde_json::json!({ "post_id": post_id, "content": "Thanks" }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let parent_id = body["data"]["id"].as_i64().unwrap();
+    let resp = app
+        .client
+        .post(app.url("/comments"))
+        .bearer_auth(&commenter)
+        .json(&serde_json::json!({
+            "post_id": post_id,
+            "parent_id": parent_id,
+            "content": "Welcome"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let list = |query: &'static str| {
+        let req = app
+            .client
+            .get(app.url(&format!("/notifications{}", query)))
+            .bearer_auth(&author);
+        async move {
+            let body: Value = req.send().await.unwrap().json().await.unwrap();
+            get_notifications(&body)
+        }
+    };
+
+    assert_eq!(list("").await.len(), 4);
+    let comments = list("?type=comment_on_post").await;
+    assert_eq!(comments.len(), 3);
+    assert!(comments.iter().all(|n| n["kind"] == "comment_on_post"));
+    assert_eq!(
+        list("?type=comment_on_post,reply_to_comment").await.len(),
+        4
+    );
+    assert_eq!(list("?type=mention").await.len(), 0);
+
+    let read_id = comments[0]["id"].as_i64().unwrap();
+    let resp = app
+        .client
+        .put(app.url(&format!("/notifications/{}/read", read_id)))
+        .bearer_auth(&author)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let unread = list("?unread=true&type=comment_on_post").await;
+    assert_eq!(unread.len(), 2);
+    assert!(unread.iter().all(|n| n["id"].as_i64().unwrap() != read_id));
+
+    // Only the owner may delete
+    let resp = app
+        .client
+        .delete(app.url(&format!("/notifications/{}", read_id)))
+        .bearer_auth(&commenter)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = app
+        .client
+        .delete(app.url(&format!("/notifications/{}", read_id)))
+        .bearer_auth(&author)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(list("").await.len(), 3);
+
+    let resp = app
+        .client
+        .delete(app.url("/notifications"))
+        .bearer_auth(&author)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["deleted"], 3);
+    assert_eq!(list("").await.len(), 0);
+}
+
+#[tokio::test]
+async fn mark_selected_notifications_read() {
+    let app = common::spawn_app().await;
+    let (author_id, author) = common::create_test_user(&app, "author").await;
+    let (_, commenter) = common::create_test_user(&app, "commenter").await;
+    common::make_admin(&app.db, author_id).await;
+    let forum_slug = common::create_test_forum(&app, &author).await;
+    let forum_id = common::get_forum_id(&app, &forum_slug).await;
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&author)
+        .json(&serde_json::json!({
+            "title": "Busy",
+            "content": "Content",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    for i in 0..4 {
+        let resp = app
+            .client
+            .post(app.url("/comments"))
+            .bearer_auth(&commenter)
+            .json(&serde_json::json!({ "post_id": post_id, "content": format!("Comment {}", i) }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    let unread = |token: String| {
+        let req = app
+            .client
+            .get(app.url("/notifications?unread=true"))
+            .bearer_auth(token);
+        async move {
+            let body: Value = req.send().await.unwrap().json().await.unwrap();
+            get_notifications(&body)
+        }
+    };
+    // Newest first
+    let notifications = unread(author.clone()).await;
+    assert_eq!(notifications.len(), 4);
+    let id = |i: usize| notifications[i]["id"].as_i64().unwrap();
+
+    let mark = |body: Value, token: String| {
+        app.client
+            .put(app.url("/notifications/read"))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+    };
+
+    // Someone else's ids are left alone
+    let resp = mark(serde_json::json!({ "ids": [id(0)] }), commenter.clone())
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["marked_read"], 0);
+
+    let resp = mark(serde_json::json!({ "ids": [id(0), id(1)] }), author.clone())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["marked_read"], 2);
+    let left: Vec<i64> = unread(author.clone())
+        .await
+        .iter()
+        .map(|n| n["id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(left, vec![id(2), id(3)]);
+
+    // Everything up to the oldest
+    // Timestamps are RFC 3339 UTC and can be sent back as is
+    let before = notifications[3]["created_at"].as_str().unwrap();
+    assert!(chrono::DateTime::parse_from_rfc3339(before).is_ok());
+    assert!(before.ends_with('Z'));
+    let resp = mark(serde_json::json!({ "before": before }), author.clone())
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["marked_read"], 1);
+    assert_eq!(unread(author.clone()).await.len(), 1);
+
+    for body in [
+        serde_json::json!({}),
+        serde_json::json!({ "ids": [] }),
+        serde_json::json!({ "before": "yesterday" }),
+    ] {
+        let resp = mark(body, author.clone()).await.unwrap();
+        assert_eq!(resp.status(), 400);
+    }
+}
+
+#[tokio::test]
+async fn quiet_hours_hold_pushes_until_they_end() {
+    use xjy::services::notification::NotificationService;
+    use xjy::services::quiet_hours::HeldNotifications;
+    use xjy::websocket::hub::NotificationHub;
+
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "sleeper").await;
+    let (actor_id, _) = common::create_test_user(&app, "night_owl").await;
+
+    let quiet_hours = |body: Value| {
+        app.client
+            .put(app.url("/notifications/quiet-hours"))
+            .bearer_auth(&token)
+            .json(&body)
+            .send()
+    };
+    let now = chrono::Utc::now();
+    let resp = quiet_hours(serde_json::json!({
+        "start": (now - chrono::Duration::hours(1)).format("%H:%M").to_string(),
+        "end": (now + chrono::Duration::hours(1)).format("%H:%M").to_string(),
+        "timezone": "UTC",
+    }))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["active"], true);
+    assert_eq!(body["data"]["timezone"], "UTC");
+
+    let hub = NotificationHub::new();
+    let mut pushes = hub.subscribe(user_id).messages;
+    let service = NotificationService::new(app.db.clone(), hub.clone());
+    for _ in 0..2 {
+        service
+            .notify(user_id, actor_id, "mention", "post", 1, "Late reply")
+            .await
+            .unwrap();
+    }
+    service
+        .notify_many(
+            &[user_id],
+            actor_id,
+            "announcement",
+            "announcement",
+            1,
+            "News",
+        )
+        .await
+        .unwrap();
+    // Saved, but not pushed
+    let resp = app
+        .client
+        .get(app.url("/notifications"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(get_notifications(&body).len(), 3);
+    assert!(pushes.try_recv().is_err());
+
+    let held = HeldNotifications::new(app.db.clone(), hub.clone());
+    held.release(now.naive_utc()).await.unwrap();
+    assert!(pushes.try_recv().is_err());
+
+    // Turning them off ends the window
+    let resp = quiet_hours(serde_json::json!({})).await.unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["start"], Value::Null);
+    assert_eq!(body["data"]["active"], false);
+    assert!(held.release(now.naive_utc()).await.unwrap() >= 3);
+    let summary: Value = serde_json::from_str(&pushes.try_recv().unwrap()).unwrap();
+    assert_eq!(summary["type"], "notification_summary");
+    assert_eq!(summary["payload"]["count"], 3);
+    assert_eq!(summary["payload"]["unread_count"], 3);
+    assert!(pushes.try_recv().is_err());
+
+    // Pushed right away again
+    service
+        .notify(user_id, actor_id, "mention", "post", 1, "Morning")
+        .await
+        .unwrap();
+    let push: Value = serde_json::from_str(&pushes.try_recv().unwrap()).unwrap();
+    assert_eq!(push["type"], "notification");
+
+    for body in [
+        serde_json::json!({ "start": "22:00" }),
+        serde_json::json!({ "start": "22:00", "end": "22:00" }),
+        serde_json::json!({ "start": "10pm", "end": "07:00" }),
+        serde_json::json!({ "start": "22:00", "end": "07:00", "timezone": "Mars/Olympus" }),
+    ] {
+        let resp = quiet_hours(body).await.unwrap();
+        assert_eq!(resp.status(), 400);
+    }
+}
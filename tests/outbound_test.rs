@@ -0,0 +1,83 @@
+mod common;
+
+fn no_redirect_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn signed_outbound_link_redirects() {
+    let app = common::spawn_app().await;
+    let target = "https://example.com/page?a=1&b=2";
+    let sig = xjy::utils::url_sign::sign_url(&xjy::utils::url_sign::url_signing_secret(), target);
+
+    let resp = no_redirect_client()
+        .get(format!("{}/out", app.addr))
+        .query(&[("url", target), ("sig", sig.as_str())])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), 303);
+    assert_eq!(resp.headers()["location"], target);
+}
+
+#[tokio::test]
+async fn unsigned_outbound_link_is_rejected() {
+    let app = common::spawn_app().await;
+
+    let resp = no_redirect_client()
+        .get(format!("{}/out", app.addr))
+        .query(&[("url", "https://evil.example/"), ("sig", "forged")])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn non_http_outbound_link_is_rejected() {
+    let app = common::spawn_app().await;
+    let target = "javascript:alert(1)";
+    let sig = xjy::utils::url_sign::sign_url(&xjy::utils::url_sign::url_signing_secret(), target);
+
+    let resp = no_redirect_client()
+        .get(format!("{}/out", app.addr))
+        .query(&[("url", target), ("sig", sig.as_str())])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn image_proxy_rejects_forged_and_private_urls() {
+    std::env::set_var("IMAGE_PROXY_ENABLED", "true");
+    let app = common::spawn_app().await;
+    let secret = xjy::utils::url_sign::url_signing_secret();
+
+    // Signature for a different URL
+    let forged = xjy::utils::url_sign::image_proxy_path(&secret, "https://example.com/a.png");
+    let (sig, _) = forged.trim_start_matches("/img/").split_once('/').unwrap();
+    let other = xjy::utils::url_sign::image_proxy_path(&secret, "https://example.com/b.png");
+    let (_, encoded) = other.trim_start_matches("/img/").split_once('/').unwrap();
+    let resp = no_redirect_client()
+        .get(format!("{}/img/{}/{}", app.addr, sig, encoded))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // Correctly signed, but pointing at a loopback address
+    let internal = xjy::utils::url_sign::image_proxy_path(&secret, "http://127.0.0.1:1/a.png");
+    let resp = no_redirect_client()
+        .get(format!("{}{}", app.addr, internal))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
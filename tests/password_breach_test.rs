@@ -0,0 +1,69 @@
+mod common;
+
+use sha1::{Digest, Sha1};
+
+const BREACHED_PASSWORD: &str = "correcthorsebattery";
+
+/// Serve a fake HaveIBeenPwned range API that knows one breached password.
+async fn spawn_fake_range_api() -> String {
+    let hash: String = Sha1::digest(BREACHED_PASSWORD.as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    let (prefix, suffix) = hash.split_at(5);
+    let prefix = prefix.to_string();
+    let body = format!("{}:42\r\n0000000000000000000000000000000000A:0", suffix);
+
+    let app = axum::Router::new().route(
+        "/range/{prefix}",
+        axum::routing::get(move |axum::extract::Path(p): axum::extract::Path<String>| {
+            let body = if p == prefix {
+                body.clone()
+            } else {
+                String::new()
+            };
+            async move { body }
+        }),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{}/range", addr)
+}
+
+#[tokio::test]
+async fn register_rejects_breached_password_when_enabled() {
+    let api_url = spawn_fake_range_api().await;
+    std::env::set_var("PASSWORD_BREACH_API_URL", api_url);
+    std::env::set_var("PASSWORD_BREACH_CHECK", "reject");
+    let app = common::spawn_app().await;
+
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .json(&serde_json::json!({
+            "username": "breached_user",
+            "email": "breached_user@test.com",
+            "password": BREACHED_PASSWORD
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .json(&serde_json::json!({
+            "username": "safe_user",
+            "email": "safe_user@test.com",
+            "password": "a-much-less-common-passphrase"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
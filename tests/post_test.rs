@@ -221,3 +221,422 @@ async fn wrong_user_cannot_update_post() {
         .unwrap();
     assert!(resp.status().is_client_error());
 }
+
+#[tokio::test]
+async fn forum_listing_tracks_unread_comments() {
+    let app = common::spawn_app().await;
+    let (token, _user_id, slug) = setup_forum(&app).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let (_, reader_token) = common::create_test_user(&app, "reader").await;
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Unread test",
+            "content": "Content"
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+
+    let listing = |token: Option<&str>| {
+        let mut req = app
+            .client
+            .get(app.url(&format!("/forums/{}/posts", forum_id)));
+        if let Some(token) = token {
+            req = req.bearer_auth(token);
+        }
+        async move {
+            let body: Value = req.send().await.unwrap().json().await.unwrap();
+            body["data"]["items"][0].clone()
+        }
+    };
+
+    // Anonymous listings carry no unread state
+    let item = listing(None).await;
+    assert!(item.get("is_unread").is_none());
+    assert!(item.get("unread_comment_count").is_none());
+
+    let item = listing(Some(&reader_token)).await;
+    assert_eq!(item["is_unread"], true);
+    assert_eq!(item["unread_comment_count"], 0);
+
+    let resp = app
+        .client
+        .put(app.url(&format!("/posts/{}/read", post_id)))
+        .bearer_auth(&reader_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let item = listing(Some(&reader_token)).await;
+    assert_eq!(item["is_unread"], false);
+    assert_eq!(item["unread_comment_count"], 0);
+
+    // A new comment by someone else makes it unread again
+    app.client
+        .post(app.url("/comments"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "post_id": post_id, "content": "New reply" }))
+        .send()
+        .await
+        .unwrap();
+    let item = listing(Some(&reader_token)).await;
+    assert_eq!(item["is_unread"], true);
+    assert_eq!(item["unread_comment_count"], 1);
+
+    let resp = app
+        .client
+        .put(app.url("/posts/999999/read"))
+        .bearer_auth(&reader_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn link_posts_carry_a_preview() {
+    use sea_orm::{ActiveModelTrait, Set};
+    use sha2::{Digest, Sha256};
+
+    let app = common::spawn_app().await;
+    let (token, _user_id, slug) = setup_forum(&app).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let create = |body: Value| {
+        let req = app
+            .client
+            .post(app.url("/posts"))
+            .bearer_auth(&token)
+            .json(&body);
+        async move {
+            let resp = req.send().await.unwrap();
+            let status = resp.status().as_u16();
+            let body: Value = resp.json().await.unwrap();
+            (status, body)
+        }
+    };
+
+    // Only http(s) links, and text posts still need content
+    let (status, _) = create(serde_json::json!({
+        "forum_id": forum_id, "title": "Bad link", "url": "javascript:alert(1)"
+    }))
+    .await;
+    assert_eq!(status, 400);
+    let (status, _) = create(serde_json::json!({
+        "forum_id": forum_id, "title": "No content", "content": ""
+    }))
+    .await;
+    assert_eq!(status, 400);
+
+    // A preview fetched earlier is reused by later posts of the same URL
+    let url = "https://example.com/articles/link-post";
+    xjy::models::link_preview::ActiveModel {
+        url_hash: Set(Sha256::digest(url.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()),
+        url: Set(url.to_string()),
+        status: Set("ready".to_string()),
+        title: Set(Some("An article".to_string())),
+        description: Set(Some("What it is about".to_string())),
+        image_url: Set(Some("https://example.com/cover.png".to_string())),
+        site_name: Set(Some("Example".to_string())),
+        fetched_at: Set(Some(chrono::Utc::now().naive_utc())),
+        created_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .insert(&app.db)
+    .await
+    .unwrap();
+
+    let (status, body) = create(serde_json::json!({
+        "forum_id": forum_id, "title": "Worth reading", "url": format!("{url}#section")
+    }))
+    .await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["url"], url);
+    assert_eq!(body["data"]["content"], "");
+    let preview = &body["data"]["link_preview"];
+    assert_eq!(preview["status"], "ready");
+    assert_eq!(preview["title"], "An article");
+    assert_eq!(preview["site_name"], "Example");
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/forums/{}/posts", forum_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["data"]["items"][0]["link_preview"]["description"],
+        "What it is about"
+    );
+
+    // Internal addresses are never fetched
+    let hits = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = hits.clone();
+    let internal = axum::Router::new().fallback(move || {
+        counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        async { "<meta property=\"og:title\" content=\"secret\">" }
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let internal_url = format!("http://{}/admin", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, internal).await.unwrap() });
+
+    let (status, body) = create(serde_json::json!({
+        "forum_id": forum_id, "title": "Internal", "url": internal_url, "content": "see"
+    }))
+    .await;
+    assert_eq!(status, 200, "{}", body);
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    let mut preview = body["data"]["link_preview"].clone();
+    for _ in 0..50 {
+        if preview["status"] != "pending" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let resp = app
+            .client
+            .get(app.url(&format!("/posts/{}", post_id)))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        preview = body["data"]["link_preview"].clone();
+    }
+    assert_eq!(preview["status"], "failed");
+    assert!(preview["title"].is_null());
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_create_post_retries_with_idempotency_key() {
+    let app = common::spawn_app().await;
+    let (token, _, slug) = setup_forum(&app).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let (_, other_token) = common::create_test_user(&app, "idem_other").await;
+    let key = format!("idem-{}", uuid::Uuid::new_v4());
+
+    let create = |token: String, title: &'static str| {
+        let app = &app;
+        let key = key.clone();
+        async move {
+            let resp = app
+                .client
+                .post(app.url("/posts"))
+                .bearer_auth(token)
+                .header("Idempotency-Key", key)
+                .json(&serde_json::json!({
+                    "forum_id": forum_id,
+                    "title": title,
+                    "content": "Sent twice",
+                }))
+                .send()
+                .await
+                .unwrap();
+            let status = resp.status().as_u16();
+            let replayed = resp.headers().contains_key("idempotent-replayed");
+            let body: Value = resp.json().await.unwrap();
+            (status, replayed, body)
+        }
+    };
+
+    let (status, replayed, first) = create(token.clone(), "Retried post").await;
+    assert_eq!(status, 200, "{}", first);
+    assert!(!replayed);
+    let (status, replayed, retry) = create(token.clone(), "Retried post").await;
+    assert_eq!(status, 200);
+    assert!(replayed);
+    assert_eq!(retry["data"]["id"], first["data"]["id"]);
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/forums/{}/posts", forum_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["items"].as_array().unwrap().len(), 1);
+
+    // Same key for another request body
+    let (status, _, _) = create(token.clone(), "Something else").await;
+    assert_eq!(status, 400);
+
+    // Keys are per user
+    let (status, replayed, other) = create(other_token, "Retried post").await;
+    assert_eq!(status, 200, "{}", other);
+    assert!(!replayed);
+    assert_ne!(other["data"]["id"], first["data"]["id"]);
+}
+
+#[tokio::test]
+async fn crossposts_point_back_at_the_original() {
+    let app = common::spawn_app().await;
+    let (author, _author_id, slug) = setup_forum(&app).await;
+    let (_, crossposter) = common::create_test_user(&app, "crossposter").await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let other_slug = common::create_test_forum(&app, &author).await;
+    let other_forum_id = common::get_forum_id(&app, &other_slug).await;
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&author)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Original",
+            "content": "Worth sharing",
+            "tags": ["news"]
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let original_id = body["data"]["id"].as_i64().unwrap();
+    assert!(body["data"]["crosspost_of_id"].is_null());
+
+    let crosspost = |post_id: i64, forum_id: i32| {
+        app.client
+            .post(app.url(&format!("/posts/{}/crosspost", post_id)))
+            .bearer_auth(&crossposter)
+            .json(&serde_json::json!({ "forum_id": forum_id }))
+            .send()
+    };
+    assert_eq!(
+        crosspost(original_id, forum_id).await.unwrap().status(),
+        400
+    );
+    assert_eq!(
+        crosspost(999_999, other_forum_id).await.unwrap().status(),
+        404
+    );
+
+    let resp = crosspost(original_id, other_forum_id).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let crosspost_id = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["forum_id"], other_forum_id);
+    assert_eq!(body["data"]["title"], "Original");
+    assert_eq!(body["data"]["content"], "Worth sharing");
+    assert_eq!(body["data"]["tags"], serde_json::json!(["news"]));
+    assert_eq!(body["data"]["crosspost_of_id"], original_id);
+    assert_eq!(body["data"]["crosspost_of"]["id"], original_id);
+    assert_eq!(body["data"]["crosspost_of"]["forum_id"], forum_id);
+
+    // Once per forum, also when going through another crosspost
+    assert_eq!(
+        crosspost(original_id, other_forum_id)
+            .await
+            .unwrap()
+            .status(),
+        409
+    );
+    assert_eq!(
+        crosspost(crosspost_id, other_forum_id)
+            .await
+            .unwrap()
+            .status(),
+        409
+    );
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/forums/{}/posts", other_forum_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["items"][0]["crosspost_of"]["id"], original_id);
+
+    // Crossposts go with the original
+    let resp = app
+        .client
+        .delete(app.url(&format!("/posts/{}", original_id)))
+        .bearer_auth(&author)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .get(app.url(&format!("/posts/{}", crosspost_id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn posts_are_found_by_slug() {
+    let app = common::spawn_app().await;
+    let (author, _author_id, slug) = setup_forum(&app).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let create = |title: &str| {
+        app.client
+            .post(app.url("/posts"))
+            .bearer_auth(&author)
+            .json(&serde_json::json!({
+                "forum_id": forum_id,
+                "title": title,
+                "content": "Content",
+            }))
+            .send()
+    };
+    let body: Value = create("Hello, World!").await.unwrap().json().await.unwrap();
+    let first_id = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["slug"], "hello-world");
+    let body: Value = create("Hello world").await.unwrap().json().await.unwrap();
+    let second_id = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["slug"], "hello-world-2");
+
+    let get = |path: String| app.client.get(app.url(&path)).send();
+    for path in [
+        format!("/posts/{}", first_id),
+        format!("/posts/{}-hello-world", first_id),
+        // A stale slug still finds the post by ID
+        format!("/posts/{}-old-title", first_id),
+        format!("/forums/{}/posts/hello-world", slug),
+    ] {
+        let resp = get(path.clone()).await.unwrap();
+        assert_eq!(resp.status(), 200, "{}", path);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["data"]["id"], first_id, "{}", path);
+    }
+    let body: Value = get(format!("/forums/{}/posts/hello-world-2", slug))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["id"], second_id);
+
+    for path in [
+        "/posts/hello-world".to_string(),
+        format!("/forums/{}/posts/missing", slug),
+        "/forums/no-such-forum/posts/hello-world".to_string(),
+    ] {
+        assert_eq!(get(path.clone()).await.unwrap().status(), 404, "{}", path);
+    }
+
+    // Editing the title keeps the slug, so links stay valid
+    let resp = app
+        .client
+        .put(app.url(&format!("/posts/{}", first_id)))
+        .bearer_auth(&author)
+        .json(&serde_json::json!({ "title": "Renamed", "content": "Content" }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["slug"], "hello-world");
+}
@@ -1,38 +1,150 @@
-use xjy::utils::pow::{
-    sign_challenge, validate_pow_solution, verify_and_decode_challenge, PowChallenge,
-};
+mod common;
 
-fn find_nonce(ch: &PowChallenge) -> String {
-    // 低难度用于测试，暴力枚举很快
+use serde_json::Value;
+
+fn find_nonce(pow_token: &str) -> String {
+    let secret = std::env::var("POW_SECRET").unwrap().into_bytes();
+    let ch = xjy::utils::pow::verify_and_decode_challenge(&secret, pow_token).unwrap();
     for i in 0u64..2_000_000 {
         let nonce = format!("{i}");
-        if validate_pow_solution(ch, &nonce).is_ok() {
+        if xjy::utils::pow::validate_pow_solution(&ch, &nonce).is_ok() {
             return nonce;
         }
     }
     panic!("nonce not found");
 }
 
-#[test]
-fn pow_roundtrip_and_solution_ok() {
-    let secret = b"test_secret".to_vec();
-    let now = xjy::utils::pow::now_epoch_seconds();
-    let ch = PowChallenge {
-        v: 1,
-        action: "vote".to_string(),
-        target_type: "post".to_string(),
-        target_id: 123,
-        user_id: 7,
-        issued_at: now,
-        expires_at: now + 120,
-        difficulty: 10,
-        salt: "abc".to_string(),
+/// A solved challenge as `(pow_token, pow_nonce, difficulty)`.
+async fn solve(app: &common::TestApp, token: Option<&str>, body: Value) -> (String, String, u64) {
+    let mut req = app.client.post(app.url("/pow/challenge")).json(&body);
+    if let Some(token) = token {
+        req = req.bearer_auth(token);
+    }
+    let resp = req.send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let pow_token = body["data"]["pow_token"].as_str().unwrap().to_string();
+    assert_eq!(body["data"]["required"], true);
+    let nonce = find_nonce(&pow_token);
+    (
+        pow_token,
+        nonce,
+        body["data"]["difficulty"].as_u64().unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn configured_actions_require_pow_and_difficulty_scales() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "powadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (_, user_token) = common::create_test_user(&app, "powuser").await;
+    let slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    // Read on every request; this binary runs on its own so other tests
+    // keep the defaults.
+    std::env::set_var("POW_REQUIRED_ACTIONS", "vote,register,create_post,report");
+    std::env::set_var("POW_AUTOTUNE_THRESHOLD", "2");
+    std::env::set_var("POW_MAX_DIFFICULTY", "9");
+
+    let resp = app
+        .client
+        .get(app.url("/pow/challenge"))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let actions = body["data"]["actions"].as_array().unwrap();
+    assert_eq!(actions.len(), 4);
+    assert!(actions
+        .iter()
+        .all(|a| a["required"] == true && a["difficulty"] == 8));
+
+    // Anonymous challenges are only for registration
+    let resp = app
+        .client
+        .post(app.url("/pow/challenge"))
+        .json(&serde_json::json!({ "action": "create_post", "target_type": "forum", "target_id": forum_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+
+    let register = |pow: Option<(String, String)>| {
+        let (pow_token, pow_nonce) = pow.unzip();
+        app.client
+            .post(app.url("/auth/register"))
+            .json(&serde_json::json!({
+                "username": "pownewbie",
+                "email": "pownewbie@test.com",
+                "password": "test_password_123",
+                "pow_token": pow_token,
+                "pow_nonce": pow_nonce
+            }))
+            .send()
+    };
+    assert_eq!(register(None).await.unwrap().status(), 400);
+    let (pow_token, pow_nonce, _) =
+        solve(&app, None, serde_json::json!({ "action": "register" })).await;
+    assert_eq!(
+        register(Some((pow_token, pow_nonce)))
+            .await
+            .unwrap()
+            .status(),
+        200
+    );
+
+    let create_post = |pow: Option<(String, String)>| {
+        let (pow_token, pow_nonce) = pow.unzip();
+        app.client
+            .post(app.url("/posts"))
+            .bearer_auth(&user_token)
+            .json(&serde_json::json!({
+                "forum_id": forum_id,
+                "title": "PoW post",
+                "content": "Paid for in hashes",
+                "pow_token": pow_token,
+                "pow_nonce": pow_nonce
+            }))
+            .send()
     };
+    assert_eq!(create_post(None).await.unwrap().status(), 400);
+
+    let post_challenge = serde_json::json!({ "action": "create_post", "target_type": "forum", "target_id": forum_id });
+    let (pow_token, pow_nonce, difficulty) =
+        solve(&app, Some(&user_token), post_challenge.clone()).await;
+    assert_eq!(difficulty, 8);
+    let pow = Some((pow_token, pow_nonce));
+    assert_eq!(create_post(pow.clone()).await.unwrap().status(), 200);
+    // Each challenge is good for one request
+    assert_eq!(create_post(pow).await.unwrap().status(), 400);
+
+    let (pow_token, pow_nonce, _) = solve(&app, Some(&user_token), post_challenge.clone()).await;
+    assert_eq!(
+        create_post(Some((pow_token, pow_nonce)))
+            .await
+            .unwrap()
+            .status(),
+        200
+    );
 
-    let token = sign_challenge(&secret, &ch).unwrap();
-    let decoded = verify_and_decode_challenge(&secret, &token).unwrap();
-    assert_eq!(decoded.target_id, 123);
+    // Two posts in the window reach the threshold: one more bit, capped at 9
+    let (_, _, difficulty) = solve(&app, Some(&user_token), post_challenge).await;
+    assert_eq!(difficulty, 9);
 
-    let nonce = find_nonce(&decoded);
-    validate_pow_solution(&decoded, &nonce).unwrap();
+    // A challenge for one target can't be spent on another
+    let (pow_token, pow_nonce, _) = solve(
+        &app,
+        Some(&user_token),
+        serde_json::json!({ "action": "report", "target_type": "forum", "target_id": forum_id }),
+    )
+    .await;
+    assert_eq!(
+        create_post(Some((pow_token, pow_nonce)))
+            .await
+            .unwrap()
+            .status(),
+        400
+    );
 }
@@ -0,0 +1,93 @@
+mod common;
+
+use sea_orm::{ConnectionTrait, Statement};
+
+async fn create_post(app: &common::TestApp, token: &str, forum_id: i32) -> reqwest::Response {
+    app.client
+        .post(app.url("/posts"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Rate limited post",
+            "content": "Counting against my budget"
+        }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn limits_are_per_user_with_smaller_new_account_budgets() {
+    // This binary runs on its own, so enabling limits here can't leak into
+    // other tests.
+    std::env::set_var("RATE_LIMIT_ENABLED", "true");
+    std::env::set_var("RATE_LIMIT_CONFIG", "auth=1:100,admin=1:100,content=60:2");
+    std::env::set_var("RATE_LIMIT_NEW_ACCOUNT_CONFIG", "content=60:1");
+    std::env::set_var("RATE_LIMIT_NEW_ACCOUNT_HOURS", "24");
+
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "rladmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let (_, new_token) = common::create_test_user(&app, "rlnew").await;
+    let (old_id, old_token) = common::create_test_user(&app, "rlold").await;
+    app.db
+        .execute(Statement::from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            "UPDATE users SET created_at = NOW() - INTERVAL '30 days' WHERE id = $1",
+            [old_id.into()],
+        ))
+        .await
+        .unwrap();
+
+    // A new account gets the smaller budget
+    let resp = create_post(&app, &new_token, forum_id).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["x-ratelimit-limit"], "1");
+    assert_eq!(resp.headers()["x-ratelimit-remaining"], "0");
+    let resp = create_post(&app, &new_token, forum_id).await;
+    assert_eq!(resp.status(), 429);
+    let retry_after: u64 = resp.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 60);
+    assert_eq!(resp.headers()["x-ratelimit-remaining"], "0");
+    assert_eq!(
+        resp.headers()["x-ratelimit-reset"],
+        retry_after.to_string().as_str()
+    );
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "Too many requests");
+    assert_eq!(body["retry_after_seconds"], retry_after);
+
+    // Another user on the same IP has a budget of their own
+    for remaining in ["1", "0"] {
+        let resp = create_post(&app, &old_token, forum_id).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["x-ratelimit-limit"], "2");
+        assert_eq!(resp.headers()["x-ratelimit-remaining"], remaining);
+    }
+    let resp = create_post(&app, &old_token, forum_id).await;
+    assert_eq!(resp.status(), 429);
+    let reset: u64 = resp.headers()["x-ratelimit-reset"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(reset > 60 && reset <= 120);
+
+    // Other groups are unaffected
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&new_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["x-ratelimit-limit"], "10");
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::Value;
+
+async fn register(app: &common::TestApp, name: &str, email: &str, invite: Option<&str>) -> u16 {
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .json(&serde_json::json!({
+            "username": name,
+            "email": email,
+            "password": "test_password_123",
+            "invite_code": invite
+        }))
+        .send()
+        .await
+        .unwrap();
+    resp.status().as_u16()
+}
+
+async fn update_settings(app: &common::TestApp, token: &str, registration: Value) -> Value {
+    let resp = app
+        .client
+        .put(app.url("/admin/settings"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "registration": registration }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"].clone()
+}
+
+#[tokio::test]
+async fn invite_only_registration_spends_codes() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "regadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (mod_id, mod_token) = common::create_test_user(&app, "regmod").await;
+    common::make_moderator(&app.db, mod_id).await;
+
+    let resp = app
+        .client
+        .get(app.url("/admin/settings"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["registration"]["mode"], "open");
+
+    let resp = app
+        .client
+        .get(app.url("/admin/settings"))
+        .bearer_auth(&mod_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let data = update_settings(
+        &app,
+        &admin_token,
+        serde_json::json!({ "mode": "invite_only" }),
+    )
+    .await;
+    assert_eq!(data["registration"]["mode"], "invite_only");
+
+    assert_eq!(
+        register(&app, "uninvited", "uninvited@test.com", None).await,
+        400
+    );
+
+    let resp = app
+        .client
+        .post(app.url("/admin/invites"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "count": 2, "note": "for the beta" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let invites = body["data"].as_array().unwrap();
+    assert_eq!(invites.len(), 2);
+    assert_eq!(invites[0]["max_uses"], 1);
+    assert_eq!(invites[0]["active"], true);
+    let code = invites[0]["code"].as_str().unwrap().to_string();
+    let other_id = invites[1]["id"].as_i64().unwrap();
+    let other_code = invites[1]["code"].as_str().unwrap().to_string();
+
+    assert_eq!(
+        register(
+            &app,
+            "invitee",
+            "invitee@test.com",
+            Some(&code.to_lowercase())
+        )
+        .await,
+        200
+    );
+    // Single-use: the code is spent
+    assert_eq!(
+        register(&app, "invitee2", "invitee2@test.com", Some(&code)).await,
+        400
+    );
+
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/invites/{}", other_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        register(&app, "invitee3", "invitee3@test.com", Some(&other_code)).await,
+        400
+    );
+
+    let resp = app
+        .client
+        .get(app.url("/admin/invites"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 2);
+    let items = body["data"]["items"].as_array().unwrap();
+    assert!(items.iter().all(|i| i["active"] == false));
+    assert!(items.iter().any(|i| i["uses"] == 1));
+
+    let invitee = xjy::models::User::find()
+        .filter(xjy::models::user::Column::Username.eq("invitee"))
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(invitee.invite_code_id.is_some());
+}
+
+#[tokio::test]
+async fn closed_registration_and_domain_lists() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "regadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+
+    let resp = app
+        .client
+        .put(app.url("/admin/settings"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "registration": { "mode": "members_only" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let data = update_settings(
+        &app,
+        &admin_token,
+        serde_json::json!({ "denied_email_domains": ["@Spam.Example", ""] }),
+    )
+    .await;
+    assert_eq!(data["registration"]["mode"], "open");
+    assert_eq!(
+        data["registration"]["denied_email_domains"],
+        serde_json::json!(["spam.example"])
+    );
+
+    assert_eq!(
+        register(&app, "spammer", "bot@mail.spam.example", None).await,
+        400
+    );
+    assert_eq!(register(&app, "legit", "legit@test.com", None).await, 200);
+
+    update_settings(
+        &app,
+        &admin_token,
+        serde_json::json!({ "allowed_email_domains": ["corp.example"] }),
+    )
+    .await;
+    assert_eq!(
+        register(&app, "outsider", "outsider@test.com", None).await,
+        400
+    );
+    assert_eq!(
+        register(&app, "employee", "employee@corp.example", None).await,
+        200
+    );
+
+    update_settings(&app, &admin_token, serde_json::json!({ "mode": "closed" })).await;
+    assert_eq!(register(&app, "late", "late@corp.example", None).await, 403);
+
+    // Existing accounts can still log in
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({ "username": "employee", "password": "test_password_123" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
@@ -1,5 +1,6 @@
 mod common;
 
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
 use serde_json::Value;
 
 #[tokio::test]
@@ -203,7 +204,7 @@ async fn list_reports_as_admin() {
         panic!("Unexpected response structure: {}", body);
     };
 
-    assert!(reports.len() > 0);
+    assert!(!reports.is_empty());
 }
 
 #[tokio::test]
@@ -289,6 +290,191 @@ async fn resolve_report() {
     assert_eq!(body["data"]["status"], "resolved");
 }
 
+async fn create_post(app: &common::TestApp, token: &str, forum_id: i32) -> i64 {
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "title": "Post",
+            "content": "Content",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["id"].as_i64().unwrap()
+}
+
+async fn report_post(app: &common::TestApp, token: &str, post_id: i64) -> i64 {
+    let resp = app
+        .client
+        .post(app.url("/reports"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "target_type": "post",
+            "target_id": post_id,
+            "reason": "spam"
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["id"].as_i64().unwrap()
+}
+
+async fn notifications(app: &common::TestApp, token: &str, kind: &str) -> Vec<Value> {
+    let resp = app
+        .client
+        .get(app.url("/notifications"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|n| n["kind"] == kind)
+        .cloned()
+        .collect()
+}
+
+#[tokio::test]
+async fn resolve_report_actions_take_effect() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "admin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (mod_id, mod_token) = common::create_test_user(&app, "mod").await;
+    common::make_moderator(&app.db, mod_id).await;
+
+    let (_user_id, user_token) = common::create_test_user(&app, "reporter").await;
+    let (poster_id, poster_token) = common::create_test_user(&app, "poster").await;
+    let forum_slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &forum_slug).await;
+
+    let resolve = |report_id: i64, token: String, body: Value| {
+        let app = &app;
+        async move {
+            app.client
+                .put(app.url(&format!("/admin/reports/{}/resolve", report_id)))
+                .bearer_auth(token)
+                .json(&body)
+                .send()
+                .await
+                .unwrap()
+        }
+    };
+
+    // Unknown actions are rejected
+    let post_id = create_post(&app, &poster_token, forum_id).await;
+    let report_id = report_post(&app, &user_token, post_id).await;
+    let resp = resolve(
+        report_id,
+        mod_token.clone(),
+        serde_json::json!({ "action": "shame" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 400);
+
+    // Hiding the content hides the post and tells the reporter
+    let resp = resolve(
+        report_id,
+        mod_token.clone(),
+        serde_json::json!({ "action": "hide_content", "note": "Off topic" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["status"], "resolved");
+    assert_eq!(body["data"]["action"], "hide_content");
+    assert_eq!(body["data"]["resolution_note"], "Off topic");
+    let post = xjy::models::Post::find_by_id(post_id as i32)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(post.is_hidden);
+
+    let resolved = notifications(&app, &user_token, "report_resolved").await;
+    assert_eq!(resolved.len(), 1);
+    assert_eq!(resolved[0]["target_type"], "report");
+    assert_eq!(resolved[0]["target_id"].as_i64(), Some(report_id));
+    assert!(resolved[0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("content was hidden"));
+
+    // Warning the author notifies them with the moderator's note
+    let post_id = create_post(&app, &poster_token, forum_id).await;
+    let report_id = report_post(&app, &user_token, post_id).await;
+    let resp = resolve(
+        report_id,
+        mod_token.clone(),
+        serde_json::json!({ "action": "warn_user", "note": "Please stop posting ads" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let warnings = notifications(&app, &poster_token, "moderation_warning").await;
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0]["message"], "Please stop posting ads");
+    assert_eq!(warnings[0]["target_id"].as_i64(), Some(post_id));
+
+    // Staff can't be banned from a report, and a failed ban leaves it pending
+    let staff_post = create_post(&app, &mod_token, forum_id).await;
+    let staff_report = report_post(&app, &user_token, staff_post).await;
+    let resp = resolve(
+        staff_report,
+        admin_token.clone(),
+        serde_json::json!({ "action": "ban_user" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 400);
+    let report = xjy::models::Report::find_by_id(staff_report as i32)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(report.status, "pending");
+
+    // Banning the author locks them out
+    let post_id = create_post(&app, &poster_token, forum_id).await;
+    let report_id = report_post(&app, &user_token, post_id).await;
+    let resp = resolve(
+        report_id,
+        mod_token.clone(),
+        serde_json::json!({ "action": "ban_user" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .get(app.url("/notifications"))
+        .bearer_auth(&poster_token)
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status() == 401 || resp.status() == 403);
+
+    let actions = xjy::models::ModerationAction::find()
+        .filter(xjy::models::moderation_action::Column::UserId.eq(poster_id))
+        .order_by_asc(xjy::models::moderation_action::Column::Id)
+        .all(&app.db)
+        .await
+        .unwrap();
+    let names: Vec<_> = actions.iter().map(|a| a.action.as_str()).collect();
+    assert_eq!(names, vec!["hide_content", "warn_user", "ban_user"]);
+    assert!(actions.iter().all(|a| a.moderator_id == Some(mod_id)));
+    assert_eq!(
+        notifications(&app, &user_token, "report_resolved")
+            .await
+            .len(),
+        3
+    );
+}
+
 #[tokio::test]
 async fn resolve_report_as_regular_user_fails() {
     let app = common::spawn_app().await;
@@ -438,20 +624,9 @@ async fn report_same_target_multiple_times() {
         .await
         .unwrap();
 
-    // Debug: see what status we get
-    let status = resp.status();
-    if status != 400 && status != 409 && status != 500 {
-        let body: Value = resp.json().await.unwrap();
-        eprintln!("Second report status: {}, Body: {}", status, body);
-    }
-
-    // Note: API returns 500 (database error) instead of 409 (conflict)
-    // This is a known implementation issue
-    assert!(
-        status == 400 || status == 409 || status == 500,
-        "Expected 400, 409, or 500, got {}",
-        status
-    );
+    assert_eq!(resp.status(), 409);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "You have already reported this");
 }
 
 #[tokio::test]
@@ -489,7 +664,8 @@ async fn list_reports_with_pagination() {
             .json(&serde_json::json!({
                 "target_type": "post",
                 "target_id": post_id,
-                "reason": format!("Reason {}", i)
+                "reason": "spam",
+                "description": format!("Reason {}", i)
             }))
             .send()
             .await
@@ -519,7 +695,7 @@ async fn list_reports_with_pagination() {
 
     // Note: Pagination might not be implemented, so just verify we got reports
     assert!(
-        reports.len() > 0,
+        !reports.is_empty(),
         "Expected at least 1 report, got {}",
         reports.len()
     );
@@ -0,0 +1,354 @@
+mod common;
+
+use sea_orm::{ConnectionTrait, EntityTrait};
+use serde_json::Value;
+
+#[tokio::test]
+async fn search_all_returns_grouped_results() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "searchadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+
+    let forum_slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &forum_slug).await;
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({
+            "title": "Gardening basics",
+            "content": "Tomatoes need sun",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+
+    app.client
+        .post(app.url("/comments"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({
+            "post_id": post_id,
+            "content": "My tomatoes love gardening"
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let resp = app
+        .client
+        .get(app.url("/search/all?q=gardening"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let data = &body["data"];
+    assert_eq!(data["posts"]["total"], 1);
+    assert_eq!(data["posts"]["items"][0]["id"], post_id);
+    assert_eq!(data["comments"]["total"], 1);
+    assert!(data["users"]["items"].is_array());
+    assert!(data["tags"]["items"].is_array());
+    assert!(data["forums"]["items"].is_array());
+
+    // Users are matched by username
+    let resp = app
+        .client
+        .get(app.url("/search/all?q=searchadm&type=users"))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let data = &body["data"];
+    assert!(data.get("posts").is_none());
+    assert_eq!(data["users"]["total"], 1);
+    assert_eq!(data["users"]["items"][0]["id"], admin_id);
+    assert!(data["users"]["items"][0].get("email").is_none());
+}
+
+#[tokio::test]
+async fn search_all_rejects_unknown_type() {
+    let app = common::spawn_app().await;
+
+    let resp = app
+        .client
+        .get(app.url("/search/all?q=x&type=widgets"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = app
+        .client
+        .get(app.url("/search/all?q=%20"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn reindex_requires_admin() {
+    let app = common::spawn_app().await;
+    let (user_id, user_token) = common::create_test_user(&app, "reindexuser").await;
+
+    let resp = app
+        .client
+        .post(app.url("/admin/search/reindex"))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    common::make_admin(&app.db, user_id).await;
+    let resp = app
+        .client
+        .post(app.url("/admin/search/reindex"))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["backend"], "postgres");
+    assert_eq!(body["data"]["indexed"], 0);
+}
+
+#[tokio::test]
+async fn search_falls_back_to_fuzzy_title_match() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "fuzzyadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let forum_slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &forum_slug).await;
+
+    app.client
+        .post(app.url("/posts"))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({
+            "title": "Rust Programming Tutorial",
+            "content": "Ownership and borrowing",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let resp = app
+        .client
+        .get(app.url("/search?q=programing%20tutorail"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 1);
+    assert_eq!(
+        body["data"]["items"][0]["title"],
+        "Rust Programming Tutorial"
+    );
+    assert_eq!(body["data"]["did_you_mean"], "programming tutorial");
+
+    // Exact matches carry no suggestion
+    let resp = app
+        .client
+        .get(app.url("/search?q=ownership"))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 1);
+    assert!(body["data"]["did_you_mean"].is_null());
+}
+
+#[tokio::test]
+async fn search_applies_advanced_filters() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "filteradmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (_, other_token) = common::create_test_user(&app, "filterother").await;
+    let forum_slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &forum_slug).await;
+
+    let mut ids = Vec::new();
+    for (token, title, content, tags) in [
+        (
+            &admin_token,
+            "Compiler notes",
+            "Borrow checker ![diagram](https://example.com/a.png)",
+            vec!["Rust"],
+        ),
+        (&admin_token, "Compiler errors", "Plain text", vec![]),
+        (&other_token, "Compiler tips", "Also plain", vec!["Rust"]),
+    ] {
+        let resp = app
+            .client
+            .post(app.url("/posts"))
+            .bearer_auth(token)
+            .json(&serde_json::json!({
+                "title": title,
+                "content": content,
+                "forum_id": forum_id,
+                "tags": tags
+            }))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        ids.push(body["data"]["id"].as_i64().unwrap());
+    }
+
+    let search = |query: &str| {
+        let url = app.url(&format!("/search?q=compiler&{}", query));
+        let client = app.client.clone();
+        async move {
+            let resp = client.get(url).send().await.unwrap();
+            let status = resp.status();
+            let body: Value = resp.json().await.unwrap();
+            (status, body)
+        }
+    };
+    let result_ids = |body: &Value| -> Vec<i64> {
+        let mut ids: Vec<i64> = body["data"]["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["id"].as_i64().unwrap())
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    let username = xjy::models::User::find_by_id(admin_id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap()
+        .username;
+    let (_, body) = search(&format!("author={}", username)).await;
+    assert_eq!(result_ids(&body), vec![ids[0], ids[1]]);
+
+    let (_, body) = search("tag=rust").await;
+    assert_eq!(result_ids(&body), vec![ids[0], ids[2]]);
+
+    let (_, body) = search("has=image").await;
+    assert_eq!(result_ids(&body), vec![ids[0]]);
+
+    let (_, body) = search("min_score=1").await;
+    assert_eq!(body["data"]["total"], 0);
+
+    let (_, body) = search("created_after=2000-01-01&created_before=2100-01-01T00:00:00Z").await;
+    assert_eq!(body["data"]["total"], 3);
+    let (_, body) = search("created_before=2000-01-01").await;
+    assert_eq!(body["data"]["total"], 0);
+
+    let (_, body) = search("author=nobody_here").await;
+    assert_eq!(body["data"]["total"], 0);
+
+    let (status, _) = search("has=poll").await;
+    assert_eq!(status, 400);
+    let (status, _) = search("created_after=yesterday").await;
+    assert_eq!(status, 400);
+}
+
+#[tokio::test]
+async fn admin_search_includes_hidden_content() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "searchadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (mod_id, mod_token) = common::create_test_user(&app, "searchmod").await;
+    common::make_moderator(&app.db, mod_id).await;
+    let (banned_id, user_token) = common::create_test_user(&app, "quarantined").await;
+
+    let forum_slug = common::create_test_forum(&app, &admin_token).await;
+    let forum_id = common::get_forum_id(&app, &forum_slug).await;
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({
+            "title": "Quarantined beekeeping",
+            "content": "Hives everywhere",
+            "forum_id": forum_id
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    app.client
+        .post(app.url("/comments"))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({
+            "post_id": post_id,
+            "content": "More beekeeping tips"
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    app.db
+        .execute_unprepared(&format!(
+            "UPDATE posts SET is_hidden = TRUE WHERE id = {post_id};
+             UPDATE users SET role = 'banned' WHERE id = {banned_id};"
+        ))
+        .await
+        .unwrap();
+
+    let resp = app
+        .client
+        .get(app.url("/search/all?q=beekeeping"))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["posts"]["total"], 0);
+    assert_eq!(body["data"]["comments"]["total"], 0);
+
+    let admin_search = |query: &str, token: &str| {
+        app.client
+            .get(app.url(&format!("/admin/search{}", query)))
+            .bearer_auth(token)
+            .send()
+    };
+
+    // Without the flag staff see what the public sees
+    let resp = admin_search("?q=beekeeping", &admin_token).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["posts"]["total"], 0);
+    assert!(body["data"].get("tags").is_none());
+
+    let resp = admin_search("?q=beekeeping&include_hidden=true", &mod_token)
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let data = &body["data"];
+    assert_eq!(data["posts"]["total"], 1);
+    assert_eq!(data["posts"]["items"][0]["id"], post_id);
+    assert_eq!(data["posts"]["items"][0]["is_hidden"], true);
+    assert_eq!(data["comments"]["total"], 1);
+    assert_eq!(data["comments"]["items"][0]["is_hidden"], false);
+
+    let resp = admin_search("?q=quarantin&type=users&include_hidden=true", &admin_token)
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"].get("posts").is_none());
+    assert_eq!(body["data"]["users"]["total"], 1);
+    assert_eq!(body["data"]["users"]["items"][0]["role"], "banned");
+
+    let resp = admin_search("?q=x&type=tags", &admin_token).await.unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let (_, regular_token) = common::create_test_user(&app, "nosy").await;
+    let resp = admin_search("?q=beekeeping&include_hidden=true", &regular_token)
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+}
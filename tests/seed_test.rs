@@ -0,0 +1,71 @@
+mod common;
+
+use serde_json::Value;
+use xjy::services::bootstrap_admin::BootstrapAdminConfig;
+use xjy::services::seed::SeedService;
+
+fn admin() -> BootstrapAdminConfig {
+    BootstrapAdminConfig {
+        username: "seed_admin".to_string(),
+        email: "seed_admin@example.com".to_string(),
+        password: "seed-admin-password".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_seed_is_idempotent() {
+    let app = common::spawn_app().await;
+    let seed = SeedService::new(app.db.clone());
+
+    let report = seed.seed(&admin(), true).await.unwrap();
+    assert!(report.admin_created);
+    assert_eq!(report.admin.role, "admin");
+    assert_eq!(report.forums_created, 4);
+    assert_eq!(report.posts_created, 3);
+
+    let again = seed.seed(&admin(), true).await.unwrap();
+    assert!(!again.admin_created);
+    assert_eq!(again.admin.id, report.admin.id);
+    assert_eq!(again.forums_created, 0);
+    assert_eq!(again.posts_created, 0);
+
+    let forum_id = common::get_forum_id(&app, "help").await;
+    let resp = app
+        .client
+        .get(app.url(&format!("/forums/{}/posts", forum_id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let posts = body["data"]["items"].as_array().unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0]["title"], "How do I format code in a post?");
+
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({
+            "username": "seed_admin",
+            "password": "seed-admin-password"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_seed_without_demo_skips_posts() {
+    let app = common::spawn_app().await;
+    let report = SeedService::new(app.db.clone())
+        .seed(&admin(), false)
+        .await
+        .unwrap();
+    assert_eq!(report.posts_created, 0);
+
+    let resp = app.client.get(app.url("/tags")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"].to_string().contains("announcement"));
+}
@@ -0,0 +1,203 @@
+mod common;
+
+use sea_orm::{ConnectionTrait, EntityTrait, QueryOrder, Statement};
+use xjy::models::{post, Post};
+use xjy::services::bootstrap_admin::BootstrapAdminConfig;
+use xjy::services::seed::SeedService;
+
+async fn get_text(app: &common::TestApp, path: &str) -> (u16, String, String) {
+    let resp = app
+        .client
+        .get(format!("{}{}", app.addr, path))
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status().as_u16();
+    let content_type = resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    (status, content_type, resp.text().await.unwrap())
+}
+
+#[tokio::test]
+async fn test_sitemap_lists_public_pages() {
+    std::env::set_var("SITE_URL", "https://forum.test");
+    std::env::set_var("PUBLIC_API_URL", "https://api.forum.test");
+    let app = common::spawn_app().await;
+
+    let admin = BootstrapAdminConfig {
+        username: "sitemap_admin".to_string(),
+        email: "sitemap_admin@example.com".to_string(),
+        password: "sitemap-admin-password".to_string(),
+    };
+    SeedService::new(app.db.clone())
+        .seed(&admin, true)
+        .await
+        .unwrap();
+    let posts = Post::find()
+        .order_by_asc(post::Column::Id)
+        .all(&app.db)
+        .await
+        .unwrap();
+    let hidden = &posts[0];
+    app.db
+        .execute(Statement::from_string(
+            app.db.get_database_backend(),
+            format!("UPDATE posts SET is_hidden = TRUE WHERE id = {}", hidden.id),
+        ))
+        .await
+        .unwrap();
+
+    let (status, content_type, index) = get_text(&app, "/sitemap.xml").await;
+    assert_eq!(status, 200);
+    assert!(content_type.starts_with("application/xml"));
+    for file in ["forums-1", "tags-1", "posts-1", "users-1"] {
+        assert!(
+            index.contains(&format!(
+                "<loc>https://api.forum.test/sitemaps/{}.xml</loc>",
+                file
+            )),
+            "{}",
+            index
+        );
+    }
+
+    let (status, _, forums) = get_text(&app, "/sitemaps/forums-1.xml").await;
+    assert_eq!(status, 200);
+    assert!(forums.contains("<loc>https://forum.test/forums/general</loc>"));
+
+    let (_, _, users) = get_text(&app, "/sitemaps/users-1.xml").await;
+    assert!(users.contains("<loc>https://forum.test/users/sitemap_admin</loc>"));
+
+    let (_, _, sitemap) = get_text(&app, "/sitemaps/posts-1.xml").await;
+    let visible = &posts[1];
+    assert!(sitemap.contains(&format!(
+        "<url><loc>https://forum.test/posts/{}-{}</loc><lastmod>{}</lastmod></url>",
+        visible.id,
+        visible.slug.as_deref().unwrap(),
+        visible.updated_at.and_utc().format("%Y-%m-%dT%H:%M:%SZ")
+    )));
+    assert!(!sitemap.contains(&format!("/posts/{}-", hidden.id)));
+
+    assert_eq!(get_text(&app, "/sitemaps/posts-2.xml").await.0, 404);
+    assert_eq!(get_text(&app, "/sitemaps/comments-1.xml").await.0, 404);
+}
+
+#[tokio::test]
+async fn test_robots_txt_points_at_the_sitemap() {
+    std::env::set_var("SITE_URL", "https://forum.test");
+    std::env::set_var("PUBLIC_API_URL", "https://api.forum.test");
+    let app = common::spawn_app().await;
+
+    let (status, content_type, body) = get_text(&app, "/robots.txt").await;
+    assert_eq!(status, 200);
+    assert!(content_type.starts_with("text/plain"));
+    assert!(body.starts_with("User-agent: *\n"));
+    assert!(body.contains("Disallow: /api/\n"));
+    assert!(body.contains("Sitemap: https://api.forum.test/sitemap.xml\n"));
+}
+
+#[tokio::test]
+async fn test_post_links_unfurl() {
+    std::env::set_var("SITE_URL", "https://forum.test");
+    std::env::set_var("PUBLIC_API_URL", "https://api.forum.test");
+    let app = common::spawn_app().await;
+
+    let (user_id, token) = common::create_test_user(&app, "unfurl_author").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let resp = app
+        .client
+        .get(app.url(&format!("/forums/{}", slug)))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let forum_id = body["data"]["id"].as_i64().unwrap();
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Rust & <friends>",
+            "content": "Hello **world**.\n\n![shot](/uploads/images/shot.png)",
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let id = body["data"]["id"].as_i64().unwrap();
+
+    let (status, content_type, html) = get_text(&app, &format!("/p/{}-rust-friends", id)).await;
+    assert_eq!(status, 200);
+    assert!(content_type.starts_with("text/html"));
+    for tag in [
+        "<meta property=\"og:title\" content=\"Rust &#38; &#60;friends&#62;\">".to_string(),
+        "<meta property=\"og:description\" content=\"Hello world.\">".to_string(),
+        "<meta property=\"og:image\" content=\"https://api.forum.test/uploads/images/shot.png\">"
+            .to_string(),
+        format!(
+            "<link rel=\"canonical\" href=\"https://forum.test/posts/{}-rust-friends\">",
+            id
+        ),
+        "<meta name=\"author\" content=\"unfurl_author".to_string(),
+        "<meta name=\"twitter:card\" content=\"summary_large_image\">".to_string(),
+    ] {
+        assert!(html.contains(&tag), "missing {}\n{}", tag, html);
+    }
+
+    let oembed = |url: String| {
+        let app = &app;
+        async move {
+            app.client
+                .get(format!("{}/oembed", app.addr))
+                .query(&[("url", url)])
+                .send()
+                .await
+                .unwrap()
+        }
+    };
+    let resp = oembed(format!("https://forum.test/posts/{}", id)).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["version"], "1.0");
+    assert_eq!(body["type"], "link");
+    assert_eq!(body["title"], "Rust & <friends>");
+    let author = body["author_name"].as_str().unwrap();
+    assert!(author.starts_with("unfurl_author"));
+    assert_eq!(
+        body["author_url"],
+        format!("https://forum.test/users/{}", author)
+    );
+    assert_eq!(body["provider_url"], "https://forum.test");
+
+    let resp = oembed(format!("https://api.forum.test/p/{}", id)).await;
+    assert_eq!(resp.status(), 200);
+    let resp = oembed(format!("https://elsewhere.test/posts/{}", id)).await;
+    assert_eq!(resp.status(), 404);
+    let resp = app
+        .client
+        .get(format!("{}/oembed", app.addr))
+        .query(&[
+            ("url", format!("https://forum.test/posts/{}", id)),
+            ("format", "xml".to_string()),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 501);
+
+    app.db
+        .execute(Statement::from_string(
+            app.db.get_database_backend(),
+            format!("UPDATE posts SET is_hidden = TRUE WHERE id = {}", id),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(get_text(&app, &format!("/p/{}", id)).await.0, 404);
+}
@@ -0,0 +1,15 @@
+mod common;
+
+/// The same app against an in-memory SQLite database. Runs on its own
+/// because it points `TEST_DATABASE_URL` at SQLite for this binary.
+async fn spawn_sqlite_app() -> common::TestApp {
+    std::env::set_var("TEST_DATABASE_URL", "sqlite::memory:");
+    std::env::set_var("POW_REQUIRED_ACTIONS", "");
+    common::spawn_app().await
+}
+
+#[tokio::test]
+async fn core_flows_run_on_sqlite() {
+    let app = spawn_sqlite_app().await;
+    common::flows::core_flows(&app).await;
+}
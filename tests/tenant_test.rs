@@ -0,0 +1,202 @@
+mod common;
+
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement};
+use serde_json::Value;
+use xjy::config::app::DatabaseSettings;
+use xjy::config::tenancy::TenancyConfig;
+use xjy::services::bootstrap_admin::BootstrapAdminConfig;
+use xjy::services::seed::SeedService;
+use xjy::services::tenant::{TenantRegistry, TenantService};
+use xjy::services::upload::{TempUpload, UploadConfig, UploadService};
+
+const TENANTS: &[(&str, &str)] = &[("acme", "acme.test"), ("globex", "globex.test")];
+
+fn settings() -> DatabaseSettings {
+    let url = std::env::var("TEST_DATABASE_URL")
+        .unwrap_or_else(|_| std::env::var("DATABASE_URL").expect("DATABASE_URL must be set"));
+    DatabaseSettings {
+        url: Some(url),
+        ..Default::default()
+    }
+}
+
+async fn reset_tenants(db: &DatabaseConnection) {
+    for (slug, _) in TENANTS {
+        for sql in [
+            format!("DROP SCHEMA IF EXISTS tenant_{} CASCADE", slug),
+            format!("DELETE FROM tenants WHERE slug = '{}'", slug),
+        ] {
+            db.execute(Statement::from_string(DbBackend::Postgres, sql))
+                .await
+                .unwrap();
+        }
+    }
+}
+
+/// The test app with tenant routing, plus the registry it uses.
+async fn spawn_tenant_app() -> Option<(common::TestApp, TenantRegistry)> {
+    let app = common::spawn_app().await;
+    if app.db.get_database_backend() != DbBackend::Postgres {
+        return None;
+    }
+    reset_tenants(&app.db).await;
+
+    let service = TenantService::new(app.db.clone());
+    for (slug, host) in TENANTS {
+        service.create(slug, slug, host).await.unwrap();
+    }
+
+    let registry = TenantRegistry::new(
+        app.db.clone(),
+        settings(),
+        TenancyConfig {
+            enabled: true,
+            max_connections: 2,
+        },
+        true,
+        None,
+        xjy::services::email::EmailService::from_env(),
+        "./test_uploads".to_string(),
+    );
+    let router = common::base_router().layer(axum::middleware::from_fn_with_state(
+        registry.clone(),
+        xjy::middleware::tenant::tenant_middleware,
+    ));
+    let addr = common::serve(common::with_services(router, app.db.clone())).await;
+    Some((common::TestApp { addr, ..app }, registry))
+}
+
+async fn register(app: &common::TestApp, host: &str, username: &str) -> (i64, String) {
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .header("Host", host)
+        .json(&serde_json::json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "test_password_123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status();
+    let body: Value = resp.json().await.unwrap();
+    assert!(status.is_success(), "register on {}: {}", host, body);
+    (
+        body["data"]["user_id"].as_i64().unwrap(),
+        body["data"]["token"].as_str().unwrap().to_string(),
+    )
+}
+
+async fn me(app: &common::TestApp, host: &str, token: &str) -> reqwest::Response {
+    app.client
+        .get(app.url("/auth/me"))
+        .header("Host", host)
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn forum_count(app: &common::TestApp, host: &str) -> usize {
+    let resp = app
+        .client
+        .get(app.url("/forums"))
+        .header("Host", host)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"].as_array().unwrap().len()
+}
+
+#[tokio::test]
+async fn test_tenants_are_isolated_by_host() {
+    let Some((app, registry)) = spawn_tenant_app().await else {
+        return;
+    };
+
+    // The same username exists independently in each community
+    let (_, acme_token) = register(&app, "acme.test:8080", "tenant_alice").await;
+    let (_, globex_token) = register(&app, "GLOBEX.test", "tenant_alice").await;
+    let (_, default_token) = register(&app, "localhost", "tenant_alice").await;
+
+    let resp = me(&app, "acme.test", &acme_token).await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["email"], "tenant_alice@example.com");
+
+    // Tokens only work on the host they were issued on
+    assert_eq!(me(&app, "globex.test", &acme_token).await.status(), 401);
+    assert_eq!(me(&app, "localhost", &acme_token).await.status(), 401);
+    assert_eq!(me(&app, "acme.test", &default_token).await.status(), 401);
+    assert_eq!(me(&app, "globex.test", &globex_token).await.status(), 200);
+
+    // Forums seeded into one tenant stay there
+    let acme = registry
+        .contexts()
+        .into_iter()
+        .find(|c| c.tenant.slug == "acme")
+        .expect("acme was set up by its first request");
+    let admin = BootstrapAdminConfig {
+        username: "acme_admin".to_string(),
+        email: "admin@acme.test".to_string(),
+        password: "acme-admin-password".to_string(),
+    };
+    SeedService::new(acme.db.clone())
+        .seed(&admin, false)
+        .await
+        .unwrap();
+    assert_eq!(forum_count(&app, "acme.test").await, 4);
+    assert_eq!(forum_count(&app, "globex.test").await, 0);
+    assert_eq!(forum_count(&app, "localhost").await, 0);
+
+    // A tenant's uploads are only served on its own host
+    let resp = app
+        .client
+        .get(format!("{}/uploads/tenants/acme/avatars/x.png", app.addr))
+        .header("Host", "globex.test")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    reset_tenants(&app.db).await;
+}
+
+#[tokio::test]
+async fn test_uploads_go_to_the_tenant_directory() {
+    let config = UploadConfig {
+        upload_dir: "./test_uploads".to_string(),
+        max_file_size: 1024,
+    };
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::new_rgb8(1, 1)
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    let png = png.into_inner();
+
+    let save = || async {
+        let mut upload = TempUpload::create(&config).await.unwrap();
+        upload.write(&png).await.unwrap();
+        UploadService::save_upload(&config, upload, "image/png", "avatars")
+            .await
+            .unwrap()
+            .url
+    };
+
+    let url = xjy::utils::tenant::scope("acme".to_string(), save()).await;
+    assert!(url.starts_with("/uploads/tenants/acme/avatars/"), "{}", url);
+    let path = std::path::Path::new("./test_uploads").join(url.trim_start_matches("/uploads/"));
+    assert!(path.exists());
+    std::fs::remove_file(path).unwrap();
+
+    let url = save().await;
+    assert!(url.starts_with("/uploads/avatars/"), "{}", url);
+    std::fs::remove_file(format!(
+        "./test_uploads{}",
+        url.trim_start_matches("/uploads")
+    ))
+    .unwrap();
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use reqwest::multipart::{Form, Part};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use xjy::models::{audit_log, AuditLog};
+
+/// A clamd that finds anything containing "EICAR".
+async fn spawn_clamd() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut command = [0; 10];
+            stream.read_exact(&mut command).await.unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+            let mut data = Vec::new();
+            loop {
+                let len = stream.read_u32().await.unwrap() as usize;
+                if len == 0 {
+                    break;
+                }
+                let mut chunk = vec![0; len];
+                stream.read_exact(&mut chunk).await.unwrap();
+                data.extend_from_slice(&chunk);
+            }
+            let reply: &[u8] = if data.windows(5).any(|w| w == b"EICAR") {
+                b"stream: Eicar-Test-Signature FOUND\0"
+            } else {
+                b"stream: OK\0"
+            };
+            stream.write_all(reply).await.unwrap();
+        }
+    });
+    addr
+}
+
+fn png(extra: &[u8]) -> Form {
+    let mut data = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::new_rgb8(2, 2)
+        .write_to(&mut data, image::ImageFormat::Png)
+        .unwrap();
+    let mut data = data.into_inner();
+    data.extend_from_slice(extra);
+    Form::new().part(
+        "file",
+        Part::bytes(data)
+            .file_name("shot.png")
+            .mime_str("image/png")
+            .unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn test_infected_uploads_are_rejected_and_audited() {
+    std::env::set_var("UPLOAD_SCAN_CLAMD", spawn_clamd().await);
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "scanned").await;
+
+    let resp = app
+        .client
+        .post(app.url("/upload/image"))
+        .bearer_auth(&token)
+        .multipart(png(
+            b"X5O!P%@AP[4\\PZX54(P^)7CC)7}$EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*",
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["error"],
+        "File rejected by virus scan: Eicar-Test-Signature"
+    );
+
+    let entries = AuditLog::find()
+        .filter(audit_log::Column::Action.eq("upload_rejected"))
+        .filter(audit_log::Column::ActorId.eq(user_id))
+        .all(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0]
+        .detail
+        .as_deref()
+        .unwrap()
+        .starts_with("signature: Eicar-Test-Signature, content type: image/png"));
+
+    let resp = app
+        .client
+        .post(app.url("/upload/image"))
+        .bearer_auth(&token)
+        .multipart(png(b""))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let url = body["data"]["url"].as_str().unwrap();
+    std::fs::remove_file(format!(
+        "./test_uploads{}",
+        url.trim_start_matches("/uploads")
+    ))
+    .unwrap();
+}
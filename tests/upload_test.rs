@@ -0,0 +1,471 @@
+mod common;
+
+use reqwest::multipart::{Form, Part};
+use serde_json::Value;
+use std::path::Path;
+
+const PNG: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
+fn png() -> Vec<u8> {
+    let mut out = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::new_rgb8(2, 2)
+        .write_to(&mut out, image::ImageFormat::Png)
+        .unwrap();
+    out.into_inner()
+}
+
+/// A 4x2 JPEG whose EXIF says to rotate it by 90°.
+fn photo_with_exif() -> Vec<u8> {
+    let mut jpeg = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::new_rgb8(4, 2)
+        .write_to(&mut jpeg, image::ImageFormat::Jpeg)
+        .unwrap();
+    let jpeg = jpeg.into_inner();
+    let exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0";
+    let mut tagged = jpeg[..2].to_vec();
+    tagged.extend_from_slice(&[0xFF, 0xE1]);
+    tagged.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+    tagged.extend_from_slice(exif);
+    tagged.extend_from_slice(&jpeg[2..]);
+    tagged
+}
+
+fn image(data: Vec<u8>, content_type: &str) -> Form {
+    Form::new().text("caption", "Screenshot").part(
+        "file",
+        Part::bytes(data)
+            .file_name("shot.png")
+            .mime_str(content_type)
+            .unwrap(),
+    )
+}
+
+fn temp_files() -> usize {
+    std::fs::read_dir("./test_uploads/.tmp").map_or(0, |dir| dir.count())
+}
+
+#[tokio::test]
+async fn test_multipart_uploads_stream_to_disk() {
+    let app = common::spawn_app().await;
+    let (_user_id, token) = common::create_test_user(&app, "streamer").await;
+
+    let resp = app
+        .client
+        .post(app.url("/upload/image"))
+        .bearer_auth(&token)
+        .multipart(image(photo_with_exif(), "image/jpeg"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let url = body["data"]["url"].as_str().unwrap();
+    assert!(url.starts_with("/uploads/images/") && url.ends_with(".jpg"));
+    let path = Path::new("./test_uploads").join(url.trim_start_matches("/uploads/"));
+
+    // Saved re-encoded: upright and without the EXIF block
+    let saved = std::fs::read(&path).unwrap();
+    assert!(!saved.windows(4).any(|w| w == b"Exif"));
+    let saved = image::load_from_memory(&saved).unwrap();
+    assert_eq!((saved.width(), saved.height()), (2, 4));
+    std::fs::remove_file(path).unwrap();
+
+    // Over UPLOAD_MAX_FILE_SIZE, though within the route's body limit
+    let resp = app
+        .client
+        .post(app.url("/upload/avatar"))
+        .bearer_auth(&token)
+        .multipart(image(PNG.repeat(5 * 1024 * 1024 / 8 + 1), "image/png"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 413);
+
+    let resp = app
+        .client
+        .post(app.url("/upload/avatar"))
+        .bearer_auth(&token)
+        .multipart(image(PNG.to_vec(), "image/jpeg"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["error"],
+        "File content does not match declared content type"
+    );
+
+    let resp = app
+        .client
+        .post(app.url("/upload/avatar"))
+        .bearer_auth(&token)
+        .multipart(Form::new().text("caption", "No file"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "No file provided");
+
+    // Rejected uploads leave nothing behind
+    assert_eq!(temp_files(), 0);
+}
+
+async fn upload(app: &common::TestApp, token: &str, kind: &str) -> String {
+    let resp = app
+        .client
+        .post(app.url(&format!("/upload/{}", kind)))
+        .bearer_auth(token)
+        .multipart(image(png(), "image/png"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["url"].as_str().unwrap().to_string()
+}
+
+async fn image_id(app: &common::TestApp, token: &str) -> i64 {
+    let resp = app
+        .client
+        .post(app.url("/upload/image"))
+        .bearer_auth(token)
+        .multipart(image(png(), "image/png"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    body["data"]["id"].as_i64().unwrap()
+}
+
+fn on_disk(url: &str) -> bool {
+    Path::new("./test_uploads")
+        .join(url.trim_start_matches("/uploads/"))
+        .exists()
+}
+
+#[tokio::test]
+async fn test_orphaned_uploads_are_deleted_after_the_grace_period() {
+    use sea_orm::sea_query::Expr;
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+    use xjy::config::upload_cleanup::UploadCleanupConfig;
+    use xjy::models::{upload, Upload};
+    use xjy::services::upload::UploadCleanup;
+
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "orphans").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let orphan = upload(&app, &token, "image").await;
+    let used = upload(&app, &token, "image").await;
+    let replaced_avatar = upload(&app, &token, "avatar").await;
+    let avatar = upload(&app, &token, "avatar").await;
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "With a picture",
+            "content": format!("![shot]({})", used.trim_start_matches('/')),
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let cleanup = UploadCleanup::new(
+        app.db.clone(),
+        "./test_uploads".to_string(),
+        UploadCleanupConfig {
+            grace_period: std::time::Duration::from_secs(3600),
+            interval: std::time::Duration::from_secs(3600),
+        },
+    );
+    let now = chrono::Utc::now().naive_utc();
+
+    // Nothing is deleted within the grace period
+    cleanup.run(now).await.unwrap();
+    assert!(on_disk(&orphan));
+
+    // Backdated rather than running later, to leave other tests' files alone
+    Upload::update_many()
+        .col_expr(
+            upload::Column::CreatedAt,
+            Expr::value(now - chrono::Duration::hours(2)),
+        )
+        .filter(upload::Column::UserId.eq(user_id))
+        .exec(&app.db)
+        .await
+        .unwrap();
+    let deleted = cleanup.run(now).await.unwrap();
+    assert!(deleted >= 2, "{}", deleted);
+    assert!(!on_disk(&orphan));
+    assert!(!on_disk(&replaced_avatar));
+    assert!(on_disk(&used));
+    assert!(on_disk(&avatar));
+
+    let uploads = Upload::find()
+        .filter(upload::Column::UserId.eq(user_id))
+        .all(&app.db)
+        .await
+        .unwrap();
+    let entity = |url: &str| {
+        uploads
+            .iter()
+            .find(|u| url.ends_with(&u.path))
+            .map(|u| (u.entity_type.clone(), u.size > 0))
+    };
+    assert_eq!(entity(&orphan), None);
+    assert_eq!(entity(&used), Some((Some("post".to_string()), true)));
+    assert_eq!(entity(&avatar), Some((Some("user".to_string()), true)));
+
+    for url in [used, avatar] {
+        std::fs::remove_file(Path::new("./test_uploads").join(url.trim_start_matches("/uploads/")))
+            .unwrap();
+    }
+}
+
+fn wide_png() -> Vec<u8> {
+    let mut out = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::new_rgb8(600, 400)
+        .write_to(&mut out, image::ImageFormat::Png)
+        .unwrap();
+    out.into_inner()
+}
+
+fn dimensions(url: &str) -> (u32, u32) {
+    let path = Path::new("./test_uploads").join(url.trim_start_matches("/uploads/"));
+    let image = image::open(path).unwrap();
+    (image.width(), image.height())
+}
+
+#[tokio::test]
+async fn test_avatar_is_cropped_from_the_kept_original() {
+    let app = common::spawn_app().await;
+    let (_user_id, token) = common::create_test_user(&app, "cropper").await;
+
+    let form = image(wide_png(), "image/png")
+        .text("focus_x", "0.25")
+        .text("zoom", "2");
+    let resp = app
+        .client
+        .post(app.url("/upload/avatar"))
+        .bearer_auth(&token)
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let avatar = body["data"]["url"].as_str().unwrap().to_string();
+    let original = body["data"]["original_url"].as_str().unwrap().to_string();
+    assert!(original.starts_with("/uploads/avatars/originals/"));
+    assert_eq!(dimensions(&avatar), (256, 256));
+    assert_eq!(dimensions(&original), (600, 400));
+
+    // Cropped again without uploading
+    let resp = app
+        .client
+        .post(app.url("/upload/avatar/crop"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "crop_x": 100, "crop_y": 50, "crop_width": 200, "crop_height": 200
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let recropped = body["data"]["url"].as_str().unwrap().to_string();
+    assert_ne!(recropped, avatar);
+    assert_eq!(body["data"]["original_url"], original.as_str());
+    assert_eq!(dimensions(&recropped), (256, 256));
+
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["avatar_url"], recropped.as_str());
+
+    // Outside the original
+    let resp = app
+        .client
+        .post(app.url("/upload/avatar/crop"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "crop_x": 500, "crop_y": 0, "crop_width": 200, "crop_height": 200
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let resp = app
+        .client
+        .post(app.url("/upload/avatar"))
+        .bearer_auth(&token)
+        .multipart(image(wide_png(), "image/png").text("zoom", "0.5"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "zoom must be between 1 and 10");
+
+    let (_other_id, other) = common::create_test_user(&app, "no_avatar").await;
+    let resp = app
+        .client
+        .post(app.url("/upload/avatar/crop"))
+        .bearer_auth(&other)
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    for url in [avatar, recropped, original] {
+        let _ = std::fs::remove_file(
+            Path::new("./test_uploads").join(url.trim_start_matches("/uploads/")),
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_uploads_attach_to_their_owners_posts() {
+    let app = common::spawn_app().await;
+    let (admin_id, token) = common::create_test_user(&app, "attacher").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (_, other) = common::create_test_user(&app, "stranger").await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let first = image_id(&app, &token).await;
+    let second = image_id(&app, &token).await;
+    let theirs = image_id(&app, &other).await;
+
+    let create = |attachment_ids: Vec<i64>| {
+        app.client
+            .post(app.url("/posts"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({
+                "forum_id": forum_id,
+                "title": "With files",
+                "content": "See attached",
+                "attachment_ids": attachment_ids,
+            }))
+            .send()
+    };
+    assert_eq!(create(vec![first, theirs]).await.unwrap().status(), 400);
+    assert_eq!(create((0..11).collect()).await.unwrap().status(), 400);
+
+    let resp = create(vec![second, first]).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    let attachments = body["data"]["attachments"].as_array().unwrap();
+    let ids: Vec<i64> = attachments
+        .iter()
+        .map(|a| a["id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(ids, vec![second, first]);
+    assert_eq!(attachments[0]["mime"], "image/png");
+    assert!(on_disk(attachments[0]["url"].as_str().unwrap()));
+
+    // An upload belongs to one post
+    assert_eq!(create(vec![first]).await.unwrap().status(), 400);
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/posts/{}", post_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["attachments"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_admins_add_custom_emoji_posts_can_use() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin) = common::create_test_user(&app, "emojiadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (_, user) = common::create_test_user(&app, "emojiuser").await;
+    let slug = common::create_test_forum(&app, &admin).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let add = |token: &str, shortcode: &str| {
+        app.client
+            .post(app.url("/admin/emoji"))
+            .bearer_auth(token)
+            .multipart(image(png(), "image/png").text("shortcode", shortcode.to_string()))
+            .send()
+    };
+    assert_eq!(add(&user, "blobwave").await.unwrap().status(), 403);
+    assert_eq!(add(&admin, "Blob Wave").await.unwrap().status(), 400);
+    let resp = add(&admin, ":blobwave:").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let emoji_id = body["data"]["id"].as_i64().unwrap();
+    let url = body["data"]["url"].as_str().unwrap().to_string();
+    assert_eq!(body["data"]["shortcode"], "blobwave");
+    assert_eq!(add(&admin, "blobwave").await.unwrap().status(), 409);
+
+    let resp = app.client.get(app.url("/emoji")).send().await.unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|e| e["shortcode"] == "blobwave" && e["url"] == url.as_str()));
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&user)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Hello",
+            "content": "Hi :blobwave: and `:blobwave:`",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    let html = body["data"]["content_html"].as_str().unwrap();
+    assert!(html.contains(&format!(
+        "<img class=\"emoji\" src=\"{}\" alt=\":blobwave:\"",
+        url
+    )));
+    assert!(html.contains("<code>:blobwave:</code>"));
+
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/emoji/{}", emoji_id)))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .get(app.url(&format!("/posts/{}", post_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"]["content_html"]
+        .as_str()
+        .unwrap()
+        .contains("Hi :blobwave: and"));
+}
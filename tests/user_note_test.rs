@@ -0,0 +1,117 @@
+mod common;
+
+use serde_json::Value;
+
+async fn add_note(
+    app: &common::TestApp,
+    token: &str,
+    user_id: i32,
+    body: &str,
+) -> reqwest::Response {
+    app.client
+        .post(app.url(&format!("/admin/users/{}/notes", user_id)))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "body": body }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn staff_keep_private_notes_on_users() {
+    let app = common::spawn_app().await;
+    let (admin_id, admin_token) = common::create_test_user(&app, "noteadmin").await;
+    common::make_admin(&app.db, admin_id).await;
+    let (mod_id, mod_token) = common::create_test_user(&app, "notemod").await;
+    common::make_moderator(&app.db, mod_id).await;
+    let (user_id, user_token) = common::create_test_user(&app, "noted").await;
+
+    // Regular users, including the subject, can't read or write notes
+    let resp = add_note(&app, &user_token, user_id, "Hello").await;
+    assert_eq!(resp.status(), 403);
+    let resp = app
+        .client
+        .get(app.url(&format!("/admin/users/{}/notes", user_id)))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = add_note(&app, &mod_token, user_id, "").await;
+    assert_eq!(resp.status(), 400);
+    let resp = add_note(&app, &mod_token, 999999, "Nobody").await;
+    assert_eq!(resp.status(), 404);
+
+    let resp = add_note(&app, &mod_token, user_id, "Warned in DMs about spam links").await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let mod_note = body["data"]["id"].as_i64().unwrap();
+    assert_eq!(body["data"]["author_id"].as_i64(), Some(mod_id as i64));
+
+    let resp = add_note(
+        &app,
+        &admin_token,
+        user_id,
+        "Same person as an earlier account",
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let admin_note = body["data"]["id"].as_i64().unwrap();
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/admin/users/{}/notes", user_id)))
+        .bearer_auth(&mod_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let notes = body["data"].as_array().unwrap();
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0]["id"].as_i64(), Some(admin_note));
+    assert!(notes[0]["author_username"]
+        .as_str()
+        .unwrap()
+        .starts_with("noteadmin_"));
+
+    // Moderators delete only their own notes; admins any
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/users/{}/notes/{}", user_id, admin_note)))
+        .bearer_auth(&mod_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/users/{}/notes/{}", user_id, mod_note)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = app
+        .client
+        .delete(app.url(&format!("/admin/users/{}/notes/{}", admin_id, admin_note)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/admin/users/{}/notes", user_id)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+}
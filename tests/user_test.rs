@@ -1,5 +1,6 @@
 mod common;
 
+use sea_orm::{ConnectionTrait, Statement};
 use serde_json::Value;
 
 #[tokio::test]
@@ -385,6 +386,635 @@ async fn user_profile_includes_post_count() {
         .unwrap();
 
     let body: Value = resp.json().await.unwrap();
-    // The profile should include user stats (implementation specific)
     assert!(body["success"].as_bool().unwrap());
+    assert_eq!(body["data"]["stats"]["post_count"], 3);
+}
+
+#[tokio::test]
+async fn profiles_show_presence_unless_hidden() {
+    let app = common::spawn_app().await;
+    let (_, token) = common::create_test_user(&app, "present").await;
+
+    // Any authenticated request marks the user as seen
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let username = body["data"]["username"].as_str().unwrap().to_string();
+    assert_eq!(body["data"]["show_presence"], true);
+
+    let profile = || async {
+        let resp = app
+            .client
+            .get(app.url(&format!("/users/{}", username)))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        body["data"].clone()
+    };
+    let data = profile().await;
+    assert_eq!(data["is_online"], true);
+    assert!(data["last_seen_at"].is_string());
+
+    let resp = app
+        .client
+        .put(app.url("/auth/profile"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "show_presence": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let data = profile().await;
+    assert!(data["is_online"].is_null());
+    assert!(data["last_seen_at"].is_null());
+}
+
+#[tokio::test]
+async fn activity_feed_merges_posts_and_comments() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "active").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let mut post_ids = Vec::new();
+    for title in ["First post", "Second post"] {
+        let resp = app
+            .client
+            .post(app.url("/posts"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({
+                "forum_id": forum_id,
+                "title": title,
+                "content": "Some **bold** words",
+            }))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        post_ids.push(body["data"]["id"].as_i64().unwrap());
+    }
+    for post_id in &post_ids {
+        let resp = app
+            .client
+            .post(app.url("/comments"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "post_id": post_id, "content": "A reply" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+    // Hiding the second post hides its comment too
+    app.db
+        .execute(Statement::from_string(
+            app.db.get_database_backend(),
+            format!(
+                "UPDATE posts SET is_hidden = TRUE WHERE id = {}",
+                post_ids[1]
+            ),
+        ))
+        .await
+        .unwrap();
+
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let username = body["data"]["username"].as_str().unwrap().to_string();
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}/activity?per_page=1", username)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 2);
+    assert_eq!(body["data"]["total_pages"], 2);
+    let newest = &body["data"]["items"][0];
+    assert_eq!(newest["kind"], "comment");
+    assert_eq!(newest["post_id"], post_ids[0]);
+    assert_eq!(newest["post_title"], "First post");
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}/activity?page=2&per_page=1", username)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let oldest = &body["data"]["items"][0];
+    assert_eq!(oldest["kind"], "post");
+    assert_eq!(oldest["id"], post_ids[0]);
+    assert_eq!(oldest["excerpt"], "Some bold words");
+
+    let resp = app
+        .client
+        .get(app.url("/users/nobody-here/activity"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn post_and_comment_history_pages_and_sorts() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "author").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let mut post_ids = Vec::new();
+    for title in ["Older", "Newer", "Hidden"] {
+        let resp = app
+            .client
+            .post(app.url("/posts"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({
+                "forum_id": forum_id,
+                "title": title,
+                "content": "Content",
+            }))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        post_ids.push(body["data"]["id"].as_i64().unwrap());
+    }
+    for post_id in &post_ids {
+        let resp = app
+            .client
+            .post(app.url("/comments"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "post_id": post_id, "content": "Reply" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+    for sql in [
+        format!("UPDATE posts SET upvotes = 5 WHERE id = {}", post_ids[0]),
+        format!(
+            "UPDATE posts SET is_hidden = TRUE WHERE id = {}",
+            post_ids[2]
+        ),
+    ] {
+        app.db
+            .execute(Statement::from_string(app.db.get_database_backend(), sql))
+            .await
+            .unwrap();
+    }
+
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let username = body["data"]["username"].as_str().unwrap().to_string();
+    let list = |path: String| {
+        let app = &app;
+        async move {
+            let resp = app.client.get(app.url(&path)).send().await.unwrap();
+            let status = resp.status();
+            let body: Value = resp.json().await.unwrap();
+            (status, body["data"].clone())
+        }
+    };
+
+    let (_, posts) = list(format!("/users/{}/posts", username)).await;
+    assert_eq!(posts["total"], 2);
+    assert_eq!(posts["items"][0]["title"], "Newer");
+    let (_, posts) = list(format!("/users/{}/posts?sort=top&per_page=1", username)).await;
+    assert_eq!(posts["total_pages"], 2);
+    assert_eq!(posts["items"][0]["title"], "Older");
+
+    let (_, comments) = list(format!("/users/{}/comments?sort=old", username)).await;
+    assert_eq!(comments["total"], 2);
+    assert_eq!(comments["items"][0]["post_id"], post_ids[0]);
+    assert_eq!(comments["items"][0]["post_title"], "Older");
+    assert_eq!(comments["items"][0]["content"], "Reply");
+    assert_eq!(comments["items"][1]["post_title"], "Newer");
+
+    let (status, _) = list(format!("/users/{}/comments?sort=hot", username)).await;
+    assert_eq!(status, 400);
+    let (status, _) = list("/users/nobody-here/posts".to_string()).await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn profile_stats_break_down_contributions() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "statful").await;
+    common::make_admin(&app.db, user_id).await;
+    let (_, fan) = common::create_test_user(&app, "fan").await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let mut post_ids = Vec::new();
+    for tags in [vec!["statcraft", "zeta"], vec!["statcraft"]] {
+        let resp = app
+            .client
+            .post(app.url("/posts"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({
+                "forum_id": forum_id,
+                "title": "Tagged",
+                "content": "Content",
+                "tags": tags,
+            }))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        post_ids.push(body["data"]["id"].as_i64().unwrap());
+    }
+    let resp = app
+        .client
+        .post(app.url("/comments"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "post_id": post_ids[0], "content": "Reply" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .post(app.url(&format!("/users/{}/follow", user_id)))
+        .bearer_auth(&fan)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    for sql in [
+        format!(
+            "UPDATE posts SET upvotes = 4, downvotes = 1 WHERE id = {}",
+            post_ids[0]
+        ),
+        format!(
+            "UPDATE comments SET upvotes = 2 WHERE user_id = {}",
+            user_id
+        ),
+    ] {
+        app.db
+            .execute(Statement::from_string(app.db.get_database_backend(), sql))
+            .await
+            .unwrap();
+    }
+
+    let resp = app
+        .client
+        .get(app.url("/auth/me"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let username = body["data"]["username"].as_str().unwrap().to_string();
+
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}", username)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let stats = &body["data"]["stats"];
+    assert_eq!(stats["post_count"], 2);
+    assert_eq!(stats["comment_count"], 1);
+    assert_eq!(stats["post_karma"], 3);
+    assert_eq!(stats["comment_karma"], 2);
+    assert_eq!(stats["follower_count"], 1);
+    assert_eq!(stats["following_count"], 0);
+    let created_at = body["data"]["created_at"].as_str().unwrap();
+    assert!(created_at.starts_with(stats["joined_at"].as_str().unwrap()));
+    assert_eq!(stats["top_tags"][0]["name"], "statcraft");
+    assert_eq!(stats["top_tags"][0]["post_count"], 2);
+    assert_eq!(stats["top_tags"][1]["name"], "zeta");
+
+    // Lists of users don't carry stats
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}/followers", user_id)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"]["items"][0].get("stats").is_none());
+}
+
+#[tokio::test]
+async fn preferences_are_stored_and_drive_the_default_sort() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "prefs").await;
+    common::make_admin(&app.db, user_id).await;
+
+    let resp = app
+        .client
+        .get(app.url("/auth/preferences"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["timezone"], "UTC");
+    assert_eq!(body["data"]["content_language"], Value::Null);
+    assert_eq!(body["data"]["show_nsfw"], false);
+    assert_eq!(body["data"]["default_post_sort"], "new");
+
+    for invalid in [
+        serde_json::json!({ "timezone": "Mars/Olympus" }),
+        serde_json::json!({ "locale": "fr" }),
+        serde_json::json!({ "content_language": "not a tag" }),
+        serde_json::json!({ "default_post_sort": "random" }),
+    ] {
+        let resp = app
+            .client
+            .put(app.url("/auth/preferences"))
+            .bearer_auth(&token)
+            .json(&invalid)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400, "{}", invalid);
+    }
+
+    let resp = app
+        .client
+        .put(app.url("/auth/preferences"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "timezone": "Asia/Shanghai",
+            "locale": "zh-CN",
+            "content_language": "ZH_hant",
+            "show_nsfw": true,
+            "default_post_sort": "top",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["timezone"], "Asia/Shanghai");
+    assert_eq!(body["data"]["locale"], "zh");
+    assert_eq!(body["data"]["content_language"], "zh-hant");
+    assert_eq!(body["data"]["show_nsfw"], true);
+    assert_eq!(body["data"]["default_post_sort"], "top");
+
+    // Omitted fields are unchanged; an empty language clears it
+    let resp = app
+        .client
+        .put(app.url("/auth/preferences"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "content_language": "" }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["content_language"], Value::Null);
+    assert_eq!(body["data"]["timezone"], "Asia/Shanghai");
+
+    // Post lists without a sort use the preference
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let mut post_ids = Vec::new();
+    for title in ["Older but better", "Newer"] {
+        let resp = app
+            .client
+            .post(app.url("/posts"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({
+                "forum_id": forum_id,
+                "title": title,
+                "content": "Content",
+            }))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        post_ids.push(body["data"]["id"].as_i64().unwrap());
+    }
+    app.db
+        .execute(Statement::from_string(
+            app.db.get_database_backend(),
+            format!("UPDATE posts SET upvotes = 5 WHERE id = {}", post_ids[0]),
+        ))
+        .await
+        .unwrap();
+
+    let first_id = |token: Option<&str>, query: &str| {
+        let mut request = app
+            .client
+            .get(app.url(&format!("/forums/{}/posts{}", forum_id, query)));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        async move {
+            let body: Value = request.send().await.unwrap().json().await.unwrap();
+            body["data"]["items"][0]["id"].as_i64().unwrap()
+        }
+    };
+    assert_eq!(first_id(Some(&token), "").await, post_ids[0]);
+    assert_eq!(first_id(Some(&token), "?sort=new").await, post_ids[1]);
+    assert_eq!(first_id(None, "").await, post_ids[1]);
+}
+
+#[tokio::test]
+async fn display_names_leave_the_username_alone() {
+    let app = common::spawn_app().await;
+    let (_, token) = common::create_test_user(&app, "renamed").await;
+    let me = |token: String| {
+        let app = &app;
+        async move {
+            let resp = app
+                .client
+                .get(app.url("/auth/me"))
+                .bearer_auth(&token)
+                .send()
+                .await
+                .unwrap();
+            let body: Value = resp.json().await.unwrap();
+            body["data"].clone()
+        }
+    };
+    let username = me(token.clone()).await["username"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let update = |display_name: Value| {
+        app.client
+            .put(app.url("/auth/profile"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "display_name": display_name }))
+            .send()
+    };
+    let resp = update(serde_json::json!("  Ada L.  ")).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["display_name"], "Ada L.");
+    assert_eq!(body["data"]["username"], username);
+    assert_eq!(me(token.clone()).await["display_name"], "Ada L.");
+
+    // The username still logs in and names the profile
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({
+            "username": username,
+            "password": "test_password_123",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["display_name"], "Ada L.");
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}", username)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["display_name"], "Ada L.");
+
+    let resp = update(serde_json::json!("x".repeat(51))).await.unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // Blank shows the username again
+    let resp = update(serde_json::json!(" ")).await.unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"]["display_name"].is_null());
+}
+
+#[tokio::test]
+async fn renamed_users_keep_their_old_profile_links() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "oldname").await;
+    let (_, other) = common::create_test_user(&app, "bystander").await;
+    let username_of = |token: String| {
+        let app = &app;
+        async move {
+            let resp = app
+                .client
+                .get(app.url("/auth/me"))
+                .bearer_auth(&token)
+                .send()
+                .await
+                .unwrap();
+            let body: Value = resp.json().await.unwrap();
+            body["data"]["username"].as_str().unwrap().to_string()
+        }
+    };
+    let old_name = username_of(token.clone()).await;
+    let rename = |token: String, username: String| {
+        let app = &app;
+        async move {
+            app.client
+                .put(app.url("/auth/username"))
+                .bearer_auth(&token)
+                .json(&serde_json::json!({ "username": username }))
+                .send()
+                .await
+                .unwrap()
+        }
+    };
+
+    let new_name = format!("{}-new", old_name);
+    let resp = rename(token.clone(), new_name.clone()).await;
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["username"], new_name);
+
+    // The old name resolves to the renamed profile
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}", old_name)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["username"], new_name);
+    assert_eq!(body["data"]["redirected_from"], old_name);
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}", new_name)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["data"].get("redirected_from").is_none());
+
+    let resp = app
+        .client
+        .post(app.url("/auth/login"))
+        .json(&serde_json::json!({
+            "username": new_name,
+            "password": "test_password_123",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // One rename per interval
+    let resp = rename(token.clone(), format!("{}-again", old_name)).await;
+    assert_eq!(resp.status(), 429);
+    assert!(resp.headers().contains_key("retry-after"));
+
+    // Names held now or before by someone else are taken
+    assert_eq!(rename(other.clone(), old_name.clone()).await.status(), 409);
+    assert_eq!(rename(other.clone(), new_name.clone()).await.status(), 409);
+    let resp = app
+        .client
+        .post(app.url("/auth/register"))
+        .json(&serde_json::json!({
+            "username": old_name,
+            "email": "squatter@test.com",
+            "password": "test_password_123",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // After the interval the user may take their old name back
+    app.db
+        .execute(Statement::from_string(
+            app.db.get_database_backend(),
+            format!(
+                "UPDATE username_history SET changed_at = '2020-01-01 00:00:00' WHERE user_id = {}",
+                user_id
+            ),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(rename(token.clone(), old_name.clone()).await.status(), 200);
+    let resp = app
+        .client
+        .get(app.url(&format!("/users/{}", new_name)))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["username"], old_name);
+    assert_eq!(body["data"]["redirected_from"], new_name);
 }
@@ -0,0 +1,59 @@
+mod common;
+
+use sea_orm::EntityTrait;
+use serde_json::Value;
+
+#[tokio::test]
+async fn views_are_deduplicated_and_flushed() {
+    // Write every counted view straight away so the table can be checked
+    std::env::set_var("VIEW_FLUSH_THRESHOLD", "1");
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "viewer").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Viewed post",
+            "content": "Content"
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap() as i32;
+
+    // Anonymous refreshes from one IP count once
+    for _ in 0..3 {
+        let resp = app
+            .client
+            .get(app.url(&format!("/posts/{}", post_id)))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["data"]["view_count"], 1);
+    }
+
+    // A signed-in reader is a separate viewer
+    for _ in 0..2 {
+        app.client
+            .get(app.url(&format!("/posts/{}", post_id)))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+    }
+
+    let post = xjy::models::Post::find_by_id(post_id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(post.view_count, 2);
+}
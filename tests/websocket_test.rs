@@ -0,0 +1,280 @@
+mod common;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Response;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::{Error, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn ws_url(app: &common::TestApp, query: &str) -> String {
+    format!("{}/ws{}", app.addr.replacen("http", "ws", 1), query)
+}
+
+async fn connect_with_protocol(
+    app: &common::TestApp,
+    protocols: &str,
+) -> Result<(Socket, Response), Error> {
+    let mut request = ws_url(app, "").into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("sec-websocket-protocol", protocols.parse().unwrap());
+    tokio_tungstenite::connect_async(request).await
+}
+
+/// The next message, which must be text, as JSON.
+async fn next_json(socket: &mut Socket) -> Value {
+    match socket.next().await {
+        Some(Ok(Message::Text(text))) => serde_json::from_str(&text).unwrap(),
+        other => panic!("expected a text message, got {:?}", other),
+    }
+}
+
+/// The close frame the server ends the socket with.
+async fn close_reason(socket: &mut Socket) -> (CloseCode, String) {
+    match socket.next().await {
+        Some(Ok(Message::Close(Some(frame)))) => (frame.code, frame.reason.to_string()),
+        other => panic!("expected a close frame, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn sockets_authenticate_without_a_token_in_the_url() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "listener").await;
+    let (_, follower) = common::create_test_user(&app, "knocker").await;
+
+    // Offered as a subprotocol
+    let (mut socket, response) = connect_with_protocol(&app, &format!("bearer, {}", token))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["sec-websocket-protocol"], "bearer");
+    assert!(response.headers().get("deprecation").is_none());
+
+    // A follow request is pushed to the socket
+    let resp = app
+        .client
+        .put(app.url("/auth/profile"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "is_private": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = app
+        .client
+        .post(app.url(&format!("/users/{}/follow", user_id)))
+        .bearer_auth(&follower)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let pushed = next_json(&mut socket).await;
+    assert_eq!(pushed["v"], 1);
+    assert_eq!(pushed["type"], "notification");
+    assert_eq!(pushed["id"], 1);
+    assert_eq!(pushed["payload"]["kind"], "follow_request");
+
+    // Client messages with an id get one reply each, numbered on
+    for (text, kind, code) in [
+        (r#"{"type": "ping", "id": 7}"#, "ack", None),
+        (r#"{"type": "ping"}"#, "", None),
+        (
+            r#"{"type": "dance", "id": 8}"#,
+            "error",
+            Some("unknown_type"),
+        ),
+        ("not json", "error", Some("invalid_message")),
+    ] {
+        socket.send(Message::Text(text.into())).await.unwrap();
+        if kind.is_empty() {
+            continue;
+        }
+        let reply = next_json(&mut socket).await;
+        assert_eq!(reply["type"], kind, "{}", text);
+        if let Some(code) = code {
+            assert_eq!(reply["payload"]["code"], code);
+        }
+    }
+    socket
+        .send(Message::Text(r#"{"type": "ping", "id": 9}"#.into()))
+        .await
+        .unwrap();
+    let reply = next_json(&mut socket).await;
+    assert_eq!(reply["id"], 5);
+    assert_eq!(reply["payload"]["reply_to"], 9);
+
+    // Sent as the first message
+    let (mut socket, _) = tokio_tungstenite::connect_async(ws_url(&app, ""))
+        .await
+        .unwrap();
+    let auth = serde_json::json!({ "type": "auth", "id": 1, "payload": { "token": token } });
+    socket
+        .send(Message::Text(auth.to_string().into()))
+        .await
+        .unwrap();
+    let ok = next_json(&mut socket).await;
+    assert_eq!(ok["type"], "auth_ok");
+    assert_eq!(ok["id"], 1);
+    assert_eq!(ok["payload"]["user_id"], user_id);
+    assert_eq!(ok["payload"]["reply_to"], 1);
+
+    // The query parameter still works, marked deprecated
+    let (_socket, response) =
+        tokio_tungstenite::connect_async(ws_url(&app, &format!("?token={}", token)))
+            .await
+            .unwrap();
+    assert_eq!(response.headers()["deprecation"], "true");
+
+    // A bad token is refused before the upgrade
+    match connect_with_protocol(&app, "bearer, not-a-jwt").await {
+        Err(Error::Http(response)) => assert_eq!(response.status(), 401),
+        other => panic!("expected 401, got {:?}", other.map(|(_, r)| r)),
+    }
+}
+
+#[tokio::test]
+async fn unauthenticated_sockets_are_closed() {
+    std::env::set_var("WS_AUTH_TIMEOUT_SECONDS", "1");
+    let app = common::spawn_app().await;
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(ws_url(&app, ""))
+        .await
+        .unwrap();
+    let auth = serde_json::json!({ "type": "auth", "payload": { "token": "not-a-jwt" } });
+    socket
+        .send(Message::Text(auth.to_string().into()))
+        .await
+        .unwrap();
+    assert_eq!(
+        close_reason(&mut socket).await,
+        (CloseCode::Policy, "Unauthorized".to_string())
+    );
+
+    // Silent sockets are closed after the handshake timeout
+    let (mut socket, _) = tokio_tungstenite::connect_async(ws_url(&app, ""))
+        .await
+        .unwrap();
+    assert_eq!(
+        close_reason(&mut socket).await,
+        (CloseCode::Policy, "Authentication timed out".to_string())
+    );
+}
+
+#[tokio::test]
+async fn post_channels_receive_live_stats() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = common::create_test_user(&app, "watcher").await;
+    common::make_admin(&app.db, user_id).await;
+    let slug = common::create_test_forum(&app, &token).await;
+    let forum_id = common::get_forum_id(&app, &slug).await;
+    let resp = app
+        .client
+        .post(app.url("/posts"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "forum_id": forum_id,
+            "title": "Live thread",
+            "content": "Watch this",
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = resp.json().await.unwrap();
+    let post_id = body["data"]["id"].as_i64().unwrap();
+    let channel = format!("post:{}", post_id);
+
+    let (mut socket, _) = connect_with_protocol(&app, &format!("bearer, {}", token))
+        .await
+        .unwrap();
+    let send = |text: Value| Message::Text(text.to_string().into());
+    socket
+        .send(send(serde_json::json!({
+            "type": "subscribe", "id": 1, "payload": { "channel": channel }
+        })))
+        .await
+        .unwrap();
+    let ack = next_json(&mut socket).await;
+    assert_eq!(
+        (ack["type"].as_str(), ack["payload"]["reply_to"].as_i64()),
+        (Some("ack"), Some(1))
+    );
+
+    // The first update follows the subscription
+    let stats = next_json(&mut socket).await;
+    assert_eq!(stats["type"], "post_stats");
+    assert_eq!(stats["payload"]["post_id"], post_id);
+    assert_eq!(stats["payload"]["viewer_count"], 1);
+    assert_eq!(stats["payload"]["comment_count"], 0);
+    assert_eq!(stats["payload"]["score"], 0);
+
+    let comment = || {
+        app.client
+            .post(app.url("/comments"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "post_id": post_id, "content": "First" }))
+            .send()
+    };
+    assert_eq!(comment().await.unwrap().status(), 200);
+    assert_eq!(comment().await.unwrap().status(), 200);
+    // Both comments arrive in one update
+    let stats = next_json(&mut socket).await;
+    assert_eq!(stats["type"], "post_stats");
+    assert_eq!(stats["payload"]["comment_count"], 2);
+    assert_eq!(stats["payload"]["score_delta"], 0);
+
+    socket
+        .send(send(serde_json::json!({
+            "type": "subscribe", "id": 2, "payload": { "channel": "post:999999" }
+        })))
+        .await
+        .unwrap();
+    let error = next_json(&mut socket).await;
+    assert_eq!(error["type"], "error");
+    assert_eq!(error["payload"]["code"], "not_found");
+
+    // Nothing more once unsubscribed
+    socket
+        .send(send(serde_json::json!({
+            "type": "unsubscribe", "id": 3, "payload": { "channel": channel }
+        })))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut socket).await["type"], "ack");
+    assert_eq!(comment().await.unwrap().status(), 200);
+    let more = tokio::time::timeout(std::time::Duration::from_millis(2500), socket.next()).await;
+    assert!(more.is_err(), "{:?}", more);
+}
+
+#[tokio::test]
+async fn chatty_clients_are_rate_limited() {
+    let app = common::spawn_app().await;
+    let (_, token) = common::create_test_user(&app, "chatty").await;
+
+    let (mut socket, _) = connect_with_protocol(&app, &format!("bearer, {}", token))
+        .await
+        .unwrap();
+    // The burst is 20 messages by default, then 5 a second
+    for id in 1..=30 {
+        let ping = serde_json::json!({ "type": "ping", "id": id });
+        socket
+            .send(Message::Text(ping.to_string().into()))
+            .await
+            .unwrap();
+    }
+    let mut limited = 0;
+    for id in 1..=30 {
+        let reply = next_json(&mut socket).await;
+        assert_eq!(reply["payload"]["reply_to"], id);
+        if reply["type"] == "error" {
+            assert!(id > 20);
+            assert_eq!(reply["payload"]["code"], "rate_limited");
+            limited += 1;
+        }
+    }
+    assert!(limited > 0);
+}